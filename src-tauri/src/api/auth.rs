@@ -4,7 +4,7 @@ use md5::{Digest, Md5};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::error::{ApiError, Result};
-use super::models::UserSession;
+use super::models::{AccountFeatures, DeviceInfo, SubscriptionInfo, UserSession};
 
 /// Generate MD5 signature for protected API endpoints
 ///
@@ -30,6 +30,11 @@ pub fn sign_get_favorites(timestamp: u64, secret: &str) -> String {
     generate_signature("favoritegetUserFavorites", "", timestamp, secret)
 }
 
+/// Generate signature for purchase/getUserPurchases endpoint
+pub fn sign_get_purchases(timestamp: u64, secret: &str) -> String {
+    generate_signature("purchasegetUserPurchases", "", timestamp, secret)
+}
+
 /// Get current Unix timestamp
 pub fn get_timestamp() -> u64 {
     SystemTime::now()
@@ -50,6 +55,11 @@ pub fn parse_login_response(response: &serde_json::Value) -> Result<UserSession>
         .ok_or_else(|| ApiError::AuthenticationError("No auth token in response".to_string()))?
         .to_string();
 
+    parse_user(user, user_auth_token)
+}
+
+/// Build a session from a user object (as in `user/login` or `user/get`)
+pub fn parse_user(user: &serde_json::Value, user_auth_token: String) -> Result<UserSession> {
     let user_id = user
         .get("id")
         .and_then(|v| v.as_u64())
@@ -77,28 +87,177 @@ pub fn parse_login_response(response: &serde_json::Value) -> Result<UserSession>
         .unwrap_or("Unknown")
         .to_string();
 
-    // Check if user has valid subscription
+    // Free and lapsed accounts have no credential parameters. They may still
+    // log in and browse; streaming is gated on `subscription.active`.
     let has_subscription = credential
         .and_then(|c| c.get("parameters"))
         .map(|p| !p.is_null() && p.as_object().map(|o| !o.is_empty()).unwrap_or(false))
         .unwrap_or(false);
 
+    let features = credential
+        .and_then(|c| c.get("parameters"))
+        .map(parse_features)
+        .unwrap_or_default();
+
+    let end_date = user
+        .get("subscription")
+        .and_then(|s| s.get("end_date"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
     if !has_subscription {
-        return Err(ApiError::IneligibleUser);
+        log::warn!("User {} has no active subscription", user_id);
     }
 
+    let country_code = user
+        .get("country_code")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_uppercase());
+
+    let zone = user
+        .get("zone")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    // Present when the login registered a device (`device_manufacturer_id`)
+    let device_id = user.get("device").and_then(|d| d.get("id")).and_then(id_string);
+
     Ok(UserSession {
         user_auth_token,
         user_id,
         email,
         display_name,
         subscription_label,
+        subscription: SubscriptionInfo {
+            active: has_subscription,
+            end_date,
+        },
+        country_code,
+        zone,
+        device_id,
+        features,
     })
 }
 
+/// Read each plan flag on its own, so one the API leaves out or sends as
+/// something other than a boolean only clears that flag
+fn parse_features(parameters: &serde_json::Value) -> AccountFeatures {
+    let flag = |key: &str| parameters[key].as_bool().unwrap_or(false);
+    AccountFeatures {
+        lossy_streaming: flag("lossy_streaming"),
+        lossless_streaming: flag("lossless_streaming"),
+        hires_streaming: flag("hires_streaming"),
+        hires_purchases_streaming: flag("hires_purchases_streaming"),
+        mobile_streaming: flag("mobile_streaming"),
+        offline_streaming: flag("offline_streaming"),
+    }
+}
+
+/// An id the API sends either as a number or as a string
+fn id_string(id: &serde_json::Value) -> Option<String> {
+    match id {
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Parse the account's registered devices, flagging `current_device_id`.
+///
+/// `user/getDevices` isn't documented. The shape assumed here follows the
+/// API's other listings and the `device` object of the login response:
+/// `{"devices": {"items": [{"id", "name", "device_manufacturer_id",
+/// "platform", "last_used_at"}]}}`. Any other shape is an error rather than
+/// an empty list, so a changed response doesn't read as "no devices".
+pub fn parse_devices(response: &serde_json::Value, current_device_id: Option<&str>) -> Result<Vec<DeviceInfo>> {
+    let items = response["devices"]["items"]
+        .as_array()
+        .ok_or_else(|| ApiError::ApiResponse("Unexpected device list response".to_string()))?;
+
+    Ok(items
+        .iter()
+        .filter_map(|device| {
+            let id = id_string(&device["id"])?;
+            let text = |key: &str| device[key].as_str().filter(|s| !s.is_empty()).map(|s| s.to_string());
+            Some(DeviceInfo {
+                name: text("name").unwrap_or_else(|| format!("Device {}", id)),
+                manufacturer_id: text("device_manufacturer_id"),
+                platform: text("platform"),
+                last_used_at: device["last_used_at"].as_i64(),
+                current: current_device_id == Some(id.as_str()),
+                id,
+            })
+        })
+        .collect())
+}
+
+/// Machine-readable `reason`/`code` values Qobuz gives when a token is
+/// revoked because the account was signed in somewhere else. Only these
+/// are matched: `message` is translated into the locale set with
+/// `set_api_locale`, so its wording can't be relied on.
+const INVALIDATION_REASONS: &[&str] = &[
+    "session_invalidated",
+    "too_many_devices",
+    "device_limit_reached",
+    "logged_in_elsewhere",
+];
+
+/// Why an authenticated request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionFailure {
+    /// Revoked because the account is in use elsewhere, with Qobuz's reason
+    Invalidated(String),
+    /// The token expired or was never valid
+    Expired,
+}
+
+/// A session Qobuz invalidated, for the app to log the account out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInvalidation {
+    pub user_id: u64,
+    /// Qobuz's reason, for display
+    pub reason: String,
+}
+
+/// Classify a 401/403 response body (`{"status": "error", "code": 401,
+/// "reason": ..., "message": ...}`) from its status and `reason`/`code`.
+/// Other statuses, and 403s that aren't about the session (e.g. streaming
+/// rights), give `None`.
+pub fn classify_session_failure(status: u16, body: &serde_json::Value) -> Option<SessionFailure> {
+    if status != 401 && status != 403 {
+        return None;
+    }
+
+    let reason = body["reason"].as_str().or_else(|| body["code"].as_str()).unwrap_or_default();
+    if INVALIDATION_REASONS.iter().any(|known| reason.eq_ignore_ascii_case(known)) {
+        // The (localized) message is only passed on for display
+        let message = body["message"].as_str().filter(|m| !m.is_empty()).unwrap_or(reason);
+        return Some(SessionFailure::Invalidated(message.to_string()));
+    }
+    (status == 401).then_some(SessionFailure::Expired)
+}
+
+/// Stable id for this machine, used to register a device at login.
+/// Derived from the systemd machine id so it never leaves the box in clear.
+pub fn local_device_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+        .map(|id| {
+            let mut hasher = Md5::new();
+            hasher.update(format!("qbz:{}", id).as_bytes());
+            format!("{:x}", hasher.finalize())
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::models::Quality;
 
     #[test]
     fn test_generate_signature() {
@@ -111,4 +270,57 @@ mod tests {
         let sig = sign_get_file_url(123456, 27, 1234567890, "testsecret");
         assert_eq!(sig.len(), 32);
     }
+
+    #[test]
+    fn test_parse_credential_feature_flags() {
+        let response = serde_json::json!({
+            "user_auth_token": "token",
+            "user": {
+                "id": 1,
+                "credential": {
+                    "id": 12,
+                    "label": "streaming-studio",
+                    "parameters": {
+                        "lossy_streaming": true,
+                        "lossless_streaming": true,
+                        "hires_streaming": false,
+                        "hires_purchases_streaming": true,
+                        "mobile_streaming": true,
+                        "offline_streaming": false,
+                        "included_format_group_ids": [1, 2],
+                        "short_label": "Studio"
+                    }
+                }
+            }
+        });
+        let session = parse_login_response(&response).unwrap();
+        assert_eq!(
+            session.features,
+            AccountFeatures {
+                lossy_streaming: true,
+                lossless_streaming: true,
+                hires_streaming: false,
+                hires_purchases_streaming: true,
+                mobile_streaming: true,
+                offline_streaming: false,
+            }
+        );
+        assert_eq!(session.subscription_label, "Studio");
+
+        // Missing flags and missing blocks default to false
+        let mut partial = response.clone();
+        partial["user"]["credential"]["parameters"] = serde_json::json!({ "lossless_streaming": true });
+        let features = parse_login_response(&partial).unwrap().features;
+        assert!(features.lossless_streaming && !features.lossy_streaming && !features.offline_streaming);
+
+        // A malformed flag only clears itself
+        partial["user"]["credential"]["parameters"] =
+            serde_json::json!({ "lossless_streaming": true, "hires_streaming": "yes" });
+        let features = parse_login_response(&partial).unwrap().features;
+        assert!(features.lossless_streaming && !features.hires_streaming);
+        assert_eq!(features.max_streaming_quality(), Some(Quality::Lossless));
+
+        partial["user"]["credential"]["parameters"] = serde_json::Value::Null;
+        assert_eq!(parse_login_response(&partial).unwrap().features, AccountFeatures::default());
+    }
 }
@@ -3,13 +3,21 @@
 //! Extracts app_id and secrets from the Qobuz JavaScript bundle.
 //! This is necessary because Qobuz doesn't provide a public API.
 
+use std::time::Duration;
+
 use regex::Regex;
 use reqwest::Client;
 
-use super::error::{ApiError, Result};
+use super::error::{BundleError, Result};
+
+/// Base URL of the Qobuz web player (login page and bundle live here)
+pub const BUNDLE_BASE_URL: &str = "https://play.qobuz.com";
+
+/// Fetches of a bundle that keeps arriving incomplete
+const BUNDLE_FETCH_ATTEMPTS: u32 = 3;
 
-const LOGIN_PAGE_URL: &str = "https://play.qobuz.com/login";
-const BUNDLE_BASE_URL: &str = "https://play.qobuz.com";
+/// Pause before re-fetching an incomplete bundle
+const BUNDLE_RETRY_DELAY: Duration = Duration::from_millis(250);
 
 /// Extracted bundle tokens
 #[derive(Debug, Clone)]
@@ -18,43 +26,126 @@ pub struct BundleTokens {
     pub secrets: Vec<String>,
 }
 
+/// Everything a bundle offers: some bundles carry several app_ids (one per
+/// product variant), which only the API can tell apart
+#[derive(Debug, Clone)]
+pub struct BundleCandidates {
+    /// Production web player's first, then the rest in bundle order
+    pub app_ids: Vec<String>,
+    pub secrets: Vec<String>,
+}
+
+impl BundleCandidates {
+    /// Tokens with the most likely app_id
+    pub fn preferred(&self) -> BundleTokens {
+        self.tokens_for(&self.app_ids[0])
+    }
+
+    pub fn tokens_for(&self, app_id: &str) -> BundleTokens {
+        BundleTokens {
+            app_id: app_id.to_string(),
+            secrets: self.secrets.clone(),
+        }
+    }
+}
+
 /// Extract app_id and secrets from Qobuz bundle
 pub async fn extract_bundle_tokens(client: &Client) -> Result<BundleTokens> {
+    extract_bundle_tokens_from(client, BUNDLE_BASE_URL).await
+}
+
+/// Extract app_id and secrets from the web player hosted at `base_url`
+pub async fn extract_bundle_tokens_from(client: &Client, base_url: &str) -> Result<BundleTokens> {
+    Ok(extract_bundle_candidates_from(client, base_url).await?.preferred())
+}
+
+/// Extract every app_id candidate and the secrets from the web player hosted at `base_url`
+pub async fn extract_bundle_candidates_from(client: &Client, base_url: &str) -> Result<BundleCandidates> {
     // Step 1: Get login page to find bundle URL
-    let login_page = client
-        .get(LOGIN_PAGE_URL)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let bundle_url = extract_bundle_url(&login_page)?;
-    let full_bundle_url = format!("{}{}", BUNDLE_BASE_URL, bundle_url);
-
-    // Step 2: Fetch the bundle
-    let bundle_content = client
-        .get(&full_bundle_url)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    // Step 3: Extract app_id
-    let app_id = extract_app_id(&bundle_content)?;
-
-    // Step 4: Extract secrets
-    let secrets = extract_secrets(&bundle_content)?;
+    let login_page = fetch_text(client, &format!("{}/login", base_url))
+        .await
+        .map_err(BundleError::LoginPageFetch)?;
+
+    let bundle_url = extract_bundle_url(&login_page).ok_or(BundleError::BundleUrlNotFound)?;
+    let version = bundle_version(&bundle_url).to_string();
+    log::info!("Using web player bundle {}", version);
+
+    // Step 2: Fetch the bundle, again if it came back cut short: a
+    // truncated bundle would otherwise only show up as app_ids or secrets
+    // not being found. A complete bundle is parsed once; re-fetching it
+    // wouldn't change what it contains.
+    let url = format!("{}{}", base_url, bundle_url);
+    let mut attempt = 1;
+    loop {
+        let error = match fetch_bundle(client, &url, &version).await? {
+            Ok(bundle_content) => return Ok(candidates(&bundle_content, &version)?),
+            Err(reason) => BundleError::IncompleteBundle { version: version.clone(), reason },
+        };
+
+        if attempt == BUNDLE_FETCH_ATTEMPTS {
+            return Err(error.into());
+        }
+        log::warn!("{}, fetching the bundle again", error);
+        tokio::time::sleep(BUNDLE_RETRY_DELAY * attempt).await;
+        attempt += 1;
+    }
+}
 
+/// Steps 3 and 4: the app_ids and secrets in a fetched bundle
+fn candidates(bundle_content: &str, version: &str) -> std::result::Result<BundleCandidates, BundleError> {
+    let app_ids = extract_app_ids(bundle_content);
+    if app_ids.is_empty() {
+        return Err(BundleError::AppIdNotFound { version: version.to_string() });
+    }
+    if app_ids.len() > 1 {
+        log::info!("Bundle {} has {} app_id candidates: {:?}", version, app_ids.len(), app_ids);
+    }
+
+    let secrets = extract_secrets(bundle_content);
     if secrets.is_empty() {
-        return Err(ApiError::BundleExtractionError(
-            "No secrets found in bundle".to_string(),
-        ));
+        return Err(BundleError::NoSecrets { version: version.to_string() });
+    }
+
+    Ok(BundleCandidates { app_ids, secrets })
+}
+
+/// GET a page as text, treating non-success statuses as failures
+async fn fetch_text(client: &Client, url: &str) -> std::result::Result<String, String> {
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    response.text().await.map_err(|e| e.to_string())
+}
+
+/// GET the bundle. The inner error says why the body is incomplete: the
+/// connection dropped while it was read, or it is shorter than the
+/// Content-Length the server announced.
+async fn fetch_bundle(
+    client: &Client,
+    url: &str,
+    version: &str,
+) -> std::result::Result<std::result::Result<String, String>, BundleError> {
+    let fetch_error = |message: String| BundleError::BundleFetch { version: version.to_string(), message };
+    let response = client.get(url).send().await.map_err(|e| fetch_error(e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(fetch_error(format!("HTTP {}", status)));
     }
 
-    Ok(BundleTokens { app_id, secrets })
+    let expected = response.content_length();
+    let body = match response.bytes().await {
+        Ok(body) => body,
+        Err(e) => return Ok(Err(format!("connection lost while reading it: {}", e))),
+    };
+    if let Some(expected) = expected.filter(|&expected| (body.len() as u64) < expected) {
+        return Ok(Err(format!("got {} of {} bytes", body.len(), expected)));
+    }
+    Ok(Ok(String::from_utf8_lossy(&body).into_owned()))
 }
 
-fn extract_bundle_url(html: &str) -> Result<String> {
+fn extract_bundle_url(html: &str) -> Option<String> {
     // Pattern: <script src="/resources/X.X.X-bXXX/bundle.js"></script>
     let re = Regex::new(r#"<script src="(/resources/\d+\.\d+\.\d+-[a-z]\d{3}/bundle\.js)"></script>"#)
         .expect("Invalid regex");
@@ -62,21 +153,37 @@ fn extract_bundle_url(html: &str) -> Result<String> {
     re.captures(html)
         .and_then(|caps| caps.get(1))
         .map(|m| m.as_str().to_string())
-        .ok_or_else(|| ApiError::BundleExtractionError("Bundle URL not found".to_string()))
 }
 
-fn extract_app_id(bundle: &str) -> Result<String> {
+/// Bundle version from its URL: "/resources/7.0.1-b001/bundle.js" -> "7.0.1-b001"
+fn bundle_version(bundle_url: &str) -> &str {
+    bundle_url
+        .trim_start_matches("/resources/")
+        .trim_end_matches("/bundle.js")
+}
+
+/// All app_ids in the bundle, the production config's first
+fn extract_app_ids(bundle: &str) -> Vec<String> {
     // Pattern: production:{api:{appId:"XXXXXXXXX"
-    let re = Regex::new(r#"production:\{api:\{appId:"(?P<app_id>\d{9})""#)
+    let production_re = Regex::new(r#"production:\{api:\{appId:"(?P<app_id>\d{9})""#)
         .expect("Invalid regex");
-
-    re.captures(bundle)
-        .and_then(|caps| caps.name("app_id"))
-        .map(|m| m.as_str().to_string())
-        .ok_or_else(|| ApiError::BundleExtractionError("App ID not found".to_string()))
+    // Any other config: appId:"XXXXXXXXX"
+    let any_re = Regex::new(r#"appId:"(?P<app_id>\d{9})""#).expect("Invalid regex");
+
+    let mut app_ids: Vec<String> = Vec::new();
+    let found = production_re
+        .captures_iter(bundle)
+        .chain(any_re.captures_iter(bundle))
+        .filter_map(|caps| caps.name("app_id"));
+    for app_id in found {
+        if !app_ids.iter().any(|id| id == app_id.as_str()) {
+            app_ids.push(app_id.as_str().to_string());
+        }
+    }
+    app_ids
 }
 
-fn extract_secrets(bundle: &str) -> Result<Vec<String>> {
+fn extract_secrets(bundle: &str) -> Vec<String> {
     // Extract seeds with their timezone keys
     // Pattern: X.initialSeed("SEED",window.utimezone.TIMEZONE)
     let seed_re = Regex::new(
@@ -98,7 +205,8 @@ fn extract_secrets(bundle: &str) -> Result<Vec<String>> {
     log::debug!("Found {} seeds with timezones: {:?}", seeds.len(), timezones);
 
     if seeds.is_empty() {
-        return Err(ApiError::BundleExtractionError("No seeds found".to_string()));
+        log::warn!("No seeds found in bundle, trying simple appSecret pattern");
+        return extract_plain_secrets(bundle);
     }
 
     // Build dynamic regex with found timezones (capitalize first letter for matching)
@@ -165,35 +273,192 @@ fn extract_secrets(bundle: &str) -> Result<Vec<String>> {
     // that might work for some bundle versions
     if secrets.is_empty() {
         log::warn!("Complex extraction failed, trying simple appSecret pattern");
-        let simple_re = Regex::new(r#"appSecret:"([a-f0-9]{32})""#).expect("Invalid regex");
-        for caps in simple_re.captures_iter(bundle) {
-            if let Some(secret) = caps.get(1) {
-                secrets.push(secret.as_str().to_string());
-            }
-        }
+        return extract_plain_secrets(bundle);
     }
 
     log::info!("Extracted {} secrets", secrets.len());
-    Ok(secrets)
+    secrets
+}
+
+/// Fallback for bundles that embed the secret directly
+fn extract_plain_secrets(bundle: &str) -> Vec<String> {
+    let simple_re = Regex::new(r#"appSecret:"([a-f0-9]{32})""#).expect("Invalid regex");
+    let secrets: Vec<String> = simple_re
+        .captures_iter(bundle)
+        .filter_map(|caps| caps.get(1))
+        .map(|m| m.as_str().to_string())
+        .collect();
+
+    log::info!("Extracted {} secrets", secrets.len());
+    secrets
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::api::ApiError;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const LOGIN_HTML: &str = r#"<html><script src="/resources/7.0.1-b001/bundle.js"></script></html>"#;
+    const BUNDLE_PATH: &str = "/resources/7.0.1-b001/bundle.js";
+    const PLAIN_SECRET_BUNDLE: &str = r#"production:{api:{appId:"123456789",appSecret:"0123456789abcdef0123456789abcdef"}}"#;
+
     #[test]
     fn test_extract_bundle_url() {
         let html = r#"<script src="/resources/7.0.1-b001/bundle.js"></script>"#;
-        let result = extract_bundle_url(html);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "/resources/7.0.1-b001/bundle.js");
+        let url = extract_bundle_url(html).unwrap();
+        assert_eq!(url, "/resources/7.0.1-b001/bundle.js");
+        assert_eq!(bundle_version(&url), "7.0.1-b001");
     }
 
     #[test]
     fn test_extract_app_id() {
         let bundle = r#"production:{api:{appId:"123456789",appSecret:"abc"}"#;
-        let result = extract_app_id(bundle);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "123456789");
+        assert_eq!(extract_app_ids(bundle), vec!["123456789".to_string()]);
+    }
+
+    async fn serve(server: &MockServer, route: &str, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(response)
+            .mount(server)
+            .await;
+    }
+
+    async fn extract(server: &MockServer) -> BundleError {
+        match extract_bundle_tokens_from(&Client::new(), &server.uri()).await {
+            Err(ApiError::Bundle(err)) => err,
+            other => panic!("expected a bundle error, got {:?}", other.map(|t| t.app_id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_page_fetch_failure() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(503)).await;
+
+        assert!(matches!(extract(&server).await, BundleError::LoginPageFetch(msg) if msg.contains("503")));
+    }
+
+    #[tokio::test]
+    async fn test_bundle_url_not_found() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string("<html></html>")).await;
+
+        assert!(matches!(extract(&server).await, BundleError::BundleUrlNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_bundle_fetch_failure_names_version() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string(LOGIN_HTML)).await;
+        serve(&server, BUNDLE_PATH, ResponseTemplate::new(404)).await;
+
+        let err = extract(&server).await;
+        assert!(matches!(&err, BundleError::BundleFetch { version, .. } if version == "7.0.1-b001"));
+        assert!(err.to_string().contains("7.0.1-b001"));
+    }
+
+    #[tokio::test]
+    async fn test_app_id_not_found() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string(LOGIN_HTML)).await;
+        serve(&server, BUNDLE_PATH, ResponseTemplate::new(200).set_body_string("var x = 1;")).await;
+
+        assert!(matches!(extract(&server).await, BundleError::AppIdNotFound { version } if version == "7.0.1-b001"));
+    }
+
+    #[tokio::test]
+    async fn test_no_secrets_decoded() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string(LOGIN_HTML)).await;
+        serve(
+            &server,
+            BUNDLE_PATH,
+            ResponseTemplate::new(200).set_body_string(r#"production:{api:{appId:"123456789"}}"#),
+        )
+        .await;
+
+        assert!(matches!(extract(&server).await, BundleError::NoSecrets { version } if version == "7.0.1-b001"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_secret_bundle_succeeds() {
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string(LOGIN_HTML)).await;
+        serve(
+            &server,
+            BUNDLE_PATH,
+            ResponseTemplate::new(200).set_body_string(PLAIN_SECRET_BUNDLE),
+        )
+        .await;
+
+        let tokens = extract_bundle_tokens_from(&Client::new(), &server.uri()).await.unwrap();
+        assert_eq!(tokens.app_id, "123456789");
+        assert_eq!(tokens.secrets, vec!["0123456789abcdef0123456789abcdef".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_complete_bundle_without_tokens_is_not_fetched_again() {
+        // The whole body arrived, it just has no usable secret
+        let no_secret = &PLAIN_SECRET_BUNDLE[..PLAIN_SECRET_BUNDLE.len() - 20];
+
+        let server = MockServer::start().await;
+        serve(&server, "/login", ResponseTemplate::new(200).set_body_string(LOGIN_HTML)).await;
+        Mock::given(method("GET"))
+            .and(path(BUNDLE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(no_secret))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        serve(&server, BUNDLE_PATH, ResponseTemplate::new(200).set_body_string(PLAIN_SECRET_BUNDLE)).await;
+
+        let err = extract(&server).await;
+        assert!(matches!(&err, BundleError::NoSecrets { version } if version == "7.0.1-b001"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_shorter_than_its_content_length_is_incomplete() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // The connection drops every time after part of the announced body
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let bundle_fetches = Arc::new(AtomicU32::new(0));
+        tokio::spawn({
+            let bundle_fetches = bundle_fetches.clone();
+            async move {
+                loop {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let mut request = vec![0u8; 4096];
+                    let read = socket.read(&mut request).await.unwrap();
+                    let request = String::from_utf8_lossy(&request[..read]).into_owned();
+                    let response = if request.starts_with("GET /login ") {
+                        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", LOGIN_HTML.len(), LOGIN_HTML)
+                    } else {
+                        bundle_fetches.fetch_add(1, Ordering::SeqCst);
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            PLAIN_SECRET_BUNDLE.len() + 100,
+                            PLAIN_SECRET_BUNDLE
+                        )
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                }
+            }
+        });
+
+        let err = match extract_bundle_tokens_from(&Client::new(), &base_url).await {
+            Err(ApiError::Bundle(err)) => err,
+            other => panic!("expected a bundle error, got {:?}", other.map(|t| t.app_id)),
+        };
+        assert!(matches!(&err, BundleError::IncompleteBundle { version, .. } if version == "7.0.1-b001"), "{}", err);
+        assert_eq!(bundle_fetches.load(Ordering::SeqCst), BUNDLE_FETCH_ATTEMPTS);
     }
 }
@@ -1,51 +1,400 @@
 //! Qobuz API client implementation
 
+use chrono::NaiveDate;
+use futures_util::stream::{self, StreamExt};
 use reqwest::{Client, StatusCode};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-
-use super::auth::{get_timestamp, parse_login_response, sign_get_favorites, sign_get_file_url};
-use super::bundle::{extract_bundle_tokens, BundleTokens};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+
+use super::auth::{
+    classify_session_failure, get_timestamp, local_device_id, parse_devices, parse_login_response, parse_user,
+    sign_get_favorites, sign_get_file_url, sign_get_purchases, SessionFailure, SessionInvalidation,
+};
+use super::bundle::{extract_bundle_candidates_from, BundleCandidates, BundleTokens, BUNDLE_BASE_URL};
 use super::endpoints::{self, paths};
 use super::error::{ApiError, Result};
 use super::models::*;
+use super::region::{self, RegionMismatch, REGION_RESTRICTIONS};
+use super::requests::{InFlightRequests, DEFAULT_REQUEST_TIMEOUT};
+use super::routing::{self as endpoint_routing, EndpointRoute};
+use super::stream_urls::{self, StreamUrlCache};
+use super::suggest::{self, Suggestion, SuggestionCache};
+
+/// Max concurrent requests for batched album fetches
+const ALBUM_BATCH_CONCURRENCY: usize = 4;
+
+/// Section sizes for `get_artist_page`
+const ARTIST_PAGE_TOP_TRACKS: u32 = 10;
+const ARTIST_PAGE_ALBUMS: u32 = 50;
+const ARTIST_PAGE_SIMILAR: u32 = 10;
+
+/// Tracks requested per page when following album/playlist pagination
+const TRACKS_PAGE_SIZE: u32 = 500;
+
+/// Items requested per page, and most pages read, when an editorial section
+/// is narrowed to a genre on our side
+const EDITORIAL_GENRE_PAGE_SIZE: u32 = 50;
+const EDITORIAL_GENRE_MAX_PAGES: u32 = 4;
+
+/// Max concurrent getFileUrl requests when probing qualities
+const QUALITY_PROBE_CONCURRENCY: usize = 2;
+/// Delay between starting quality probe requests
+const QUALITY_PROBE_SPACING: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Released tracks remembered as checked before they are looked up again
+const MAX_CHECKED_RELEASES: usize = 4096;
+
+/// Release dates of tracks seen in the catalog. Only dates still ahead are
+/// kept; tracks already out are remembered as checked, oldest dropped first.
+#[derive(Default)]
+struct ReleaseWindows {
+    upcoming: HashMap<u64, NaiveDate>,
+    released: HashSet<u64>,
+    released_order: VecDeque<u64>,
+}
+
+impl ReleaseWindows {
+    fn record(&mut self, track_id: u64, available_on: Option<NaiveDate>, today: NaiveDate) {
+        self.upcoming.retain(|_, date| *date > today);
+        match available_on.filter(|date| *date > today) {
+            Some(date) => {
+                self.upcoming.insert(track_id, date);
+            }
+            None => {
+                self.upcoming.remove(&track_id);
+                if self.released.insert(track_id) {
+                    self.released_order.push_back(track_id);
+                    if self.released_order.len() > MAX_CHECKED_RELEASES {
+                        if let Some(oldest) = self.released_order.pop_front() {
+                            self.released.remove(&oldest);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_known(&self, track_id: u64) -> bool {
+        self.upcoming.contains_key(&track_id) || self.released.contains(&track_id)
+    }
+
+    /// First streaming day of `track_id` if it is still ahead of `today`
+    fn upcoming(&self, track_id: u64, today: NaiveDate) -> Option<NaiveDate> {
+        self.upcoming.get(&track_id).copied().filter(|date| *date > today)
+    }
+}
 
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0";
 
 /// Qobuz API client
+///
+/// Clones share the tokens, session and caches, so a clone can make
+/// requests without holding the app's client lock.
+#[derive(Clone)]
 pub struct QobuzClient {
     http: Client,
+    api_base_url: String,
+    bundle_base_url: String,
     tokens: Arc<RwLock<Option<BundleTokens>>>,
     session: Arc<RwLock<Option<UserSession>>>,
     validated_secret: Arc<RwLock<Option<String>>>,
     locale: Arc<RwLock<String>>,
+    /// Country the connection comes from, detected on the first region error
+    network_region: Arc<RwLock<Option<String>>>,
+    /// Stream URLs resolved for playback, reused until they near expiry
+    stream_urls: Arc<RwLock<StreamUrlCache>>,
+    /// Recent type-ahead suggestions per query
+    suggestions: Arc<RwLock<SuggestionCache>>,
+    /// Every app_id the last bundle offered, in the order they were tried
+    app_id_candidates: Arc<RwLock<Vec<String>>>,
+    /// Known favorite ids per type ("albums", "tracks", "artists"), kept in
+    /// step with confirmed adds and removes
+    favorite_ids: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    /// Deprecated track ids mapped to the ids the catalog replaced them with
+    track_replacements: Arc<RwLock<HashMap<u64, u64>>>,
+    /// Release dates of tracks seen in the catalog; tracks missing here are
+    /// looked up before streaming
+    release_windows: Arc<RwLock<ReleaseWindows>>,
+    /// Requests the frontend can cancel by id
+    requests: Arc<InFlightRequests>,
+    /// Reasons for sessions Qobuz invalidated, for the app to prompt a re-login
+    session_invalidated: broadcast::Sender<SessionInvalidation>,
+    /// Sent at login to register this machine as a device
+    device_manufacturer_id: Option<String>,
+    /// Container to pick when a quality is offered in several
+    preferred_container: Option<AudioContainer>,
+    /// API endpoints to fall back on (None = only `api_base_url`)
+    route: Option<Arc<dyn EndpointRoute>>,
 }
 
-impl QobuzClient {
-    /// Create a new client
-    pub fn new() -> Result<Self> {
-        let http = Client::builder()
-            .user_agent(USER_AGENT)
-            .cookie_store(true)
-            .build()?;
+/// Builder for [`QobuzClient`]
+///
+/// Everything defaults to the production endpoints; tests can inject an HTTP
+/// client, point the client at a mock server, and pre-seed bundle tokens.
+#[derive(Default)]
+pub struct QobuzClientBuilder {
+    http: Option<Client>,
+    api_base_url: Option<String>,
+    bundle_base_url: Option<String>,
+    tokens: Option<BundleTokens>,
+    device_manufacturer_id: Option<String>,
+    request_timeout: Option<Duration>,
+}
 
-        Ok(Self {
+impl QobuzClientBuilder {
+    /// Use a pre-built HTTP client
+    pub fn http_client(mut self, http: Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Override the API base URL (default: `endpoints::BASE_URL`)
+    pub fn api_base_url(mut self, url: impl Into<String>) -> Self {
+        self.api_base_url = Some(url.into());
+        self
+    }
+
+    /// Override the web player URL used for bundle extraction
+    pub fn bundle_base_url(mut self, url: impl Into<String>) -> Self {
+        self.bundle_base_url = Some(url.into());
+        self
+    }
+
+    /// Start with known bundle tokens (skips the need to call `init`)
+    pub fn tokens(mut self, tokens: BundleTokens) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Register logins as this device (see `auth::local_device_id`)
+    pub fn device_manufacturer_id(mut self, id: impl Into<String>) -> Self {
+        self.device_manufacturer_id = Some(id.into());
+        self
+    }
+
+    /// Give up on a request after this long (default: `DEFAULT_REQUEST_TIMEOUT`).
+    /// Ignored when an HTTP client is injected.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<QobuzClient> {
+        let http = match self.http {
+            Some(http) => http,
+            None => Client::builder()
+                .user_agent(USER_AGENT)
+                .cookie_store(true)
+                .timeout(self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT))
+                .build()?,
+        };
+
+        Ok(QobuzClient {
             http,
-            tokens: Arc::new(RwLock::new(None)),
+            api_base_url: self.api_base_url.unwrap_or_else(|| endpoints::BASE_URL.to_string()),
+            bundle_base_url: self.bundle_base_url.unwrap_or_else(|| BUNDLE_BASE_URL.to_string()),
+            tokens: Arc::new(RwLock::new(self.tokens)),
             session: Arc::new(RwLock::new(None)),
             validated_secret: Arc::new(RwLock::new(None)),
             locale: Arc::new(RwLock::new("en".to_string())),
+            network_region: Arc::new(RwLock::new(None)),
+            stream_urls: Arc::new(RwLock::new(StreamUrlCache::default())),
+            suggestions: Arc::new(RwLock::new(SuggestionCache::default())),
+            app_id_candidates: Arc::new(RwLock::new(Vec::new())),
+            favorite_ids: Arc::new(RwLock::new(HashMap::new())),
+            track_replacements: Arc::new(RwLock::new(HashMap::new())),
+            release_windows: Arc::new(RwLock::new(ReleaseWindows::default())),
+            requests: Arc::new(InFlightRequests::default()),
+            session_invalidated: broadcast::channel(4).0,
+            device_manufacturer_id: self.device_manufacturer_id,
+            preferred_container: None,
+            route: None,
+        })
+    }
+}
+
+impl QobuzClient {
+    /// Create a new client
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Start building a client with custom transport/endpoints
+    pub fn builder() -> QobuzClientBuilder {
+        QobuzClientBuilder::default()
+    }
+
+    /// Registry of cancellable requests, usable without locking the client
+    pub fn requests(&self) -> Arc<InFlightRequests> {
+        self.requests.clone()
+    }
+
+    /// Send API requests to the route's endpoints, falling down its list
+    /// when one can't be reached (see `config::endpoint_priority`)
+    pub fn set_endpoint_route(&mut self, route: Arc<dyn EndpointRoute>) {
+        self.route = Some(route);
+    }
+
+    /// Prefer a container when getFileUrl offers a choice (None = take the
+    /// main file). URLs resolved under the old preference are dropped.
+    pub fn set_preferred_container(&mut self, container: Option<AudioContainer>) {
+        if self.preferred_container != container {
+            self.preferred_container = container;
+            self.stream_urls = Arc::new(RwLock::new(StreamUrlCache::default()));
+        }
+    }
+
+    /// Register logins as this machine (see `auth::local_device_id`). Off by
+    /// default: only setups whose formats are restricted without a device
+    /// need it. Takes effect at the next login.
+    pub fn set_device_registration(&mut self, enabled: bool) {
+        self.device_manufacturer_id = if enabled { local_device_id() } else { None };
+    }
+
+    /// Build full URL for an endpoint
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}{}", self.api_base_url, endpoint)
+    }
+
+    /// Send an API request built against `api_base_url`. With a route, it
+    /// goes to each endpoint in turn until one answers; a server error (5xx)
+    /// counts as a failed attempt and moves on, any other HTTP answer ends
+    /// the search. When every endpoint fails with a server error, the last
+    /// response is returned for the caller to report.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let Some(route) = self.route.as_deref() else {
+            return Ok(request.send().await?);
+        };
+        let request = request.build()?;
+        let Some(path) = request.url().as_str().strip_prefix(&self.api_base_url).map(str::to_string) else {
+            return Ok(self.http.execute(request).await?);
+        };
+
+        let server_error = std::sync::Mutex::new(None);
+        let result = endpoint_routing::try_in_order(route, |base| {
+            let attempt = request.try_clone();
+            let url = format!("{}{}", base, path);
+            let server_error = &server_error;
+            async move {
+                let mut attempt = attempt
+                    .ok_or_else(|| ApiError::ApiResponse("Request can't be retried".to_string()))?;
+                *attempt.url_mut() = url
+                    .parse()
+                    .map_err(|e| ApiError::ApiResponse(format!("Invalid endpoint {}: {}", url, e)))?;
+                let response = self.http.execute(attempt).await?;
+                let status = response.status();
+                if status.is_server_error() {
+                    *server_error.lock().unwrap() = Some(response);
+                    return Err(ApiError::ApiResponse(format!("Server error: {}", status)));
+                }
+                Ok(response)
+            }
         })
+        .await;
+
+        match (result, server_error.into_inner().unwrap()) {
+            (Err(_), Some(response)) => Ok(response),
+            (result, _) => result,
+        }
     }
 
     /// Initialize client by extracting bundle tokens
     pub async fn init(&self) -> Result<()> {
-        let tokens = extract_bundle_tokens(&self.http).await?;
+        let tokens = self.fetch_bundle_tokens().await?;
         *self.tokens.write().await = Some(tokens);
         Ok(())
     }
 
+    /// Extract the bundle and settle on one of its app_ids
+    async fn fetch_bundle_tokens(&self) -> Result<BundleTokens> {
+        let candidates = extract_bundle_candidates_from(&self.http, &self.bundle_base_url).await?;
+        *self.app_id_candidates.write().await = candidates.app_ids.clone();
+        self.choose_app_id(&candidates).await
+    }
+
+    /// First app_id the API accepts, in preference order. With a single
+    /// candidate nothing is checked; a candidate that can't be checked is
+    /// skipped, and if none is accepted the preferred one is kept so the
+    /// failure surfaces on the actual request.
+    async fn choose_app_id(&self, candidates: &BundleCandidates) -> Result<BundleTokens> {
+        if candidates.app_ids.len() == 1 {
+            return Ok(candidates.preferred());
+        }
+
+        for app_id in &candidates.app_ids {
+            match self.app_id_accepted(app_id).await {
+                Ok(true) => {
+                    log::info!("Using bundle app_id {}", app_id);
+                    return Ok(candidates.tokens_for(app_id));
+                }
+                Ok(false) => {
+                    log::warn!("Bundle app_id {} rejected by the API, trying the next candidate", app_id);
+                }
+                Err(e) if e.is_transport() => {
+                    log::warn!("Couldn't check bundle app_id {} ({}), trying the next candidate", app_id, e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        log::warn!("No bundle app_id was accepted, keeping {}", candidates.app_ids[0]);
+        Ok(candidates.preferred())
+    }
+
+    /// Whether a catalog call with this app_id gets past the app check.
+    /// Only a 400 whose error names the app_id counts as a rejection; other
+    /// failures (auth, missing track) happen after the app check passed.
+    async fn app_id_accepted(&self, app_id: &str) -> Result<bool> {
+        let test_track_id = 5966783u64; // Known test track
+        let request = self
+            .http
+            .get(self.url(paths::TRACK_GET))
+            .header("X-App-Id", app_id)
+            .query(&[("track_id", test_track_id.to_string())]);
+        let response = self.send(request).await?;
+        if response.status() != StatusCode::BAD_REQUEST {
+            return Ok(true);
+        }
+
+        let message = response
+            .json::<Value>()
+            .await
+            .ok()
+            .and_then(|body| body["message"].as_str().map(|s| s.to_string()))
+            .unwrap_or_default();
+        Ok(!is_app_id_rejection(&message))
+    }
+
+    /// Bundle tokens in use, for diagnostics
+    pub async fn api_diagnostics(&self) -> ApiDiagnostics {
+        ApiDiagnostics {
+            app_id: self.tokens.read().await.as_ref().map(|t| t.app_id.clone()),
+            app_id_candidates: self.app_id_candidates.read().await.clone(),
+            secret_validated: self.validated_secret.read().await.is_some(),
+            api_base_url: self.api_base_url.clone(),
+        }
+    }
+
+    /// Re-extract bundle tokens and re-validate a secret.
+    ///
+    /// The new bundle is fetched before touching the cached tokens, so requests
+    /// already in flight keep using the old tokens until the swap.
+    pub async fn refresh_tokens(&self) -> Result<()> {
+        let tokens = self.fetch_bundle_tokens().await?;
+        {
+            let mut current_tokens = self.tokens.write().await;
+            let mut validated_secret = self.validated_secret.write().await;
+            *current_tokens = Some(tokens);
+            *validated_secret = None;
+        }
+        self.secret().await?;
+        log::info!("Bundle tokens refreshed and secret re-validated");
+        Ok(())
+    }
+
     /// Set the locale for API requests
     pub async fn set_locale(&self, locale: String) {
         *self.locale.write().await = locale;
@@ -61,6 +410,37 @@ impl QobuzClient {
         self.locale.read().await.clone()
     }
 
+    /// Override the detected network region (country code)
+    pub async fn set_network_region(&self, region: Option<String>) {
+        *self.network_region.write().await = region;
+    }
+
+    /// Detect the network region from the storefront and remember it.
+    /// Clones share the result, so this runs on a clone without the app's
+    /// client lock.
+    pub async fn detect_network_region(&self) {
+        if let Some(region) = region::detect_network_region().await {
+            *self.network_region.write().await = Some(region);
+        }
+    }
+
+    /// Explain a region-restricted failure using the account and network
+    /// regions. The network region is never looked up here, since callers
+    /// may hold the client lock; if it isn't known yet, a lookup starts in
+    /// the background for the next failure.
+    async fn region_mismatch(&self) -> ApiError {
+        let account_region = self.session.read().await.as_ref().and_then(|s| s.country_code.clone());
+        let network_region = self.network_region.read().await.clone();
+        if network_region.is_none() {
+            let client = self.clone();
+            tokio::spawn(async move { client.detect_network_region().await });
+        }
+
+        let mismatch = RegionMismatch::new(account_region, network_region);
+        log::warn!("Region restriction: {:?}", mismatch);
+        ApiError::RegionMismatch(mismatch)
+    }
+
     /// Get app ID
     async fn app_id(&self) -> Result<String> {
         self.tokens
@@ -78,13 +458,28 @@ impl QobuzClient {
             return Ok(secret);
         }
 
-        // Need to validate secrets
-        let tokens = self.tokens.read().await;
-        let tokens = tokens
+        self.select_secret(None).await
+    }
+
+    /// Validate the bundle secrets in order and cache the first that works.
+    /// `last` is moved to the end so a secret that went stale is tried last.
+    async fn select_secret(&self, last: Option<&str>) -> Result<String> {
+        // Clone the secrets so the tokens lock isn't held across requests
+        // (test_secret reads the app_id from the same lock).
+        let mut secrets = self
+            .tokens
+            .read()
+            .await
             .as_ref()
+            .map(|t| t.secrets.clone())
             .ok_or_else(|| ApiError::BundleExtractionError("Client not initialized".to_string()))?;
 
-        for secret in &tokens.secrets {
+        if let Some(index) = last.and_then(|last| secrets.iter().position(|s| s == last)) {
+            let secret = secrets.remove(index);
+            secrets.push(secret);
+        }
+
+        for secret in &secrets {
             if self.test_secret(secret).await? {
                 *self.validated_secret.write().await = Some(secret.clone());
                 return Ok(secret.clone());
@@ -100,8 +495,8 @@ impl QobuzClient {
         let timestamp = get_timestamp();
         let signature = sign_get_file_url(test_track_id, 5, timestamp, secret);
 
-        let url = endpoints::build_url(paths::TRACK_GET_FILE_URL);
-        let response = self
+        let url = self.url(paths::TRACK_GET_FILE_URL);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -111,23 +506,27 @@ impl QobuzClient {
                 ("intent", "stream".to_string()),
                 ("request_ts", timestamp.to_string()),
                 ("request_sig", signature),
-            ])
-            .send()
-            .await?;
+            ]);
+        let response = self.send(request).await?;
 
         Ok(response.status() != StatusCode::BAD_REQUEST)
     }
 
     /// Login with email and password
     pub async fn login(&self, email: &str, password: &str) -> Result<UserSession> {
-        let url = endpoints::build_url(paths::USER_LOGIN);
-        let response = self
+        let url = self.url(paths::USER_LOGIN);
+        let mut query = vec![("email", email), ("password", password)];
+        // Only sent when device registration is enabled: some accounts only
+        // get their full formats on a registered device
+        if let Some(device) = self.device_manufacturer_id.as_deref() {
+            query.push(("device_manufacturer_id", device));
+        }
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
-            .query(&[("email", email), ("password", password)])
-            .send()
-            .await?;
+            .query(&query);
+        let response = self.send(request).await?;
 
         match response.status() {
             StatusCode::OK => {
@@ -144,14 +543,186 @@ impl QobuzClient {
         }
     }
 
+    /// Log in with an existing user auth token instead of a password.
+    /// The token is checked against `user/get`, which also provides the
+    /// display name and subscription.
+    pub async fn login_with_token(&self, token: &str) -> Result<UserSession> {
+        let url = self.url(paths::USER_GET);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", token);
+        let response = self.send(request).await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let json: Value = response.json().await?;
+                let session = parse_user(&json, token.to_string())?;
+                *self.session.write().await = Some(session.clone());
+                Ok(session)
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(ApiError::AuthenticationError("Invalid or expired token".to_string()))
+            }
+            StatusCode::BAD_REQUEST => Err(ApiError::InvalidAppId),
+            status => Err(ApiError::ApiResponse(format!("Unexpected status: {}", status))),
+        }
+    }
+
+    /// Install a session without logging in
+    #[cfg(test)]
+    pub(crate) async fn set_session(&self, session: UserSession) {
+        *self.session.write().await = Some(session);
+    }
+
     /// Check if logged in
     pub async fn is_logged_in(&self) -> bool {
         self.session.read().await.is_some()
     }
 
+    /// Devices registered on the logged-in account
+    pub async fn get_account_devices(&self) -> Result<Vec<DeviceInfo>> {
+        let url = self.url(paths::USER_GET_DEVICES);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?);
+        let response = self.send_authed(request).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(device_management_unavailable());
+        }
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to get devices: {}", response.status())));
+        }
+
+        let json: Value = response.json().await?;
+        let current = self.session.read().await.as_ref().and_then(|s| s.device_id.clone());
+        parse_devices(&json, current.as_deref())
+    }
+
+    /// Log a registered device out of the account. The device this app is
+    /// logged in as can't be revoked here; that is what `logout` is for.
+    pub async fn revoke_device(&self, device_id: &str) -> Result<()> {
+        let current = self.session.read().await.as_ref().and_then(|s| s.device_id.clone());
+        if current.as_deref() == Some(device_id) {
+            return Err(ApiError::ApiResponse(
+                "This is the current device; log out instead of revoking it".to_string(),
+            ));
+        }
+
+        let url = self.url(paths::USER_DELETE_DEVICE);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?)
+            .query(&[("device_id", device_id)]);
+        let response = self.send_authed(request).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND => Err(device_management_unavailable()),
+            StatusCode::BAD_REQUEST => {
+                // e.g. the API refusing to revoke the device making the request
+                let message = response
+                    .json::<Value>()
+                    .await
+                    .ok()
+                    .and_then(|body| body["message"].as_str().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "the API refused to revoke it".to_string());
+                Err(ApiError::ApiResponse(format!("Can't revoke device {}: {}", device_id, message)))
+            }
+            status => Err(ApiError::ApiResponse(format!("Failed to revoke device {}: {}", device_id, status))),
+        }
+    }
+
+    /// Report that a stream of `track_id` started
+    pub async fn report_stream_start(&self, track_id: u64) -> Result<()> {
+        self.report_streaming(paths::TRACK_REPORT_STREAMING_START, track_id, None).await
+    }
+
+    /// Report that a stream of `track_id` ended after `listened_secs`
+    pub async fn report_stream_end(&self, track_id: u64, listened_secs: u64) -> Result<()> {
+        self.report_streaming(paths::TRACK_REPORT_STREAMING_END, track_id, Some(listened_secs)).await
+    }
+
+    /// Qobuz doesn't document these endpoints. The shape follows the one
+    /// used by other clients: a form-encoded POST whose `events` field is a
+    /// JSON array of events. A refused report only loses the report, so
+    /// callers just log the error.
+    async fn report_streaming(&self, endpoint: &str, track_id: u64, duration: Option<u64>) -> Result<()> {
+        let mut event = serde_json::json!({
+            "user_id": self.user_id().await?,
+            "track_id": track_id,
+            "date": get_timestamp(),
+            "online": true,
+        });
+        if let Some(duration) = duration {
+            event["duration"] = duration.into();
+        }
+
+        let request = self
+            .http
+            .post(self.url(endpoint))
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?)
+            .form(&[("events", serde_json::json!([event]).to_string())]);
+        let response = self.send_authed(request).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(ApiError::ApiResponse(format!("Stream report for track {} refused: {}", track_id, status))),
+        }
+    }
+
+    /// Notified whenever Qobuz invalidates the session
+    pub fn subscribe_session_invalidated(&self) -> broadcast::Receiver<SessionInvalidation> {
+        self.session_invalidated.subscribe()
+    }
+
+    /// Send an authenticated request. A session Qobuz ended because the
+    /// account signed in elsewhere is dropped, broadcast once to
+    /// `subscribe_session_invalidated` for the app's logout cleanup and
+    /// reported as `SessionInvalidated`; an expired token as
+    /// `AuthenticationError`.
+    async fn send_authed(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let response = self.send(request).await?;
+        let status = response.status();
+        if !matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            return Ok(response);
+        }
+
+        let body = response.json::<Value>().await.unwrap_or(Value::Null);
+        match classify_session_failure(status.as_u16(), &body) {
+            Some(SessionFailure::Invalidated(reason)) => {
+                log::warn!("Qobuz invalidated the session: {}", reason);
+                // Concurrent requests refused together report it once
+                let ended = self.session.write().await.take();
+                if let Some(session) = ended {
+                    self.logout().await;
+                    let _ = self.session_invalidated.send(SessionInvalidation {
+                        user_id: session.user_id,
+                        reason: reason.clone(),
+                    });
+                }
+                Err(ApiError::SessionInvalidated(reason))
+            }
+            Some(SessionFailure::Expired) => {
+                Err(ApiError::AuthenticationError("Session expired, please log in again".to_string()))
+            }
+            None => Err(ApiError::ApiResponse(format!("Request refused: {}", status))),
+        }
+    }
+
     /// Logout - clear the session
     pub async fn logout(&self) {
         *self.session.write().await = None;
+        self.favorite_ids.write().await.clear();
+        // Signed for this account
+        self.stream_urls.write().await.clear();
     }
 
     /// Get current user info (display name and subscription)
@@ -161,6 +732,42 @@ impl QobuzClient {
         })
     }
 
+    /// Get the subscription state of the logged-in user
+    pub async fn get_subscription(&self) -> Option<SubscriptionInfo> {
+        self.session.read().await.as_ref().map(|s| s.subscription.clone())
+    }
+
+    /// Feature flags of the logged-in account's plan
+    pub async fn get_account_features(&self) -> Option<AccountFeatures> {
+        self.session.read().await.as_ref().map(|s| s.features.clone())
+    }
+
+    /// Zone and registered device of the logged-in session
+    pub async fn session_zone(&self) -> Option<SessionZone> {
+        self.session.read().await.as_ref().map(|s| SessionZone {
+            zone: s.zone.clone(),
+            device_id: s.device_id.clone(),
+        })
+    }
+
+    /// Fail early when the logged-in account cannot stream
+    async fn ensure_can_stream(&self) -> Result<()> {
+        match self.session.read().await.as_ref() {
+            Some(session) if !session.subscription.active => Err(ApiError::NoActiveSubscription),
+            _ => Ok(()),
+        }
+    }
+
+    /// Id of the logged-in user
+    pub async fn user_id(&self) -> Result<u64> {
+        self.session
+            .read()
+            .await
+            .as_ref()
+            .map(|s| s.user_id)
+            .ok_or_else(|| ApiError::AuthenticationError("Not logged in".to_string()))
+    }
+
     /// Get user auth token header value
     async fn auth_token(&self) -> Result<String> {
         self.session
@@ -175,8 +782,8 @@ impl QobuzClient {
 
     /// Search for albums
     pub async fn search_albums(&self, query: &str, limit: u32, offset: u32) -> Result<SearchResultsPage<Album>> {
-        let url = endpoints::build_url(paths::ALBUM_SEARCH);
-        let response: Value = self
+        let url = self.url(paths::ALBUM_SEARCH);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -184,9 +791,8 @@ impl QobuzClient {
                 ("query", query),
                 ("limit", &limit.to_string()),
                 ("offset", &offset.to_string()),
-            ])
-            .send()
-            .await?
+            ]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -199,8 +805,8 @@ impl QobuzClient {
 
     /// Search for tracks
     pub async fn search_tracks(&self, query: &str, limit: u32, offset: u32) -> Result<SearchResultsPage<Track>> {
-        let url = endpoints::build_url(paths::TRACK_SEARCH);
-        let response: Value = self
+        let url = self.url(paths::TRACK_SEARCH);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -208,9 +814,8 @@ impl QobuzClient {
                 ("query", query),
                 ("limit", &limit.to_string()),
                 ("offset", &offset.to_string()),
-            ])
-            .send()
-            .await?
+            ]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -223,8 +828,8 @@ impl QobuzClient {
 
     /// Search for artists
     pub async fn search_artists(&self, query: &str, limit: u32, offset: u32) -> Result<SearchResultsPage<Artist>> {
-        let url = endpoints::build_url(paths::ARTIST_SEARCH);
-        let response: Value = self
+        let url = self.url(paths::ARTIST_SEARCH);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -232,9 +837,8 @@ impl QobuzClient {
                 ("query", query),
                 ("limit", &limit.to_string()),
                 ("offset", &offset.to_string()),
-            ])
-            .send()
-            .await?
+            ]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -245,10 +849,42 @@ impl QobuzClient {
         Ok(serde_json::from_value(artists.clone())?)
     }
 
+    /// Type-ahead suggestions (artists, albums, tracks) for a partial query.
+    /// Short queries return nothing without a request; answers are cached
+    /// briefly, per locale.
+    pub async fn get_suggestions(&self, query: &str) -> Result<Vec<Suggestion>> {
+        let Some(query) = suggest::normalize_query(query) else {
+            return Ok(Vec::new());
+        };
+        let locale = self.locale().await;
+        if let Some(suggestions) = self.suggestions.read().await.get(&locale, &query, std::time::Instant::now()) {
+            return Ok(suggestions);
+        }
+
+        let url = self.url(paths::CATALOG_AUTOSUGGEST);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .query(&[("query", query.as_str()), ("limit", "5"), ("lang", locale.as_str())]);
+        let response = self.send(request).await?;
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to get suggestions: {}", response.status())));
+        }
+        let response: Value = response.json().await?;
+
+        let suggestions = suggest::parse_suggestions(&response);
+        self.suggestions
+            .write()
+            .await
+            .insert(locale, query, suggestions.clone(), std::time::Instant::now());
+        Ok(suggestions)
+    }
+
     /// Get similar artists for an artist ID
     pub async fn get_similar_artists(&self, artist_id: u64, limit: u32, offset: u32) -> Result<SearchResultsPage<Artist>> {
-        let url = endpoints::build_url(paths::ARTIST_GET_SIMILAR);
-        let response: Value = self
+        let url = self.url(paths::ARTIST_GET_SIMILAR);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -256,9 +892,8 @@ impl QobuzClient {
                 ("artist_id", artist_id.to_string()),
                 ("limit", limit.to_string()),
                 ("offset", offset.to_string()),
-            ])
-            .send()
-            .await?
+            ]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -273,24 +908,88 @@ impl QobuzClient {
 
     /// Get album by ID
     pub async fn get_album(&self, album_id: &str) -> Result<Album> {
-        let url = endpoints::build_url(paths::ALBUM_GET);
-        let response: Value = self
+        self.fetch_album(album_id, &[]).await
+    }
+
+    /// Get an album with its complete track list, following track
+    /// pagination for very long releases (box sets)
+    pub async fn get_album_all_tracks(&self, album_id: &str) -> Result<Album> {
+        let mut album = self.get_album(album_id).await?;
+        if let Some(tracks) = album.tracks.as_mut() {
+            while (tracks.items.len() as u32) < tracks.total {
+                let page = self
+                    .fetch_album(
+                        album_id,
+                        &[
+                            ("limit", TRACKS_PAGE_SIZE.to_string()),
+                            ("offset", tracks.items.len().to_string()),
+                        ],
+                    )
+                    .await?;
+                let items = page.tracks.map(|t| t.items).unwrap_or_default();
+                if items.is_empty() {
+                    break;
+                }
+                tracks.items.extend(items);
+            }
+            // Pages are ordered individually
+            tracks
+                .items
+                .sort_by_key(|t| (t.media_number.unwrap_or(1), t.track_number));
+        }
+        album.index_discs();
+        Ok(album)
+    }
+
+    async fn fetch_album(&self, album_id: &str, page: &[(&str, String)]) -> Result<Album> {
+        let url = self.url(paths::ALBUM_GET);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .query(&[("album_id", album_id)])
-            .send()
-            .await?
-            .json()
-            .await?;
+            .query(page);
+        let response = self.send(request).await?;
 
-        Ok(serde_json::from_value(response)?)
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to get album {}: {}", album_id, response.status())));
+        }
+
+        let response: Value = response.json().await?;
+        let mut album: Album = serde_json::from_value(response)?;
+        album.index_discs();
+        if let Some(tracks) = &album.tracks {
+            self.record_release_windows(tracks.items.iter().map(|track| (track, Some(&album))))
+                .await;
+        }
+        Ok(album)
+    }
+
+    /// Get several albums concurrently (at most `ALBUM_BATCH_CONCURRENCY` in flight).
+    /// Duplicate ids are fetched once; each id maps to its own result so one
+    /// failure doesn't abort the batch.
+    pub async fn get_albums(&self, album_ids: &[String]) -> HashMap<String, Result<Album>> {
+        let mut unique: Vec<&String> = Vec::with_capacity(album_ids.len());
+        for id in album_ids {
+            if !unique.contains(&id) {
+                unique.push(id);
+            }
+        }
+
+        stream::iter(unique.into_iter().cloned())
+            .map(|id: String| async move {
+                let album = self.get_album(&id).await;
+                (id, album)
+            })
+            .buffer_unordered(ALBUM_BATCH_CONCURRENCY)
+            .collect()
+            .await
     }
 
     /// Get featured albums by type (new-releases, press-awards, most-streamed)
     pub async fn get_featured_albums(&self, featured_type: &str, limit: u32, offset: u32) -> Result<SearchResultsPage<Album>> {
-        let url = endpoints::build_url(paths::ALBUM_GET_FEATURED);
-        let response: Value = self
+        let url = self.url(paths::ALBUM_GET_FEATURED);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
@@ -298,9 +997,8 @@ impl QobuzClient {
                 ("type", featured_type),
                 ("limit", &limit.to_string()),
                 ("offset", &offset.to_string()),
-            ])
-            .send()
-            .await?
+            ]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -311,20 +1009,165 @@ impl QobuzClient {
         Ok(serde_json::from_value(albums.clone())?)
     }
 
-    /// Get track by ID
-    pub async fn get_track(&self, track_id: u64) -> Result<Track> {
-        let url = endpoints::build_url(paths::TRACK_GET);
-        let response: Value = self
+    /// Get an editorial section, optionally narrowed to one genre. The API
+    /// doesn't filter every section by genre, so with a genre the section is
+    /// read in larger pages and filtered here until `limit` items are found.
+    pub async fn get_editorial(
+        &self,
+        editorial_type: EditorialType,
+        genre_id: Option<u64>,
+        limit: u32,
+    ) -> Result<Editorial> {
+        let Some(genre_id) = genre_id else {
+            return self.editorial_page(editorial_type, None, limit, 0).await;
+        };
+
+        let page_size = limit.max(EDITORIAL_GENRE_PAGE_SIZE);
+        let mut editorial = Editorial::empty(editorial_type);
+        for page in 0..EDITORIAL_GENRE_MAX_PAGES {
+            let mut items = self
+                .editorial_page(editorial_type, Some(genre_id), page_size, page * page_size)
+                .await?;
+            let fetched = items.len();
+            items.retain_genre(genre_id);
+            editorial.append(items);
+            if fetched < page_size as usize || editorial.len() >= limit as usize {
+                break;
+            }
+        }
+        editorial.truncate(limit as usize);
+        Ok(editorial)
+    }
+
+    /// One page of an editorial section
+    async fn editorial_page(
+        &self,
+        editorial_type: EditorialType,
+        genre_id: Option<u64>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Editorial> {
+        let (path, featured_type, key) = match editorial_type {
+            EditorialType::FeaturedFocus => (paths::FOCUS_LIST, None, "focus"),
+            EditorialType::AlbumOfTheWeek => (paths::ALBUM_GET_FEATURED, Some("album-of-the-week"), "albums"),
+            EditorialType::PlaylistsOfTheWeek => {
+                (paths::PLAYLIST_GET_FEATURED, Some("editor-picks"), "playlists")
+            }
+        };
+
+        let mut query = vec![
+            ("limit", limit.to_string()),
+            ("offset", offset.to_string()),
+            ("lang", self.locale().await),
+        ];
+        if let Some(featured_type) = featured_type {
+            query.push(("type", featured_type.to_string()));
+        }
+        if let Some(genre_id) = genre_id {
+            query.push(("genre_ids", genre_id.to_string()));
+        }
+
+        let request = self
             .http
-            .get(&url)
+            .get(self.url(path))
             .header("X-App-Id", self.app_id().await?)
-            .query(&[("track_id", track_id.to_string())])
-            .send()
-            .await?
+            .query(&query);
+
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
-        Ok(serde_json::from_value(response)?)
+        let items = response
+            .get(key)
+            .and_then(|c| c.get("items"))
+            .cloned()
+            .ok_or_else(|| ApiError::ApiResponse(format!("No {} in response", key)))?;
+
+        Ok(match editorial_type {
+            EditorialType::FeaturedFocus => Editorial::FeaturedFocus(serde_json::from_value(items)?),
+            EditorialType::AlbumOfTheWeek => Editorial::AlbumOfTheWeek(serde_json::from_value(items)?),
+            EditorialType::PlaylistsOfTheWeek => {
+                Editorial::PlaylistsOfTheWeek(serde_json::from_value(items)?)
+            }
+        })
+    }
+
+    /// Get track by ID. A deprecated id answers with its replacement, whose
+    /// id is returned in `id` with the requested one in `replaces`.
+    pub async fn get_track(&self, track_id: u64) -> Result<Track> {
+        let url = self.url(paths::TRACK_GET);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .query(&[("track_id", track_id.to_string())]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to get track {}: {}", track_id, response.status())));
+        }
+
+        let response: Value = response.json().await?;
+        let mut track: Track = serde_json::from_value(response)?;
+        if track.id != 0 && track.id != track_id {
+            self.record_track_replacement(track_id, track.id).await;
+            track.replaces = Some(track_id);
+        }
+        self.record_release_windows([(&track, None)]).await;
+        Ok(track)
+    }
+
+    /// Remember which tracks can't be streamed yet, so stream requests for
+    /// them fail with `NotYetReleased` instead of an opaque catalog error
+    async fn record_release_windows<'a>(
+        &self,
+        tracks: impl IntoIterator<Item = (&'a Track, Option<&'a Album>)>,
+    ) {
+        let today = chrono::Utc::now().date_naive();
+        let mut windows = self.release_windows.write().await;
+        for (track, album) in tracks {
+            let available_on = track
+                .availability(album, today)
+                .available_on
+                .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+            windows.record(track.id, available_on, today);
+        }
+    }
+
+    /// Fail when `track_id` is released on a later date. Tracks not seen in
+    /// the catalog yet are fetched first; if that fails the stream request
+    /// goes ahead and reports its own error.
+    async fn ensure_released(&self, track_id: u64) -> Result<()> {
+        let known = self.release_windows.read().await.is_known(track_id);
+        if !known {
+            if let Err(e) = self.get_track(track_id).await {
+                log::warn!("Couldn't check the release date of track {}: {}", track_id, e);
+            }
+        }
+
+        let today = chrono::Utc::now().date_naive();
+        match self.release_windows.read().await.upcoming(track_id, today) {
+            Some(date) => Err(ApiError::NotYetReleased {
+                track_id,
+                available_on: date.format("%Y-%m-%d").to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Id the catalog currently uses for `track_id` (itself unless it was replaced)
+    pub async fn canonical_track_id(&self, track_id: u64) -> u64 {
+        self.track_replacements
+            .read()
+            .await
+            .get(&track_id)
+            .copied()
+            .unwrap_or(track_id)
+    }
+
+    async fn record_track_replacement(&self, old_id: u64, new_id: u64) {
+        log::info!("Track {} was replaced by {}", old_id, new_id);
+        self.track_replacements.write().await.insert(old_id, new_id);
     }
 
     /// Get artist by ID
@@ -343,7 +1186,7 @@ impl QobuzClient {
         limit: Option<u32>,
         offset: Option<u32>,
     ) -> Result<Artist> {
-        let url = endpoints::build_url(paths::ARTIST_GET);
+        let url = self.url(paths::ARTIST_GET);
         let locale = self.locale().await;
         let mut query = vec![
             ("artist_id", artist_id.to_string()),
@@ -357,13 +1200,13 @@ impl QobuzClient {
             query.push(("offset", o.to_string()));
         }
 
-        let response: Value = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
-            .query(&query)
-            .send()
-            .await?
+            .query(&query);
+
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -378,7 +1221,7 @@ impl QobuzClient {
         limit: Option<u32>,
         offset: Option<u32>,
     ) -> Result<Artist> {
-        let url = endpoints::build_url(paths::ARTIST_GET);
+        let url = self.url(paths::ARTIST_GET);
         let locale = self.locale().await;
         let mut query = vec![
             ("artist_id", artist_id.to_string()),
@@ -394,29 +1237,109 @@ impl QobuzClient {
             query.push(("offset", o.to_string()));
         }
 
-        let response: Value = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
-            .query(&query)
-            .send()
-            .await?
+            .query(&query);
+
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
         Ok(serde_json::from_value(response)?)
     }
 
-    /// Get playlist by ID
-    pub async fn get_playlist(&self, playlist_id: u64) -> Result<Playlist> {
-        let url = endpoints::build_url(paths::PLAYLIST_GET);
+    /// Get an artist's most popular tracks
+    pub async fn get_artist_top_tracks(&self, artist_id: u64, limit: u32) -> Result<Vec<Track>> {
+        let url = self.url(paths::ARTIST_GET);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .query(&[
+                ("artist_id", artist_id.to_string()),
+                ("extra", "tracks".to_string()),
+                ("limit", limit.to_string()),
+            ]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!(
+                "Failed to get top tracks for artist {}: {}",
+                artist_id,
+                response.status()
+            )));
+        }
+
+        let response: Value = response.json().await?;
+        let tracks = response
+            .get("tracks")
+            .ok_or_else(|| ApiError::ApiResponse("No tracks in response".to_string()))?;
+        let tracks: TracksContainer = serde_json::from_value(tracks.clone())?;
+        Ok(tracks.items)
+    }
+
+    /// Get everything the artist page shows (bio, top tracks, albums, similar
+    /// artists) concurrently. Only the artist itself is required; a failed
+    /// section is left empty and reported in `errors`.
+    pub async fn get_artist_page(&self, artist_id: u64) -> Result<ArtistPage> {
+        let (artist, top_tracks, albums, similar) = tokio::join!(
+            self.get_artist(artist_id, false),
+            self.get_artist_top_tracks(artist_id, ARTIST_PAGE_TOP_TRACKS),
+            self.get_artist_with_pagination(artist_id, true, Some(ARTIST_PAGE_ALBUMS), Some(0)),
+            self.get_similar_artists(artist_id, ARTIST_PAGE_SIMILAR, 0),
+        );
+
+        let artist = artist?;
+        let mut errors = Vec::new();
+        let top_tracks = page_section(ArtistPageSection::TopTracks, top_tracks, &mut errors);
+        let albums = albums.and_then(|a| {
+            a.albums
+                .map(|albums| albums.items)
+                .ok_or_else(|| ApiError::ApiResponse("No albums in response".to_string()))
+        });
+        let albums = page_section(ArtistPageSection::Albums, albums, &mut errors);
+        let similar = page_section(ArtistPageSection::Similar, similar.map(|p| p.items), &mut errors);
+
+        for error in &errors {
+            log::warn!("Artist {} page: {:?} failed: {}", artist_id, error.section, error.message);
+        }
+
+        Ok(ArtistPage { artist, top_tracks, albums, similar, errors })
+    }
+
+    /// Get playlist by ID (first page of tracks)
+    pub async fn get_playlist(&self, playlist_id: u64) -> Result<Playlist> {
+        self.fetch_playlist(playlist_id, 0).await
+    }
+
+    /// Get a playlist with its complete track list, in playlist order
+    pub async fn get_playlist_all_tracks(&self, playlist_id: u64) -> Result<Playlist> {
+        let mut playlist = self.get_playlist(playlist_id).await?;
+        if let Some(tracks) = playlist.tracks.as_mut() {
+            while (tracks.items.len() as u32) < tracks.total {
+                let page = self.fetch_playlist(playlist_id, tracks.items.len()).await?;
+                let items = page.tracks.map(|t| t.items).unwrap_or_default();
+                if items.is_empty() {
+                    break;
+                }
+                tracks.items.extend(items);
+            }
+        }
+        Ok(playlist)
+    }
+
+    async fn fetch_playlist(&self, playlist_id: u64, offset: usize) -> Result<Playlist> {
+        let url = self.url(paths::PLAYLIST_GET);
         let mut request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .query(&[
                 ("playlist_id", playlist_id.to_string()),
-                ("limit", "500".to_string()),
+                ("limit", TRACKS_PAGE_SIZE.to_string()),
+                ("offset", offset.to_string()),
                 ("extra", "tracks".to_string()),
             ]);
 
@@ -424,38 +1347,81 @@ impl QobuzClient {
             request = request.header("X-User-Auth-Token", token);
         }
 
-        let response: Value = request.send().await?.json().await?;
+        let response: Value = self.send_authed(request).await?.json().await?;
 
         Ok(serde_json::from_value(response)?)
     }
 
     // === Authenticated endpoints ===
 
-    /// Get stream URL for a track (requires auth + signature)
-    pub async fn get_stream_url(&self, track_id: u64, quality: Quality) -> Result<StreamUrl> {
-        log::info!("Getting stream URL for track {} with quality {:?}", track_id, quality);
-        let url = endpoints::build_url(paths::TRACK_GET_FILE_URL);
-        let timestamp = get_timestamp();
+    /// Get stream URL for a track (requires auth + signature).
+    /// If the signature is rejected, the secret may have been rotated
+    /// mid-session: a fresh one is selected and the request retried once.
+    ///
+    /// Replaced track ids are requested under their canonical id; the URL's
+    /// `track_id` is the id actually streamed and `replaces` the one asked for.
+    pub async fn get_stream_url(&self, requested_id: u64, quality: Quality) -> Result<StreamUrl> {
+        log::info!("Getting stream URL for track {} with quality {:?}", requested_id, quality);
+        self.ensure_can_stream().await?;
+        let track_id = self.canonical_track_id(requested_id).await;
+        self.ensure_released(track_id).await?;
         log::debug!("Getting secret for signing...");
         let secret = self.secret().await?;
-        log::debug!("Secret obtained, signing request...");
-        let signature = sign_get_file_url(track_id, quality.id(), timestamp, &secret);
+
+        let mut stream_url = self.signed_stream_url_with_retry(track_id, quality, &secret).await?;
+        if stream_url.track_id != track_id {
+            self.record_track_replacement(track_id, stream_url.track_id).await;
+        }
+        if stream_url.track_id != requested_id {
+            stream_url.replaces = Some(requested_id);
+        }
+        Ok(stream_url)
+    }
+
+    /// `signed_stream_url`, retried once with a fresh secret if the signature is rejected
+    async fn signed_stream_url_with_retry(&self, track_id: u64, quality: Quality, secret: &str) -> Result<StreamUrl> {
+        match self.signed_stream_url(track_id, quality, secret).await {
+            Err(ApiError::InvalidAppSecret) => {
+                log::warn!("Stream signature rejected for track {}, re-selecting app secret", track_id);
+                *self.validated_secret.write().await = None;
+                let fresh = self.select_secret(Some(secret)).await?;
+                if fresh == secret {
+                    // The secret still validates, so it isn't what's wrong
+                    return Err(ApiError::InvalidAppSecret);
+                }
+                self.signed_stream_url(track_id, quality, &fresh).await
+            }
+            result => result,
+        }
+    }
+
+    /// One getFileUrl request signed with `secret`
+    async fn signed_stream_url(&self, track_id: u64, quality: Quality, secret: &str) -> Result<StreamUrl> {
+        let url = self.url(paths::TRACK_GET_FILE_URL);
+        let timestamp = get_timestamp();
+        let signature = sign_get_file_url(track_id, quality.id(), timestamp, secret);
+
+        let mut query = vec![
+            ("track_id", track_id.to_string()),
+            ("format_id", quality.id().to_string()),
+            ("intent", "stream".to_string()),
+            ("request_ts", timestamp.to_string()),
+            ("request_sig", signature),
+        ];
+        // Without the device context some zones only hand out restricted streams
+        let device_id = self.session.read().await.as_ref().and_then(|s| s.device_id.clone());
+        if let Some(device_id) = device_id {
+            query.push(("device_id", device_id));
+        }
 
         log::debug!("Sending stream URL request...");
-        let response = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&[
-                ("track_id", track_id.to_string()),
-                ("format_id", quality.id().to_string()),
-                ("intent", "stream".to_string()),
-                ("request_ts", timestamp.to_string()),
-                ("request_sig", signature),
-            ])
-            .send()
-            .await?;
+            .query(&query);
+        let response = self.send_authed(request).await?;
 
         log::info!("Stream URL response status: {}", response.status());
         match response.status() {
@@ -468,15 +1434,46 @@ impl QobuzClient {
                     .and_then(|v| serde_json::from_value(v.clone()).ok())
                     .unwrap_or_default();
 
-                Ok(StreamUrl {
-                    url: json["url"].as_str().unwrap_or("").to_string(),
-                    format_id: json["format_id"].as_u64().unwrap_or(0) as u32,
-                    mime_type: json["mime_type"].as_str().unwrap_or("").to_string(),
-                    sampling_rate: json["sampling_rate"].as_f64().unwrap_or(0.0),
-                    bit_depth: json["bit_depth"].as_u64().map(|v| v as u32),
-                    track_id,
+                // Alternative files only list what differs from the main one
+                let file = stream_urls::choose_file(&json, self.preferred_container);
+                let field = |key: &str| if file[key].is_null() { &json[key] } else { &file[key] };
+                let mime_type = field("mime_type").as_str().unwrap_or("").to_string();
+                if !std::ptr::eq(file, &json) {
+                    log::info!("Track {}: using the {} file offered alongside the main one", track_id, mime_type);
+                }
+
+                let mut stream_url = StreamUrl {
+                    url: field("url").as_str().unwrap_or("").to_string(),
+                    format_id: field("format_id").as_u64().unwrap_or(0) as u32,
+                    container: AudioContainer::from_mime(&mime_type),
+                    mime_type,
+                    sampling_rate: field("sampling_rate").as_f64().unwrap_or(0.0),
+                    bit_depth: field("bit_depth").as_u64().map(|v| v as u32),
+                    track_id: json["track_id"].as_u64().filter(|id| *id != 0).unwrap_or(track_id),
                     restrictions,
-                })
+                    downgrade: None,
+                    duration: json["duration"].as_u64().map(|v| v as u32),
+                    is_preview: false,
+                    replaces: None,
+                    quality: None,
+                    quality_label: String::new(),
+                };
+                stream_url.derive_quality();
+
+                // Previews are playable, just short: flag them instead of failing
+                stream_url.is_preview = json["sample"].as_bool().unwrap_or(false)
+                    || stream_url.has_preview_restriction();
+                if stream_url.is_preview {
+                    log::warn!("Track {} is only available as a preview", track_id);
+                } else if stream_url
+                    .restrictions
+                    .iter()
+                    .any(|r| REGION_RESTRICTIONS.contains(&r.code.as_str()))
+                {
+                    return Err(self.region_mismatch().await);
+                }
+
+                Ok(stream_url)
             }
             StatusCode::BAD_REQUEST => Err(ApiError::InvalidAppSecret),
             status => Err(ApiError::ApiResponse(format!("Unexpected status: {}", status))),
@@ -491,25 +1488,68 @@ impl QobuzClient {
     ) -> Result<StreamUrl> {
         log::info!("Getting stream URL with fallback for track {}, preferred quality: {:?}", track_id, preferred);
         let qualities = Quality::fallback_order();
-        let start_idx = qualities.iter().position(|q| *q == preferred).unwrap_or(0);
+        let mut fallback_reasons: Vec<String> = Vec::new();
+
+        // Don't ask for formats the plan doesn't include
+        let plan_max = self
+            .session
+            .read()
+            .await
+            .as_ref()
+            .and_then(|s| s.features.max_streaming_quality());
+        let first = match plan_max {
+            Some(max) if max < preferred => {
+                log::info!("Plan streams up to {:?}, capping {:?} for track {}", max, preferred, track_id);
+                fallback_reasons.push(format!("{} is not included in your plan", preferred.label()));
+                max
+            }
+            _ => preferred,
+        };
+        let start_idx = qualities.iter().position(|q| *q == first).unwrap_or(0);
 
         for quality in &qualities[start_idx..] {
             log::info!("Trying quality: {:?}", quality);
             match self.get_stream_url(track_id, *quality).await {
-                Ok(url) if !url.has_restrictions() => {
+                Ok(mut url) if !url.has_restrictions() => {
                     log::info!("Got stream URL successfully: {} (format: {})", url.url, url.mime_type);
+                    let delivered = url.delivered_quality().unwrap_or(*quality);
+                    if delivered < preferred {
+                        log::info!("Quality downgraded from {:?} to {:?} for track {}", preferred, delivered, track_id);
+                        let reason = if fallback_reasons.is_empty() {
+                            format!("{} not available for this track", preferred.label())
+                        } else {
+                            fallback_reasons.join("; ")
+                        };
+                        url.downgrade = Some(QualityDowngrade {
+                            requested: preferred,
+                            delivered,
+                            reason,
+                        });
+                    }
                     return Ok(url);
                 },
-                Ok(_) => {
+                Ok(url) => {
                     log::info!("Quality {:?} has restrictions, trying next", quality);
+                    let codes: Vec<&str> = url.restrictions.iter().map(|r| r.code.as_str()).collect();
+                    fallback_reasons.push(codes.join(","));
                     continue;
                 },
                 Err(ApiError::InvalidAppSecret) => {
                     log::error!("Invalid app secret");
                     return Err(ApiError::InvalidAppSecret);
                 },
+                Err(ApiError::NoActiveSubscription) => {
+                    return Err(ApiError::NoActiveSubscription);
+                },
+                Err(
+                    e @ (ApiError::RegionMismatch(_)
+                    | ApiError::NotYetReleased { .. }
+                    | ApiError::SessionInvalidated(_)
+                    | ApiError::AuthenticationError(_)),
+                ) => return Err(e),
                 Err(e) => {
                     log::warn!("Quality {:?} failed: {}, trying next", quality, e);
+                    fallback_reasons.push(e.to_string());
                     continue;
                 },
             }
@@ -519,64 +1559,294 @@ impl QobuzClient {
         Err(ApiError::NoQualityAvailable)
     }
 
+    /// Stream URL for playback: a previously resolved URL is reused unless it
+    /// is about to expire, in which case a freshly signed one is requested.
+    pub async fn playback_stream_url(&self, track_id: u64, quality: Quality) -> Result<StreamUrl> {
+        let now = chrono::Utc::now().timestamp();
+        if let Some(url) = self.stream_urls.read().await.get(track_id, quality, now) {
+            log::debug!("Reusing stream URL for track {}", track_id);
+            return Ok(url);
+        }
+
+        let url = self.get_stream_url_with_fallback(track_id, quality).await?;
+        self.stream_urls
+            .write()
+            .await
+            .insert(track_id, quality, url.clone(), chrono::Utc::now().timestamp());
+        Ok(url)
+    }
+
+    /// Drop the resolved URLs of a track so the next playback re-requests them
+    pub async fn invalidate_stream_url(&self, track_id: u64) {
+        self.stream_urls.write().await.remove_track(track_id);
+    }
+
+    /// Keep the resolved URLs of `track_ids` only
+    pub async fn retain_stream_urls(&self, track_ids: &HashSet<u64>) {
+        self.stream_urls.write().await.retain_tracks(track_ids);
+    }
+
+    /// Resolved URLs that are about to expire
+    pub async fn stale_stream_urls(&self) -> Vec<(u64, Quality)> {
+        self.stream_urls.read().await.stale(chrono::Utc::now().timestamp())
+    }
+
+    /// Re-request a resolved URL that is about to expire. An entry that can't
+    /// be refreshed is dropped. Returns whether it was refreshed.
+    pub async fn refresh_stream_url(&self, track_id: u64, quality: Quality) -> bool {
+        match self.get_stream_url_with_fallback(track_id, quality).await {
+            Ok(url) => {
+                self.stream_urls
+                    .write()
+                    .await
+                    .insert(track_id, quality, url, chrono::Utc::now().timestamp());
+                true
+            }
+            Err(e) => {
+                log::warn!("Failed to refresh stream URL for track {}: {}", track_id, e);
+                self.stream_urls.write().await.remove_track(track_id);
+                false
+            }
+        }
+    }
+
+    /// Size, type and range support of a track's stream, from a one-byte
+    /// range request rather than a download. A URL the CDN reports as
+    /// expired is re-resolved once.
+    pub async fn probe_stream(&self, track_id: u64, quality: Quality) -> Result<StreamProbe> {
+        let first_byte = |url: String| self.http.get(url).header(reqwest::header::RANGE, "bytes=0-0").send();
+
+        let url = self.playback_stream_url(track_id, quality).await?;
+        let mut response = first_byte(url.url).await?;
+        if stream_urls::is_expired_status(response.status()) {
+            log::info!("Stream URL of track {} expired, re-resolving before probing", track_id);
+            self.invalidate_stream_url(track_id).await;
+            let url = self.playback_stream_url(track_id, quality).await?;
+            response = first_byte(url.url).await?;
+        }
+
+        // The body (the whole file, if the server ignored the range) is
+        // dropped unread
+        Ok(stream_urls::probe_from_response(track_id, quality, &response))
+    }
+
+    /// Re-validate the app secret and retry one stream request with a fresh
+    /// signature. Tells a stale secret or signature apart from a genuine
+    /// restriction when a single track won't play. With `force_new_secret`
+    /// the cached secret is re-selected even if it still validates; it is
+    /// tested either way, so `secret_was_stale` always reports whether it
+    /// still worked.
+    pub async fn retry_stream(&self, track_id: u64, quality: Quality, force_new_secret: bool) -> StreamRetryOutcome {
+        log::info!("Retrying stream request for track {} ({:?}), force_new_secret: {}", track_id, quality, force_new_secret);
+        let previous = self.validated_secret.read().await.clone();
+
+        let mut secret_was_stale = false;
+        let result = async {
+            secret_was_stale = match previous.as_deref() {
+                Some(secret) => !self.test_secret(secret).await?,
+                None => false,
+            };
+            if secret_was_stale || (force_new_secret && previous.is_some()) {
+                *self.validated_secret.write().await = None;
+                self.select_secret(previous.as_deref()).await?;
+            }
+            // Signed with a new timestamp
+            self.get_stream_url(track_id, quality).await
+        }
+        .await;
+
+        let current = self.validated_secret.read().await.clone();
+        // Resolved URLs of the track may come from the stale signature
+        self.invalidate_stream_url(track_id).await;
+
+        let mut outcome = StreamRetryOutcome {
+            track_id,
+            quality,
+            secret_was_stale,
+            secret_changed: current.is_some() && current != previous,
+            stream_url: None,
+            restrictions: Vec::new(),
+            error: None,
+        };
+        match result {
+            Ok(url) => {
+                outcome.restrictions = url.restrictions.iter().map(|r| r.code.clone()).collect();
+                outcome.stream_url = Some(url);
+            }
+            Err(e) => outcome.error = Some(e.to_string()),
+        }
+        log::info!(
+            "Stream retry for track {}: stale secret {}, changed {}, error {:?}",
+            track_id, outcome.secret_was_stale, outcome.secret_changed, outcome.error
+        );
+        outcome
+    }
+
+    /// Qualities the stream endpoint actually serves for this track, highest
+    /// first. A quality counts only when it comes back unrestricted, in that
+    /// format and as the full track (not a preview).
+    pub async fn probe_available_qualities(&self, track_id: u64) -> Result<Vec<Quality>> {
+        let results: Vec<(Quality, Result<StreamUrl>)> = stream::iter(Quality::fallback_order().iter().copied().enumerate())
+            .map(|(index, quality): (usize, Quality)| async move {
+                tokio::time::sleep(QUALITY_PROBE_SPACING * index as u32).await;
+                (quality, self.get_stream_url(track_id, quality).await)
+            })
+            .buffer_unordered(QUALITY_PROBE_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut available = Vec::new();
+        for (quality, result) in results {
+            match result {
+                Ok(url) => {
+                    if !url.has_restrictions()
+                        && !url.is_preview
+                        && url.delivered_quality().unwrap_or(quality) == quality
+                    {
+                        available.push(quality);
+                    }
+                }
+                Err(
+                    e @ (ApiError::InvalidAppSecret
+                    | ApiError::NoActiveSubscription
+                    | ApiError::NotYetReleased { .. }
+                    | ApiError::SessionInvalidated(_)),
+                ) => return Err(e),
+                Err(e) => log::debug!("Quality probe {:?} failed for track {}: {}", quality, track_id, e),
+            }
+        }
+
+        available.sort_by(|a, b| b.cmp(a));
+        log::info!("Track {} streams at {:?}", track_id, available);
+        Ok(available)
+    }
+
     /// Get user favorites (requires auth + signature)
     pub async fn get_favorites(&self, fav_type: &str, limit: u32, offset: u32) -> Result<Value> {
-        let url = endpoints::build_url(paths::FAVORITE_GET_USER_FAVORITES);
+        self.get_favorites_sorted(fav_type, limit, offset, FavoritesSort::default()).await
+    }
+
+    /// Get user favorites in the given order (requires auth + signature)
+    pub async fn get_favorites_sorted(
+        &self,
+        fav_type: &str,
+        limit: u32,
+        offset: u32,
+        sort: FavoritesSort,
+    ) -> Result<Value> {
+        let url = self.url(paths::FAVORITE_GET_USER_FAVORITES);
         let timestamp = get_timestamp();
         let secret = self.secret().await?;
         let signature = sign_get_favorites(timestamp, &secret);
 
-        let response: Value = self
+        let mut query = vec![
+            ("type", fav_type.to_string()),
+            ("limit", limit.to_string()),
+            ("offset", offset.to_string()),
+            ("request_ts", timestamp.to_string()),
+            ("request_sig", signature),
+        ];
+        if let Some((sort_by, order)) = sort.query_params() {
+            query.push(("sort", sort_by.to_string()));
+            query.push(("order", order.to_string()));
+        }
+
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&[
-                ("type", fav_type),
-                ("limit", &limit.to_string()),
-                ("offset", &offset.to_string()),
-                ("request_ts", &timestamp.to_string()),
-                ("request_sig", &signature),
-            ])
-            .send()
-            .await?
-            .json()
-            .await?;
+            .query(&query);
+        let mut response: Value = self.send_authed(request).await?.json().await?;
 
+        sort.apply(&mut response, fav_type);
         Ok(response)
     }
 
+    /// Get the user's purchased albums or tracks (requires auth + signature)
+    pub async fn get_purchases(&self, purchase_type: PurchaseType, limit: u32, offset: u32) -> Result<Purchases> {
+        let url = self.url(paths::PURCHASE_GET_USER_PURCHASES);
+        let timestamp = get_timestamp();
+        let secret = self.secret().await?;
+        let signature = sign_get_purchases(timestamp, &secret);
+
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?)
+            .query(&[
+                ("type", purchase_type.as_str().to_string()),
+                ("limit", limit.to_string()),
+                ("offset", offset.to_string()),
+                ("request_ts", timestamp.to_string()),
+                ("request_sig", signature),
+            ]);
+        let response = self.send_authed(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to get purchases: {}", response.status())));
+        }
+
+        let response: Value = response.json().await?;
+        Ok(serde_json::from_value(response)?)
+    }
+
     /// Get user's playlists
     pub async fn get_user_playlists(&self) -> Result<Vec<Playlist>> {
-        let url = endpoints::build_url(paths::PLAYLIST_GET_USER_PLAYLISTS);
-        let response: Value = self
+        let url = self.url(paths::PLAYLIST_GET_USER_PLAYLISTS);
+        let user_id = self.user_id().await?;
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .send()
-            .await?
-            .json()
-            .await?;
+            .query(&[("filter", "owner,subscriber"), ("limit", "500")]);
+        let response: Value = self.send_authed(request).await?.json().await?;
 
-        let playlists = response
-            .get("playlists")
-            .and_then(|p| p.get("items"))
-            .ok_or_else(|| ApiError::ApiResponse("No playlists in response".to_string()))?;
+        parse_user_playlists(&response, user_id)
+    }
 
-        Ok(serde_json::from_value(playlists.clone())?)
+    /// Follow another user's public playlist
+    pub async fn subscribe_playlist(&self, playlist_id: u64) -> Result<()> {
+        self.set_playlist_subscription(paths::PLAYLIST_SUBSCRIBE, playlist_id).await
+    }
+
+    /// Stop following a playlist
+    pub async fn unsubscribe_playlist(&self, playlist_id: u64) -> Result<()> {
+        self.set_playlist_subscription(paths::PLAYLIST_UNSUBSCRIBE, playlist_id).await
+    }
+
+    async fn set_playlist_subscription(&self, endpoint: &str, playlist_id: u64) -> Result<()> {
+        let url = self.url(endpoint);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?)
+            .query(&[("playlist_id", playlist_id.to_string())]);
+        let response = self.send_authed(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!(
+                "Failed to update subscription to playlist {}: {}",
+                playlist_id,
+                response.status()
+            )));
+        }
+
+        Ok(())
     }
 
     /// Search playlists
     pub async fn search_playlists(&self, query: &str, limit: u32) -> Result<SearchResultsPage<Playlist>> {
-        let url = endpoints::build_url(paths::PLAYLIST_SEARCH);
-        let response: Value = self
+        let url = self.url(paths::PLAYLIST_SEARCH);
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
-            .query(&[("query", query), ("limit", &limit.to_string())])
-            .send()
-            .await?
+            .query(&[("query", query), ("limit", &limit.to_string())]);
+        let response: Value = self.send(request).await?
             .json()
             .await?;
 
@@ -589,7 +1859,7 @@ impl QobuzClient {
 
     /// Create a new playlist
     pub async fn create_playlist(&self, name: &str, description: Option<&str>, is_public: bool) -> Result<Playlist> {
-        let url = endpoints::build_url(paths::PLAYLIST_CREATE);
+        let url = self.url(paths::PLAYLIST_CREATE);
 
         let mut params = vec![
             ("name", name.to_string()),
@@ -599,76 +1869,142 @@ impl QobuzClient {
             params.push(("description", desc.to_string()));
         }
 
-        let response: Playlist = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&params)
-            .send()
-            .await?
-            .json()
-            .await?;
+            .query(&params);
+        let response: Playlist = self.send_authed(request).await?.json().await?;
 
         Ok(response)
     }
 
     /// Delete a playlist
     pub async fn delete_playlist(&self, playlist_id: u64) -> Result<()> {
-        let url = endpoints::build_url(paths::PLAYLIST_DELETE);
+        let url = self.url(paths::PLAYLIST_DELETE);
 
-        self.http
+        let request = self
+            .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&[("playlist_id", playlist_id.to_string())])
-            .send()
-            .await?;
+            .query(&[("playlist_id", playlist_id.to_string())]);
+        self.send_authed(request).await?;
 
         Ok(())
     }
 
     /// Add tracks to a playlist
     pub async fn add_tracks_to_playlist(&self, playlist_id: u64, track_ids: &[u64]) -> Result<()> {
-        let url = endpoints::build_url(paths::PLAYLIST_ADD_TRACKS);
+        let url = self.url(paths::PLAYLIST_ADD_TRACKS);
         let track_ids_str = track_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
 
-        self.http
+        let request = self
+            .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
             .query(&[
                 ("playlist_id", playlist_id.to_string()),
                 ("track_ids", track_ids_str),
-            ])
-            .send()
-            .await?;
+            ]);
+        let response = self.send_authed(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!(
+                "Failed to add tracks to playlist {}: {}",
+                playlist_id,
+                response.status()
+            )));
+        }
 
         Ok(())
     }
 
     /// Remove tracks from a playlist
     pub async fn remove_tracks_from_playlist(&self, playlist_id: u64, playlist_track_ids: &[u64]) -> Result<()> {
-        let url = endpoints::build_url(paths::PLAYLIST_DELETE_TRACKS);
+        let url = self.url(paths::PLAYLIST_DELETE_TRACKS);
         let track_ids_str = playlist_track_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
 
-        self.http
+        let request = self
+            .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
             .query(&[
                 ("playlist_id", playlist_id.to_string()),
                 ("playlist_track_ids", track_ids_str),
-            ])
-            .send()
-            .await?;
+            ]);
+        let response = self.send_authed(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!(
+                "Failed to remove tracks from playlist {}: {}",
+                playlist_id,
+                response.status()
+            )));
+        }
 
         Ok(())
     }
 
+    /// Move a track from one playlist to another.
+    ///
+    /// The track is added to the destination first and only then removed from
+    /// the source. If the removal fails, the entry just added to the destination
+    /// is removed again so the track never ends up in both playlists.
+    pub async fn move_track_between_playlists(
+        &self,
+        track_id: u64,
+        from_playlist_id: u64,
+        to_playlist_id: u64,
+    ) -> Result<PlaylistTrackMove> {
+        if from_playlist_id == to_playlist_id {
+            return Err(ApiError::ApiResponse("Source and destination playlists are the same".to_string()));
+        }
+
+        let source = self.get_playlist(from_playlist_id).await?;
+        let source_entries = playlist_entries_for_track(&source, track_id);
+        if source_entries.is_empty() {
+            return Err(ApiError::ApiResponse(format!(
+                "Track {} is not in playlist {}",
+                track_id, from_playlist_id
+            )));
+        }
+
+        // Snapshot existing destination entries so a rollback only removes ours
+        let existing_entries = playlist_entries_for_track(&self.get_playlist(to_playlist_id).await?, track_id);
+
+        self.add_tracks_to_playlist(to_playlist_id, &[track_id]).await?;
+
+        if let Err(e) = self.remove_tracks_from_playlist(from_playlist_id, &source_entries).await {
+            log::warn!(
+                "Removing track {} from playlist {} failed, rolling back add to {}: {}",
+                track_id, from_playlist_id, to_playlist_id, e
+            );
+            let destination = self.get_playlist(to_playlist_id).await?;
+            let added: Vec<u64> = playlist_entries_for_track(&destination, track_id)
+                .into_iter()
+                .filter(|id| !existing_entries.contains(id))
+                .collect();
+            if !added.is_empty() {
+                self.remove_tracks_from_playlist(to_playlist_id, &added).await?;
+            }
+            return Err(e);
+        }
+
+        let from = self.get_playlist(from_playlist_id).await?;
+        let to = self.get_playlist(to_playlist_id).await?;
+        Ok(PlaylistTrackMove {
+            from_tracks_count: from.tracks_count,
+            to_tracks_count: to.tracks_count,
+        })
+    }
+
     /// Update playlist metadata
     pub async fn update_playlist(&self, playlist_id: u64, name: Option<&str>, description: Option<&str>, is_public: Option<bool>) -> Result<Playlist> {
-        let url = endpoints::build_url(paths::PLAYLIST_UPDATE);
+        let url = self.url(paths::PLAYLIST_UPDATE);
 
         let mut params = vec![("playlist_id", playlist_id.to_string())];
         if let Some(n) = name {
@@ -681,60 +2017,144 @@ impl QobuzClient {
             params.push(("is_public", p.to_string()));
         }
 
-        let response: Playlist = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&params)
-            .send()
-            .await?
-            .json()
-            .await?;
+            .query(&params);
+        let response: Playlist = self.send_authed(request).await?.json().await?;
 
         Ok(response)
     }
 
     /// Add item to favorites
     pub async fn add_favorite(&self, fav_type: &str, item_id: &str) -> Result<()> {
-        let url = endpoints::build_url(paths::FAVORITE_CREATE);
+        self.add_favorites(fav_type, &[item_id.to_string()]).await
+    }
+
+    /// Add several items of the same type to favorites in one request
+    pub async fn add_favorites(&self, fav_type: &str, item_ids: &[String]) -> Result<()> {
+        if item_ids.is_empty() {
+            return Ok(());
+        }
+
+        let url = self.url(paths::FAVORITE_CREATE);
         let type_key = format!("{}_ids", fav_type); // album_ids, track_ids, artist_ids
 
-        let response = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&[(&type_key, item_id)])
-            .send()
-            .await?;
+            .query(&[(&type_key, item_ids.join(","))]);
+        let response = self.send_authed(request).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to add favorite: {}", response.status())));
+        }
+
+        if let Some(known) = self.favorite_ids.write().await.get_mut(&favorites_key(fav_type)) {
+            known.extend(item_ids.iter().cloned());
+        }
+        Ok(())
+    }
+
+    /// Get the ids of all the user's favorites of one type ("albums", "tracks", "artists")
+    pub async fn get_favorite_ids(&self, fav_type: &str) -> Result<HashSet<String>> {
+        let url = self.url(paths::FAVORITE_GET_USER_FAVORITE_IDS);
+        let request = self
+            .http
+            .get(&url)
+            .header("X-App-Id", self.app_id().await?)
+            .header("X-User-Auth-Token", self.auth_token().await?);
+        let response: Value = self.send_authed(request).await?.json().await?;
+
+        let ids: HashSet<String> = response
+            .get(fav_type)
+            .and_then(|v| v.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|id| match id {
+                        Value::String(s) => Some(s.clone()),
+                        Value::Number(n) => Some(n.to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.favorite_ids
+            .write()
+            .await
+            .insert(favorites_key(fav_type), ids.clone());
+        Ok(ids)
+    }
+
+    /// Replace the known favorite ids of one type, e.g. from a local sync
+    pub async fn seed_favorite_ids(&self, fav_type: &str, ids: HashSet<String>) {
+        self.favorite_ids.write().await.insert(favorites_key(fav_type), ids);
+    }
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(ApiError::ApiResponse(format!("Failed to add favorite: {}", response.status())))
+    /// Whether each of `item_ids` is a favorite, answered from the known
+    /// favorite ids. A type not known yet costs one getUserFavoriteIds request.
+    pub async fn favorites_status(&self, fav_type: &str, item_ids: &[String]) -> Result<HashMap<String, bool>> {
+        let key = favorites_key(fav_type);
+        let cached = self.favorite_ids.read().await.get(&key).cloned();
+        let known = match cached {
+            Some(known) => known,
+            None => self.get_favorite_ids(&key).await?,
+        };
+
+        Ok(item_ids
+            .iter()
+            .map(|id| (id.clone(), known.contains(id)))
+            .collect())
+    }
+
+    /// Favorite every streamable track of an album that isn't already a favorite
+    pub async fn favorite_album_tracks(&self, album_id: &str) -> Result<AlbumTracksFavorited> {
+        let album = self.get_album(album_id).await?;
+        let favorite_ids = self.get_favorite_ids("tracks").await?;
+
+        let mut result = AlbumTracksFavorited::default();
+        for track in album.tracks.map(|t| t.items).unwrap_or_default() {
+            if !track.streamable {
+                result.unavailable += 1;
+            } else if favorite_ids.contains(&track.id.to_string()) {
+                result.already_favorited += 1;
+            } else {
+                result.added.push(track.id);
+            }
         }
+
+        let ids: Vec<String> = result.added.iter().map(|id| id.to_string()).collect();
+        self.add_favorites("track", &ids).await?;
+        Ok(result)
     }
 
     /// Remove item from favorites
     pub async fn remove_favorite(&self, fav_type: &str, item_id: &str) -> Result<()> {
-        let url = endpoints::build_url(paths::FAVORITE_DELETE);
+        let url = self.url(paths::FAVORITE_DELETE);
         let type_key = format!("{}_ids", fav_type);
 
-        let response = self
+        let request = self
             .http
             .get(&url)
             .header("X-App-Id", self.app_id().await?)
             .header("X-User-Auth-Token", self.auth_token().await?)
-            .query(&[(&type_key, item_id)])
-            .send()
-            .await?;
+            .query(&[(&type_key, item_id)]);
+        let response = self.send_authed(request).await?;
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(ApiError::ApiResponse(format!("Failed to remove favorite: {}", response.status())))
+        if !response.status().is_success() {
+            return Err(ApiError::ApiResponse(format!("Failed to remove favorite: {}", response.status())));
         }
+
+        if let Some(known) = self.favorite_ids.write().await.get_mut(&favorites_key(fav_type)) {
+            known.remove(item_id);
+        }
+        Ok(())
     }
 }
 
@@ -743,3 +2163,1677 @@ impl Default for QobuzClient {
         Self::new().expect("Failed to create client")
     }
 }
+
+/// Qobuz's error for an unknown or retired app ("Invalid or missing app_id
+/// parameter"). Parameter names aren't localized, so the match is on those.
+fn is_app_id_rejection(message: &str) -> bool {
+    message.contains("app_id")
+}
+
+/// Error for a 404 from the device endpoints, whose paths are unconfirmed
+fn device_management_unavailable() -> ApiError {
+    ApiError::ApiResponse("Device management isn't available: the API has no device endpoint".to_string())
+}
+
+/// Favorite ids key: add/remove take "track", listings "tracks"
+fn favorites_key(fav_type: &str) -> String {
+    if fav_type.ends_with('s') {
+        fav_type.to_string()
+    } else {
+        format!("{}s", fav_type)
+    }
+}
+
+/// Playlist entry ids (`playlist_track_id`) holding the given track
+fn playlist_entries_for_track(playlist: &Playlist, track_id: u64) -> Vec<u64> {
+    playlist
+        .tracks
+        .as_ref()
+        .map(|tracks| {
+            tracks
+                .items
+                .iter()
+                .filter(|t| t.id == track_id)
+                .filter_map(|t| t.playlist_track_id)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse a getUserPlaylists response (owned and followed playlists),
+/// flagging the ones owned by `user_id`
+fn parse_user_playlists(response: &Value, user_id: u64) -> Result<Vec<Playlist>> {
+    let playlists = response
+        .get("playlists")
+        .and_then(|p| p.get("items"))
+        .ok_or_else(|| ApiError::ApiResponse("No playlists in response".to_string()))?;
+
+    let mut playlists: Vec<Playlist> = serde_json::from_value(playlists.clone())?;
+    for playlist in &mut playlists {
+        playlist.is_owner = playlist.owner.id == user_id;
+    }
+    Ok(playlists)
+}
+
+/// Unwrap an optional artist page section, recording the failure
+fn page_section<T>(
+    section: ArtistPageSection,
+    result: Result<Vec<T>>,
+    errors: &mut Vec<ArtistPageError>,
+) -> Vec<T> {
+    result.unwrap_or_else(|e| {
+        errors.push(ArtistPageError { section, message: e.to_string() });
+        Vec::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::region::RegionFault;
+    use crate::api::stream_urls;
+    use crate::api::test_support::{logged_in_client, mock_client, mount_secret_probe, test_session, test_tokens};
+    use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_bundle_with_two_app_ids_falls_back_to_accepted_one() {
+        let server = MockServer::start().await;
+        let bundle = concat!(
+            r#"development:{api:{appId:"111111111"}},"#,
+            r#"production:{api:{appId:"222222222",appSecret:"0123456789abcdef0123456789abcdef"}}"#
+        );
+        Mock::given(method("GET"))
+            .and(path("/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<html><script src="/resources/7.0.1-b001/bundle.js"></script></html>"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/resources/7.0.1-b001/bundle.js"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(bundle))
+            .mount(&server)
+            .await;
+        // The production app_id has been retired
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(header("X-App-Id", "222222222"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "status": "error",
+                "code": 400,
+                "message": "Invalid or missing app_id parameter (MissingParameter)"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(header("X-App-Id", "111111111"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 5966783 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .build()
+            .unwrap();
+        client.init().await.unwrap();
+
+        assert_eq!(client.app_id().await.unwrap(), "111111111");
+        let diagnostics = client.api_diagnostics().await;
+        assert_eq!(diagnostics.app_id.as_deref(), Some("111111111"));
+        // Production is tried first even though it appears later in the bundle
+        assert_eq!(diagnostics.app_id_candidates, vec!["222222222".to_string(), "111111111".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_app_id_is_kept_when_the_check_fails_for_other_reasons() {
+        let server = MockServer::start().await;
+        let bundle = concat!(
+            r#"development:{api:{appId:"111111111"}},"#,
+            r#"production:{api:{appId:"222222222",appSecret:"0123456789abcdef0123456789abcdef"}}"#
+        );
+        Mock::given(method("GET"))
+            .and(path("/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<html><script src="/resources/7.0.1-b001/bundle.js"></script></html>"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/resources/7.0.1-b001/bundle.js"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(bundle))
+            .mount(&server)
+            .await;
+        // Past the app check, the catalog wants a signed-in user
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(header("X-App-Id", "222222222"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "status": "error",
+                "code": 401,
+                "message": "User authentication is required."
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .build()
+            .unwrap();
+        client.init().await.unwrap();
+        assert_eq!(client.app_id().await.unwrap(), "222222222");
+
+        // An unreachable API skips the check instead of failing init
+        let client = QobuzClient::builder()
+            .api_base_url("http://127.0.0.1:1")
+            .bundle_base_url(server.uri())
+            .build()
+            .unwrap();
+        client.init().await.unwrap();
+        assert_eq!(client.app_id().await.unwrap(), "222222222");
+    }
+
+    #[tokio::test]
+    async fn test_get_albums_batches_and_reports_failures() {
+        let server = MockServer::start().await;
+
+        for id in ["a1", "a2"] {
+            Mock::given(method("GET"))
+                .and(path(paths::ALBUM_GET))
+                .and(query_param("album_id", id))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": id,
+                    "title": format!("Album {}", id)
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .and(query_param("album_id", "missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "status": "error",
+                "code": 404,
+                "message": "No result matching given argument"
+            })))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let ids: Vec<String> = ["a1", "missing", "a2", "a1"].iter().map(|s| s.to_string()).collect();
+        let results = client.get_albums(&ids).await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results["a1"].as_ref().unwrap().title, "Album a1");
+        assert_eq!(results["a2"].as_ref().unwrap().title, "Album a2");
+        assert!(results["missing"].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_favorite_album_tracks_skips_existing_and_unavailable() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .and(query_param("album_id", "alb"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "alb",
+                "title": "Album",
+                "tracks": { "total": 4, "items": [
+                    { "id": 1, "track_number": 1, "streamable": true },
+                    { "id": 2, "track_number": 2, "streamable": true },
+                    { "id": 3, "track_number": 3, "streamable": false },
+                    { "id": 4, "track_number": 4, "streamable": true }
+                ]}
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITE_IDS))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": [], "tracks": [2, 99], "artists": []
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_CREATE))
+            .and(query_param("track_ids", "1,4"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "status": "success" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let result = client.favorite_album_tracks("alb").await.unwrap();
+        assert_eq!(
+            result,
+            AlbumTracksFavorited {
+                added: vec![1, 4],
+                already_favorited: 1,
+                unavailable: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_favorites_status_from_seeded_ids_and_api_fallback() {
+        let server = MockServer::start().await;
+
+        // Only albums, which were never seeded, are fetched
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITE_IDS))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": ["alb1"], "tracks": [99], "artists": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_DELETE))
+            .and(query_param("track_ids", "3"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "status": "success" })))
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+        client
+            .seed_favorite_ids("tracks", ["1", "3"].iter().map(|id| id.to_string()).collect())
+            .await;
+
+        let ids: Vec<String> = ["1", "2", "3"].iter().map(|id| id.to_string()).collect();
+        let status = client.favorites_status("track", &ids).await.unwrap();
+        assert_eq!(
+            status,
+            HashMap::from([("1".to_string(), true), ("2".to_string(), false), ("3".to_string(), true)])
+        );
+
+        // A confirmed removal shows up without refetching
+        client.remove_favorite("track", "3").await.unwrap();
+        let status = client.favorites_status("tracks", &ids).await.unwrap();
+        assert_eq!(status["3"], false);
+
+        let albums = client
+            .favorites_status("albums", &["alb1".to_string(), "alb2".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(albums, HashMap::from([("alb1".to_string(), true), ("alb2".to_string(), false)]));
+    }
+
+    #[tokio::test]
+    async fn test_login_and_search_against_mock_server() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::USER_LOGIN))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "user_auth_token": "token-abc",
+                "user": {
+                    "id": 42,
+                    "email": "user@example.com",
+                    "display_name": "Test User",
+                    "credential": { "parameters": { "short_label": "Studio" } }
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_SEARCH))
+            .and(query_param("query", "miles"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": {
+                    "items": [{ "id": "kind-of-blue", "title": "Kind of Blue" }],
+                    "total": 1,
+                    "offset": 0,
+                    "limit": 20
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+
+        let session = client.login("user@example.com", "secret").await.unwrap();
+        assert_eq!(session.user_auth_token, "token-abc");
+        assert_eq!(session.subscription_label, "Studio");
+        assert!(session.subscription.active);
+        assert!(client.is_logged_in().await);
+
+        let results = client.search_albums("miles", 20, 0).await.unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.items[0].title, "Kind of Blue");
+    }
+
+    #[tokio::test]
+    async fn test_login_with_token_validates_without_password_login() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::USER_GET))
+            .and(header("X-User-Auth-Token", "good-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 42,
+                "email": "user@example.com",
+                "display_name": "Test User",
+                "credential": { "parameters": { "short_label": "Studio" } }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_GET))
+            .and(header("X-User-Auth-Token", "expired-token"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_LOGIN))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+
+        let err = client.login_with_token("expired-token").await.unwrap_err();
+        assert!(matches!(err, ApiError::AuthenticationError(_)), "{:?}", err);
+        assert!(!client.is_logged_in().await);
+
+        let session = client.login_with_token("good-token").await.unwrap();
+        assert_eq!(session.user_auth_token, "good-token");
+        assert_eq!(session.user_id, 42);
+        assert_eq!(session.display_name, "Test User");
+        assert!(session.subscription.active);
+        assert_eq!(client.auth_token().await.unwrap(), "good-token");
+    }
+
+    #[tokio::test]
+    async fn test_session_invalidated_elsewhere_is_reported_apart_from_expiry() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_GET))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 42 })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET_USER_PLAYLISTS))
+            .and(header("X-User-Auth-Token", "kicked-token"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "status": "error",
+                "code": 401,
+                "reason": "session_invalidated",
+                // Translated with the API locale
+                "message": "Votre session a été invalidée : le compte est utilisé sur un autre appareil"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET_USER_PLAYLISTS))
+            .and(header("X-User-Auth-Token", "stale-token"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "status": "error",
+                "code": 401,
+                "message": "User authentication is required."
+            })))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let mut invalidated = client.subscribe_session_invalidated();
+
+        client.login_with_token("kicked-token").await.unwrap();
+        let err = client.get_user_playlists().await.unwrap_err();
+        match err {
+            ApiError::SessionInvalidated(reason) => assert!(reason.contains("autre appareil"), "{}", reason),
+            other => panic!("expected SessionInvalidated, got {:?}", other),
+        }
+        let invalidation = invalidated.try_recv().unwrap();
+        assert_eq!(invalidation.user_id, 42);
+        assert!(invalidation.reason.contains("autre appareil"));
+        assert!(!client.is_logged_in().await);
+
+        // An expired token stays an authentication error and isn't broadcast
+        client.login_with_token("stale-token").await.unwrap();
+        let err = client.get_user_playlists().await.unwrap_err();
+        assert!(matches!(err, ApiError::AuthenticationError(_)), "{:?}", err);
+        assert!(invalidated.try_recv().is_err());
+        assert!(client.is_logged_in().await);
+    }
+
+    struct TestRoute {
+        order: Vec<String>,
+        attempts: std::sync::Mutex<Vec<(String, bool)>>,
+    }
+
+    impl EndpointRoute for TestRoute {
+        fn try_order(&self) -> Vec<String> {
+            self.order.clone()
+        }
+
+        fn record(&self, url: &str, success: bool, _latency_ms: Option<u32>) {
+            self.attempts.lock().unwrap().push((url.to_string(), success));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoint_falls_back_to_the_next() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(query_param("track_id", "5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 5, "title": "Up" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Nothing listens on port 1
+        let down = "http://127.0.0.1:1".to_string();
+        let route = Arc::new(TestRoute {
+            order: vec![down.clone(), server.uri()],
+            attempts: Default::default(),
+        });
+        let mut client = mock_client(&server);
+        client.set_endpoint_route(route.clone());
+
+        assert_eq!(client.get_track(5).await.unwrap().title, "Up");
+        assert_eq!(*route.attempts.lock().unwrap(), vec![(down, false), (server.uri(), true)]);
+    }
+
+    #[tokio::test]
+    async fn test_server_error_is_recorded_as_a_failed_attempt() {
+        let failing = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(2)
+            .mount(&failing)
+            .await;
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 5, "title": "Up" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let route = Arc::new(TestRoute {
+            order: vec![failing.uri(), server.uri()],
+            attempts: Default::default(),
+        });
+        let mut client = mock_client(&server);
+        client.set_endpoint_route(route.clone());
+
+        assert_eq!(client.get_track(5).await.unwrap().title, "Up");
+        assert_eq!(*route.attempts.lock().unwrap(), vec![(failing.uri(), false), (server.uri(), true)]);
+
+        // With nowhere else to go, the caller sees the server's status
+        let route = Arc::new(TestRoute {
+            order: vec![failing.uri()],
+            attempts: Default::default(),
+        });
+        client.set_endpoint_route(route.clone());
+        let err = client.get_track(5).await.unwrap_err();
+        assert!(err.to_string().contains("503"), "{}", err);
+        assert_eq!(*route.attempts.lock().unwrap(), vec![(failing.uri(), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_post_events() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(paths::TRACK_REPORT_STREAMING_END))
+            .and(header("X-User-Auth-Token", "token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "status": "success" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = mock_client(&server);
+        client.set_session(test_session()).await;
+
+        client.report_stream_end(77, 95).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body = String::from_utf8(requests[0].body.clone()).unwrap();
+        let events = urlencoding::decode(body.strip_prefix("events=").unwrap()).unwrap();
+        let events: Value = serde_json::from_str(&events).unwrap();
+        assert_eq!(events[0]["track_id"], 77);
+        assert_eq!(events[0]["duration"], 95);
+        assert_eq!(events[0]["user_id"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_account_devices_are_listed_and_revoked_by_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_GET_DEVICES))
+            .and(header("X-User-Auth-Token", "token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "devices": {
+                    "items": [
+                        { "id": 501, "name": "Living room", "platform": "android", "last_used_at": 1760000000 },
+                        { "id": "502", "name": "Laptop", "device_manufacturer_id": "abc123" },
+                        { "name": "No id, ignored" }
+                    ]
+                }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_DELETE_DEVICE))
+            .and(header("X-App-Id", "123456789"))
+            .and(header("X-User-Auth-Token", "token"))
+            .and(query_param("device_id", "501"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "status": "success" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let err = client.get_account_devices().await.unwrap_err();
+        assert!(matches!(err, ApiError::AuthenticationError(_)), "{:?}", err);
+
+        client.set_session(UserSession { device_id: Some("502".to_string()), ..test_session() }).await;
+
+        let devices = client.get_account_devices().await.unwrap();
+        assert_eq!(
+            devices,
+            vec![
+                DeviceInfo {
+                    id: "501".to_string(),
+                    name: "Living room".to_string(),
+                    manufacturer_id: None,
+                    platform: Some("android".to_string()),
+                    last_used_at: Some(1760000000),
+                    current: false,
+                },
+                DeviceInfo {
+                    id: "502".to_string(),
+                    name: "Laptop".to_string(),
+                    manufacturer_id: Some("abc123".to_string()),
+                    platform: None,
+                    last_used_at: None,
+                    current: true,
+                },
+            ]
+        );
+
+        client.revoke_device("501").await.unwrap();
+        // The current device is refused before anything is sent
+        let err = client.revoke_device("502").await.unwrap_err();
+        assert!(err.to_string().contains("log out"), "{}", err);
+
+        // Any other response shape is an error, not an empty list
+        assert!(parse_devices(&serde_json::json!({ "devices": [] }), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_device_endpoints_report_device_management_unavailable() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(&server).await;
+
+        for err in [
+            client.get_account_devices().await.unwrap_err(),
+            client.revoke_device("501").await.unwrap_err(),
+        ] {
+            assert!(err.to_string().contains("Device management isn't available"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_move_track_rolls_back_destination_when_remove_fails() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET))
+            .and(query_param("playlist_id", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 1, "name": "Source", "tracks_count": 1,
+                "tracks": { "items": [{ "id": 55, "playlist_track_id": 9001 }], "total": 1 }
+            })))
+            .mount(&server)
+            .await;
+
+        // Destination already holds track 55 once; the snapshot must keep that entry
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET))
+            .and(query_param("playlist_id", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 2, "name": "Destination", "tracks_count": 1,
+                "tracks": { "items": [{ "id": 55, "playlist_track_id": 8000 }], "total": 1 }
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET))
+            .and(query_param("playlist_id", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 2, "name": "Destination", "tracks_count": 2,
+                "tracks": {
+                    "items": [
+                        { "id": 55, "playlist_track_id": 8000 },
+                        { "id": 55, "playlist_track_id": 9100 }
+                    ],
+                    "total": 2
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_ADD_TRACKS))
+            .and(query_param("playlist_id", "2"))
+            .and(query_param("track_ids", "55"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 2 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_DELETE_TRACKS))
+            .and(query_param("playlist_id", "1"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_DELETE_TRACKS))
+            .and(query_param("playlist_id", "2"))
+            .and(query_param("playlist_track_ids", "9100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 2 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let result = client.move_track_between_playlists(55, 1, 2).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lapsed_subscription_can_browse_but_not_stream() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::USER_LOGIN))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "user_auth_token": "token-free",
+                "user": {
+                    "id": 7,
+                    "email": "lapsed@example.com",
+                    "display_name": "Lapsed User",
+                    "credential": { "id": null, "label": "free", "parameters": null },
+                    "subscription": { "end_date": "2024-01-31" }
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_SEARCH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": { "items": [], "total": 0, "offset": 0, "limit": 20 }
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+
+        let session = client.login("lapsed@example.com", "secret").await.unwrap();
+        assert!(!session.subscription.active);
+        assert_eq!(session.subscription.end_date.as_deref(), Some("2024-01-31"));
+        assert_eq!(client.get_subscription().await, Some(session.subscription.clone()));
+
+        assert!(client.search_albums("anything", 20, 0).await.is_ok());
+
+        let err = client
+            .get_stream_url_with_fallback(1234, Quality::HiRes)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::NoActiveSubscription));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tokens_re_extracts_and_revalidates() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<script src="/resources/7.0.1-b001/bundle.js"></script>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Secret "fedcba9876543210fedcba9876543210", split into seed + info, plus 44 chars of extras
+        let bundle = format!(
+            r#"production:{{api:{{appId:"987654321"}}}} a.initialSeed("ZmVkY2JhOTg3NjU0",window.utimezone.berlin) name:"Europe/Berlin",info:"MzIxMGZlZGNiYTk4NzY1NDMyMTA=",extras:"{}""#,
+            "A".repeat(44)
+        );
+        Mock::given(method("GET"))
+            .and(path("/resources/7.0.1-b001/bundle.js"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(bundle))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(wiremock::matchers::header("X-App-Id", "987654321"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        client.refresh_tokens().await.unwrap();
+
+        assert_eq!(client.app_id().await.unwrap(), "987654321");
+        assert_eq!(
+            client.validated_secret.read().await.as_deref(),
+            Some("fedcba9876543210fedcba9876543210")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_stream_replaces_stale_secret() {
+        const STALE: &str = "0123456789abcdef0123456789abcdef";
+        const VALID: &str = "fedcba9876543210fedcba9876543210";
+        let server = MockServer::start().await;
+
+        // Only requests signed with the valid secret are accepted
+        let signed_with_valid = |request: &wiremock::Request| {
+            let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+            let field = |key: &str| query.get(key).and_then(|v| v.parse().ok()).unwrap_or(0);
+            query.get("request_sig").map(String::as_str)
+                == Some(sign_get_file_url(field("track_id"), field("format_id") as u32, field("request_ts"), VALID).as_str())
+        };
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(signed_with_valid)
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .tokens(BundleTokens {
+                app_id: "123456789".to_string(),
+                secrets: vec![STALE.to_string(), VALID.to_string()],
+            })
+            .build()
+            .unwrap();
+        client.set_session(test_session()).await;
+        *client.validated_secret.write().await = Some(STALE.to_string());
+
+        let outcome = client.retry_stream(1234, Quality::Lossless, false).await;
+        assert!(outcome.secret_was_stale);
+        assert!(outcome.secret_changed);
+        assert_eq!(outcome.error, None);
+        assert_eq!(outcome.stream_url.unwrap().url, "https://example.com/cd.flac");
+        assert_eq!(client.validated_secret.read().await.as_deref(), Some(VALID));
+
+        // Forcing a new secret still reports whether the old one worked
+        let outcome = client.retry_stream(1234, Quality::Lossless, true).await;
+        assert!(!outcome.secret_was_stale);
+        assert!(!outcome.secret_changed);
+        assert_eq!(client.validated_secret.read().await.as_deref(), Some(VALID));
+    }
+
+    #[tokio::test]
+    async fn test_stream_request_recovers_from_rotated_secret() {
+        const STALE: &str = "0123456789abcdef0123456789abcdef";
+        const VALID: &str = "fedcba9876543210fedcba9876543210";
+        let server = MockServer::start().await;
+
+        let signed_with_valid = |request: &wiremock::Request| {
+            let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+            let field = |key: &str| query.get(key).and_then(|v| v.parse().ok()).unwrap_or(0);
+            query.get("request_sig").map(String::as_str)
+                == Some(sign_get_file_url(field("track_id"), field("format_id") as u32, field("request_ts"), VALID).as_str())
+        };
+        // The valid secret is probed first (the stale one is moved last), then
+        // the stream request is retried: the stale secret is only seen once
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(signed_with_valid)
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac"
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .tokens(BundleTokens {
+                app_id: "123456789".to_string(),
+                secrets: vec![STALE.to_string(), VALID.to_string()],
+            })
+            .build()
+            .unwrap();
+        client.set_session(test_session()).await;
+        // Validated earlier in the session, then rotated out by Qobuz
+        *client.validated_secret.write().await = Some(STALE.to_string());
+
+        let url = client.get_stream_url(1234, Quality::Lossless).await.unwrap();
+        assert_eq!(url.url, "https://example.com/cd.flac");
+        assert_eq!(client.validated_secret.read().await.as_deref(), Some(VALID));
+    }
+
+    #[tokio::test]
+    async fn test_stream_fallback_reports_downgrade() {
+        let server = MockServer::start().await;
+
+        mount_secret_probe(&server).await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/hires.flac",
+                "format_id": 7,
+                "restrictions": [{ "code": "FormatRestrictedByFormatAvailability" }]
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "6"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac",
+                "sampling_rate": 44.1,
+                "bit_depth": 16
+            })))
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let url = client
+            .get_stream_url_with_fallback(1234, Quality::HiRes)
+            .await
+            .unwrap();
+
+        assert_eq!(url.url, "https://example.com/cd.flac");
+        assert_eq!(
+            url.downgrade,
+            Some(QualityDowngrade {
+                requested: Quality::HiRes,
+                delivered: Quality::Lossless,
+                reason: "FormatRestrictedByFormatAvailability".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_fallback_explains_unrestricted_downgrade() {
+        let server = MockServer::start().await;
+
+        mount_secret_probe(&server).await;
+
+        // A CD-only track answers a hi-res request with its CD stream
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac"
+            })))
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let downgrade = client
+            .get_stream_url_with_fallback(1234, Quality::HiRes)
+            .await
+            .unwrap()
+            .downgrade
+            .unwrap();
+        assert_eq!(downgrade.delivered, Quality::Lossless);
+        assert!(!downgrade.reason.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_fallback_skips_formats_outside_the_plan() {
+        let server = MockServer::start().await;
+
+        mount_secret_probe(&server).await;
+
+        // A hi-res request reaching the server would fail the test
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "27"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "6"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        client
+            .set_session(UserSession {
+                features: AccountFeatures { lossy_streaming: true, lossless_streaming: true, ..AccountFeatures::default() },
+                ..test_session()
+            })
+            .await;
+
+        let downgrade = client
+            .get_stream_url_with_fallback(1234, Quality::UltraHiRes)
+            .await
+            .unwrap()
+            .downgrade
+            .unwrap();
+        assert_eq!(downgrade.delivered, Quality::Lossless);
+        assert!(downgrade.reason.contains("not included in your plan"));
+    }
+
+    #[tokio::test]
+    async fn test_replaced_track_id_is_surfaced_and_streamed() {
+        let server = MockServer::start().await;
+
+        // The remastered track answers for its deprecated id
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(query_param("track_id", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 200, "title": "Song (Remastered)", "streamable": true
+            })))
+            .mount(&server)
+            .await;
+        mount_secret_probe(&server).await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("track_id", "200"))
+            .and(query_param("format_id", "6"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/remaster.flac",
+                "format_id": 6,
+                "track_id": 200
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let track = client.get_track(100).await.unwrap();
+        assert_eq!((track.id, track.replaces), (200, Some(100)));
+        assert_eq!(client.canonical_track_id(100).await, 200);
+
+        let url = client.get_stream_url(100, Quality::Lossless).await.unwrap();
+        assert_eq!(url.url, "https://example.com/remaster.flac");
+        assert_eq!((url.track_id, url.replaces), (200, Some(100)));
+    }
+
+    #[tokio::test]
+    async fn test_preview_stream_is_flagged_not_rejected() {
+        let server = MockServer::start().await;
+
+        mount_secret_probe(&server).await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "6"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/sample.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac",
+                "sampling_rate": 44.1,
+                "bit_depth": 16,
+                "duration": 30,
+                "sample": true,
+                "restrictions": [{ "code": "TrackRestrictedByPurchaseCredentials" }]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let url = client
+            .get_stream_url_with_fallback(1234, Quality::Lossless)
+            .await
+            .unwrap();
+
+        assert!(url.is_preview);
+        assert_eq!(url.url, "https://example.com/sample.flac");
+        assert_eq!(url.duration, Some(30));
+        assert_eq!(url.downgrade, None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_stream_reports_size_and_range_support() {
+        let server = MockServer::start().await;
+        for (track_id, file) in [("1", "ranged.flac"), ("2", "plain.flac")] {
+            Mock::given(method("GET"))
+                .and(path(paths::TRACK_GET_FILE_URL))
+                .and(query_param("track_id", track_id))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "url": format!("{}/cdn/{}", server.uri(), file),
+                    "format_id": 6,
+                    "mime_type": "audio/flac"
+                })))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/cdn/ranged.flac"))
+            .and(header("Range", "bytes=0-0"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("Content-Type", "audio/flac")
+                    .insert_header("Content-Range", "bytes 0-0/48213377")
+                    .insert_header("Accept-Ranges", "bytes")
+                    .set_body_bytes(b"f".to_vec()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Ignores the range and would send the whole file
+        Mock::given(method("GET"))
+            .and(path("/cdn/plain.flac"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "audio/flac")
+                    .insert_header("Accept-Ranges", "none")
+                    .set_body_bytes(vec![0u8; 4096]),
+            )
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+        *client.validated_secret.write().await = Some("0123456789abcdef0123456789abcdef".to_string());
+
+        let probe = client.probe_stream(1, Quality::Lossless).await.unwrap();
+        assert_eq!(
+            probe,
+            StreamProbe {
+                track_id: 1,
+                quality: Quality::Lossless,
+                status: 206,
+                content_type: Some("audio/flac".to_string()),
+                content_length: Some(48_213_377),
+                accepts_ranges: true,
+            }
+        );
+
+        let probe = client.probe_stream(2, Quality::Lossless).await.unwrap();
+        assert_eq!(probe.status, 200);
+        assert_eq!(probe.content_length, Some(4096));
+        assert!(!probe.accepts_ranges);
+    }
+
+    #[test]
+    fn test_short_stream_of_long_track_is_preview() {
+        let mut url: StreamUrl = serde_json::from_value(serde_json::json!({
+            "url": "https://example.com/a.flac",
+            "format_id": 6,
+            "mime_type": "audio/flac",
+            "sampling_rate": 44.1,
+            "bit_depth": 16,
+            "track_id": 1,
+            "restrictions": [],
+            "duration": 30
+        }))
+        .unwrap();
+
+        url.check_duration(30);
+        assert!(!url.is_preview);
+        url.check_duration(241);
+        assert!(url.is_preview);
+    }
+
+    #[tokio::test]
+    async fn test_preferred_container_is_picked_when_offered() {
+        let server = MockServer::start().await;
+        // Track 1 comes as ALAC with a FLAC alternative, track 2 as ALAC only
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("track_id", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/1.m4a",
+                "format_id": 7,
+                "mime_type": "audio/mp4; codecs=\"alac\"",
+                "sampling_rate": 96.0,
+                "bit_depth": 24,
+                "files": [{ "url": "https://example.com/1.flac", "mime_type": "audio/flac" }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("track_id", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/2.m4a",
+                "format_id": 7,
+                "mime_type": "audio/mp4; codecs=\"alac\"",
+                "sampling_rate": 96.0,
+                "bit_depth": 24
+            })))
+            .mount(&server)
+            .await;
+
+        let mut client = mock_client(&server);
+        client.set_session(test_session()).await;
+        *client.validated_secret.write().await = Some("0123456789abcdef0123456789abcdef".to_string());
+
+        // Without a preference the main file is used
+        let url = client.get_stream_url(1, Quality::HiRes).await.unwrap();
+        assert_eq!(url.container, AudioContainer::Alac);
+
+        client.set_preferred_container(Some(AudioContainer::Flac));
+        let url = client.get_stream_url(1, Quality::HiRes).await.unwrap();
+        assert_eq!(url.url, "https://example.com/1.flac");
+        assert_eq!(url.container, AudioContainer::Flac);
+        assert_eq!(url.mime_type, "audio/flac");
+        // Quality fields the alternative doesn't repeat come from the main file
+        assert_eq!(url.bit_depth, Some(24));
+        assert_eq!(url.quality, Some(Quality::HiRes));
+
+        let url = client.get_stream_url_with_fallback(2, Quality::HiRes).await.unwrap();
+        assert_eq!(url.url, "https://example.com/2.m4a");
+        assert_eq!(url.container, AudioContainer::Alac);
+        assert!(url.downgrade.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_only_unrestricted_qualities() {
+        let server = MockServer::start().await;
+
+        // HiRes requests come back downgraded to CD with a restriction
+        let restricted = || {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/restricted.flac",
+                "format_id": 6,
+                "mime_type": "audio/flac",
+                "restrictions": [{ "code": "FormatRestrictedByFormatAvailability" }]
+            }))
+        };
+        let allowed = |format_id: u32, mime: &str| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("https://example.com/{}", format_id),
+                "format_id": format_id,
+                "mime_type": mime,
+                "sampling_rate": 44.1,
+                "restrictions": []
+            }))
+        };
+
+        for (format_id, response) in [
+            ("27", restricted()),
+            ("7", restricted()),
+            ("6", allowed(6, "audio/flac")),
+            ("5", allowed(5, "audio/mpeg")),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(paths::TRACK_GET_FILE_URL))
+                .and(query_param("format_id", format_id))
+                .and(query_param("track_id", "1234"))
+                .respond_with(response)
+                .mount(&server)
+                .await;
+        }
+        mount_secret_probe(&server).await;
+
+        let client = logged_in_client(&server).await;
+
+        let qualities = client.probe_available_qualities(1234).await.unwrap();
+        assert_eq!(qualities, vec![Quality::Lossless, Quality::Mp3]);
+    }
+
+    #[tokio::test]
+    async fn test_editorial_parses_typed_albums_and_filters_genre() {
+        let server = MockServer::start().await;
+        let album = |id: String, genre_id: u64| {
+            serde_json::json!({ "id": id, "title": "Album", "genre": { "id": genre_id, "name": "Genre" } })
+        };
+
+        // The only jazz album of the first page is past the requested limit
+        let mut first: Vec<Value> = (0..49).map(|i| album(format!("rock{}", i), 113)).collect();
+        first.push(album("jazz1".to_string(), 112));
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET_FEATURED))
+            .and(query_param("type", "album-of-the-week"))
+            .and(query_param("genre_ids", "112"))
+            .and(query_param("limit", "50"))
+            .and(query_param("offset", "0"))
+            .and(query_param("lang", "en"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": { "items": first, "total": 53, "offset": 0, "limit": 50 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET_FEATURED))
+            .and(query_param("offset", "50"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": {
+                    "items": [album("jazz2".to_string(), 112), album("jazz3".to_string(), 112), { "id": "untagged" }],
+                    "total": 53, "offset": 50, "limit": 50
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let editorial_type: EditorialType = "album-of-the-week".parse().unwrap();
+        let editorial = client.get_editorial(editorial_type, Some(112), 2).await.unwrap();
+
+        let Editorial::AlbumOfTheWeek(albums) = editorial else {
+            panic!("expected albums, got {:?}", editorial);
+        };
+        let ids: Vec<&str> = albums.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, vec!["jazz1", "jazz2"]);
+        assert_eq!(albums[0].genre.as_ref().unwrap().id, 112);
+
+        assert!("album-of-the-month".parse::<EditorialType>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_region_restricted_stream_explains_mismatch() {
+        let server = MockServer::start().await;
+
+        // Every quality (and the secret probe) is refused for licensing reasons
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "",
+                "format_id": 5,
+                "restrictions": [{ "code": "TrackRestrictedByRightHolders" }]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        client.set_session(UserSession { country_code: Some("FR".to_string()), ..test_session() }).await;
+        client.set_network_region(Some("US".to_string())).await;
+
+        let err = client.get_stream_url_with_fallback(1234, Quality::HiRes).await.unwrap_err();
+        let ApiError::RegionMismatch(mismatch) = &err else {
+            panic!("expected a region mismatch, got {:?}", err);
+        };
+        assert_eq!(mismatch.fault, RegionFault::AccountRegion);
+        assert_eq!(mismatch.account_region.as_deref(), Some("FR"));
+        let message = err.to_string();
+        assert!(message.contains("registered in FR"), "{}", message);
+        assert!(message.contains("connecting from US"), "{}", message);
+
+        // Same region: the content itself isn't licensed there
+        client.set_network_region(Some("FR".to_string())).await;
+        let err = client.get_stream_url(1234, Quality::Lossless).await.unwrap_err();
+        assert!(matches!(err, ApiError::RegionMismatch(RegionMismatch { fault: RegionFault::Content, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_stale_stream_url_is_refreshed_silently() {
+        let server = MockServer::start().await;
+
+        let expiry = chrono::Utc::now().timestamp() + 3600;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("track_id", "1234"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("https://streaming.example.com/new.flac?etsp={}", expiry),
+                "format_id": 7,
+                "mime_type": "audio/flac",
+                "sampling_rate": 96,
+                "bit_depth": 24,
+                "restrictions": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        mount_secret_probe(&server).await;
+
+        let client = logged_in_client(&server).await;
+
+        // Resolved a while ago, expired since
+        let mut stale: StreamUrl = serde_json::from_value(serde_json::json!({
+            "url": "https://streaming.example.com/old.flac?etsp=1000",
+            "format_id": 7, "mime_type": "audio/flac", "sampling_rate": 96.0,
+            "bit_depth": 24, "track_id": 1234, "restrictions": []
+        }))
+        .unwrap();
+        stale.derive_quality();
+        let now = chrono::Utc::now().timestamp();
+        client.stream_urls.write().await.insert(1234, Quality::HiRes, stale, now);
+
+        let url = client.playback_stream_url(1234, Quality::HiRes).await.unwrap();
+        assert!(url.url.contains("new.flac"), "{}", url.url);
+        assert_eq!(stream_urls::url_expiry(&url.url), Some(expiry));
+
+        // The fresh URL is reused without another request
+        let again = client.playback_stream_url(1234, Quality::HiRes).await.unwrap();
+        assert_eq!(again.url, url.url);
+    }
+
+    async fn mount_artist_page(server: &MockServer, similar: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(paths::ARTIST_GET))
+            .and(query_param_is_missing("extra"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 7,
+                "name": "Artist",
+                "biography": { "summary": "Bio", "content": null, "source": null }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ARTIST_GET))
+            .and(query_param("extra", "tracks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 7,
+                "tracks": { "items": [{ "id": 70, "title": "Hit", "duration": 200 }], "total": 1 }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ARTIST_GET))
+            .and(query_param("extra", "albums"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 7,
+                "albums": { "items": [{ "id": "al1", "title": "Record" }], "total": 1 }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ARTIST_GET_SIMILAR))
+            .respond_with(similar)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_artist_page_assembles_sections() {
+        let server = MockServer::start().await;
+        mount_artist_page(
+            &server,
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "artists": { "items": [{ "id": 8, "name": "Other" }], "total": 1, "offset": 0, "limit": 10 }
+            })),
+        )
+        .await;
+        let client = mock_client(&server);
+
+        let page = client.get_artist_page(7).await.unwrap();
+        assert!(page.is_complete());
+        assert_eq!(page.artist.name, "Artist");
+        assert_eq!(page.artist.biography.and_then(|b| b.summary).as_deref(), Some("Bio"));
+        assert_eq!(page.top_tracks.iter().map(|t| t.id).collect::<Vec<_>>(), vec![70]);
+        assert_eq!(page.albums.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["al1"]);
+        assert_eq!(page.similar.iter().map(|a| a.id).collect::<Vec<_>>(), vec![8]);
+    }
+
+    #[tokio::test]
+    async fn test_get_artist_page_degrades_on_section_failure() {
+        let server = MockServer::start().await;
+        mount_artist_page(
+            &server,
+            ResponseTemplate::new(500).set_body_json(serde_json::json!({
+                "status": "error", "code": 500, "message": "Internal error"
+            })),
+        )
+        .await;
+        let client = mock_client(&server);
+
+        let page = client.get_artist_page(7).await.unwrap();
+        assert_eq!(page.artist.name, "Artist");
+        assert_eq!(page.top_tracks.len(), 1);
+        assert_eq!(page.albums.len(), 1);
+        assert!(page.similar.is_empty());
+        assert_eq!(page.errors.len(), 1);
+        assert_eq!(page.errors[0].section, ArtistPageSection::Similar);
+    }
+
+    #[test]
+    fn test_parse_user_playlists_flags_owned_and_followed() {
+        let response = serde_json::json!({
+            "playlists": {
+                "items": [
+                    { "id": 1, "name": "Mine", "owner": { "id": 42, "name": "me" } },
+                    { "id": 2, "name": "Followed", "owner": { "id": 7, "name": "dj" }, "is_public": true },
+                    {
+                        "id": 3, "name": "Shared", "owner": { "id": 7, "name": "dj" },
+                        "is_collaborative": true
+                    }
+                ],
+                "total": 3
+            }
+        });
+
+        let playlists = parse_user_playlists(&response, 42).unwrap();
+        let flags: Vec<(u64, bool, bool)> = playlists
+            .iter()
+            .map(|p| (p.id, p.is_owner, p.is_collaborative))
+            .collect();
+        assert_eq!(flags, vec![(1, true, false), (2, false, false), (3, false, true)]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_request_carries_session_device() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::USER_LOGIN))
+            .and(query_param("device_manufacturer_id", "machine-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "user_auth_token": "token",
+                "user": {
+                    "id": 7,
+                    "email": "user@example.com",
+                    "zone": "FR",
+                    "device": { "id": 987, "device_manufacturer_id": "machine-1" },
+                    "credential": { "parameters": { "short_label": "Studio" } }
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        mount_secret_probe(&server).await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .and(query_param("format_id", "27"))
+            .and(query_param("device_id", "987"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/hires.flac",
+                "format_id": 27,
+                "mime_type": "audio/flac"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .tokens(test_tokens())
+            .device_manufacturer_id("machine-1")
+            .build()
+            .unwrap();
+        client.login("user@example.com", "secret").await.unwrap();
+        assert_eq!(
+            client.session_zone().await,
+            Some(SessionZone { zone: Some("FR".to_string()), device_id: Some("987".to_string()) })
+        );
+
+        let url = client.get_stream_url(1234, Quality::UltraHiRes).await.unwrap();
+        assert_eq!(url.url, "https://example.com/hires.flac");
+    }
+
+    #[tokio::test]
+    async fn test_login_registers_no_device_unless_enabled() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::USER_LOGIN))
+            .and(query_param_is_missing("device_manufacturer_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "user_auth_token": "token",
+                "user": { "id": 7, "credential": { "parameters": { "short_label": "Studio" } } }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut client = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .bundle_base_url(server.uri())
+            .tokens(test_tokens())
+            .device_manufacturer_id("machine-1")
+            .build()
+            .unwrap();
+        client.set_device_registration(false);
+        client.login("user@example.com", "secret").await.unwrap();
+        assert_eq!(client.session_zone().await.and_then(|zone| zone.device_id), None);
+    }
+
+    #[tokio::test]
+    async fn test_future_release_is_refused_for_streaming() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(query_param("track_id", "300"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 300,
+                "title": "Upcoming Single",
+                "streamable": true,
+                "release_date_original": "2999-03-01",
+                "release_date_stream": "2999-03-01",
+                "release_date_download": "2999-03-08",
+                "streamable_at": null,
+                "purchasable_at": null
+            })))
+            .mount(&server)
+            .await;
+        // Any stream request reaching the server would succeed
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": "https://example.com/cd.flac", "format_id": 6, "mime_type": "audio/flac"
+            })))
+            .mount(&server)
+            .await;
+        // Only the album carries the dates
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(query_param("track_id", "302"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 302,
+                "title": "Upcoming Album Track",
+                "streamable": true,
+                "album": { "id": "a2", "title": "Upcoming", "release_date_stream": "2999-04-01" }
+            })))
+            .mount(&server)
+            .await;
+        let client = mock_client(&server);
+
+        // Refused on a cold stream request: the track is fetched to learn its
+        // date, and no stream URL is requested
+        for (requested, date) in [(300, "2999-03-01"), (302, "2999-04-01")] {
+            let err = client.get_stream_url_with_fallback(requested, Quality::Lossless).await.unwrap_err();
+            match err {
+                ApiError::NotYetReleased { track_id, available_on } => {
+                    assert_eq!(track_id, requested);
+                    assert_eq!(available_on, date);
+                }
+                other => panic!("expected NotYetReleased, got {:?}", other),
+            }
+        }
+
+        let track = client.get_track(300).await.unwrap();
+        let today = chrono::Utc::now().date_naive();
+        let availability = track.availability(None, today);
+        assert_eq!(availability.release_date_download.as_deref(), Some("2999-03-08"));
+        assert_eq!(availability.available_on.as_deref(), Some("2999-03-01"));
+        assert!(!availability.streamable_now);
+
+        // Album dates apply to tracks without their own, and past dates are playable
+        let album: Album = serde_json::from_value(serde_json::json!({
+            "id": "a1", "release_date_original": "2001-05-01", "release_date_stream": "2001-05-01"
+        }))
+        .unwrap();
+        let released: Track = serde_json::from_value(serde_json::json!({ "id": 301, "streamable": true })).unwrap();
+        let availability = released.availability(Some(&album), today);
+        assert_eq!(availability.release_date_stream.as_deref(), Some("2001-05-01"));
+        assert_eq!(availability.available_on, None);
+        assert!(availability.streamable_now);
+    }
+
+    #[test]
+    fn test_release_windows_stay_bounded() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 10).unwrap();
+        let tomorrow = today.succ_opt().unwrap();
+        let mut windows = ReleaseWindows::default();
+
+        // Only future dates are kept, and drop out once they pass
+        windows.record(1, Some(tomorrow), today);
+        windows.record(2, Some(today), today);
+        assert_eq!(windows.upcoming(1, today), Some(tomorrow));
+        assert_eq!(windows.upcoming(2, today), None);
+        assert!(windows.is_known(2));
+        windows.record(3, None, tomorrow);
+        assert!(windows.upcoming.is_empty());
+
+        // Released tracks are remembered up to a limit, oldest first out
+        for track_id in 10..10 + MAX_CHECKED_RELEASES as u64 {
+            windows.record(track_id, None, today);
+        }
+        assert_eq!(windows.released.len(), MAX_CHECKED_RELEASES);
+        assert!(!windows.is_known(2));
+        assert!(windows.is_known(10 + MAX_CHECKED_RELEASES as u64 - 1));
+    }
+}
@@ -6,11 +6,20 @@ pub const BASE_URL: &str = "https://www.qobuz.com/api.json/0.2";
 pub mod paths {
     // User
     pub const USER_LOGIN: &str = "/user/login";
+    pub const USER_GET: &str = "/user/get";
+    // Undocumented: no published client calls these; the names follow the
+    // API's get/delete conventions and are unconfirmed against the live
+    // service. A 404 is reported as device management being unavailable
+    // (see `QobuzClient::get_account_devices`, `auth::parse_devices`).
+    pub const USER_GET_DEVICES: &str = "/user/getDevices";
+    pub const USER_DELETE_DEVICE: &str = "/user/deleteDevice";
 
     // Track
     pub const TRACK_GET: &str = "/track/get";
     pub const TRACK_SEARCH: &str = "/track/search";
     pub const TRACK_GET_FILE_URL: &str = "/track/getFileUrl";
+    pub const TRACK_REPORT_STREAMING_START: &str = "/track/reportStreamingStart";
+    pub const TRACK_REPORT_STREAMING_END: &str = "/track/reportStreamingEnd";
 
     // Album
     pub const ALBUM_GET: &str = "/album/get";
@@ -31,14 +40,27 @@ pub mod paths {
     pub const PLAYLIST_ADD_TRACKS: &str = "/playlist/addTracks";
     pub const PLAYLIST_DELETE_TRACKS: &str = "/playlist/deleteTracks";
     pub const PLAYLIST_UPDATE: &str = "/playlist/update";
+    pub const PLAYLIST_SUBSCRIBE: &str = "/playlist/subscribe";
+    pub const PLAYLIST_UNSUBSCRIBE: &str = "/playlist/unsubscribe";
+    pub const PLAYLIST_GET_FEATURED: &str = "/playlist/getFeatured";
 
     // Favorites
     pub const FAVORITE_GET_USER_FAVORITES: &str = "/favorite/getUserFavorites";
+    pub const FAVORITE_GET_USER_FAVORITE_IDS: &str = "/favorite/getUserFavoriteIds";
     pub const FAVORITE_CREATE: &str = "/favorite/create";
     pub const FAVORITE_DELETE: &str = "/favorite/delete";
 
+    // Purchases
+    pub const PURCHASE_GET_USER_PURCHASES: &str = "/purchase/getUserPurchases";
+
     // Label
     pub const LABEL_GET: &str = "/label/get";
+
+    // Editorial
+    pub const FOCUS_LIST: &str = "/focus/list";
+
+    // Catalog
+    pub const CATALOG_AUTOSUGGEST: &str = "/catalog/autosuggest";
 }
 
 /// Build full URL for an endpoint
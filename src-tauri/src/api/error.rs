@@ -2,11 +2,17 @@
 
 use thiserror::Error;
 
+use super::region::RegionMismatch;
+
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("Authentication failed: {0}")]
     AuthenticationError(String),
 
+    /// The account signed in elsewhere and Qobuz ended this session
+    #[error("Session ended: {0}")]
+    SessionInvalidated(String),
+
     #[error("Invalid app ID")]
     InvalidAppId,
 
@@ -16,12 +22,18 @@ pub enum ApiError {
     #[error("Failed to extract bundle tokens: {0}")]
     BundleExtractionError(String),
 
-    #[error("User is not eligible (no active subscription)")]
-    IneligibleUser,
+    #[error("Failed to extract bundle tokens: {0}")]
+    Bundle(#[from] BundleError),
+
+    #[error("No active subscription: streaming is unavailable")]
+    NoActiveSubscription,
 
     #[error("Track is not streamable")]
     NonStreamable,
 
+    #[error("Track {track_id} is not released yet: available on {available_on}")]
+    NotYetReleased { track_id: u64, available_on: String },
+
     #[error("Invalid quality format: {0}")]
     InvalidQuality(u32),
 
@@ -39,6 +51,47 @@ pub enum ApiError {
 
     #[error("Rate limited, retry after {0} seconds")]
     RateLimited(u64),
+
+    #[error("Request cancelled")]
+    Cancelled,
+
+    #[error("{0}")]
+    RegionMismatch(RegionMismatch),
+}
+
+impl ApiError {
+    /// Qobuz couldn't be reached or asked to back off; says nothing about
+    /// the requested item
+    pub fn is_transport(&self) -> bool {
+        match self {
+            ApiError::NetworkError(e) => !e.is_decode(),
+            ApiError::RateLimited(_) | ApiError::Cancelled => true,
+            _ => false,
+        }
+    }
+}
+
+/// The step of bundle token extraction that failed
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("login page fetch failed: {0}")]
+    LoginPageFetch(String),
+
+    #[error("bundle URL not found in login page HTML")]
+    BundleUrlNotFound,
+
+    #[error("bundle {version} fetch failed: {message}")]
+    BundleFetch { version: String, message: String },
+
+    /// The bundle kept arriving cut short (e.g. the connection dropped)
+    #[error("bundle {version} is incomplete: {reason}")]
+    IncompleteBundle { version: String, reason: String },
+
+    #[error("app id not found in bundle {version}")]
+    AppIdNotFound { version: String },
+
+    #[error("no secrets decoded from bundle {version}")]
+    NoSecrets { version: String },
 }
 
 pub type Result<T> = std::result::Result<T, ApiError>;
@@ -12,7 +12,16 @@ pub mod client;
 pub mod endpoints;
 pub mod error;
 pub mod models;
+pub mod region;
+pub mod requests;
+pub mod routing;
+pub mod stream_urls;
+pub mod suggest;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use client::QobuzClient;
-pub use error::ApiError;
+pub use error::{ApiError, BundleError};
 pub use models::*;
+pub use requests::{InFlightRequests, DEFAULT_REQUEST_TIMEOUT};
+pub use suggest::{Suggestion, SuggestionKind};
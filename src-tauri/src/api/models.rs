@@ -1,9 +1,12 @@
 //! API response models
 
-use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// Audio quality format IDs
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum Quality {
     Mp3 = 5,
@@ -50,6 +53,116 @@ pub struct UserSession {
     pub email: String,
     pub display_name: String,
     pub subscription_label: String,
+    #[serde(default)]
+    pub subscription: SubscriptionInfo,
+    /// Country the account is registered in, which decides the catalog it sees
+    #[serde(default)]
+    pub country_code: Option<String>,
+    /// Zone (store) the account is served from, which decides the formats it may stream
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// Device registered for this session, sent with stream requests
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// What the plan allows, from the login `credential.parameters` block
+    #[serde(default)]
+    pub features: AccountFeatures,
+}
+
+/// Feature flags of the account's plan. A flag is false when the login
+/// response leaves it out or doesn't send a boolean (free and lapsed
+/// accounts have no block).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountFeatures {
+    #[serde(default)]
+    pub lossy_streaming: bool,
+    #[serde(default)]
+    pub lossless_streaming: bool,
+    #[serde(default)]
+    pub hires_streaming: bool,
+    /// Hi-Res streaming of purchased albums
+    #[serde(default)]
+    pub hires_purchases_streaming: bool,
+    #[serde(default)]
+    pub mobile_streaming: bool,
+    /// Downloading for offline listening
+    #[serde(default)]
+    pub offline_streaming: bool,
+}
+
+impl AccountFeatures {
+    /// Best quality the plan streams, or None when it grants no streaming
+    /// flag (the block is missing or lists none, so nothing can be capped)
+    pub fn max_streaming_quality(&self) -> Option<Quality> {
+        if self.hires_streaming {
+            Some(Quality::UltraHiRes)
+        } else if self.lossless_streaming {
+            Some(Quality::Lossless)
+        } else if self.lossy_streaming {
+            Some(Quality::Mp3)
+        } else {
+            None
+        }
+    }
+}
+
+/// Zone and registered device of the current session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionZone {
+    pub zone: Option<String>,
+    pub device_id: Option<String>,
+}
+
+/// A device registered on the account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+    pub manufacturer_id: Option<String>,
+    pub platform: Option<String>,
+    /// Last activity, Unix seconds
+    pub last_used_at: Option<i64>,
+    /// The device this app is logged in as
+    pub current: bool,
+}
+
+/// Subscription state reported at login
+///
+/// Free or lapsed accounts can still log in and browse the catalog, but
+/// streaming is refused with `ApiError::NoActiveSubscription`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionInfo {
+    pub active: bool,
+    /// Subscription end date as reported by Qobuz (YYYY-MM-DD)
+    pub end_date: Option<String>,
+}
+
+/// File container a stream is delivered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioContainer {
+    Flac,
+    Alac,
+    Mp3,
+    #[default]
+    Other,
+}
+
+impl AudioContainer {
+    /// Container of a stream from its MIME type, e.g. `audio/flac` or
+    /// `audio/mp4; codecs="alac"`
+    pub fn from_mime(mime_type: &str) -> Self {
+        let mime_type = mime_type.to_ascii_lowercase();
+        if mime_type.contains("flac") {
+            Self::Flac
+        } else if mime_type.contains("alac") {
+            Self::Alac
+        } else if mime_type.contains("mpeg") || mime_type.contains("mp3") {
+            Self::Mp3
+        } else {
+            Self::Other
+        }
+    }
 }
 
 /// Stream URL response
@@ -62,14 +175,157 @@ pub struct StreamUrl {
     pub bit_depth: Option<u32>,
     pub track_id: u64,
     pub restrictions: Vec<StreamRestriction>,
+    /// Set by the fallback path when the delivered quality is below the requested one
+    #[serde(default)]
+    pub downgrade: Option<QualityDowngrade>,
+    /// Length of the delivered file in seconds, when reported
+    #[serde(default)]
+    pub duration: Option<u32>,
+    /// Only a short preview clip is available (e.g. 30 seconds)
+    #[serde(default)]
+    pub is_preview: bool,
+    /// Id that was requested, when the catalog replaced it with `track_id`
+    #[serde(default)]
+    pub replaces: Option<u64>,
+    /// Quality tier of the delivered format (None for unknown format ids)
+    #[serde(default)]
+    pub quality: Option<Quality>,
+    /// Display label of the delivered format, e.g. "FLAC 24-bit/96kHz"
+    #[serde(default)]
+    pub quality_label: String,
+    /// Container of the delivered file
+    #[serde(default)]
+    pub container: AudioContainer,
 }
 
+/// Longest clip Qobuz serves as a track preview
+pub const PREVIEW_MAX_SECS: u32 = 30;
+
+/// Restriction codes that come with a preview clip instead of the full track
+const PREVIEW_RESTRICTIONS: &[&str] = &["TrackRestrictedByPurchaseCredentials"];
+
 impl StreamUrl {
     pub fn has_restrictions(&self) -> bool {
         self.restrictions.iter().any(|r| {
             r.code == "FormatRestrictedByFormatAvailability"
         })
     }
+
+    pub fn has_preview_restriction(&self) -> bool {
+        self.restrictions
+            .iter()
+            .any(|r| PREVIEW_RESTRICTIONS.contains(&r.code.as_str()))
+    }
+
+    /// Flag the stream as a preview when it is a short clip of a longer track
+    pub fn check_duration(&mut self, track_duration_secs: u32) {
+        if let Some(duration) = self.duration {
+            if duration <= PREVIEW_MAX_SECS && duration < track_duration_secs {
+                self.is_preview = true;
+            }
+        }
+    }
+
+    /// Quality actually delivered, from the returned format_id
+    pub fn delivered_quality(&self) -> Option<Quality> {
+        Quality::from_id(self.format_id)
+    }
+
+    /// Fill `quality` and `quality_label` from the numeric format fields
+    pub fn derive_quality(&mut self) {
+        self.quality = self.delivered_quality();
+        self.quality_label = quality_label(self.format_id, self.sampling_rate, self.bit_depth);
+    }
+}
+
+/// Display label for a delivered format: "MP3 320", "CD 16/44.1",
+/// "FLAC 24-bit/96kHz". `sampling_rate` is in kHz (Hz values are converted).
+pub fn quality_label(format_id: u32, sampling_rate: f64, bit_depth: Option<u32>) -> String {
+    let quality = Quality::from_id(format_id);
+    if quality == Some(Quality::Mp3) {
+        return "MP3 320".to_string();
+    }
+
+    let depth = bit_depth.or(match quality {
+        Some(Quality::Lossless) => Some(16),
+        Some(Quality::HiRes) | Some(Quality::UltraHiRes) => Some(24),
+        _ => None,
+    });
+    let khz = if sampling_rate > 1000.0 { sampling_rate / 1000.0 } else { sampling_rate };
+    // One decimal is enough for 44.1/88.2/176.4; drop it for whole rates
+    let rate = (khz > 0.0).then(|| {
+        let rounded = (khz * 10.0).round() / 10.0;
+        if rounded.fract() == 0.0 {
+            format!("{}", rounded as u64)
+        } else {
+            format!("{:.1}", rounded)
+        }
+    });
+
+    match (depth, rate) {
+        (Some(16), Some(rate)) if rate == "44.1" => "CD 16/44.1".to_string(),
+        (Some(depth), Some(rate)) => format!("FLAC {}-bit/{}kHz", depth, rate),
+        (Some(depth), None) => format!("FLAC {}-bit", depth),
+        (None, Some(rate)) => format!("FLAC {}kHz", rate),
+        (None, None) => quality.map(|q| q.label().to_string()).unwrap_or_else(|| "Unknown".to_string()),
+    }
+}
+
+/// Requested vs. delivered quality when the stream had to fall back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityDowngrade {
+    pub requested: Quality,
+    pub delivered: Quality,
+    /// Restriction codes (or error) that forced the fallback
+    pub reason: String,
+}
+
+/// Outcome of re-signing and retrying one stream request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamRetryOutcome {
+    pub track_id: u64,
+    pub quality: Quality,
+    /// The secret in use before the retry no longer validated
+    pub secret_was_stale: bool,
+    /// A different secret than the previously cached one signed the retry
+    pub secret_changed: bool,
+    /// Set when the retry succeeded
+    pub stream_url: Option<StreamUrl>,
+    /// Restriction codes returned with the stream (empty when none)
+    pub restrictions: Vec<String>,
+    /// Error of a failed retry
+    pub error: Option<String>,
+}
+
+/// Headers of a resolved stream, read without downloading the file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamProbe {
+    pub track_id: u64,
+    pub quality: Quality,
+    /// HTTP status of the probe (206 when the range was honoured)
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// Size of the whole file in bytes
+    pub content_length: Option<u64>,
+    /// Byte ranges are served, so seeking works while streaming
+    pub accepts_ranges: bool,
+}
+
+/// Outcome of favoriting every track of an album
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlbumTracksFavorited {
+    /// Track ids newly added to favorites
+    pub added: Vec<u64>,
+    pub already_favorited: usize,
+    /// Tracks skipped because they are not streamable in the user's region
+    pub unavailable: usize,
+}
+
+/// Track counts of both playlists after moving a track between them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistTrackMove {
+    pub from_tracks_count: u32,
+    pub to_tracks_count: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,6 +345,13 @@ pub struct Album {
     #[serde(default)]
     pub image: ImageSet,
     pub release_date_original: Option<String>,
+    pub release_date_stream: Option<String>,
+    pub release_date_download: Option<String>,
+    /// Start of the streaming window (unix seconds)
+    pub streamable_at: Option<i64>,
+    /// Start of the purchase window (unix seconds)
+    pub purchasable_at: Option<i64>,
+    pub streamable: Option<bool>,
     pub label: Option<Label>,
     pub genre: Option<Genre>,
     pub tracks_count: Option<u32>,
@@ -99,8 +362,288 @@ pub struct Album {
     pub hires_streamable: bool,
     pub maximum_sampling_rate: Option<f64>,
     pub maximum_bit_depth: Option<u32>,
-    #[serde(default)]
+    /// Tracks, ordered by disc (media_number) then track_number
+    #[serde(default, deserialize_with = "deserialize_album_tracks")]
     pub tracks: Option<TracksContainer>,
+    /// Hint that the album is a continuous mix and should be played gaplessly
+    #[serde(default, alias = "is_gapless")]
+    pub gapless: bool,
+    /// Disc boundaries within `tracks` (populated by `index_discs`)
+    #[serde(default)]
+    pub discs: Vec<DiscBoundary>,
+    /// Extra material shipped with the album (digital booklets, ...)
+    #[serde(default)]
+    pub goodies: Vec<Goodie>,
+    /// Series the album is a volume of
+    #[serde(default, deserialize_with = "deserialize_release_group")]
+    pub series: Option<ReleaseGroup>,
+    /// Box set the album belongs to, or its volumes when it is one
+    #[serde(default, deserialize_with = "deserialize_release_group")]
+    pub box_set: Option<ReleaseGroup>,
+}
+
+impl Album {
+    /// Other releases of the album's box set and series, each once: the
+    /// box set itself, its volumes, then the series, in volume order
+    pub fn related_releases(&self) -> Vec<RelatedRelease> {
+        let mut seen = HashSet::from([self.id.as_str()]);
+        let mut related = Vec::new();
+
+        let groups = [(&self.box_set, ReleaseRelation::BoxSet), (&self.series, ReleaseRelation::Series)];
+        for (group, relation) in groups {
+            let Some(group) = group else { continue };
+            let mut releases: Vec<&ReleaseRef> = group.albums.items.iter().collect();
+            releases.sort_by_key(|release| release.volume.unwrap_or(u32::MAX));
+
+            if let Some(id) = group.id.as_deref().filter(|_| relation == ReleaseRelation::BoxSet) {
+                if seen.insert(id) {
+                    related.push(RelatedRelease {
+                        album_id: id.to_string(),
+                        title: group.title.clone(),
+                        relation,
+                        volume: None,
+                        image: None,
+                    });
+                }
+            }
+            for release in releases {
+                if seen.insert(release.id.as_str()) {
+                    related.push(RelatedRelease {
+                        album_id: release.id.clone(),
+                        title: release.title.clone(),
+                        relation,
+                        volume: release.volume,
+                        image: release.image.best().cloned(),
+                    });
+                }
+            }
+        }
+        related
+    }
+
+    /// Compute disc boundaries from the (already ordered) track list
+    pub fn index_discs(&mut self) {
+        let mut discs: Vec<DiscBoundary> = Vec::new();
+        if let Some(tracks) = &self.tracks {
+            for (index, track) in tracks.items.iter().enumerate() {
+                let media_number = track.media_number.unwrap_or(1);
+                match discs.last_mut() {
+                    Some(disc) if disc.media_number == media_number => disc.track_count += 1,
+                    _ => discs.push(DiscBoundary {
+                        media_number,
+                        start_index: index as u32,
+                        track_count: 1,
+                    }),
+                }
+            }
+        }
+        self.discs = discs;
+    }
+
+    /// The album's digital booklet, if it has one
+    pub fn booklet(&self) -> Option<&Goodie> {
+        self.goodies.iter().find(|g| g.is_booklet())
+    }
+
+    /// Release dates and whether the album can be streamed on `today`
+    pub fn availability(&self, today: NaiveDate) -> ReleaseAvailability {
+        ReleaseAvailability::new(
+            ReleaseDates {
+                original: self.release_date_original.clone(),
+                stream: self.release_date_stream.clone(),
+                download: self.release_date_download.clone(),
+                streamable_at: self.streamable_at,
+                purchasable_at: self.purchasable_at,
+            },
+            self.streamable.unwrap_or(true),
+            today,
+        )
+    }
+}
+
+/// Catalog release dates (`YYYY-MM-DD`) and window starts (unix seconds)
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ReleaseDates {
+    original: Option<String>,
+    stream: Option<String>,
+    download: Option<String>,
+    streamable_at: Option<i64>,
+    purchasable_at: Option<i64>,
+}
+
+impl ReleaseDates {
+    /// These dates, with the missing ones taken from `fallback`
+    fn or(self, fallback: ReleaseDates) -> ReleaseDates {
+        ReleaseDates {
+            original: self.original.or(fallback.original),
+            stream: self.stream.or(fallback.stream),
+            download: self.download.or(fallback.download),
+            streamable_at: self.streamable_at.or(fallback.streamable_at),
+            purchasable_at: self.purchasable_at.or(fallback.purchasable_at),
+        }
+    }
+}
+
+/// When an album or track becomes available
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseAvailability {
+    pub release_date_original: Option<String>,
+    pub release_date_stream: Option<String>,
+    pub release_date_download: Option<String>,
+    pub streamable_at: Option<i64>,
+    pub purchasable_at: Option<i64>,
+    /// First day of streaming (`YYYY-MM-DD`), when that is still ahead
+    pub available_on: Option<String>,
+    /// Playback can start now
+    pub streamable_now: bool,
+}
+
+impl ReleaseAvailability {
+    fn new(dates: ReleaseDates, streamable: bool, today: NaiveDate) -> Self {
+        // The window timestamp is the precise one; the stream date is the fallback
+        let stream_start = dates
+            .streamable_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.date_naive())
+            .or_else(|| parse_release_date(dates.stream.as_deref()));
+        let available_on = stream_start
+            .filter(|date| *date > today)
+            .map(|date| date.format("%Y-%m-%d").to_string());
+
+        Self {
+            release_date_original: dates.original,
+            release_date_stream: dates.stream,
+            release_date_download: dates.download,
+            streamable_at: dates.streamable_at,
+            purchasable_at: dates.purchasable_at,
+            streamable_now: streamable && available_on.is_none(),
+            available_on,
+        }
+    }
+}
+
+fn parse_release_date(date: Option<&str>) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date?.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Album goodie (bonus file such as a PDF booklet)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Goodie {
+    #[serde(default)]
+    pub id: u64,
+    pub file_format_id: Option<u32>,
+    #[serde(default)]
+    pub name: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub original_url: Option<String>,
+}
+
+impl Goodie {
+    /// Qobuz file format id for digital booklets
+    pub const BOOKLET_FORMAT_ID: u32 = 21;
+
+    pub fn is_booklet(&self) -> bool {
+        self.file_format_id == Some(Self::BOOKLET_FORMAT_ID)
+            || self
+                .download_url()
+                .map(|url| url.to_ascii_lowercase().ends_with(".pdf"))
+                .unwrap_or(false)
+    }
+
+    /// URL of the file itself, preferring the original upload
+    pub fn download_url(&self) -> Option<&str> {
+        self.original_url.as_deref().or(self.url.as_deref())
+    }
+}
+
+/// A series or box set as sent with an album.
+///
+/// These blocks are not part of the documented album response; they are read
+/// in the API's usual `{ "items": [...] }` list shape, and an album whose
+/// block doesn't match is treated as having none.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseGroup {
+    /// Album id of the box set itself; series have none
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub albums: ReleaseRefs,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseRefs {
+    #[serde(default)]
+    pub items: Vec<ReleaseRef>,
+}
+
+/// A release listed in a series or box set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseRef {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    pub volume: Option<u32>,
+    #[serde(default)]
+    pub image: ImageSet,
+}
+
+/// A series/box-set block that doesn't parse is dropped rather than failing
+/// the whole album
+fn deserialize_release_group<'de, D>(deserializer: D) -> Result<Option<ReleaseGroup>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| match serde_json::from_value(value) {
+        Ok(group) => Some(group),
+        Err(e) => {
+            log::debug!("Ignoring unrecognized series/box set block: {}", e);
+            None
+        }
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseRelation {
+    Series,
+    BoxSet,
+}
+
+/// Another release of an album's series or box set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelatedRelease {
+    pub album_id: String,
+    pub title: String,
+    pub relation: ReleaseRelation,
+    pub volume: Option<u32>,
+    pub image: Option<String>,
+}
+
+/// A disc within an album's ordered track list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscBoundary {
+    pub media_number: u32,
+    /// Index of the disc's first track in `Album::tracks`
+    pub start_index: u32,
+    pub track_count: u32,
+}
+
+/// Album track lists are not always returned in disc/track order for
+/// multi-disc releases, so sort them on the way in.
+fn deserialize_album_tracks<'de, D>(deserializer: D) -> Result<Option<TracksContainer>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut tracks = Option::<TracksContainer>::deserialize(deserializer)?;
+    if let Some(container) = tracks.as_mut() {
+        container
+            .items
+            .sort_by_key(|t| (t.media_number.unwrap_or(1), t.track_number));
+    }
+    Ok(tracks)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,12 +673,58 @@ pub struct Track {
     pub hires_streamable: bool,
     pub maximum_sampling_rate: Option<f64>,
     pub maximum_bit_depth: Option<u32>,
+    pub maximum_bit_rate: Option<f64>,
     #[serde(default)]
     pub streamable: bool,
+    pub release_date_original: Option<String>,
+    pub release_date_stream: Option<String>,
+    pub release_date_download: Option<String>,
+    /// Start of the streaming window (unix seconds)
+    pub streamable_at: Option<i64>,
+    /// Start of the purchase window (unix seconds)
+    pub purchasable_at: Option<i64>,
     #[serde(default)]
     pub parental_warning: bool,
     /// Playlist-specific: ID within the playlist (for removal)
     pub playlist_track_id: Option<u64>,
+    /// Id that was requested, when the catalog replaced it with `id`
+    /// (e.g. after a remaster); set by `QobuzClient::get_track`
+    #[serde(default)]
+    pub replaces: Option<u64>,
+}
+
+impl Track {
+    /// Release dates and whether the track can be streamed on `today`.
+    /// Dates the track doesn't carry are taken from its embedded album,
+    /// then from `album`.
+    pub fn availability(&self, album: Option<&Album>, today: NaiveDate) -> ReleaseAvailability {
+        let mut dates = ReleaseDates {
+            original: self.release_date_original.clone(),
+            stream: self.release_date_stream.clone(),
+            download: self.release_date_download.clone(),
+            streamable_at: self.streamable_at,
+            purchasable_at: self.purchasable_at,
+        };
+        if let Some(summary) = &self.album {
+            dates = dates.or(ReleaseDates {
+                original: summary.release_date_original.clone(),
+                stream: summary.release_date_stream.clone(),
+                download: summary.release_date_download.clone(),
+                streamable_at: summary.streamable_at,
+                purchasable_at: summary.purchasable_at,
+            });
+        }
+        if let Some(album) = album {
+            dates = dates.or(ReleaseDates {
+                original: album.release_date_original.clone(),
+                stream: album.release_date_stream.clone(),
+                download: album.release_date_download.clone(),
+                streamable_at: album.streamable_at,
+                purchasable_at: album.purchasable_at,
+            });
+        }
+        ReleaseAvailability::new(dates, self.streamable, today)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -146,6 +735,17 @@ pub struct AlbumSummary {
     pub title: String,
     #[serde(default)]
     pub image: ImageSet,
+    /// Release dates, for tracks that don't carry their own
+    #[serde(default)]
+    pub release_date_original: Option<String>,
+    #[serde(default)]
+    pub release_date_stream: Option<String>,
+    #[serde(default)]
+    pub release_date_download: Option<String>,
+    #[serde(default)]
+    pub streamable_at: Option<i64>,
+    #[serde(default)]
+    pub purchasable_at: Option<i64>,
 }
 
 /// Artist model
@@ -191,6 +791,41 @@ pub struct ArtistAlbums {
     pub limit: u32,
 }
 
+/// Everything the artist page shows, fetched in one call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistPage {
+    pub artist: Artist,
+    pub top_tracks: Vec<Track>,
+    pub albums: Vec<Album>,
+    pub similar: Vec<Artist>,
+    /// Sections that failed to load (left empty above)
+    #[serde(default)]
+    pub errors: Vec<ArtistPageError>,
+}
+
+impl ArtistPage {
+    /// True when every section loaded
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Optional section of an artist page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtistPageSection {
+    TopTracks,
+    Albums,
+    Similar,
+}
+
+/// A section of an artist page that failed to load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistPageError {
+    pub section: ArtistPageSection,
+    pub message: String,
+}
+
 /// Playlist model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
@@ -208,8 +843,16 @@ pub struct Playlist {
     pub duration: u32,
     #[serde(default)]
     pub is_public: bool,
+    /// Other users can edit this playlist
+    #[serde(default)]
+    pub is_collaborative: bool,
+    /// Owned by the logged-in user (false for followed playlists)
+    #[serde(default)]
+    pub is_owner: bool,
     #[serde(default)]
     pub tracks: Option<TracksContainer>,
+    #[serde(default)]
+    pub genres: Vec<Genre>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -273,6 +916,239 @@ pub struct SearchResultsPage<T> {
     pub limit: u32,
 }
 
+/// Bundle tokens the client runs with
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiDiagnostics {
+    pub app_id: Option<String>,
+    /// All app_ids found in the bundle, preferred first
+    pub app_id_candidates: Vec<String>,
+    pub secret_validated: bool,
+    pub api_base_url: String,
+}
+
+/// Kind of purchased content to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PurchaseType {
+    Albums,
+    Tracks,
+}
+
+impl PurchaseType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PurchaseType::Albums => "albums",
+            PurchaseType::Tracks => "tracks",
+        }
+    }
+}
+
+/// Bought content, as opposed to streamed favorites
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Purchases {
+    #[serde(default)]
+    pub albums: Option<SearchResultsPage<Album>>,
+    #[serde(default)]
+    pub tracks: Option<SearchResultsPage<Track>>,
+}
+
+/// Sort order for the favorites view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FavoritesSort {
+    /// Most recently added first (API default)
+    #[default]
+    DateAddedDesc,
+    DateAddedAsc,
+    Artist,
+    Title,
+}
+
+impl FavoritesSort {
+    /// Key used to cache pages fetched with this sort
+    pub fn key(&self) -> &'static str {
+        match self {
+            FavoritesSort::DateAddedDesc => "date_added_desc",
+            FavoritesSort::DateAddedAsc => "date_added_asc",
+            FavoritesSort::Artist => "artist",
+            FavoritesSort::Title => "title",
+        }
+    }
+
+    /// `sort`/`order` query params for getUserFavorites, where the API supports them
+    pub fn query_params(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            FavoritesSort::DateAddedDesc => Some(("date_added", "desc")),
+            FavoritesSort::DateAddedAsc => Some(("date_added", "asc")),
+            FavoritesSort::Artist | FavoritesSort::Title => None,
+        }
+    }
+
+    /// Sort a getUserFavorites page in place and make sure every item carries
+    /// a `favorited_at` key (null when the API didn't provide one).
+    ///
+    /// Artist/title ordering is applied client-side, so it is only stable
+    /// within the returned page.
+    pub fn apply(&self, page: &mut serde_json::Value, fav_type: &str) {
+        let Some(items) = page
+            .get_mut(fav_type)
+            .and_then(|c| c.get_mut("items"))
+            .and_then(|i| i.as_array_mut())
+        else {
+            return;
+        };
+
+        for item in items.iter_mut() {
+            if let Some(obj) = item.as_object_mut() {
+                obj.entry("favorited_at").or_insert(serde_json::Value::Null);
+            }
+        }
+
+        let favorited_at = |item: &serde_json::Value| item["favorited_at"].as_i64().unwrap_or(0);
+        let text = |item: &serde_json::Value, keys: &[&str]| -> String {
+            keys.iter()
+                .find_map(|k| item.pointer(k).and_then(|v| v.as_str()))
+                .unwrap_or("")
+                .to_lowercase()
+        };
+
+        match self {
+            FavoritesSort::DateAddedDesc => items.sort_by_key(|i| std::cmp::Reverse(favorited_at(i))),
+            FavoritesSort::DateAddedAsc => items.sort_by_key(favorited_at),
+            FavoritesSort::Artist => {
+                items.sort_by_key(|i| text(i, &["/artist/name", "/performer/name", "/name"]))
+            }
+            FavoritesSort::Title => items.sort_by_key(|i| text(i, &["/title", "/name"])),
+        }
+    }
+}
+
+/// Editorial sections of the Qobuz home page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EditorialType {
+    FeaturedFocus,
+    AlbumOfTheWeek,
+    PlaylistsOfTheWeek,
+}
+
+impl EditorialType {
+    pub const ALL: [EditorialType; 3] = [
+        EditorialType::FeaturedFocus,
+        EditorialType::AlbumOfTheWeek,
+        EditorialType::PlaylistsOfTheWeek,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            EditorialType::FeaturedFocus => "featured-focus",
+            EditorialType::AlbumOfTheWeek => "album-of-the-week",
+            EditorialType::PlaylistsOfTheWeek => "playlists-of-the-week",
+        }
+    }
+}
+
+impl std::str::FromStr for EditorialType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|t| t.key() == s).ok_or_else(|| {
+            let known: Vec<&str> = Self::ALL.iter().map(|t| t.key()).collect();
+            format!("Unknown editorial type '{}' (expected one of: {})", s, known.join(", "))
+        })
+    }
+}
+
+/// Editorial focus article
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusItem {
+    #[serde(deserialize_with = "deserialize_id_string")]
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub image: Option<String>,
+    #[serde(default)]
+    pub genre_ids: Vec<u64>,
+}
+
+/// Focus ids come back as strings or numbers depending on the endpoint
+fn deserialize_id_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!("invalid id: {}", other))),
+    }
+}
+
+/// Typed content of an editorial section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "items", rename_all = "kebab-case")]
+pub enum Editorial {
+    FeaturedFocus(Vec<FocusItem>),
+    AlbumOfTheWeek(Vec<Album>),
+    PlaylistsOfTheWeek(Vec<Playlist>),
+}
+
+impl Editorial {
+    pub fn empty(editorial_type: EditorialType) -> Self {
+        match editorial_type {
+            EditorialType::FeaturedFocus => Editorial::FeaturedFocus(Vec::new()),
+            EditorialType::AlbumOfTheWeek => Editorial::AlbumOfTheWeek(Vec::new()),
+            EditorialType::PlaylistsOfTheWeek => Editorial::PlaylistsOfTheWeek(Vec::new()),
+        }
+    }
+
+    /// Add the items of another page of the same section
+    pub fn append(&mut self, other: Editorial) {
+        match (self, other) {
+            (Editorial::FeaturedFocus(items), Editorial::FeaturedFocus(more)) => items.extend(more),
+            (Editorial::AlbumOfTheWeek(albums), Editorial::AlbumOfTheWeek(more)) => albums.extend(more),
+            (Editorial::PlaylistsOfTheWeek(playlists), Editorial::PlaylistsOfTheWeek(more)) => {
+                playlists.extend(more)
+            }
+            _ => log::warn!("Editorial pages of different sections can't be combined"),
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            Editorial::FeaturedFocus(items) => items.truncate(len),
+            Editorial::AlbumOfTheWeek(albums) => albums.truncate(len),
+            Editorial::PlaylistsOfTheWeek(playlists) => playlists.truncate(len),
+        }
+    }
+
+    /// Keep only items tagged with the genre. Items without genre info are
+    /// dropped too, since the API ignores `genre_ids` on some sections.
+    pub fn retain_genre(&mut self, genre_id: u64) {
+        match self {
+            Editorial::FeaturedFocus(items) => items.retain(|f| f.genre_ids.contains(&genre_id)),
+            Editorial::AlbumOfTheWeek(albums) => {
+                albums.retain(|a| a.genre.as_ref().is_some_and(|g| g.id == genre_id))
+            }
+            Editorial::PlaylistsOfTheWeek(playlists) => {
+                playlists.retain(|p| p.genres.iter().any(|g| g.id == genre_id))
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Editorial::FeaturedFocus(items) => items.len(),
+            Editorial::AlbumOfTheWeek(albums) => albums.len(),
+            Editorial::PlaylistsOfTheWeek(playlists) => playlists.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// Favorites container
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Favorites {
@@ -280,3 +1156,136 @@ pub struct Favorites {
     pub tracks: Option<SearchResultsPage<Track>>,
     pub artists: Option<SearchResultsPage<Artist>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_labels() {
+        let cases = [
+            ((5, 44.1, None), "MP3 320"),
+            ((6, 44.1, Some(16)), "CD 16/44.1"),
+            ((6, 48.0, Some(16)), "FLAC 16-bit/48kHz"),
+            ((7, 96.0, Some(24)), "FLAC 24-bit/96kHz"),
+            ((27, 192.0, Some(24)), "FLAC 24-bit/192kHz"),
+            ((27, 176.4, Some(24)), "FLAC 24-bit/176.4kHz"),
+            // Non-standard rates, Hz instead of kHz, missing fields
+            ((7, 47.999_9, Some(24)), "FLAC 24-bit/48kHz"),
+            ((7, 88_200.0, Some(24)), "FLAC 24-bit/88.2kHz"),
+            ((7, 0.0, None), "FLAC 24-bit"),
+            ((99, 0.0, None), "Unknown"),
+        ];
+        for ((format_id, rate, depth), expected) in cases {
+            assert_eq!(quality_label(format_id, rate, depth), expected, "{} {} {:?}", format_id, rate, depth);
+        }
+    }
+
+    #[test]
+    fn test_favorites_sort_params_and_order() {
+        assert_eq!(FavoritesSort::DateAddedAsc.query_params(), Some(("date_added", "asc")));
+        assert_eq!(FavoritesSort::Title.query_params(), None);
+
+        let mut page = serde_json::json!({
+            "albums": { "items": [
+                { "id": "a", "title": "Beta", "favorited_at": 200 },
+                { "id": "b", "title": "alpha" },
+                { "id": "c", "title": "Gamma", "favorited_at": 300 }
+            ]}
+        });
+
+        FavoritesSort::DateAddedAsc.apply(&mut page, "albums");
+        let ids: Vec<&str> = page["albums"]["items"].as_array().unwrap().iter().map(|i| i["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+        assert!(page["albums"]["items"][0]["favorited_at"].is_null());
+
+        FavoritesSort::Title.apply(&mut page, "albums");
+        let ids: Vec<&str> = page["albums"]["items"].as_array().unwrap().iter().map(|i| i["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+
+        FavoritesSort::DateAddedDesc.apply(&mut page, "albums");
+        let ids: Vec<&str> = page["albums"]["items"].as_array().unwrap().iter().map(|i| i["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_album_tracks_ordered_by_disc_then_track() {
+        let json = serde_json::json!({
+            "id": "abc",
+            "title": "Double Album",
+            "tracks": {
+                "total": 4,
+                "items": [
+                    { "id": 3, "track_number": 1, "media_number": 2 },
+                    { "id": 2, "track_number": 2, "media_number": 1 },
+                    { "id": 4, "track_number": 2, "media_number": 2 },
+                    { "id": 1, "track_number": 1, "media_number": 1 }
+                ]
+            }
+        });
+
+        let mut album: Album = serde_json::from_value(json).unwrap();
+        album.index_discs();
+
+        let ids: Vec<u64> = album.tracks.as_ref().unwrap().items.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert_eq!(
+            album.discs,
+            vec![
+                DiscBoundary { media_number: 1, start_index: 0, track_count: 2 },
+                DiscBoundary { media_number: 2, start_index: 2, track_count: 2 },
+            ]
+        );
+        assert!(!album.gapless);
+    }
+
+    #[test]
+    fn test_related_releases_from_series_and_box_set() {
+        let json = serde_json::json!({
+            "id": "vol2",
+            "title": "Bach Cantatas, Vol. 2",
+            "series": {
+                "title": "Bach Cantatas",
+                "albums": {
+                    "items": [
+                        { "id": "vol3", "title": "Vol. 3", "volume": 3 },
+                        { "id": "vol2", "title": "Vol. 2", "volume": 2 },
+                        { "id": "vol1", "title": "Vol. 1", "volume": 1, "image": { "large": "https://img/v1.jpg" } }
+                    ]
+                }
+            },
+            "box_set": {
+                "id": "box",
+                "title": "Complete Cantatas",
+                "albums": { "items": [{ "id": "vol1", "title": "Vol. 1", "volume": 1 }] }
+            }
+        });
+
+        let album: Album = serde_json::from_value(json).unwrap();
+        let related = album.related_releases();
+        let links: Vec<(&str, ReleaseRelation, Option<u32>)> =
+            related.iter().map(|r| (r.album_id.as_str(), r.relation, r.volume)).collect();
+        assert_eq!(
+            links,
+            vec![
+                ("box", ReleaseRelation::BoxSet, None),
+                ("vol1", ReleaseRelation::BoxSet, Some(1)),
+                ("vol3", ReleaseRelation::Series, Some(3)),
+            ]
+        );
+        assert_eq!(related[0].title, "Complete Cantatas");
+
+        // Round-trips through the album cache, and is empty without either
+        let cached: Album = serde_json::from_str(&serde_json::to_string(&album).unwrap()).unwrap();
+        assert_eq!(cached.related_releases(), related);
+        let plain: Album = serde_json::from_value(serde_json::json!({ "id": "x", "title": "X" })).unwrap();
+        assert!(plain.related_releases().is_empty());
+
+        // A block of another shape is ignored instead of failing the album
+        let odd: Album = serde_json::from_value(serde_json::json!({
+            "id": "x", "title": "X", "series": { "albums": [{ "id": "y" }] }
+        }))
+        .unwrap();
+        assert!(odd.related_releases().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! Region restriction diagnostics
+//!
+//! Qobuz licenses its catalog per country and serves an account the catalog
+//! of the country it was registered in. Travelling users see content vanish
+//! or refuse to stream with no explanation, so streams refused with a
+//! territorial restriction code are turned into a [`RegionMismatch`] that
+//! says whether the account region or the content itself is the problem.
+
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Stream restrictions caused by territorial licensing
+pub const REGION_RESTRICTIONS: [&str; 1] = ["TrackRestrictedByRightHolders"];
+
+/// Storefront root; it redirects to `/<country>-<lang>/...` for the caller's location
+pub const STOREFRONT_URL: &str = "https://www.qobuz.com/";
+
+const STOREFRONT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Which side of a region restriction is at fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionFault {
+    /// The content isn't licensed in the account's region
+    Content,
+    /// The user is outside their account's region
+    AccountRegion,
+}
+
+/// A region-restricted failure with both regions involved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegionMismatch {
+    /// Country the account is registered in (ISO 3166-1 alpha-2)
+    pub account_region: Option<String>,
+    /// Country the network connection appears to come from
+    pub network_region: Option<String>,
+    pub fault: RegionFault,
+}
+
+impl RegionMismatch {
+    pub fn new(account_region: Option<String>, network_region: Option<String>) -> Self {
+        let fault = match (&account_region, &network_region) {
+            (Some(account), Some(network)) if !account.eq_ignore_ascii_case(network) => {
+                RegionFault::AccountRegion
+            }
+            _ => RegionFault::Content,
+        };
+        Self { account_region, network_region, fault }
+    }
+}
+
+impl fmt::Display for RegionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.fault, &self.account_region, &self.network_region) {
+            (RegionFault::AccountRegion, Some(account), Some(network)) => write!(
+                f,
+                "Your Qobuz account is registered in {} but you are connecting from {}. \
+                 This content is only available from inside your account's region.",
+                account, network
+            ),
+            (_, Some(account), _) => write!(
+                f,
+                "This content is not licensed in {}, your account's region.",
+                account
+            ),
+            _ => write!(f, "This content is not licensed in your account's region."),
+        }
+    }
+}
+
+/// Country code from a storefront redirect such as `/gb-en/discover`
+pub fn region_from_storefront_path(location: &str) -> Option<String> {
+    let path = location.split("qobuz.com").last().unwrap_or(location);
+    let segment = path.trim_start_matches('/').split('/').next()?;
+    let (country, lang) = segment.split_once('-')?;
+    if country.len() == 2 && lang.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(country.to_uppercase())
+    } else {
+        None
+    }
+}
+
+/// Detect the network's country from where the storefront redirects to
+pub async fn detect_network_region() -> Option<String> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(STOREFRONT_TIMEOUT)
+        .build()
+        .ok()?;
+
+    let response = client.head(STOREFRONT_URL).send().await.ok()?;
+    let location = response.headers().get(reqwest::header::LOCATION)?.to_str().ok()?;
+    let region = region_from_storefront_path(location);
+    log::info!("Detected network region: {:?}", region);
+    region
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storefront_path_parsing() {
+        assert_eq!(region_from_storefront_path("/gb-en/discover"), Some("GB".to_string()));
+        assert_eq!(
+            region_from_storefront_path("https://www.qobuz.com/fr-fr/shop"),
+            Some("FR".to_string())
+        );
+        assert_eq!(region_from_storefront_path("/discover"), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Cancellable API requests
+//!
+//! A command that may run long (a search, an album fetch) can be given a
+//! request id by the frontend. The request future is registered under that
+//! id while it runs; `cancel` aborts it, which drops the in-flight `reqwest`
+//! future and closes its connection. The registry lives outside the client
+//! mutex so a cancel never waits behind the request it is cancelling, and
+//! `run_with` takes the client lock inside the request so a request still
+//! waiting for it can be cancelled too.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::future::{AbortHandle, Abortable};
+
+use super::error::{ApiError, Result};
+use super::QobuzClient;
+
+/// Applied to every API request unless the client is built with another
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requests in flight, by the id the caller gave them
+#[derive(Default)]
+pub struct InFlightRequests {
+    /// Abort handle per id, tagged with the run that registered it
+    handles: Mutex<HashMap<String, (u64, AbortHandle)>>,
+    next_run: AtomicU64,
+}
+
+/// Removes the registration when the request finishes, fails or is dropped
+struct Registration<'a> {
+    requests: &'a InFlightRequests,
+    request_id: String,
+    run: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut handles) = self.requests.handles.lock() {
+            // A newer request may have taken over the id
+            if handles.get(&self.request_id).is_some_and(|(run, _)| *run == self.run) {
+                handles.remove(&self.request_id);
+            }
+        }
+    }
+}
+
+impl InFlightRequests {
+    /// Run `request`, cancellable by `request_id` when one is given. A new
+    /// request under an id still in flight cancels the older one.
+    pub async fn run<T, F>(&self, request_id: Option<&str>, request: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let Some(request_id) = request_id else {
+            return request.await;
+        };
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let run = self.next_run.fetch_add(1, Ordering::Relaxed);
+        let previous = self
+            .handles
+            .lock()
+            .map_err(|e| ApiError::ApiResponse(format!("Lock error: {}", e)))?
+            .insert(request_id.to_string(), (run, handle));
+        if let Some((_, previous)) = previous {
+            log::debug!("Request {} replaced, cancelling the previous one", request_id);
+            previous.abort();
+        }
+        let _registration = Registration { requests: self, request_id: request_id.to_string(), run };
+
+        match Abortable::new(request, registration).await {
+            Ok(result) => result,
+            Err(_) => {
+                log::info!("Request {} cancelled", request_id);
+                Err(ApiError::Cancelled)
+            }
+        }
+    }
+
+    /// Like `run`, with a snapshot of the locked `client`. The lock is only
+    /// held to clone the client, and waiting for it counts as part of the
+    /// request.
+    pub async fn run_with<T, F, Fut>(
+        &self,
+        request_id: Option<&str>,
+        client: &tokio::sync::Mutex<QobuzClient>,
+        request: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(QobuzClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.run(request_id, async {
+            let client = client.lock().await.clone();
+            request(client).await
+        })
+        .await
+    }
+
+    /// Abort the request registered under `request_id`; false if none is
+    /// in flight
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let handle = self.handles.lock().ok().and_then(|mut handles| handles.remove(request_id));
+        match handle {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of registered requests
+    pub fn len(&self) -> usize {
+        self.handles.lock().map(|handles| handles.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::bundle::BundleTokens;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::{mock_client, TEST_APP_ID};
+    use crate::api::QobuzClient;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_cancel_aborts_slow_request_and_frees_it() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "id": "slow", "title": "Slow" }))
+                    .set_delay(Duration::from_secs(10)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = Arc::new(mock_client(&server));
+        let requests = client.requests();
+
+        let started = Instant::now();
+        let pending = tokio::spawn({
+            let client = client.clone();
+            let requests = requests.clone();
+            async move { requests.run(Some("album-view"), client.get_album("slow")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(requests.len(), 1);
+
+        assert!(requests.cancel("album-view"));
+        let result = tokio::time::timeout(Duration::from_secs(2), pending).await.unwrap().unwrap();
+        assert!(matches!(result, Err(ApiError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(requests.is_empty());
+        assert!(!requests.cancel("album-view"));
+
+        // Without an id the request simply runs (into the per-request timeout here)
+        let impatient = QobuzClient::builder()
+            .api_base_url(server.uri())
+            .tokens(BundleTokens { app_id: TEST_APP_ID.to_string(), secrets: Vec::new() })
+            .request_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let err = requests.run(None, impatient.get_album("slow")).await.unwrap_err();
+        assert!(matches!(err, ApiError::NetworkError(ref e) if e.is_timeout()), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_request_waiting_for_the_client_lock_can_be_cancelled() {
+        let server = MockServer::start().await;
+        let client = Arc::new(tokio::sync::Mutex::new(mock_client(&server)));
+        let requests = Arc::new(InFlightRequests::default());
+
+        // Another command holds the client
+        let busy = client.lock().await;
+        let pending = tokio::spawn({
+            let (client, requests) = (client.clone(), requests.clone());
+            async move {
+                requests
+                    .run_with(Some("search"), &client, |client| async move { client.get_album("1").await })
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(requests.cancel("search"));
+        let result = tokio::time::timeout(Duration::from_secs(2), pending).await.unwrap().unwrap();
+        assert!(matches!(result, Err(ApiError::Cancelled)));
+        assert!(server.received_requests().await.unwrap().is_empty());
+        drop(busy);
+    }
+}
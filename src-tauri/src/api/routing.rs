@@ -0,0 +1,45 @@
+//! Endpoint fallback for API requests
+//!
+//! The client can be given an [`EndpointRoute`]: the API endpoints to try in
+//! order and a record of how each attempt went (see
+//! `config::endpoint_priority`). A request that can't reach an endpoint, or
+//! gets a server error from it, is sent to the next one.
+
+use std::future::Future;
+use std::time::Instant;
+
+use super::error::{ApiError, Result};
+
+pub trait EndpointRoute: Send + Sync {
+    /// Base URLs to try, best first
+    fn try_order(&self) -> Vec<String>;
+    /// Record one attempt against `url`
+    fn record(&self, url: &str, success: bool, latency_ms: Option<u32>);
+}
+
+/// Run `request` against each endpoint in try order until one succeeds,
+/// recording the outcome of every attempt. The last error is returned when
+/// all of them fail.
+pub async fn try_in_order<T, F, Fut>(route: &dyn EndpointRoute, mut request: F) -> Result<T>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut last_error = None;
+    for url in route.try_order() {
+        let started = Instant::now();
+        let result = request(url.clone()).await;
+        let latency_ms = started.elapsed().as_millis().min(u32::MAX as u128) as u32;
+
+        route.record(&url, result.is_ok(), Some(latency_ms));
+        match result {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                log::warn!("Endpoint {} failed: {}", url, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| ApiError::ApiResponse("No API endpoints configured".to_string())))
+}
@@ -0,0 +1,232 @@
+//! Stream URL expiry
+//!
+//! Signed stream URLs are only valid for a limited time; the expiry is the
+//! `etsp` query parameter (Unix seconds). Resolved URLs are kept in a
+//! [`StreamUrlCache`] and re-requested once they are about to expire, so a
+//! track that waited in the queue doesn't fail when it finally plays. Only
+//! URLs of queued tracks are kept; the cache is emptied on logout.
+
+use reqwest::header::{HeaderName, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use reqwest::StatusCode;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use super::models::{AudioContainer, Quality, StreamProbe, StreamUrl};
+use super::QobuzClient;
+
+/// Re-request URLs expiring within this many seconds
+const REFRESH_MARGIN_SECS: i64 = 60;
+
+/// Age after which a URL without an `etsp` parameter is re-requested
+const MAX_AGE_SECS: i64 = 30 * 60;
+
+/// How often the background task looks for stale URLs
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Expiry of a signed stream URL (Unix seconds), from its `etsp` parameter
+pub fn url_expiry(url: &str) -> Option<i64> {
+    let url = reqwest::Url::parse(url).ok()?;
+    url.query_pairs()
+        .find(|(key, _)| key == "etsp")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Status codes the CDN answers with once a signed URL has expired
+pub fn is_expired_status(status: StatusCode) -> bool {
+    matches!(status, StatusCode::FORBIDDEN | StatusCode::GONE)
+}
+
+/// The file to stream from a getFileUrl response, honouring the preferred
+/// container when the same quality is offered in more than one.
+///
+/// The alternatives are assumed to come as a `files` array of objects shaped
+/// like the response itself (`url`, `mime_type`, optionally `format_id`).
+/// This isn't documented, so the main file is kept whenever the array is
+/// missing or malformed, and entries without a URL or of another
+/// `format_id` are ignored: a container preference never changes the
+/// quality streamed.
+pub fn choose_file(response: &serde_json::Value, preferred: Option<AudioContainer>) -> &serde_json::Value {
+    let Some(preferred) = preferred else {
+        return response;
+    };
+    let Some(alternatives) = response["files"].as_array() else {
+        log::debug!("No alternative files offered, streaming the main file");
+        return response;
+    };
+    let same_quality = |file: &&serde_json::Value| {
+        file["format_id"].is_null() || file["format_id"] == response["format_id"]
+    };
+    std::iter::once(response)
+        .chain(alternatives.iter().filter(same_quality))
+        .filter(|file| file["url"].as_str().is_some_and(|url| !url.is_empty()))
+        .find(|file| AudioContainer::from_mime(file["mime_type"].as_str().unwrap_or("")) == preferred)
+        .unwrap_or(response)
+}
+
+/// Read a `Range: bytes=0-0` response. A 206 carries the file size in
+/// `Content-Range` (`bytes 0-0/<total>`); a server that ignores the range
+/// answers 200 with the full `Content-Length`.
+pub fn probe_from_response(track_id: u64, quality: Quality, response: &reqwest::Response) -> StreamProbe {
+    let headers = response.headers();
+    let header = |name: HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+    let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+
+    let content_length = if partial {
+        header(CONTENT_RANGE)
+            .and_then(|range| range.rsplit_once('/'))
+            .and_then(|(_, total)| total.trim().parse().ok())
+    } else {
+        header(CONTENT_LENGTH).and_then(|length| length.trim().parse().ok())
+    };
+
+    StreamProbe {
+        track_id,
+        quality,
+        status: response.status().as_u16(),
+        content_type: header(CONTENT_TYPE).map(|s| s.to_string()),
+        content_length,
+        accepts_ranges: partial || header(ACCEPT_RANGES).is_some_and(|v| v.eq_ignore_ascii_case("bytes")),
+    }
+}
+
+struct CachedStreamUrl {
+    url: StreamUrl,
+    fetched_at: i64,
+}
+
+impl CachedStreamUrl {
+    fn is_stale(&self, now: i64) -> bool {
+        match url_expiry(&self.url.url) {
+            Some(expiry) => expiry - REFRESH_MARGIN_SECS <= now,
+            None => now - self.fetched_at >= MAX_AGE_SECS,
+        }
+    }
+}
+
+/// Resolved stream URLs per track and requested quality
+#[derive(Default)]
+pub struct StreamUrlCache {
+    entries: HashMap<(u64, Quality), CachedStreamUrl>,
+}
+
+impl StreamUrlCache {
+    /// The cached URL, unless it is missing or about to expire
+    pub fn get(&self, track_id: u64, quality: Quality, now: i64) -> Option<StreamUrl> {
+        self.entries
+            .get(&(track_id, quality))
+            .filter(|entry| !entry.is_stale(now))
+            .map(|entry| entry.url.clone())
+    }
+
+    pub fn insert(&mut self, track_id: u64, quality: Quality, url: StreamUrl, now: i64) {
+        self.entries.insert((track_id, quality), CachedStreamUrl { url, fetched_at: now });
+    }
+
+    /// Forget every URL of a track (e.g. after the CDN rejected it)
+    pub fn remove_track(&mut self, track_id: u64) {
+        self.entries.retain(|(id, _), _| *id != track_id);
+    }
+
+    /// Forget the URLs of every track not in `track_ids`
+    pub fn retain_tracks(&mut self, track_ids: &HashSet<u64>) {
+        self.entries.retain(|(id, _), _| track_ids.contains(id));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Keys of the entries that need a new URL
+    pub fn stale(&self, now: i64) -> Vec<(u64, Quality)> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.is_stale(now))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Periodically re-request the cached stream URLs of queued tracks (the ids
+/// `queued` returns) before they expire, dropping those of other tracks.
+/// The client is cloned out of its lock, so commands aren't held up by a refresh.
+pub async fn refresh_task<F>(client: Arc<Mutex<QobuzClient>>, queued: F)
+where
+    F: Fn() -> HashSet<u64>,
+{
+    log::info!("Stream URL refresh task started");
+    loop {
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+        let client = client.lock().await.clone();
+        client.retain_stream_urls(&queued()).await;
+        let stale = client.stale_stream_urls().await;
+
+        let mut refreshed = 0;
+        for (track_id, quality) in stale {
+            if client.refresh_stream_url(track_id, quality).await {
+                refreshed += 1;
+            }
+        }
+        if refreshed > 0 {
+            log::info!("Refreshed {} stale stream URLs", refreshed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url_expiring_at(expiry: i64) -> StreamUrl {
+        serde_json::from_value(serde_json::json!({
+            "url": format!("https://streaming.example.com/a.flac?etsp={}", expiry),
+            "format_id": 6, "mime_type": "audio/flac", "sampling_rate": 44.1,
+            "bit_depth": 16, "track_id": 1, "restrictions": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_only_queued_tracks_are_kept_and_refreshed() {
+        let mut cache = StreamUrlCache::default();
+        cache.insert(1, Quality::Lossless, url_expiring_at(1_000), 0);
+        cache.insert(2, Quality::Lossless, url_expiring_at(1_000), 0);
+        cache.insert(3, Quality::Lossless, url_expiring_at(10_000), 0);
+
+        cache.retain_tracks(&HashSet::from([1, 3]));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stale(950), vec![(1, Quality::Lossless)]);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_choose_file_falls_back_to_the_main_file() {
+        let mut response = serde_json::json!({
+            "url": "https://example.com/a.m4a", "format_id": 6, "mime_type": "audio/mp4"
+        });
+        let flac = Some(AudioContainer::Flac);
+        assert_eq!(choose_file(&response, flac), &response);
+
+        response["files"] = serde_json::json!({ "url": "https://example.com/a.flac" });
+        assert_eq!(choose_file(&response, flac)["url"], "https://example.com/a.m4a");
+
+        // Only a FLAC of the same quality, with a URL, is taken
+        response["files"] = serde_json::json!([
+            { "url": "https://example.com/hires.flac", "format_id": 7, "mime_type": "audio/flac" },
+            { "format_id": 6, "mime_type": "audio/flac" },
+            { "url": "https://example.com/a.flac", "format_id": 6, "mime_type": "audio/flac" }
+        ]);
+        assert_eq!(choose_file(&response, flac)["url"], "https://example.com/a.flac");
+    }
+}
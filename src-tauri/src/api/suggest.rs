@@ -0,0 +1,187 @@
+//! Search suggestions (type-ahead)
+//!
+//! Suggestions are requested on every keystroke, so they have to be cheap:
+//! queries shorter than [`MIN_QUERY_CHARS`] never reach the network, and
+//! answers are kept for [`SUGGESTION_TTL`] so retyping or deleting a
+//! character is served from memory. Suggestions are localized, so answers
+//! are kept per locale.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Shorter queries get no suggestions
+pub const MIN_QUERY_CHARS: usize = 2;
+
+/// How long a suggestion list is reused
+pub const SUGGESTION_TTL: Duration = Duration::from_secs(60);
+
+/// Most queries kept in the cache
+const MAX_CACHED_QUERIES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionKind {
+    Artist,
+    Album,
+    Track,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub text: String,
+    pub kind: SuggestionKind,
+}
+
+/// Cache key for a query, or None when it is too short to suggest anything
+pub fn normalize_query(query: &str) -> Option<String> {
+    let query = query.trim().to_lowercase();
+    (query.chars().count() >= MIN_QUERY_CHARS).then_some(query)
+}
+
+/// Parse a suggest response: artists first, then albums, then tracks
+pub fn parse_suggestions(response: &Value) -> Vec<Suggestion> {
+    let sections = [
+        ("artists", "name", SuggestionKind::Artist),
+        ("albums", "title", SuggestionKind::Album),
+        ("tracks", "title", SuggestionKind::Track),
+    ];
+
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    for (section, field, kind) in sections {
+        let items = response
+            .get(section)
+            .and_then(|s| s.get("items"))
+            .and_then(|i| i.as_array());
+        for item in items.into_iter().flatten() {
+            let Some(text) = item.get(field).and_then(|v| v.as_str()).map(str::trim) else {
+                continue;
+            };
+            if text.is_empty() || suggestions.iter().any(|s| s.kind == kind && s.text == text) {
+                continue;
+            }
+            suggestions.push(Suggestion { text: text.to_string(), kind });
+        }
+    }
+    suggestions
+}
+
+/// Recent suggestion lists per locale and normalized query
+#[derive(Default)]
+pub struct SuggestionCache {
+    entries: HashMap<(String, String), (Instant, Vec<Suggestion>)>,
+}
+
+impl SuggestionCache {
+    pub fn get(&self, locale: &str, query: &str, now: Instant) -> Option<Vec<Suggestion>> {
+        self.entries
+            .get(&(locale.to_string(), query.to_string()))
+            .filter(|(fetched_at, _)| now.duration_since(*fetched_at) < SUGGESTION_TTL)
+            .map(|(_, suggestions)| suggestions.clone())
+    }
+
+    pub fn insert(&mut self, locale: String, query: String, suggestions: Vec<Suggestion>, now: Instant) {
+        self.entries
+            .retain(|_, (fetched_at, _)| now.duration_since(*fetched_at) < SUGGESTION_TTL);
+        if self.entries.len() >= MAX_CACHED_QUERIES {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (fetched_at, _))| *fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert((locale, query), (now, suggestions));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::mock_client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_suggestions_parsed_cached_and_short_queries_skipped() {
+        let server = MockServer::start().await;
+        // Only one request reaches the server
+        Mock::given(method("GET"))
+            .and(path(paths::CATALOG_AUTOSUGGEST))
+            .and(query_param("query", "mile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "artists": { "items": [{ "id": 1, "name": "Miles Davis" }] },
+                "albums": { "items": [
+                    { "id": "a", "title": "Milestones" },
+                    { "id": "b", "title": "Milestones" }
+                ] },
+                "tracks": { "items": [{ "id": 3, "title": "Miles Runs the Voodoo Down" }, { "id": 4 }] }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+
+        let expected = vec![
+            Suggestion { text: "Miles Davis".to_string(), kind: SuggestionKind::Artist },
+            Suggestion { text: "Milestones".to_string(), kind: SuggestionKind::Album },
+            Suggestion { text: "Miles Runs the Voodoo Down".to_string(), kind: SuggestionKind::Track },
+        ];
+        assert_eq!(client.get_suggestions("mile").await.unwrap(), expected);
+        // Same query with different case/spacing comes from the cache
+        assert_eq!(client.get_suggestions(" Mile ").await.unwrap(), expected);
+
+        for query in ["", " ", "m", " M "] {
+            assert!(client.get_suggestions(query).await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_are_per_locale_and_errors_are_not_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::CATALOG_AUTOSUGGEST))
+            .and(query_param("lang", "en"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "artists": { "items": [{ "id": 1, "name": "Édith Piaf" }] }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // The French answer fails once, then succeeds
+        Mock::given(method("GET"))
+            .and(path(paths::CATALOG_AUTOSUGGEST))
+            .and(query_param("lang", "fr"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::CATALOG_AUTOSUGGEST))
+            .and(query_param("lang", "fr"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": { "items": [{ "id": "a", "title": "La Vie en rose" }] }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let english = client.get_suggestions("piaf").await.unwrap();
+        assert_eq!(english[0].kind, SuggestionKind::Artist);
+
+        client.set_locale("fr".to_string()).await;
+        assert!(client.get_suggestions("piaf").await.is_err());
+        let french = client.get_suggestions("piaf").await.unwrap();
+        assert_eq!(french[0].text, "La Vie en rose");
+
+        client.set_locale("en".to_string()).await;
+        assert_eq!(client.get_suggestions("piaf").await.unwrap(), english);
+    }
+}
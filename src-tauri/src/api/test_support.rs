@@ -0,0 +1,63 @@
+//! Fixtures shared by tests that run the client against a mock server
+
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::bundle::BundleTokens;
+use super::endpoints::paths;
+use super::models::{AccountFeatures, SubscriptionInfo, UserSession};
+use super::QobuzClient;
+
+pub const TEST_APP_ID: &str = "123456789";
+pub const TEST_SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+pub fn test_tokens() -> BundleTokens {
+    BundleTokens {
+        app_id: TEST_APP_ID.to_string(),
+        secrets: vec![TEST_SECRET.to_string()],
+    }
+}
+
+/// Client pointed at `server` for both the API and the web bundle, with
+/// tokens already extracted
+pub fn mock_client(server: &MockServer) -> QobuzClient {
+    QobuzClient::builder()
+        .api_base_url(server.uri())
+        .bundle_base_url(server.uri())
+        .tokens(test_tokens())
+        .build()
+        .unwrap()
+}
+
+/// Session of an active subscriber; override fields with struct update syntax
+pub fn test_session() -> UserSession {
+    UserSession {
+        user_auth_token: "token".to_string(),
+        user_id: 1,
+        email: String::new(),
+        display_name: String::new(),
+        subscription_label: String::new(),
+        subscription: SubscriptionInfo { active: true, end_date: None },
+        country_code: None,
+        zone: None,
+        device_id: None,
+        features: AccountFeatures::default(),
+    }
+}
+
+/// `mock_client` with `test_session` installed
+pub async fn logged_in_client(server: &MockServer) -> QobuzClient {
+    let client = mock_client(server);
+    client.set_session(test_session()).await;
+    client
+}
+
+/// Accept the secret probe (a format 5 file URL request) signed requests start with
+pub async fn mount_secret_probe(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path(paths::TRACK_GET_FILE_URL))
+        .and(query_param("format_id", "5"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+        .mount(server)
+        .await;
+}
@@ -0,0 +1,234 @@
+//! Duplicate favorite tracks
+//!
+//! The same recording often ends up favorited more than once, under the
+//! ids of different releases (album and compilation, reissues). Favorites
+//! sharing an ISRC are the same recording. Tracks without an ISRC match on
+//! title, version, artist and duration instead, and only when all of them
+//! agree: a live take, an edit or a remix keeps its own version string or
+//! length, so it's never folded into the original.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest difference in duration between two tracks matched by metadata
+const DURATION_TOLERANCE_SECS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMatch {
+    Isrc,
+    Metadata,
+}
+
+/// A favorite track, as shown when reviewing duplicates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FavoriteTrackRef {
+    pub id: u64,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: u64,
+    pub isrc: Option<String>,
+}
+
+/// Favorites holding the same recording: `canonical` is the one to keep
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub canonical: FavoriteTrackRef,
+    pub duplicates: Vec<FavoriteTrackRef>,
+    pub matched_by: DuplicateMatch,
+}
+
+/// What's compared of one favorite
+struct Candidate<'a> {
+    item: &'a Value,
+    id: u64,
+    isrc: Option<String>,
+    /// Normalized title, version and artist
+    identity: (String, String, String),
+    duration: u64,
+}
+
+impl<'a> Candidate<'a> {
+    fn from_item(item: &'a Value) -> Option<Self> {
+        let id = item["id"].as_u64()?;
+        let isrc = item["isrc"]
+            .as_str()
+            .map(|isrc| isrc.trim().to_ascii_uppercase())
+            .filter(|isrc| !isrc.is_empty());
+        let text = |value: &Value| normalize(value.as_str().unwrap_or(""));
+        Some(Self {
+            item,
+            id,
+            isrc,
+            identity: (text(&item["title"]), text(&item["version"]), text(&item["performer"]["name"])),
+            duration: item["duration"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// Same title, version and artist, and about the same length
+    fn same_metadata(&self, other: &Candidate) -> bool {
+        !self.identity.0.is_empty()
+            && !self.identity.2.is_empty()
+            && self.identity == other.identity
+            && self.duration > 0
+            && other.duration > 0
+            && self.duration.abs_diff(other.duration) <= DURATION_TOLERANCE_SECS
+    }
+
+    /// Keep streamable copies first, then the best quality, then the oldest id
+    fn rank(&self) -> (bool, u64, u64, std::cmp::Reverse<u64>) {
+        let bit_depth = self.item["maximum_bit_depth"].as_u64().unwrap_or(0);
+        let sample_rate = self.item["maximum_sampling_rate"].as_f64().unwrap_or(0.0);
+        (
+            self.item["streamable"].as_bool().unwrap_or(false),
+            bit_depth,
+            (sample_rate * 1000.0) as u64,
+            std::cmp::Reverse(self.id),
+        )
+    }
+
+    fn to_ref(&self) -> FavoriteTrackRef {
+        let text = |value: &Value| value.as_str().map(|s| s.to_string());
+        FavoriteTrackRef {
+            id: self.id,
+            title: self.item["title"].as_str().unwrap_or("").to_string(),
+            artist: text(&self.item["performer"]["name"]),
+            album: text(&self.item["album"]["title"]),
+            duration: self.duration,
+            isrc: self.isrc.clone(),
+        }
+    }
+}
+
+/// Lowercase words, without punctuation or extra whitespace
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Group favorite tracks holding the same recording. Tracks with different
+/// ISRCs are never grouped; a track without one joins the first group whose
+/// tracks all match its metadata.
+pub fn find_duplicates(items: &[Value]) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<(DuplicateMatch, Vec<Candidate>)> = Vec::new();
+    let mut by_isrc: HashMap<String, usize> = HashMap::new();
+    let mut without_isrc = Vec::new();
+
+    for candidate in items.iter().filter_map(Candidate::from_item) {
+        match candidate.isrc.clone() {
+            Some(isrc) => match by_isrc.get(&isrc) {
+                Some(&index) => groups[index].1.push(candidate),
+                None => {
+                    by_isrc.insert(isrc, groups.len());
+                    groups.push((DuplicateMatch::Isrc, vec![candidate]));
+                }
+            },
+            None => without_isrc.push(candidate),
+        }
+    }
+    for candidate in without_isrc {
+        let group = groups
+            .iter_mut()
+            .find(|(_, members)| members.iter().all(|member| member.same_metadata(&candidate)));
+        match group {
+            Some((matched_by, members)) => {
+                *matched_by = DuplicateMatch::Metadata;
+                members.push(candidate);
+            }
+            None => groups.push((DuplicateMatch::Metadata, vec![candidate])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(matched_by, mut members)| {
+            members.sort_by_key(|member| std::cmp::Reverse(member.rank()));
+            let canonical = members.remove(0).to_ref();
+            DuplicateGroup {
+                canonical,
+                duplicates: members.iter().map(Candidate::to_ref).collect(),
+                matched_by,
+            }
+        })
+        .collect()
+}
+
+/// Ids of `group` safe to unfavorite: only those the current favorites
+/// still group with the same canonical track
+pub fn merge_plan(group: &DuplicateGroup, current: &[DuplicateGroup]) -> Vec<u64> {
+    let Some(confirmed) = current.iter().find(|found| found.canonical.id == group.canonical.id) else {
+        return Vec::new();
+    };
+    group
+        .duplicates
+        .iter()
+        .map(|duplicate| duplicate.id)
+        .filter(|id| *id != group.canonical.id)
+        .filter(|id| confirmed.duplicates.iter().any(|duplicate| duplicate.id == *id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn track(id: u64, title: &str, isrc: Option<&str>, duration: u64, bit_depth: u64) -> Value {
+        json!({
+            "id": id,
+            "title": title,
+            "isrc": isrc,
+            "duration": duration,
+            "performer": { "name": "Nina Simone" },
+            "album": { "title": format!("Album {}", id) },
+            "maximum_bit_depth": bit_depth,
+            "streamable": true,
+        })
+    }
+
+    #[test]
+    fn test_isrc_and_metadata_duplicates_grouped_and_merged_conservatively() {
+        let mut live = track(6, "Feeling Good", None, 175, 16);
+        live["version"] = json!("Live");
+        let favorites = vec![
+            track(1, "Feeling Good", Some("usrc16500123"), 174, 16),
+            // Same recording on a compilation, in hi-res
+            track(2, "Feeling Good", Some("USRC16500123"), 174, 24),
+            // Remaster with its own ISRC: left alone
+            track(3, "Feeling Good", Some("USRC19900001"), 174, 24),
+            // No ISRC, same title/artist and length
+            track(4, "Sinnerman", None, 622, 16),
+            track(5, "Sinnerman!", None, 621, 16),
+            // Near-duplicates: a live version, and an edit of different length
+            live,
+            track(7, "Sinnerman", None, 250, 16),
+        ];
+
+        let groups = find_duplicates(&favorites);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].matched_by, DuplicateMatch::Isrc);
+        assert_eq!(groups[0].canonical.id, 2);
+        assert_eq!(groups[0].duplicates.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(groups[1].matched_by, DuplicateMatch::Metadata);
+        assert_eq!(groups[1].canonical.id, 4);
+        assert_eq!(groups[1].duplicates.iter().map(|t| t.id).collect::<Vec<_>>(), vec![5]);
+
+        // A group edited to include other tracks only removes the confirmed duplicate
+        let mut tampered = groups[1].clone();
+        tampered.duplicates.push(groups[1].canonical.clone());
+        tampered.duplicates.push(FavoriteTrackRef { id: 7, ..groups[1].canonical.clone() });
+        assert_eq!(merge_plan(&tampered, &groups), vec![5]);
+
+        // Nothing is removed once the group no longer exists
+        let after_merge = find_duplicates(&favorites[2..]);
+        assert!(merge_plan(&groups[0], &after_merge).is_empty());
+    }
+}
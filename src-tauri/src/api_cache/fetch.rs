@@ -0,0 +1,242 @@
+//! Fetch strategies
+//!
+//! Whether a cached fetch (album, artist, playlist, search page) asks the
+//! API cache or the network first, and whether an unreachable API falls
+//! back to the cache. Only connection failures and timeouts fall back: an
+//! error from the API itself (not found, unauthorized) is an answer, and
+//! stale data would hide it.
+
+use std::future::Future;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::ApiCache;
+use crate::api::error::ApiError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchStrategy {
+    /// An unexpired cache entry, else the network, else an expired entry
+    #[default]
+    CacheFirst,
+    /// The network, else a cache entry of any age
+    NetworkFirst,
+    /// A cache entry of any age; the network is never used
+    CacheOnly,
+    /// The network only. Responses are still cached.
+    NetworkOnly,
+}
+
+impl FetchStrategy {
+    /// Whether an unreachable API is answered from the cache
+    pub fn falls_back_to_cache(self) -> bool {
+        matches!(self, FetchStrategy::CacheFirst | FetchStrategy::NetworkFirst)
+    }
+}
+
+/// Age of the cache entries a read accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Within the entry type's TTL
+    Fresh,
+    /// Expired entries too
+    Any,
+}
+
+impl Freshness {
+    /// `ttl_secs` argument for the `ApiCache` getters
+    pub fn ttl(self) -> Option<i64> {
+        match self {
+            Freshness::Fresh => None,
+            Freshness::Any => Some(i64::MAX),
+        }
+    }
+}
+
+/// A fetched value, flagged when it came from the cache
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fetched<T> {
+    pub value: T,
+    pub from_cache: bool,
+}
+
+impl<T> Fetched<T> {
+    fn cached(value: T) -> Self {
+        Self { value, from_cache: true }
+    }
+}
+
+/// The API couldn't be reached at all
+pub fn is_unreachable(error: &ApiError) -> bool {
+    matches!(error, ApiError::NetworkError(e) if e.is_connect() || e.is_timeout())
+}
+
+/// Parse a cached JSON entry
+pub fn parse_cached<T: DeserializeOwned>(cached: Option<String>) -> Result<Option<T>, String> {
+    cached
+        .map(|data| serde_json::from_str(&data).map_err(|e| format!("Failed to parse cached data: {}", e)))
+        .transpose()
+}
+
+/// A value as JSON, for the cache
+pub fn to_cache_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("Failed to serialize for the cache: {}", e))
+}
+
+/// Get a value from the cache (`read`) or the network (`fetch`) as
+/// `strategy` says. Values from the network are cached with `write`.
+pub async fn fetch_cached<T, F, Fut>(
+    strategy: FetchStrategy,
+    cache: &Mutex<ApiCache>,
+    read: impl Fn(&ApiCache, Freshness) -> Result<Option<T>, String>,
+    write: impl FnOnce(&ApiCache, &T) -> Result<(), String>,
+    fetch: F,
+) -> Result<Fetched<T>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = crate::api::error::Result<T>>,
+{
+    match strategy {
+        FetchStrategy::CacheFirst => {
+            if let Some(value) = read(&*cache.lock().await, Freshness::Fresh)? {
+                return Ok(Fetched::cached(value));
+            }
+        }
+        FetchStrategy::CacheOnly => {
+            return read(&*cache.lock().await, Freshness::Any)?
+                .map(Fetched::cached)
+                .ok_or_else(|| "Not in the cache".to_string());
+        }
+        FetchStrategy::NetworkFirst | FetchStrategy::NetworkOnly => {}
+    }
+
+    match fetch().await {
+        Ok(value) => {
+            if let Err(e) = write(&*cache.lock().await, &value) {
+                log::warn!("Failed to cache response: {}", e);
+            }
+            Ok(Fetched { value, from_cache: false })
+        }
+        Err(e) if strategy.falls_back_to_cache() && is_unreachable(&e) => {
+            log::warn!("API unreachable ({}), trying the cache", e);
+            read(&*cache.lock().await, Freshness::Any)?
+                .map(Fetched::cached)
+                .ok_or_else(|| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::path::Path;
+
+    #[derive(Clone, Copy, Debug)]
+    enum Cached {
+        Nothing,
+        Fresh,
+        Expired,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Network {
+        Up,
+        Unreachable,
+        Refused,
+    }
+
+    async fn unreachable() -> ApiError {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let error = client.get("http://127.0.0.1:9/").send().await.unwrap_err();
+        assert!(error.is_connect());
+        ApiError::NetworkError(error)
+    }
+
+    /// Fetch album "1" with `strategy`: the value and where it came from,
+    /// and whether the network was asked
+    async fn run(strategy: FetchStrategy, cached: Cached, network: Network) -> (Result<(String, bool), String>, bool) {
+        let cache = ApiCache::new(Path::new(":memory:")).unwrap();
+        match cached {
+            Cached::Nothing => {}
+            Cached::Fresh => cache.set_album("1", "cached").unwrap(),
+            Cached::Expired => {
+                cache.set_album("1", "expired").unwrap();
+                cache.conn.execute("UPDATE cached_albums SET fetched_at = 0", []).unwrap();
+            }
+        }
+        let cache = Mutex::new(cache);
+
+        let error = match network {
+            Network::Up => None,
+            Network::Unreachable => Some(unreachable().await),
+            Network::Refused => Some(ApiError::ApiResponse("Album not found".to_string())),
+        };
+        let fetched = Cell::new(false);
+        let was_fetched = &fetched;
+        let result = fetch_cached(
+            strategy,
+            &cache,
+            |cache, freshness| cache.get_album("1", freshness.ttl()),
+            |cache, value: &String| cache.set_album("1", value),
+            || async move {
+                was_fetched.set(true);
+                match error {
+                    None => Ok("network".to_string()),
+                    Some(e) => Err(e),
+                }
+            },
+        )
+        .await;
+
+        if matches!(result, Ok(Fetched { from_cache: false, .. })) {
+            let stored = cache.lock().await.get_album("1", None).unwrap();
+            assert_eq!(stored.as_deref(), Some("network"));
+        }
+        (result.map(|f| (f.value, f.from_cache)), fetched.get())
+    }
+
+    #[tokio::test]
+    async fn test_each_strategy_on_hit_miss_and_network_failure() {
+        use FetchStrategy::*;
+        let network = |value: &str| Ok((value.to_string(), false));
+        let cache = |value: &str| Ok((value.to_string(), true));
+
+        let cases = [
+            // Hit: the cache strategies answer without asking the network
+            (CacheFirst, Cached::Fresh, Network::Up, cache("cached"), false),
+            (NetworkFirst, Cached::Fresh, Network::Up, network("network"), true),
+            (CacheOnly, Cached::Fresh, Network::Up, cache("cached"), false),
+            (NetworkOnly, Cached::Fresh, Network::Up, network("network"), true),
+            // Miss (an expired entry doesn't count as a hit, except offline)
+            (CacheFirst, Cached::Expired, Network::Up, network("network"), true),
+            (NetworkFirst, Cached::Nothing, Network::Up, network("network"), true),
+            (CacheOnly, Cached::Expired, Network::Up, cache("expired"), false),
+            (CacheOnly, Cached::Nothing, Network::Up, Err("Not in the cache".to_string()), false),
+            (NetworkOnly, Cached::Nothing, Network::Up, network("network"), true),
+            // Network failure: falls back to any cached entry, if allowed
+            (CacheFirst, Cached::Expired, Network::Unreachable, cache("expired"), true),
+            (NetworkFirst, Cached::Fresh, Network::Unreachable, cache("cached"), true),
+            (CacheOnly, Cached::Expired, Network::Unreachable, cache("expired"), false),
+            (CacheFirst, Cached::Nothing, Network::Unreachable, Err(String::new()), true),
+            (NetworkFirst, Cached::Nothing, Network::Unreachable, Err(String::new()), true),
+            (NetworkOnly, Cached::Fresh, Network::Unreachable, Err(String::new()), true),
+            // An error from the API itself is never masked by the cache
+            (NetworkFirst, Cached::Fresh, Network::Refused, Err("API error: Album not found".to_string()), true),
+        ];
+
+        for (strategy, cached, network_state, expected, expect_fetch) in cases {
+            let (result, fetched) = run(strategy, cached, network_state).await;
+            let case = format!("{:?} / {:?} / {:?}", strategy, cached, network_state);
+            match (&result, &expected) {
+                // Network errors carry the OS message
+                (Err(e), Err(expected)) if expected.is_empty() => assert!(e.starts_with("Network error"), "{}: {}", case, e),
+                _ => assert_eq!(result, expected, "{}", case),
+            }
+            assert_eq!(fetched, expect_fetch, "{}", case);
+        }
+    }
+}
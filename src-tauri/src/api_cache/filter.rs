@@ -0,0 +1,185 @@
+//! Local filtering of synced favorites
+//!
+//! Genre, release year and quality are pulled out of each favorite when it
+//! is synced (see `ApiCache::apply_favorites_delta`) into indexed columns,
+//! with one `synced_favorite_genres` row per genre so items that belong to
+//! several genres (or a sub-genre and its parents) match any of them.
+//! Favorites synced before these columns existed only match an empty filter
+//! until the next full sync.
+
+use chrono::Datelike;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use super::ApiCache;
+use crate::api::Quality;
+
+/// Filter for the synced favorites view. Empty fields don't filter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FavoriteFilter {
+    /// Match items in any of these genres
+    pub genres: Vec<u64>,
+    /// Inclusive release year range, e.g. (1990, 1999) for the nineties
+    pub year_range: Option<(i32, i32)>,
+    /// Lowest quality the item must be available in
+    pub quality_min: Option<Quality>,
+}
+
+/// Filterable fields of one favorite
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FavoriteFacets {
+    pub genre_ids: Vec<u64>,
+    pub release_year: Option<i32>,
+    pub quality: Option<Quality>,
+}
+
+impl FavoriteFacets {
+    /// Facets of an album or track (tracks use their album's metadata)
+    pub fn from_item(item: &Value) -> Self {
+        let album = item.get("album").filter(|a| a.is_object());
+        let field = |key: &str| {
+            item.get(key)
+                .filter(|v| !v.is_null())
+                .or_else(|| album.and_then(|a| a.get(key)).filter(|v| !v.is_null()))
+        };
+
+        let mut genre_ids = BTreeSet::new();
+        if let Some(genre) = field("genre") {
+            genre_ids.extend(genre["id"].as_u64());
+            // Parent genres, so filtering by "Jazz" also matches its sub-genres
+            if let Some(path) = genre["path"].as_array() {
+                genre_ids.extend(path.iter().filter_map(|id| id.as_u64()));
+            }
+        }
+        if let Some(genres) = field("genres").and_then(|g| g.as_array()) {
+            genre_ids.extend(genres.iter().filter_map(|g| g["id"].as_u64()));
+        }
+
+        let release_year = field("release_date_original")
+            .and_then(|d| d.as_str())
+            .and_then(|d| d.get(..4))
+            .and_then(|y| y.parse().ok())
+            .or_else(|| {
+                field("released_at")
+                    .and_then(|t| t.as_i64())
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|d| d.year())
+            });
+
+        let bit_depth = field("maximum_bit_depth").and_then(|v| v.as_u64());
+        let sampling_rate = field("maximum_sampling_rate").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let quality = bit_depth.map(|depth| match depth {
+            d if d > 16 && sampling_rate > 96.0 => Quality::UltraHiRes,
+            d if d > 16 => Quality::HiRes,
+            _ => Quality::Lossless,
+        });
+
+        Self {
+            genre_ids: genre_ids.into_iter().collect(),
+            release_year,
+            quality,
+        }
+    }
+}
+
+impl ApiCache {
+    /// The user's synced favorites of one type matching `filter`, most
+    /// recently added first
+    pub fn filter_favorites(&self, user_id: u64, fav_type: &str, filter: &FavoriteFilter) -> Result<Vec<Value>, String> {
+        let mut sql = String::from("SELECT f.data FROM synced_favorites f WHERE f.user_id = ? AND f.fav_type = ?");
+        let mut args = vec![SqlValue::Integer(user_id as i64), SqlValue::Text(fav_type.to_string())];
+
+        if let Some((from, to)) = filter.year_range {
+            sql.push_str(" AND f.release_year BETWEEN ? AND ?");
+            args.push(SqlValue::Integer(from.min(to) as i64));
+            args.push(SqlValue::Integer(from.max(to) as i64));
+        }
+        if let Some(quality) = filter.quality_min {
+            // Format ids grow with quality (5 < 6 < 7 < 27)
+            sql.push_str(" AND f.quality >= ?");
+            args.push(SqlValue::Integer(quality.id() as i64));
+        }
+        if !filter.genres.is_empty() {
+            let placeholders = vec!["?"; filter.genres.len()].join(", ");
+            sql.push_str(&format!(
+                " AND EXISTS (SELECT 1 FROM synced_favorite_genres g
+                   WHERE g.user_id = f.user_id AND g.fav_type = f.fav_type AND g.item_id = f.item_id
+                   AND g.genre_id IN ({}))",
+                placeholders
+            ));
+            args.extend(filter.genres.iter().map(|id| SqlValue::Integer(*id as i64)));
+        }
+        sql.push_str(" ORDER BY f.favorited_at DESC, f.item_id");
+
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to prepare favorites filter: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(args), |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to filter favorites: {}", e))?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let data = row.map_err(|e| format!("Failed to read synced favorite row: {}", e))?;
+            if let Ok(item) = serde_json::from_str(&data) {
+                items.push(item);
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_filter_by_genre_decade_and_quality() {
+        let mut cache = ApiCache::new(Path::new(":memory:")).unwrap();
+        let albums = [
+            // Vocal jazz is a sub-genre of jazz (112)
+            serde_json::json!({ "id": "a", "favorited_at": 5, "release_date_original": "1994-03-01",
+                "genre": { "id": 119, "name": "Vocal Jazz", "path": [112, 119] },
+                "maximum_bit_depth": 24, "maximum_sampling_rate": 96 }),
+            serde_json::json!({ "id": "b", "favorited_at": 4, "release_date_original": "1999-12-31",
+                "genre": { "id": 112, "name": "Jazz" }, "maximum_bit_depth": 16, "maximum_sampling_rate": 44.1 }),
+            serde_json::json!({ "id": "c", "favorited_at": 3, "release_date_original": "2001-01-01",
+                "genre": { "id": 112, "name": "Jazz" }, "maximum_bit_depth": 24, "maximum_sampling_rate": 192 }),
+            serde_json::json!({ "id": "d", "favorited_at": 2, "release_date_original": "1995-06-01",
+                "genre": { "id": 113, "name": "Rock" }, "maximum_bit_depth": 24, "maximum_sampling_rate": 48 }),
+        ];
+        cache.apply_favorites_delta(1, "albums", &albums, &[], true, 10).unwrap();
+
+        // Multi-genre track: tagged on the track and through its album
+        let track = serde_json::json!({ "id": 7, "favorited_at": 1,
+            "genres": [{ "id": 113 }],
+            "album": { "genre": { "id": 112 }, "release_date_original": "1992-01-01", "maximum_bit_depth": 16 } });
+        cache.apply_favorites_delta(1, "tracks", &[track], &[], true, 10).unwrap();
+
+        let ids = |items: Vec<Value>| -> Vec<String> {
+            items.iter().filter_map(crate::api_cache::favorite_item_id).collect()
+        };
+
+        let nineties_jazz = FavoriteFilter { genres: vec![112], year_range: Some((1990, 1999)), quality_min: None };
+        assert_eq!(ids(cache.filter_favorites(1, "albums", &nineties_jazz).unwrap()), vec!["a", "b"]);
+
+        let hires = FavoriteFilter { quality_min: Some(Quality::HiRes), ..Default::default() };
+        assert_eq!(ids(cache.filter_favorites(1, "albums", &hires).unwrap()), vec!["a", "c", "d"]);
+
+        let rock_or_jazz_hires = FavoriteFilter { genres: vec![113, 119], quality_min: Some(Quality::HiRes), year_range: None };
+        assert_eq!(ids(cache.filter_favorites(1, "albums", &rock_or_jazz_hires).unwrap()), vec!["a", "d"]);
+
+        for genre in [112, 113] {
+            let filter = FavoriteFilter { genres: vec![genre], ..Default::default() };
+            assert_eq!(ids(cache.filter_favorites(1, "tracks", &filter).unwrap()), vec!["7"]);
+        }
+
+        assert_eq!(cache.filter_favorites(1, "albums", &FavoriteFilter::default()).unwrap().len(), 4);
+    }
+}
@@ -3,15 +3,27 @@
 //! SQLite-based cache for API responses (albums, artists, etc.)
 //! with TTL-based expiration.
 
+pub mod duplicates;
+pub mod fetch;
+pub mod filter;
+pub mod sync;
+pub mod warm;
+
 use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
+
+use crate::api::QobuzClient;
 
 /// API cache state shared across commands
 pub struct ApiCacheState {
     pub cache: Arc<Mutex<ApiCache>>,
+    /// Cancels the running cache warm-up, if any
+    warming: std::sync::Mutex<Option<watch::Sender<bool>>>,
 }
 
 impl ApiCacheState {
@@ -31,13 +43,54 @@ impl ApiCacheState {
 
         Ok(Self {
             cache: Arc::new(Mutex::new(cache)),
+            warming: std::sync::Mutex::new(None),
         })
     }
+
+    /// Start prefetching favorites/playlists in the background,
+    /// cancelling any warm-up still in progress.
+    pub fn start_warming(&self, client: Arc<Mutex<QobuzClient>>) {
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        if let Ok(mut warming) = self.warming.lock() {
+            if let Some(previous) = warming.replace(cancel_tx) {
+                let _ = previous.send(true);
+            }
+        }
+
+        log::info!("Starting API cache warm-up");
+        tauri::async_runtime::spawn(warm::warm_cache(
+            client,
+            self.cache.clone(),
+            warm::WARM_REQUEST_SPACING,
+            cancel_rx,
+        ));
+    }
+
+    /// Cancel a running warm-up (e.g. on logout)
+    pub fn cancel_warming(&self) {
+        if let Ok(mut warming) = self.warming.lock() {
+            if let Some(cancel) = warming.take() {
+                let _ = cancel.send(true);
+            }
+        }
+    }
 }
 
 /// Default TTL for cached items (24 hours)
 const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
 
+/// TTL for cached favorites pages (10 minutes) - favorites change often
+pub const FAVORITES_TTL_SECS: i64 = 10 * 60;
+
+/// TTL for the cached user playlist list and playlists (10 minutes)
+pub const USER_PLAYLISTS_TTL_SECS: i64 = 10 * 60;
+
+/// TTL for probed stream qualities (6 hours) - they follow the user's plan
+pub const QUALITY_PROBE_TTL_SECS: i64 = 6 * 60 * 60;
+
+/// Editorial sections change at most daily
+pub const EDITORIAL_TTL_SECS: i64 = 24 * 60 * 60;
+
 pub struct ApiCache {
     conn: Connection,
 }
@@ -52,6 +105,23 @@ impl ApiCache {
     }
 
     fn init(&self) -> Result<(), String> {
+        // The synced favorites and quality probes are per account since
+        // user_id was added to their keys; copies from before are dropped and
+        // rebuilt by the next sync or probe
+        for table in ["synced_favorites", "synced_favorite_genres", "favorites_sync", "cached_quality_probes"] {
+            if self.lacks_column(table, "user_id")? {
+                self.conn
+                    .execute(&format!("DROP TABLE {}", table), [])
+                    .map_err(|e| format!("Failed to migrate {}: {}", table, e))?;
+            }
+        }
+        // Editorial sections are localized; unkeyed copies are refetched
+        if self.lacks_column("cached_editorial", "locale")? {
+            self.conn
+                .execute("DROP TABLE cached_editorial", [])
+                .map_err(|e| format!("Failed to migrate cached_editorial: {}", e))?;
+        }
+
         self.conn
             .execute_batch(
                 r#"
@@ -71,18 +141,132 @@ impl ApiCache {
                 );
                 CREATE INDEX IF NOT EXISTS idx_cached_artists_fetched ON cached_artists(fetched_at);
 
+                CREATE TABLE IF NOT EXISTS cached_artist_pages (
+                    artist_id INTEGER NOT NULL,
+                    locale TEXT NOT NULL,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL,
+                    PRIMARY KEY (artist_id, locale)
+                );
+
                 CREATE TABLE IF NOT EXISTS cached_tracks (
                     track_id INTEGER PRIMARY KEY,
                     data TEXT NOT NULL,
                     fetched_at INTEGER NOT NULL
                 );
                 CREATE INDEX IF NOT EXISTS idx_cached_tracks_fetched ON cached_tracks(fetched_at);
+
+                CREATE TABLE IF NOT EXISTS cached_favorites (
+                    fav_type TEXT NOT NULL,
+                    sort TEXT NOT NULL,
+                    page_limit INTEGER NOT NULL,
+                    page_offset INTEGER NOT NULL,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL,
+                    PRIMARY KEY (fav_type, sort, page_limit, page_offset)
+                );
+                CREATE INDEX IF NOT EXISTS idx_cached_favorites_fetched ON cached_favorites(fetched_at);
+
+                CREATE TABLE IF NOT EXISTS cached_user_playlists (
+                    id INTEGER PRIMARY KEY CHECK (id = 1),
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS cached_playlists (
+                    playlist_id INTEGER PRIMARY KEY,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS cached_quality_probes (
+                    user_id INTEGER NOT NULL,
+                    track_id INTEGER NOT NULL,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL,
+                    PRIMARY KEY (user_id, track_id)
+                );
+
+                CREATE TABLE IF NOT EXISTS cached_editorial (
+                    editorial_type TEXT NOT NULL,
+                    genre_id INTEGER NOT NULL,
+                    page_limit INTEGER NOT NULL,
+                    locale TEXT NOT NULL,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL,
+                    PRIMARY KEY (editorial_type, genre_id, page_limit, locale)
+                );
+
+                CREATE TABLE IF NOT EXISTS cached_searches (
+                    kind TEXT NOT NULL,
+                    query TEXT NOT NULL,
+                    page_offset INTEGER NOT NULL,
+                    data TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL,
+                    PRIMARY KEY (kind, query, page_offset)
+                );
+
+                CREATE TABLE IF NOT EXISTS synced_favorites (
+                    user_id INTEGER NOT NULL,
+                    fav_type TEXT NOT NULL,
+                    item_id TEXT NOT NULL,
+                    data TEXT NOT NULL,
+                    favorited_at INTEGER,
+                    PRIMARY KEY (user_id, fav_type, item_id)
+                );
+
+                CREATE TABLE IF NOT EXISTS favorites_sync (
+                    user_id INTEGER NOT NULL,
+                    fav_type TEXT NOT NULL,
+                    last_synced_at INTEGER NOT NULL,
+                    PRIMARY KEY (user_id, fav_type)
+                );
+
+                CREATE TABLE IF NOT EXISTS synced_favorite_genres (
+                    user_id INTEGER NOT NULL,
+                    fav_type TEXT NOT NULL,
+                    item_id TEXT NOT NULL,
+                    genre_id INTEGER NOT NULL,
+                    PRIMARY KEY (user_id, fav_type, item_id, genre_id)
+                );
+                CREATE INDEX IF NOT EXISTS idx_synced_favorite_genres_genre
+                    ON synced_favorite_genres(user_id, fav_type, genre_id);
                 "#,
             )
             .map_err(|e| format!("Failed to initialize API cache: {}", e))?;
+
+        // Filter columns (added after synced_favorites shipped). Rows synced
+        // before have them empty, so force a full resync to fill them in.
+        let added_year = self.conn.execute("ALTER TABLE synced_favorites ADD COLUMN release_year INTEGER", []).is_ok();
+        let added_quality = self.conn.execute("ALTER TABLE synced_favorites ADD COLUMN quality INTEGER", []).is_ok();
+        if added_year || added_quality {
+            self.conn
+                .execute("DELETE FROM favorites_sync", [])
+                .map_err(|e| format!("Failed to reset favorites sync: {}", e))?;
+        }
+        self.conn
+            .execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_synced_favorites_year ON synced_favorites(user_id, fav_type, release_year);
+                 CREATE INDEX IF NOT EXISTS idx_synced_favorites_quality ON synced_favorites(user_id, fav_type, quality);",
+            )
+            .map_err(|e| format!("Failed to initialize API cache: {}", e))?;
         Ok(())
     }
 
+    /// Whether `table` exists but was created without `column`
+    fn lacks_column(&self, table: &str, column: &str) -> Result<bool, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info(?)")
+            .map_err(|e| format!("Failed to inspect {}: {}", table, e))?;
+        let columns = stmt
+            .query_map(params![table], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to inspect {}: {}", table, e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to inspect {}: {}", table, e))?;
+        Ok(!columns.is_empty() && !columns.iter().any(|c| c == column))
+    }
+
     fn current_timestamp() -> i64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -196,6 +380,40 @@ impl ApiCache {
         Ok(())
     }
 
+    /// Get a cached artist page if it exists and hasn't expired
+    pub fn get_artist_page(&self, artist_id: u64, locale: &str, ttl_secs: Option<i64>) -> Result<Option<String>, String> {
+        let ttl = ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+        let min_fetched_at = Self::current_timestamp() - ttl;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_artist_pages WHERE artist_id = ? AND locale = ? AND fetched_at > ?",
+                params![artist_id, locale, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached artist page: {}", e))
+    }
+
+    /// Cache an assembled artist page
+    pub fn set_artist_page(&self, artist_id: u64, locale: &str, data: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_artist_pages (artist_id, locale, data, fetched_at) VALUES (?, ?, ?, ?)",
+                params![artist_id, locale, data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache artist page: {}", e))?;
+        Ok(())
+    }
+
+    /// Drop the cached pages of one artist, in every locale
+    pub fn remove_artist_page(&self, artist_id: u64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM cached_artist_pages WHERE artist_id = ?", params![artist_id])
+            .map_err(|e| format!("Failed to remove cached artist page: {}", e))?;
+        Ok(())
+    }
+
     // ============ Track Cache ============
 
     /// Get a cached track if it exists and hasn't expired
@@ -228,8 +446,456 @@ impl ApiCache {
         Ok(())
     }
 
+    // ============ Quality Probe Cache ============
+
+    /// Get the qualities of a track probed for the user if they haven't expired
+    pub fn get_quality_probe(&self, user_id: u64, track_id: u64) -> Result<Option<String>, String> {
+        let min_fetched_at = Self::current_timestamp() - QUALITY_PROBE_TTL_SECS;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_quality_probes WHERE user_id = ? AND track_id = ? AND fetched_at > ?",
+                params![user_id, track_id, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached quality probe: {}", e))
+    }
+
+    /// Cache the qualities of a track probed for the user
+    pub fn set_quality_probe(&self, user_id: u64, track_id: u64, data: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_quality_probes (user_id, track_id, data, fetched_at) VALUES (?, ?, ?, ?)",
+                params![user_id, track_id, data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache quality probe: {}", e))?;
+        Ok(())
+    }
+
+    // ============ Editorial Cache ============
+
+    /// Get a cached editorial section if it hasn't expired
+    /// genre_id 0 is the unfiltered section
+    pub fn get_editorial(
+        &self,
+        editorial_type: &str,
+        genre_id: u64,
+        limit: u32,
+        locale: &str,
+    ) -> Result<Option<String>, String> {
+        let min_fetched_at = Self::current_timestamp() - EDITORIAL_TTL_SECS;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_editorial
+                 WHERE editorial_type = ? AND genre_id = ? AND page_limit = ? AND locale = ? AND fetched_at > ?",
+                params![editorial_type, genre_id, limit, locale, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached editorial: {}", e))
+    }
+
+    /// Cache an editorial section
+    pub fn set_editorial(
+        &self,
+        editorial_type: &str,
+        genre_id: u64,
+        limit: u32,
+        locale: &str,
+        data: &str,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_editorial (editorial_type, genre_id, page_limit, locale, data, fetched_at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![editorial_type, genre_id, limit, locale, data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache editorial: {}", e))?;
+        Ok(())
+    }
+
+    // ============ Search Cache ============
+
+    /// Cache a search results page; `kind` is "albums" or "tracks"
+    pub fn set_search_page(&self, kind: &str, query: &str, offset: u32, data: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_searches (kind, query, page_offset, data, fetched_at)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![kind, normalize_query(query), offset, data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache search page: {}", e))?;
+        Ok(())
+    }
+
+    /// Get a cached search page if it exists and hasn't expired
+    pub fn get_search_page(
+        &self,
+        kind: &str,
+        query: &str,
+        offset: u32,
+        ttl_secs: Option<i64>,
+    ) -> Result<Option<String>, String> {
+        let ttl = ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+        let min_fetched_at = Self::current_timestamp() - ttl;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_searches
+                 WHERE kind = ? AND query = ? AND page_offset = ? AND fetched_at > ?",
+                params![kind, normalize_query(query), offset, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached search page: {}", e))
+    }
+
+    /// Search what is cached, for when the API can't be reached: items of
+    /// `kind` from earlier search pages (the same query first) and cached
+    /// albums/tracks, keeping those containing every word of `query`.
+    /// Expiry is ignored; stale results beat none.
+    pub fn search_cached(&self, kind: &str, query: &str, limit: usize) -> Result<Vec<Value>, String> {
+        let query = normalize_query(query);
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        let entity_table = match kind {
+            "albums" => Some("cached_albums"),
+            "tracks" => Some("cached_tracks"),
+            _ => None,
+        };
+
+        let mut items = Vec::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare(
+                    "SELECT data FROM cached_searches WHERE kind = ?
+                     ORDER BY query = ? DESC, fetched_at DESC, page_offset",
+                )
+                .map_err(|e| format!("Failed to query cached searches: {}", e))?;
+            let pages = stmt
+                .query_map(params![kind, query], |row| row.get::<_, String>(0))
+                .map_err(|e| format!("Failed to query cached searches: {}", e))?;
+            for page in pages.flatten() {
+                if let Ok(Value::Object(mut page)) = serde_json::from_str::<Value>(&page) {
+                    if let Some(Value::Array(page_items)) = page.remove("items") {
+                        items.extend(page_items);
+                    }
+                }
+            }
+        }
+        if let Some(table) = entity_table {
+            let mut stmt = self
+                .conn
+                .prepare(&format!("SELECT data FROM {} ORDER BY fetched_at DESC", table))
+                .map_err(|e| format!("Failed to query cached {}: {}", kind, e))?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| format!("Failed to query cached {}: {}", kind, e))?;
+            items.extend(rows.flatten().filter_map(|data| serde_json::from_str::<Value>(&data).ok()));
+        }
+
+        let mut seen = HashSet::new();
+        Ok(items
+            .into_iter()
+            .filter(|item| matches_query(item, &terms))
+            .filter(|item| favorite_item_id(item).is_some_and(|id| seen.insert(id)))
+            .take(limit)
+            .collect())
+    }
+
+    // ============ Favorites Cache ============
+
+    /// Get a cached favorites page if it exists and hasn't expired
+    /// fav_type is the plural form used by the API ("albums", "tracks", "artists")
+    pub fn get_favorites(
+        &self,
+        fav_type: &str,
+        sort: &str,
+        limit: u32,
+        offset: u32,
+        ttl_secs: Option<i64>,
+    ) -> Result<Option<String>, String> {
+        let ttl = ttl_secs.unwrap_or(FAVORITES_TTL_SECS);
+        let min_fetched_at = Self::current_timestamp() - ttl;
+
+        let result: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT data FROM cached_favorites
+                 WHERE fav_type = ? AND sort = ? AND page_limit = ? AND page_offset = ? AND fetched_at > ?",
+                params![fav_type, sort, limit, offset, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached favorites: {}", e))?;
+
+        Ok(result)
+    }
+
+    /// Cache a favorites page response
+    pub fn set_favorites(&self, fav_type: &str, sort: &str, limit: u32, offset: u32, data: &str) -> Result<(), String> {
+        let fetched_at = Self::current_timestamp();
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_favorites (fav_type, sort, page_limit, page_offset, data, fetched_at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![fav_type, sort, limit, offset, data, fetched_at],
+            )
+            .map_err(|e| format!("Failed to cache favorites: {}", e))?;
+        Ok(())
+    }
+
+    /// Apply a confirmed favorite add/remove to the cached favorites pages.
+    ///
+    /// Removals are patched in place so cached pages stay valid. Additions drop
+    /// the cached pages for that type, since we only know the item id and the
+    /// API returns newly added items first (which shifts every page).
+    /// fav_type is the singular form used by add/remove ("album", "track", "artist").
+    pub fn apply_favorite_change(&self, fav_type: &str, item_id: &str, added: bool) -> Result<usize, String> {
+        let plural = favorites_plural(fav_type);
+
+        if added {
+            let deleted = self
+                .conn
+                .execute("DELETE FROM cached_favorites WHERE fav_type = ?", params![plural])
+                .map_err(|e| format!("Failed to invalidate cached favorites: {}", e))?;
+            return Ok(deleted);
+        }
+
+        let pages: Vec<(String, u32, u32, String)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT sort, page_limit, page_offset, data FROM cached_favorites WHERE fav_type = ?")
+                .map_err(|e| format!("Failed to prepare cached favorites query: {}", e))?;
+            let rows = stmt
+                .query_map(params![plural], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+                .map_err(|e| format!("Failed to query cached favorites: {}", e))?;
+            let mut pages = Vec::new();
+            for row in rows {
+                pages.push(row.map_err(|e| format!("Failed to read cached favorites row: {}", e))?);
+            }
+            pages
+        };
+
+        let mut updated = 0;
+        for (sort, limit, offset, data) in pages {
+            let mut json: Value = match serde_json::from_str(&data) {
+                Ok(json) => json,
+                Err(_) => continue,
+            };
+            if remove_favorite_item(&mut json, &plural, item_id) {
+                self.conn
+                    .execute(
+                        "UPDATE cached_favorites SET data = ?
+                         WHERE fav_type = ? AND sort = ? AND page_limit = ? AND page_offset = ?",
+                        params![json.to_string(), plural, sort, limit, offset],
+                    )
+                    .map_err(|e| format!("Failed to update cached favorites: {}", e))?;
+                updated += 1;
+            } else if let Some(total) = json
+                .get(&plural)
+                .and_then(|p| p.get("total"))
+                .and_then(|t| t.as_u64())
+            {
+                // Item lives on another page, but the total still changed
+                json[&plural]["total"] = Value::from(total.saturating_sub(1));
+                self.conn
+                    .execute(
+                        "UPDATE cached_favorites SET data = ?
+                         WHERE fav_type = ? AND sort = ? AND page_limit = ? AND page_offset = ?",
+                        params![json.to_string(), plural, sort, limit, offset],
+                    )
+                    .map_err(|e| format!("Failed to update cached favorites: {}", e))?;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    // ============ Synced Favorites ============
+    // Kept per account: every query is scoped to the user the copy belongs to
+
+    /// When the user's favorites of this type were last synced (None = never)
+    pub fn favorites_last_synced(&self, user_id: u64, fav_type: &str) -> Result<Option<i64>, String> {
+        self.conn
+            .query_row(
+                "SELECT last_synced_at FROM favorites_sync WHERE user_id = ? AND fav_type = ?",
+                params![user_id, fav_type],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query favorites sync state: {}", e))
+    }
+
+    /// Ids of the user's locally synced favorites of one type
+    pub fn synced_favorite_ids(&self, user_id: u64, fav_type: &str) -> Result<HashSet<String>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT item_id FROM synced_favorites WHERE user_id = ? AND fav_type = ?")
+            .map_err(|e| format!("Failed to prepare synced favorites query: {}", e))?;
+        let rows = stmt
+            .query_map(params![user_id, fav_type], |row| row.get(0))
+            .map_err(|e| format!("Failed to query synced favorites: {}", e))?;
+
+        let mut ids = HashSet::new();
+        for row in rows {
+            ids.insert(row.map_err(|e| format!("Failed to read synced favorite row: {}", e))?);
+        }
+        Ok(ids)
+    }
+
+    /// Number of the user's locally synced favorites of one type
+    pub fn synced_favorite_count(&self, user_id: u64, fav_type: &str) -> Result<u64, String> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM synced_favorites WHERE user_id = ? AND fav_type = ?",
+                params![user_id, fav_type],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as u64)
+            .map_err(|e| format!("Failed to count synced favorites: {}", e))
+    }
+
+    /// The user's locally synced favorites of one type, most recently added first
+    pub fn synced_favorites(&self, user_id: u64, fav_type: &str) -> Result<Vec<Value>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT data FROM synced_favorites WHERE user_id = ? AND fav_type = ?
+                 ORDER BY favorited_at DESC, item_id",
+            )
+            .map_err(|e| format!("Failed to prepare synced favorites query: {}", e))?;
+        let rows = stmt
+            .query_map(params![user_id, fav_type], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to query synced favorites: {}", e))?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let data = row.map_err(|e| format!("Failed to read synced favorite row: {}", e))?;
+            if let Ok(item) = serde_json::from_str(&data) {
+                items.push(item);
+            }
+        }
+        Ok(items)
+    }
+
+    /// Merge a favorites delta into the user's local copy and record the sync
+    /// time. With `replace` the local copy is rebuilt from `added` (full sync).
+    pub fn apply_favorites_delta(
+        &mut self,
+        user_id: u64,
+        fav_type: &str,
+        added: &[Value],
+        removed: &[String],
+        replace: bool,
+        synced_at: i64,
+    ) -> Result<(), String> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start favorites sync transaction: {}", e))?;
+
+        if replace {
+            tx.execute(
+                "DELETE FROM synced_favorites WHERE user_id = ? AND fav_type = ?",
+                params![user_id, fav_type],
+            )
+            .map_err(|e| format!("Failed to reset synced favorites: {}", e))?;
+            tx.execute(
+                "DELETE FROM synced_favorite_genres WHERE user_id = ? AND fav_type = ?",
+                params![user_id, fav_type],
+            )
+            .map_err(|e| format!("Failed to reset synced favorite genres: {}", e))?;
+        }
+        for id in removed {
+            tx.execute(
+                "DELETE FROM synced_favorites WHERE user_id = ? AND fav_type = ? AND item_id = ?",
+                params![user_id, fav_type, id],
+            )
+            .map_err(|e| format!("Failed to remove synced favorite: {}", e))?;
+            tx.execute(
+                "DELETE FROM synced_favorite_genres WHERE user_id = ? AND fav_type = ? AND item_id = ?",
+                params![user_id, fav_type, id],
+            )
+            .map_err(|e| format!("Failed to remove synced favorite genres: {}", e))?;
+        }
+        for item in added {
+            let Some(id) = favorite_item_id(item) else {
+                continue;
+            };
+            let facets = filter::FavoriteFacets::from_item(item);
+            tx.execute(
+                "INSERT OR REPLACE INTO synced_favorites
+                 (user_id, fav_type, item_id, data, favorited_at, release_year, quality)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    user_id,
+                    fav_type,
+                    id,
+                    item.to_string(),
+                    item["favorited_at"].as_i64(),
+                    facets.release_year,
+                    facets.quality.map(|q| q.id()),
+                ],
+            )
+            .map_err(|e| format!("Failed to store synced favorite: {}", e))?;
+            tx.execute(
+                "DELETE FROM synced_favorite_genres WHERE user_id = ? AND fav_type = ? AND item_id = ?",
+                params![user_id, fav_type, id],
+            )
+            .map_err(|e| format!("Failed to update synced favorite genres: {}", e))?;
+            for genre_id in &facets.genre_ids {
+                tx.execute(
+                    "INSERT OR IGNORE INTO synced_favorite_genres (user_id, fav_type, item_id, genre_id)
+                     VALUES (?, ?, ?, ?)",
+                    params![user_id, fav_type, id, genre_id],
+                )
+                .map_err(|e| format!("Failed to store synced favorite genre: {}", e))?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO favorites_sync (user_id, fav_type, last_synced_at) VALUES (?, ?, ?)",
+            params![user_id, fav_type, synced_at],
+        )
+        .map_err(|e| format!("Failed to record favorites sync: {}", e))?;
+
+        // Cached pages no longer match the synced set
+        if replace || !added.is_empty() || !removed.is_empty() {
+            tx.execute("DELETE FROM cached_favorites WHERE fav_type = ?", params![fav_type])
+                .map_err(|e| format!("Failed to invalidate cached favorites: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit favorites sync: {}", e))
+    }
+
+    /// Forget when the user's favorites were synced, so the next sync is a
+    /// full one (on logout, in case they changed elsewhere meanwhile)
+    pub fn reset_favorites_sync(&self, user_id: u64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM favorites_sync WHERE user_id = ?", params![user_id])
+            .map_err(|e| format!("Failed to reset favorites sync: {}", e))?;
+        Ok(())
+    }
+
     // ============ Maintenance ============
 
+    /// Drop what is cached for the logged-in account, so the next account on
+    /// this machine is never served it. Returns the number of rows removed.
+    pub fn clear_account_data(&self) -> Result<usize, String> {
+        let mut removed = 0;
+        // Playlists too, since private ones must only reach their owner
+        for table in ["cached_favorites", "cached_user_playlists", "cached_playlists"] {
+            removed += self
+                .conn
+                .execute(&format!("DELETE FROM {}", table), [])
+                .map_err(|e| format!("Failed to clear account cache: {}", e))?;
+        }
+        Ok(removed)
+    }
+
     /// Clear all cached artists for a specific locale
     /// This is useful when user changes language and wants fresh data in the new language
     pub fn clear_artists_by_locale(&self, locale: &str) -> Result<usize, String> {
@@ -240,6 +906,9 @@ impl ApiCache {
                 params![locale],
             )
             .map_err(|e| format!("Failed to clear cached artists by locale: {}", e))?;
+        self.conn
+            .execute("DELETE FROM cached_artist_pages WHERE locale = ?", params![locale])
+            .map_err(|e| format!("Failed to clear cached artist pages by locale: {}", e))?;
         
         log::info!("Cleared {} cached artist(s) for locale '{}'", deleted, locale);
         Ok(deleted)
@@ -251,11 +920,110 @@ impl ApiCache {
             .conn
             .execute("DELETE FROM cached_artists", [])
             .map_err(|e| format!("Failed to clear all cached artists: {}", e))?;
+        self.conn
+            .execute("DELETE FROM cached_artist_pages", [])
+            .map_err(|e| format!("Failed to clear all cached artist pages: {}", e))?;
         
         log::info!("Cleared {} cached artist(s)", deleted);
         Ok(deleted)
     }
 
+    // ============ User Playlists Cache ============
+
+    /// Get the cached list of the user's playlists if it hasn't expired
+    pub fn get_user_playlists(&self, ttl_secs: Option<i64>) -> Result<Option<String>, String> {
+        let ttl = ttl_secs.unwrap_or(USER_PLAYLISTS_TTL_SECS);
+        let min_fetched_at = Self::current_timestamp() - ttl;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_user_playlists WHERE id = 1 AND fetched_at > ?",
+                params![min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached user playlists: {}", e))
+    }
+
+    /// Cache the user's playlist list
+    pub fn set_user_playlists(&self, data: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_user_playlists (id, data, fetched_at) VALUES (1, ?, ?)",
+                params![data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache user playlists: {}", e))?;
+        Ok(())
+    }
+
+    /// Replace one playlist in the cached list, keeping the list's age. The
+    /// list holds playlists without their tracks. Returns false when no
+    /// list is cached or it doesn't contain the playlist.
+    pub fn update_user_playlist(&self, playlist: &Value) -> Result<bool, String> {
+        let Some(data) = self
+            .conn
+            .query_row("SELECT data FROM cached_user_playlists WHERE id = 1", [], |row| row.get::<_, String>(0))
+            .optional()
+            .map_err(|e| format!("Failed to query cached user playlists: {}", e))?
+        else {
+            return Ok(false);
+        };
+
+        let mut playlists: Vec<Value> = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to parse cached playlists: {}", e))?;
+        let Some(entry) = playlists.iter_mut().find(|p| p["id"] == playlist["id"]) else {
+            return Ok(false);
+        };
+        *entry = playlist.clone();
+        if let Some(fields) = entry.as_object_mut() {
+            fields.remove("tracks");
+        }
+
+        self.conn
+            .execute(
+                "UPDATE cached_user_playlists SET data = ? WHERE id = 1",
+                params![Value::Array(playlists).to_string()],
+            )
+            .map_err(|e| format!("Failed to update cached user playlists: {}", e))?;
+        Ok(true)
+    }
+
+    /// Drop the cached playlist list and playlists (after create/delete/edit)
+    pub fn invalidate_user_playlists(&self) -> Result<(), String> {
+        self.conn
+            .execute_batch("DELETE FROM cached_user_playlists; DELETE FROM cached_playlists;")
+            .map_err(|e| format!("Failed to invalidate cached user playlists: {}", e))?;
+        Ok(())
+    }
+
+    // ============ Playlist Cache ============
+
+    /// Get a cached playlist (with its tracks) if it hasn't expired
+    pub fn get_playlist(&self, playlist_id: u64, ttl_secs: Option<i64>) -> Result<Option<String>, String> {
+        let ttl = ttl_secs.unwrap_or(USER_PLAYLISTS_TTL_SECS);
+        let min_fetched_at = Self::current_timestamp() - ttl;
+
+        self.conn
+            .query_row(
+                "SELECT data FROM cached_playlists WHERE playlist_id = ? AND fetched_at > ?",
+                params![playlist_id, min_fetched_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query cached playlist: {}", e))
+    }
+
+    /// Cache a playlist response
+    pub fn set_playlist(&self, playlist_id: u64, data: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO cached_playlists (playlist_id, data, fetched_at) VALUES (?, ?, ?)",
+                params![playlist_id, data, Self::current_timestamp()],
+            )
+            .map_err(|e| format!("Failed to cache playlist: {}", e))?;
+        Ok(())
+    }
+
     /// Clear expired entries from all tables
     pub fn cleanup_expired(&self, ttl_secs: Option<i64>) -> Result<usize, String> {
         let ttl = ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
@@ -279,6 +1047,14 @@ impl ApiCache {
             )
             .map_err(|e| format!("Failed to cleanup cached artists: {}", e))?;
 
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_artist_pages WHERE fetched_at <= ?",
+                params![min_fetched_at],
+            )
+            .map_err(|e| format!("Failed to cleanup cached artist pages: {}", e))?;
+
         total_deleted += self
             .conn
             .execute(
@@ -287,20 +1063,273 @@ impl ApiCache {
             )
             .map_err(|e| format!("Failed to cleanup cached tracks: {}", e))?;
 
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_favorites WHERE fetched_at <= ?",
+                params![Self::current_timestamp() - FAVORITES_TTL_SECS],
+            )
+            .map_err(|e| format!("Failed to cleanup cached favorites: {}", e))?;
+
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_quality_probes WHERE fetched_at <= ?",
+                params![Self::current_timestamp() - QUALITY_PROBE_TTL_SECS],
+            )
+            .map_err(|e| format!("Failed to cleanup cached quality probes: {}", e))?;
+
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_editorial WHERE fetched_at <= ?",
+                params![Self::current_timestamp() - EDITORIAL_TTL_SECS],
+            )
+            .map_err(|e| format!("Failed to cleanup cached editorial: {}", e))?;
+
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_user_playlists WHERE fetched_at <= ?",
+                params![Self::current_timestamp() - USER_PLAYLISTS_TTL_SECS],
+            )
+            .map_err(|e| format!("Failed to cleanup cached user playlists: {}", e))?;
+
+        total_deleted += self
+            .conn
+            .execute(
+                "DELETE FROM cached_playlists WHERE fetched_at <= ?",
+                params![Self::current_timestamp() - USER_PLAYLISTS_TTL_SECS],
+            )
+            .map_err(|e| format!("Failed to cleanup cached playlists: {}", e))?;
+
         Ok(total_deleted)
     }
 
-    /// Clear all cached data
-    pub fn clear_all(&self) -> Result<(), String> {
-        self.conn
-            .execute_batch(
-                r#"
-                DELETE FROM cached_albums;
-                DELETE FROM cached_artists;
-                DELETE FROM cached_tracks;
-                "#,
-            )
-            .map_err(|e| format!("Failed to clear API cache: {}", e))?;
-        Ok(())
+    /// Clear all cached data, returning the number of rows removed
+    pub fn clear_all(&self) -> Result<usize, String> {
+        let mut removed = 0;
+        for table in [
+            "cached_albums",
+            "cached_artists",
+            "cached_artist_pages",
+            "cached_tracks",
+            "cached_favorites",
+            "cached_user_playlists",
+            "cached_playlists",
+            "cached_quality_probes",
+            "cached_editorial",
+            "cached_searches",
+            "synced_favorites",
+            "synced_favorite_genres",
+            "favorites_sync",
+        ] {
+            removed += self
+                .conn
+                .execute(&format!("DELETE FROM {}", table), [])
+                .map_err(|e| format!("Failed to clear API cache: {}", e))?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Search queries are cached case- and whitespace-insensitively
+fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Every term appears in the item's title, name, artist or album
+fn matches_query(item: &Value, terms: &[&str]) -> bool {
+    const FIELDS: [&str; 7] = [
+        "/title",
+        "/name",
+        "/version",
+        "/artist/name",
+        "/performer/name",
+        "/album/title",
+        "/album/artist/name",
+    ];
+    let text = FIELDS
+        .iter()
+        .filter_map(|field| item.pointer(field).and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    terms.iter().all(|term| text.contains(term))
+}
+
+/// Map the singular favorite type used by add/remove to the plural used by getUserFavorites
+pub fn favorites_plural(fav_type: &str) -> String {
+    if fav_type.ends_with('s') {
+        fav_type.to_string()
+    } else {
+        format!("{}s", fav_type)
+    }
+}
+
+/// Id of a favorites item as a string (the API mixes string and numeric ids)
+pub fn favorite_item_id(item: &Value) -> Option<String> {
+    match item.get("id") {
+        Some(Value::String(id)) => Some(id.clone()),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Remove an item from a getUserFavorites page, adjusting the total.
+/// Returns true if the item was present on this page.
+fn remove_favorite_item(page: &mut Value, plural: &str, item_id: &str) -> bool {
+    let Some(container) = page.get_mut(plural) else {
+        return false;
+    };
+    let Some(items) = container.get_mut("items").and_then(|i| i.as_array_mut()) else {
+        return false;
+    };
+
+    let before = items.len();
+    items.retain(|item| favorite_item_id(item).as_deref() != Some(item_id));
+    let removed = before - items.len();
+
+    if removed == 0 {
+        return false;
+    }
+
+    if let Some(total) = container.get("total").and_then(|t| t.as_u64()) {
+        container["total"] = Value::from(total.saturating_sub(removed as u64));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_cache() -> ApiCache {
+        ApiCache::new(Path::new(":memory:")).expect("in-memory cache")
+    }
+
+    #[test]
+    fn test_remove_favorite_updates_cached_page() {
+        let cache = memory_cache();
+        let page = serde_json::json!({
+            "tracks": { "items": [{ "id": 1 }, { "id": 2 }], "total": 2, "offset": 0, "limit": 50 }
+        });
+        cache.set_favorites("tracks", "date_added_desc", 50, 0, &page.to_string()).unwrap();
+
+        let updated = cache.apply_favorite_change("track", "1", false).unwrap();
+        assert_eq!(updated, 1);
+
+        let cached: Value = serde_json::from_str(&cache.get_favorites("tracks", "date_added_desc", 50, 0, None).unwrap().unwrap()).unwrap();
+        assert_eq!(cached["tracks"]["items"], serde_json::json!([{ "id": 2 }]));
+        assert_eq!(cached["tracks"]["total"], 1);
+    }
+
+    #[test]
+    fn test_add_favorite_invalidates_cached_pages() {
+        let cache = memory_cache();
+        let page = serde_json::json!({ "albums": { "items": [{ "id": "abc" }], "total": 1 } });
+        cache.set_favorites("albums", "title", 50, 0, &page.to_string()).unwrap();
+
+        cache.apply_favorite_change("album", "def", true).unwrap();
+        assert!(cache.get_favorites("albums", "title", 50, 0, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_clear_account_data_keeps_only_the_catalog() {
+        let cache = memory_cache();
+        let page = serde_json::json!({ "tracks": { "items": [{ "id": 1 }], "total": 1 } }).to_string();
+        cache.set_favorites("tracks", "date_added_desc", 50, 0, &page).unwrap();
+        cache.set_user_playlists(r#"{"playlists":{"items":[]}}"#).unwrap();
+        cache.set_playlist(7, r#"{"id":7,"public":false}"#).unwrap();
+        cache.set_album("alb", r#"{"id":"alb"}"#).unwrap();
+        assert_eq!(cache.get_favorites("tracks", "date_added_desc", 50, 0, None).unwrap(), Some(page));
+
+        cache.clear_account_data().unwrap();
+        assert_eq!(cache.get_favorites("tracks", "date_added_desc", 50, 0, None).unwrap(), None);
+        assert_eq!(cache.get_user_playlists(None).unwrap(), None);
+        assert_eq!(cache.get_playlist(7, None).unwrap(), None);
+        // The catalog isn't per account
+        assert_eq!(cache.get_album("alb", None).unwrap().as_deref(), Some(r#"{"id":"alb"}"#));
+    }
+
+    #[test]
+    fn test_filter_columns_migration_forces_full_resync() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE synced_favorites (
+                 user_id INTEGER NOT NULL,
+                 fav_type TEXT NOT NULL,
+                 item_id TEXT NOT NULL,
+                 data TEXT NOT NULL,
+                 favorited_at INTEGER,
+                 PRIMARY KEY (user_id, fav_type, item_id)
+             );
+             CREATE TABLE favorites_sync (
+                 user_id INTEGER NOT NULL,
+                 fav_type TEXT NOT NULL,
+                 last_synced_at INTEGER NOT NULL,
+                 PRIMARY KEY (user_id, fav_type)
+             );
+             INSERT INTO favorites_sync VALUES (1, 'albums', 100);",
+        )
+        .unwrap();
+
+        let cache = ApiCache { conn };
+        cache.init().unwrap();
+        assert_eq!(cache.favorites_last_synced(1, "albums").unwrap(), None);
+
+        // Later starts keep the sync point
+        cache.conn.execute("INSERT INTO favorites_sync VALUES (1, 'albums', 200)", []).unwrap();
+        cache.init().unwrap();
+        assert_eq!(cache.favorites_last_synced(1, "albums").unwrap(), Some(200));
+    }
+
+    #[test]
+    fn test_synced_favorites_are_kept_per_user() {
+        let mut cache = memory_cache();
+        cache.apply_favorites_delta(1, "tracks", &[serde_json::json!({ "id": 10 })], &[], true, 100).unwrap();
+        cache.apply_favorites_delta(2, "tracks", &[serde_json::json!({ "id": 20 })], &[], true, 200).unwrap();
+
+        assert_eq!(cache.synced_favorite_ids(1, "tracks").unwrap(), HashSet::from(["10".to_string()]));
+        assert_eq!(cache.synced_favorite_ids(2, "tracks").unwrap(), HashSet::from(["20".to_string()]));
+        // A full sync of one account leaves the other's copy alone
+        cache.apply_favorites_delta(2, "tracks", &[], &[], true, 300).unwrap();
+        assert_eq!(cache.synced_favorite_count(1, "tracks").unwrap(), 1);
+
+        cache.reset_favorites_sync(1).unwrap();
+        assert_eq!(cache.favorites_last_synced(1, "tracks").unwrap(), None);
+        assert_eq!(cache.favorites_last_synced(2, "tracks").unwrap(), Some(300));
+    }
+
+    #[test]
+    fn test_quality_probes_are_kept_per_user() {
+        let cache = memory_cache();
+        cache.set_quality_probe(1, 42, "[27,7,6]").unwrap();
+
+        assert_eq!(cache.get_quality_probe(1, 42).unwrap().as_deref(), Some("[27,7,6]"));
+        assert_eq!(cache.get_quality_probe(2, 42).unwrap(), None);
+    }
+
+    #[test]
+    fn test_synced_favorites_without_user_id_are_rebuilt() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE synced_favorites (
+                 fav_type TEXT NOT NULL,
+                 item_id TEXT NOT NULL,
+                 data TEXT NOT NULL,
+                 favorited_at INTEGER,
+                 PRIMARY KEY (fav_type, item_id)
+             );
+             CREATE TABLE favorites_sync (fav_type TEXT PRIMARY KEY, last_synced_at INTEGER NOT NULL);
+             INSERT INTO synced_favorites VALUES ('tracks', '1', '{}', 5);
+             INSERT INTO favorites_sync VALUES ('tracks', 100);",
+        )
+        .unwrap();
+
+        let cache = ApiCache { conn };
+        cache.init().unwrap();
+        assert_eq!(cache.synced_favorite_count(1, "tracks").unwrap(), 0);
+        assert_eq!(cache.favorites_last_synced(1, "tracks").unwrap(), None);
     }
 }
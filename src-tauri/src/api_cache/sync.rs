@@ -0,0 +1,299 @@
+//! Incremental favorites sync
+//!
+//! Keeps a local copy of every favorite per type. Once a full sync has run,
+//! later syncs only fetch favorites added since the last one (newest-first
+//! pages, stopping at the first older item) and find removals by diffing the
+//! local ids against getUserFavoriteIds, which is a single request. A full
+//! sync is used when the local copy is empty or the API doesn't report when
+//! items were favorited.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+
+use super::{favorite_item_id, ApiCache};
+use crate::api::{FavoritesSort, QobuzClient};
+
+pub const FAVORITE_TYPES: [&str; 3] = ["albums", "tracks", "artists"];
+
+/// Page size for sync requests (API maximum)
+const SYNC_PAGE_LIMIT: u32 = 500;
+
+/// Changes applied to the local favorites by one sync
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FavoritesDelta {
+    pub fav_type: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// The whole list was re-fetched instead of just the changes
+    pub full_sync: bool,
+}
+
+impl FavoritesDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Sync the logged-in user's favorites of one type ("albums", "tracks",
+/// "artists") into `cache`
+pub async fn sync_favorites(
+    client: &QobuzClient,
+    cache: &Mutex<ApiCache>,
+    fav_type: &str,
+) -> Result<FavoritesDelta, String> {
+    let user_id = client.user_id().await.map_err(|e| e.to_string())?;
+    let (last_synced, local_ids) = {
+        let cache = cache.lock().await;
+        (cache.favorites_last_synced(user_id, fav_type)?, cache.synced_favorite_ids(user_id, fav_type)?)
+    };
+    let started_at = chrono::Utc::now().timestamp();
+
+    let added_since = match last_synced {
+        Some(since) if !local_ids.is_empty() => fetch_added_since(client, fav_type, since).await?,
+        _ => None,
+    };
+
+    let Some(new_items) = added_since else {
+        log::info!("Favorites sync ({}): full sync", fav_type);
+        return full_sync(client, cache, user_id, fav_type, &local_ids, started_at).await;
+    };
+
+    let remote_ids = client
+        .get_favorite_ids(fav_type)
+        .await
+        .map_err(|e| format!("Failed to get favorite ids: {}", e))?;
+
+    let added: Vec<Value> = new_items
+        .into_iter()
+        .filter(|item| {
+            favorite_item_id(item).is_some_and(|id| !local_ids.contains(&id) && remote_ids.contains(&id))
+        })
+        .collect();
+    let mut removed: Vec<String> = local_ids.difference(&remote_ids).cloned().collect();
+    removed.sort();
+
+    cache
+        .lock()
+        .await
+        .apply_favorites_delta(user_id, fav_type, &added, &removed, false, started_at)?;
+
+    let delta = FavoritesDelta {
+        fav_type: fav_type.to_string(),
+        added: added.iter().filter_map(favorite_item_id).collect(),
+        removed,
+        full_sync: false,
+    };
+    log::info!(
+        "Favorites sync ({}): {} added, {} removed",
+        fav_type,
+        delta.added.len(),
+        delta.removed.len()
+    );
+    Ok(delta)
+}
+
+/// Favorites added at or after `since`, newest first.
+/// None when the API doesn't report `favorited_at`, so a delta can't be computed.
+async fn fetch_added_since(
+    client: &QobuzClient,
+    fav_type: &str,
+    since: i64,
+) -> Result<Option<Vec<Value>>, String> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let page = client
+            .get_favorites_sorted(fav_type, SYNC_PAGE_LIMIT, offset, FavoritesSort::DateAddedDesc)
+            .await
+            .map_err(|e| format!("Failed to get favorites: {}", e))?;
+        let page_items = page_items(&page, fav_type);
+        let page_len = page_items.len() as u32;
+
+        for item in page_items {
+            let Some(favorited_at) = item["favorited_at"].as_i64() else {
+                return Ok(None);
+            };
+            if favorited_at < since {
+                return Ok(Some(items));
+            }
+            items.push(item);
+        }
+
+        offset += page_len;
+        if page_len < SYNC_PAGE_LIMIT || offset >= page_total(&page, fav_type) {
+            return Ok(Some(items));
+        }
+    }
+}
+
+/// Re-fetch every favorite of the type and rebuild the local copy
+async fn full_sync(
+    client: &QobuzClient,
+    cache: &Mutex<ApiCache>,
+    user_id: u64,
+    fav_type: &str,
+    local_ids: &HashSet<String>,
+    started_at: i64,
+) -> Result<FavoritesDelta, String> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let page = client
+            .get_favorites_sorted(fav_type, SYNC_PAGE_LIMIT, offset, FavoritesSort::DateAddedDesc)
+            .await
+            .map_err(|e| format!("Failed to get favorites: {}", e))?;
+        let page_items = page_items(&page, fav_type);
+        let page_len = page_items.len() as u32;
+        items.extend(page_items);
+
+        offset += page_len;
+        if page_len < SYNC_PAGE_LIMIT || offset >= page_total(&page, fav_type) {
+            break;
+        }
+    }
+
+    let remote_ids: HashSet<String> = items.iter().filter_map(favorite_item_id).collect();
+    let mut added: Vec<String> = remote_ids.difference(local_ids).cloned().collect();
+    let mut removed: Vec<String> = local_ids.difference(&remote_ids).cloned().collect();
+    added.sort();
+    removed.sort();
+
+    cache
+        .lock()
+        .await
+        .apply_favorites_delta(user_id, fav_type, &items, &[], true, started_at)?;
+
+    Ok(FavoritesDelta {
+        fav_type: fav_type.to_string(),
+        added,
+        removed,
+        full_sync: true,
+    })
+}
+
+fn page_items(page: &Value, fav_type: &str) -> Vec<Value> {
+    page.get(fav_type)
+        .and_then(|c| c.get("items"))
+        .and_then(|i| i.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn page_total(page: &Value, fav_type: &str) -> u32 {
+    page.get(fav_type)
+        .and_then(|c| c.get("total"))
+        .and_then(|t| t.as_u64())
+        .unwrap_or(0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::{logged_in_client, mount_secret_probe};
+    use std::path::Path;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_delta_sync_only_updates_changed_items() {
+        let server = MockServer::start().await;
+
+        // Newest first: track 3 was added after the last sync, track 2 before it
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITES))
+            .and(query_param("type", "tracks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tracks": {
+                    "items": [
+                        { "id": 3, "title": "New", "favorited_at": 2000 },
+                        { "id": 2, "title": "Renamed upstream", "favorited_at": 900 }
+                    ],
+                    "total": 2, "offset": 0, "limit": 500
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITE_IDS))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tracks": [2, 3]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        mount_secret_probe(&server).await;
+        let client = logged_in_client(&server).await;
+        let cache = Mutex::new(ApiCache::new(Path::new(":memory:")).unwrap());
+        cache
+            .lock()
+            .await
+            .apply_favorites_delta(
+                1,
+                "tracks",
+                &[
+                    serde_json::json!({ "id": 1, "title": "Old", "favorited_at": 100 }),
+                    serde_json::json!({ "id": 2, "title": "Kept", "favorited_at": 900 }),
+                ],
+                &[],
+                true,
+                1000,
+            )
+            .unwrap();
+
+        let delta = sync_favorites(&client, &cache, "tracks").await.unwrap();
+
+        assert_eq!(
+            delta,
+            FavoritesDelta {
+                fav_type: "tracks".to_string(),
+                added: vec!["3".to_string()],
+                removed: vec!["1".to_string()],
+                full_sync: false,
+            }
+        );
+
+        let cache = cache.lock().await;
+        let items = cache.synced_favorites(1, "tracks").unwrap();
+        let titles: Vec<&str> = items.iter().filter_map(|i| i["title"].as_str()).collect();
+        // Unchanged items are left alone
+        assert_eq!(titles, vec!["New", "Kept"]);
+        assert!(cache.favorites_last_synced(1, "tracks").unwrap().unwrap() > 1000);
+    }
+
+    #[tokio::test]
+    async fn test_empty_local_copy_runs_full_sync() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITES))
+            .and(query_param("type", "albums"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "albums": { "items": [{ "id": "a1" }, { "id": "a2" }], "total": 2 }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITE_IDS))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        mount_secret_probe(&server).await;
+        let client = logged_in_client(&server).await;
+        let cache = Mutex::new(ApiCache::new(Path::new(":memory:")).unwrap());
+
+        let delta = sync_favorites(&client, &cache, "albums").await.unwrap();
+
+        assert!(delta.full_sync);
+        assert_eq!(delta.added, vec!["a1".to_string(), "a2".to_string()]);
+        assert_eq!(cache.lock().await.synced_favorite_ids(1, "albums").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Cache warming
+//!
+//! After login, prefetch the user's favorites and playlists into the API cache
+//! so the first views render instantly. Requests are spaced out to stay clear
+//! of API throttling, and the run stops as soon as it is cancelled.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+
+use super::sync::FAVORITE_TYPES;
+use super::ApiCache;
+use crate::api::{FavoritesSort, QobuzClient};
+
+/// Delay between warm-up requests
+pub const WARM_REQUEST_SPACING: Duration = Duration::from_millis(750);
+
+/// Page size used by the favorites views (matches the get_favorites default)
+const FAVORITES_PAGE_LIMIT: u32 = 50;
+
+/// Fetch favorites and playlists metadata into `cache`.
+/// Returns the number of responses cached.
+pub async fn warm_cache(
+    client: Arc<Mutex<QobuzClient>>,
+    cache: Arc<Mutex<ApiCache>>,
+    spacing: Duration,
+    mut cancel: watch::Receiver<bool>,
+) -> usize {
+    let mut warmed = 0;
+    let sort = FavoritesSort::default();
+
+    for (index, fav_type) in FAVORITE_TYPES.iter().enumerate() {
+        if index > 0 && !wait_or_cancel(spacing, &mut cancel).await {
+            return warmed;
+        }
+
+        let result = {
+            let client = client.lock().await;
+            client
+                .get_favorites_sorted(fav_type, FAVORITES_PAGE_LIMIT, 0, sort)
+                .await
+        };
+        match result {
+            Ok(favorites) => {
+                let cache = cache.lock().await;
+                match cache.set_favorites(fav_type, sort.key(), FAVORITES_PAGE_LIMIT, 0, &favorites.to_string()) {
+                    Ok(()) => warmed += 1,
+                    Err(e) => log::warn!("Cache warm: failed to store {} favorites: {}", fav_type, e),
+                }
+            }
+            Err(e) => log::warn!("Cache warm: failed to fetch {} favorites: {}", fav_type, e),
+        }
+    }
+
+    if !wait_or_cancel(spacing, &mut cancel).await {
+        return warmed;
+    }
+
+    let result = {
+        let client = client.lock().await;
+        client.get_user_playlists().await
+    };
+    match result {
+        Ok(playlists) => match serde_json::to_string(&playlists) {
+            Ok(json) => {
+                let cache = cache.lock().await;
+                match cache.set_user_playlists(&json) {
+                    Ok(()) => warmed += 1,
+                    Err(e) => log::warn!("Cache warm: failed to store playlists: {}", e),
+                }
+            }
+            Err(e) => log::warn!("Cache warm: failed to serialize playlists: {}", e),
+        },
+        Err(e) => log::warn!("Cache warm: failed to fetch playlists: {}", e),
+    }
+
+    log::info!("Cache warm complete ({} responses cached)", warmed);
+    warmed
+}
+
+/// Sleep for `spacing`; false if cancelled in the meantime
+async fn wait_or_cancel(spacing: Duration, cancel: &mut watch::Receiver<bool>) -> bool {
+    if *cancel.borrow() {
+        return false;
+    }
+    tokio::select! {
+        _ = tokio::time::sleep(spacing) => !*cancel.borrow(),
+        _ = cancel.changed() => {
+            log::info!("Cache warm cancelled");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::{logged_in_client, mount_secret_probe};
+    use std::path::Path;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_warm_after_login_hits_each_endpoint_once() {
+        let server = MockServer::start().await;
+
+        mount_secret_probe(&server).await;
+        for fav_type in FAVORITE_TYPES {
+            Mock::given(method("GET"))
+                .and(path(paths::FAVORITE_GET_USER_FAVORITES))
+                .and(query_param("type", fav_type))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "albums": { "items": [], "total": 0, "offset": 0, "limit": 50 },
+                    "tracks": { "items": [], "total": 0, "offset": 0, "limit": 50 },
+                    "artists": { "items": [], "total": 0, "offset": 0, "limit": 50 }
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path(paths::PLAYLIST_GET_USER_PLAYLISTS))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "playlists": { "items": [], "total": 0, "offset": 0, "limit": 500 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+
+        let client = Arc::new(Mutex::new(client));
+        let cache = Arc::new(Mutex::new(ApiCache::new(Path::new(":memory:")).unwrap()));
+        let (_cancel_tx, cancel_rx) = watch::channel(false);
+
+        let handle = tokio::spawn(warm_cache(
+            client,
+            cache.clone(),
+            Duration::from_millis(10),
+            cancel_rx,
+        ));
+        assert_eq!(handle.await.unwrap(), 4);
+
+        let cache = cache.lock().await;
+        assert!(cache.get_favorites("albums", FavoritesSort::default().key(), 50, 0, None).unwrap().is_some());
+        assert!(cache.get_user_playlists(None).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_warm_stops_when_cancelled() {
+        let (cancel_tx, mut cancel_rx) = watch::channel(false);
+        cancel_tx.send(true).unwrap();
+        assert!(!wait_or_cancel(Duration::from_secs(60), &mut cancel_rx).await);
+    }
+}
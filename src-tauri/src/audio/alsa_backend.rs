@@ -7,16 +7,18 @@
 //!
 //! Uses CPAL's ALSA host with specific device selection.
 
-use super::backend::{AlsaPlugin, AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult};
+use super::backend::{AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult, OpenedStream, cpal_buffer_size, device_buffer_range, DEFAULT_BUFFER_RANGE, device_sample_formats, negotiate_sample_format, validate_output_rate};
 use rodio::{
     cpal::{
         traits::{DeviceTrait, HostTrait},
-        BufferSize, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize, SupportedStreamConfig,
+        SampleRate, SupportedStreamConfig,
     },
-    OutputStream, OutputStreamHandle,
+    OutputStream,
 };
 use std::process::Command;
 
+use super::capabilities::{alsa_card_busy, alsa_card_index, read_usb_stream, DeviceCaps};
+
 pub struct AlsaBackend {
     host: rodio::cpal::Host,
 }
@@ -313,7 +315,7 @@ impl AudioBackend for AlsaBackend {
     fn create_output_stream(
         &self,
         config: &BackendConfig,
-    ) -> BackendResult<(OutputStream, OutputStreamHandle)> {
+    ) -> BackendResult<OpenedStream> {
         log::info!(
             "[ALSA Backend] Creating stream: {}Hz, {} channels, exclusive: {}, plugin: {:?}",
             config.sample_rate,
@@ -345,57 +347,57 @@ impl AudioBackend for AlsaBackend {
         let device_name = device.name().unwrap_or_else(|_| "unknown".to_string());
         log::info!("[ALSA Backend] Using device: {}", device_name);
 
-        // Create StreamConfig with requested sample rate
-        let stream_config = StreamConfig {
-            channels: config.channels,
-            sample_rate: SampleRate(config.sample_rate),
-            buffer_size: if config.exclusive_mode {
-                // Smaller buffer for exclusive mode = lower latency
-                BufferSize::Fixed(512)
-            } else {
-                BufferSize::Default
-            },
-        };
-
         // Check if device supports this configuration
-        let supported_configs = device
+        let supported_configs: Vec<_> = device
             .supported_output_configs()
-            .map_err(|e| format!("Failed to get supported configs: {}", e))?;
+            .map_err(|e| format!("Failed to get supported configs: {}", e))?
+            .collect();
 
-        let mut found_matching = false;
-        for range in supported_configs {
-            if range.channels() == config.channels
-                && config.sample_rate >= range.min_sample_rate().0
-                && config.sample_rate <= range.max_sample_rate().0
-            {
-                found_matching = true;
-                log::info!(
-                    "[ALSA Backend] Device supports {}Hz (range: {}-{}Hz)",
-                    config.sample_rate,
-                    range.min_sample_rate().0,
-                    range.max_sample_rate().0
-                );
-                break;
-            }
-        }
+        let ranges: Vec<(u16, u32, u32)> = supported_configs
+            .iter()
+            .map(|range| (range.channels(), range.min_sample_rate().0, range.max_sample_rate().0))
+            .collect();
 
-        if !found_matching {
+        let found_matching =
+            validate_output_rate(config.sample_rate, config.channels, &ranges, config.bit_perfect)?;
+
+        if found_matching {
+            log::info!("[ALSA Backend] Device supports {}Hz", config.sample_rate);
+        } else {
             log::warn!(
                 "[ALSA Backend] Device may not support {}Hz, attempting anyway",
                 config.sample_rate
             );
         }
 
+        // Resolve buffer/period sizes against what the device reports
+        let buffer = config.audio_config.resolve(
+            device_buffer_range(&supported_configs, config.channels),
+            config.exclusive_mode,
+        )?;
+        log::info!("[ALSA Backend] Buffer config: {:?}", buffer);
+
+        // Pick the sample format for the source depth
+        let sample_format = negotiate_sample_format(
+            &device_sample_formats(&supported_configs, config.channels),
+            config.source_bits,
+            config.bit_perfect,
+        )?;
+        log::info!("[ALSA Backend] Sample format: {:?} (source: {:?}-bit)", sample_format, config.source_bits);
+        let cpal_format = sample_format
+            .to_cpal()
+            .ok_or_else(|| format!("{:?} output is not supported", sample_format))?;
+
         // Create SupportedStreamConfig
         let supported_config = SupportedStreamConfig::new(
-            stream_config.channels,
-            stream_config.sample_rate,
-            SupportedBufferSize::Range { min: 64, max: 8192 },
-            SampleFormat::F32,
+            config.channels,
+            SampleRate(config.sample_rate),
+            DEFAULT_BUFFER_RANGE,
+            cpal_format,
         );
 
         // Create OutputStream with custom config
-        let stream = OutputStream::try_from_device_config(&device, supported_config)
+        let (stream, handle) = OutputStream::try_from_device_config_with_buffer_size(&device, supported_config, cpal_buffer_size(buffer))
             .map_err(|e| {
                 if config.exclusive_mode {
                     format!(
@@ -413,7 +415,34 @@ impl AudioBackend for AlsaBackend {
             config.exclusive_mode
         );
 
-        Ok(stream)
+        Ok(OpenedStream { stream, handle, buffer, sample_format })
+    }
+
+    fn device_capabilities(&self, device_id: &str) -> BackendResult<DeviceCaps> {
+        // CPAL doesn't enumerate every hw: device; those rely on the USB stream file
+        let configs: Vec<_> = self
+            .host
+            .output_devices()
+            .map_err(|e| format!("Failed to enumerate devices: {}", e))?
+            .find(|d| d.name().ok().as_deref() == Some(device_id))
+            .and_then(|device| device.supported_output_configs().ok())
+            .map(|configs| configs.collect())
+            .unwrap_or_default();
+
+        let mut caps = DeviceCaps::from_configs(&configs);
+        if let Some(card) = alsa_card_index(device_id) {
+            if let Some(altsets) = read_usb_stream(card) {
+                caps.merge_usb_stream(&altsets);
+            }
+            // Exclusive needs direct hardware access and nobody else holding the card
+            caps.exclusive_available = (device_id.starts_with("hw:") || device_id.starts_with("plughw:"))
+                && !alsa_card_busy(card);
+        }
+
+        if caps == DeviceCaps::default() {
+            return Err(format!("Device '{}' reported no capabilities", device_id));
+        }
+        Ok(caps)
     }
 
     fn is_available(&self) -> bool {
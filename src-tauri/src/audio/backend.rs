@@ -3,11 +3,27 @@
 //! Provides a unified interface for different audio backends (PipeWire, ALSA, PulseAudio)
 //! allowing users to choose their preferred audio stack.
 
+use rodio::cpal::{BufferSize, SampleFormat, SupportedBufferSize};
 use rodio::{OutputStream, OutputStreamHandle};
 use serde::{Deserialize, Serialize};
 
+use super::capabilities::DeviceCaps;
+use super::dsd::{negotiate_dsd_output, DsdMode, DsdOutput};
+
+/// Highest PCM sample rate we negotiate (768kHz, covers DoP carriers for DSD256)
+pub const MAX_PCM_SAMPLE_RATE: u32 = 768_000;
+
+/// CPAL's ALSA host configures the hardware buffer as two periods
+pub const PERIODS_PER_BUFFER: u32 = 2;
+
+/// Period size used in exclusive mode when none is configured
+pub const EXCLUSIVE_PERIOD_FRAMES: u32 = 512;
+
+/// Buffer range reported in the stream config; the size itself is passed separately
+pub const DEFAULT_BUFFER_RANGE: SupportedBufferSize = SupportedBufferSize::Range { min: 64, max: 8192 };
+
 /// Supported audio backends
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AudioBackendType {
     /// PipeWire backend (modern, recommended)
     /// - Supports device selection without changing system default
@@ -100,8 +116,256 @@ pub struct BackendConfig {
     /// Channels
     pub channels: u16,
 
+    /// Bit depth of the source (None = unknown or lossy)
+    pub source_bits: Option<u32>,
+
     /// Exclusive mode flag
     pub exclusive_mode: bool,
+
+    /// Bit-perfect flag (DAC passthrough) - unsupported rates are an error
+    /// instead of being handed to the device/server for resampling
+    pub bit_perfect: bool,
+
+    /// How DSD content is delivered
+    pub dsd_mode: DsdMode,
+
+    /// Requested buffer/period sizes
+    pub audio_config: AudioConfig,
+}
+
+impl BackendConfig {
+    /// How a `dsd_rate` stream is sent to a device with `caps` under this
+    /// config's DSD mode (see `dsd::negotiate_dsd_output`)
+    pub fn dsd_output(&self, dsd_rate: u32, caps: &DeviceCaps) -> BackendResult<DsdOutput> {
+        negotiate_dsd_output(self.dsd_mode, dsd_rate, caps, self.bit_perfect)
+    }
+}
+
+/// User-configured output buffering, in frames (None = device/server default).
+///
+/// Smaller buffers lower seek/volume latency but underrun on loaded systems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioConfig {
+    pub buffer_frames: Option<u32>,
+    pub period_frames: Option<u32>,
+}
+
+/// Buffering actually applied to an opened stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveBufferConfig {
+    pub buffer_frames: u32,
+    pub period_frames: u32,
+}
+
+impl AudioConfig {
+    /// Resolve the requested sizes against the device's reported period range.
+    ///
+    /// CPAL opens the buffer as exactly two periods, so a buffer and a period
+    /// that don't fit that are rejected, as are zero sizes; sizes outside the
+    /// device range are clamped into it.
+    /// Returns None when the device default should be used.
+    pub fn resolve(
+        &self,
+        device_range: Option<(u32, u32)>,
+        exclusive_mode: bool,
+    ) -> BackendResult<Option<EffectiveBufferConfig>> {
+        if self.buffer_frames == Some(0) || self.period_frames == Some(0) {
+            return Err("Buffer and period sizes must be greater than zero".to_string());
+        }
+        if let (Some(buffer), Some(period)) = (self.buffer_frames, self.period_frames) {
+            if buffer != period * PERIODS_PER_BUFFER {
+                return Err(format!(
+                    "Buffer of {} frames must hold exactly {} periods of {} frames",
+                    buffer, PERIODS_PER_BUFFER, period
+                ));
+            }
+        }
+
+        let requested = self
+            .period_frames
+            .or(self.buffer_frames.map(|b| (b / PERIODS_PER_BUFFER).max(1)))
+            .or(exclusive_mode.then_some(EXCLUSIVE_PERIOD_FRAMES));
+        let Some(requested) = requested else {
+            return Ok(None);
+        };
+
+        let period_frames = match device_range {
+            Some((min, max)) => {
+                let clamped = requested.clamp(min, max);
+                if clamped != requested {
+                    log::warn!(
+                        "Period of {} frames outside device range {}-{}, using {}",
+                        requested, min, max, clamped
+                    );
+                }
+                clamped
+            }
+            None => requested,
+        };
+
+        Ok(Some(EffectiveBufferConfig {
+            buffer_frames: period_frames * PERIODS_PER_BUFFER,
+            period_frames,
+        }))
+    }
+}
+
+/// Period-size range reported by the device for a channel count
+pub fn device_buffer_range(
+    configs: &[rodio::cpal::SupportedStreamConfigRange],
+    channels: u16,
+) -> Option<(u32, u32)> {
+    configs
+        .iter()
+        .filter(|c| c.channels() == channels)
+        .find_map(|c| match c.buffer_size() {
+            SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+            SupportedBufferSize::Unknown => None,
+        })
+}
+
+/// Buffer size to hand to CPAL, which takes it as the period size
+pub fn cpal_buffer_size(buffer: Option<EffectiveBufferConfig>) -> BufferSize {
+    match buffer {
+        Some(b) => BufferSize::Fixed(b.period_frames),
+        None => BufferSize::Default,
+    }
+}
+
+/// An opened output stream and the buffering it was opened with
+pub struct OpenedStream {
+    pub stream: OutputStream,
+    pub handle: OutputStreamHandle,
+    pub buffer: Option<EffectiveBufferConfig>,
+    pub sample_format: OutputSampleFormat,
+}
+
+/// PCM sample format of an output stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSampleFormat {
+    /// 16-bit signed integer
+    S16,
+    /// 24-bit signed integer, packed (S24_3LE)
+    S24,
+    /// 32-bit signed integer (24-bit content is carried in the top bits)
+    S32,
+    /// 32-bit float
+    F32,
+}
+
+impl OutputSampleFormat {
+    /// Formats that carry `bits` of integer PCM without conversion
+    fn is_lossless_for(self, bits: u32) -> bool {
+        match self {
+            Self::S16 => bits <= 16,
+            Self::S24 => bits <= 24,
+            Self::S32 => bits <= 32,
+            Self::F32 => false,
+        }
+    }
+
+    pub fn from_cpal(format: SampleFormat) -> Option<Self> {
+        match format {
+            SampleFormat::I16 => Some(Self::S16),
+            SampleFormat::I32 => Some(Self::S32),
+            SampleFormat::F32 => Some(Self::F32),
+            _ => None,
+        }
+    }
+
+    /// CPAL has no packed 24-bit format, so S24 can't be opened through it
+    pub fn to_cpal(self) -> Option<SampleFormat> {
+        match self {
+            Self::S16 => Some(SampleFormat::I16),
+            Self::S24 => None,
+            Self::S32 => Some(SampleFormat::I32),
+            Self::F32 => Some(SampleFormat::F32),
+        }
+    }
+}
+
+/// Sample formats a device offers for a channel count
+pub fn device_sample_formats(
+    configs: &[rodio::cpal::SupportedStreamConfigRange],
+    channels: u16,
+) -> Vec<OutputSampleFormat> {
+    let mut formats: Vec<OutputSampleFormat> = configs
+        .iter()
+        .filter(|c| c.channels() == channels)
+        .filter_map(|c| OutputSampleFormat::from_cpal(c.sample_format()))
+        .collect();
+    formats.sort();
+    formats.dedup();
+    formats
+}
+
+/// Bit depth the player's samples carry exactly: decoders produce f32,
+/// which holds integer PCM of up to 24 bits without loss
+pub const DECODED_BITS: u32 = 24;
+
+/// Pick the output sample format for a source of `source_bits` (None for
+/// lossy or unknown sources, played as 16-bit).
+///
+/// The smallest integer format holding the source unchanged wins: S16 for
+/// 16-bit content, S32 for 24-bit (S24 can't be opened through CPAL, so
+/// S32 carries it in the top bits). Without one, F32 and then the deepest
+/// integer format are used. In bit-perfect mode only formats holding the
+/// source unchanged are accepted; a source deeper than `DECODED_BITS` is
+/// still truncated by the decoder (see `describe_chain`).
+pub fn negotiate_sample_format(
+    supported: &[OutputSampleFormat],
+    source_bits: Option<u32>,
+    bit_perfect: bool,
+) -> BackendResult<OutputSampleFormat> {
+    use OutputSampleFormat::*;
+
+    let bits = source_bits.unwrap_or(16).min(DECODED_BITS);
+    let lossless = [S16, S32]
+        .into_iter()
+        .find(|format| supported.contains(format) && format.is_lossless_for(bits));
+
+    match lossless {
+        Some(format) => Ok(format),
+        None if bit_perfect => Err(format!(
+            "Device has no sample format for {}-bit samples without conversion and bit-perfect output is enabled",
+            bits
+        )),
+        // Device reported no usable formats: let CPAL convert from float
+        None => Ok([F32, S32, S16].into_iter().find(|format| supported.contains(format)).unwrap_or(F32)),
+    }
+}
+
+/// Check a requested rate against a device's supported ranges
+/// (`(channels, min_rate, max_rate)` per range).
+///
+/// Returns Ok(true) if a range matches. In bit-perfect mode an unsupported
+/// rate is an error; otherwise Ok(false) and the caller may attempt anyway.
+pub fn validate_output_rate(
+    sample_rate: u32,
+    channels: u16,
+    ranges: &[(u16, u32, u32)],
+    bit_perfect: bool,
+) -> BackendResult<bool> {
+    if sample_rate == 0 || sample_rate > MAX_PCM_SAMPLE_RATE {
+        return Err(format!(
+            "Sample rate {}Hz is outside the supported PCM range (max {}Hz)",
+            sample_rate, MAX_PCM_SAMPLE_RATE
+        ));
+    }
+
+    let supported = ranges
+        .iter()
+        .any(|&(ch, min, max)| ch == channels && sample_rate >= min && sample_rate <= max);
+
+    if !supported && bit_perfect {
+        return Err(format!(
+            "Device does not support {}Hz/{}ch and bit-perfect output is enabled",
+            sample_rate, channels
+        ));
+    }
+
+    Ok(supported)
 }
 
 /// Result type for backend operations
@@ -119,10 +383,10 @@ pub trait AudioBackend: Send + Sync {
     fn enumerate_devices(&self) -> BackendResult<Vec<AudioDevice>>;
 
     /// Create an output stream for the given configuration
-    fn create_output_stream(
-        &self,
-        config: &BackendConfig,
-    ) -> BackendResult<(OutputStream, OutputStreamHandle)>;
+    fn create_output_stream(&self, config: &BackendConfig) -> BackendResult<OpenedStream>;
+
+    /// Query what a device supports (rates, formats, channels, DSD)
+    fn device_capabilities(&self, device_id: &str) -> BackendResult<DeviceCaps>;
 
     /// Check if this backend is available on the current system
     fn is_available(&self) -> bool;
@@ -210,3 +474,84 @@ impl BackendManager {
             .unwrap_or(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGES: &[(u16, u32, u32)] = &[(2, 44_100, 384_000)];
+
+    #[test]
+    fn test_buffer_config_clamped_to_device_range() {
+        let config = AudioConfig { buffer_frames: None, period_frames: Some(16) };
+        let effective = config.resolve(Some((64, 8192)), false).unwrap().unwrap();
+        assert_eq!(effective.period_frames, 64);
+        assert_eq!(effective.buffer_frames, 128);
+
+        let config = AudioConfig { buffer_frames: Some(65_536), period_frames: None };
+        let effective = config.resolve(Some((64, 8192)), false).unwrap().unwrap();
+        assert_eq!(effective, EffectiveBufferConfig { buffer_frames: 16_384, period_frames: 8192 });
+    }
+
+    #[test]
+    fn test_buffer_config_rejects_invalid_sizes() {
+        assert!(AudioConfig { buffer_frames: Some(0), period_frames: None }
+            .resolve(None, false)
+            .is_err());
+        assert!(AudioConfig { buffer_frames: Some(512), period_frames: Some(512) }
+            .resolve(None, false)
+            .is_err());
+        assert!(AudioConfig { buffer_frames: Some(4096), period_frames: Some(512) }
+            .resolve(None, false)
+            .is_err());
+        assert_eq!(
+            AudioConfig { buffer_frames: Some(1024), period_frames: Some(512) }
+                .resolve(None, false)
+                .unwrap(),
+            Some(EffectiveBufferConfig { buffer_frames: 1024, period_frames: 512 })
+        );
+        assert_eq!(AudioConfig::default().resolve(Some((64, 8192)), false).unwrap(), None);
+        assert_eq!(
+            AudioConfig::default().resolve(None, true).unwrap().map(|b| b.period_frames),
+            Some(EXCLUSIVE_PERIOD_FRAMES)
+        );
+    }
+
+    #[test]
+    fn test_validate_output_rate_supported() {
+        assert_eq!(validate_output_rate(192_000, 2, RANGES, true), Ok(true));
+    }
+
+    #[test]
+    fn test_validate_output_rate_rejects_unsupported_in_bit_perfect() {
+        assert!(validate_output_rate(768_000, 2, RANGES, true).is_err());
+        assert_eq!(validate_output_rate(768_000, 2, RANGES, false), Ok(false));
+    }
+
+    #[test]
+    fn test_negotiate_sample_format_follows_source_depth() {
+        use OutputSampleFormat::*;
+
+        // 16-bit and lossy sources
+        assert_eq!(negotiate_sample_format(&[S16, S24, S32, F32], Some(16), true), Ok(S16));
+        assert_eq!(negotiate_sample_format(&[S16, S32], None, true), Ok(S16));
+        // S24 can't be opened; S32 holds the samples unchanged
+        assert_eq!(negotiate_sample_format(&[S24, S32, F32], Some(16), true), Ok(S32));
+        assert_eq!(negotiate_sample_format(&[S24, F32], Some(16), false), Ok(F32));
+        assert!(negotiate_sample_format(&[S24, F32], Some(16), true).is_err());
+        assert_eq!(negotiate_sample_format(&[], None, false), Ok(F32));
+
+        // A 24-bit source goes to S32 on a capable device...
+        assert_eq!(negotiate_sample_format(&[S16, S24, S32, F32], Some(24), true), Ok(S32));
+        assert_eq!(negotiate_sample_format(&[S16, S32], Some(24), false), Ok(S32));
+        // ...and is refused by a 16-bit-only device in bit-perfect mode
+        assert!(negotiate_sample_format(&[S16], Some(24), true).is_err());
+        assert_eq!(negotiate_sample_format(&[S16], Some(24), false), Ok(S16));
+        assert_eq!(negotiate_sample_format(&[S16, F32], Some(24), false), Ok(F32));
+    }
+
+    #[test]
+    fn test_validate_output_rate_rejects_out_of_range() {
+        assert!(validate_output_rate(1_536_000, 2, RANGES, false).is_err());
+    }
+}
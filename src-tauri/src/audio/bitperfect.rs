@@ -0,0 +1,357 @@
+//! Bit-perfect self-test
+//!
+//! Plays a known 16-bit PCM pattern through the player, as a WAV file taking
+//! the same decode, gain and mixer path as a track, while recording the
+//! output device's loopback or monitor source, then compares the capture
+//! sample for sample. Anything in the path that touches the samples (a
+//! mixer, volume, resampling) shows up as a mismatch. Without a loopback
+//! source for that device the result is "cannot verify".
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, BufferSize, SampleRate, StreamConfig};
+use serde::Serialize;
+
+pub const TEST_SAMPLE_RATE: u32 = 44_100;
+pub const TEST_CHANNELS: u16 = 2;
+/// Pattern length in frames (one second)
+const TEST_FRAMES: usize = TEST_SAMPLE_RATE as usize;
+/// Leading samples used to find the pattern in the capture
+const SYNC_SAMPLES: usize = 64;
+/// Extra capture time after the pattern for output latency
+pub const CAPTURE_TAIL: Duration = Duration::from_millis(500);
+
+/// Deterministic full-scale noise, so every bit of every sample is exercised
+#[derive(Debug, Clone)]
+pub struct TestPattern {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<i16>,
+}
+
+impl TestPattern {
+    pub fn new() -> Self {
+        let mut state: u32 = 0x9E37_79B9;
+        let samples = (0..TEST_FRAMES * TEST_CHANNELS as usize)
+            .map(|_| {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 16) as u16 as i16
+            })
+            .collect();
+        Self { sample_rate: TEST_SAMPLE_RATE, channels: TEST_CHANNELS, samples }
+    }
+
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+
+    /// The pattern as a 16-bit WAV file, for playing it like a track
+    pub fn to_wav(&self) -> Vec<u8> {
+        super::wav::pcm16(self.sample_rate, self.channels, &self.samples)
+    }
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plays the pattern to the device under test, returning once it has played
+pub trait PatternOutput {
+    fn name(&self) -> Option<String>;
+    fn play(&mut self, pattern: &TestPattern) -> Result<(), String>;
+}
+
+/// Records what the output device actually received
+pub trait LoopbackCapture {
+    fn name(&self) -> Option<String>;
+    fn start(&mut self, sample_rate: u32, channels: u16) -> Result<(), String>;
+    /// Stop recording and return the interleaved samples
+    fn finish(&mut self) -> Result<Vec<i16>, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitPerfectStatus {
+    Pass,
+    Fail,
+    /// No loopback/monitor source to capture the output with
+    CannotVerify,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BitPerfectReport {
+    pub status: BitPerfectStatus,
+    pub output_device: Option<String>,
+    pub loopback_device: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples_compared: usize,
+    pub mismatched_samples: usize,
+    /// Index (into the pattern) of the first modified sample
+    pub first_mismatch: Option<usize>,
+    /// Largest difference between a sent and a captured sample
+    pub max_deviation: u32,
+    /// What was found, when not a clean pass
+    pub detail: Option<String>,
+}
+
+/// Play the test pattern on `output` and compare what `loopback` captured
+pub fn run_bitperfect_test(
+    output: &mut dyn PatternOutput,
+    loopback: Option<&mut dyn LoopbackCapture>,
+) -> Result<BitPerfectReport, String> {
+    let pattern = TestPattern::new();
+    let mut report = BitPerfectReport {
+        status: BitPerfectStatus::CannotVerify,
+        output_device: output.name(),
+        loopback_device: None,
+        sample_rate: pattern.sample_rate,
+        channels: pattern.channels,
+        samples_compared: 0,
+        mismatched_samples: 0,
+        first_mismatch: None,
+        max_deviation: 0,
+        detail: None,
+    };
+
+    let Some(loopback) = loopback else {
+        report.detail = Some("No loopback or monitor source available".to_string());
+        return Ok(report);
+    };
+    report.loopback_device = loopback.name();
+
+    loopback.start(pattern.sample_rate, pattern.channels)?;
+    let played = output.play(&pattern);
+    let captured = loopback.finish()?;
+    played?;
+
+    compare(&pattern, &captured, &mut report);
+    Ok(report)
+}
+
+/// Find the pattern in the capture and compare it sample for sample
+fn compare(pattern: &TestPattern, captured: &[i16], report: &mut BitPerfectReport) {
+    report.status = BitPerfectStatus::Fail;
+
+    let sync = &pattern.samples[..SYNC_SAMPLES];
+    let channels = pattern.channels as usize;
+    let Some(start) = captured
+        .windows(SYNC_SAMPLES)
+        .enumerate()
+        .step_by(channels)
+        .find(|(_, window)| *window == sync)
+        .map(|(index, _)| index)
+    else {
+        report.detail = Some(if captured.iter().all(|&s| s == 0) {
+            "Nothing was captured from the loopback source".to_string()
+        } else {
+            "Test pattern not found in the capture: the output was resampled, mixed or level-changed"
+                .to_string()
+        });
+        return;
+    };
+
+    let received = &captured[start..];
+    for (index, (&sent, &got)) in pattern.samples.iter().zip(received).enumerate() {
+        if sent != got {
+            report.mismatched_samples += 1;
+            report.first_mismatch.get_or_insert(index);
+            report.max_deviation = report.max_deviation.max((sent as i32 - got as i32).unsigned_abs());
+        }
+    }
+    report.samples_compared = received.len().min(pattern.samples.len());
+
+    if report.samples_compared < pattern.samples.len() {
+        report.detail = Some(format!(
+            "Capture ended after {} of {} samples",
+            report.samples_compared,
+            pattern.samples.len()
+        ));
+    } else if report.mismatched_samples > 0 {
+        report.detail = Some(format!(
+            "{} of {} samples were modified (max deviation {})",
+            report.mismatched_samples, report.samples_compared, report.max_deviation
+        ));
+    } else {
+        report.status = BitPerfectStatus::Pass;
+    }
+}
+
+fn stream_config(sample_rate: u32, channels: u16) -> StreamConfig {
+    StreamConfig { channels, sample_rate: SampleRate(sample_rate), buffer_size: BufferSize::Default }
+}
+
+/// Records 16-bit samples from a cpal input (loopback or monitor) device
+pub struct CpalLoopbackCapture {
+    device: cpal::Device,
+    stream: Option<cpal::Stream>,
+    captured: Arc<Mutex<Vec<i16>>>,
+}
+
+impl CpalLoopbackCapture {
+    pub fn new(device: cpal::Device) -> Self {
+        Self { device, stream: None, captured: Arc::default() }
+    }
+
+    /// The monitor or loopback source of `output`, if it has one
+    pub fn find(host: &cpal::Host, output: &str) -> Option<Self> {
+        let mut candidates: Vec<(String, cpal::Device)> = host
+            .input_devices()
+            .ok()?
+            .filter_map(|device| Some((device.name().ok()?, device)))
+            .collect();
+        let names: Vec<&str> = candidates.iter().map(|(name, _)| name.as_str()).collect();
+        let index = loopback_for(&names, output)?;
+        Some(Self::new(candidates.swap_remove(index).1))
+    }
+}
+
+/// Index of the input that records `output`: its monitor, or for a loopback
+/// (snd-aloop) output, a loopback input. Another device's monitor would
+/// capture unrelated audio, so anything else is no match.
+fn loopback_for(inputs: &[&str], output: &str) -> Option<usize> {
+    let output_lower = output.to_lowercase();
+    inputs.iter().position(|input| {
+        let lower = input.to_lowercase();
+        (lower.contains("monitor") && input.contains(output))
+            || (lower.contains("loopback") && output_lower.contains("loopback"))
+    })
+}
+
+impl LoopbackCapture for CpalLoopbackCapture {
+    fn name(&self) -> Option<String> {
+        self.device.name().ok()
+    }
+
+    fn start(&mut self, sample_rate: u32, channels: u16) -> Result<(), String> {
+        let captured = self.captured.clone();
+        let stream = self
+            .device
+            .build_input_stream(
+                &stream_config(sample_rate, channels),
+                move |data: &[i16], _| {
+                    if let Ok(mut buffer) = captured.lock() {
+                        buffer.extend_from_slice(data);
+                    }
+                },
+                |e| log::warn!("Bit-perfect test capture error: {}", e),
+                None,
+            )
+            .map_err(|e| format!("Loopback can't record 16-bit {} Hz: {}", sample_rate, e))?;
+        stream.play().map_err(|e| format!("Failed to start capture: {}", e))?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<i16>, String> {
+        drop(self.stream.take());
+        let mut captured = self.captured.lock().map_err(|e| format!("Lock error: {}", e))?;
+        Ok(std::mem::take(&mut *captured))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output wired straight into the capture, after `latency` silent samples
+    struct MockOutput {
+        wire: Arc<Mutex<Vec<i16>>>,
+        latency: usize,
+        modify: fn(usize, i16) -> i16,
+    }
+
+    impl PatternOutput for MockOutput {
+        fn name(&self) -> Option<String> {
+            Some("mock-dac".to_string())
+        }
+
+        fn play(&mut self, pattern: &TestPattern) -> Result<(), String> {
+            let mut wire = self.wire.lock().unwrap();
+            wire.extend(std::iter::repeat(0).take(self.latency));
+            wire.extend(pattern.samples.iter().enumerate().map(|(i, &s)| (self.modify)(i, s)));
+            wire.extend(std::iter::repeat(0).take(1000));
+            Ok(())
+        }
+    }
+
+    struct MockLoopback {
+        wire: Arc<Mutex<Vec<i16>>>,
+    }
+
+    impl LoopbackCapture for MockLoopback {
+        fn name(&self) -> Option<String> {
+            Some("mock-dac.monitor".to_string())
+        }
+
+        fn start(&mut self, _sample_rate: u32, _channels: u16) -> Result<(), String> {
+            self.wire.lock().unwrap().clear();
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<Vec<i16>, String> {
+            Ok(std::mem::take(&mut *self.wire.lock().unwrap()))
+        }
+    }
+
+    fn run(modify: fn(usize, i16) -> i16) -> BitPerfectReport {
+        let wire = Arc::new(Mutex::new(Vec::new()));
+        let mut output = MockOutput { wire: wire.clone(), latency: 882, modify };
+        let mut loopback = MockLoopback { wire };
+        run_bitperfect_test(&mut output, Some(&mut loopback)).unwrap()
+    }
+
+    #[test]
+    fn test_loopback_pass_modification_and_cannot_verify() {
+        let report = run(|_, s| s);
+        assert_eq!(report.status, BitPerfectStatus::Pass);
+        assert_eq!(report.samples_compared, TEST_FRAMES * TEST_CHANNELS as usize);
+        assert_eq!(report.mismatched_samples, 0);
+        assert_eq!(report.loopback_device.as_deref(), Some("mock-dac.monitor"));
+
+        // A single flipped LSB after the sync preamble is caught
+        let report = run(|i, s| if i == 1000 { s ^ 1 } else { s });
+        assert_eq!(report.status, BitPerfectStatus::Fail);
+        assert_eq!(report.mismatched_samples, 1);
+        assert_eq!(report.first_mismatch, Some(1000));
+        assert_eq!(report.max_deviation, 1);
+
+        // -6 dB of software volume: the pattern can't even be found
+        let report = run(|_, s| s / 2);
+        assert_eq!(report.status, BitPerfectStatus::Fail);
+        assert!(report.detail.unwrap().contains("not found"));
+
+        let wire = Arc::new(Mutex::new(Vec::new()));
+        let mut output = MockOutput { wire, latency: 0, modify: |_, s| s };
+        let report = run_bitperfect_test(&mut output, None).unwrap();
+        assert_eq!(report.status, BitPerfectStatus::CannotVerify);
+        assert_eq!(report.output_device.as_deref(), Some("mock-dac"));
+    }
+
+    #[test]
+    fn test_loopback_is_only_taken_from_the_output_device() {
+        let inputs = ["Built-in Mic", "Monitor of usb-dac", "Monitor of hdmi", "hw:CARD=Loopback,DEV=1"];
+
+        assert_eq!(loopback_for(&inputs, "hdmi"), Some(2));
+        assert_eq!(loopback_for(&inputs, "hw:CARD=Loopback,DEV=0"), Some(3));
+        // Another device's monitor would capture unrelated audio
+        assert_eq!(loopback_for(&inputs, "speakers"), None);
+        assert_eq!(loopback_for(&inputs[..1], "hdmi"), None);
+    }
+
+    #[test]
+    fn test_pattern_wav_holds_the_samples() {
+        let pattern = TestPattern::new();
+        let wav = pattern.to_wav();
+        assert_eq!(wav.len(), crate::audio::wav::HEADER_LEN + pattern.samples.len() * 2);
+        assert_eq!(&wav[44..46], &pattern.samples[0].to_le_bytes());
+    }
+}
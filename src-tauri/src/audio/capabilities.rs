@@ -0,0 +1,475 @@
+//! Output device capability detection
+//!
+//! Reports what a device can play so the UI can show e.g. "up to
+//! 384kHz/32-bit, DSD256". PCM ranges come from the device's hw params (as
+//! reported through CPAL) or, for PipeWire/Pulse sinks, the sink's sample
+//! spec. USB DACs additionally list every altsetting in
+//! `/proc/asound/cardN/stream0`, the only place native DSD formats and
+//! packed 24-bit show up.
+//!
+//! Devices often report partial information: no configs at all, an
+//! unbounded rate range from a plug device, or only the current sample
+//! spec. Every field is best-effort and unknown values stay None.
+
+use rodio::cpal::SupportedStreamConfigRange;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use super::backend::{AudioBackend, AudioBackendType, AudioDevice, BackendResult, OutputSampleFormat, MAX_PCM_SAMPLE_RATE};
+use super::dsd::{dop_pcm_rate, DSD128_RATE, DSD64_RATE};
+
+/// DSD rates from DSD64 up to DSD512
+const DSD_RATES: [u32; 4] = [DSD64_RATE, DSD128_RATE, DSD128_RATE * 2, DSD128_RATE * 4];
+
+/// What an output device supports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceCaps {
+    /// Highest PCM sample rate
+    pub max_rate: Option<u32>,
+    /// Highest PCM bit depth
+    pub max_bits: Option<u32>,
+    pub formats: Vec<OutputSampleFormat>,
+    pub max_channels: Option<u16>,
+    /// DSD playback, either native or as DoP
+    pub supports_dsd: bool,
+    /// Highest DSD rate (2822400 = DSD64)
+    pub max_dsd_rate: Option<u32>,
+    /// Highest DSD rate the driver takes natively, without DoP
+    #[serde(default)]
+    pub max_native_dsd_rate: Option<u32>,
+    /// The device can be opened exclusively for bit-perfect playback
+    pub exclusive_available: bool,
+}
+
+impl DeviceCaps {
+    /// PCM capabilities from the configs CPAL reports for a device
+    pub fn from_configs(configs: &[SupportedStreamConfigRange]) -> Self {
+        let mut caps = Self::default();
+        for config in configs {
+            // Plug devices advertise anything up to u32::MAX and convert
+            let max_rate = config.max_sample_rate().0.min(MAX_PCM_SAMPLE_RATE);
+            if max_rate > 0 {
+                caps.add_pcm(OutputSampleFormat::from_cpal(config.sample_format()), max_rate, config.channels());
+            }
+        }
+        caps.refresh_dsd();
+        caps
+    }
+
+    /// Capabilities from a PipeWire/Pulse sample spec (the sink's current
+    /// format, so a lower bound of what the device supports)
+    pub fn from_sample_spec(spec: &SampleSpec) -> Self {
+        let mut caps = Self::default();
+        caps.add_pcm(spec.format, spec.rate, spec.channels);
+        caps.refresh_dsd();
+        caps
+    }
+
+    /// Add the altsettings a USB DAC lists in its stream file
+    pub fn merge_usb_stream(&mut self, altsets: &[UsbAltset]) {
+        for altset in altsets {
+            let Some(max_rate) = altset.rates.iter().copied().max() else {
+                continue;
+            };
+            match dsd_bits_per_frame(&altset.format) {
+                Some(bits) => {
+                    let dsd_rate = max_rate * bits;
+                    self.max_dsd_rate = self.max_dsd_rate.max(Some(dsd_rate));
+                    self.max_native_dsd_rate = self.max_native_dsd_rate.max(Some(dsd_rate));
+                    self.max_channels = self.max_channels.max(Some(altset.channels));
+                }
+                None => {
+                    self.add_pcm(alsa_sample_format(&altset.format), max_rate, altset.channels);
+                    self.max_bits = self.max_bits.max(altset.bits);
+                }
+            }
+        }
+        self.refresh_dsd();
+    }
+
+    fn add_pcm(&mut self, format: Option<OutputSampleFormat>, max_rate: u32, channels: u16) {
+        self.max_rate = self.max_rate.max(Some(max_rate.min(MAX_PCM_SAMPLE_RATE)));
+        if channels > 0 {
+            self.max_channels = self.max_channels.max(Some(channels));
+        }
+        if let Some(format) = format {
+            if !self.formats.contains(&format) {
+                self.formats.push(format);
+                self.formats.sort();
+            }
+            // Float is converted by the driver and says nothing about the DAC
+            let bits = match format {
+                OutputSampleFormat::S16 => Some(16),
+                OutputSampleFormat::S24 => Some(24),
+                OutputSampleFormat::S32 => Some(32),
+                OutputSampleFormat::F32 => None,
+            };
+            self.max_bits = self.max_bits.max(bits);
+        }
+    }
+
+    /// DoP needs a 24-bit integer container at a sixteenth of the DSD rate
+    fn refresh_dsd(&mut self) {
+        let holds_24_bit = self.formats.iter().any(|f| matches!(f, OutputSampleFormat::S24 | OutputSampleFormat::S32));
+        if let (true, Some(max_rate)) = (holds_24_bit, self.max_rate) {
+            let dop = DSD_RATES.iter().copied().filter(|&rate| dop_pcm_rate(rate) <= max_rate).max();
+            self.max_dsd_rate = self.max_dsd_rate.max(dop);
+        }
+        self.supports_dsd = self.max_dsd_rate.is_some();
+    }
+}
+
+/// ALSA PCM format name to an output format
+fn alsa_sample_format(name: &str) -> Option<OutputSampleFormat> {
+    match name {
+        "S16_LE" | "S16_BE" => Some(OutputSampleFormat::S16),
+        "S24_3LE" | "S24_3BE" => Some(OutputSampleFormat::S24),
+        // S24_LE carries 24 bits in a 32-bit container
+        "S24_LE" | "S24_BE" | "S32_LE" | "S32_BE" => Some(OutputSampleFormat::S32),
+        "FLOAT_LE" | "FLOAT_BE" => Some(OutputSampleFormat::F32),
+        _ => None,
+    }
+}
+
+/// DSD bits carried per channel in one frame of a native DSD format
+fn dsd_bits_per_frame(name: &str) -> Option<u32> {
+    match name {
+        "DSD_U8" => Some(8),
+        "DSD_U16_LE" | "DSD_U16_BE" => Some(16),
+        "DSD_U32_LE" | "DSD_U32_BE" => Some(32),
+        _ => None,
+    }
+}
+
+/// One playback altsetting of a USB audio interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbAltset {
+    pub format: String,
+    pub channels: u16,
+    /// Listed rates; a continuous range is kept as its two ends
+    pub rates: Vec<u32>,
+    pub bits: Option<u32>,
+}
+
+/// Parse the playback altsettings of `/proc/asound/cardN/stream0`
+pub fn parse_usb_stream(contents: &str) -> Vec<UsbAltset> {
+    let mut altsets = Vec::new();
+    let mut current: Option<UsbAltset> = None;
+    let mut in_playback = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with("Playback:") {
+            in_playback = true;
+            continue;
+        }
+        if line.starts_with("Capture:") {
+            in_playback = false;
+        }
+        if !in_playback {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "Format" => {
+                altsets.extend(current.take());
+                current = Some(UsbAltset {
+                    // "Format: S32_LE" (older kernels list several, keep the first)
+                    format: value.split_whitespace().next().unwrap_or("").to_string(),
+                    channels: 0,
+                    rates: Vec::new(),
+                    bits: None,
+                });
+            }
+            "Channels" => {
+                if let Some(altset) = current.as_mut() {
+                    altset.channels = value.parse().unwrap_or(0);
+                }
+            }
+            "Rates" => {
+                // "44100, 48000, 96000" or "8000 - 96000 (continuous)"
+                if let Some(altset) = current.as_mut() {
+                    altset.rates = value
+                        .split([',', '-', '('])
+                        .filter_map(|rate| rate.trim().parse().ok())
+                        .collect();
+                }
+            }
+            "Bits" => {
+                if let Some(altset) = current.as_mut() {
+                    altset.bits = value.parse().ok();
+                }
+            }
+            _ => {}
+        }
+    }
+    altsets.extend(current);
+    altsets
+}
+
+/// Current sample spec of a PipeWire/Pulse sink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleSpec {
+    pub format: Option<OutputSampleFormat>,
+    pub channels: u16,
+    pub rate: u32,
+}
+
+/// Sink details from `pactl list sinks` needed for capability detection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PactlSink {
+    pub sample_spec: Option<SampleSpec>,
+    /// ALSA card backing the sink, if any
+    pub alsa_card: Option<u32>,
+}
+
+/// Find `sink_name` in `pactl list sinks` output
+pub fn parse_pactl_sink(output: &str, sink_name: &str) -> Option<PactlSink> {
+    let mut found: Option<PactlSink> = None;
+    let mut in_sink = false;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("Sink #") {
+            if found.is_some() {
+                break;
+            }
+            in_sink = false;
+        } else if let Some(name) = line.strip_prefix("Name:") {
+            in_sink = name.trim() == sink_name;
+            if in_sink {
+                found = Some(PactlSink::default());
+            }
+        } else if !in_sink {
+            continue;
+        } else if let Some(spec) = line.strip_prefix("Sample Specification:") {
+            // "s32le 2ch 192000Hz"
+            let mut parts = spec.split_whitespace();
+            let format = parts.next().and_then(|f| match f {
+                "s16le" | "s16be" => Some(OutputSampleFormat::S16),
+                "s24le" | "s24be" => Some(OutputSampleFormat::S24),
+                "s24-32le" | "s24-32be" | "s32le" | "s32be" => Some(OutputSampleFormat::S32),
+                "float32le" | "float32be" => Some(OutputSampleFormat::F32),
+                _ => None,
+            });
+            let channels = parts.next().and_then(|c| c.strip_suffix("ch")?.parse().ok());
+            let rate = parts.next().and_then(|r| r.strip_suffix("Hz")?.parse().ok());
+            if let (Some(sink), Some(channels), Some(rate)) = (found.as_mut(), channels, rate) {
+                sink.sample_spec = Some(SampleSpec { format, channels, rate });
+            }
+        } else if let Some(card) = line.strip_prefix("alsa.card =") {
+            if let Some(sink) = found.as_mut() {
+                sink.alsa_card = card.trim().trim_matches('"').parse().ok();
+            }
+        }
+    }
+    found
+}
+
+/// Card number of an ALSA device id ("hw:2,0", "plughw:CARD=DAC,DEV=0")
+pub fn alsa_card_index(device_id: &str) -> Option<u32> {
+    let (_, spec) = device_id.split_once(':')?;
+    let card = spec.split(',').next()?;
+    let card = card.strip_prefix("CARD=").unwrap_or(card);
+    if let Ok(index) = card.parse() {
+        return Some(index);
+    }
+    // Named cards are symlinks to their cardN directory
+    let target = fs::read_link(format!("/proc/asound/{}", card)).ok()?;
+    target.to_str()?.strip_prefix("card")?.parse().ok()
+}
+
+/// Playback altsettings of a USB card (None for non-USB cards)
+pub fn read_usb_stream(card: u32) -> Option<Vec<UsbAltset>> {
+    let contents = fs::read_to_string(format!("/proc/asound/card{}/stream0", card)).ok()?;
+    Some(parse_usb_stream(&contents))
+}
+
+/// Whether any playback substream of the card is open
+pub fn alsa_card_busy(card: u32) -> bool {
+    let pattern = format!("/proc/asound/card{}/pcm*p/sub*/status", card);
+    let Ok(paths) = glob::glob(&pattern) else {
+        return false;
+    };
+    paths
+        .flatten()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .any(|status| status.trim() != "closed")
+}
+
+/// Capabilities per device, kept until the device disconnects
+#[derive(Default)]
+pub struct CapabilityCache {
+    entries: Mutex<HashMap<(AudioBackendType, String), DeviceCaps>>,
+}
+
+impl CapabilityCache {
+    pub fn get_or_query(&self, backend: &dyn AudioBackend, device_id: &str) -> BackendResult<DeviceCaps> {
+        let key = (backend.backend_type(), device_id.to_string());
+        if let Some(caps) = self.entries.lock().ok().and_then(|entries| entries.get(&key).cloned()) {
+            return Ok(caps);
+        }
+
+        let caps = backend.device_capabilities(device_id)?;
+        log::info!("Capabilities of {}: {:?}", device_id, caps);
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(key, caps.clone());
+        }
+        Ok(caps)
+    }
+
+    /// Forget devices of a backend that are no longer connected
+    pub fn retain_connected(&self, backend_type: AudioBackendType, connected: &[AudioDevice]) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|(backend, id), _| {
+                *backend != backend_type || connected.iter().any(|device| &device.id == id)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::backend::{BackendConfig, OpenedStream};
+    use rodio::cpal::{SampleFormat, SampleRate, SupportedBufferSize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A USB DAC: 16/32-bit PCM up to 384kHz plus native DSD256
+    const STREAM0: &str = "\
+Topping DX3 Pro at usb-0000:00:14.0-2, high speed : USB Audio
+
+Playback:
+  Status: Stop
+  Interface 1
+    Altset 1
+    Format: S16_LE
+    Channels: 2
+    Endpoint: 0x01 (1 OUT) (ASYNC)
+    Rates: 44100, 48000, 88200, 96000, 176400, 192000, 352800, 384000
+    Bits: 16
+  Interface 1
+    Altset 2
+    Format: S32_LE
+    Channels: 2
+    Endpoint: 0x01 (1 OUT) (ASYNC)
+    Rates: 44100, 48000, 88200, 96000, 176400, 192000, 352800, 384000
+    Bits: 32
+  Interface 1
+    Altset 3
+    Format: DSD_U32_BE
+    Channels: 2
+    Endpoint: 0x01 (1 OUT) (ASYNC)
+    Rates: 88200, 176400, 352800
+    Bits: 32
+
+Capture:
+  Interface 2
+    Altset 1
+    Format: S16_LE
+    Channels: 8
+    Rates: 48000
+";
+
+    fn config(channels: u16, min: u32, max: u32, format: SampleFormat) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(channels, SampleRate(min), SampleRate(max), SupportedBufferSize::Unknown, format)
+    }
+
+    #[test]
+    fn test_usb_dac_capabilities() {
+        // hw params only report 16/32-bit stereo; native DSD comes from stream0
+        let mut caps = DeviceCaps::from_configs(&[
+            config(2, 44_100, 384_000, SampleFormat::I16),
+            config(2, 44_100, 384_000, SampleFormat::I32),
+        ]);
+        caps.merge_usb_stream(&parse_usb_stream(STREAM0));
+
+        assert_eq!(
+            caps,
+            DeviceCaps {
+                max_rate: Some(384_000),
+                max_bits: Some(32),
+                formats: vec![OutputSampleFormat::S16, OutputSampleFormat::S32],
+                // Capture channels are ignored
+                max_channels: Some(2),
+                supports_dsd: true,
+                max_dsd_rate: Some(DSD128_RATE * 2),
+                max_native_dsd_rate: Some(DSD128_RATE * 2),
+                exclusive_available: false,
+            }
+        );
+
+        // A plug device with an unbounded range and no usable formats
+        let plug = DeviceCaps::from_configs(&[config(2, 4_000, u32::MAX, SampleFormat::U8)]);
+        assert_eq!(plug.max_rate, Some(MAX_PCM_SAMPLE_RATE));
+        assert!(plug.formats.is_empty());
+        assert!(!plug.supports_dsd);
+
+        // Only the sink's current spec is known: 24-bit at 192kHz carries DSD64 as DoP
+        let pactl = "Sink #1\n\tName: alsa_output.usb\n\tSample Specification: s24le 2ch 192000Hz\n\
+            \tProperties:\n\t\talsa.card = \"2\"\n";
+        let sink = parse_pactl_sink(pactl, "alsa_output.usb").unwrap();
+        assert_eq!(sink.alsa_card, Some(2));
+        let caps = DeviceCaps::from_sample_spec(&sink.sample_spec.unwrap());
+        assert_eq!(caps.max_bits, Some(24));
+        assert_eq!(caps.max_dsd_rate, Some(DSD64_RATE));
+        assert!(parse_pactl_sink(pactl, "other").is_none());
+
+        assert_eq!(alsa_card_index("hw:3,0"), Some(3));
+        assert_eq!(alsa_card_index("plughw:CARD=1,DEV=0"), Some(1));
+    }
+
+    /// Device reporting a fixed 16-bit/48kHz capability set
+    struct MockBackend {
+        queries: AtomicUsize,
+    }
+
+    impl AudioBackend for MockBackend {
+        fn backend_type(&self) -> AudioBackendType {
+            AudioBackendType::Alsa
+        }
+
+        fn enumerate_devices(&self) -> BackendResult<Vec<AudioDevice>> {
+            Ok(Vec::new())
+        }
+
+        fn create_output_stream(&self, _config: &BackendConfig) -> BackendResult<OpenedStream> {
+            Err("mock device has no output".to_string())
+        }
+
+        fn device_capabilities(&self, _device_id: &str) -> BackendResult<DeviceCaps> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            Ok(DeviceCaps::from_configs(&[config(2, 48_000, 48_000, SampleFormat::I16)]))
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn description(&self) -> &'static str {
+            "mock"
+        }
+    }
+
+    #[test]
+    fn test_capabilities_cached_until_disconnect() {
+        let backend = MockBackend { queries: AtomicUsize::new(0) };
+        let cache = CapabilityCache::default();
+
+        let caps = cache.get_or_query(&backend, "hw:0,0").unwrap();
+        assert_eq!((caps.max_rate, caps.max_bits, caps.supports_dsd), (Some(48_000), Some(16), false));
+        assert_eq!(cache.get_or_query(&backend, "hw:0,0").unwrap(), caps);
+        assert_eq!(backend.queries.load(Ordering::SeqCst), 1);
+
+        // Unplugged: the next query goes to the device again
+        cache.retain_connected(AudioBackendType::Alsa, &[]);
+        cache.get_or_query(&backend, "hw:0,0").unwrap();
+        assert_eq!(backend.queries.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,203 @@
+//! Channel mapping for non-stereo outputs
+//!
+//! A mono speaker that just plays the left channel loses half the mix, and
+//! a multichannel interface gets stereo spread by the driver in whatever way
+//! it likes. `ChannelMap` turns stereo content into the layout the output
+//! actually has: a mono downmix or the two channels placed on a chosen pair
+//! of a multichannel device (the other channels silent). Content that isn't
+//! stereo is passed through. Never applied in bit-perfect mode (see
+//! `AudioSettings::effective_channel_mode`).
+
+use rodio::source::SeekError;
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Most output channels accepted for multichannel passthrough
+pub const MAX_DEVICE_CHANNELS: u16 = 8;
+
+/// Gain applied to each channel when summing stereo to mono
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DownmixLaw {
+    /// -3 dB: keeps the power of uncorrelated content, may clip centered content
+    #[default]
+    Minus3Db,
+    /// -6 dB: plain average, never clips
+    Minus6Db,
+}
+
+impl DownmixLaw {
+    pub fn gain(&self) -> f32 {
+        match self {
+            DownmixLaw::Minus3Db => std::f32::consts::FRAC_1_SQRT_2,
+            DownmixLaw::Minus6Db => 0.5,
+        }
+    }
+}
+
+/// How stereo content is laid out on the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ChannelMode {
+    /// Left/right as decoded
+    #[default]
+    Stereo,
+    /// Sum both channels into one
+    Mono { law: DownmixLaw },
+    /// Left/right on channels `left`/`right` (0-based) of a device with
+    /// `device_channels` outputs
+    Multichannel { device_channels: u16, left: u16, right: u16 },
+}
+
+impl ChannelMode {
+    pub fn validate(&self) -> Result<(), String> {
+        if let ChannelMode::Multichannel { device_channels, left, right } = *self {
+            if !(3..=MAX_DEVICE_CHANNELS).contains(&device_channels) {
+                return Err(format!("Device channels must be between 3 and {}", MAX_DEVICE_CHANNELS));
+            }
+            if left >= device_channels || right >= device_channels || left == right {
+                return Err("Left and right must be two different device channels".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Channels to open the output stream with for content with `source_channels`
+    pub fn stream_channels(&self, source_channels: u16) -> u16 {
+        match *self {
+            ChannelMode::Multichannel { device_channels, .. } if source_channels == 2 => device_channels,
+            ChannelMode::Mono { .. } if source_channels == 2 => 1,
+            _ => source_channels,
+        }
+    }
+}
+
+/// Source wrapper mapping stereo frames to the configured channel layout
+pub struct ChannelMap<S> {
+    inner: S,
+    mode: ChannelMode,
+    /// Output frame being emitted
+    frame: Vec<f32>,
+    /// Next sample of `frame` to emit
+    index: usize,
+}
+
+impl<S: Source<Item = f32>> ChannelMap<S> {
+    pub fn new(inner: S, mode: ChannelMode) -> Self {
+        // Only stereo content is remapped
+        let mode = if inner.channels() == 2 { mode } else { ChannelMode::Stereo };
+        Self { inner, mode, frame: Vec::new(), index: 0 }
+    }
+
+    /// Read the next input frame and build the output frame from it
+    fn next_frame(&mut self) -> bool {
+        let (Some(left), Some(right)) = (self.inner.next(), self.inner.next()) else {
+            return false;
+        };
+
+        self.frame.clear();
+        self.index = 0;
+        match self.mode {
+            ChannelMode::Stereo => self.frame.extend([left, right]),
+            ChannelMode::Mono { law } => {
+                self.frame.push(((left + right) * law.gain()).clamp(-1.0, 1.0));
+            }
+            ChannelMode::Multichannel { device_channels, left: l, right: r } => {
+                self.frame.resize(device_channels as usize, 0.0);
+                self.frame[l as usize] = left;
+                self.frame[r as usize] = right;
+            }
+        }
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ChannelMap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let ChannelMode::Stereo = self.mode {
+            return self.inner.next();
+        }
+        if self.index >= self.frame.len() && !self.next_frame() {
+            return None;
+        }
+        let sample = self.frame[self.index];
+        self.index += 1;
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ChannelMap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let remaining = self.inner.current_frame_len()?;
+        match self.mode {
+            ChannelMode::Stereo => Some(remaining),
+            _ => {
+                let out_channels = self.channels() as usize;
+                Some(remaining / 2 * out_channels + (self.frame.len() - self.index))
+            }
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self.mode {
+            ChannelMode::Stereo => self.inner.channels(),
+            ChannelMode::Mono { .. } => 1,
+            ChannelMode::Multichannel { device_channels, .. } => device_channels,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.frame.clear();
+        self.index = 0;
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn stereo() -> SamplesBuffer<f32> {
+        SamplesBuffer::new(2, 44100, vec![0.125, 0.375, -0.25, 0.25, 0.625, 0.875])
+    }
+
+    #[test]
+    fn test_mono_downmix_and_stereo_passthrough() {
+        let downmix = ChannelMode::Mono { law: DownmixLaw::Minus6Db };
+        assert_eq!(downmix.stream_channels(2), 1);
+        let mono = ChannelMap::new(stereo(), downmix);
+        assert_eq!(mono.channels(), 1);
+        assert_eq!(mono.collect::<Vec<_>>(), vec![0.25, 0.0, 0.75]);
+
+        // -3 dB keeps more level and clamps instead of wrapping
+        let mono = ChannelMap::new(stereo(), ChannelMode::Mono { law: DownmixLaw::Minus3Db });
+        let mono: Vec<f32> = mono.collect();
+        assert!((mono[0] - 0.5 * std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(&mono[1..], &[0.0, 1.0]);
+
+        let passthrough = ChannelMap::new(stereo(), ChannelMode::Stereo);
+        assert_eq!(passthrough.channels(), 2);
+        assert_eq!(passthrough.collect::<Vec<_>>(), stereo().collect::<Vec<_>>());
+
+        let surround = ChannelMode::Multichannel { device_channels: 4, left: 2, right: 3 };
+        assert_eq!(surround.stream_channels(2), 4);
+        let mapped = ChannelMap::new(stereo(), surround);
+        assert_eq!(mapped.channels(), 4);
+        assert_eq!(
+            mapped.collect::<Vec<_>>(),
+            vec![0.0, 0.0, 0.125, 0.375, 0.0, 0.0, -0.25, 0.25, 0.0, 0.0, 0.625, 0.875]
+        );
+    }
+}
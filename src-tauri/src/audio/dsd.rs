@@ -0,0 +1,179 @@
+//! DSD output support
+//!
+//! DSD content can be sent to the DAC either as DoP (DSD over PCM: DSD bits
+//! wrapped in 24-bit PCM frames with alternating marker bytes) or natively
+//! when the driver exposes a DSD sample format.
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::{BackendResult, MAX_PCM_SAMPLE_RATE};
+use super::capabilities::DeviceCaps;
+use super::OutputSampleFormat;
+
+/// DSD64 bit rate (64 × 44.1kHz)
+pub const DSD64_RATE: u32 = 2_822_400;
+
+/// DSD128 bit rate (128 × 44.1kHz)
+pub const DSD128_RATE: u32 = 5_644_800;
+
+/// DoP marker bytes, alternating every PCM frame
+const DOP_MARKER_A: u8 = 0x05;
+const DOP_MARKER_B: u8 = 0xFA;
+
+/// How DSD content is delivered to the output device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DsdMode {
+    /// Convert DSD to PCM before output
+    #[default]
+    Off,
+    /// DSD over PCM (DoP) - works with any DAC that detects the DoP markers
+    Dop,
+    /// Native DSD - requires driver support for a DSD sample format
+    Native,
+}
+
+/// How a DSD stream ends up being sent to the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsdOutput {
+    /// Converted to PCM
+    Pcm,
+    /// DoP frames at this PCM carrier rate
+    Dop { pcm_rate: u32 },
+    /// Native DSD at the stream's own rate
+    Native,
+}
+
+/// PCM sample rate needed to carry a DSD stream as DoP
+/// (each 24-bit PCM frame carries 16 DSD bits per channel)
+pub fn dop_pcm_rate(dsd_rate: u32) -> u32 {
+    dsd_rate / 16
+}
+
+/// Decide how to send a `dsd_rate` stream to a device with `caps`.
+///
+/// DoP needs a 24-bit integer container at a sixteenth of the DSD rate,
+/// native DSD a driver DSD format at the full rate. When the device can't
+/// take the stream the requested way, bit-perfect mode is an error;
+/// otherwise the stream is converted to PCM.
+pub fn negotiate_dsd_output(
+    mode: DsdMode,
+    dsd_rate: u32,
+    caps: &DeviceCaps,
+    bit_perfect: bool,
+) -> BackendResult<DsdOutput> {
+    let supported = match mode {
+        DsdMode::Off => return Ok(DsdOutput::Pcm),
+        DsdMode::Dop => {
+            let pcm_rate = dop_pcm_rate(dsd_rate);
+            let holds_24_bit = caps
+                .formats
+                .iter()
+                .any(|f| matches!(f, OutputSampleFormat::S24 | OutputSampleFormat::S32));
+            let rate_ok = pcm_rate <= MAX_PCM_SAMPLE_RATE && caps.max_rate.is_some_and(|max| pcm_rate <= max);
+            (holds_24_bit && rate_ok).then_some(DsdOutput::Dop { pcm_rate })
+        }
+        DsdMode::Native => caps
+            .max_native_dsd_rate
+            .is_some_and(|max| dsd_rate <= max)
+            .then_some(DsdOutput::Native),
+    };
+
+    match supported {
+        Some(output) => Ok(output),
+        None if bit_perfect => Err(format!(
+            "Device can't take DSD at {}Hz as {:?} and bit-perfect output is enabled",
+            dsd_rate, mode
+        )),
+        None => {
+            log::info!("Device can't take DSD at {}Hz as {:?}, converting to PCM", dsd_rate, mode);
+            Ok(DsdOutput::Pcm)
+        }
+    }
+}
+
+/// Pack interleaved DSD bytes into DoP frames.
+///
+/// `dsd` holds one byte (8 DSD bits, MSB first) per channel, interleaved by
+/// channel. Two bytes per channel make up one PCM frame. Each output sample is
+/// a 24-bit DoP word (`marker << 16 | msb << 8 | lsb`) left-justified in an i32.
+pub fn pack_dop(dsd: &[u8], channels: u16) -> Vec<i32> {
+    let channels = channels.max(1) as usize;
+    let frame_bytes = channels * 2;
+    let frames = dsd.len() / frame_bytes;
+    let mut out = Vec::with_capacity(frames * channels);
+
+    for frame in 0..frames {
+        let marker = if frame % 2 == 0 { DOP_MARKER_A } else { DOP_MARKER_B };
+        let base = frame * frame_bytes;
+        for channel in 0..channels {
+            let msb = dsd[base + channel];
+            let lsb = dsd[base + channels + channel];
+            let word = ((marker as u32) << 16) | ((msb as u32) << 8) | lsb as u32;
+            out.push((word << 8) as i32);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dop_pcm_rate() {
+        assert_eq!(dop_pcm_rate(DSD64_RATE), 176_400);
+        assert_eq!(dop_pcm_rate(DSD128_RATE), 352_800);
+    }
+
+    #[test]
+    fn test_pack_dop_stereo() {
+        // Two frames of stereo DSD: [L0, R0, L1, R1] per frame
+        let dsd = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+        let packed = pack_dop(&dsd, 2);
+
+        assert_eq!(
+            packed,
+            vec![
+                (0x05_11_33u32 << 8) as i32,
+                (0x05_22_44u32 << 8) as i32,
+                (0xFA_55_77u32 << 8) as i32,
+                (0xFA_66_88u32 << 8) as i32,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pack_dop_drops_partial_frame() {
+        let dsd = [0x11, 0x22, 0x33];
+        assert!(pack_dop(&dsd, 2).is_empty());
+    }
+
+    #[test]
+    fn test_dsd_rejects_unsupported_rates_in_bit_perfect() {
+        // 192kHz/32-bit DAC: DoP up to DSD64 (176.4kHz carrier), no native DSD
+        let caps = DeviceCaps {
+            max_rate: Some(192_000),
+            formats: vec![OutputSampleFormat::S16, OutputSampleFormat::S32],
+            ..DeviceCaps::default()
+        };
+
+        assert_eq!(
+            negotiate_dsd_output(DsdMode::Dop, DSD64_RATE, &caps, true),
+            Ok(DsdOutput::Dop { pcm_rate: 176_400 })
+        );
+        assert!(negotiate_dsd_output(DsdMode::Dop, DSD128_RATE, &caps, true).is_err());
+        assert!(negotiate_dsd_output(DsdMode::Native, DSD64_RATE, &caps, true).is_err());
+        // Outside bit-perfect mode the stream is converted instead
+        assert_eq!(negotiate_dsd_output(DsdMode::Dop, DSD128_RATE, &caps, false), Ok(DsdOutput::Pcm));
+        assert_eq!(negotiate_dsd_output(DsdMode::Off, DSD64_RATE, &caps, true), Ok(DsdOutput::Pcm));
+
+        // 16-bit only: no container for DoP
+        let caps = DeviceCaps { formats: vec![OutputSampleFormat::S16], ..caps };
+        assert!(negotiate_dsd_output(DsdMode::Dop, DSD64_RATE, &caps, true).is_err());
+
+        let caps = DeviceCaps { max_native_dsd_rate: Some(DSD128_RATE), ..caps };
+        assert_eq!(negotiate_dsd_output(DsdMode::Native, DSD128_RATE, &caps, true), Ok(DsdOutput::Native));
+        assert!(negotiate_dsd_output(DsdMode::Native, DSD128_RATE * 2, &caps, true).is_err());
+    }
+}
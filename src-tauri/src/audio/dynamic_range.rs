@@ -0,0 +1,120 @@
+//! Sample peak and approximate dynamic range (DR) measurement
+//!
+//! Follows the TT DR meter approach: audio is cut into 3 second blocks and
+//! for each channel DR is the ratio, in dB, of the second highest block
+//! peak to the RMS of the loudest 20% of blocks. The track value is the
+//! rounded mean over channels. Close to, but not certified against, the
+//! official meter.
+
+use serde::{Deserialize, Serialize};
+
+/// Block length used for the RMS/peak statistics
+const BLOCK_SECS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DynamicRangeReport {
+    /// Highest sample magnitude over all channels
+    pub peak_dbfs: f64,
+    /// Approximate DR value (as in "DR12")
+    pub dynamic_range: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Block {
+    sum_squares: f64,
+    peak: f64,
+    frames: u64,
+}
+
+/// Incremental DR meter for one track
+pub struct DynamicRangeMeter {
+    channels: usize,
+    block_frames: u64,
+    /// Finished blocks, per channel
+    blocks: Vec<Vec<Block>>,
+    current: Vec<Block>,
+    /// Position within the current frame, samples may arrive in any chunking
+    channel: usize,
+}
+
+impl DynamicRangeMeter {
+    pub fn new(channels: u16, sample_rate: u32) -> Result<Self, String> {
+        if channels == 0 || sample_rate == 0 {
+            return Err("Invalid audio format for DR measurement".to_string());
+        }
+        let channels = channels as usize;
+        Ok(Self {
+            channels,
+            block_frames: (sample_rate * BLOCK_SECS) as u64,
+            blocks: vec![Vec::new(); channels],
+            current: vec![Block::default(); channels],
+            channel: 0,
+        })
+    }
+
+    /// Feed interleaved samples in [-1.0, 1.0]
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let sample = sample as f64;
+            let block = &mut self.current[self.channel];
+            block.sum_squares += sample * sample;
+            block.peak = block.peak.max(sample.abs());
+
+            self.channel += 1;
+            if self.channel == self.channels {
+                self.channel = 0;
+                self.current.iter_mut().for_each(|block| block.frames += 1);
+                if self.current[0].frames == self.block_frames {
+                    self.end_block();
+                }
+            }
+        }
+    }
+
+    fn end_block(&mut self) {
+        for (blocks, current) in self.blocks.iter_mut().zip(self.current.iter_mut()) {
+            blocks.push(std::mem::take(current));
+        }
+    }
+
+    /// None for digital silence, which has no meaningful DR
+    pub fn report(mut self) -> Option<DynamicRangeReport> {
+        // A partial last block still counts, as with the reference meter
+        if self.current[0].frames > 0 {
+            self.end_block();
+        }
+
+        let mut peak: f64 = 0.0;
+        let mut channel_dr = Vec::with_capacity(self.channels);
+        for blocks in &self.blocks {
+            if blocks.is_empty() {
+                continue;
+            }
+            // Scaled so a full scale sine reads 1.0
+            let mut rms: Vec<f64> = blocks
+                .iter()
+                .map(|block| (2.0 * block.sum_squares / block.frames as f64).sqrt())
+                .collect();
+            let mut peaks: Vec<f64> = blocks.iter().map(|block| block.peak).collect();
+            rms.sort_by(|a, b| b.total_cmp(a));
+            peaks.sort_by(|a, b| b.total_cmp(a));
+            peak = peak.max(peaks[0]);
+
+            let loudest = (blocks.len() / 5).max(1);
+            let top_rms = (rms[..loudest].iter().map(|r| r * r).sum::<f64>() / loudest as f64).sqrt();
+            let second_peak = *peaks.get(1).unwrap_or(&peaks[0]);
+            if top_rms > 0.0 && second_peak > 0.0 {
+                channel_dr.push(20.0 * (second_peak / top_rms).log10());
+            }
+        }
+
+        if channel_dr.is_empty() {
+            return None;
+        }
+        let dynamic_range = channel_dr.iter().sum::<f64>() / channel_dr.len() as f64;
+        Some(DynamicRangeReport {
+            peak_dbfs: 20.0 * peak.log10(),
+            dynamic_range: dynamic_range.round().max(0.0) as u32,
+        })
+    }
+}
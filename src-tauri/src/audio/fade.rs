@@ -0,0 +1,151 @@
+//! Short fade-in on playback start/resume
+//!
+//! Starting at full volume instantly can click or feel abrupt on some
+//! systems. `FadeIn` wraps a decoded source and ramps its gain from zero
+//! whenever the shared `FadeControl` is triggered. It only scales samples,
+//! so the playback position is unaffected. Separate from crossfade.
+
+use rodio::source::SeekError;
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default fade-in length
+pub const DEFAULT_FADE_IN_MS: u32 = 50;
+
+/// Longest fade-in accepted from settings
+pub const MAX_FADE_IN_MS: u32 = 1000;
+
+/// Starts fades on the sources wrapped with it
+#[derive(Debug, Clone, Default)]
+pub struct FadeControl {
+    pending_ms: Arc<AtomicU32>,
+}
+
+impl FadeControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fade the next samples in over `ms` (0 = no fade)
+    pub fn trigger(&self, ms: u32) {
+        self.pending_ms.store(ms.min(MAX_FADE_IN_MS), Ordering::Relaxed);
+    }
+
+    fn take(&self) -> u32 {
+        if self.pending_ms.load(Ordering::Relaxed) == 0 {
+            return 0;
+        }
+        self.pending_ms.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Source wrapper applying the fades started through its `FadeControl`
+pub struct FadeIn<S> {
+    inner: S,
+    control: FadeControl,
+    /// Length of the running fade in frames
+    total_frames: u64,
+    /// Samples into the running fade
+    position: u64,
+}
+
+impl<S: Source<Item = f32>> FadeIn<S> {
+    pub fn new(inner: S, control: FadeControl) -> Self {
+        Self { inner, control, total_frames: 0, position: 0 }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for FadeIn<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let channels = self.inner.channels().max(1) as u64;
+
+        let ms = self.control.take();
+        if ms > 0 {
+            self.total_frames = ms as u64 * self.inner.sample_rate() as u64 / 1000;
+            self.position = 0;
+        }
+
+        let sample = self.inner.next()?;
+        if self.total_frames == 0 {
+            return Some(sample);
+        }
+
+        // Same gain for every channel of a frame
+        let frame = self.position / channels;
+        self.position += 1;
+        if frame >= self.total_frames {
+            self.total_frames = 0;
+            return Some(sample);
+        }
+
+        let gain = frame as f32 / self.total_frames as f32;
+        Some(sample * gain)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for FadeIn<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn constant_source() -> SamplesBuffer<f32> {
+        // 1 kHz stereo so 50 ms = 50 frames
+        SamplesBuffer::new(2, 1000, vec![0.5; 400])
+    }
+
+    #[test]
+    fn test_resume_ramps_from_near_zero_to_full() {
+        let control = FadeControl::new();
+        let mut source = FadeIn::new(constant_source(), control.clone());
+
+        // Play a bit at full gain, then pause/resume
+        assert!(source.by_ref().take(20).all(|s| s == 0.5));
+        control.trigger(DEFAULT_FADE_IN_MS);
+
+        let buffer: Vec<f32> = source.by_ref().take(100).collect();
+        assert_eq!(&buffer[..2], &[0.0, 0.0]);
+        assert_eq!(buffer[0], buffer[1], "channels share the frame gain");
+        assert!(buffer.windows(2).all(|w| w[1] >= w[0]));
+        assert!(buffer[98] < 0.5);
+        assert!(source.all(|s| s == 0.5));
+    }
+
+    #[test]
+    fn test_untouched_without_fade() {
+        let control = FadeControl::new();
+        let source = FadeIn::new(constant_source(), control.clone());
+        control.trigger(0);
+
+        assert!(source.take(100).all(|s| s == 0.5));
+    }
+}
@@ -0,0 +1,179 @@
+//! Pre-gain (headroom) and ReplayGain
+//!
+//! Hot masters, and EQ boosts downstream, can produce intersample overs
+//! once the DAC reconstructs the signal. A global pre-gain (e.g. -3 dB)
+//! leaves headroom for them. Gains are added in dB: pre-gain plus the
+//! track's ReplayGain make one factor applied here, and the sink volume
+//! multiplies on top (which is again additive in dB).
+//!
+//! Signal chain, in order:
+//!
+//! 1. decode to f32 (exact for sources up to `DECODED_BITS` deep)
+//! 2. silence trim (`audio::silence`)
+//! 3. gain: pre-gain + ReplayGain (this module), ahead of the resampler so
+//!    its headroom is there before the resampler's overshoot is clipped
+//! 4. resample to the output rate (`audio::resample`)
+//! 5. channel mapping (`audio::channels`)
+//! 6. fade-in (`audio::fade`)
+//! 7. taps (`audio::tap`)
+//! 8. sink volume
+//!
+//! In bit-perfect mode the pre-gain is 0 dB and samples pass untouched
+//! (see `AudioSettings::effective_pregain_db`).
+
+use rodio::source::SeekError;
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Accepted pre-gain range; only attenuation makes sense as headroom
+pub const MIN_PREGAIN_DB: f32 = -24.0;
+pub const MAX_PREGAIN_DB: f32 = 0.0;
+
+pub fn validate_pregain_db(db: f32) -> Result<(), String> {
+    if !(MIN_PREGAIN_DB..=MAX_PREGAIN_DB).contains(&db) {
+        return Err(format!(
+            "Pre-gain must be between {} and {} dB",
+            MIN_PREGAIN_DB, MAX_PREGAIN_DB
+        ));
+    }
+    Ok(())
+}
+
+/// dB to a linear amplitude factor
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Gains shared with the sources wrapped with it; changes apply immediately
+#[derive(Debug, Clone, Default)]
+pub struct GainControl {
+    /// f32 bits, in dB
+    pregain_db: Arc<AtomicU32>,
+    replaygain_db: Arc<AtomicU32>,
+}
+
+impl GainControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_pregain_db(&self, db: f32) {
+        self.pregain_db.store(db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Track (or album) ReplayGain, None when the track has none
+    pub fn set_replaygain_db(&self, db: Option<f32>) {
+        self.replaygain_db.store(db.unwrap_or(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn pregain_db(&self) -> f32 {
+        f32::from_bits(self.pregain_db.load(Ordering::Relaxed))
+    }
+
+    pub fn replaygain_db(&self) -> f32 {
+        f32::from_bits(self.replaygain_db.load(Ordering::Relaxed))
+    }
+
+    /// Total gain applied before the sink volume
+    pub fn total_db(&self) -> f32 {
+        self.pregain_db() + self.replaygain_db()
+    }
+}
+
+/// Source wrapper applying the gain of its `GainControl`
+pub struct Gain<S> {
+    inner: S,
+    control: GainControl,
+    /// Linear factor for the current frame
+    factor: f32,
+    /// Samples left in the current frame
+    frame_left: u16,
+}
+
+impl<S: Source<Item = f32>> Gain<S> {
+    pub fn new(inner: S, control: GainControl) -> Self {
+        Self { inner, control, factor: 1.0, frame_left: 0 }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Gain<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // Same factor for every channel of a frame
+        if self.frame_left == 0 {
+            self.factor = db_to_linear(self.control.total_db());
+            self.frame_left = self.inner.channels().max(1);
+        }
+        self.frame_left -= 1;
+
+        let sample = self.inner.next()?;
+        if self.factor == 1.0 {
+            // Unity gain stays bit-exact
+            return Some(sample);
+        }
+        Some((sample * self.factor).clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Gain<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::audio_settings::AudioSettings;
+    use rodio::buffer::SamplesBuffer;
+
+    fn source() -> SamplesBuffer<f32> {
+        SamplesBuffer::new(2, 1000, vec![0.8, -0.5, 0.25, -1.0])
+    }
+
+    #[test]
+    fn test_minus_6db_halves_amplitude_and_passthrough_bypasses() {
+        let control = GainControl::new();
+        let mut settings = AudioSettings { pregain_db: -6.0206, ..AudioSettings::default() };
+
+        // Unity sink volume on top leaves the pre-gain alone
+        control.set_pregain_db(settings.effective_pregain_db());
+        let output: Vec<f32> = Gain::new(source(), control.clone()).amplify(1.0).collect();
+        let halved: Vec<f32> = source().map(|s| s / 2.0).collect();
+        assert!(output.iter().zip(&halved).all(|(a, b)| (a - b).abs() < 1e-5), "{:?}", output);
+
+        // ReplayGain adds in dB: -6 + 6 = unity
+        control.set_replaygain_db(Some(6.0206));
+        let output: Vec<f32> = Gain::new(source(), control.clone()).collect();
+        assert_eq!(output, source().collect::<Vec<_>>());
+        control.set_replaygain_db(None);
+
+        settings.dac_passthrough = true;
+        control.set_pregain_db(settings.effective_pregain_db());
+        let output: Vec<f32> = Gain::new(source(), control).collect();
+        assert_eq!(output, source().collect::<Vec<_>>());
+    }
+}
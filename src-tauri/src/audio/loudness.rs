@@ -0,0 +1,232 @@
+//! EBU R128 loudness measurement
+//!
+//! Measures integrated loudness, true peak and loudness range of decoded
+//! audio, per track or across an album (for album-normalized gain).
+//! Samples are fed incrementally so whole tracks never sit in memory as PCM.
+
+use std::collections::HashMap;
+
+use ebur128::{EbuR128, Mode};
+use serde::{Deserialize, Serialize};
+
+/// ReplayGain 2.0 reference level used for album gain
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessReport {
+    pub integrated_lufs: f64,
+    pub true_peak_dbtp: f64,
+    /// Loudness range (LRA) in LU
+    pub loudness_range: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlbumLoudnessReport {
+    pub album: LoudnessReport,
+    /// Gain (dB) bringing the album to `REFERENCE_LUFS`
+    pub album_gain_db: f64,
+    pub tracks: Vec<(u64, LoudnessReport)>,
+}
+
+/// Which measured gain the normalization stage applies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    /// Each track brought to `REFERENCE_LUFS` on its own
+    Track,
+    /// One gain for the whole album, keeping the level differences between its tracks
+    Album,
+}
+
+/// Gain (dB) bringing `report` to the reference level, limited so its true peak stays below 0 dBTP
+pub fn track_gain_db(report: &LoudnessReport) -> f32 {
+    (REFERENCE_LUFS - report.integrated_lufs).min(-report.true_peak_dbtp) as f32
+}
+
+/// Album-wide gain and the per-track peaks it was measured with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlbumGain {
+    pub album_id: String,
+    pub integrated_lufs: f64,
+    /// Gain (dB) bringing the album to `REFERENCE_LUFS`
+    pub gain_db: f64,
+    /// True peak (dBTP) per track id
+    pub track_peaks: HashMap<u64, f64>,
+}
+
+impl AlbumGain {
+    pub fn from_report(album_id: String, report: &AlbumLoudnessReport) -> Self {
+        Self {
+            album_id,
+            integrated_lufs: report.album.integrated_lufs,
+            gain_db: report.album_gain_db,
+            track_peaks: report
+                .tracks
+                .iter()
+                .map(|(track_id, track)| (*track_id, track.true_peak_dbtp))
+                .collect(),
+        }
+    }
+
+    /// Gain to apply to one of the album's tracks. The loudest peak of the
+    /// album limits it, so every track gets the same gain; None for tracks
+    /// not on the album.
+    pub fn gain_for_track(&self, track_id: u64) -> Option<f32> {
+        if !self.track_peaks.contains_key(&track_id) {
+            return None;
+        }
+        let album_peak = self.track_peaks.values().copied().fold(f64::NEG_INFINITY, f64::max);
+        Some(self.gain_db.min(-album_peak) as f32)
+    }
+}
+
+/// Incremental R128 meter for one track
+pub struct LoudnessMeter {
+    meter: EbuR128,
+    channels: u16,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: u16, sample_rate: u32) -> Result<Self, String> {
+        let meter = EbuR128::new(
+            channels as u32,
+            sample_rate,
+            Mode::I | Mode::LRA | Mode::TRUE_PEAK,
+        )
+        .map_err(|e| format!("Failed to create loudness meter: {}", e))?;
+
+        Ok(Self { meter, channels })
+    }
+
+    /// Feed interleaved samples in [-1.0, 1.0]
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+        self.meter
+            .add_frames_f32(samples)
+            .map_err(|e| format!("Failed to analyze audio: {}", e))
+    }
+
+    fn true_peak_dbtp(&self) -> Result<f64, String> {
+        let mut peak: f64 = 0.0;
+        for channel in 0..self.channels as u32 {
+            let channel_peak = self
+                .meter
+                .true_peak(channel)
+                .map_err(|e| format!("Failed to read true peak: {}", e))?;
+            peak = peak.max(channel_peak);
+        }
+        Ok(20.0 * peak.log10())
+    }
+
+    pub fn report(&self) -> Result<LoudnessReport, String> {
+        Ok(LoudnessReport {
+            integrated_lufs: self
+                .meter
+                .loudness_global()
+                .map_err(|e| format!("Failed to compute integrated loudness: {}", e))?,
+            true_peak_dbtp: self.true_peak_dbtp()?,
+            loudness_range: self
+                .meter
+                .loudness_range()
+                .map_err(|e| format!("Failed to compute loudness range: {}", e))?,
+        })
+    }
+}
+
+/// Combine per-track meters into an album report (gated across all tracks)
+pub fn measure_album(tracks: &[(u64, LoudnessMeter)]) -> Result<AlbumLoudnessReport, String> {
+    if tracks.is_empty() {
+        return Err("Album has no tracks to measure".to_string());
+    }
+
+    let mut track_reports = Vec::with_capacity(tracks.len());
+    let mut album_peak = f64::NEG_INFINITY;
+    for (track_id, meter) in tracks {
+        let track_report = meter.report()?;
+        album_peak = album_peak.max(track_report.true_peak_dbtp);
+        track_reports.push((*track_id, track_report));
+    }
+
+    let meters = || tracks.iter().map(|(_, meter)| &meter.meter);
+    let integrated_lufs = EbuR128::loudness_global_multiple(meters())
+        .map_err(|e| format!("Failed to compute album loudness: {}", e))?;
+    let loudness_range = EbuR128::loudness_range_multiple(meters())
+        .map_err(|e| format!("Failed to compute album loudness range: {}", e))?;
+
+    Ok(AlbumLoudnessReport {
+        album: LoudnessReport {
+            integrated_lufs,
+            true_peak_dbtp: album_peak,
+            loudness_range,
+        },
+        album_gain_db: REFERENCE_LUFS - integrated_lufs,
+        tracks: track_reports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stereo 1 kHz sine at `dbfs`
+    fn sine(dbfs: f32, seconds: u32) -> Vec<f32> {
+        let sample_rate = 48_000;
+        let amplitude = 10f32.powf(dbfs / 20.0);
+        (0..sample_rate * seconds)
+            .flat_map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                let value = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * t).sin();
+                [value, value]
+            })
+            .collect()
+    }
+
+    fn meter(samples: &[f32]) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(2, 48_000).unwrap();
+        meter.add_samples(samples).unwrap();
+        meter
+    }
+
+    /// EBU Tech 3341 case 1: stereo 1 kHz sine at -23 dBFS reads -23 LUFS
+    fn sine_at_minus_23(seconds: u32) -> LoudnessMeter {
+        meter(&sine(-23.0, seconds))
+    }
+
+    #[test]
+    fn test_measure_reference_sine() {
+        let report = sine_at_minus_23(20).report().unwrap();
+        assert!((report.integrated_lufs + 23.0).abs() < 0.1, "{:?}", report);
+        assert!((report.true_peak_dbtp + 23.0).abs() < 0.5, "{:?}", report);
+        assert!(report.loudness_range < 1.0, "{:?}", report);
+    }
+
+    #[test]
+    fn test_album_gain_relative_to_reference() {
+        let album = measure_album(&[(1, sine_at_minus_23(10)), (2, sine_at_minus_23(10))]).unwrap();
+        assert_eq!(album.tracks.len(), 2);
+        assert!((album.album.integrated_lufs + 23.0).abs() < 0.1);
+        assert!((album.album_gain_db - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_album_gain_matches_combined_loudness() {
+        let quiet = sine(-26.0, 10);
+        let loud = sine(-16.0, 10);
+        let album = measure_album(&[(1, meter(&quiet)), (2, meter(&loud))]).unwrap();
+
+        // The album reads like the two tracks played back to back
+        let combined = meter(&[quiet, loud].concat()).report().unwrap();
+        let gain = AlbumGain::from_report("alb".to_string(), &album);
+        assert!((gain.integrated_lufs - combined.integrated_lufs).abs() < 0.01, "{:?}", gain);
+        assert!((gain.gain_db - (REFERENCE_LUFS - combined.integrated_lufs)).abs() < 0.01);
+
+        // Both tracks get the same gain; the loud track's peak isn't limiting yet
+        assert_eq!(gain.track_peaks.len(), 2);
+        assert!((gain.track_peaks[&2] + 16.0).abs() < 0.5);
+        let track_gain = gain.gain_for_track(1).unwrap();
+        assert_eq!(gain.gain_for_track(2), Some(track_gain));
+        assert!((track_gain as f64 - gain.gain_db).abs() < 1e-4);
+        assert_eq!(gain.gain_for_track(3), None);
+    }
+}
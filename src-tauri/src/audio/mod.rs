@@ -4,17 +4,51 @@
 //! allowing users to choose their preferred audio stack.
 
 pub mod backend;
+pub mod bitperfect;
+pub mod capabilities;
+pub mod channels;
+pub mod dsd;
+pub mod dynamic_range;
+pub mod fade;
+pub mod gain;
+pub mod loudness;
 pub mod pipewire_backend;
 pub mod alsa_backend;
 pub mod pulse_backend;
+pub mod resample;
+pub mod scrub;
+pub mod silence;
+pub mod tap;
+pub mod wav;
 
 // Re-export commonly used types
 pub use backend::{
     AlsaPlugin,
     AudioBackend,
     AudioBackendType,
+    AudioConfig,
     AudioDevice,
     BackendConfig,
     BackendManager,
     BackendResult,
+    DECODED_BITS,
+    DEFAULT_BUFFER_RANGE,
+    EffectiveBufferConfig,
+    MAX_PCM_SAMPLE_RATE,
+    OpenedStream,
+    OutputSampleFormat,
+    cpal_buffer_size,
+    device_buffer_range,
+    device_sample_formats,
+    negotiate_sample_format,
+    validate_output_rate,
 };
+pub use capabilities::{CapabilityCache, DeviceCaps};
+pub use channels::{ChannelMap, ChannelMode, DownmixLaw};
+pub use dsd::{DsdMode, DsdOutput};
+pub use fade::{FadeControl, FadeIn, DEFAULT_FADE_IN_MS};
+pub use gain::{Gain, GainControl};
+pub use resample::{Resample, ResampleQuality};
+pub use scrub::{ScrubSession, ScrubSource, SCRUB_TIMEOUT};
+pub use silence::{SilenceTrim, SilenceTrimConfig};
+pub use tap::{AudioTap, TapBuffer, TapRegistry, Tapped, WavRecorderTap};
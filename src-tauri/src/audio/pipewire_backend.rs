@@ -6,16 +6,18 @@
 //! - Creates stream using CPAL "pulse" or "pipewire" device
 //! - Does NOT change system default (only affects QBZ)
 
-use super::backend::{AlsaPlugin, AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult};
+use super::backend::{AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult, OpenedStream, cpal_buffer_size, device_buffer_range, DEFAULT_BUFFER_RANGE, device_sample_formats, negotiate_sample_format, validate_output_rate};
 use rodio::{
     cpal::{
         traits::{DeviceTrait, HostTrait},
-        BufferSize, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize, SupportedStreamConfig,
+        SampleRate, SupportedStreamConfig,
     },
-    OutputStream, OutputStreamHandle,
+    OutputStream,
 };
 use std::process::Command;
 
+use super::capabilities::{parse_pactl_sink, read_usb_stream, DeviceCaps};
+
 pub struct PipeWireBackend {
     host: rodio::cpal::Host,
 }
@@ -127,7 +129,7 @@ impl AudioBackend for PipeWireBackend {
     fn create_output_stream(
         &self,
         config: &BackendConfig,
-    ) -> BackendResult<(OutputStream, OutputStreamHandle)> {
+    ) -> BackendResult<OpenedStream> {
         let target_sink = config.device_id.clone();
 
         // Temporarily set default sink to target (if specified)
@@ -208,61 +210,88 @@ impl AudioBackend for PipeWireBackend {
             config.exclusive_mode
         );
 
-        // Create StreamConfig with desired sample rate
-        let stream_config = StreamConfig {
-            channels: config.channels,
-            sample_rate: SampleRate(config.sample_rate),
-            buffer_size: if config.exclusive_mode {
-                BufferSize::Fixed(512)  // Lower latency for exclusive mode
-            } else {
-                BufferSize::Default
-            },
-        };
-
         // Check if device supports this configuration
-        let supported_configs = device
+        let supported_configs: Vec<_> = device
             .supported_output_configs()
-            .map_err(|e| format!("Failed to get supported configs: {}", e))?;
-
-        let mut found_matching = false;
-        for range in supported_configs {
-            if range.channels() == config.channels
-                && config.sample_rate >= range.min_sample_rate().0
-                && config.sample_rate <= range.max_sample_rate().0
-            {
-                found_matching = true;
-                log::info!(
-                    "[PipeWire Backend] Device supports {}Hz (range: {}-{}Hz)",
-                    config.sample_rate,
-                    range.min_sample_rate().0,
-                    range.max_sample_rate().0
-                );
-                break;
-            }
-        }
+            .map_err(|e| format!("Failed to get supported configs: {}", e))?
+            .collect();
+
+        let ranges: Vec<(u16, u32, u32)> = supported_configs
+            .iter()
+            .map(|range| (range.channels(), range.min_sample_rate().0, range.max_sample_rate().0))
+            .collect();
 
-        if !found_matching {
+        let found_matching =
+            validate_output_rate(config.sample_rate, config.channels, &ranges, config.bit_perfect)?;
+
+        if found_matching {
+            log::info!("[PipeWire Backend] Device supports {}Hz", config.sample_rate);
+        } else {
             log::warn!(
                 "[PipeWire Backend] Device may not support {}Hz, attempting anyway",
                 config.sample_rate
             );
         }
 
+        // Resolve buffer/period sizes against what the device reports
+        let buffer = config.audio_config.resolve(
+            device_buffer_range(&supported_configs, config.channels),
+            config.exclusive_mode,
+        )?;
+        log::info!("[PipeWire Backend] Buffer config: {:?}", buffer);
+
+        // Pick the sample format for the source depth
+        let sample_format = negotiate_sample_format(
+            &device_sample_formats(&supported_configs, config.channels),
+            config.source_bits,
+            config.bit_perfect,
+        )?;
+        log::info!("[PipeWire Backend] Sample format: {:?} (source: {:?}-bit)", sample_format, config.source_bits);
+        let cpal_format = sample_format
+            .to_cpal()
+            .ok_or_else(|| format!("{:?} output is not supported", sample_format))?;
+
         // Create SupportedStreamConfig
         let supported_config = SupportedStreamConfig::new(
-            stream_config.channels,
-            stream_config.sample_rate,
-            SupportedBufferSize::Range { min: 64, max: 8192 },
-            SampleFormat::F32,
+            config.channels,
+            SampleRate(config.sample_rate),
+            DEFAULT_BUFFER_RANGE,
+            cpal_format,
         );
 
         // Create OutputStream with custom config
-        let stream = OutputStream::try_from_device_config(&device, supported_config)
+        let (stream, handle) = OutputStream::try_from_device_config_with_buffer_size(&device, supported_config, cpal_buffer_size(buffer))
             .map_err(|e| format!("Failed to create output stream at {}Hz: {}", config.sample_rate, e))?;
 
         log::info!("[PipeWire Backend] ✓ Output stream created successfully at {}Hz", config.sample_rate);
 
-        Ok(stream)
+        Ok(OpenedStream { stream, handle, buffer, sample_format })
+    }
+
+    fn device_capabilities(&self, device_id: &str) -> BackendResult<DeviceCaps> {
+        let output = Command::new("pactl")
+            .args(["list", "sinks"])
+            .output()
+            .map_err(|e| format!("Failed to run pactl: {}", e))?;
+        if !output.status.success() {
+            return Err("pactl command failed".to_string());
+        }
+
+        let sink = parse_pactl_sink(&String::from_utf8_lossy(&output.stdout), device_id)
+            .ok_or_else(|| format!("Sink '{}' not found", device_id))?;
+
+        // The sample spec is only the current format; a USB DAC behind the
+        // sink lists its full range
+        let mut caps = sink
+            .sample_spec
+            .as_ref()
+            .map(DeviceCaps::from_sample_spec)
+            .unwrap_or_default();
+        if let Some(altsets) = sink.alsa_card.and_then(read_usb_stream) {
+            caps.merge_usb_stream(&altsets);
+        }
+        // exclusive_available stays false: the server shares the device
+        Ok(caps)
     }
 
     fn is_available(&self) -> bool {
@@ -3,9 +3,9 @@
 //! Similar to PipeWire backend but for systems running PulseAudio only.
 //! Uses same approach: pactl + PULSE_SINK + CPAL "pulse" device.
 
-use super::backend::{AlsaPlugin, AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult};
+use super::backend::{AudioBackend, AudioBackendType, AudioDevice, BackendConfig, BackendResult, OpenedStream};
+use super::capabilities::DeviceCaps;
 use super::pipewire_backend::PipeWireBackend;
-use rodio::{OutputStream, OutputStreamHandle};
 
 pub struct PulseBackend {
     // Reuse PipeWire backend implementation (they're compatible)
@@ -30,14 +30,15 @@ impl AudioBackend for PulseBackend {
         self.inner.enumerate_devices()
     }
 
-    fn create_output_stream(
-        &self,
-        config: &BackendConfig,
-    ) -> BackendResult<(OutputStream, OutputStreamHandle)> {
+    fn create_output_stream(&self, config: &BackendConfig) -> BackendResult<OpenedStream> {
         // Delegate to PipeWire backend (same mechanism)
         self.inner.create_output_stream(config)
     }
 
+    fn device_capabilities(&self, device_id: &str) -> BackendResult<DeviceCaps> {
+        self.inner.device_capabilities(device_id)
+    }
+
     fn is_available(&self) -> bool {
         // Check if PulseAudio is running
         std::process::Command::new("pactl")
@@ -0,0 +1,269 @@
+//! Sample rate conversion
+//!
+//! When a stream is reused for a track at another rate (DAC passthrough
+//! off), rodio would otherwise convert with linear interpolation, which
+//! aliases audibly on high-frequency content. `Resample` converts to the
+//! output rate first with rubato's windowed-sinc resampler. Only inserted
+//! when the source and output rates differ; bit-perfect playback never
+//! resamples.
+
+use rodio::source::SeekError;
+use rodio::Source;
+use rubato::{
+    calculate_cutoff, Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType,
+    WindowFunction,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Input frames handed to the resampler per call
+const CHUNK_FRAMES: usize = 1024;
+
+/// Resampler quality, trading CPU for stopband attenuation and passband width
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleQuality {
+    Fast,
+    #[default]
+    Balanced,
+    Best,
+}
+
+impl ResampleQuality {
+    fn parameters(&self) -> SincInterpolationParameters {
+        let (sinc_len, oversampling_factor, interpolation, window) = match self {
+            ResampleQuality::Fast => (64, 128, SincInterpolationType::Linear, WindowFunction::Hann2),
+            ResampleQuality::Balanced => (128, 256, SincInterpolationType::Cubic, WindowFunction::Blackman2),
+            ResampleQuality::Best => (256, 256, SincInterpolationType::Cubic, WindowFunction::BlackmanHarris2),
+        };
+        SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: calculate_cutoff(sinc_len, window),
+            oversampling_factor,
+            interpolation,
+            window,
+        }
+    }
+}
+
+/// Source wrapper converting to `out_rate`
+pub struct Resample<S> {
+    inner: S,
+    resampler: SincFixedIn<f32>,
+    in_rate: u32,
+    out_rate: u32,
+    channels: usize,
+    /// Per-channel input chunk and resampled output
+    input: Vec<Vec<f32>>,
+    output: Vec<Vec<f32>>,
+    /// Resampler output frames still to drop for its filter delay
+    delay_frames: usize,
+    input_frames: u64,
+    emitted_frames: u64,
+    input_done: bool,
+    /// Resampled frames in `output` ready to play, and the next sample of them
+    ready_frames: usize,
+    read_index: usize,
+}
+
+impl<S: Source<Item = f32>> Resample<S> {
+    /// Gives the source back when no conversion is needed or rubato
+    /// can't convert between the rates
+    pub fn new(inner: S, out_rate: u32, quality: ResampleQuality) -> Result<Self, S> {
+        let in_rate = inner.sample_rate();
+        if in_rate == out_rate || in_rate == 0 || out_rate == 0 {
+            return Err(inner);
+        }
+        let channels = inner.channels().max(1) as usize;
+        let resampler = match SincFixedIn::new(
+            out_rate as f64 / in_rate as f64,
+            1.0,
+            quality.parameters(),
+            CHUNK_FRAMES,
+            channels,
+        ) {
+            Ok(resampler) => resampler,
+            Err(e) => {
+                log::warn!("Can't resample {}Hz -> {}Hz: {}, leaving it to the output", in_rate, out_rate, e);
+                return Err(inner);
+            }
+        };
+
+        let mut resample = Self {
+            input: vec![Vec::with_capacity(CHUNK_FRAMES); channels],
+            output: resampler.output_buffer_allocate(true),
+            resampler,
+            in_rate,
+            out_rate,
+            channels,
+            delay_frames: 0,
+            input_frames: 0,
+            emitted_frames: 0,
+            input_done: false,
+            ready_frames: 0,
+            read_index: 0,
+            inner,
+        };
+        resample.reset();
+        Ok(resample)
+    }
+
+    /// Start over from the inner source's current position
+    fn reset(&mut self) {
+        self.resampler.reset();
+        self.delay_frames = self.resampler.output_delay();
+        self.input_frames = 0;
+        self.emitted_frames = 0;
+        self.input_done = false;
+        self.ready_frames = 0;
+        self.read_index = 0;
+    }
+
+    /// Output frames the input read so far converts to
+    fn expected_frames(&self) -> u64 {
+        (self.input_frames * self.out_rate as u64).div_ceil(self.in_rate as u64)
+    }
+
+    /// Read the next input chunk, returning false once the input has ended
+    fn fill_input(&mut self) -> bool {
+        let wanted = self.resampler.input_frames_next();
+        self.input.iter_mut().for_each(|channel| channel.clear());
+        'frames: for _ in 0..wanted {
+            for channel in 0..self.channels {
+                match self.inner.next() {
+                    Some(sample) => self.input[channel].push(sample),
+                    None => {
+                        // Drop a partial frame
+                        for partial in &mut self.input[..channel] {
+                            partial.pop();
+                        }
+                        self.input_done = true;
+                        break 'frames;
+                    }
+                }
+            }
+        }
+        self.input_frames += self.input[0].len() as u64;
+        !self.input_done
+    }
+
+    /// Resample until output frames are ready, returning false at the end
+    fn next_chunk(&mut self) -> bool {
+        loop {
+            let written = if !self.input_done && self.fill_input() {
+                self.resampler.process_into_buffer(&self.input, &mut self.output, None)
+            } else {
+                // Zero-padded chunks flush the filter delay out after the input
+                if self.emitted_frames >= self.expected_frames() {
+                    return false;
+                }
+                let input = (!self.input[0].is_empty()).then_some(&self.input[..]);
+                self.resampler.process_partial_into_buffer(input, &mut self.output, None)
+            };
+            let written = match written {
+                Ok((_, written)) => written,
+                Err(e) => {
+                    log::error!("Resampling failed: {}", e);
+                    return false;
+                }
+            };
+            self.input.iter_mut().for_each(|channel| channel.clear());
+
+            let skipped = self.delay_frames.min(written);
+            self.delay_frames -= skipped;
+            let mut ready = written - skipped;
+            if self.input_done {
+                let remaining = self.expected_frames().saturating_sub(self.emitted_frames);
+                ready = ready.min(remaining as usize);
+            }
+            if ready > 0 {
+                // Move the frames to play to the front of each channel
+                for channel in &mut self.output {
+                    channel.copy_within(skipped..skipped + ready, 0);
+                }
+                self.emitted_frames += ready as u64;
+                self.ready_frames = ready;
+                self.read_index = 0;
+                return true;
+            }
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Resample<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.read_index >= self.ready_frames * self.channels && !self.next_chunk() {
+            return None;
+        }
+        let frame = self.read_index / self.channels;
+        let channel = self.read_index % self.channels;
+        self.read_index += 1;
+        // The filter can overshoot a full-scale input
+        Some(self.output[channel][frame].clamp(-1.0, 1.0))
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Resample<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        // Output frames don't line up with the input's; the format is fixed
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.out_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f64::consts::PI;
+
+    /// Frequency of a mono signal from its rising zero crossings
+    fn frequency(samples: &[f32], rate: u32) -> f64 {
+        let crossings = samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        crossings as f64 * rate as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn test_sine_keeps_frequency_from_44k1_to_48k() {
+        let tone = 1000.0;
+        let sine: Vec<f32> = (0..44_100)
+            .map(|i| ((2.0 * PI * tone * i as f64 / 44_100.0).sin() * 0.5) as f32)
+            .collect();
+
+        for quality in [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::Best] {
+            let source = SamplesBuffer::new(1, 44_100, sine.clone());
+            let resampled = Resample::new(source, 48_000, quality).ok().unwrap();
+            assert_eq!(resampled.sample_rate(), 48_000);
+            let output: Vec<f32> = resampled.collect();
+            assert!((output.len() as i64 - 48_000).abs() <= 1, "{} samples", output.len());
+
+            // Skip the filter ramp at both ends
+            let steady = &output[1000..output.len() - 1000];
+            let measured = frequency(steady, 48_000);
+            assert!((measured - tone).abs() / tone < 0.005, "{:?}: {}Hz", quality, measured);
+            let peak = steady.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+            assert!((0.48..=0.52).contains(&peak), "{:?}: peak {}", quality, peak);
+        }
+
+        assert!(Resample::new(SamplesBuffer::new(1, 48_000, vec![0f32; 10]), 48_000, ResampleQuality::Best).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Scrub preview while dragging the seek bar
+//!
+//! A drag decodes its own source from the track data once, so the source
+//! feeding the main sink is never touched, and each preview only seeks it.
+//! The drag target is only applied as a real seek once the drag ends
+//! (`Player::commit_seek`). A drag that goes quiet for `SCRUB_TIMEOUT`
+//! stops previewing, so the main sink isn't left muted if the end of the
+//! drag never arrives.
+
+use rodio::buffer::SamplesBuffer;
+use rodio::Source;
+use std::time::{Duration, Instant};
+
+/// Length of each preview snippet
+pub const SCRUB_SNIPPET: Duration = Duration::from_millis(200);
+
+/// Gain applied to snippets relative to the playback volume
+pub const SCRUB_GAIN: f32 = 0.4;
+
+/// Time without a preview after which the drag stops previewing
+pub const SCRUB_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Ramp at the start of a snippet to avoid clicks
+const SCRUB_RAMP: Duration = Duration::from_millis(10);
+
+/// Decoded track kept for the length of a drag
+pub struct ScrubSource {
+    source: Box<dyn Source<Item = f32> + Send>,
+}
+
+impl ScrubSource {
+    pub fn new(source: Box<dyn Source<Item = f32> + Send>) -> Self {
+        Self { source }
+    }
+
+    /// A short, quieter snippet starting at `position`, or None when the
+    /// source can't seek
+    pub fn snippet(&mut self, position: Duration) -> Option<Box<dyn Source<Item = f32> + Send>> {
+        self.source.try_seek(position).ok()?;
+
+        let channels = self.source.channels();
+        let sample_rate = self.source.sample_rate();
+        let len = (SCRUB_SNIPPET.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+        let samples: Vec<f32> = self.source.by_ref().take(len).collect();
+
+        // Decays over its whole length, so consecutive snippets don't click
+        let mut snippet = SamplesBuffer::new(channels, sample_rate, samples).take_duration(SCRUB_SNIPPET);
+        snippet.set_filter_fadeout();
+        Some(Box::new(snippet.fade_in(SCRUB_RAMP).amplify(SCRUB_GAIN)))
+    }
+}
+
+/// Seek target of an in-progress drag
+#[derive(Debug, Default)]
+pub struct ScrubSession {
+    target: Option<u64>,
+    /// When the last preview was played, while previewing
+    last_preview: Option<Instant>,
+}
+
+impl ScrubSession {
+    /// Record a preview at `position_secs`. Returns true when it starts
+    /// previewing (the start of a drag, or after a timeout).
+    pub fn preview(&mut self, position_secs: u64, now: Instant) -> bool {
+        self.target = Some(position_secs);
+        self.last_preview.replace(now).is_none()
+    }
+
+    /// Position of the last preview, if a drag is in progress
+    pub fn target(&self) -> Option<u64> {
+        self.target
+    }
+
+    pub fn is_previewing(&self) -> bool {
+        self.last_preview.is_some()
+    }
+
+    /// Stop previewing when nothing was previewed for `SCRUB_TIMEOUT`,
+    /// keeping the target for the end of the drag. Returns true when it
+    /// stopped.
+    pub fn time_out(&mut self, now: Instant) -> bool {
+        match self.last_preview {
+            Some(last) if now.duration_since(last) >= SCRUB_TIMEOUT => {
+                self.last_preview = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// End the drag, returning the position to seek to
+    pub fn commit(&mut self) -> Option<u64> {
+        self.last_preview = None;
+        self.target.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_plays_snippet_without_committing() {
+        // 1 kHz mono, sample value = elapsed ms / 10000
+        let samples: Vec<f32> = (0..3000).map(|i| i as f32 / 10_000.0).collect();
+        let mut source = ScrubSource::new(Box::new(SamplesBuffer::new(1, 1000, samples)));
+
+        let start = Instant::now();
+        let mut session = ScrubSession::default();
+        assert!(session.preview(2, start));
+        let snippet: Vec<f32> = source.snippet(Duration::from_secs(2)).unwrap().collect();
+
+        assert!((199..=200).contains(&snippet.len()), "{}", snippet.len());
+        // Audio comes from the target, quieter and decaying (about half way at 100 ms)
+        let expected = 0.21 * SCRUB_GAIN * 0.5;
+        let sample = snippet[100];
+        assert!((sample - expected).abs() < expected * 0.1, "{}", sample);
+        assert!(snippet[0] < 0.001);
+
+        // The same source previews backwards too
+        assert!(!session.preview(1, start + Duration::from_millis(100)));
+        let snippet: Vec<f32> = source.snippet(Duration::from_secs(1)).unwrap().collect();
+        let expected = 0.11 * SCRUB_GAIN * 0.5;
+        assert!((snippet[100] - expected).abs() < expected * 0.1, "{}", snippet[100]);
+
+        // A quiet drag stops previewing but keeps its target
+        assert!(!session.time_out(start + Duration::from_millis(500)));
+        assert!(session.time_out(start + Duration::from_millis(100) + SCRUB_TIMEOUT));
+        assert!(!session.is_previewing());
+
+        // Nothing is committed until the drag ends
+        assert_eq!(session.target(), Some(1));
+        assert_eq!(session.commit(), Some(1));
+        assert_eq!(session.commit(), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! Leading/trailing silence trim
+//!
+//! Some rips pad tracks with seconds of digital silence, which breaks the
+//! flow between tracks of a live or gapless album. `SilenceTrim` drops
+//! near-silence at the start and end of a track when it lasts at least the
+//! configured time. It is deliberately conservative:
+//!
+//! - the threshold is well below anything audible, so quiet intros and
+//!   fade-outs (room tone, applause tails) are kept
+//! - silence is only held back near the start and within the last
+//!   [`TRIM_WINDOW_MS`] of the track, never in the middle
+//! - a silent run longer than the window is played, not guessed at
+//!
+//! Never applied in bit-perfect mode (see `AudioSettings::effective_silence_trim`).
+
+use rodio::source::SeekError;
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How far into the start and end of a track silence is looked for
+pub const TRIM_WINDOW_MS: u32 = 5000;
+
+/// Quietest and loudest accepted threshold
+const MIN_THRESHOLD_DBFS: f32 = -96.0;
+const MAX_THRESHOLD_DBFS: f32 = -40.0;
+
+/// Shortest silence worth trimming
+const MIN_SILENCE_MS: u32 = 250;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SilenceTrimConfig {
+    /// Samples at or below this level count as silence
+    pub threshold_dbfs: f32,
+    /// Silence shorter than this is kept
+    pub min_silence_ms: u32,
+}
+
+impl Default for SilenceTrimConfig {
+    fn default() -> Self {
+        Self { threshold_dbfs: -60.0, min_silence_ms: 1000 }
+    }
+}
+
+impl SilenceTrimConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_THRESHOLD_DBFS..=MAX_THRESHOLD_DBFS).contains(&self.threshold_dbfs) {
+            return Err(format!(
+                "Silence threshold must be between {} and {} dBFS",
+                MIN_THRESHOLD_DBFS, MAX_THRESHOLD_DBFS
+            ));
+        }
+        if !(MIN_SILENCE_MS..=TRIM_WINDOW_MS).contains(&self.min_silence_ms) {
+            return Err(format!(
+                "Minimum silence must be between {} and {} ms",
+                MIN_SILENCE_MS, TRIM_WINDOW_MS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Threshold as a sample magnitude (full scale = 1.0)
+    fn threshold_sample(&self) -> f32 {
+        10f32.powf(self.threshold_dbfs / 20.0)
+    }
+}
+
+/// Source wrapper skipping leading/trailing silence
+pub struct SilenceTrim<S> {
+    inner: S,
+    threshold: f32,
+    min_frames: u64,
+    window_frames: u64,
+    channels: usize,
+    /// Leading silence is still to be checked
+    check_leading: bool,
+    frames_read: u64,
+    total_frames: Option<u64>,
+    /// Samples ready to be played
+    ready: VecDeque<f32>,
+    /// Silent samples held back until it's known whether they end the track
+    held: Vec<f32>,
+    frame: Vec<f32>,
+    finished: bool,
+}
+
+impl<S: Source<Item = f32>> SilenceTrim<S> {
+    /// `trim_leading` is off when starting mid-track (seek/resume)
+    pub fn new(inner: S, config: SilenceTrimConfig, trim_leading: bool) -> Self {
+        let sample_rate = inner.sample_rate() as u64;
+        let total_frames = inner.total_duration().map(|d| (d.as_secs_f64() * sample_rate as f64) as u64);
+        Self {
+            threshold: config.threshold_sample(),
+            min_frames: config.min_silence_ms as u64 * sample_rate / 1000,
+            window_frames: TRIM_WINDOW_MS as u64 * sample_rate / 1000,
+            channels: inner.channels().max(1) as usize,
+            check_leading: trim_leading,
+            frames_read: 0,
+            total_frames,
+            ready: VecDeque::new(),
+            held: Vec::new(),
+            frame: Vec::new(),
+            finished: false,
+            inner,
+        }
+    }
+
+    /// Read the next whole frame into `self.frame`
+    fn read_frame(&mut self) -> bool {
+        self.frame.clear();
+        for _ in 0..self.channels {
+            match self.inner.next() {
+                Some(sample) => self.frame.push(sample),
+                None => return false,
+            }
+        }
+        self.frames_read += 1;
+        true
+    }
+
+    fn frame_is_silent(&self) -> bool {
+        self.frame.iter().all(|s| s.abs() <= self.threshold)
+    }
+
+    fn held_frames(&self) -> u64 {
+        (self.held.len() / self.channels) as u64
+    }
+
+    fn release_held(&mut self) {
+        self.ready.extend(self.held.drain(..));
+    }
+
+    /// Drop silence before the first sound if it is long enough
+    fn trim_leading(&mut self) {
+        self.check_leading = false;
+        while self.read_frame() {
+            if !self.frame_is_silent() {
+                if self.held_frames() >= self.min_frames {
+                    log::info!("Silence trim: skipped {} leading frames", self.held_frames());
+                    self.held.clear();
+                }
+                self.release_held();
+                self.ready.extend(self.frame.iter().copied());
+                return;
+            }
+            self.held.extend(self.frame.iter().copied());
+            if self.held_frames() > self.window_frames {
+                break;
+            }
+        }
+        // Too long to be padding (or the whole track is silent): play it
+        self.release_held();
+    }
+
+    /// Whether the read position is close enough to the end to hold back silence
+    fn near_end(&self) -> bool {
+        self.total_frames
+            .is_some_and(|total| total.saturating_sub(self.frames_read) <= self.window_frames)
+    }
+
+    fn fill(&mut self) {
+        loop {
+            if !self.read_frame() {
+                if self.held_frames() >= self.min_frames {
+                    log::info!("Silence trim: skipped {} trailing frames", self.held_frames());
+                    self.held.clear();
+                }
+                self.release_held();
+                self.finished = true;
+                return;
+            }
+
+            if self.frame_is_silent() && self.near_end() && self.held_frames() < self.window_frames {
+                self.held.extend(self.frame.iter().copied());
+                continue;
+            }
+
+            self.release_held();
+            self.ready.extend(self.frame.iter().copied());
+            return;
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for SilenceTrim<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.check_leading {
+            self.trim_leading();
+        }
+        if self.ready.is_empty() && !self.finished {
+            self.fill();
+        }
+        self.ready.pop_front()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for SilenceTrim<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        // Samples are buffered, so the inner frame boundaries don't line up;
+        // a decoded track keeps one format throughout
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.ready.clear();
+        self.held.clear();
+        self.check_leading = false;
+        self.finished = false;
+        self.frames_read = (pos.as_secs_f64() * self.inner.sample_rate() as f64) as u64;
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const RATE: u32 = 1000;
+
+    /// One second of music followed by `tail` (stereo, 1kHz)
+    fn track(tail: &[f32]) -> SamplesBuffer<f32> {
+        let mut samples: Vec<f32> = (0..RATE as usize * 2).map(|i| if i % 4 < 2 { 0.25 } else { -0.25 }).collect();
+        samples.extend_from_slice(tail);
+        SamplesBuffer::new(2, RATE, samples)
+    }
+
+    #[test]
+    fn test_trailing_silence_trimmed_quiet_tail_kept() {
+        let config = SilenceTrimConfig { threshold_dbfs: -60.0, min_silence_ms: 1000 };
+        let music = RATE as usize * 2;
+
+        // 2s of dither-level noise (~-70 dBFS) is silence
+        let silence: Vec<f32> = (0..RATE as usize * 4).map(|i| if i % 2 == 0 { 3e-4 } else { -3e-4 }).collect();
+        assert_eq!(SilenceTrim::new(track(&silence), config, true).count(), music);
+
+        // A 2s quiet tail at ~-50 dBFS is still music
+        let quiet: Vec<f32> = vec![3e-3; RATE as usize * 4];
+        assert_eq!(SilenceTrim::new(track(&quiet), config, true).count(), music + quiet.len());
+
+        // Silence shorter than the minimum is kept
+        let short = vec![0.0; RATE as usize];
+        assert_eq!(SilenceTrim::new(track(&short), config, true).count(), music + short.len());
+
+        // Leading silence goes too, the music itself is untouched
+        let mut padded = vec![0.0; RATE as usize * 3];
+        padded.extend(track(&[]));
+        let trimmed: Vec<f32> =
+            SilenceTrim::new(SamplesBuffer::new(2, RATE, padded), config, true).collect();
+        assert_eq!(trimmed, track(&[]).collect::<Vec<_>>());
+    }
+}
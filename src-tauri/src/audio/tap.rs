@@ -0,0 +1,297 @@
+//! Taps on the output PCM for external analysis
+//!
+//! A registered [`AudioTap`] receives a copy of every buffer the player
+//! outputs (after channel mapping and fade-in, before the sink volume).
+//! Each tap runs on its own thread behind a small bounded queue: the audio
+//! thread only ever `try_send`s, so a slow tap loses buffers (counted in
+//! [`TapRegistry::dropped`]) instead of stalling playback.
+
+use rodio::source::SeekError;
+use rodio::Source;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::wav;
+
+/// Frames per buffer handed to taps
+pub const TAP_BUFFER_FRAMES: usize = 2048;
+
+/// Buffers queued per tap before new ones are dropped
+const TAP_QUEUE_LEN: usize = 32;
+
+/// One buffer of interleaved output samples (full scale = 1.0)
+#[derive(Debug, Clone)]
+pub struct TapBuffer {
+    pub samples: Arc<[f32]>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl TapBuffer {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+}
+
+/// Consumer of output PCM. Runs on its own thread, so it may block,
+/// but buffers arriving while it is busy are dropped once its queue is full.
+pub trait AudioTap: Send + 'static {
+    fn on_buffer(&mut self, buffer: &TapBuffer);
+
+    /// Called once when the tap is unregistered or the player goes away
+    fn finish(&mut self) {}
+}
+
+struct TapHandle {
+    sender: SyncSender<TapBuffer>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Registered taps, shared between the player and its audio thread
+#[derive(Clone, Default)]
+pub struct TapRegistry {
+    taps: Arc<Mutex<HashMap<u64, TapHandle>>>,
+    /// Lets the audio path skip copying while nothing is registered
+    count: Arc<AtomicUsize>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TapRegistry {
+    /// Start delivering buffers to `tap`, returning an id to unregister it
+    pub fn register(&self, mut tap: Box<dyn AudioTap>) -> Result<u64, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::sync_channel::<TapBuffer>(TAP_QUEUE_LEN);
+
+        thread::Builder::new()
+            .name(format!("audio-tap-{}", id))
+            .spawn(move || {
+                for buffer in receiver {
+                    tap.on_buffer(&buffer);
+                }
+                tap.finish();
+            })
+            .map_err(|e| format!("Failed to spawn audio tap thread: {}", e))?;
+
+        // On error the sender is dropped, which ends the tap thread
+        let mut taps = self.taps.lock().map_err(|e| format!("Lock error: {}", e))?;
+        taps.insert(id, TapHandle { sender, dropped: Arc::new(AtomicU64::new(0)) });
+        self.count.store(taps.len(), Ordering::Relaxed);
+        log::info!("Audio tap {} registered", id);
+        Ok(id)
+    }
+
+    /// Stop delivering to a tap; it finishes once its queue is drained
+    pub fn unregister(&self, id: u64) -> bool {
+        let Ok(mut taps) = self.taps.lock() else {
+            return false;
+        };
+        let removed = taps.remove(&id).is_some();
+        self.count.store(taps.len(), Ordering::Relaxed);
+        removed
+    }
+
+    /// Buffers a tap has lost because it fell behind
+    pub fn dropped(&self, id: u64) -> Option<u64> {
+        let taps = self.taps.lock().ok()?;
+        taps.get(&id).map(|tap| tap.dropped.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.load(Ordering::Relaxed) == 0
+    }
+
+    /// Number of registered taps
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Hand a buffer to every tap without blocking
+    fn dispatch(&self, buffer: TapBuffer) {
+        // try_lock: never wait on a register/unregister from the audio thread
+        let Ok(mut taps) = self.taps.try_lock() else {
+            return;
+        };
+        taps.retain(|_, tap| match tap.sender.try_send(buffer.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                tap.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            // The tap thread is gone (it panicked)
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+        self.count.store(taps.len(), Ordering::Relaxed);
+    }
+}
+
+/// Source wrapper copying the samples it yields to the registered taps
+pub struct Tapped<S> {
+    inner: S,
+    taps: TapRegistry,
+    pending: Vec<f32>,
+}
+
+impl<S: Source<Item = f32>> Tapped<S> {
+    pub fn new(inner: S, taps: TapRegistry) -> Self {
+        Self { inner, taps, pending: Vec::new() }
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let buffer = TapBuffer {
+            samples: Arc::from(std::mem::take(&mut self.pending)),
+            sample_rate: self.inner.sample_rate(),
+            channels: self.inner.channels(),
+        };
+        self.taps.dispatch(buffer);
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Tapped<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.inner.next();
+        if self.taps.is_empty() {
+            self.pending.clear();
+            return sample;
+        }
+
+        match sample {
+            Some(sample) => {
+                self.pending.push(sample);
+                if self.pending.len() >= TAP_BUFFER_FRAMES * self.inner.channels().max(1) as usize {
+                    self.flush();
+                }
+            }
+            None => self.flush(),
+        }
+        sample
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Tapped<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.pending.clear();
+        self.inner.try_seek(pos)
+    }
+}
+
+/// Example tap recording the output to a 16-bit WAV file.
+/// Buffers in a different format than the first one are skipped.
+pub struct WavRecorderTap {
+    writer: BufWriter<File>,
+    format: Option<(u32, u16)>,
+    data_bytes: u32,
+}
+
+impl WavRecorderTap {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create recording: {}", e))?;
+        Ok(Self { writer: BufWriter::new(file), format: None, data_bytes: 0 })
+    }
+
+    fn write_header(&mut self, sample_rate: u32, channels: u16) -> std::io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&wav::pcm16_header(sample_rate, channels, self.data_bytes))?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl AudioTap for WavRecorderTap {
+    fn on_buffer(&mut self, buffer: &TapBuffer) {
+        let format = (buffer.sample_rate, buffer.channels);
+        match self.format {
+            None => {
+                self.format = Some(format);
+                if let Err(e) = self.write_header(format.0, format.1) {
+                    log::warn!("Recording tap failed to write header: {}", e);
+                }
+            }
+            Some(current) if current != format => return,
+            Some(_) => {}
+        }
+
+        for &sample in buffer.samples.iter() {
+            if self.writer.write_all(&wav::to_i16(sample).to_le_bytes()).is_err() {
+                return;
+            }
+        }
+        self.data_bytes = self.data_bytes.saturating_add(buffer.samples.len() as u32 * 2);
+    }
+
+    fn finish(&mut self) {
+        if let Some((sample_rate, channels)) = self.format {
+            if let Err(e) = self.write_header(sample_rate, channels).and_then(|_| self.writer.flush()) {
+                log::warn!("Recording tap failed to finalize: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    struct CountingTap {
+        frames: Arc<AtomicUsize>,
+    }
+
+    impl AudioTap for CountingTap {
+        fn on_buffer(&mut self, buffer: &TapBuffer) {
+            assert_eq!((buffer.sample_rate, buffer.channels), (48000, 2));
+            self.frames.fetch_add(buffer.frames(), Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_counting_tap_receives_every_frame() {
+        let frames = Arc::new(AtomicUsize::new(0));
+        let taps = TapRegistry::default();
+        let id = taps.register(Box::new(CountingTap { frames: frames.clone() })).unwrap();
+
+        // 2.5 buffers of stereo audio, passed through unchanged
+        let total_frames = TAP_BUFFER_FRAMES * 5 / 2;
+        let samples: Vec<f32> = (0..total_frames * 2).map(|i| i as f32 / 32_768.0).collect();
+        let played: Vec<f32> = Tapped::new(SamplesBuffer::new(2, 48000, samples.clone()), taps.clone()).collect();
+        assert_eq!(played, samples);
+
+        for _ in 0..100 {
+            if frames.load(Ordering::SeqCst) == total_frames {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(frames.load(Ordering::SeqCst), total_frames);
+        assert_eq!(taps.dropped(id), Some(0));
+        assert!(taps.unregister(id));
+        assert!(taps.is_empty());
+    }
+}
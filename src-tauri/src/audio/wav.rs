@@ -0,0 +1,39 @@
+//! 16-bit PCM WAV encoding
+
+/// Size of the header written by `pcm16_header`
+pub const HEADER_LEN: usize = 44;
+
+/// Header of a 16-bit PCM WAV file holding `data_bytes` of samples
+pub fn pcm16_header(sample_rate: u32, channels: u16, data_bytes: u32) -> Vec<u8> {
+    let block_align = channels * 2;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_bytes).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_bytes.to_le_bytes());
+    header
+}
+
+/// An f32 sample (full scale = 1.0) as a 16-bit sample, exact for
+/// samples that came from 16-bit content
+pub fn to_i16(sample: f32) -> i16 {
+    (sample * 32_768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// A complete WAV file of interleaved 16-bit samples
+pub fn pcm16(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let mut wav = pcm16_header(sample_rate, channels, (samples.len() * 2) as u32);
+    wav.reserve(samples.len() * 2);
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
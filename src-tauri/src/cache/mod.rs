@@ -7,13 +7,66 @@
 //! Flow:
 //! 1. When a track is evicted from memory, it's saved to disk cache
 //! 2. When loading, check memory -> disk -> network
+//! 3. A track is only reported as evicted once it's in neither cache
+//!
+//! The `CacheMode` trades memory for re-downloads on constrained systems.
+//!
+//! Caching and prefetch activity is reported as `CacheEvent`s through an
+//! optional emitter, so the UI can show which queue items will play instantly.
 
 pub mod playback_cache;
 
 pub use playback_cache::{PlaybackCache, PlaybackCacheStats};
 
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
+
+use futures_util::future::AbortHandle;
+use serde::{Deserialize, Serialize};
+
+/// Memory budget for `CacheMode::SmallDisk`
+pub const SMALL_DISK_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// How aggressively audio data is cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Memory cache with disk spillover and prefetching
+    #[default]
+    Full,
+    /// Nothing is stored or prefetched; each track is downloaded only for the
+    /// player and dropped when playback moves on
+    StreamOnly,
+    /// Small memory cache; tracks that don't fit go straight to the disk cache
+    SmallDisk,
+}
+
+/// Cache activity, emitted under `CacheEvent::name` with the fields as payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum CacheEvent {
+    /// Track is ready for instant playback (memory or disk)
+    TrackCached { track_id: u64, size: usize },
+    /// Track is no longer cached, in memory or on disk
+    Evicted { track_id: u64 },
+    PrefetchStarted { track_id: u64 },
+    PrefetchFailed { track_id: u64, error: String },
+}
+
+impl CacheEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheEvent::TrackCached { .. } => "cache-track-cached",
+            CacheEvent::Evicted { .. } => "cache-evicted",
+            CacheEvent::PrefetchStarted { .. } => "prefetch-started",
+            CacheEvent::PrefetchFailed { .. } => "prefetch-failed",
+        }
+    }
+}
+
+/// Receives cache events. Called without the cache lock held, from whatever
+/// thread touched the cache, so it must return quickly.
+pub type CacheEventEmitter = Arc<dyn Fn(CacheEvent) + Send + Sync>;
 
 /// Cached audio data for a track
 #[derive(Clone)]
@@ -33,6 +86,14 @@ struct CacheState {
     current_size: usize,
     /// Track IDs currently being fetched
     fetching: HashSet<u64>,
+    /// Cancellable prefetches among `fetching`
+    prefetch_tasks: HashMap<u64, AbortHandle>,
+    /// Active caching mode
+    mode: CacheMode,
+    /// Prefetch the first tracks of an album/playlist as soon as it's queued
+    warm_additions: bool,
+    /// Tracks kept prefetched wherever they are in the queue
+    warm_tracks: Vec<u64>,
 }
 
 /// Audio cache manager with LRU eviction and disk spillover
@@ -42,6 +103,8 @@ pub struct AudioCache {
     max_size_bytes: usize,
     /// Optional disk-based L2 cache for evicted tracks
     playback_cache: Option<Arc<PlaybackCache>>,
+    /// Where cache events go, once the app is up
+    emitter: RwLock<Option<CacheEventEmitter>>,
 }
 
 impl Default for AudioCache {
@@ -59,9 +122,14 @@ impl AudioCache {
                 access_order: Vec::new(),
                 current_size: 0,
                 fetching: HashSet::new(),
+                prefetch_tasks: HashMap::new(),
+                mode: CacheMode::Full,
+                warm_additions: false,
+                warm_tracks: Vec::new(),
             }),
             max_size_bytes,
             playback_cache: None,
+            emitter: RwLock::new(None),
         }
     }
 
@@ -73,9 +141,14 @@ impl AudioCache {
                 access_order: Vec::new(),
                 current_size: 0,
                 fetching: HashSet::new(),
+                prefetch_tasks: HashMap::new(),
+                mode: CacheMode::Full,
+                warm_additions: false,
+                warm_tracks: Vec::new(),
             }),
             max_size_bytes,
             playback_cache: Some(playback_cache),
+            emitter: RwLock::new(None),
         }
     }
 
@@ -89,6 +162,144 @@ impl AudioCache {
         self.playback_cache.as_ref()
     }
 
+    /// Report cache events to `emitter`
+    pub fn set_event_emitter(&self, emitter: CacheEventEmitter) {
+        *self.emitter.write().unwrap() = Some(emitter);
+    }
+
+    /// Send an event to the emitter, if any (call without the lock held)
+    fn emit(&self, event: CacheEvent) {
+        let emitter = self.emitter.read().unwrap().clone();
+        if let Some(emitter) = emitter {
+            emitter(event);
+        }
+    }
+
+    /// Report those of `track_ids` that are cached neither in memory nor on
+    /// disk (call without the lock held)
+    fn emit_evicted(&self, track_ids: impl IntoIterator<Item = u64>) {
+        for track_id in track_ids {
+            let on_disk = self.playback_cache.as_ref().is_some_and(|pc| pc.contains(track_id));
+            if !on_disk && !self.contains(track_id) {
+                self.emit(CacheEvent::Evicted { track_id });
+            }
+        }
+    }
+
+    /// Get the active caching mode
+    pub fn mode(&self) -> CacheMode {
+        self.state.lock().unwrap().mode
+    }
+
+    /// Switch caching mode, releasing memory the new mode doesn't allow
+    pub fn set_mode(&self, mode: CacheMode) {
+        let evicted = {
+            let mut state = self.state.lock().unwrap();
+            state.mode = mode;
+            let limit = match mode {
+                CacheMode::StreamOnly => 0,
+                _ => self.memory_limit(mode),
+            };
+            Self::evict_to(&mut state, limit)
+        };
+
+        if mode == CacheMode::StreamOnly {
+            self.emit_evicted(evicted.iter().map(|track| track.track_id));
+        } else {
+            self.spill(evicted);
+        }
+        log::info!("Audio cache mode set to {:?}", mode);
+    }
+
+    /// Whether upcoming tracks may be downloaded ahead of time
+    pub fn prefetch_enabled(&self) -> bool {
+        self.mode() != CacheMode::StreamOnly
+    }
+
+    /// Whether the first tracks of a queued album/playlist are prefetched
+    /// right away, wherever they land in the queue
+    pub fn warm_additions_enabled(&self) -> bool {
+        self.state.lock().unwrap().warm_additions
+    }
+
+    pub fn set_warm_additions(&self, enabled: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.warm_additions = enabled;
+        if !enabled {
+            state.warm_tracks.clear();
+        }
+    }
+
+    /// Keep `track_ids` prefetched in place of the previous additions;
+    /// ignored unless warming additions is enabled
+    pub fn set_warm_tracks(&self, track_ids: Vec<u64>) {
+        let mut state = self.state.lock().unwrap();
+        if state.warm_additions {
+            state.warm_tracks = track_ids;
+        }
+    }
+
+    /// Tracks kept warm, after dropping those `keep` rejects
+    pub fn retain_warm_tracks(&self, keep: impl Fn(u64) -> bool) -> Vec<u64> {
+        let mut state = self.state.lock().unwrap();
+        state.warm_tracks.retain(|track_id| keep(*track_id));
+        state.warm_tracks.clone()
+    }
+
+    /// Memory budget for the given mode
+    fn memory_limit(&self, mode: CacheMode) -> usize {
+        match mode {
+            CacheMode::SmallDisk => self.max_size_bytes.min(SMALL_DISK_MEMORY_BYTES),
+            _ => self.max_size_bytes,
+        }
+    }
+
+    /// Evict least recently used tracks until `limit` bytes would fit (lock held)
+    fn evict_to(state: &mut CacheState, limit: usize) -> Vec<CachedTrack> {
+        let mut evicted = Vec::new();
+        while state.current_size > limit && !state.access_order.is_empty() {
+            let oldest_id = state.access_order.remove(0);
+            if let Some(track) = state.tracks.remove(&oldest_id) {
+                state.current_size = state.current_size.saturating_sub(track.size_bytes);
+                log::debug!(
+                    "Evicting track {} ({} bytes) from memory cache",
+                    oldest_id,
+                    track.size_bytes
+                );
+                evicted.push(track);
+            }
+        }
+        evicted
+    }
+
+    /// Write evicted tracks to the disk cache and report those that didn't
+    /// make it there, or were pushed out of it (call without the lock held)
+    fn spill(&self, tracks: Vec<CachedTrack>) {
+        let mut gone = Vec::new();
+        for track in tracks {
+            if let Some(playback_cache) = &self.playback_cache {
+                gone.extend(playback_cache.insert(track.track_id, &track.data));
+            }
+            gone.push(track.track_id);
+        }
+        self.emit_evicted(gone);
+    }
+
+    /// Whether a streamed track is kept whole for the cache. In `StreamOnly`
+    /// mode only the playback buffer is held.
+    pub fn keeps_streams(&self) -> bool {
+        self.mode() != CacheMode::StreamOnly
+    }
+
+    /// Keep a copy of freshly downloaded data according to the cache mode and
+    /// hand the data back for playback. In `StreamOnly` mode nothing is copied.
+    pub fn retain_for_playback(&self, track_id: u64, data: Vec<u8>) -> Vec<u8> {
+        if self.mode() != CacheMode::StreamOnly {
+            self.insert(track_id, data.clone());
+        }
+        data
+    }
+
     /// Get a track from cache if available
     pub fn get(&self, track_id: u64) -> Option<CachedTrack> {
         let mut state = self.state.lock().unwrap();
@@ -117,58 +328,92 @@ impl AudioCache {
         self.state.lock().unwrap().fetching.contains(&track_id)
     }
 
-    /// Mark a track as being fetched
+    /// Mark a track as being prefetched
     pub fn mark_fetching(&self, track_id: u64) {
         self.state.lock().unwrap().fetching.insert(track_id);
+        self.emit(CacheEvent::PrefetchStarted { track_id });
+    }
+
+    /// Report a prefetch that won't complete
+    pub fn prefetch_failed(&self, track_id: u64, error: &str) {
+        self.emit(CacheEvent::PrefetchFailed { track_id, error: error.to_string() });
     }
 
     /// Unmark a track as being fetched
     pub fn unmark_fetching(&self, track_id: u64) {
-        self.state.lock().unwrap().fetching.remove(&track_id);
+        let mut state = self.state.lock().unwrap();
+        state.fetching.remove(&track_id);
+        state.prefetch_tasks.remove(&track_id);
+    }
+
+    /// Remember how to cancel the prefetch of `track_id`; ignored if it
+    /// already finished
+    pub fn set_prefetch_task(&self, track_id: u64, task: AbortHandle) {
+        let mut state = self.state.lock().unwrap();
+        if state.fetching.contains(&track_id) {
+            state.prefetch_tasks.insert(track_id, task);
+        }
+    }
+
+    /// Cancel prefetches of tracks that are no longer in `window`, returning
+    /// their ids. Fetches started for playback aren't touched.
+    pub fn cancel_prefetches_outside(&self, window: &[u64]) -> Vec<u64> {
+        let mut state = self.state.lock().unwrap();
+        let stale: Vec<u64> = state
+            .prefetch_tasks
+            .keys()
+            .filter(|track_id| !window.contains(track_id))
+            .copied()
+            .collect();
+        for track_id in &stale {
+            if let Some(task) = state.prefetch_tasks.remove(track_id) {
+                task.abort();
+            }
+            state.fetching.remove(track_id);
+        }
+        stale
     }
 
     /// Insert a track into cache, evicting old entries to disk if needed
     pub fn insert(&self, track_id: u64, data: Vec<u8>) {
         let size = data.len();
+        let mode = self.mode();
 
-        // Don't cache if track is larger than max cache size
-        if size > self.max_size_bytes {
+        if mode == CacheMode::StreamOnly {
+            log::debug!("Stream-only mode, not caching track {}", track_id);
+            return;
+        }
+
+        let limit = self.memory_limit(mode);
+
+        // Don't cache in memory if track is larger than the memory budget
+        if size > limit {
+            if mode == CacheMode::SmallDisk {
+                if let Some(playback_cache) = &self.playback_cache {
+                    log::debug!("Track {} ({} bytes) cached on disk only", track_id, size);
+                    let evicted = playback_cache.insert(track_id, &data);
+                    self.emit_evicted(evicted);
+                    self.emit(CacheEvent::TrackCached { track_id, size });
+                    return;
+                }
+            }
             log::warn!(
                 "Track {} ({} bytes) too large for cache (max {} bytes)",
                 track_id,
                 size,
-                self.max_size_bytes
+                limit
             );
             return;
         }
 
         // Collect tracks to evict (to avoid holding lock while writing to disk)
-        let mut tracks_to_spill: Vec<CachedTrack> = Vec::new();
-
-        {
+        let tracks_to_spill = {
             let mut state = self.state.lock().unwrap();
-
-            // Evict old entries to make room
-            while state.current_size + size > self.max_size_bytes && !state.access_order.is_empty() {
-                let oldest_id = state.access_order.remove(0);
-                if let Some(track) = state.tracks.remove(&oldest_id) {
-                    state.current_size = state.current_size.saturating_sub(track.size_bytes);
-                    log::debug!(
-                        "Evicting track {} ({} bytes) from memory cache",
-                        oldest_id,
-                        track.size_bytes
-                    );
-                    tracks_to_spill.push(track);
-                }
-            }
-        }
+            Self::evict_to(&mut state, limit - size)
+        };
 
         // Spill evicted tracks to disk cache (outside of lock)
-        if let Some(playback_cache) = &self.playback_cache {
-            for track in tracks_to_spill {
-                playback_cache.insert(track.track_id, &track.data);
-            }
-        }
+        self.spill(tracks_to_spill);
 
         let mut state = self.state.lock().unwrap();
 
@@ -195,18 +440,41 @@ impl AudioCache {
             track_id,
             size,
             state.current_size,
-            self.max_size_bytes
+            limit
         );
+        drop(state);
+
+        self.emit(CacheEvent::TrackCached { track_id, size });
     }
 
-    /// Clear all cached data
-    pub fn clear(&self) {
-        let mut state = self.state.lock().unwrap();
-        state.tracks.clear();
-        state.access_order.clear();
-        state.current_size = 0;
-        state.fetching.clear();
+    /// Clear all cached data, returning the bytes freed
+    pub fn clear(&self) -> u64 {
+        let (freed, cleared) = {
+            let mut state = self.state.lock().unwrap();
+            let freed = state.current_size as u64;
+            let cleared: Vec<u64> = state.tracks.drain().map(|(track_id, _)| track_id).collect();
+            state.access_order.clear();
+            state.current_size = 0;
+            state.fetching.clear();
+            for (_, task) in state.prefetch_tasks.drain() {
+                task.abort();
+            }
+            (freed, cleared)
+        };
+        self.emit_evicted(cleared);
         log::info!("Cache cleared");
+        freed
+    }
+
+    /// Clear the disk cache, returning the bytes freed
+    pub fn clear_disk(&self) -> u64 {
+        let Some(playback_cache) = &self.playback_cache else {
+            return 0;
+        };
+        let cleared = playback_cache.track_ids();
+        let freed = playback_cache.clear();
+        self.emit_evicted(cleared);
+        freed
     }
 
     /// Get cache statistics
@@ -215,7 +483,7 @@ impl AudioCache {
         CacheStats {
             cached_tracks: state.tracks.len(),
             current_size_bytes: state.current_size,
-            max_size_bytes: self.max_size_bytes,
+            max_size_bytes: self.memory_limit(state.mode),
             fetching_count: state.fetching.len(),
         }
     }
@@ -229,3 +497,103 @@ pub struct CacheStats {
     pub max_size_bytes: usize,
     pub fetching_count: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_prefetch_and_evict_sequence_emits_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let cache = AudioCache::new(1000);
+        let sink = events.clone();
+        cache.set_event_emitter(Arc::new(move |event| sink.lock().unwrap().push(event)));
+
+        cache.mark_fetching(1);
+        cache.insert(1, vec![0u8; 600]);
+        cache.unmark_fetching(1);
+        cache.mark_fetching(2);
+        cache.prefetch_failed(2, "Failed to get stream URL");
+        cache.unmark_fetching(2);
+        // Doesn't fit next to track 1, which is evicted
+        cache.insert(3, vec![0u8; 600]);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                CacheEvent::PrefetchStarted { track_id: 1 },
+                CacheEvent::TrackCached { track_id: 1, size: 600 },
+                CacheEvent::PrefetchStarted { track_id: 2 },
+                CacheEvent::PrefetchFailed { track_id: 2, error: "Failed to get stream URL".to_string() },
+                CacheEvent::Evicted { track_id: 1 },
+                CacheEvent::TrackCached { track_id: 3, size: 600 },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(CacheEvent::TrackCached { track_id: 3, size: 600 }).unwrap(),
+            serde_json::json!({ "track_id": 3, "size": 600 })
+        );
+    }
+
+    #[test]
+    fn test_only_tracks_leaving_the_disk_cache_are_reported_evicted() {
+        let dir = std::env::temp_dir().join(format!("qbz-playback-cache-{}", std::process::id()));
+        let playback_cache = Arc::new(PlaybackCache::in_dir(dir.clone(), 1000).unwrap());
+        let cache = AudioCache::with_playback_cache(1000, playback_cache.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        cache.set_event_emitter(Arc::new(move |event| {
+            if let CacheEvent::Evicted { track_id } = event {
+                sink.lock().unwrap().push(track_id);
+            }
+        }));
+
+        // Track 1 spills to disk and is still playable
+        cache.insert(1, vec![1u8; 600]);
+        cache.insert(2, vec![2u8; 600]);
+        assert!(playback_cache.contains(1));
+        assert!(events.lock().unwrap().is_empty());
+
+        // Spilling track 2 pushes track 1 off the disk
+        cache.insert(3, vec![3u8; 600]);
+        assert_eq!(*events.lock().unwrap(), vec![1]);
+
+        // Track 3 is in memory, track 2 only on disk
+        cache.clear_disk();
+        assert_eq!(*events.lock().unwrap(), vec![1, 2]);
+        cache.clear();
+        assert_eq!(*events.lock().unwrap(), vec![1, 2, 3]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_stream_only_hands_data_to_player_without_caching() {
+        let cache = AudioCache::new(1024 * 1024);
+        cache.set_mode(CacheMode::StreamOnly);
+
+        let data = vec![7u8; 4096];
+        let played = cache.retain_for_playback(1, data.clone());
+        cache.insert(2, data.clone());
+
+        assert_eq!(played, data);
+        assert!(!cache.contains(1));
+        assert!(!cache.contains(2));
+        assert_eq!(cache.stats().current_size_bytes, 0);
+        assert!(!cache.prefetch_enabled());
+        assert!(!cache.keeps_streams());
+    }
+
+    #[test]
+    fn test_switching_to_stream_only_frees_memory() {
+        let cache = AudioCache::new(1024 * 1024);
+        let played = cache.retain_for_playback(1, vec![1u8; 1024]);
+
+        assert_eq!(played.len(), 1024);
+        assert!(cache.contains(1));
+
+        cache.set_mode(CacheMode::StreamOnly);
+        assert!(!cache.contains(1));
+        assert_eq!(cache.stats().current_size_bytes, 0);
+    }
+}
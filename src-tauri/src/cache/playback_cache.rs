@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use std::time::SystemTime;
 
 /// Entry metadata for tracking cache usage
@@ -30,7 +30,7 @@ struct PlaybackCacheState {
 pub struct PlaybackCache {
     state: Mutex<PlaybackCacheState>,
     /// Cache directory path
-    cache_dir: PathBuf,
+    cache_dir: RwLock<PathBuf>,
     /// Maximum cache size in bytes (default: 500MB)
     max_size_bytes: u64,
 }
@@ -38,11 +38,11 @@ pub struct PlaybackCache {
 impl PlaybackCache {
     /// Create a new playback cache
     pub fn new(max_size_bytes: u64) -> Result<Self, String> {
-        let cache_dir = dirs::cache_dir()
-            .ok_or("Could not determine cache directory")?
-            .join("qbz")
-            .join("playback");
+        Self::in_dir(crate::storage::storage_root().join(crate::storage::PLAYBACK_DIR), max_size_bytes)
+    }
 
+    /// Create a playback cache keeping its files in `cache_dir`
+    pub fn in_dir(cache_dir: PathBuf, max_size_bytes: u64) -> Result<Self, String> {
         // Create directory
         fs::create_dir_all(&cache_dir)
             .map_err(|e| format!("Failed to create playback cache directory: {}", e))?;
@@ -52,7 +52,7 @@ impl PlaybackCache {
                 entries: HashMap::new(),
                 current_size: 0,
             }),
-            cache_dir,
+            cache_dir: RwLock::new(cache_dir),
             max_size_bytes,
         };
 
@@ -61,7 +61,7 @@ impl PlaybackCache {
 
         log::info!(
             "Playback cache initialized at {:?} (max {} MB)",
-            cache.cache_dir,
+            cache.dir(),
             max_size_bytes / (1024 * 1024)
         );
 
@@ -74,7 +74,7 @@ impl PlaybackCache {
         state.entries.clear();
         state.current_size = 0;
 
-        if let Ok(entries) = fs::read_dir(&self.cache_dir) {
+        if let Ok(entries) = fs::read_dir(self.dir()) {
             for entry in entries.flatten() {
                 if let Ok(metadata) = entry.metadata() {
                     if metadata.is_file() {
@@ -111,9 +111,22 @@ impl PlaybackCache {
         );
     }
 
+    fn dir(&self) -> PathBuf {
+        self.cache_dir.read().unwrap().clone()
+    }
+
+    /// Use the files in `dir` from now on (after a storage migration)
+    pub fn set_dir(&self, dir: PathBuf) {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::warn!("Failed to create playback cache directory: {}", e);
+        }
+        *self.cache_dir.write().unwrap() = dir;
+        self.rebuild_state();
+    }
+
     /// Get file path for a track
     fn track_path(&self, track_id: u64) -> PathBuf {
-        self.cache_dir.join(format!("{}.audio", track_id))
+        self.dir().join(format!("{}.audio", track_id))
     }
 
     /// Check if a track is in the cache
@@ -121,6 +134,11 @@ impl PlaybackCache {
         self.state.lock().unwrap().entries.contains_key(&track_id)
     }
 
+    /// Ids of the cached tracks
+    pub fn track_ids(&self) -> Vec<u64> {
+        self.state.lock().unwrap().entries.keys().copied().collect()
+    }
+
     /// Get a track from the cache
     pub fn get(&self, track_id: u64) -> Option<Vec<u8>> {
         let path = self.track_path(track_id);
@@ -166,8 +184,9 @@ impl PlaybackCache {
         }
     }
 
-    /// Insert a track into the cache (called when evicting from memory cache)
-    pub fn insert(&self, track_id: u64, data: &[u8]) {
+    /// Insert a track into the cache (called when evicting from memory cache).
+    /// Returns the tracks evicted to make room for it.
+    pub fn insert(&self, track_id: u64, data: &[u8]) -> Vec<u64> {
         let size = data.len() as u64;
 
         // Don't cache if larger than max size
@@ -178,11 +197,11 @@ impl PlaybackCache {
                 size / (1024 * 1024),
                 self.max_size_bytes / (1024 * 1024)
             );
-            return;
+            return Vec::new();
         }
 
         // Evict old entries if needed
-        self.evict_if_needed(size);
+        let evicted = self.evict_if_needed(size);
 
         let path = self.track_path(track_id);
 
@@ -224,11 +243,13 @@ impl PlaybackCache {
                 log::warn!("Failed to create playback cache file for track {}: {}", track_id, e);
             }
         }
+        evicted
     }
 
-    /// Evict oldest entries to make room for new data
-    fn evict_if_needed(&self, needed_bytes: u64) {
+    /// Evict oldest entries to make room for new data, returning their ids
+    fn evict_if_needed(&self, needed_bytes: u64) -> Vec<u64> {
         let mut state = self.state.lock().unwrap();
+        let mut evicted = Vec::new();
 
         while state.current_size + needed_bytes > self.max_size_bytes && !state.entries.is_empty() {
             // Find oldest entry
@@ -241,9 +262,10 @@ impl PlaybackCache {
             if let Some(track_id) = oldest_id {
                 if let Some(entry) = state.entries.remove(&track_id) {
                     state.current_size = state.current_size.saturating_sub(entry.size_bytes);
+                    evicted.push(track_id);
 
                     // Delete file
-                    let path = self.cache_dir.join(format!("{}.audio", track_id));
+                    let path = self.track_path(track_id);
                     if let Err(e) = fs::remove_file(&path) {
                         log::debug!("Failed to delete playback cache file: {}", e);
                     } else {
@@ -258,14 +280,16 @@ impl PlaybackCache {
                 break;
             }
         }
+        evicted
     }
 
-    /// Clear the entire cache
-    pub fn clear(&self) {
+    /// Clear the entire cache, returning the bytes freed
+    pub fn clear(&self) -> u64 {
         let mut state = self.state.lock().unwrap();
+        let freed = state.current_size;
 
         for track_id in state.entries.keys() {
-            let path = self.cache_dir.join(format!("{}.audio", track_id));
+            let path = self.track_path(*track_id);
             let _ = fs::remove_file(&path);
         }
 
@@ -273,6 +297,7 @@ impl PlaybackCache {
         state.current_size = 0;
 
         log::info!("Playback cache cleared");
+        freed
     }
 
     /// Get cache statistics
@@ -6,6 +6,7 @@ use tokio::sync::Mutex;
 
 use crate::api::models::Quality;
 use crate::AppState;
+use crate::player::download_audio;
 use crate::cast::dlna::{
     DiscoveredDlnaDevice, DlnaConnection, DlnaDiscovery, DlnaError, DlnaMetadata, DlnaStatus,
 };
@@ -215,31 +216,3 @@ pub async fn dlna_set_volume(volume: f32, state: State<'_, DlnaState>) -> Result
     let conn = connection.as_mut().ok_or_else(|| "Not connected".to_string())?;
     conn.set_volume(volume).await.map_err(|e| e.to_string())
 }
-
-async fn download_audio(url: &str) -> Result<Vec<u8>, String> {
-    use std::time::Duration;
-
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(120))
-        .connect_timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let response = client
-        .get(url)
-        .header("User-Agent", "Mozilla/5.0")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch audio: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP error: {}", response.status()));
-    }
-
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
-
-    Ok(bytes.to_vec())
-}
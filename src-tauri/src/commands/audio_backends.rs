@@ -1,7 +1,15 @@
 //! Tauri commands for audio backend management
 
-use crate::audio::{AlsaPlugin, AudioBackendType, AudioDevice, BackendManager};
+use crate::audio::{AlsaPlugin, AudioBackendType, AudioDevice, BackendManager, CapabilityCache, DeviceCaps};
+use crate::config::audio_settings::AudioSettingsState;
 use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// Device capabilities, queried once per connected device
+#[derive(Default)]
+pub struct DeviceCapsState {
+    cache: CapabilityCache,
+}
 
 /// Backend information for UI display
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +83,39 @@ pub fn get_devices_for_backend(backend_type: AudioBackendType) -> Result<Vec<Aud
     Ok(devices)
 }
 
+/// Report what a device supports (max rate/bit depth, formats, channels,
+/// DSD, exclusive access). Uses the configured backend unless one is given.
+#[tauri::command]
+pub fn get_device_capabilities(
+    device_id: String,
+    backend_type: Option<AudioBackendType>,
+    caps_state: State<'_, DeviceCapsState>,
+    settings_state: State<'_, AudioSettingsState>,
+) -> Result<DeviceCaps, String> {
+    log::info!("Command: get_device_capabilities({})", device_id);
+
+    let backend_type = match backend_type {
+        Some(backend_type) => backend_type,
+        None => settings_state
+            .store
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .get_settings()?
+            .backend_type
+            .unwrap_or_default(),
+    };
+    let backend = BackendManager::create_backend(backend_type)?;
+
+    // Cached entries last until their device disappears
+    let devices = backend.enumerate_devices()?;
+    caps_state.cache.retain_connected(backend_type, &devices);
+    if !devices.iter().any(|device| device.id == device_id) {
+        return Err(format!("Device '{}' is not connected", device_id));
+    }
+
+    caps_state.cache.get_or_query(backend.as_ref(), &device_id)
+}
+
 /// Get list of available ALSA plugins
 #[tauri::command]
 pub fn get_alsa_plugins() -> Result<Vec<AlsaPluginInfo>, String> {
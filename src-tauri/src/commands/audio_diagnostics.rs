@@ -1,6 +1,20 @@
 //! Audio diagnostics commands for detecting actual hardware sample rate
 
 use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use tauri::{AppHandle, Manager};
+
+use crate::audio::bitperfect::{
+    self, BitPerfectReport, CpalLoopbackCapture, LoopbackCapture, PatternOutput, TestPattern,
+};
+use crate::player::Player;
+use crate::AppState;
+
+/// How long the player may take to open the device and start the pattern
+const PATTERN_START_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Hardware audio status
 #[derive(Debug, Clone, serde::Serialize)]
@@ -71,3 +85,93 @@ pub fn get_hardware_audio_status() -> Result<HardwareAudioStatus, String> {
         is_active: false,
     })
 }
+
+/// Plays the pattern through the player like a track, so it takes the same
+/// decode, gain and mixer path
+struct PlayerPatternOutput<'a> {
+    player: &'a Player,
+    device: String,
+}
+
+impl PatternOutput for PlayerPatternOutput<'_> {
+    fn name(&self) -> Option<String> {
+        Some(self.device.clone())
+    }
+
+    fn play(&mut self, pattern: &TestPattern) -> Result<(), String> {
+        // Track 0 is "no track": nothing is reported or shown as playing
+        self.player.play_data(pattern.to_wav(), 0)?;
+
+        let started = Instant::now() + PATTERN_START_TIMEOUT;
+        while !self.player.state.is_playing() {
+            if Instant::now() > started {
+                return Err("Player didn't start the test pattern".to_string());
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let deadline = Instant::now() + pattern.duration() * 2 + bitperfect::CAPTURE_TAIL;
+        while self.player.state.is_playing() {
+            if Instant::now() > deadline {
+                let _ = self.player.stop();
+                return Err("Output stalled while playing the test pattern".to_string());
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        thread::sleep(bitperfect::CAPTURE_TAIL);
+        Ok(())
+    }
+}
+
+/// Play a known PCM pattern through the player on `device` (the current
+/// output when omitted) and verify it sample for sample through that
+/// device's loopback/monitor source. Playback is stopped first so the test
+/// has the device to itself; the previous device is restored afterwards.
+#[tauri::command]
+pub async fn run_bitperfect_test(
+    device: Option<String>,
+    app: AppHandle,
+) -> Result<BitPerfectReport, String> {
+    log::info!("Command: run_bitperfect_test {:?}", device);
+
+    tokio::task::spawn_blocking(move || -> Result<BitPerfectReport, String> {
+        let player = &app.state::<AppState>().player;
+        player.stop()?;
+
+        let previous = player.state.current_device();
+        let switch = device.is_some() && device != previous;
+        if switch {
+            player.reinit_device(device.clone())?;
+        }
+
+        let result = run_through_player(player, device.or_else(|| previous.clone()));
+
+        if switch {
+            player.reinit_device(previous)?;
+        }
+        let report = result?;
+        log::info!("Bit-perfect test result: {:?} ({:?})", report.status, report.detail);
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Bit-perfect test failed: {}", e))?
+}
+
+fn run_through_player(player: &Player, device: Option<String>) -> Result<BitPerfectReport, String> {
+    let host = rodio::cpal::default_host();
+    let device = match device {
+        Some(name) => name,
+        None => host
+            .default_output_device()
+            .and_then(|d| d.name().ok())
+            .ok_or_else(|| "No default output device".to_string())?,
+    };
+    log::info!("Running bit-perfect test on {}", device);
+
+    let mut loopback = CpalLoopbackCapture::find(&host, &device);
+    let mut output = PlayerPatternOutput { player, device };
+    bitperfect::run_bitperfect_test(
+        &mut output,
+        loopback.as_mut().map(|l| l as &mut dyn LoopbackCapture),
+    )
+}
@@ -2,6 +2,10 @@
 
 use tauri::State;
 
+use crate::api::models::{AccountFeatures, ApiDiagnostics, DeviceInfo, SessionZone};
+use crate::api::QobuzClient;
+use crate::api_cache::ApiCacheState;
+use crate::config::cache_settings::{warm_cache_on_login_enabled, CacheSettingsState};
 use crate::credentials;
 use crate::AppState;
 
@@ -10,30 +14,82 @@ pub struct LoginResponse {
     pub success: bool,
     pub user_name: Option<String>,
     pub subscription: Option<String>,
+    /// False for free or lapsed accounts (browse-only, no streaming)
+    pub subscription_active: bool,
     pub error: Option<String>,
 }
 
+impl LoginResponse {
+    fn failed(error: String) -> Self {
+        Self {
+            success: false,
+            user_name: None,
+            subscription: None,
+            subscription_active: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// Kick off background cache warming after a successful login, if enabled
+fn warm_cache_after_login(
+    state: &AppState,
+    cache_state: &ApiCacheState,
+    cache_settings: &CacheSettingsState,
+) {
+    if warm_cache_on_login_enabled(cache_settings) {
+        cache_state.start_warming(state.client.clone());
+    }
+}
+
 #[tauri::command]
 pub async fn login(
     email: String,
     password: String,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    cache_settings: State<'_, CacheSettingsState>,
 ) -> Result<LoginResponse, String> {
     let client = state.client.lock().await;
 
     match client.login(&email, &password).await {
-        Ok(session) => Ok(LoginResponse {
-            success: true,
-            user_name: Some(session.display_name),
-            subscription: Some(session.subscription_label),
-            error: None,
-        }),
-        Err(e) => Ok(LoginResponse {
-            success: false,
-            user_name: None,
-            subscription: None,
-            error: Some(e.to_string()),
-        }),
+        Ok(session) => {
+            warm_cache_after_login(&state, &cache_state, &cache_settings);
+            Ok(LoginResponse {
+                success: true,
+                user_name: Some(session.display_name),
+                subscription: Some(session.subscription_label),
+                subscription_active: session.subscription.active,
+                error: None,
+            })
+        }
+        Err(e) => Ok(LoginResponse::failed(e.to_string())),
+    }
+}
+
+/// Log in with an existing user auth token (no password needed)
+#[tauri::command]
+pub async fn login_with_token(
+    token: String,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    cache_settings: State<'_, CacheSettingsState>,
+) -> Result<LoginResponse, String> {
+    log::info!("Command: login_with_token");
+    let client = state.client.lock().await;
+
+    match client.login_with_token(token.trim()).await {
+        Ok(session) => {
+            warm_cache_after_login(&state, &cache_state, &cache_settings);
+            Ok(LoginResponse {
+                success: true,
+                user_name: Some(session.display_name),
+                subscription: Some(session.subscription_label),
+                subscription_active: session.subscription.active,
+                error: None,
+            })
+        }
+        Err(e) => Ok(LoginResponse::failed(e.to_string())),
     }
 }
 
@@ -52,9 +108,52 @@ pub async fn init_client(state: State<'_, AppState>) -> Result<bool, String> {
     }
 }
 
+/// Force re-extraction of bundle tokens (app_id + secrets), e.g. after Qobuz rotates secrets
+#[tauri::command]
+pub async fn refresh_bundle_tokens(state: State<'_, AppState>) -> Result<bool, String> {
+    log::info!("Command: refresh_bundle_tokens");
+    let client = state.client.lock().await;
+    client
+        .refresh_tokens()
+        .await
+        .map(|_| true)
+        .map_err(|e| format!("Failed to refresh bundle tokens: {}", e))
+}
+
+/// Bundle app_id in use and the candidates it was chosen from
+#[tauri::command]
+pub async fn get_api_diagnostics(state: State<'_, AppState>) -> Result<ApiDiagnostics, String> {
+    let client = state.client.lock().await;
+    Ok(client.api_diagnostics().await)
+}
+
 #[tauri::command]
-pub async fn logout(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn logout(
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<(), String> {
     let client = state.client.lock().await;
+    let user_id = client.user_id().await.ok();
+    end_account_session(&client, &cache_state, user_id).await
+}
+
+/// Drop the account's cached data and end the client session. Shared by
+/// the logout command and sessions Qobuz invalidates, where the client has
+/// already dropped the session and `user_id` comes from the invalidation.
+pub(crate) async fn end_account_session(
+    client: &QobuzClient,
+    cache_state: &ApiCacheState,
+    user_id: Option<u64>,
+) -> Result<(), String> {
+    cache_state.cancel_warming();
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Some(user_id) = user_id {
+            // Favorites may change elsewhere before the next login
+            cache.reset_favorites_sync(user_id)?;
+        }
+        cache.clear_account_data()?;
+    }
     client.logout().await;
     Ok(())
 }
@@ -63,17 +162,54 @@ pub async fn logout(state: State<'_, AppState>) -> Result<(), String> {
 pub struct UserInfo {
     pub user_name: String,
     pub subscription: String,
+    pub subscription_active: bool,
 }
 
 #[tauri::command]
 pub async fn get_user_info(state: State<'_, AppState>) -> Result<Option<UserInfo>, String> {
     let client = state.client.lock().await;
+    let subscription_active = client
+        .get_subscription()
+        .await
+        .map(|s| s.active)
+        .unwrap_or(false);
     Ok(client.get_user_info().await.map(|(name, sub)| UserInfo {
         user_name: name,
         subscription: sub,
+        subscription_active,
     }))
 }
 
+/// What the logged-in account's plan allows (None when logged out)
+#[tauri::command]
+pub async fn get_account_features(state: State<'_, AppState>) -> Result<Option<AccountFeatures>, String> {
+    let client = state.client.lock().await;
+    Ok(client.get_account_features().await)
+}
+
+/// Zone the session is served from and the device it registered, if any
+#[tauri::command]
+pub async fn get_session_zone(state: State<'_, AppState>) -> Result<Option<SessionZone>, String> {
+    let client = state.client.lock().await;
+    Ok(client.session_zone().await)
+}
+
+/// Devices registered on the account, e.g. to free a slot when hitting the
+/// concurrent-stream limit
+#[tauri::command]
+pub async fn get_account_devices(state: State<'_, AppState>) -> Result<Vec<DeviceInfo>, String> {
+    let client = state.client.lock().await;
+    client.get_account_devices().await.map_err(|e| e.to_string())
+}
+
+/// Log another device out of the account
+#[tauri::command]
+pub async fn revoke_device(device_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Command: revoke_device {}", device_id);
+    let client = state.client.lock().await;
+    client.revoke_device(&device_id).await.map_err(|e| e.to_string())
+}
+
 // === Credential persistence commands ===
 
 /// Check if saved credentials exist in system keyring
@@ -97,47 +233,45 @@ pub fn clear_saved_credentials() -> Result<(), String> {
 /// Auto-login using saved credentials
 /// Returns LoginResponse with success status
 #[tauri::command]
-pub async fn auto_login(state: State<'_, AppState>) -> Result<LoginResponse, String> {
+pub async fn auto_login(
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    cache_settings: State<'_, CacheSettingsState>,
+) -> Result<LoginResponse, String> {
+    Ok(login_with_saved_credentials(&state, &cache_state, &cache_settings).await)
+}
+
+/// Log in with the credentials saved in the system keyring
+pub async fn login_with_saved_credentials(
+    state: &AppState,
+    cache_state: &ApiCacheState,
+    cache_settings: &CacheSettingsState,
+) -> LoginResponse {
     // Check for saved credentials
     let creds = match credentials::load_qobuz_credentials() {
         Ok(Some(c)) => c,
-        Ok(None) => {
-            return Ok(LoginResponse {
-                success: false,
-                user_name: None,
-                subscription: None,
-                error: Some("No saved credentials".to_string()),
-            });
-        }
-        Err(e) => {
-            return Ok(LoginResponse {
-                success: false,
-                user_name: None,
-                subscription: None,
-                error: Some(e),
-            });
-        }
+        Ok(None) => return LoginResponse::failed("No saved credentials".to_string()),
+        Err(e) => return LoginResponse::failed(e),
     };
 
     // Try to login with saved credentials
     let client = state.client.lock().await;
     match client.login(&creds.email, &creds.password).await {
-        Ok(session) => Ok(LoginResponse {
-            success: true,
-            user_name: Some(session.display_name),
-            subscription: Some(session.subscription_label),
-            error: None,
-        }),
+        Ok(session) => {
+            warm_cache_after_login(state, cache_state, cache_settings);
+            LoginResponse {
+                success: true,
+                user_name: Some(session.display_name),
+                subscription: Some(session.subscription_label),
+                subscription_active: session.subscription.active,
+                error: None,
+            }
+        }
         Err(e) => {
             // Credentials might be invalid, but don't clear them automatically
             // Let the user decide
             log::warn!("Auto-login failed: {}", e);
-            Ok(LoginResponse {
-                success: false,
-                user_name: None,
-                subscription: None,
-                error: Some(e.to_string()),
-            })
+            LoginResponse::failed(e.to_string())
         }
     }
 }
@@ -1,11 +1,152 @@
 //! Cache management commands
 
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, State};
 
-use crate::api_cache::ApiCacheState;
-use crate::cache::CacheStats;
+use crate::api_cache::{ApiCache, ApiCacheState};
+use crate::cache::{AudioCache, CacheStats};
+use crate::config::cache_settings::CacheSettingsState;
+use crate::config::download_settings::DownloadSettingsState;
+use crate::download_cache::commands::clear_offline_downloads;
+use crate::download_cache::DownloadCacheState;
+use crate::library::commands::LibraryState;
+use crate::library::get_artwork_cache_dir;
+use crate::nostr_cache::{NostrCache, NostrCacheState};
+use crate::storage::{self, StorageMigrationReport};
 use crate::AppState;
 
+/// Which caches `clear_caches` should clear
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ClearOptions {
+    /// In-memory and on-disk playback audio
+    pub audio: bool,
+    /// Nostr profiles, tracks, playlists and relay queries
+    pub nostr: bool,
+    /// Qobuz API responses
+    pub query: bool,
+    /// Downloaded artwork not used by the local library
+    pub images: bool,
+    /// Offline downloads (deletes the downloaded files)
+    pub offline_downloads: bool,
+}
+
+/// What each selected cache freed (None = not selected)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClearedCaches {
+    pub audio_bytes: Option<u64>,
+    pub nostr_rows: Option<usize>,
+    pub query_rows: Option<usize>,
+    pub image_bytes: Option<u64>,
+    pub offline_download_bytes: Option<u64>,
+}
+
+/// Clear the selected in-app caches (everything except offline downloads)
+fn clear_selected(
+    options: &ClearOptions,
+    audio: &AudioCache,
+    nostr: &NostrCache,
+    api: &ApiCache,
+    artwork_dir: &Path,
+    keep_images: &HashSet<String>,
+) -> Result<ClearedCaches, String> {
+    let mut cleared = ClearedCaches::default();
+
+    if options.audio {
+        let disk = audio.clear_disk();
+        cleared.audio_bytes = Some(audio.clear() + disk);
+    }
+    if options.nostr {
+        cleared.nostr_rows = Some(nostr.clear_all()?);
+    }
+    if options.query {
+        cleared.query_rows = Some(api.clear_all()?);
+    }
+    if options.images {
+        cleared.image_bytes = Some(clear_image_dir(artwork_dir, keep_images));
+    }
+
+    Ok(cleared)
+}
+
+/// Kind of cached API entity `refresh_entity` can reload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityType {
+    Album,
+    Artist,
+    Track,
+    Playlist,
+}
+
+/// Payload of the `entity-refreshed` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityRefreshed {
+    pub entity_type: EntityType,
+    pub id: String,
+    pub data: Value,
+}
+
+fn numeric_id(entity_type: EntityType, id: &str) -> Result<u64, String> {
+    id.parse()
+        .map_err(|_| format!("Invalid {:?} id: {}", entity_type, id))
+}
+
+/// Write freshly fetched data over the cached entry of one entity. Only
+/// that entity's rows change; an artist's assembled page is dropped so it
+/// is rebuilt from the new data.
+fn store_refreshed(
+    cache: &ApiCache,
+    entity_type: EntityType,
+    id: &str,
+    locale: &str,
+    data: &Value,
+) -> Result<(), String> {
+    let json = data.to_string();
+    match entity_type {
+        EntityType::Album => cache.set_album(id, &json),
+        EntityType::Artist => {
+            let artist_id = numeric_id(entity_type, id)?;
+            cache.set_artist(artist_id, locale, &json)?;
+            cache.remove_artist_page(artist_id)
+        }
+        EntityType::Track => cache.set_track(numeric_id(entity_type, id)?, &json),
+        EntityType::Playlist => {
+            cache.set_playlist(numeric_id(entity_type, id)?, &json)?;
+            cache.update_user_playlist(data).map(|_| ())
+        }
+    }
+}
+
+fn to_json<T: Serialize>(result: crate::api::error::Result<T>) -> Result<Value, String> {
+    let entity = result.map_err(|e| e.to_string())?;
+    serde_json::to_value(entity).map_err(|e| format!("Failed to serialize entity: {}", e))
+}
+
+/// Delete the files in `dir` that aren't in `keep`, returning the bytes freed
+fn clear_image_dir(dir: &Path, keep: &HashSet<String>) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+
+    let mut freed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || keep.contains(path.to_string_lossy().as_ref()) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if std::fs::remove_file(&path).is_ok() {
+            freed += size;
+        }
+    }
+    freed
+}
+
 /// Get cache statistics
 #[tauri::command]
 pub fn get_cache_stats(state: State<'_, AppState>) -> CacheStats {
@@ -28,3 +169,242 @@ pub async fn clear_artist_cache(cache_state: State<'_, ApiCacheState>) -> Result
     let cache = cache_state.cache.lock().await;
     cache.clear_all_artists()
 }
+
+/// Reload one album, artist, track or playlist from the API regardless of
+/// its cache age and cache the result, leaving every other entry alone.
+/// Emits `entity-refreshed` so open views can show the new data.
+#[tauri::command]
+pub async fn refresh_entity(
+    entity_type: EntityType,
+    id: String,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Value, String> {
+    log::info!("Command: refresh_entity {:?} {}", entity_type, id);
+
+    let (data, locale) = {
+        let client = state.client.lock().await;
+        let data = match entity_type {
+            EntityType::Album => to_json(client.get_album(&id).await)?,
+            EntityType::Artist => to_json(client.get_artist(numeric_id(entity_type, &id)?, true).await)?,
+            EntityType::Track => to_json(client.get_track(numeric_id(entity_type, &id)?).await)?,
+            EntityType::Playlist => to_json(client.get_playlist(numeric_id(entity_type, &id)?).await)?,
+        };
+        (data, client.get_locale().await)
+    };
+
+    {
+        let cache = cache_state.cache.lock().await;
+        store_refreshed(&cache, entity_type, &id, &locale, &data)?;
+    }
+
+    let _ = app_handle.emit(
+        "entity-refreshed",
+        EntityRefreshed { entity_type, id, data: data.clone() },
+    );
+    Ok(data)
+}
+
+/// Clear the selected caches. Clearing offline downloads deletes the
+/// downloaded files, so it also needs `confirm_offline_downloads`.
+#[tauri::command]
+pub async fn clear_caches(
+    options: ClearOptions,
+    confirm_offline_downloads: Option<bool>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    nostr_state: State<'_, NostrCacheState>,
+    download_state: State<'_, DownloadCacheState>,
+    library_state: State<'_, LibraryState>,
+) -> Result<ClearedCaches, String> {
+    log::info!("Command: clear_caches {:?}", options);
+
+    if options.offline_downloads && !confirm_offline_downloads.unwrap_or(false) {
+        return Err("Clearing offline downloads deletes the downloaded files and must be confirmed".to_string());
+    }
+
+    let keep_images = if options.images {
+        let db = library_state.db.lock().await;
+        db.referenced_image_paths().map_err(|e| e.to_string())?
+    } else {
+        HashSet::new()
+    };
+
+    let mut cleared = {
+        let api = cache_state.cache.lock().await;
+        let nostr = nostr_state.cache.lock().await;
+        clear_selected(
+            &options,
+            &state.audio_cache,
+            &nostr,
+            &api,
+            &get_artwork_cache_dir(),
+            &keep_images,
+        )?
+    };
+
+    if options.offline_downloads {
+        cleared.offline_download_bytes =
+            Some(clear_offline_downloads(&download_state, &library_state).await?);
+    }
+
+    log::info!("Cleared caches: {:?}", cleared);
+    Ok(cleared)
+}
+
+/// Move the disk audio cache, offline downloads and artwork to `new_dir`,
+/// updating the stored paths. Emits `storage-migration:progress` events while
+/// copying. The running caches switch over once it's done.
+#[tauri::command]
+pub async fn migrate_storage(
+    new_dir: String,
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    download_state: State<'_, DownloadCacheState>,
+    library_state: State<'_, LibraryState>,
+    cache_settings: State<'_, CacheSettingsState>,
+    download_settings: State<'_, DownloadSettingsState>,
+) -> Result<StorageMigrationReport, String> {
+    log::info!("Command: migrate_storage to {}", new_dir);
+
+    let from = storage::storage_root();
+    let to = PathBuf::from(new_dir);
+    let downloads = download_state.db.clone();
+    let library = library_state.db.clone();
+    let cache_settings = cache_settings.inner().clone();
+
+    let report = tokio::task::spawn_blocking(move || {
+        storage::migrate_storage(
+            &from,
+            &to,
+            &downloads,
+            &library,
+            |root| {
+                let store = cache_settings.lock().map_err(|e| format!("Lock error: {}", e))?;
+                store.set_storage_dir(Some(&root.to_string_lossy()))
+            },
+            |progress| {
+                let _ = app_handle.emit("storage-migration:progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Storage migration task failed: {}", e))??;
+
+    // Point the running caches at the new location
+    let root = Path::new(&report.to);
+    storage::set_storage_root(root);
+    download_state.set_cache_dir(root.join(storage::DOWNLOADS_DIR));
+    if let Some(playback_cache) = app_state.audio_cache.get_playback_cache() {
+        playback_cache.set_dir(root.join(storage::PLAYBACK_DIR));
+    }
+
+    let downloads_dir = root.join(storage::DOWNLOADS_DIR);
+    if let Ok(store) = download_settings.lock() {
+        if let Err(e) = store.set_download_root(&downloads_dir.to_string_lossy()) {
+            log::warn!("Failed to update download root: {}", e);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_cache::CachedQuery;
+
+    fn stores() -> (AudioCache, NostrCache, ApiCache) {
+        let audio = AudioCache::new(1024 * 1024);
+        audio.insert(1, vec![0u8; 1000]);
+        audio.insert(2, vec![0u8; 500]);
+
+        let nostr = NostrCache::new(Path::new(":memory:")).unwrap();
+        nostr
+            .set_query(&CachedQuery {
+                query_key: "relay-query".to_string(),
+                result_ids: "[]".to_string(),
+                fetched_at: 0,
+                expires_at: i64::MAX,
+            })
+            .unwrap();
+
+        let api = ApiCache::new(Path::new(":memory:")).unwrap();
+        api.set_album("a1", "{}").unwrap();
+        api.set_album("a2", "{}").unwrap();
+        api.set_track(7, "{}").unwrap();
+
+        (audio, nostr, api)
+    }
+
+    #[test]
+    fn test_each_option_clears_only_its_target() {
+        let dir = std::env::temp_dir().join(format!("qbz-clear-caches-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("library.jpg");
+        std::fs::write(&kept, vec![0u8; 300]).unwrap();
+        std::fs::write(dir.join("notification.jpg"), vec![0u8; 200]).unwrap();
+        let keep: HashSet<String> = [kept.to_string_lossy().to_string()].into();
+
+        let (audio, nostr, api) = stores();
+        let only_audio = ClearOptions { audio: true, ..Default::default() };
+        let cleared = clear_selected(&only_audio, &audio, &nostr, &api, &dir, &keep).unwrap();
+        assert_eq!(cleared, ClearedCaches { audio_bytes: Some(1500), ..Default::default() });
+        assert_eq!(audio.stats().current_size_bytes, 0);
+        assert!(api.get_album("a1", None).unwrap().is_some());
+        assert_eq!(nostr.get_stats().unwrap().query_count, 1);
+
+        let (audio, nostr, api) = stores();
+        let only_query = ClearOptions { query: true, ..Default::default() };
+        let cleared = clear_selected(&only_query, &audio, &nostr, &api, &dir, &keep).unwrap();
+        assert_eq!(cleared, ClearedCaches { query_rows: Some(3), ..Default::default() });
+        assert!(api.get_album("a1", None).unwrap().is_none());
+        assert_eq!(audio.stats().current_size_bytes, 1500);
+
+        let only_nostr = ClearOptions { nostr: true, ..Default::default() };
+        let cleared = clear_selected(&only_nostr, &audio, &nostr, &api, &dir, &keep).unwrap();
+        assert_eq!(cleared, ClearedCaches { nostr_rows: Some(1), ..Default::default() });
+        assert_eq!(audio.stats().current_size_bytes, 1500);
+
+        let only_images = ClearOptions { images: true, ..Default::default() };
+        let cleared = clear_selected(&only_images, &audio, &nostr, &api, &dir, &keep).unwrap();
+        assert_eq!(cleared, ClearedCaches { image_bytes: Some(200), ..Default::default() });
+        assert!(kept.exists());
+        assert!(!dir.join("notification.jpg").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_replaces_only_that_entity() {
+        let (_, _, api) = stores();
+        api.set_artist(9, "en", r#"{"id":9,"name":"Old"}"#).unwrap();
+        api.set_artist_page(9, "en", "{}").unwrap();
+        api.set_artist_page(10, "en", "{}").unwrap();
+        api.set_user_playlists(r#"[{"id":1,"name":"Old"},{"id":2,"name":"Other"}]"#).unwrap();
+
+        let album = serde_json::json!({ "id": "a1", "title": "Remastered" });
+        store_refreshed(&api, EntityType::Album, "a1", "en", &album).unwrap();
+        let cached: Value = serde_json::from_str(&api.get_album("a1", None).unwrap().unwrap()).unwrap();
+        assert_eq!(cached, album);
+        assert_eq!(api.get_album("a2", None).unwrap().as_deref(), Some("{}"));
+        assert_eq!(api.get_track(7, None).unwrap().as_deref(), Some("{}"));
+
+        let artist = serde_json::json!({ "id": 9, "name": "New" });
+        store_refreshed(&api, EntityType::Artist, "9", "en", &artist).unwrap();
+        assert!(api.get_artist(9, "en", None).unwrap().unwrap().contains("New"));
+        assert!(api.get_artist_page(9, "en", None).unwrap().is_none());
+        assert!(api.get_artist_page(10, "en", None).unwrap().is_some());
+
+        let playlist = serde_json::json!({ "id": 1, "name": "Renamed", "tracks": { "items": [] } });
+        store_refreshed(&api, EntityType::Playlist, "1", "en", &playlist).unwrap();
+        let playlists: Value = serde_json::from_str(&api.get_user_playlists(None).unwrap().unwrap()).unwrap();
+        assert_eq!(
+            playlists,
+            serde_json::json!([{ "id": 1, "name": "Renamed" }, { "id": 2, "name": "Other" }])
+        );
+
+        assert!(store_refreshed(&api, EntityType::Track, "abc", "en", &Value::Null).is_err());
+    }
+}
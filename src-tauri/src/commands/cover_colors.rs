@@ -0,0 +1,48 @@
+//! Cover art color commands
+
+use std::collections::HashMap;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::commands::notification::cache_artwork;
+use crate::cover_colors::{palette_from_bytes, CoverPalette};
+
+/// Colors returned when the caller doesn't ask for a count
+const DEFAULT_COLOR_COUNT: usize = 5;
+
+/// Upper bound on requested colors
+const MAX_COLOR_COUNT: usize = 16;
+
+/// Extracted palettes, keyed by image URL and color count
+#[derive(Default)]
+pub struct CoverColorsState {
+    palettes: Mutex<HashMap<(String, usize), CoverPalette>>,
+}
+
+/// Dominant colors of a cover plus a contrasting text/background pair
+#[tauri::command]
+pub async fn extract_dominant_colors(
+    image_url: String,
+    count: Option<usize>,
+    colors_state: State<'_, CoverColorsState>,
+) -> Result<CoverPalette, String> {
+    let count = count.unwrap_or(DEFAULT_COLOR_COUNT).clamp(1, MAX_COLOR_COUNT);
+    let key = (image_url, count);
+    if let Some(palette) = colors_state.palettes.lock().await.get(&key) {
+        return Ok(palette.clone());
+    }
+
+    log::info!("Command: extract_dominant_colors {}", key.0);
+    let url = key.0.clone();
+    // Download (through the artwork cache) and decode off the async runtime
+    let palette = tokio::task::spawn_blocking(move || {
+        let path = cache_artwork(&url)?;
+        let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read cover: {}", e))?;
+        palette_from_bytes(&bytes, count)
+    })
+    .await
+    .map_err(|e| format!("Cover color task failed: {}", e))??;
+
+    colors_state.palettes.lock().await.insert(key, palette.clone());
+    Ok(palette)
+}
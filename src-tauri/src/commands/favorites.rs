@@ -1,26 +1,84 @@
 //! Favorites-related Tauri commands
 
+use std::collections::HashMap;
+
+use serde::Serialize;
 use serde_json::Value;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 
+use crate::api::{AlbumTracksFavorited, FavoritesSort};
+use crate::api_cache::duplicates::{self, DuplicateGroup};
+use crate::api_cache::filter::FavoriteFilter;
+use crate::api_cache::sync::{self, FavoritesDelta, FAVORITE_TYPES};
+use crate::api_cache::{favorites_plural, ApiCacheState};
 use crate::AppState;
 
+/// Payload of the `favorites-changed` event, emitted after a confirmed add/remove
+#[derive(Debug, Clone, Serialize)]
+pub struct FavoritesChangedEvent {
+    pub fav_type: String,
+    pub item_id: String,
+    pub added: bool,
+}
+
+/// Update the favorites cache and notify every open view of a confirmed change
+async fn publish_favorite_change(
+    app_handle: &AppHandle,
+    cache_state: &ApiCacheState,
+    event: FavoritesChangedEvent,
+) {
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Err(e) = cache.apply_favorite_change(&event.fav_type, &event.item_id, event.added) {
+            log::warn!("Failed to update favorites cache: {}", e);
+        }
+    }
+    let _ = app_handle.emit("favorites-changed", &event);
+}
+
 /// Get user's favorites
 /// fav_type can be: "albums", "tracks", or "artists"
+/// sort can be: "date_added_desc" (default), "date_added_asc", "artist", or "title"
 #[tauri::command]
 pub async fn get_favorites(
     fav_type: String,
     limit: Option<u32>,
     offset: Option<u32>,
+    sort: Option<FavoritesSort>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<Value, String> {
-    log::info!("Command: get_favorites type={} limit={:?} offset={:?}", fav_type, limit, offset);
+    log::info!(
+        "Command: get_favorites type={} limit={:?} offset={:?} sort={:?}",
+        fav_type, limit, offset, sort
+    );
+
+    let fav_type = favorites_plural(&fav_type);
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    let sort = sort.unwrap_or_default();
+
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Some(cached_data) = cache.get_favorites(&fav_type, sort.key(), limit, offset, None)? {
+            log::debug!("Cache hit for favorites {} (offset {})", fav_type, offset);
+            return serde_json::from_str(&cached_data)
+                .map_err(|e| format!("Failed to parse cached favorites: {}", e));
+        }
+    }
 
     let client = state.client.lock().await;
-    client
-        .get_favorites(&fav_type, limit.unwrap_or(50), offset.unwrap_or(0))
+    let favorites = client
+        .get_favorites_sorted(&fav_type, limit, offset, sort)
         .await
-        .map_err(|e| format!("Failed to get favorites: {}", e))
+        .map_err(|e| format!("Failed to get favorites: {}", e))?;
+
+    {
+        let cache = cache_state.cache.lock().await;
+        cache.set_favorites(&fav_type, sort.key(), limit, offset, &favorites.to_string())?;
+    }
+
+    Ok(favorites)
 }
 
 /// Add item to favorites
@@ -30,14 +88,60 @@ pub async fn add_favorite(
     fav_type: String,
     item_id: String,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
     log::info!("Command: add_favorite type={} id={}", fav_type, item_id);
 
-    let client = state.client.lock().await;
-    client
-        .add_favorite(&fav_type, &item_id)
-        .await
-        .map_err(|e| format!("Failed to add favorite: {}", e))
+    {
+        let client = state.client.lock().await;
+        client
+            .add_favorite(&fav_type, &item_id)
+            .await
+            .map_err(|e| format!("Failed to add favorite: {}", e))?;
+    }
+
+    publish_favorite_change(
+        &app_handle,
+        &cache_state,
+        FavoritesChangedEvent { fav_type, item_id, added: true },
+    )
+    .await;
+    Ok(())
+}
+
+/// Favorite every track on an album in one batched request.
+/// Tracks already favorited or unavailable in the user's region are skipped.
+#[tauri::command]
+pub async fn favorite_album_tracks(
+    album_id: String,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
+) -> Result<AlbumTracksFavorited, String> {
+    log::info!("Command: favorite_album_tracks album={}", album_id);
+
+    let result = {
+        let client = state.client.lock().await;
+        client
+            .favorite_album_tracks(&album_id)
+            .await
+            .map_err(|e| format!("Failed to favorite album tracks: {}", e))?
+    };
+
+    for track_id in &result.added {
+        publish_favorite_change(
+            &app_handle,
+            &cache_state,
+            FavoritesChangedEvent {
+                fav_type: "track".to_string(),
+                item_id: track_id.to_string(),
+                added: true,
+            },
+        )
+        .await;
+    }
+    Ok(result)
 }
 
 /// Remove item from favorites
@@ -47,12 +151,195 @@ pub async fn remove_favorite(
     fav_type: String,
     item_id: String,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
     log::info!("Command: remove_favorite type={} id={}", fav_type, item_id);
 
+    {
+        let client = state.client.lock().await;
+        client
+            .remove_favorite(&fav_type, &item_id)
+            .await
+            .map_err(|e| format!("Failed to remove favorite: {}", e))?;
+    }
+
+    publish_favorite_change(
+        &app_handle,
+        &cache_state,
+        FavoritesChangedEvent { fav_type, item_id, added: false },
+    )
+    .await;
+    Ok(())
+}
+
+/// Favorite status of several items of one type at once, for marking
+/// search results. Answered locally once the type's ids are known; changes
+/// made through add/remove_favorite are reflected immediately.
+#[tauri::command]
+pub async fn get_favorites_status(
+    fav_type: String,
+    item_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, bool>, String> {
+    log::info!("Command: get_favorites_status type={} ({} ids)", fav_type, item_ids.len());
+
     let client = state.client.lock().await;
     client
-        .remove_favorite(&fav_type, &item_id)
+        .favorites_status(&fav_type, &item_ids)
         .await
-        .map_err(|e| format!("Failed to remove favorite: {}", e))
+        .map_err(|e| format!("Failed to get favorites status: {}", e))
+}
+
+/// Sync favorites into the local copy, fetching only what changed since the
+/// last sync where possible. Syncs every type when fav_type is omitted.
+/// Emits `favorites-synced` for each type that changed.
+#[tauri::command]
+pub async fn sync_favorites(
+    fav_type: Option<String>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
+) -> Result<Vec<FavoritesDelta>, String> {
+    log::info!("Command: sync_favorites type={:?}", fav_type);
+
+    let fav_types: Vec<String> = match fav_type {
+        Some(fav_type) => vec![favorites_plural(&fav_type)],
+        None => FAVORITE_TYPES.iter().map(|t| t.to_string()).collect(),
+    };
+
+    let client = state.client.lock().await;
+    let user_id = client.user_id().await.map_err(|e| e.to_string())?;
+    let mut deltas = Vec::new();
+    for fav_type in fav_types {
+        let delta = sync::sync_favorites(&client, &cache_state.cache, &fav_type).await?;
+        // The synced copy is complete, so favorite status checks can use it
+        let synced_ids = cache_state.cache.lock().await.synced_favorite_ids(user_id, &fav_type)?;
+        client.seed_favorite_ids(&fav_type, synced_ids).await;
+        if !delta.is_empty() {
+            let _ = app_handle.emit("favorites-synced", &delta);
+        }
+        deltas.push(delta);
+    }
+    Ok(deltas)
+}
+
+/// Id of the logged-in user, whose synced favorites are read
+pub(crate) async fn current_user_id(state: &AppState) -> Result<u64, String> {
+    let client = state.client.lock().await;
+    client.user_id().await.map_err(|e| e.to_string())
+}
+
+/// Get the locally synced favorites of one type (see `sync_favorites`)
+#[tauri::command]
+pub async fn get_synced_favorites(
+    fav_type: String,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Vec<Value>, String> {
+    log::info!("Command: get_synced_favorites type={}", fav_type);
+    let user_id = current_user_id(&state).await?;
+    let cache = cache_state.cache.lock().await;
+    cache.synced_favorites(user_id, &favorites_plural(&fav_type))
+}
+
+/// Filter the locally synced favorites by genre, release years and quality
+#[tauri::command]
+pub async fn filter_favorites(
+    fav_type: String,
+    filter: FavoriteFilter,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Vec<Value>, String> {
+    log::info!("Command: filter_favorites type={} {:?}", fav_type, filter);
+    let user_id = current_user_id(&state).await?;
+    let cache = cache_state.cache.lock().await;
+    cache.filter_favorites(user_id, &favorites_plural(&fav_type), &filter)
+}
+
+/// Favorite tracks holding the same recording, from the synced copy (run
+/// `sync_favorites` first for an up-to-date answer)
+#[tauri::command]
+pub async fn find_duplicate_favorites(
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    log::info!("Command: find_duplicate_favorites");
+    let user_id = current_user_id(&state).await?;
+    let cache = cache_state.cache.lock().await;
+    Ok(duplicates::find_duplicates(&cache.synced_favorites(user_id, "tracks")?))
+}
+
+/// Unfavorite the duplicates of a group found by `find_duplicate_favorites`,
+/// keeping its canonical track. Only tracks the synced favorites still pair
+/// with the canonical one are removed; returns their ids.
+#[tauri::command]
+pub async fn merge_favorite_duplicates(
+    group: DuplicateGroup,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
+) -> Result<Vec<u64>, String> {
+    log::info!(
+        "Command: merge_favorite_duplicates canonical={} ({} duplicates)",
+        group.canonical.id,
+        group.duplicates.len()
+    );
+
+    let user_id = current_user_id(&state).await?;
+    let to_remove = {
+        let cache = cache_state.cache.lock().await;
+        let current = duplicates::find_duplicates(&cache.synced_favorites(user_id, "tracks")?);
+        duplicates::merge_plan(&group, &current)
+    };
+    if to_remove.len() < group.duplicates.len() {
+        log::info!("Keeping {} tracks no longer confirmed as duplicates", group.duplicates.len() - to_remove.len());
+    }
+
+    let mut removed = Vec::new();
+    for track_id in to_remove {
+        let result = {
+            let client = state.client.lock().await;
+            client.remove_favorite("track", &track_id.to_string()).await
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to unfavorite duplicate {}: {}", track_id, e);
+            continue;
+        }
+        publish_favorite_change(
+            &app_handle,
+            &cache_state,
+            FavoritesChangedEvent { fav_type: "track".to_string(), item_id: track_id.to_string(), added: false },
+        )
+        .await;
+        removed.push(track_id);
+    }
+
+    // Drop them from the synced copy too, keeping its sync point
+    if !removed.is_empty() {
+        let mut cache = cache_state.cache.lock().await;
+        if let Some(synced_at) = cache.favorites_last_synced(user_id, "tracks")? {
+            let ids: Vec<String> = removed.iter().map(|id| id.to_string()).collect();
+            cache.apply_favorites_delta(user_id, "tracks", &[], &ids, false, synced_at)?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_favorites_changed_payload() {
+        let event = FavoritesChangedEvent {
+            fav_type: "track".to_string(),
+            item_id: "12345".to_string(),
+            added: true,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "fav_type": "track", "item_id": "12345", "added": true })
+        );
+    }
 }
@@ -0,0 +1,153 @@
+//! Library stats dashboard command
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::api_cache::{ApiCache, ApiCacheState};
+use crate::commands::favorites::current_user_id;
+use crate::download_cache::DownloadCacheState;
+use crate::reco_store::db::RecoStoreDb;
+use crate::reco_store::{ListeningStats, RecoState};
+use crate::AppState;
+
+/// Most played artists/tracks listed by default
+const DEFAULT_TOP_LIMIT: u32 = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FavoriteCounts {
+    pub tracks: u64,
+    pub albums: u64,
+    pub artists: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStats {
+    /// In-memory audio cache
+    pub memory_cache_bytes: u64,
+    /// On-disk playback cache
+    pub playback_cache_bytes: u64,
+    pub offline_tracks: u64,
+    pub offline_bytes: u64,
+}
+
+/// Listening footprint for the stats dashboard
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryStats {
+    /// Counted from the synced favorites (zero before the first sync)
+    pub favorites: FavoriteCounts,
+    pub listening: ListeningStats,
+    pub storage: StorageStats,
+}
+
+/// Favorites of `user_id` (none when logged out) and play history
+/// aggregates; storage is filled in by the caller
+fn collect_stats(
+    reco: &RecoStoreDb,
+    api: &ApiCache,
+    user_id: Option<u64>,
+    top_limit: u32,
+) -> Result<LibraryStats, String> {
+    let favorites = match user_id {
+        Some(user_id) => FavoriteCounts {
+            tracks: api.synced_favorite_count(user_id, "tracks")?,
+            albums: api.synced_favorite_count(user_id, "albums")?,
+            artists: api.synced_favorite_count(user_id, "artists")?,
+        },
+        None => FavoriteCounts::default(),
+    };
+    Ok(LibraryStats {
+        favorites,
+        listening: reco.get_listening_stats(top_limit)?,
+        storage: StorageStats::default(),
+    })
+}
+
+/// Favorite counts, play history totals, most played artists/tracks and
+/// cache/offline storage use
+#[tauri::command]
+pub async fn get_library_stats(
+    top_limit: Option<u32>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    reco_state: State<'_, RecoState>,
+    download_state: State<'_, DownloadCacheState>,
+) -> Result<LibraryStats, String> {
+    log::info!("Command: get_library_stats");
+
+    let user_id = current_user_id(&state).await.ok();
+    let mut stats = {
+        let reco = reco_state.db.lock().await;
+        let api = cache_state.cache.lock().await;
+        collect_stats(&reco, &api, user_id, top_limit.unwrap_or(DEFAULT_TOP_LIMIT))?
+    };
+
+    stats.storage.memory_cache_bytes = state.audio_cache.stats().current_size_bytes as u64;
+    stats.storage.playback_cache_bytes = state
+        .audio_cache
+        .get_playback_cache()
+        .map(|cache| cache.stats().current_size_bytes)
+        .unwrap_or(0);
+
+    let limit = *download_state.limit_bytes.lock().await;
+    let offline = download_state
+        .db
+        .lock()
+        .await
+        .get_stats(&download_state.get_cache_path(), limit)?;
+    stats.storage.offline_tracks = offline.ready_tracks as u64;
+    stats.storage.offline_bytes = offline.total_size_bytes;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reco_store::{RecoEventInput, RecoEventType, RecoItemType, TopArtistSeed, TopTrackSeed};
+    use std::path::Path;
+
+    fn play(track_id: u64, artist_id: u64, duration_secs: Option<u64>) -> RecoEventInput {
+        RecoEventInput {
+            event_type: RecoEventType::Play,
+            item_type: RecoItemType::Track,
+            track_id: Some(track_id),
+            album_id: None,
+            artist_id: Some(artist_id),
+            playlist_id: None,
+            duration_secs,
+            played: None,
+        }
+    }
+
+    #[test]
+    fn test_stats_aggregate_history_and_favorites() {
+        let reco = RecoStoreDb::new(Path::new(":memory:")).unwrap();
+        let mut api = ApiCache::new(Path::new(":memory:")).unwrap();
+
+        // Nothing recorded yet
+        assert_eq!(collect_stats(&reco, &api, Some(1), 5).unwrap(), LibraryStats::default());
+
+        for event in [play(1, 10, Some(200)), play(1, 10, None), play(2, 10, None), play(3, 20, Some(240))] {
+            reco.insert_event(&event).unwrap();
+        }
+        // The listen of the second play of track 1 ends
+        reco.set_play_duration(1, 180).unwrap();
+        // Favorite events aren't plays
+        reco.insert_event(&RecoEventInput { event_type: RecoEventType::Favorite, ..play(4, 30, None) })
+            .unwrap();
+
+        let tracks = [serde_json::json!({ "id": 1 }), serde_json::json!({ "id": 2 })];
+        api.apply_favorites_delta(1, "tracks", &tracks, &[], true, 1).unwrap();
+        api.apply_favorites_delta(1, "albums", &[serde_json::json!({ "id": "a" })], &[], true, 1).unwrap();
+
+        let stats = collect_stats(&reco, &api, Some(1), 1).unwrap();
+        assert_eq!(stats.favorites, FavoriteCounts { tracks: 2, albums: 1, artists: 0 });
+        assert_eq!(stats.listening.play_count, 4);
+        assert_eq!(stats.listening.listening_secs, 620);
+        assert_eq!(stats.listening.top_artists, vec![TopArtistSeed { artist_id: 10, play_count: 3 }]);
+        assert_eq!(stats.listening.top_tracks, vec![TopTrackSeed { track_id: 1, play_count: 2 }]);
+    }
+}
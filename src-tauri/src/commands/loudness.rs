@@ -0,0 +1,295 @@
+//! Loudness analysis commands
+
+use std::collections::HashMap;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::api::models::Quality;
+use crate::audio::loudness::{
+    self, AlbumGain, AlbumLoudnessReport, LoudnessMeter, LoudnessReport, NormalizationMode,
+};
+use crate::player::download_audio;
+use crate::download_cache::DownloadCacheState;
+use crate::player::{DecodedChunks, Player};
+use crate::AppState;
+
+/// Measured reports, keyed by track id / album id
+#[derive(Default)]
+pub struct LoudnessState {
+    tracks: Mutex<HashMap<u64, LoudnessReport>>,
+    albums: Mutex<HashMap<String, AlbumLoudnessReport>>,
+    /// Album gains for `NormalizationMode::Album`, keyed by album id
+    album_gains: Mutex<HashMap<String, AlbumGain>>,
+}
+
+impl LoudnessState {
+    /// Measured gain for a track under `mode`. In album mode the gain of
+    /// `album_id` (the album the track is played from) is used; without one,
+    /// a track on several measured albums takes the one with the lowest id.
+    /// Album mode falls back to the track's own gain when no album of it
+    /// has been measured.
+    pub async fn replaygain_db(&self, track_id: u64, album_id: Option<&str>, mode: NormalizationMode) -> Option<f32> {
+        if mode == NormalizationMode::Off {
+            return None;
+        }
+        if mode == NormalizationMode::Album {
+            let album_gains = self.album_gains.lock().await;
+            let album_gain = match album_id {
+                Some(album_id) => album_gains.get(album_id).and_then(|album| album.gain_for_track(track_id)),
+                None => album_gains
+                    .iter()
+                    .filter_map(|(id, album)| Some((id, album.gain_for_track(track_id)?)))
+                    .min_by(|a, b| a.0.cmp(b.0))
+                    .map(|(_, gain)| gain),
+            };
+            if album_gain.is_some() {
+                return album_gain;
+            }
+        }
+        self.tracks.lock().await.get(&track_id).map(loudness::track_gain_db)
+    }
+}
+
+/// Set the player's normalization gain for the track about to play, from
+/// `album_id` when it's played as part of an album
+pub(crate) async fn apply_normalization(
+    player: &Player,
+    loudness_state: &LoudnessState,
+    track_id: u64,
+    album_id: Option<&str>,
+) {
+    let mode = player.normalization_mode();
+    player.set_replaygain_db(loudness_state.replaygain_db(track_id, album_id, mode).await);
+}
+
+/// Complete encoded file for a track, if one is available locally.
+/// Only finished downloads are used, never partial ones.
+pub(crate) async fn cached_track_data(
+    track_id: u64,
+    state: &AppState,
+    download_cache: &DownloadCacheState,
+) -> Result<Option<Vec<u8>>, String> {
+    // get_file_path only returns downloads with status 'ready'
+    let file_path = download_cache.db.lock().await.get_file_path(track_id)?;
+    if let Some(file_path) = file_path {
+        if let Ok(data) = std::fs::read(&file_path) {
+            return Ok(Some(data));
+        }
+    }
+
+    if let Some(cached) = state.audio_cache.get(track_id) {
+        return Ok(Some(cached.data));
+    }
+    Ok(state
+        .audio_cache
+        .get_playback_cache()
+        .and_then(|cache| cache.get(track_id)))
+}
+
+/// Get the complete encoded file for a track, fetching it in full if
+/// it isn't available locally
+async fn load_full_track(
+    track_id: u64,
+    state: &AppState,
+    download_cache: &DownloadCacheState,
+) -> Result<Vec<u8>, String> {
+    if let Some(data) = cached_track_data(track_id, state, download_cache).await? {
+        return Ok(data);
+    }
+
+    let stream_url = {
+        let client = state.client.lock().await;
+        client
+            .get_stream_url_with_fallback(track_id, Quality::UltraHiRes)
+            .await
+            .map_err(|e| format!("Failed to get stream URL: {}", e))?
+    };
+    download_audio(&stream_url.url).await
+}
+
+/// Decode and meter a track (CPU heavy; run on a blocking task)
+fn analyze(data: &[u8]) -> Result<LoudnessMeter, String> {
+    let mut decoded = DecodedChunks::new(data)?;
+    let mut meter = LoudnessMeter::new(decoded.channels(), decoded.sample_rate())?;
+    while let Some(chunk) = decoded.next_chunk() {
+        meter.add_samples(chunk)?;
+    }
+    Ok(meter)
+}
+
+async fn analyze_track(
+    track_id: u64,
+    state: &AppState,
+    download_cache: &DownloadCacheState,
+) -> Result<LoudnessMeter, String> {
+    let data = load_full_track(track_id, state, download_cache).await?;
+    tokio::task::spawn_blocking(move || analyze(&data))
+        .await
+        .map_err(|e| format!("Loudness task failed: {}", e))?
+}
+
+/// Measure EBU R128 integrated loudness, true peak and loudness range of a track
+#[tauri::command]
+pub async fn measure_loudness(
+    track_id: u64,
+    state: State<'_, AppState>,
+    download_cache: State<'_, DownloadCacheState>,
+    loudness_state: State<'_, LoudnessState>,
+) -> Result<LoudnessReport, String> {
+    if let Some(report) = loudness_state.tracks.lock().await.get(&track_id) {
+        return Ok(*report);
+    }
+
+    log::info!("Command: measure_loudness {}", track_id);
+    let report = analyze_track(track_id, &state, &download_cache).await?.report()?;
+
+    loudness_state.tracks.lock().await.insert(track_id, report);
+    Ok(report)
+}
+
+/// Measure every track of an album plus album-wide loudness and gain
+#[tauri::command]
+pub async fn measure_album_loudness(
+    album_id: String,
+    state: State<'_, AppState>,
+    download_cache: State<'_, DownloadCacheState>,
+    loudness_state: State<'_, LoudnessState>,
+) -> Result<AlbumLoudnessReport, String> {
+    if let Some(report) = loudness_state.albums.lock().await.get(&album_id) {
+        return Ok(report.clone());
+    }
+
+    log::info!("Command: measure_album_loudness {}", album_id);
+    let album = {
+        let client = state.client.lock().await;
+        client.get_album(&album_id).await.map_err(|e| e.to_string())?
+    };
+    let track_ids: Vec<u64> = album
+        .tracks
+        .map(|tracks| tracks.items.iter().map(|t| t.id).collect())
+        .unwrap_or_default();
+
+    let mut tracks = Vec::with_capacity(track_ids.len());
+    for track_id in track_ids {
+        tracks.push((track_id, analyze_track(track_id, &state, &download_cache).await?));
+    }
+    let report = loudness::measure_album(&tracks)?;
+
+    {
+        let mut cached_tracks = loudness_state.tracks.lock().await;
+        for (track_id, track_report) in &report.tracks {
+            cached_tracks.insert(*track_id, *track_report);
+        }
+    }
+    loudness_state.albums.lock().await.insert(album_id, report.clone());
+    Ok(report)
+}
+
+/// Measure an album as a whole and cache its gain for album-mode
+/// normalization. Only complete local copies are used (downloads, cached
+/// audio); when any track isn't available the missing ids are reported
+/// instead of measuring a partial album.
+#[tauri::command]
+pub async fn compute_album_gain(
+    album_id: String,
+    state: State<'_, AppState>,
+    download_cache: State<'_, DownloadCacheState>,
+    loudness_state: State<'_, LoudnessState>,
+) -> Result<AlbumGain, String> {
+    if let Some(gain) = loudness_state.album_gains.lock().await.get(&album_id) {
+        return Ok(gain.clone());
+    }
+
+    log::info!("Command: compute_album_gain {}", album_id);
+    let album = {
+        let client = state.client.lock().await;
+        client.get_album_all_tracks(&album_id).await.map_err(|e| e.to_string())?
+    };
+    let track_ids: Vec<u64> = album
+        .tracks
+        .map(|tracks| tracks.items.iter().map(|t| t.id).collect())
+        .unwrap_or_default();
+
+    let mut available = Vec::with_capacity(track_ids.len());
+    let mut missing = Vec::new();
+    for track_id in &track_ids {
+        match cached_track_data(*track_id, &state, &download_cache).await? {
+            Some(data) => available.push((*track_id, data)),
+            None => missing.push(track_id.to_string()),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!(
+            "{} of {} tracks are not available locally: {}",
+            missing.len(),
+            track_ids.len(),
+            missing.join(", ")
+        ));
+    }
+
+    let report = tokio::task::spawn_blocking(move || {
+        let tracks = available
+            .into_iter()
+            .map(|(track_id, data)| analyze(&data).map(|meter| (track_id, meter)))
+            .collect::<Result<Vec<_>, String>>()?;
+        loudness::measure_album(&tracks)
+    })
+    .await
+    .map_err(|e| format!("Loudness task failed: {}", e))??;
+
+    let gain = AlbumGain::from_report(album_id.clone(), &report);
+    {
+        let mut cached_tracks = loudness_state.tracks.lock().await;
+        for (track_id, track_report) in &report.tracks {
+            cached_tracks.insert(*track_id, *track_report);
+        }
+    }
+    loudness_state.albums.lock().await.insert(album_id.clone(), report);
+    loudness_state.album_gains.lock().await.insert(album_id, gain.clone());
+    Ok(gain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::audio_settings::AudioSettings;
+
+    fn album_gain(album_id: &str, gain_db: f64, track_ids: &[u64]) -> AlbumGain {
+        AlbumGain {
+            album_id: album_id.to_string(),
+            integrated_lufs: -18.0 - gain_db,
+            gain_db,
+            track_peaks: track_ids.iter().map(|id| (*id, -10.0)).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_album_gain_follows_the_album_the_track_is_played_from() {
+        let loudness_state = LoudnessState::default();
+        {
+            let mut album_gains = loudness_state.album_gains.lock().await;
+            // Track 7 is on both albums
+            album_gains.insert("b".to_string(), album_gain("b", -5.0, &[7, 8]));
+            album_gains.insert("a".to_string(), album_gain("a", -3.0, &[7, 9]));
+        }
+        let player = Player::detached();
+        player
+            .reload_settings(AudioSettings { normalization: NormalizationMode::Album, ..AudioSettings::default() })
+            .unwrap();
+
+        apply_normalization(&player, &loudness_state, 7, Some("b")).await;
+        assert_eq!(player.replaygain_db(), -5.0);
+        apply_normalization(&player, &loudness_state, 7, Some("a")).await;
+        assert_eq!(player.replaygain_db(), -3.0);
+
+        // Played on its own, always the same album
+        for _ in 0..3 {
+            apply_normalization(&player, &loudness_state, 7, None).await;
+            assert_eq!(player.replaygain_db(), -3.0);
+        }
+
+        // A track without any measurement plays unnormalized
+        apply_normalization(&player, &loudness_state, 42, None).await;
+        assert_eq!(player.replaygain_db(), 0.0);
+    }
+}
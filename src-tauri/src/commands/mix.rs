@@ -0,0 +1,373 @@
+//! "My Mix": a personalized queue seeded by favorites
+//!
+//! Favorite tracks are interleaved with discoveries taken from the radio of
+//! favorite artists (their top tracks and those of similar artists).
+//! Recently played tracks are left out so a mix doesn't replay the last
+//! sessions. The same seed gives the same mix for the same favorites.
+
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::api::models::{Artist, Track};
+use crate::api::QobuzClient;
+use crate::commands::playback::play_current_track;
+use crate::commands::queue::{artist_radio_tracks, queue_track};
+use crate::queue::{QueueSource, QueueSourceKind, QueueTrack};
+use crate::reco_store::RecoState;
+use crate::AppState;
+
+const DEFAULT_MIX_LENGTH: usize = 50;
+/// Longest mix built
+pub const MAX_MIX_LENGTH: usize = 200;
+/// Share of favorites in a mix by default, the rest being discoveries
+const DEFAULT_FAVORITES_RATIO: f32 = 0.3;
+/// Page size when fetching favorite tracks (API maximum)
+const MIX_FAVORITES_PAGE_LIMIT: u32 = 500;
+/// Favorite artists considered as radio seeds
+const MIX_FAVORITE_ARTISTS: u32 = 50;
+/// Radio seeds used per mix
+const MIX_SEED_ARTISTS: usize = 4;
+/// Recently played tracks kept out of a mix
+const MIX_RECENT_EXCLUDED: u32 = 100;
+
+const MIX_NAME: &str = "My Mix";
+
+/// How a mix is built
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MixOptions {
+    pub length: usize,
+    /// 0.0 = only discoveries, 1.0 = only favorites
+    pub favorites_ratio: f32,
+    pub seed: u64,
+}
+
+impl MixOptions {
+    /// Defaults for missing values; the length is capped at [`MAX_MIX_LENGTH`]
+    pub fn new(length: Option<usize>, favorites_ratio: Option<f32>, seed: Option<u64>) -> Result<Self, String> {
+        let favorites_ratio = favorites_ratio.unwrap_or(DEFAULT_FAVORITES_RATIO);
+        if !(0.0..=1.0).contains(&favorites_ratio) {
+            return Err("Favorites ratio must be between 0 and 1".to_string());
+        }
+        let length = length.unwrap_or(DEFAULT_MIX_LENGTH).min(MAX_MIX_LENGTH);
+        if length == 0 {
+            return Err("Mix length must be at least 1".to_string());
+        }
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64
+        });
+        Ok(Self { length, favorites_ratio, seed })
+    }
+}
+
+/// SplitMix64, enough to shuffle reproducibly from a seed
+struct MixRng(u64);
+
+impl MixRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Items of a favorites response section, skipping malformed ones
+fn favorite_items<T: serde::de::DeserializeOwned>(response: &Value, section: &str) -> Vec<T> {
+    response
+        .get(section)
+        .and_then(|s| s.get("items"))
+        .and_then(|i| i.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| serde_json::from_value(item.clone()).ok())
+        .collect()
+}
+
+/// Every favorite track, a page at a time, so the mix samples from all of them
+async fn favorite_tracks(client: &QobuzClient) -> Result<Vec<Track>, String> {
+    let mut tracks = Vec::new();
+    let mut offset = 0;
+    loop {
+        let response = client
+            .get_favorites("tracks", MIX_FAVORITES_PAGE_LIMIT, offset)
+            .await
+            .map_err(|e| format!("Failed to get favorite tracks: {}", e))?;
+        let page_len = response["tracks"]["items"].as_array().map_or(0, |items| items.len()) as u32;
+        let total = response["tracks"]["total"].as_u64().unwrap_or(0);
+        tracks.extend(favorite_items::<Track>(&response, "tracks"));
+
+        offset += page_len;
+        if page_len < MIX_FAVORITES_PAGE_LIMIT || offset as u64 >= total {
+            return Ok(tracks);
+        }
+    }
+}
+
+/// Spread `favorites` evenly through `discoveries`, keeping the order of each
+fn interleave(favorites: Vec<QueueTrack>, discoveries: Vec<QueueTrack>) -> Vec<QueueTrack> {
+    let total = favorites.len() + discoveries.len();
+    let favorite_count = favorites.len();
+    let mut favorites = favorites.into_iter();
+    let mut discoveries = discoveries.into_iter();
+
+    let mut mixed = Vec::with_capacity(total);
+    let mut favorites_used = 0;
+    for i in 0..total {
+        let next = if (i + 1) * favorite_count / total > favorites_used {
+            favorites_used += 1;
+            favorites.next()
+        } else {
+            discoveries.next()
+        };
+        mixed.extend(next);
+    }
+    mixed
+}
+
+/// Build a mix of favorite tracks and radio discoveries, leaving out `recent`.
+/// When one side runs short the other fills the remaining length.
+pub(crate) async fn build_my_mix(
+    client: &QobuzClient,
+    recent: &HashSet<u64>,
+    options: &MixOptions,
+) -> Result<Vec<QueueTrack>, String> {
+    let mut rng = MixRng(options.seed);
+    let favorites_source = QueueSource {
+        kind: QueueSourceKind::Favorites,
+        id: None,
+        name: Some(MIX_NAME.to_string()),
+    };
+    let discovery_source = QueueSource {
+        kind: QueueSourceKind::Radio,
+        id: None,
+        name: Some(MIX_NAME.to_string()),
+    };
+
+    let favorite_tracks = favorite_tracks(client).await?;
+    let mut favorites: Vec<QueueTrack> = favorite_tracks
+        .iter()
+        .filter(|t| t.streamable && !recent.contains(&t.id))
+        .map(|t| queue_track(t, None, &favorites_source))
+        .collect();
+    rng.shuffle(&mut favorites);
+
+    // Favorite artists seed the radio, or the artists of favorite tracks without any
+    let mut seed_artists: Vec<u64> = match client.get_favorites("artists", MIX_FAVORITE_ARTISTS, 0).await {
+        Ok(response) => favorite_items::<Artist>(&response, "artists")
+            .into_iter()
+            .map(|a| a.id)
+            .collect(),
+        Err(e) => {
+            log::warn!("No favorite artists for the mix: {}", e);
+            Vec::new()
+        }
+    };
+    if seed_artists.is_empty() {
+        for artist in favorite_tracks.iter().filter_map(|t| t.performer.as_ref()) {
+            if !seed_artists.contains(&artist.id) {
+                seed_artists.push(artist.id);
+            }
+        }
+    }
+    rng.shuffle(&mut seed_artists);
+    seed_artists.truncate(MIX_SEED_ARTISTS);
+
+    let favorites_wanted = (options.length as f32 * options.favorites_ratio).round() as usize;
+    let discoveries_wanted = options.length - favorites_wanted.min(favorites.len());
+
+    // Discoveries are tracks that are neither favorites nor recently played
+    let mut exclude: HashSet<u64> = recent.clone();
+    exclude.extend(favorite_tracks.iter().map(|t| t.id));
+    let mut discoveries = Vec::new();
+    if discoveries_wanted > 0 && !seed_artists.is_empty() {
+        let per_artist = discoveries_wanted.div_ceil(seed_artists.len());
+        for artist_id in &seed_artists {
+            let tracks = artist_radio_tracks(client, *artist_id, &exclude, per_artist, &discovery_source).await;
+            exclude.extend(tracks.iter().map(|t| t.id));
+            discoveries.extend(tracks);
+        }
+    }
+    rng.shuffle(&mut discoveries);
+    discoveries.truncate(discoveries_wanted);
+
+    favorites.truncate(options.length - discoveries.len());
+    let mix = interleave(favorites, discoveries);
+    if mix.is_empty() {
+        return Err("Not enough favorites to build a mix".to_string());
+    }
+    Ok(mix)
+}
+
+/// Replace the queue with a personalized mix and play it. Returns the track
+/// playing.
+///
+/// `favorites_ratio` is the share of favorites (default 0.3); `seed` makes
+/// the shuffle reproducible.
+#[tauri::command]
+pub async fn play_my_mix(
+    length: Option<usize>,
+    favorites_ratio: Option<f32>,
+    seed: Option<u64>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    reco_state: State<'_, RecoState>,
+) -> Result<Option<QueueTrack>, String> {
+    log::info!("Command: play_my_mix length={:?} ratio={:?}", length, favorites_ratio);
+    let options = MixOptions::new(length, favorites_ratio, seed)?;
+
+    let mut recent: HashSet<u64> = {
+        let db = reco_state.db.lock().await;
+        db.get_recent_track_ids(MIX_RECENT_EXCLUDED)?.into_iter().collect()
+    };
+    recent.extend(state.queue.get_state().history.iter().map(|t| t.id));
+
+    let client = state.client.lock().await.clone();
+    let tracks = build_my_mix(&client, &recent, &options).await?;
+    log::info!("My Mix: {} tracks (seed {})", tracks.len(), options.seed);
+
+    state.queue.set_queue(tracks, Some(0));
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    play_current_track(&app_handle).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::{logged_in_client, mount_secret_probe};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn track(id: u64, artist_id: u64) -> Value {
+        serde_json::json!({
+            "id": id,
+            "title": format!("Track {}", id),
+            "duration": 200,
+            "streamable": true,
+            "performer": { "id": artist_id, "name": format!("Artist {}", artist_id) }
+        })
+    }
+
+    async fn mock_json(server: &MockServer, route: &str, param: (&str, &str), body: Value) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(query_param(param.0, param.1))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_mix_interleaves_favorites_and_discoveries_without_recent_tracks() {
+        let server = MockServer::start().await;
+        mock_json(
+            &server,
+            paths::FAVORITE_GET_USER_FAVORITES,
+            ("type", "tracks"),
+            serde_json::json!({ "tracks": { "items": [track(1, 10), track(2, 10), track(3, 30), track(4, 30)] } }),
+        )
+        .await;
+        mock_json(
+            &server,
+            paths::FAVORITE_GET_USER_FAVORITES,
+            ("type", "artists"),
+            serde_json::json!({ "artists": { "items": [{ "id": 10, "name": "Artist 10" }] } }),
+        )
+        .await;
+        mock_json(
+            &server,
+            paths::ARTIST_GET_SIMILAR,
+            ("artist_id", "10"),
+            serde_json::json!({ "artists": { "items": [{ "id": 20, "name": "Artist 20" }], "total": 1, "offset": 0, "limit": 5 } }),
+        )
+        .await;
+        // Top tracks include a favorite and a recently played track
+        mock_json(
+            &server,
+            paths::ARTIST_GET,
+            ("artist_id", "10"),
+            serde_json::json!({ "tracks": { "items": [track(1, 10), track(11, 10), track(12, 10)], "total": 3 } }),
+        )
+        .await;
+        mock_json(
+            &server,
+            paths::ARTIST_GET,
+            ("artist_id", "20"),
+            serde_json::json!({ "tracks": { "items": [track(22, 20), track(21, 20)], "total": 2 } }),
+        )
+        .await;
+
+        mount_secret_probe(&server).await;
+        let client = logged_in_client(&server).await;
+        let recent: HashSet<u64> = [2, 22].into_iter().collect();
+        let options = MixOptions::new(Some(6), Some(0.5), Some(7)).unwrap();
+
+        let mix = build_my_mix(&client, &recent, &options).await.unwrap();
+
+        let ids_of = |kind: QueueSourceKind| -> HashSet<u64> {
+            mix.iter()
+                .filter(|t| t.source.as_ref().map(|s| s.kind) == Some(kind))
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(ids_of(QueueSourceKind::Favorites), [1, 3, 4].into_iter().collect());
+        assert_eq!(ids_of(QueueSourceKind::Radio), [11, 12, 21].into_iter().collect());
+        // Evenly interleaved rather than one block after the other
+        for pair in mix.windows(2) {
+            assert_ne!(pair[0].source.as_ref().unwrap().kind, pair[1].source.as_ref().unwrap().kind);
+        }
+
+        // Same seed, same mix
+        let again = build_my_mix(&client, &recent, &options).await.unwrap();
+        assert_eq!(
+            again.iter().map(|t| t.id).collect::<Vec<_>>(),
+            mix.iter().map(|t| t.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mix_samples_from_every_favorites_page() {
+        let server = MockServer::start().await;
+        let first_page: Vec<Value> = (1..=MIX_FAVORITES_PAGE_LIMIT as u64).map(|id| track(id, 10)).collect();
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITES))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({ "tracks": { "items": first_page, "total": 501 } }),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::FAVORITE_GET_USER_FAVORITES))
+            .and(query_param("offset", "500"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({ "tracks": { "items": [track(501, 10)], "total": 501 } }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        mount_secret_probe(&server).await;
+        let client = logged_in_client(&server).await;
+
+        let tracks = favorite_tracks(&client).await.unwrap();
+
+        assert_eq!(tracks.len(), 501);
+        assert_eq!(tracks.last().map(|t| t.id), Some(501));
+    }
+}
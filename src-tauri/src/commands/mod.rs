@@ -6,26 +6,38 @@ pub mod audio_backends;
 pub mod audio_diagnostics;
 pub mod auth;
 pub mod cache;
+pub mod cover_colors;
 pub mod favorites;
 pub mod lastfm;
+pub mod library_stats;
+pub mod loudness;
+pub mod mix;
 pub mod notification;
 pub mod playback;
 pub mod playlist;
 pub mod playlist_import;
+pub mod purchases;
 pub mod queue;
 pub mod search;
 pub mod share;
+pub mod track_technical;
 
 pub use audio_backends::*;
 pub use audio_diagnostics::*;
 pub use auth::*;
 pub use cache::*;
+pub use cover_colors::*;
 pub use favorites::*;
 pub use lastfm::*;
+pub use library_stats::*;
+pub use loudness::*;
+pub use mix::*;
 pub use notification::*;
 pub use playback::*;
 pub use playlist::*;
 pub use playlist_import::*;
+pub use purchases::*;
 pub use queue::*;
 pub use search::*;
 pub use share::*;
+pub use track_technical::*;
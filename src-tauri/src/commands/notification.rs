@@ -8,10 +8,7 @@ use std::io::Write;
 
 /// Cache directory for notification artwork
 fn get_artwork_cache_dir() -> Result<PathBuf, String> {
-    let cache_dir = dirs::cache_dir()
-        .ok_or_else(|| "Could not find cache directory".to_string())?
-        .join("qbz")
-        .join("artwork");
+    let cache_dir = crate::storage::storage_root().join(crate::storage::ARTWORK_DIR);
 
     fs::create_dir_all(&cache_dir)
         .map_err(|e| format!("Failed to create artwork cache dir: {}", e))?;
@@ -20,7 +17,7 @@ fn get_artwork_cache_dir() -> Result<PathBuf, String> {
 }
 
 /// Download artwork to cache and return the path
-fn cache_artwork(url: &str) -> Result<PathBuf, String> {
+pub(crate) fn cache_artwork(url: &str) -> Result<PathBuf, String> {
     if let Some(local_path) = resolve_local_artwork(url) {
         if local_path.exists() {
             return Ok(local_path);
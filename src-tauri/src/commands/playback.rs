@@ -1,26 +1,168 @@
 //! Playback-related Tauri commands
 
-use std::sync::Arc;
-use tauri::State;
+use futures_util::future::{AbortHandle, Abortable};
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::sync::Mutex;
 
 use crate::api::client::QobuzClient;
-use crate::api::models::Quality;
-use crate::cache::AudioCache;
+use crate::api::models::{Quality, QualityDowngrade, StreamProbe, StreamRetryOutcome};
+use crate::api::stream_urls;
+use crate::api_cache::ApiCacheState;
+use crate::cache::{AudioCache, CacheMode};
+use crate::commands::favorites::current_user_id;
+use crate::commands::loudness::{apply_normalization, LoudnessState};
+use crate::config::playback_settings::PlaybackSettingsState;
+use crate::download_cache::throughput::auto_quality;
 use crate::download_cache::DownloadCacheState;
-use crate::player::PlaybackState;
-use crate::queue::QueueManager;
+use crate::metered::MeteredGate;
+use crate::player::{
+    download_audio, download_track, spawn_download, DownloadHandle, OutputFormat, PlaybackChainReport, PlaybackState,
+    StreamDecoder, EXPIRED_URL_ERROR,
+};
+use crate::queue::{QueueManager, QueueSourceKind, QueueTrack};
+use crate::session_store::SessionStoreState;
 use crate::AppState;
 
-/// Play a track by ID (with caching support)
+/// Payload of the `quality-downgraded` event
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QualityDowngradedEvent {
+    pub track_id: u64,
+    #[serde(flatten)]
+    pub downgrade: QualityDowngrade,
+}
+
+static STREAMING_QUALITY: RwLock<Quality> = RwLock::new(Quality::UltraHiRes);
+
+/// Quality the user chose to stream at; auto quality only goes below it
+pub fn streaming_quality() -> Quality {
+    STREAMING_QUALITY.read().map(|quality| *quality).unwrap_or(Quality::UltraHiRes)
+}
+
+/// Apply the saved streaming quality at startup
+pub fn load_streaming_quality(settings: &PlaybackSettingsState) {
+    let saved = settings
+        .lock()
+        .map_err(|e| e.to_string())
+        .and_then(|store| store.get_streaming_quality());
+    match saved.map(Quality::from_id) {
+        Ok(Some(saved)) => {
+            if let Ok(mut quality) = STREAMING_QUALITY.write() {
+                *quality = saved;
+            }
+        }
+        Ok(None) => log::warn!("Unknown saved streaming quality, using the default"),
+        Err(e) => log::warn!("Using the default streaming quality: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_streaming_quality() -> Quality {
+    streaming_quality()
+}
+
+#[tauri::command]
+pub fn set_streaming_quality(
+    quality: Quality,
+    settings: State<'_, PlaybackSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: set_streaming_quality {:?}", quality);
+    settings
+        .lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .set_streaming_quality(quality.id())?;
+    *STREAMING_QUALITY.write().map_err(|e| format!("Lock error: {}", e))? = quality;
+    Ok(())
+}
+
+/// Remember the position of the track that is playing (or was last played)
+/// so long tracks can be resumed later
+fn save_current_position(state: &AppState, session_store: &SessionStoreState) {
+    let Ok(playback) = state.player.get_state() else {
+        return;
+    };
+    if playback.track_id == 0 {
+        return;
+    }
+    if let Ok(store) = session_store.store.lock() {
+        if let Err(e) = store.save_track_position(playback.track_id, playback.position, playback.duration) {
+            log::warn!("Failed to save position for track {}: {}", playback.track_id, e);
+        }
+    }
+}
+
+/// Play a track by ID (with caching support).
+/// With `resume_from_saved`, long tracks continue from their saved position.
 #[tauri::command]
 pub async fn play_track(
     track_id: u64,
+    resume_from_saved: Option<bool>,
     state: State<'_, AppState>,
     download_cache: State<'_, DownloadCacheState>,
+    session_store: State<'_, SessionStoreState>,
+    loudness_state: State<'_, LoudnessState>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
     log::info!("Command: play_track {}", track_id);
 
+    // Track change: remember where the outgoing track was left
+    save_current_position(&state, &session_store);
+
+    // Measured track/album gain, if normalization is on
+    let album_id = state
+        .queue
+        .current_track()
+        .filter(|track| track.id == track_id)
+        .and_then(|track| track.source)
+        .filter(|source| source.kind == QueueSourceKind::Album)
+        .and_then(|source| source.id);
+    apply_normalization(&state.player, &loudness_state, track_id, album_id.as_deref()).await;
+
+    let start_secs = if resume_from_saved.unwrap_or(false) {
+        session_store
+            .store
+            .lock()
+            .ok()
+            .and_then(|store| store.get_track_position(track_id).ok().flatten())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    if start_secs > 0 {
+        log::info!("Resuming track {} from saved position {}s", track_id, start_secs);
+    }
+
+    // Cached/local playback has no stream quality to report
+    state.player.state.set_stream_quality(None, None);
+    state.player.state.set_stream_preview(false);
+
+    // A preloaded track is already downloaded and probed; this also cancels
+    // the preload of any other track
+    if let Some(preloaded) = state.player.take_preloaded(track_id) {
+        log::info!("Playing track {} from preload ({} bytes)", track_id, preloaded.data.len());
+        state
+            .player
+            .state
+            .set_stream_quality(Some(preloaded.requested_quality), preloaded.delivered_quality);
+        state.player.state.set_stream_preview(preloaded.is_preview);
+
+        let audio_data = if preloaded.is_preview {
+            preloaded.data
+        } else {
+            state.audio_cache.retain_for_playback(track_id, preloaded.data)
+        };
+        state.player.play_data_from(audio_data, track_id, start_secs)?;
+
+        spawn_prefetch(
+            state.client.clone(),
+            state.audio_cache.clone(),
+            &state.queue,
+        );
+
+        return Ok(());
+    }
+
     // First check download cache (persistent disk cache)
     {
         let db = download_cache.db.lock().await;
@@ -37,7 +179,7 @@ pub async fn play_track(
                 let audio_data = std::fs::read(path)
                     .map_err(|e| format!("Failed to read cached file: {}", e))?;
 
-                state.player.play_data(audio_data, track_id)?;
+                state.player.play_data_from(audio_data, track_id, start_secs)?;
 
                 // Prefetch next track in background
                 spawn_prefetch(
@@ -52,11 +194,12 @@ pub async fn play_track(
     }
 
     let cache = state.audio_cache.clone();
+    let stream_only = cache.mode() == CacheMode::StreamOnly;
 
     // Check if track is in memory cache (L1)
-    if let Some(cached) = cache.get(track_id) {
+    if let Some(cached) = cache.get(track_id).filter(|_| !stream_only) {
         log::info!("Playing track {} from memory cache ({} bytes)", track_id, cached.size_bytes);
-        state.player.play_data(cached.data, track_id)?;
+        state.player.play_data_from(cached.data, track_id, start_secs)?;
 
         // Prefetch next track in background
         spawn_prefetch(
@@ -69,14 +212,14 @@ pub async fn play_track(
     }
 
     // Check if track is in playback cache (L2 - disk)
-    if let Some(playback_cache) = cache.get_playback_cache() {
+    if let Some(playback_cache) = cache.get_playback_cache().filter(|_| !stream_only) {
         if let Some(audio_data) = playback_cache.get(track_id) {
             log::info!("Playing track {} from playback cache ({} bytes)", track_id, audio_data.len());
 
             // Promote back to memory cache
             cache.insert(track_id, audio_data.clone());
 
-            state.player.play_data(audio_data, track_id)?;
+            state.player.play_data_from(audio_data, track_id, start_secs)?;
 
             // Prefetch next track in background
             spawn_prefetch(
@@ -92,31 +235,79 @@ pub async fn play_track(
     // Not in any cache - download and cache in memory
     log::info!("Track {} not in any cache, streaming...", track_id);
 
-    let client = state.client.lock().await;
-
-    // Get the stream URL with highest quality available
-    let stream_url = client
-        .get_stream_url_with_fallback(track_id, Quality::UltraHiRes)
+    // Get the stream URL with highest quality available (or the auto-selected one)
+    let quality = auto_quality().starting_quality(streaming_quality());
+    let mut stream_url = state
+        .client
+        .lock()
+        .await
+        .playback_stream_url(track_id, quality)
         .await
         .map_err(|e| format!("Failed to get stream URL: {}", e))?;
 
     log::info!("Got stream URL for track {}", track_id);
 
-    // Download the audio
-    let audio_data = download_audio(&stream_url.url).await?;
+    if let Some(track) = state.queue.current_track().filter(|t| t.id == track_id) {
+        stream_url.check_duration(track.duration_secs as u32);
+    }
+    if stream_url.is_preview {
+        log::warn!("Track {} is a preview, playing the sample", track_id);
+    }
+
+    state
+        .player
+        .state
+        .set_stream_quality(Some(quality), stream_url.delivered_quality());
+    state.player.state.set_stream_preview(stream_url.is_preview);
+    if let Some(downgrade) = stream_url.downgrade.clone() {
+        let _ = app_handle.emit(
+            "quality-downgraded",
+            &QualityDowngradedEvent { track_id, downgrade },
+        );
+    }
+
+    // Start playing while the rest downloads; previews are small anyway
+    let mut whole = None;
+    if !stream_url.is_preview {
+        match play_streaming(&stream_url.url, track_id, start_secs, &state, &app_handle).await {
+            Ok(None) => {
+                spawn_prefetch(
+                    state.client.clone(),
+                    state.audio_cache.clone(),
+                    &state.queue,
+                );
+                return Ok(());
+            }
+            Ok(Some(data)) => {
+                log::info!("Track {} can't be decoded progressively, playing it downloaded", track_id);
+                whole = Some(data);
+            }
+            Err(e) => log::warn!("Streaming track {} failed, downloading it whole: {}", track_id, e),
+        }
+    }
+
+    // Download the audio; a URL that expired since it was resolved is re-requested once
+    let audio_data = match whole {
+        Some(data) => data,
+        None => {
+            let client = state.client.lock().await.clone();
+            download_track(&client, track_id, quality, &stream_url.url).await?
+        }
+    };
     let data_size = audio_data.len();
 
-    // Cache it
-    cache.insert(track_id, audio_data.clone());
+    // Cache it (unless stream-only); a preview must not stand in for the full track
+    let audio_data = if stream_url.is_preview {
+        audio_data
+    } else {
+        cache.retain_for_playback(track_id, audio_data)
+    };
 
     // Play it
-    state.player.play_data(audio_data, track_id)?;
+    state.player.play_data_from(audio_data, track_id, start_secs)?;
 
     log::info!("Playing track {} ({} bytes)", track_id, data_size);
 
-    // Release client lock before prefetching
-    drop(client);
-
     // Prefetch next track in background
     spawn_prefetch(
         state.client.clone(),
@@ -127,6 +318,111 @@ pub async fn play_track(
     Ok(())
 }
 
+/// Play the queue's current track, for commands that replace the queue and
+/// start it. Returns the track.
+pub(crate) async fn play_current_track(app_handle: &AppHandle) -> Result<Option<QueueTrack>, String> {
+    let Some(track) = app_handle.state::<AppState>().queue.current_track() else {
+        return Ok(None);
+    };
+    play_track(
+        track.id,
+        None,
+        app_handle.state(),
+        app_handle.state(),
+        app_handle.state(),
+        app_handle.state(),
+        app_handle.clone(),
+    )
+    .await?;
+    Ok(Some(track))
+}
+
+/// Qualities the stream endpoint actually serves for a track (highest first),
+/// probed per quality rather than read from the catalog
+#[tauri::command]
+pub async fn probe_available_qualities(
+    track_id: u64,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Vec<Quality>, String> {
+    log::info!("Command: probe_available_qualities {}", track_id);
+
+    // What is served depends on the account's plan, so probes are per user
+    let user_id = current_user_id(&state).await?;
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Some(cached) = cache.get_quality_probe(user_id, track_id)? {
+            return serde_json::from_str(&cached)
+                .map_err(|e| format!("Failed to parse cached quality probe: {}", e));
+        }
+    }
+
+    let qualities = {
+        let client = state.client.lock().await;
+        client
+            .probe_available_qualities(track_id)
+            .await
+            .map_err(|e| format!("Failed to probe qualities: {}", e))?
+    };
+
+    {
+        let cache = cache_state.cache.lock().await;
+        let json = serde_json::to_string(&qualities)
+            .map_err(|e| format!("Failed to serialize qualities: {}", e))?;
+        cache.set_quality_probe(user_id, track_id, &json)?;
+    }
+
+    Ok(qualities)
+}
+
+/// Re-sign and retry the stream request of a track that won't play, to
+/// diagnose a stale app secret vs. a genuine restriction
+#[tauri::command]
+pub async fn retry_stream(
+    track_id: u64,
+    quality: Quality,
+    force_new_secret: bool,
+    state: State<'_, AppState>,
+) -> Result<StreamRetryOutcome, String> {
+    log::info!("Command: retry_stream {} {:?}", track_id, quality);
+    let client = state.client.lock().await;
+    Ok(client.retry_stream(track_id, quality, force_new_secret).await)
+}
+
+/// File size, type and range support of a track's stream, without
+/// downloading it
+#[tauri::command]
+pub async fn probe_stream(
+    track_id: u64,
+    quality: Quality,
+    state: State<'_, AppState>,
+) -> Result<StreamProbe, String> {
+    log::info!("Command: probe_stream {} {:?}", track_id, quality);
+    let client = state.client.lock().await;
+    client
+        .probe_stream(track_id, quality)
+        .await
+        .map_err(|e| format!("Failed to probe stream: {}", e))
+}
+
+/// Download the selected track ahead of play so it starts instantly.
+/// Selecting another track cancels the previous preload.
+#[tauri::command]
+pub async fn preload_track(track_id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Command: preload_track {}", track_id);
+
+    let quality = auto_quality().starting_quality(streaming_quality());
+    state.player.preload(&state.client, track_id, quality).await
+}
+
+/// Drop the preloaded track
+#[tauri::command]
+pub fn cancel_preload(state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Command: cancel_preload");
+    state.player.cancel_preload();
+    Ok(())
+}
+
 /// Prefetch a track into the in-memory cache without starting playback
 #[tauri::command]
 pub async fn prefetch_track(
@@ -138,6 +434,11 @@ pub async fn prefetch_track(
 
     let cache = state.audio_cache.clone();
 
+    if !cache.prefetch_enabled() {
+        log::debug!("Stream-only mode, skipping prefetch of track {}", track_id);
+        return Ok(());
+    }
+
     if cache.contains(track_id) {
         log::info!("Track {} already in memory cache", track_id);
         return Ok(());
@@ -168,17 +469,26 @@ pub async fn prefetch_track(
 
         let client = state.client.lock().await;
         let stream_url = client
-            .get_stream_url_with_fallback(track_id, Quality::UltraHiRes)
+            .get_stream_url_with_fallback(track_id, auto_quality().starting_quality(streaming_quality()))
             .await
             .map_err(|e| format!("Failed to get stream URL: {}", e))?;
         drop(client);
 
+        // Previews are fetched at play time and never cached
+        if stream_url.is_preview {
+            log::info!("Not prefetching track {}: only a preview is available", track_id);
+            return Ok(());
+        }
+
         let audio_data = download_audio(&stream_url.url).await?;
         cache.insert(track_id, audio_data);
-        Ok(())
+        Ok::<(), String>(())
     }
     .await;
 
+    if let Err(e) = &result {
+        cache.prefetch_failed(track_id, e);
+    }
     cache.unmark_fetching(track_id);
     result
 }
@@ -197,6 +507,9 @@ pub async fn play_track_url(
     let audio_data = download_audio(&url).await?;
     log::info!("Downloaded {} bytes from URL", audio_data.len());
 
+    // Never measured; the previous track's gain mustn't carry over
+    state.player.set_replaygain_db(None);
+
     // Play using the existing player
     state.player.play_data(audio_data, track_id)?;
 
@@ -208,6 +521,10 @@ pub async fn play_track_url(
 
 /// Prefetch upcoming Nostr/URL-based tracks
 fn spawn_url_prefetch(cache: Arc<AudioCache>, queue: &QueueManager) {
+    if !cache.prefetch_enabled() {
+        return;
+    }
+
     let upcoming_tracks = queue.peek_upcoming(PREFETCH_LOOKAHEAD);
 
     if upcoming_tracks.is_empty() {
@@ -250,6 +567,7 @@ fn spawn_url_prefetch(cache: Arc<AudioCache>, queue: &QueueManager) {
                 }
                 Err(e) => {
                     log::warn!("URL prefetch failed for track {}: {}", track_id, e);
+                    cache_clone.prefetch_failed(track_id, &e);
                 }
             }
             cache_clone.unmark_fetching(track_id);
@@ -257,36 +575,130 @@ fn spawn_url_prefetch(cache: Arc<AudioCache>, queue: &QueueManager) {
     }
 }
 
-/// Download audio from URL
-async fn download_audio(url: &str) -> Result<Vec<u8>, String> {
-    use std::time::Duration;
+/// How a track opened by `open_stream` can be played
+enum OpenedStream {
+    /// Decodable as it downloads
+    Progressive(StreamDecoder, DownloadHandle),
+    /// The format needs the whole file (e.g. MP4 with its index at the
+    /// end); this is the finished download
+    Whole(Vec<u8>),
+}
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(120))
-        .connect_timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+/// Request `url` from byte `offset` on (blocking)
+fn fetch_from(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    offset: u64,
+) -> Result<reqwest::blocking::Response, String> {
+    let mut request = client.get(url).header("User-Agent", "Mozilla/5.0");
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    let mut response = request.send().map_err(|e| format!("Failed to fetch audio: {}", e))?;
+    if stream_urls::is_expired_status(response.status()) {
+        return Err(format!("{} ({})", EXPIRED_URL_ERROR, response.status()));
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
 
-    log::info!("Downloading audio...");
+    // A server ignoring the range sends the whole file
+    if offset > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        std::io::copy(&mut (&mut response).take(offset), &mut std::io::sink())
+            .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+    }
+    Ok(response)
+}
 
-    let response = client
-        .get(url)
-        .header("User-Agent", "Mozilla/5.0")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch audio: {}", e))?;
+/// Open `url` and probe it as it downloads (blocking). With `keep`, the
+/// download also keeps the whole file for the cache.
+fn open_stream(url: &str, keep: bool) -> Result<OpenedStream, String> {
+    // No overall timeout: the download is paced by playback
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(10))
+        .timeout(None)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP error: {}", response.status()));
+    let mut response = fetch_from(&client, url, 0)?;
+
+    // Without a length the decoder can't seek, so play it downloaded
+    let Some(content_length) = response.content_length() else {
+        let mut data = Vec::new();
+        response
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+        return Ok(OpenedStream::Whole(data));
+    };
+
+    let reopen = {
+        let url = url.to_string();
+        move |offset| fetch_from(&client, &url, offset)
+    };
+    let (reader, download) = spawn_download(response, reopen, Some(content_length), keep)?;
+    // Keeps the download going if the probe gives up on it
+    let keep_alive = reader.keep_alive();
+    match StreamDecoder::new(reader) {
+        Ok(decoder) => Ok(OpenedStream::Progressive(decoder, download)),
+        Err(e) => {
+            log::debug!("Progressive decode unavailable, finishing the download: {}", e);
+            let data = download
+                .join()
+                .map_err(|_| "Stream download thread panicked".to_string())??;
+            drop(keep_alive);
+            data.map(OpenedStream::Whole)
+                .ok_or_else(|| "Stream download wasn't kept".to_string())
+        }
     }
+}
 
-    let bytes = response
-        .bytes()
+/// Play a track while it downloads, from `start_secs` on. Once the
+/// download completes the data is cached and handed to the player, unless
+/// the cache is stream-only. Returns the downloaded file instead when the
+/// format can't be streamed.
+async fn play_streaming(
+    url: &str,
+    track_id: u64,
+    start_secs: u64,
+    state: &AppState,
+    app_handle: &AppHandle,
+) -> Result<Option<Vec<u8>>, String> {
+    let url = url.to_string();
+    let keep = state.audio_cache.keeps_streams();
+    let opened = tokio::task::spawn_blocking(move || open_stream(&url, keep))
         .await
-        .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+        .map_err(|e| format!("Stream task failed: {}", e))??;
+    let (decoder, download) = match opened {
+        OpenedStream::Progressive(decoder, download) => (decoder, download),
+        OpenedStream::Whole(data) => return Ok(Some(data)),
+    };
+
+    state.player.play_stream_from(decoder, track_id, start_secs)?;
+    log::info!("Streaming track {}", track_id);
+
+    let app_handle = app_handle.clone();
+    tokio::spawn(async move {
+        let data = match tokio::task::spawn_blocking(move || download.join()).await {
+            Ok(Ok(Ok(Some(data)))) => data,
+            Ok(Ok(Ok(None))) => return,
+            Ok(Ok(Err(e))) => {
+                log::info!("Stream download of track {} ended early: {}", track_id, e);
+                return;
+            }
+            _ => {
+                log::warn!("Stream download thread of track {} panicked", track_id);
+                return;
+            }
+        };
+        log::info!("Stream download of track {} complete ({} bytes)", track_id, data.len());
+        let state = app_handle.state::<AppState>();
+        let data = state.audio_cache.retain_for_playback(track_id, data);
+        if let Err(e) = state.player.complete_stream(track_id, data) {
+            log::warn!("{}", e);
+        }
+    });
 
-    log::info!("Downloaded {} bytes", bytes.len());
-    Ok(bytes.to_vec())
+    Ok(None)
 }
 
 /// Number of Qobuz tracks to prefetch (not total tracks, just Qobuz)
@@ -302,87 +714,149 @@ fn spawn_prefetch(
     cache: Arc<AudioCache>,
     queue: &QueueManager,
 ) {
-    // Look further ahead to find Qobuz tracks in mixed playlists
-    let upcoming_tracks = queue.peek_upcoming(PREFETCH_LOOKAHEAD);
+    if !cache.prefetch_enabled() {
+        log::debug!("Stream-only mode, prefetch disabled");
+        return;
+    }
+    update_prefetch_window(&cache, queue, crate::metered::metered(), |track_id| {
+        let (task, registration) = AbortHandle::new_pair();
+        // Spawned on the app runtime: this also runs from the queue-changed
+        // listener, outside of any async command
+        tauri::async_runtime::spawn(Abortable::new(
+            prefetch_track_data(client.clone(), cache.clone(), track_id),
+            registration,
+        ));
+        task
+    });
+}
 
-    if upcoming_tracks.is_empty() {
-        log::debug!("No upcoming tracks to prefetch");
+/// Re-plan prefetching after the queue changed, or the connection became
+/// (un)metered
+pub fn refresh_prefetch(state: &AppState) {
+    spawn_prefetch(state.client.clone(), state.audio_cache.clone(), &state.queue);
+}
+
+/// Keep the first tracks of a just-queued album or playlist warm, as many
+/// as the prefetch window holds, so it starts instantly wherever playback
+/// enters it. Takes effect on the next `refresh_prefetch`.
+pub(crate) fn warm_queue_additions(cache: &AudioCache, added: &[QueueTrack]) {
+    if !cache.warm_additions_enabled() {
+        return;
+    }
+    let track_ids = added
+        .iter()
+        .filter(|track| !track.is_local && track.audio_url.is_none())
+        .take(QOBUZ_PREFETCH_COUNT)
+        .map(|track| track.id)
+        .collect();
+    cache.set_warm_tracks(track_ids);
+}
+
+/// Keep prefetching on the next `QOBUZ_PREFETCH_COUNT` Qobuz tracks, plus
+/// the warm queue additions still queued: prefetches of tracks that dropped
+/// out of that window (the queue was reordered or edited) are cancelled,
+/// and missing ones started with `start`. While `gate` is paused the window
+/// is empty, so every prefetch stops.
+fn update_prefetch_window(
+    cache: &AudioCache,
+    queue: &QueueManager,
+    gate: &MeteredGate,
+    mut start: impl FnMut(u64) -> AbortHandle,
+) {
+    if gate.is_paused() {
+        let cancelled = cache.cancel_prefetches_outside(&[]);
+        log::debug!("Metered connection, prefetch paused (cancelled {:?})", cancelled);
         return;
     }
 
-    let mut qobuz_prefetched = 0;
+    // Look further ahead to find Qobuz tracks in mixed playlists; local
+    // tracks don't need prefetching
+    let mut window: Vec<QueueTrack> = queue
+        .peek_upcoming(PREFETCH_LOOKAHEAD)
+        .into_iter()
+        .filter(|track| !track.is_local)
+        .take(QOBUZ_PREFETCH_COUNT)
+        .collect();
 
-    for track in upcoming_tracks {
-        // Stop once we've prefetched enough Qobuz tracks
-        if qobuz_prefetched >= QOBUZ_PREFETCH_COUNT {
-            break;
+    let queued = queue.tracks();
+    for track_id in cache.retain_warm_tracks(|track_id| queued.iter().any(|track| track.id == track_id)) {
+        if window.iter().all(|track| track.id != track_id) {
+            window.extend(queued.iter().find(|track| track.id == track_id).cloned());
         }
+    }
 
-        let track_id = track.id;
-        let track_title = track.title.clone();
+    let window_ids: Vec<u64> = window.iter().map(|track| track.id).collect();
+    let cancelled = cache.cancel_prefetches_outside(&window_ids);
+    if !cancelled.is_empty() {
+        log::info!("Cancelled prefetch of tracks no longer up next: {:?}", cancelled);
+    }
 
-        // Skip local tracks - they don't need prefetching from Qobuz
-        if track.is_local {
-            log::debug!("Skipping prefetch for local track: {} - {}", track_id, track_title);
-            continue;
-        }
+    if window.is_empty() {
+        log::debug!("No upcoming tracks to prefetch");
+        return;
+    }
+
+    for track in window {
+        let track_id = track.id;
 
         // Check if already cached or being fetched
         if cache.contains(track_id) {
             log::debug!("Track {} already cached", track_id);
-            qobuz_prefetched += 1; // Count as "handled"
             continue;
         }
-
         if cache.is_fetching(track_id) {
             log::debug!("Track {} already being fetched", track_id);
-            qobuz_prefetched += 1; // Count as "handled"
             continue;
         }
 
-        // Mark as fetching
         cache.mark_fetching(track_id);
-        qobuz_prefetched += 1;
-
-        let client_clone = client.clone();
-        let cache_clone = cache.clone();
+        log::info!("Prefetching track: {} - {}", track_id, track.title);
+        let task = start(track_id);
+        cache.set_prefetch_task(track_id, task);
+    }
+}
 
-        log::info!("Prefetching track: {} - {}", track_id, track_title);
+/// Download one upcoming track into the cache
+async fn prefetch_track_data(client: Arc<Mutex<QobuzClient>>, cache: Arc<AudioCache>, track_id: u64) {
+    let result = async {
+        let client_guard = client.lock().await;
+        let stream_url = client_guard
+            .get_stream_url_with_fallback(track_id, auto_quality().starting_quality(streaming_quality()))
+            .await
+            .map_err(|e| format!("Failed to get stream URL: {}", e))?;
+        drop(client_guard);
 
-        // Spawn background task for each track
-        tokio::spawn(async move {
-            let result = async {
-                let client_guard = client_clone.lock().await;
-                let stream_url = client_guard
-                    .get_stream_url_with_fallback(track_id, Quality::UltraHiRes)
-                    .await
-                    .map_err(|e| format!("Failed to get stream URL: {}", e))?;
-                drop(client_guard);
-
-                let data = download_audio(&stream_url.url).await?;
-                Ok::<Vec<u8>, String>(data)
-            }
-            .await;
+        if stream_url.is_preview {
+            return Err("only a preview is available".to_string());
+        }
 
-            match result {
-                Ok(data) => {
-                    cache_clone.insert(track_id, data);
-                    log::info!("Prefetch complete for track {}", track_id);
-                }
-                Err(e) => {
-                    log::warn!("Prefetch failed for track {}: {}", track_id, e);
-                }
-            }
+        let data = download_audio(&stream_url.url).await?;
+        Ok::<Vec<u8>, String>(data)
+    }
+    .await;
 
-            cache_clone.unmark_fetching(track_id);
-        });
+    match result {
+        Ok(data) => {
+            cache.insert(track_id, data);
+            log::info!("Prefetch complete for track {}", track_id);
+        }
+        Err(e) => {
+            log::warn!("Prefetch failed for track {}: {}", track_id, e);
+            cache.prefetch_failed(track_id, &e);
+        }
     }
+
+    cache.unmark_fetching(track_id);
 }
 
 /// Pause playback
 #[tauri::command]
-pub fn pause_playback(state: State<'_, AppState>) -> Result<(), String> {
+pub fn pause_playback(
+    state: State<'_, AppState>,
+    session_store: State<'_, SessionStoreState>,
+) -> Result<(), String> {
     log::info!("Command: pause_playback");
+    save_current_position(&state, &session_store);
     state.media_controls.set_playback(false);
     state.player.pause()
 }
@@ -397,8 +871,12 @@ pub fn resume_playback(state: State<'_, AppState>) -> Result<(), String> {
 
 /// Stop playback
 #[tauri::command]
-pub fn stop_playback(state: State<'_, AppState>) -> Result<(), String> {
+pub fn stop_playback(
+    state: State<'_, AppState>,
+    session_store: State<'_, SessionStoreState>,
+) -> Result<(), String> {
     log::info!("Command: stop_playback");
+    save_current_position(&state, &session_store);
     state.media_controls.set_stopped();
     state.player.stop()
 }
@@ -449,6 +927,43 @@ pub fn seek(position: u64, state: State<'_, AppState>) -> Result<(), String> {
     result
 }
 
+/// Preview the audio at a position while the seek bar is dragged
+#[tauri::command]
+pub fn scrub_preview(position: u64, state: State<'_, AppState>) -> Result<(), String> {
+    log::debug!("Command: scrub_preview {}", position);
+    state.player.scrub_preview(position)
+}
+
+/// Finish a seek bar drag, seeking to the last previewed position
+#[tauri::command]
+pub fn commit_seek(state: State<'_, AppState>) -> Result<Option<u64>, String> {
+    log::info!("Command: commit_seek");
+    let position = state.player.commit_seek()?;
+
+    if let Some(position) = position {
+        let playback_state = state.player.get_state().unwrap_or_default();
+        state.media_controls.set_playback_with_progress(
+            playback_state.is_playing,
+            position,
+        );
+    }
+
+    Ok(position)
+}
+
+/// Get the sample format and effective buffer sizes of the open output stream
+#[tauri::command]
+pub fn get_output_format(state: State<'_, AppState>) -> Result<Option<OutputFormat>, String> {
+    Ok(state.player.state.output_format())
+}
+
+/// Describe the signal path from decoder to output device, for bug reports
+#[tauri::command]
+pub fn dump_playback_chain(state: State<'_, AppState>) -> Result<PlaybackChainReport, String> {
+    log::info!("Command: dump_playback_chain");
+    state.player.dump_playback_chain()
+}
+
 /// Get current playback state (also updates MPRIS progress)
 #[tauri::command]
 pub fn get_playback_state(state: State<'_, AppState>) -> Result<PlaybackState, String> {
@@ -787,3 +1302,113 @@ pub fn debug_get_cpal_devices() -> Result<Vec<String>, String> {
     log::info!("CPAL devices: {:?}", devices);
     Ok(devices)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::test_track;
+    use std::sync::Mutex as StdMutex;
+
+    #[tokio::test]
+    async fn test_reordered_queue_cancels_stale_prefetch_and_starts_new_next() {
+        let gate = MeteredGate::new();
+        let cache = AudioCache::new(1024 * 1024);
+        let queue = QueueManager::new();
+        queue.set_queue((1..=6).map(test_track).collect(), Some(0));
+
+        // Prefetches never finish on their own here
+        let tasks = StdMutex::new(Vec::new());
+        let start = |track_id: u64| {
+            let (task, registration) = AbortHandle::new_pair();
+            tokio::spawn(Abortable::new(std::future::pending::<()>(), registration));
+            tasks.lock().unwrap().push((track_id, task.clone()));
+            task
+        };
+
+        update_prefetch_window(&cache, &queue, &gate, start);
+        let started: Vec<u64> = tasks.lock().unwrap().iter().map(|(id, _)| *id).collect();
+        assert_eq!(started, vec![2, 3, 4]);
+
+        // Track 6 jumps to the front: 4 is no longer up next, 2 and 3 still are
+        assert!(queue.move_track(5, 1));
+        update_prefetch_window(&cache, &queue, &gate, start);
+
+        let tasks = tasks.lock().unwrap();
+        let started: Vec<u64> = tasks.iter().map(|(id, _)| *id).collect();
+        assert_eq!(started, vec![2, 3, 4, 6]);
+        let aborted: Vec<u64> = tasks.iter().filter(|(_, task)| task.is_aborted()).map(|(id, _)| *id).collect();
+        assert_eq!(aborted, vec![4]);
+        assert!(!cache.is_fetching(4));
+        assert!(cache.is_fetching(2) && cache.is_fetching(3) && cache.is_fetching(6));
+    }
+
+    #[tokio::test]
+    async fn test_queued_album_warms_first_tracks_until_rest_enter_window() {
+        let gate = MeteredGate::new();
+        let cache = AudioCache::new(1024 * 1024);
+        cache.set_warm_additions(true);
+        let queue = QueueManager::new();
+        queue.set_queue((1..=5).map(test_track).collect(), Some(0));
+
+        let started = StdMutex::new(Vec::new());
+        let start = |track_id: u64| {
+            let (task, registration) = AbortHandle::new_pair();
+            tokio::spawn(Abortable::new(std::future::pending::<()>(), registration));
+            started.lock().unwrap().push(track_id);
+            task
+        };
+
+        // An album appended after the upcoming tracks
+        let album: Vec<QueueTrack> = (11..=16).map(test_track).collect();
+        warm_queue_additions(&cache, &album);
+        queue.insert_tracks(album, crate::queue::QueueInsertMode::Append);
+        update_prefetch_window(&cache, &queue, &gate, start);
+        assert_eq!(*started.lock().unwrap(), vec![2, 3, 4, 11, 12, 13]);
+        assert!(!cache.is_fetching(14));
+
+        // Playback jumps into the album: the rest follow as they come up
+        queue.play_index(7);
+        update_prefetch_window(&cache, &queue, &gate, start);
+        assert_eq!(started.lock().unwrap()[6..], [14, 15, 16]);
+        assert!(!cache.is_fetching(2) && cache.is_fetching(11));
+
+        // Replacing the queue cancels the warm tracks
+        queue.set_queue((21..=22).map(test_track).collect(), Some(0));
+        update_prefetch_window(&cache, &queue, &gate, start);
+        assert!(!cache.is_fetching(11) && !cache.is_fetching(14));
+        assert!(cache.retain_warm_tracks(|_| true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metered_connection_stops_prefetch_until_cleared() {
+        let gate = MeteredGate::new();
+        gate.set_pause_on_metered(true);
+        let cache = AudioCache::new(1024 * 1024);
+        let queue = QueueManager::new();
+        queue.set_queue((1..=4).map(test_track).collect(), Some(0));
+
+        let tasks = StdMutex::new(Vec::new());
+        let start = |track_id: u64| {
+            let (task, registration) = AbortHandle::new_pair();
+            tokio::spawn(Abortable::new(std::future::pending::<()>(), registration));
+            tasks.lock().unwrap().push((track_id, task.clone()));
+            task
+        };
+
+        update_prefetch_window(&cache, &queue, &gate, start);
+        assert_eq!(tasks.lock().unwrap().len(), 3);
+
+        // Going metered cancels what is in flight and starts nothing
+        gate.set_manual(true);
+        update_prefetch_window(&cache, &queue, &gate, start);
+        assert_eq!(tasks.lock().unwrap().len(), 3);
+        assert!(tasks.lock().unwrap().iter().all(|(_, task)| task.is_aborted()));
+        assert!(!cache.is_fetching(2));
+
+        // Resuming plans the window again
+        gate.set_manual(false);
+        update_prefetch_window(&cache, &queue, &gate, start);
+        let started: Vec<u64> = tasks.lock().unwrap().iter().map(|(id, _)| *id).collect();
+        assert_eq!(started, vec![2, 3, 4, 2, 3, 4]);
+    }
+}
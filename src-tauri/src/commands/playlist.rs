@@ -2,36 +2,76 @@
 
 use tauri::State;
 
-use crate::api::models::{Playlist, SearchResultsPage};
+use crate::api::models::{Playlist, PlaylistTrackMove, SearchResultsPage};
+use crate::api_cache::fetch::{fetch_cached, parse_cached, to_cache_json, FetchStrategy};
+use crate::api_cache::ApiCacheState;
+use crate::offline::OfflineState;
 use crate::AppState;
 
 /// Get user's playlists
 #[tauri::command]
 pub async fn get_user_playlists(
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<Vec<Playlist>, String> {
     log::info!("Command: get_user_playlists");
 
-    let client = state.client.lock().await;
-    client
-        .get_user_playlists()
-        .await
-        .map_err(|e| format!("Failed to get user playlists: {}", e))
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Some(cached_data) = cache.get_user_playlists(None)? {
+            log::debug!("Cache hit for user playlists");
+            return serde_json::from_str(&cached_data)
+                .map_err(|e| format!("Failed to parse cached playlists: {}", e));
+        }
+    }
+
+    let playlists = {
+        let client = state.client.lock().await;
+        client
+            .get_user_playlists()
+            .await
+            .map_err(|e| format!("Failed to get user playlists: {}", e))?
+    };
+
+    {
+        let cache = cache_state.cache.lock().await;
+        let json = serde_json::to_string(&playlists)
+            .map_err(|e| format!("Failed to serialize playlists: {}", e))?;
+        cache.set_user_playlists(&json)?;
+    }
+
+    Ok(playlists)
+}
+
+/// Drop the cached playlist list and playlists after a successful change
+async fn invalidate_user_playlists(cache_state: &ApiCacheState) {
+    let cache = cache_state.cache.lock().await;
+    if let Err(e) = cache.invalidate_user_playlists() {
+        log::warn!("Failed to invalidate playlists cache: {}", e);
+    }
 }
 
 /// Get a specific playlist by ID
 #[tauri::command]
 pub async fn get_playlist(
     playlist_id: u64,
+    strategy: Option<FetchStrategy>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
 ) -> Result<Playlist, String> {
     log::info!("Command: get_playlist {}", playlist_id);
 
-    let client = state.client.lock().await;
-    client
-        .get_playlist(playlist_id)
-        .await
-        .map_err(|e| format!("Failed to get playlist: {}", e))
+    fetch_cached(
+        offline_state.fetch_strategy(strategy),
+        &cache_state.cache,
+        |cache, freshness| parse_cached(cache.get_playlist(playlist_id, freshness.ttl())?),
+        |cache, playlist| cache.set_playlist(playlist_id, &to_cache_json(playlist)?),
+        || async { state.client.lock().await.get_playlist(playlist_id).await },
+    )
+    .await
+    .map(|fetched| fetched.value)
+    .map_err(|e| format!("Failed to get playlist: {}", e))
 }
 
 /// Search playlists
@@ -57,14 +97,20 @@ pub async fn create_playlist(
     description: Option<String>,
     is_public: Option<bool>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<Playlist, String> {
     log::info!("Command: create_playlist \"{}\"", name);
 
-    let client = state.client.lock().await;
-    client
-        .create_playlist(&name, description.as_deref(), is_public.unwrap_or(false))
-        .await
-        .map_err(|e| format!("Failed to create playlist: {}", e))
+    let result = {
+        let client = state.client.lock().await;
+        client
+            .create_playlist(&name, description.as_deref(), is_public.unwrap_or(false))
+            .await
+            .map_err(|e| format!("Failed to create playlist: {}", e))?
+    };
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(result)
 }
 
 /// Delete a playlist
@@ -72,14 +118,62 @@ pub async fn create_playlist(
 pub async fn delete_playlist(
     playlist_id: u64,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<(), String> {
     log::info!("Command: delete_playlist {}", playlist_id);
 
-    let client = state.client.lock().await;
-    client
-        .delete_playlist(playlist_id)
-        .await
-        .map_err(|e| format!("Failed to delete playlist: {}", e))
+    {
+        let client = state.client.lock().await;
+        client
+            .delete_playlist(playlist_id)
+            .await
+            .map_err(|e| format!("Failed to delete playlist: {}", e))?;
+    }
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(())
+}
+
+/// Follow another user's playlist
+#[tauri::command]
+pub async fn subscribe_playlist(
+    playlist_id: u64,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<(), String> {
+    log::info!("Command: subscribe_playlist {}", playlist_id);
+
+    {
+        let client = state.client.lock().await;
+        client
+            .subscribe_playlist(playlist_id)
+            .await
+            .map_err(|e| format!("Failed to subscribe to playlist: {}", e))?;
+    }
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(())
+}
+
+/// Stop following a playlist
+#[tauri::command]
+pub async fn unsubscribe_playlist(
+    playlist_id: u64,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<(), String> {
+    log::info!("Command: unsubscribe_playlist {}", playlist_id);
+
+    {
+        let client = state.client.lock().await;
+        client
+            .unsubscribe_playlist(playlist_id)
+            .await
+            .map_err(|e| format!("Failed to unsubscribe from playlist: {}", e))?;
+    }
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(())
 }
 
 /// Add tracks to a playlist
@@ -88,14 +182,43 @@ pub async fn add_tracks_to_playlist(
     playlist_id: u64,
     track_ids: Vec<u64>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<(), String> {
     log::info!("Command: add_tracks_to_playlist {} ({} tracks)", playlist_id, track_ids.len());
 
-    let client = state.client.lock().await;
-    client
-        .add_tracks_to_playlist(playlist_id, &track_ids)
-        .await
-        .map_err(|e| format!("Failed to add tracks to playlist: {}", e))
+    {
+        let client = state.client.lock().await;
+        client
+            .add_tracks_to_playlist(playlist_id, &track_ids)
+            .await
+            .map_err(|e| format!("Failed to add tracks to playlist: {}", e))?;
+    }
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(())
+}
+
+/// Move a track from one playlist to another without leaving it in both
+#[tauri::command]
+pub async fn move_track_between_playlists(
+    track_id: u64,
+    from_id: u64,
+    to_id: u64,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<PlaylistTrackMove, String> {
+    log::info!("Command: move_track_between_playlists {} ({} -> {})", track_id, from_id, to_id);
+
+    let result = {
+        let client = state.client.lock().await;
+        client
+            .move_track_between_playlists(track_id, from_id, to_id)
+            .await
+            .map_err(|e| format!("Failed to move track between playlists: {}", e))?
+    };
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(result)
 }
 
 /// Remove tracks from a playlist
@@ -104,14 +227,20 @@ pub async fn remove_tracks_from_playlist(
     playlist_id: u64,
     playlist_track_ids: Vec<u64>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<(), String> {
     log::info!("Command: remove_tracks_from_playlist {} ({} tracks)", playlist_id, playlist_track_ids.len());
 
-    let client = state.client.lock().await;
-    client
-        .remove_tracks_from_playlist(playlist_id, &playlist_track_ids)
-        .await
-        .map_err(|e| format!("Failed to remove tracks from playlist: {}", e))
+    {
+        let client = state.client.lock().await;
+        client
+            .remove_tracks_from_playlist(playlist_id, &playlist_track_ids)
+            .await
+            .map_err(|e| format!("Failed to remove tracks from playlist: {}", e))?;
+    }
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(())
 }
 
 /// Update playlist metadata
@@ -122,12 +251,18 @@ pub async fn update_playlist(
     description: Option<String>,
     is_public: Option<bool>,
     state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<Playlist, String> {
     log::info!("Command: update_playlist {}", playlist_id);
 
-    let client = state.client.lock().await;
-    client
-        .update_playlist(playlist_id, name.as_deref(), description.as_deref(), is_public)
-        .await
-        .map_err(|e| format!("Failed to update playlist: {}", e))
+    let result = {
+        let client = state.client.lock().await;
+        client
+            .update_playlist(playlist_id, name.as_deref(), description.as_deref(), is_public)
+            .await
+            .map_err(|e| format!("Failed to update playlist: {}", e))?
+    };
+
+    invalidate_user_playlists(&cache_state).await;
+    Ok(result)
 }
@@ -0,0 +1,71 @@
+//! Purchased (owned) content commands
+
+use tauri::State;
+
+use crate::api::models::{PurchaseType, Purchases};
+use crate::AppState;
+
+/// Get the user's purchased albums or tracks, separate from favorites
+#[tauri::command]
+pub async fn get_purchases(
+    purchase_type: PurchaseType,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Purchases, String> {
+    log::info!(
+        "Command: get_purchases type={:?} limit={:?} offset={:?}",
+        purchase_type, limit, offset
+    );
+
+    let client = state.client.lock().await;
+    client
+        .get_purchases(purchase_type, limit.unwrap_or(50), offset.unwrap_or(0))
+        .await
+        .map_err(|e| format!("Failed to get purchases: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purchases_response_lists_owned_albums_and_tracks() {
+        let purchases: Purchases = serde_json::from_value(serde_json::json!({
+            "albums": {
+                "items": [{
+                    "id": "alb1",
+                    "title": "Bought Album",
+                    "artist": { "id": 1, "name": "Band" },
+                    "tracks": { "items": [{ "id": 11, "title": "One" }, { "id": 12, "title": "Two" }], "total": 2 }
+                }, {
+                    "id": "alb2",
+                    "title": "Listed Without Tracks"
+                }],
+                "total": 2, "offset": 0, "limit": 50
+            },
+            "tracks": {
+                "items": [{ "id": 30, "title": "Single" }, { "id": 12, "title": "Two" }],
+                "total": 2, "offset": 0, "limit": 50
+            }
+        }))
+        .unwrap();
+
+        let albums = purchases.albums.as_ref().unwrap();
+        assert_eq!(albums.items.len(), 2);
+        assert_eq!(albums.items[0].title, "Bought Album");
+        let album_tracks: Vec<u64> = albums.items[0].tracks.as_ref().unwrap().items.iter().map(|t| t.id).collect();
+        assert_eq!(album_tracks, vec![11, 12]);
+        assert!(albums.items[1].tracks.is_none());
+        let tracks: Vec<u64> = purchases.tracks.as_ref().unwrap().items.iter().map(|t| t.id).collect();
+        assert_eq!(tracks, vec![30, 12]);
+
+        // Listing only one kind leaves the other out
+        let tracks_only: Purchases = serde_json::from_value(serde_json::json!({
+            "tracks": { "items": [{ "id": 7 }], "total": 1, "offset": 0, "limit": 50 }
+        }))
+        .unwrap();
+        assert!(tracks_only.albums.is_none());
+        assert_eq!(tracks_only.tracks.unwrap().items[0].id, 7);
+    }
+}
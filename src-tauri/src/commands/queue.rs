@@ -1,62 +1,393 @@
 //! Queue management Tauri commands
 
-use tauri::State;
+use std::collections::HashSet;
 
-use crate::queue::{QueueState, QueueTrack, RepeatMode};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Mutex;
+
+use crate::api::models::{Album, FavoritesSort, ImageSet, Playlist, Track};
+use crate::api::{ApiError, QobuzClient};
+use crate::api_cache::{sync, ApiCacheState};
+use crate::config::playback_settings::PlaybackSettingsState;
+use crate::commands::playback::play_current_track;
+use crate::player::PlaybackEvent;
+use crate::queue::{
+    NowPlayingContext, QueueEndAction, QueueInsertMode, QueueManager, QueueSource, QueueSourceKind, QueueState,
+    QueueTrack, RepeatMode, SourceStatus, UnplayablePolicy,
+};
 use crate::AppState;
 
+/// Tracks appended each time radio takes over
+const RADIO_BATCH: usize = 20;
+/// Similar artists drawn from for radio
+const RADIO_SIMILAR_ARTISTS: u32 = 5;
+/// Top tracks taken per radio artist
+const RADIO_TRACKS_PER_ARTIST: u32 = 5;
+
+/// Outcome of queueing an album or playlist
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct QueueAddReport {
+    pub added: usize,
+    /// Tracks left out because they can't be streamed
+    pub skipped: Vec<u64>,
+}
+
 /// Add a track to the queue
 #[tauri::command]
-pub fn add_to_queue(track: QueueTrack, state: State<'_, AppState>) -> Result<(), String> {
+pub fn add_to_queue(track: QueueTrack, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     log::info!("Command: add_to_queue - {} by {}", track.title, track.artist);
     state.queue.add_track(track);
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
 /// Add a track to play next
 #[tauri::command]
-pub fn add_to_queue_next(track: QueueTrack, state: State<'_, AppState>) -> Result<(), String> {
+pub fn add_to_queue_next(track: QueueTrack, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     log::info!("Command: add_to_queue_next - {} by {}", track.title, track.artist);
     state.queue.add_track_next(track);
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
 /// Add multiple tracks to the queue
 #[tauri::command]
-pub fn add_tracks_to_queue(tracks: Vec<QueueTrack>, state: State<'_, AppState>) -> Result<(), String> {
+pub fn add_tracks_to_queue(
+    tracks: Vec<QueueTrack>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
     log::info!("Command: add_tracks_to_queue - {} tracks", tracks.len());
     state.queue.add_tracks(tracks);
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
+fn artwork(image: &ImageSet) -> Option<String> {
+    image
+        .large
+        .as_ref()
+        .or(image.thumbnail.as_ref())
+        .or(image.small.as_ref())
+        .cloned()
+}
+
+/// Queue entry for a catalog track. Album tracks don't carry their album,
+/// so it is passed in when known.
+pub(crate) fn queue_track(track: &Track, album: Option<&Album>, source: &QueueSource) -> QueueTrack {
+    let artist = track
+        .performer
+        .as_ref()
+        .map(|p| p.name.clone())
+        .or_else(|| album.map(|a| a.artist.name.clone()))
+        .unwrap_or_default();
+    let album_title = track
+        .album
+        .as_ref()
+        .map(|a| a.title.clone())
+        .or_else(|| album.map(|a| a.title.clone()))
+        .unwrap_or_default();
+    let artwork_url = track
+        .album
+        .as_ref()
+        .and_then(|a| artwork(&a.image))
+        .or_else(|| album.and_then(|a| artwork(&a.image)));
+
+    QueueTrack {
+        id: track.id,
+        title: track.title.clone(),
+        artist,
+        album: album_title,
+        duration_secs: track.duration as u64,
+        artwork_url,
+        hires: track.hires_streamable,
+        bit_depth: track.maximum_bit_depth,
+        sample_rate: track.maximum_sampling_rate,
+        is_local: false,
+        audio_url: None,
+        nostr_event_id: None,
+        nostr_pubkey: None,
+        source: Some(source.clone()),
+    }
+}
+
+/// Streamable tracks in order, and the ids of the ones skipped
+fn queue_tracks<'a>(
+    tracks: impl IntoIterator<Item = &'a Track>,
+    album: Option<&Album>,
+    source: &QueueSource,
+) -> (Vec<QueueTrack>, Vec<u64>) {
+    let mut queued = Vec::new();
+    let mut skipped = Vec::new();
+    for track in tracks {
+        if track.streamable {
+            queued.push(queue_track(track, album, source));
+        } else {
+            skipped.push(track.id);
+        }
+    }
+    (queued, skipped)
+}
+
+fn album_source(album: &Album) -> QueueSource {
+    QueueSource {
+        kind: QueueSourceKind::Album,
+        id: Some(album.id.clone()),
+        name: Some(album.title.clone()),
+    }
+}
+
+fn album_queue_tracks(album: &Album) -> (Vec<QueueTrack>, Vec<u64>) {
+    let tracks = album.tracks.iter().flat_map(|t| t.items.iter());
+    queue_tracks(tracks, Some(album), &album_source(album))
+}
+
+fn playlist_queue_tracks(playlist: &Playlist) -> (Vec<QueueTrack>, Vec<u64>) {
+    let source = QueueSource {
+        kind: QueueSourceKind::Playlist,
+        id: Some(playlist.id.to_string()),
+        name: Some(playlist.name.clone()),
+    };
+    let tracks = playlist.tracks.iter().flat_map(|t| t.items.iter());
+    queue_tracks(tracks, None, &source)
+}
+
+fn insert_block(
+    (tracks, skipped): (Vec<QueueTrack>, Vec<u64>),
+    mode: QueueInsertMode,
+    state: &AppState,
+    app_handle: &AppHandle,
+) -> QueueAddReport {
+    if !skipped.is_empty() {
+        log::info!("Skipping {} unavailable tracks: {:?}", skipped.len(), skipped);
+    }
+    let added = tracks.len();
+    if added > 0 {
+        // Picked up by the prefetch refresh on queue-changed
+        crate::commands::playback::warm_queue_additions(&state.audio_cache, &tracks);
+        state.queue.insert_tracks(tracks, mode);
+        let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    }
+    QueueAddReport { added, skipped }
+}
+
+/// Queue a whole album in disc/track order
+#[tauri::command]
+pub async fn queue_add_album(
+    album_id: String,
+    mode: QueueInsertMode,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<QueueAddReport, String> {
+    log::info!("Command: queue_add_album - {} ({:?})", album_id, mode);
+    let album = {
+        let client = state.client.lock().await;
+        client.get_album_all_tracks(&album_id).await.map_err(|e| e.to_string())?
+    };
+    Ok(insert_block(album_queue_tracks(&album), mode, &state, &app_handle))
+}
+
+/// The album's playable tracks and the index of `track_id` among them. An
+/// unplayable chosen track hands over to the next playable one; a track the
+/// album doesn't list starts the album from the top.
+fn continue_album_queue(album: &Album, track_id: u64) -> (Vec<QueueTrack>, usize) {
+    let mut album_tracks: Vec<&Track> = album.tracks.iter().flat_map(|t| t.items.iter()).collect();
+    album_tracks.sort_by_key(|t| (t.media_number.unwrap_or(1), t.track_number));
+
+    let (tracks, skipped) = queue_tracks(album_tracks.iter().copied(), Some(album), &album_source(album));
+    if !skipped.is_empty() {
+        log::info!("Skipping {} unavailable album tracks: {:?}", skipped.len(), skipped);
+    }
+
+    let start = match album_tracks.iter().position(|t| t.id == track_id) {
+        Some(position) => album_tracks[..position].iter().filter(|t| t.streamable).count(),
+        None => {
+            log::warn!("Track {} is not listed on album {}, starting from the top", track_id, album.id);
+            0
+        }
+    };
+    let start = start.min(tracks.len().saturating_sub(1));
+    (tracks, start)
+}
+
+/// Replace the queue with the album of `track_id`, in disc/track order and
+/// positioned at that track, so the rest of the album follows it (earlier
+/// tracks stay reachable with previous), and play it. Returns the track
+/// playing.
+#[tauri::command]
+pub async fn queue_continue_album(
+    track_id: u64,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Option<QueueTrack>, String> {
+    log::info!("Command: queue_continue_album - {}", track_id);
+    let client = state.client.lock().await.clone();
+    let track = client.get_track(track_id).await.map_err(|e| e.to_string())?;
+    let album_id = track
+        .album
+        .map(|album| album.id)
+        .ok_or_else(|| format!("Track {} doesn't belong to an album", track_id))?;
+    let album = client.get_album_all_tracks(&album_id).await.map_err(|e| e.to_string())?;
+
+    let (tracks, start) = continue_album_queue(&album, track_id);
+    if tracks.is_empty() {
+        return Ok(None);
+    }
+    state.queue.set_queue(tracks, Some(start));
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    play_current_track(&app_handle).await
+}
+
+/// Queue a whole playlist in playlist order
+#[tauri::command]
+pub async fn queue_add_playlist(
+    playlist_id: u64,
+    mode: QueueInsertMode,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<QueueAddReport, String> {
+    log::info!("Command: queue_add_playlist - {} ({:?})", playlist_id, mode);
+    let playlist = {
+        let client = state.client.lock().await;
+        client
+            .get_playlist_all_tracks(playlist_id)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    Ok(insert_block(playlist_queue_tracks(&playlist), mode, &state, &app_handle))
+}
+
+/// Favorite tracks in `sort` order, each once, and the ids of the
+/// unavailable ones left out
+fn favorites_queue_tracks(items: Vec<serde_json::Value>, sort: FavoritesSort) -> (Vec<QueueTrack>, Vec<u64>) {
+    let mut page = serde_json::json!({ "tracks": { "items": items } });
+    sort.apply(&mut page, "tracks");
+
+    let mut seen = HashSet::new();
+    let tracks: Vec<Track> = page["tracks"]["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| serde_json::from_value::<Track>(item.clone()).ok())
+        .filter(|track| seen.insert(track.id))
+        .collect();
+    let source = QueueSource {
+        kind: QueueSourceKind::Favorites,
+        id: None,
+        name: Some("Favorites".to_string()),
+    };
+    queue_tracks(&tracks, None, &source)
+}
+
+/// Replace the queue with every favorite track and play it. Returns the
+/// track playing. Nothing is created on Qobuz: tracks come from the synced favorites copy,
+/// which is brought up to date first (falling back to it as is when offline).
+#[tauri::command]
+pub async fn queue_play_favorites(
+    sort: Option<FavoritesSort>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    app_handle: AppHandle,
+) -> Result<Option<QueueTrack>, String> {
+    let sort = sort.unwrap_or_default();
+    log::info!("Command: queue_play_favorites ({:?})", sort);
+
+    let client = state.client.lock().await.clone();
+    let user_id = client.user_id().await.map_err(|e| e.to_string())?;
+    let synced = sync::sync_favorites(&client, &cache_state.cache, "tracks").await;
+    let items = {
+        let cache = cache_state.cache.lock().await;
+        let items = cache.synced_favorites(user_id, "tracks")?;
+        if let Err(e) = synced {
+            if items.is_empty() {
+                return Err(format!("Failed to load favorite tracks: {}", e));
+            }
+            log::warn!("Favorites sync failed, using the local copy: {}", e);
+        }
+        items
+    };
+
+    let (tracks, skipped) = favorites_queue_tracks(items, sort);
+    if !skipped.is_empty() {
+        log::info!("Skipping {} unavailable favorites: {:?}", skipped.len(), skipped);
+    }
+    if tracks.is_empty() {
+        return Ok(None);
+    }
+
+    state.queue.set_queue(tracks, Some(0));
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    play_current_track(&app_handle).await
+}
+
 /// Set the entire queue (replaces existing)
 #[tauri::command]
-pub fn set_queue(tracks: Vec<QueueTrack>, start_index: Option<usize>, state: State<'_, AppState>) -> Result<(), String> {
+pub fn set_queue(
+    tracks: Vec<QueueTrack>,
+    start_index: Option<usize>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
     log::info!("Command: set_queue - {} tracks, start at {:?}", tracks.len(), start_index);
     state.queue.set_queue(tracks, start_index);
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
 /// Clear the queue
 #[tauri::command]
-pub fn clear_queue(state: State<'_, AppState>) -> Result<(), String> {
+pub fn clear_queue(state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     log::info!("Command: clear_queue");
     state.queue.clear();
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
 /// Remove a track from the queue by index
 #[tauri::command]
-pub fn remove_from_queue(index: usize, state: State<'_, AppState>) -> Result<Option<QueueTrack>, String> {
+pub fn remove_from_queue(
+    index: usize,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Option<QueueTrack>, String> {
     log::info!("Command: remove_from_queue - index {}", index);
-    Ok(state.queue.remove_track(index))
+    let removed = state.queue.remove_track(index);
+    if removed.is_some() {
+        let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    }
+    Ok(removed)
 }
 
 /// Move a track from one position to another in the queue
 #[tauri::command]
-pub fn move_queue_track(from_index: usize, to_index: usize, state: State<'_, AppState>) -> Result<bool, String> {
+pub fn move_queue_track(
+    from_index: usize,
+    to_index: usize,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<bool, String> {
     log::info!("Command: move_queue_track - from {} to {}", from_index, to_index);
-    Ok(state.queue.move_track(from_index, to_index))
+    let moved = state.queue.move_track(from_index, to_index);
+    if moved {
+        let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    }
+    Ok(moved)
+}
+
+/// Move several tracks (e.g. a multi-selection drag) as one block
+#[tauri::command]
+pub fn move_queue_tracks(
+    indices: Vec<usize>,
+    to_index: usize,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<bool, String> {
+    log::info!("Command: move_queue_tracks - {:?} to {}", indices, to_index);
+    let moved = state.queue.move_many(&indices, to_index);
+    if moved {
+        let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    }
+    Ok(moved)
 }
 
 /// Get current track in queue
@@ -71,11 +402,157 @@ pub fn peek_next_track(state: State<'_, AppState>) -> Result<Option<QueueTrack>,
     Ok(state.queue.peek_next())
 }
 
-/// Advance to next track and return it
+/// Payload of the `queue-empty` event
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct QueueEmptyEvent {
+    /// Track that played last
+    pub last_track_id: u64,
+    /// What was done about it; `Stop` when radio found nothing to add
+    pub action: QueueEndAction,
+    /// Tracks appended by radio
+    pub radio_tracks: usize,
+}
+
+/// Radio tracks for a seed track: top tracks of its artist and of similar
+/// artists, leaving out anything in `exclude`
+async fn radio_tracks(
+    client: &QobuzClient,
+    seed_track_id: u64,
+    exclude: &HashSet<u64>,
+) -> Result<Vec<QueueTrack>, ApiError> {
+    let seed = client.get_track(seed_track_id).await?;
+    let Some(artist) = seed.performer else {
+        return Ok(Vec::new());
+    };
+    let source = QueueSource {
+        kind: QueueSourceKind::Radio,
+        id: Some(artist.id.to_string()),
+        name: Some(format!("{} radio", artist.name)),
+    };
+    Ok(artist_radio_tracks(client, artist.id, exclude, RADIO_BATCH, &source).await)
+}
+
+/// Up to `limit` streamable top tracks of an artist and its similar artists,
+/// leaving out anything in `exclude`. Lookups that fail are skipped.
+pub(crate) async fn artist_radio_tracks(
+    client: &QobuzClient,
+    artist_id: u64,
+    exclude: &HashSet<u64>,
+    limit: usize,
+    source: &QueueSource,
+) -> Vec<QueueTrack> {
+    let mut artist_ids = vec![artist_id];
+    match client.get_similar_artists(artist_id, RADIO_SIMILAR_ARTISTS, 0).await {
+        Ok(similar) => artist_ids.extend(similar.items.iter().map(|a| a.id)),
+        Err(e) => log::debug!("No similar artists for radio seed {}: {}", artist_id, e),
+    }
+
+    let mut seen = exclude.clone();
+    let mut tracks = Vec::new();
+    for artist_id in artist_ids {
+        let top = match client.get_artist_top_tracks(artist_id, RADIO_TRACKS_PER_ARTIST).await {
+            Ok(top) => top,
+            Err(e) => {
+                log::debug!("No top tracks for radio artist {}: {}", artist_id, e);
+                continue;
+            }
+        };
+        for track in top.iter().filter(|t| t.streamable) {
+            if seen.insert(track.id) {
+                tracks.push(queue_track(track, None, source));
+            }
+        }
+        if tracks.len() >= limit {
+            break;
+        }
+    }
+    tracks.truncate(limit);
+    tracks
+}
+
+/// Handle the queue running out after `last`: with radio on, related tracks
+/// are appended and the first of them becomes current. Returns the track to
+/// play, or None when playback should stop. `on_empty` is called either way.
+async fn continue_after_queue_end<F>(
+    queue: &QueueManager,
+    client: &Mutex<QobuzClient>,
+    last: &QueueTrack,
+    on_empty: F,
+) -> Option<QueueTrack>
+where
+    F: FnOnce(&QueueEmptyEvent),
+{
+    let mut event = QueueEmptyEvent {
+        last_track_id: last.id,
+        action: QueueEndAction::Stop,
+        radio_tracks: 0,
+    };
+
+    // Local and Nostr tracks have no catalog entry to seed from
+    let mut next = None;
+    if queue.end_action() == QueueEndAction::Radio && !last.is_local && last.audio_url.is_none() {
+        let queued: HashSet<u64> = queue.track_ids().into_iter().collect();
+        let client = client.lock().await.clone();
+        match radio_tracks(&client, last.id, &queued).await {
+            Ok(tracks) if !tracks.is_empty() => {
+                event.action = QueueEndAction::Radio;
+                event.radio_tracks = tracks.len();
+                // The queue may have changed during the lookups
+                let start = queue.add_tracks(tracks);
+                next = queue.play_index(start);
+            }
+            Ok(_) => log::info!("Radio found nothing to add after track {}", last.id),
+            Err(e) => log::warn!("Radio failed after track {}: {}", last.id, e),
+        }
+    }
+
+    on_empty(&event);
+    next
+}
+
+/// Advance to next track and return it.
+///
+/// When the queue is exhausted a `queue-empty` event is emitted and, by the
+/// queue end action, playback either stops (position reset) or radio tracks
+/// are appended and the first one is returned.
 #[tauri::command]
-pub fn next_track(state: State<'_, AppState>) -> Result<Option<QueueTrack>, String> {
+pub async fn next_track(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<QueueTrack>, String> {
     log::info!("Command: next_track");
-    Ok(state.queue.next())
+    let last = state.queue.current_track();
+    if let Some(track) = state.queue.next() {
+        return Ok(Some(track));
+    }
+    let Some(last) = last else {
+        return Ok(None);
+    };
+
+    let next = continue_after_queue_end(&state.queue, &state.client, &last, |event| {
+        log::info!("Queue empty after track {} ({:?})", event.last_track_id, event.action);
+        let _ = app_handle.emit("queue-empty", event);
+    })
+    .await;
+
+    match next {
+        Some(track) => {
+            let _ = app_handle.emit("queue-changed", state.queue.get_state());
+            Ok(Some(track))
+        }
+        None => {
+            state.player.stop()?;
+            let _ = app_handle.emit(
+                "playback:state",
+                &PlaybackEvent {
+                    is_playing: false,
+                    position: 0,
+                    ..state.player.get_playback_event()
+                },
+            );
+            Ok(None)
+        }
+    }
 }
 
 /// Go to previous track and return it
@@ -94,9 +571,10 @@ pub fn play_queue_index(index: usize, state: State<'_, AppState>) -> Result<Opti
 
 /// Set shuffle mode
 #[tauri::command]
-pub fn set_shuffle(enabled: bool, state: State<'_, AppState>) -> Result<(), String> {
+pub fn set_shuffle(enabled: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
     log::info!("Command: set_shuffle - {}", enabled);
     state.queue.set_shuffle(enabled);
+    let _ = app_handle.emit("queue-changed", state.queue.get_state());
     Ok(())
 }
 
@@ -130,8 +608,379 @@ pub fn get_repeat(state: State<'_, AppState>) -> Result<String, String> {
     })
 }
 
+/// Apply the saved queue end action at startup
+pub fn load_queue_end_action(queue: &QueueManager, settings: &PlaybackSettingsState) {
+    let saved = settings
+        .lock()
+        .map_err(|e| e.to_string())
+        .and_then(|store| store.get_queue_end_radio());
+    match saved {
+        Ok(true) => queue.set_end_action(QueueEndAction::Radio),
+        Ok(false) => queue.set_end_action(QueueEndAction::Stop),
+        Err(e) => log::warn!("Using the default queue end action: {}", e),
+    }
+}
+
+/// Set what happens once the last track has played ("stop" or "radio")
+#[tauri::command]
+pub fn set_queue_end_action(
+    action: QueueEndAction,
+    state: State<'_, AppState>,
+    settings: State<'_, PlaybackSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: set_queue_end_action - {:?}", action);
+    settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .set_queue_end_radio(action == QueueEndAction::Radio)?;
+    state.queue.set_end_action(action);
+    Ok(())
+}
+
+/// Get what happens once the last track has played
+#[tauri::command]
+pub fn get_queue_end_action(state: State<'_, AppState>) -> Result<QueueEndAction, String> {
+    Ok(state.queue.end_action())
+}
+
+/// Set what happens when a queued track can't be played
+#[tauri::command]
+pub fn set_unplayable_policy(policy: UnplayablePolicy, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Command: set_unplayable_policy - {:?}", policy);
+    state.queue.set_unplayable_policy(policy);
+    Ok(())
+}
+
+/// Get what happens when a queued track can't be played
+#[tauri::command]
+pub fn get_unplayable_policy(state: State<'_, AppState>) -> Result<UnplayablePolicy, String> {
+    Ok(state.queue.unplayable_policy())
+}
+
+/// The current track can't be played (restricted, removed). Applies the
+/// unplayable policy and returns the track to play instead, or None once
+/// playback stops. Emits `track-unplayable` unless skipping silently. A
+/// report for a track that's no longer current is ignored (None, playback
+/// untouched).
+#[tauri::command]
+pub fn skip_unplayable_track(
+    track_id: u64,
+    reason: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<QueueTrack>, String> {
+    log::info!("Command: skip_unplayable_track - {} ({:?})", track_id, reason);
+    let Some(outcome) = state.queue.handle_unplayable(track_id, reason) else {
+        log::info!("Track {} is no longer current, ignoring", track_id);
+        return Ok(None);
+    };
+
+    if outcome.breaker_tripped {
+        log::warn!("{} unplayable tracks in a row, stopping", outcome.consecutive);
+    }
+    if outcome.notify() {
+        let _ = app_handle.emit("track-unplayable", &outcome);
+    }
+
+    if outcome.next.is_some() {
+        let _ = app_handle.emit("queue-changed", state.queue.get_state());
+    } else {
+        state.player.stop()?;
+        let _ = app_handle.emit(
+            "playback:state",
+            &PlaybackEvent {
+                is_playing: false,
+                position: 0,
+                ..state.player.get_playback_event()
+            },
+        );
+    }
+    Ok(outcome.next)
+}
+
 /// Get full queue state for frontend
 #[tauri::command]
 pub fn get_queue_state(state: State<'_, AppState>) -> Result<QueueState, String> {
     Ok(state.queue.get_state())
 }
+
+/// Get the now playing track with its queue index and originating source.
+///
+/// Album and playlist sources are re-fetched so the UI can tell whether the
+/// source was removed or no longer contains the track.
+#[tauri::command]
+pub async fn get_now_playing_context(state: State<'_, AppState>) -> Result<Option<NowPlayingContext>, String> {
+    let Some(mut context) = state.queue.now_playing_context() else {
+        return Ok(None);
+    };
+
+    if let Some(source) = &context.source {
+        context.source_status = resolve_source_status(source, context.track.id, &state).await;
+    }
+
+    Ok(Some(context))
+}
+
+async fn resolve_source_status(source: &QueueSource, track_id: u64, state: &AppState) -> SourceStatus {
+    let Some(id) = source.id.as_deref() else {
+        return SourceStatus::Unknown;
+    };
+
+    let tracks = match source.kind {
+        QueueSourceKind::Album => {
+            let client = state.client.lock().await;
+            match client.get_album(id).await {
+                Ok(album) => album.tracks,
+                Err(ApiError::ApiResponse(e)) => {
+                    log::debug!("Now playing source album {} unavailable: {}", id, e);
+                    return SourceStatus::Removed;
+                }
+                Err(_) => return SourceStatus::Unknown,
+            }
+        }
+        QueueSourceKind::Playlist => {
+            let Ok(playlist_id) = id.parse::<u64>() else {
+                return SourceStatus::Unknown;
+            };
+            let client = state.client.lock().await;
+            match client.get_playlist(playlist_id).await {
+                // Error payloads deserialize into a default playlist
+                Ok(playlist) if playlist.id == playlist_id => playlist.tracks,
+                Ok(_) => {
+                    log::debug!("Now playing source playlist {} no longer exists", id);
+                    return SourceStatus::Removed;
+                }
+                Err(_) => return SourceStatus::Unknown,
+            }
+        }
+        _ => return SourceStatus::Unknown,
+    };
+
+    match tracks {
+        Some(container) if container.items.iter().any(|t| t.id == track_id) => SourceStatus::Available,
+        Some(_) => SourceStatus::TrackRemoved,
+        None => SourceStatus::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::mock_client;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn track(id: u64, disc: u32, number: u32, streamable: bool) -> serde_json::Value {
+        serde_json::json!({
+            "id": id, "title": format!("Track {}", id), "media_number": disc,
+            "track_number": number, "duration": 60, "streamable": streamable
+        })
+    }
+
+    #[tokio::test]
+    async fn test_append_multi_disc_album_in_order_with_source() {
+        let server = MockServer::start().await;
+        let album = |items: Vec<serde_json::Value>| {
+            serde_json::json!({
+                "id": "box", "title": "Box Set", "artist": { "id": 5, "name": "Band" },
+                "image": { "large": "http://art/large.jpg" },
+                "tracks": { "items": items, "total": 5 }
+            })
+        };
+        // Tracks come back over two pages, neither in disc order
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .and(query_param_is_missing("offset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(album(vec![
+                track(21, 2, 1, true),
+                track(12, 1, 2, true),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .and(query_param("offset", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(album(vec![
+                track(11, 1, 1, true),
+                track(22, 2, 2, true),
+                track(23, 2, 3, false),
+            ])))
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let album = client.get_album_all_tracks("box").await.unwrap();
+        let (tracks, skipped) = album_queue_tracks(&album);
+        assert_eq!(skipped, vec![23]);
+
+        let queue = QueueManager::new();
+        let mut existing = tracks[0].clone();
+        existing.id = 1;
+        existing.source = None;
+        queue.set_queue(vec![existing], Some(0));
+        queue.insert_tracks(tracks, QueueInsertMode::Append);
+
+        let state = queue.get_state();
+        let ids: Vec<u64> = state.upcoming.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![11, 12, 21, 22]);
+        for queued in &state.upcoming {
+            let source = queued.source.as_ref().unwrap();
+            assert_eq!(source.kind, QueueSourceKind::Album);
+            assert_eq!(source.id.as_deref(), Some("box"));
+            assert_eq!(source.name.as_deref(), Some("Box Set"));
+            assert_eq!(queued.artist, "Band");
+            assert_eq!(queued.artwork_url.as_deref(), Some("http://art/large.jpg"));
+        }
+        assert!(state.current_track.unwrap().source.is_none());
+    }
+
+    #[test]
+    fn test_continue_album_positions_queue_at_chosen_track() {
+        let album = |items: Vec<serde_json::Value>| -> Album {
+            serde_json::from_value(serde_json::json!({
+                "id": "lp", "title": "Double LP", "artist": { "id": 5, "name": "Band" },
+                "tracks": { "items": items, "total": 4 }
+            }))
+            .unwrap()
+        };
+        let mut double_lp = album(vec![
+            track(11, 1, 1, true),
+            track(12, 1, 2, false),
+            track(21, 2, 1, true),
+            track(22, 2, 2, true),
+        ]);
+        // Whatever order the tracks arrive in
+        double_lp.tracks.as_mut().unwrap().items.reverse();
+
+        let queue = QueueManager::new();
+        let (tracks, start) = continue_album_queue(&double_lp, 21);
+        queue.set_queue(tracks, Some(start));
+        assert_eq!(queue.track_ids(), vec![11, 21, 22]);
+        assert_eq!(queue.current_track().map(|t| t.id), Some(21));
+        assert_eq!(queue.next().map(|t| t.id), Some(22));
+
+        // An unavailable choice continues with the next playable track
+        let (tracks, start) = continue_album_queue(&double_lp, 12);
+        assert_eq!(tracks[start].id, 21);
+
+        // A single is a queue of one
+        let (tracks, start) = continue_album_queue(&album(vec![track(7, 1, 1, true)]), 7);
+        assert_eq!((tracks.len(), start), (1, 0));
+        assert_eq!(tracks[0].source.as_ref().and_then(|s| s.id.as_deref()), Some("lp"));
+    }
+
+    #[tokio::test]
+    async fn test_drained_queue_stops_or_continues_with_radio() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET))
+            .and(query_param("track_id", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": 2, "title": "Track 2", "performer": { "id": 5, "name": "Band" }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::ARTIST_GET_SIMILAR))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "artists": { "items": [{ "id": 6, "name": "Other Band" }], "total": 1, "limit": 5, "offset": 0 }
+            })))
+            .mount(&server)
+            .await;
+        for (artist_id, tracks) in [
+            ("5", vec![track(2, 1, 2, true), track(30, 1, 1, true)]),
+            ("6", vec![track(31, 1, 1, true), track(32, 1, 2, false)]),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(paths::ARTIST_GET))
+                .and(query_param("artist_id", artist_id))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": artist_id.parse::<u64>().unwrap(), "name": "Artist",
+                    "tracks": { "items": tracks, "total": 2 }
+                })))
+                .mount(&server)
+                .await;
+        }
+        let client = Mutex::new(mock_client(&server));
+
+        let source = QueueSource { kind: QueueSourceKind::Album, id: None, name: None };
+        let tracks: Vec<QueueTrack> = [1, 2]
+            .iter()
+            .map(|&id| queue_track(&serde_json::from_value(track(id, 1, id as u32, true)).unwrap(), None, &source))
+            .collect();
+        let queue = QueueManager::new();
+
+        // Drain the queue the way the player does at each track end
+        let drain = |queue: &QueueManager| {
+            queue.set_queue(tracks.clone(), Some(0));
+            assert_eq!(queue.next().unwrap().id, 2);
+            let last = queue.current_track().unwrap();
+            assert!(queue.next().is_none());
+            last
+        };
+
+        // Stop (default): the event fires and nothing is left to play
+        let last = drain(&queue);
+        let mut events = Vec::new();
+        let next = continue_after_queue_end(&queue, &client, &last, |e| events.push(e.clone())).await;
+        assert!(next.is_none());
+        assert_eq!(
+            events,
+            vec![QueueEmptyEvent { last_track_id: 2, action: QueueEndAction::Stop, radio_tracks: 0 }]
+        );
+        assert_eq!(queue.track_ids(), vec![1, 2]);
+
+        // Radio: related tracks not already queued are appended and playback continues
+        queue.set_end_action(QueueEndAction::Radio);
+        let last = drain(&queue);
+        let mut events = Vec::new();
+        let next = continue_after_queue_end(&queue, &client, &last, |e| events.push(e.clone())).await;
+        assert_eq!(next.unwrap().id, 30);
+        assert_eq!(
+            events,
+            vec![QueueEmptyEvent { last_track_id: 2, action: QueueEndAction::Radio, radio_tracks: 2 }]
+        );
+        assert_eq!(queue.track_ids(), vec![1, 2, 30, 31]);
+        let current = queue.current_track().unwrap();
+        assert_eq!(current.id, 30);
+        let radio = current.source.unwrap();
+        assert_eq!(radio.kind, QueueSourceKind::Radio);
+        assert_eq!(radio.name.as_deref(), Some("Band radio"));
+    }
+
+    #[test]
+    fn test_favorites_queue_follows_sort_and_skips_unavailable() {
+        let mut cache = crate::api_cache::ApiCache::new(std::path::Path::new(":memory:")).unwrap();
+        let favorite = |id: u64, title: &str, artist: &str, favorited_at: i64, streamable: bool| {
+            serde_json::json!({
+                "id": id, "title": title, "duration": 100, "streamable": streamable,
+                "performer": { "id": id, "name": artist }, "favorited_at": favorited_at
+            })
+        };
+        let added = vec![
+            favorite(1, "Carousel", "Bea", 300, true),
+            favorite(2, "Anthem", "Cid", 100, true),
+            favorite(3, "Blues", "Abe", 200, true),
+            favorite(4, "Dusk", "Dee", 400, false),
+        ];
+        cache.apply_favorites_delta(1, "tracks", &added, &[], true, 500).unwrap();
+
+        let order = |sort| {
+            let (tracks, skipped) = favorites_queue_tracks(cache.synced_favorites(1, "tracks").unwrap(), sort);
+            assert_eq!(skipped, vec![4]);
+            tracks.iter().map(|t| t.id).collect::<Vec<_>>()
+        };
+        assert_eq!(order(FavoritesSort::DateAddedDesc), vec![1, 3, 2]);
+        assert_eq!(order(FavoritesSort::DateAddedAsc), vec![2, 3, 1]);
+        assert_eq!(order(FavoritesSort::Title), vec![2, 3, 1]);
+        assert_eq!(order(FavoritesSort::Artist), vec![3, 1, 2]);
+
+        // Duplicates are queued once, and the queue remembers where it came from
+        let mut items = cache.synced_favorites(1, "tracks").unwrap();
+        items.push(items[0].clone());
+        let (tracks, _) = favorites_queue_tracks(items, FavoritesSort::DateAddedDesc);
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].source.as_ref().map(|s| s.kind), Some(QueueSourceKind::Favorites));
+    }
+}
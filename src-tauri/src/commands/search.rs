@@ -1,11 +1,20 @@
 //! Search commands
 
-use tauri::State;
+use std::future::Future;
+use tauri::{AppHandle, Emitter, State};
 
-use crate::api::{Album, Artist, ArtistAlbums, SearchResultsPage, Track};
-use crate::api_cache::ApiCacheState;
+use crate::api::{
+    Album, Artist, ArtistAlbums, ArtistPage, Editorial, EditorialType, RelatedRelease,
+    ReleaseAvailability, SearchResultsPage, Suggestion, Track,
+};
+use crate::api_cache::fetch::{fetch_cached, parse_cached, to_cache_json, FetchStrategy, Freshness};
+use crate::api_cache::{ApiCache, ApiCacheState};
+use crate::offline::OfflineState;
 use crate::AppState;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchAllResults {
@@ -14,18 +23,104 @@ pub struct SearchAllResults {
     pub artists: SearchResultsPage<Artist>,
 }
 
+/// Most cached matches an offline search looks through
+const MAX_CACHED_RESULTS: usize = 500;
+
+/// A search page, flagged when it came from the local cache
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchPage<T> {
+    #[serde(flatten)]
+    pub page: SearchResultsPage<T>,
+    pub from_cache: bool,
+}
+
+/// Search and cache the page, as `strategy` says. An unexpired page of
+/// the same search counts as a cache hit; when the API can't be reached,
+/// or with `FetchStrategy::CacheOnly` (manual offline mode), matching
+/// cached items are searched instead (see `ApiCache::search_cached`).
+async fn search_with_cache_fallback<T, F, Fut>(
+    kind: &str,
+    query: &str,
+    limit: u32,
+    offset: u32,
+    strategy: FetchStrategy,
+    cache: &Mutex<ApiCache>,
+    fetch: F,
+) -> Result<SearchPage<T>, String>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = crate::api::error::Result<SearchResultsPage<T>>>,
+{
+    let fetched = fetch_cached(
+        strategy,
+        cache,
+        |cache, freshness| match freshness {
+            Freshness::Fresh => {
+                let page: Option<SearchResultsPage<T>> =
+                    parse_cached(cache.get_search_page(kind, query, offset, None)?)?;
+                // A page cached with a smaller limit doesn't answer this search
+                Ok(page.filter(|page| page.limit >= limit).map(|mut page| {
+                    page.items.truncate(limit as usize);
+                    page.limit = limit;
+                    page
+                }))
+            }
+            Freshness::Any => cached_matches(cache, kind, query, limit, offset).map(Some),
+        },
+        |cache, page| cache.set_search_page(kind, query, offset, &to_cache_json(page)?),
+        fetch,
+    )
+    .await?;
+    Ok(SearchPage { page: fetched.value, from_cache: fetched.from_cache })
+}
+
+/// A page of the cached items matching `query`
+fn cached_matches<T: DeserializeOwned>(
+    cache: &ApiCache,
+    kind: &str,
+    query: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<SearchResultsPage<T>, String> {
+    let matches = cache.search_cached(kind, query, MAX_CACHED_RESULTS)?;
+    let total = matches.len() as u32;
+    let items = matches
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .filter_map(|item| serde_json::from_value(item).ok())
+        .collect();
+    Ok(SearchResultsPage { items, total, offset, limit })
+}
+
 #[tauri::command]
 pub async fn search_albums(
     query: String,
     limit: Option<u32>,
     offset: Option<u32>,
+    strategy: Option<FetchStrategy>,
+    request_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<SearchResultsPage<Album>, String> {
-    let client = state.client.lock().await;
-    client
-        .search_albums(&query, limit.unwrap_or(20), offset.unwrap_or(0))
-        .await
-        .map_err(|e| e.to_string())
+    api_cache: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
+) -> Result<SearchPage<Album>, String> {
+    let (limit, offset) = (limit.unwrap_or(20), offset.unwrap_or(0));
+    let query = query.as_str();
+    search_with_cache_fallback(
+        "albums",
+        query,
+        limit,
+        offset,
+        offline_state.fetch_strategy(strategy),
+        &api_cache.cache,
+        || {
+            state.requests.run_with(request_id.as_deref(), &state.client, |client| async move {
+                client.search_albums(query, limit, offset).await
+            })
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -33,13 +128,28 @@ pub async fn search_tracks(
     query: String,
     limit: Option<u32>,
     offset: Option<u32>,
+    strategy: Option<FetchStrategy>,
+    request_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<SearchResultsPage<Track>, String> {
-    let client = state.client.lock().await;
-    client
-        .search_tracks(&query, limit.unwrap_or(20), offset.unwrap_or(0))
-        .await
-        .map_err(|e| e.to_string())
+    api_cache: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
+) -> Result<SearchPage<Track>, String> {
+    let (limit, offset) = (limit.unwrap_or(20), offset.unwrap_or(0));
+    let query = query.as_str();
+    search_with_cache_fallback(
+        "tracks",
+        query,
+        limit,
+        offset,
+        offline_state.fetch_strategy(strategy),
+        &api_cache.cache,
+        || {
+            state.requests.run_with(request_id.as_deref(), &state.client, |client| async move {
+                client.search_tracks(query, limit, offset).await
+            })
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -47,11 +157,15 @@ pub async fn search_artists(
     query: String,
     limit: Option<u32>,
     offset: Option<u32>,
+    request_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<SearchResultsPage<Artist>, String> {
-    let client = state.client.lock().await;
-    client
-        .search_artists(&query, limit.unwrap_or(20), offset.unwrap_or(0))
+    let (limit, offset) = (limit.unwrap_or(20), offset.unwrap_or(0));
+    state
+        .requests
+        .run_with(request_id.as_deref(), &state.client, |client| async move {
+            client.search_artists(&query, limit, offset).await
+        })
         .await
         .map_err(|e| e.to_string())
 }
@@ -59,54 +173,255 @@ pub async fn search_artists(
 #[tauri::command]
 pub async fn search_all(
     query: String,
+    request_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<SearchAllResults, String> {
-    let client = state.client.lock().await;
-    
-    let (albums_result, tracks_result, artists_result) = tokio::join!(
-        client.search_albums(&query, 30, 0),
-        client.search_tracks(&query, 8, 0),
-        client.search_artists(&query, 12, 0)
-    );
+    state
+        .requests
+        .run_with(request_id.as_deref(), &state.client, |client| async move {
+            let (albums_result, tracks_result, artists_result) = tokio::join!(
+                client.search_albums(&query, 30, 0),
+                client.search_tracks(&query, 8, 0),
+                client.search_artists(&query, 12, 0)
+            );
+
+            Ok(SearchAllResults {
+                albums: albums_result?,
+                tracks: tracks_result?,
+                artists: artists_result?,
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Event carrying each page of a streamed search
+const SEARCH_PAGE_EVENT: &str = "search-results-page";
+const DEFAULT_STREAM_PAGE_SIZE: u32 = 50;
+const MAX_STREAM_PAGE_SIZE: u32 = 500;
+/// Infinite scroll stops after this many items
+const MAX_STREAMED_ITEMS: u32 = 500;
+
+/// What `search_stream` pages through
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Albums,
+    Tracks,
+    Artists,
+}
+
+/// Payload of a `search-results-page` event
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchPageEvent<T> {
+    pub request_id: String,
+    pub items: Vec<T>,
+    pub offset: u32,
+    pub has_more: bool,
+}
+
+/// Fetch pages until the results (or `max_items`) run out, handing each
+/// page to `emit`. Returns the items delivered.
+async fn stream_pages<T, F, Fut, E>(
+    request_id: &str,
+    page_size: u32,
+    max_items: u32,
+    mut fetch: F,
+    mut emit: E,
+) -> crate::api::error::Result<u32>
+where
+    F: FnMut(u32, u32) -> Fut,
+    Fut: Future<Output = crate::api::error::Result<SearchResultsPage<T>>>,
+    E: FnMut(SearchPageEvent<T>),
+{
+    let mut offset = 0;
+    while offset < max_items {
+        let page = fetch(offset, page_size.min(max_items - offset)).await?;
+
+        let next = offset + page.items.len() as u32;
+        let has_more = next > offset && next < page.total.min(max_items);
+        emit(SearchPageEvent { request_id: request_id.to_string(), items: page.items, offset, has_more });
+
+        offset = next;
+        if !has_more {
+            break;
+        }
+    }
+    Ok(offset)
+}
+
+fn emit_page<T: Serialize + Clone>(app_handle: &AppHandle) -> impl FnMut(SearchPageEvent<T>) + '_ {
+    move |event| {
+        let _ = app_handle.emit(SEARCH_PAGE_EVENT, event);
+    }
+}
 
-    Ok(SearchAllResults {
-        albums: albums_result.map_err(|e| e.to_string())?,
-        tracks: tracks_result.map_err(|e| e.to_string())?,
-        artists: artists_result.map_err(|e| e.to_string())?,
-    })
+/// Search page by page, emitting `search-results-page` events as each page
+/// arrives. Starting another request under the same `request_id` (or
+/// `cancel_request`) stops this one. Returns the number of items delivered.
+#[tauri::command]
+pub async fn search_stream(
+    kind: SearchKind,
+    query: String,
+    request_id: String,
+    page_size: Option<u32>,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    log::info!("Command: search_stream {:?} \"{}\" (request {})", kind, query, request_id);
+
+    let page_size = page_size.unwrap_or(DEFAULT_STREAM_PAGE_SIZE).clamp(1, MAX_STREAM_PAGE_SIZE);
+    let max_items = MAX_STREAMED_ITEMS;
+    let client = &state.client;
+    let query = query.as_str();
+    let request_id = request_id.as_str();
+
+    // The client is locked per page so other commands can run in between
+    let stream = async {
+        match kind {
+            SearchKind::Albums => {
+                stream_pages(request_id, page_size, max_items, |offset, limit| async move {
+                    client.lock().await.search_albums(query, limit, offset).await
+                }, emit_page(&app_handle))
+                .await
+            }
+            SearchKind::Tracks => {
+                stream_pages(request_id, page_size, max_items, |offset, limit| async move {
+                    client.lock().await.search_tracks(query, limit, offset).await
+                }, emit_page(&app_handle))
+                .await
+            }
+            SearchKind::Artists => {
+                stream_pages(request_id, page_size, max_items, |offset, limit| async move {
+                    client.lock().await.search_artists(query, limit, offset).await
+                }, emit_page(&app_handle))
+                .await
+            }
+        }
+    };
+    state.requests.run(Some(request_id), stream).await.map_err(|e| e.to_string())
+}
+
+/// Abort a request started with a `request_id` (searches, streamed searches,
+/// album or track fetches); returns false if none is in flight under that id
+#[tauri::command]
+pub fn cancel_request(request_id: String, state: State<'_, AppState>) -> bool {
+    log::info!("Command: cancel_request {}", request_id);
+    state.requests.cancel(&request_id)
+}
+
+/// Type-ahead suggestions; empty for queries too short to suggest anything
+#[tauri::command]
+pub async fn get_search_suggestions(
+    query: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Suggestion>, String> {
+    log::info!("Command: get_search_suggestions {}", query);
+    let client = state.client.lock().await;
+    client.get_suggestions(&query).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn get_album(
     album_id: String,
+    request_id: Option<String>,
+    strategy: Option<FetchStrategy>,
     state: State<'_, AppState>,
     cache_state: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
 ) -> Result<Album, String> {
-    // Check cache first
+    let album_id = album_id.as_str();
+    let fetched = fetch_cached(
+        offline_state.fetch_strategy(strategy),
+        &cache_state.cache,
+        |cache, freshness| parse_cached(cache.get_album(album_id, freshness.ttl())?),
+        |cache, album| cache.set_album(album_id, &to_cache_json(album)?),
+        || {
+            state.requests.run_with(request_id.as_deref(), &state.client, |client| async move {
+                client.get_album(album_id).await
+            })
+        },
+    )
+    .await?;
+    log::debug!("Album {} (from cache: {})", album_id, fetched.from_cache);
+    Ok(fetched.value)
+}
+
+/// Other releases of an album's series or box set, for moving between
+/// volumes; empty when the album belongs to neither. Cached with the album.
+#[tauri::command]
+pub async fn get_related_releases(
+    album_id: String,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
+) -> Result<Vec<RelatedRelease>, String> {
+    log::info!("Command: get_related_releases {}", album_id);
+    let album = get_album(album_id, None, None, state, cache_state, offline_state).await?;
+    Ok(album.related_releases())
+}
+
+/// Albums fetched in a batch, with per-id failures reported separately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlbumsBatch {
+    pub albums: HashMap<String, Album>,
+    pub errors: HashMap<String, String>,
+}
+
+/// Get several albums at once (e.g. the distinct albums of a playlist).
+/// Cached albums are served from the API cache; misses are fetched concurrently.
+#[tauri::command]
+pub async fn get_albums(
+    album_ids: Vec<String>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<AlbumsBatch, String> {
+    let mut batch = AlbumsBatch {
+        albums: HashMap::new(),
+        errors: HashMap::new(),
+    };
+
     {
         let cache = cache_state.cache.lock().await;
-        if let Some(cached_data) = cache.get_album(&album_id, None)? {
-            log::debug!("Cache hit for album {}", album_id);
-            return serde_json::from_str(&cached_data)
-                .map_err(|e| format!("Failed to parse cached album: {}", e));
+        for (album_id, cached_data) in cache.get_albums(&album_ids, None)? {
+            let album = serde_json::from_str(&cached_data)
+                .map_err(|e| format!("Failed to parse cached album: {}", e))?;
+            batch.albums.insert(album_id, album);
         }
     }
 
-    // Cache miss - fetch from API
-    log::debug!("Cache miss for album {}, fetching from API", album_id);
-    let client = state.client.lock().await;
-    let album = client.get_album(&album_id).await.map_err(|e| e.to_string())?;
+    let misses: Vec<String> = album_ids
+        .into_iter()
+        .filter(|id| !batch.albums.contains_key(id))
+        .collect();
 
-    // Cache the result
-    {
-        let cache = cache_state.cache.lock().await;
-        let json = serde_json::to_string(&album)
-            .map_err(|e| format!("Failed to serialize album: {}", e))?;
-        cache.set_album(&album_id, &json)?;
-        log::debug!("Cached album {}", album_id);
+    if misses.is_empty() {
+        return Ok(batch);
     }
 
-    Ok(album)
+    log::debug!("Fetching {} uncached albums from API", misses.len());
+    let results = {
+        let client = state.client.lock().await;
+        client.get_albums(&misses).await
+    };
+
+    let cache = cache_state.cache.lock().await;
+    for (album_id, result) in results {
+        match result {
+            Ok(album) => {
+                let json = serde_json::to_string(&album)
+                    .map_err(|e| format!("Failed to serialize album: {}", e))?;
+                cache.set_album(&album_id, &json)?;
+                batch.albums.insert(album_id, album);
+            }
+            Err(e) => {
+                log::warn!("Failed to fetch album {}: {}", album_id, e);
+                batch.errors.insert(album_id, e.to_string());
+            }
+        }
+    }
+
+    Ok(batch)
 }
 
 /// Get featured albums by type (new-releases, press-awards)
@@ -124,9 +439,52 @@ pub async fn get_featured_albums(
         .map_err(|e| e.to_string())
 }
 
+/// Get an editorial section (featured-focus, album-of-the-week,
+/// playlists-of-the-week), optionally narrowed to one genre
+#[tauri::command]
+pub async fn get_editorial(
+    editorial_type: String,
+    genre_id: Option<u64>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<Editorial, String> {
+    log::info!("Command: get_editorial {} (genre {:?})", editorial_type, genre_id);
+
+    let editorial_type: EditorialType = editorial_type.parse()?;
+    let limit = limit.unwrap_or(12);
+    let genre_key = genre_id.unwrap_or(0);
+    let client = state.client.lock().await.clone();
+    let locale = client.get_locale().await;
+
+    {
+        let cache = cache_state.cache.lock().await;
+        if let Some(cached) = cache.get_editorial(editorial_type.key(), genre_key, limit, &locale)? {
+            log::debug!("Cache hit for editorial {}", editorial_type.key());
+            return serde_json::from_str(&cached)
+                .map_err(|e| format!("Failed to parse cached editorial: {}", e));
+        }
+    }
+
+    let editorial = client
+        .get_editorial(editorial_type, genre_id, limit)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    {
+        let cache = cache_state.cache.lock().await;
+        let json = serde_json::to_string(&editorial)
+            .map_err(|e| format!("Failed to serialize editorial: {}", e))?;
+        cache.set_editorial(editorial_type.key(), genre_key, limit, &locale, &json)?;
+    }
+
+    Ok(editorial)
+}
+
 #[tauri::command]
 pub async fn get_track(
     track_id: u64,
+    request_id: Option<String>,
     state: State<'_, AppState>,
     cache_state: State<'_, ApiCacheState>,
 ) -> Result<Track, String> {
@@ -142,8 +500,13 @@ pub async fn get_track(
 
     // Cache miss - fetch from API
     log::debug!("Cache miss for track {}, fetching from API", track_id);
-    let client = state.client.lock().await;
-    let track = client.get_track(track_id).await.map_err(|e| e.to_string())?;
+    let track = state
+        .requests
+        .run_with(request_id.as_deref(), &state.client, |client| async move {
+            client.get_track(track_id).await
+        })
+        .await
+        .map_err(|e| e.to_string())?;
 
     // Cache the result
     {
@@ -156,12 +519,45 @@ pub async fn get_track(
     Ok(track)
 }
 
+/// Release dates and streaming window of a track, so the UI can show
+/// "available on <date>" for releases that can't be played yet
+#[tauri::command]
+pub async fn get_track_availability(
+    track_id: u64,
+    state: State<'_, AppState>,
+) -> Result<ReleaseAvailability, String> {
+    let client = state.client.lock().await;
+    let track = client.get_track(track_id).await.map_err(|e| e.to_string())?;
+
+    // Album tracks often leave the dates to the album
+    let album = match &track.album {
+        Some(summary) if track.release_date_stream.is_none() && track.streamable_at.is_none() => {
+            client.get_album(&summary.id).await.ok()
+        }
+        _ => None,
+    };
+    Ok(track.availability(album.as_ref(), chrono::Utc::now().date_naive()))
+}
+
+/// Release dates and streaming window of an album
+#[tauri::command]
+pub async fn get_album_availability(
+    album_id: String,
+    state: State<'_, AppState>,
+) -> Result<ReleaseAvailability, String> {
+    let client = state.client.lock().await;
+    let album = client.get_album(&album_id).await.map_err(|e| e.to_string())?;
+    Ok(album.availability(chrono::Utc::now().date_naive()))
+}
+
 /// Get artist with albums
 #[tauri::command]
 pub async fn get_artist(
     artist_id: u64,
+    strategy: Option<FetchStrategy>,
     state: State<'_, AppState>,
     cache_state: State<'_, ApiCacheState>,
+    offline_state: State<'_, OfflineState>,
 ) -> Result<Artist, String> {
     log::info!("Command: get_artist {}", artist_id);
 
@@ -171,33 +567,53 @@ pub async fn get_artist(
         client.get_locale().await
     };
 
-    // Check cache first
+    let fetched = fetch_cached(
+        offline_state.fetch_strategy(strategy),
+        &cache_state.cache,
+        |cache, freshness| parse_cached(cache.get_artist(artist_id, &locale, freshness.ttl())?),
+        |cache, artist| cache.set_artist(artist_id, &locale, &to_cache_json(artist)?),
+        || async { state.client.lock().await.get_artist(artist_id, true).await },
+    )
+    .await?;
+    log::debug!("Artist {} (locale: {}, from cache: {})", artist_id, locale, fetched.from_cache);
+    Ok(fetched.value)
+}
+
+/// Get an artist's bio, top tracks, albums and similar artists in one call.
+/// Sections that failed are listed in `errors`; only complete pages are cached.
+#[tauri::command]
+pub async fn get_artist_page(
+    artist_id: u64,
+    state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<ArtistPage, String> {
+    log::info!("Command: get_artist_page {}", artist_id);
+
+    let client = state.client.lock().await;
+    let locale = client.get_locale().await;
+
     {
         let cache = cache_state.cache.lock().await;
-        if let Some(cached_data) = cache.get_artist(artist_id, &locale, None)? {
-            log::debug!("Cache hit for artist {} (locale: {})", artist_id, locale);
+        if let Some(cached_data) = cache.get_artist_page(artist_id, &locale, None)? {
+            log::debug!("Cache hit for artist page {} (locale: {})", artist_id, locale);
             return serde_json::from_str(&cached_data)
-                .map_err(|e| format!("Failed to parse cached artist: {}", e));
+                .map_err(|e| format!("Failed to parse cached artist page: {}", e));
         }
     }
 
-    // Cache miss - fetch from API
-    log::debug!("Cache miss for artist {} (locale: {}), fetching from API", artist_id, locale);
-    let client = state.client.lock().await;
-    let artist = client
-        .get_artist(artist_id, true)
+    let page = client
+        .get_artist_page(artist_id)
         .await
         .map_err(|e| e.to_string())?;
 
-    // Cache the result
-    {
+    if page.is_complete() {
         let cache = cache_state.cache.lock().await;
-        let json = serde_json::to_string(&artist)
-            .map_err(|e| format!("Failed to serialize artist: {}", e))?;
-        cache.set_artist(artist_id, &locale, &json)?;
+        let json = serde_json::to_string(&page)
+            .map_err(|e| format!("Failed to serialize artist page: {}", e))?;
+        cache.set_artist_page(artist_id, &locale, &json)?;
     }
 
-    Ok(artist)
+    Ok(page)
 }
 
 /// Get artist detail with albums, playlists, and appears-on tracks
@@ -264,3 +680,120 @@ pub async fn get_similar_artists(
         .await
         .map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::mock_client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock_track_page(server: &MockServer, offset: u32, ids: &[u64]) {
+        let items: Vec<_> = ids
+            .iter()
+            .map(|id| serde_json::json!({ "id": id, "title": format!("Track {}", id), "duration": 200 }))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_SEARCH))
+            .and(query_param("offset", offset.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tracks": { "items": items, "total": 5, "offset": offset, "limit": 2 }
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_pages_stream_in_order_and_stop_when_cancelled() {
+        let server = MockServer::start().await;
+        mock_track_page(&server, 0, &[1, 2]).await;
+        mock_track_page(&server, 2, &[3, 4]).await;
+        mock_track_page(&server, 4, &[5]).await;
+
+        let client = mock_client(&server);
+        let fetch = |offset, limit| {
+            let client = &client;
+            async move { client.search_tracks("miles", limit, offset).await }
+        };
+        let requests = client.requests();
+
+        let mut pages = Vec::new();
+        let delivered = stream_pages("search", 2, 100, fetch, |event| {
+            let ids: Vec<u64> = event.items.iter().map(|t| t.id).collect();
+            pages.push((event.request_id, event.offset, ids, event.has_more));
+        })
+        .await
+        .unwrap();
+        assert_eq!(delivered, 5);
+        assert_eq!(
+            pages,
+            vec![
+                ("search".to_string(), 0, vec![1, 2], true),
+                ("search".to_string(), 2, vec![3, 4], true),
+                ("search".to_string(), 4, vec![5], false),
+            ]
+        );
+
+        // Cancelling after the first page stops further pages
+        let mut offsets = Vec::new();
+        let stream = stream_pages("search", 2, 100, fetch, |event| {
+            offsets.push(event.offset);
+            assert!(requests.cancel("search"));
+        });
+        let result = requests.run(Some("search"), stream).await;
+        assert!(matches!(result, Err(crate::api::ApiError::Cancelled)));
+        assert_eq!(offsets, vec![0]);
+        assert!(!requests.cancel("search"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_search_returns_cached_subset() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_SEARCH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tracks": {
+                    "items": [
+                        { "id": 1, "title": "So What", "duration": 545, "performer": { "id": 9, "name": "Miles Davis" } },
+                        { "id": 2, "title": "Blue in Green", "duration": 337, "performer": { "id": 9, "name": "Miles Davis" } },
+                        { "id": 3, "title": "Naima", "duration": 261, "performer": { "id": 8, "name": "John Coltrane" } }
+                    ],
+                    "total": 3, "offset": 0, "limit": 20
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let cache = Mutex::new(ApiCache::new(std::path::Path::new(":memory:")).unwrap());
+
+        let (cache, client) = (&cache, &client);
+        let search = move |query: &'static str, limit: u32, strategy: FetchStrategy| {
+            search_with_cache_fallback("tracks", query, limit, 0, strategy, cache, move || {
+                client.search_tracks(query, limit, 0)
+            })
+        };
+
+        let online = search("Jazz", 20, FetchStrategy::CacheFirst).await.unwrap();
+        assert!(!online.from_cache);
+        assert_eq!(online.page.items.len(), 3);
+
+        // The same search again is answered by the cached page
+        let again = search(" jazz ", 2, FetchStrategy::CacheFirst).await.unwrap();
+        assert!(again.from_cache);
+        assert_eq!(again.page.items.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(again.page.total, 3);
+
+        // Offline: no request is made, matching cached tracks come back flagged
+        let offline = search("miles", 20, FetchStrategy::CacheOnly).await.unwrap();
+        assert!(offline.from_cache);
+        let ids: Vec<u64> = offline.page.items.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(offline.page.total, 2);
+
+        let offline = search("blue DAVIS", 20, FetchStrategy::CacheOnly).await.unwrap();
+        assert_eq!(offline.page.items.iter().map(|t| t.id).collect::<Vec<_>>(), vec![2]);
+    }
+}
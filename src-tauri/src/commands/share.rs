@@ -2,7 +2,7 @@
 
 use tauri::State;
 
-use crate::share::{ContentType, ShareError, SongLinkResponse};
+use crate::share::{ContentType, QobuzEntity, ShareError, SongLinkResponse};
 use crate::AppState;
 use std::collections::HashMap;
 
@@ -221,3 +221,14 @@ pub fn get_qobuz_album_url(album_id: String) -> String {
 pub fn get_qobuz_artist_url(artist_id: u64) -> String {
     format!("https://www.qobuz.com/artist/{}", artist_id)
 }
+
+/// Build the canonical open.qobuz.com link for an album, track, artist or playlist
+#[tauri::command]
+pub fn build_share_url(
+    entity_type: String,
+    id: String,
+    locale: Option<String>,
+) -> Result<String, String> {
+    let entity: QobuzEntity = entity_type.parse().map_err(|e: ShareError| e.to_string())?;
+    crate::share::build_share_url(entity, &id, locale.as_deref()).map_err(|e| e.to_string())
+}
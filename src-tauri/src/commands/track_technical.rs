@@ -0,0 +1,253 @@
+//! Track technical metadata command
+
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::api::models::Track;
+use crate::audio::dynamic_range::{DynamicRangeMeter, DynamicRangeReport};
+use crate::commands::loudness::cached_track_data;
+use crate::download_cache::DownloadCacheState;
+use crate::player::{extract_audio_metadata, DecodedChunks};
+use crate::AppState;
+
+/// Where a technical value comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    /// Advertised by the catalog for the best available format
+    Catalog,
+    /// Read from the local file
+    Measured,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TechnicalValue<T> {
+    pub value: T,
+    pub source: ValueSource,
+}
+
+impl<T> TechnicalValue<T> {
+    fn catalog(value: T) -> Self {
+        Self { value, source: ValueSource::Catalog }
+    }
+
+    fn measured(value: T) -> Self {
+        Self { value, source: ValueSource::Measured }
+    }
+}
+
+/// Catalog and measured technical details of a track
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackTechnical {
+    pub track_id: u64,
+    pub sampling_rate_khz: Option<TechnicalValue<f64>>,
+    pub bit_depth: Option<TechnicalValue<u32>>,
+    pub bit_rate_kbps: Option<TechnicalValue<f64>>,
+    /// Peak and DR are only known once the file is available locally
+    pub peak_dbfs: Option<TechnicalValue<f64>>,
+    pub dynamic_range: Option<TechnicalValue<u32>>,
+}
+
+/// Catalog fields kept from `track/get`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CatalogTechnical {
+    maximum_sampling_rate: Option<f64>,
+    maximum_bit_depth: Option<u32>,
+    maximum_bit_rate: Option<f64>,
+}
+
+impl From<&Track> for CatalogTechnical {
+    fn from(track: &Track) -> Self {
+        Self {
+            maximum_sampling_rate: track.maximum_sampling_rate,
+            maximum_bit_depth: track.maximum_bit_depth,
+            maximum_bit_rate: track.maximum_bit_rate,
+        }
+    }
+}
+
+/// Values read from the decoded file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MeasuredTechnical {
+    sample_rate: u32,
+    /// None for lossy or unknown formats
+    bit_depth: Option<u32>,
+    bit_rate_kbps: Option<f64>,
+    /// None for digital silence
+    dynamic_range: Option<DynamicRangeReport>,
+}
+
+/// Catalog details and measurements, keyed by track id
+#[derive(Default)]
+pub struct TrackTechnicalState {
+    catalog: Mutex<HashMap<u64, CatalogTechnical>>,
+    measured: Mutex<HashMap<u64, MeasuredTechnical>>,
+}
+
+/// Decode a track for its format, bit rate, peak and DR (CPU heavy; run on a blocking task)
+fn measure(data: &[u8]) -> Result<MeasuredTechnical, String> {
+    let (_, _, bit_depth) = extract_audio_metadata(data)?;
+    let mut decoded = DecodedChunks::new(data)?;
+    let channels = decoded.channels();
+    let sample_rate = decoded.sample_rate();
+    let mut meter = DynamicRangeMeter::new(channels, sample_rate)?;
+
+    let mut samples = 0u64;
+    while let Some(chunk) = decoded.next_chunk() {
+        samples += chunk.len() as u64;
+        meter.add_samples(chunk);
+    }
+
+    let duration_secs = samples as f64 / channels as f64 / sample_rate as f64;
+    Ok(MeasuredTechnical {
+        sample_rate,
+        bit_depth,
+        bit_rate_kbps: (duration_secs > 0.0).then(|| data.len() as f64 * 8.0 / duration_secs / 1000.0),
+        dynamic_range: meter.report(),
+    })
+}
+
+/// Measured values win over the catalog's, which describe the best
+/// available format rather than the file at hand
+fn combine(track_id: u64, catalog: &CatalogTechnical, measured: Option<&MeasuredTechnical>) -> TrackTechnical {
+    let dynamic_range = measured.and_then(|m| m.dynamic_range);
+
+    TrackTechnical {
+        track_id,
+        sampling_rate_khz: measured
+            .map(|m| TechnicalValue::measured(m.sample_rate as f64 / 1000.0))
+            .or(catalog.maximum_sampling_rate.map(TechnicalValue::catalog)),
+        bit_depth: measured
+            .and_then(|m| m.bit_depth)
+            .map(TechnicalValue::measured)
+            .or(catalog.maximum_bit_depth.map(TechnicalValue::catalog)),
+        bit_rate_kbps: measured
+            .and_then(|m| m.bit_rate_kbps)
+            .map(TechnicalValue::measured)
+            .or(catalog.maximum_bit_rate.map(TechnicalValue::catalog)),
+        peak_dbfs: dynamic_range.map(|dr| TechnicalValue::measured(dr.peak_dbfs)),
+        dynamic_range: dynamic_range.map(|dr| TechnicalValue::measured(dr.dynamic_range)),
+    }
+}
+
+/// Sample rate, bit depth and bit rate of a track, plus measured peak and
+/// approximate DR when the file is downloaded or cached. The file is never
+/// fetched just for this.
+#[tauri::command]
+pub async fn get_track_technical(
+    track_id: u64,
+    state: State<'_, AppState>,
+    download_cache: State<'_, DownloadCacheState>,
+    technical_state: State<'_, TrackTechnicalState>,
+) -> Result<TrackTechnical, String> {
+    log::info!("Command: get_track_technical {}", track_id);
+
+    let cached_catalog = technical_state.catalog.lock().await.get(&track_id).copied();
+    let catalog = match cached_catalog {
+        Some(catalog) => catalog,
+        None => {
+            let track = {
+                let client = state.client.lock().await;
+                client.get_track(track_id).await.map_err(|e| e.to_string())?
+            };
+            let catalog = CatalogTechnical::from(&track);
+            technical_state.catalog.lock().await.insert(track_id, catalog);
+            catalog
+        }
+    };
+
+    let mut measured = technical_state.measured.lock().await.get(&track_id).copied();
+    if measured.is_none() {
+        if let Some(data) = cached_track_data(track_id, &state, &download_cache).await? {
+            let result = tokio::task::spawn_blocking(move || measure(&data))
+                .await
+                .map_err(|e| format!("Analysis task failed: {}", e))?;
+            match result {
+                Ok(m) => {
+                    technical_state.measured.lock().await.insert(track_id, m);
+                    measured = Some(m);
+                }
+                // Catalog values are still worth returning
+                Err(e) => log::warn!("Failed to analyze track {}: {}", track_id, e),
+            }
+        }
+    }
+
+    Ok(combine(track_id, &catalog, measured.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 16-bit mono WAV
+    fn wav(sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data_len = (samples.len() * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+
+    #[test]
+    fn test_catalog_values_then_measured_once_file_available() {
+        let track: Track = serde_json::from_value(serde_json::json!({
+            "id": 7,
+            "title": "Tone",
+            "maximum_sampling_rate": 96.0,
+            "maximum_bit_depth": 24,
+            "maximum_bit_rate": 4608
+        }))
+        .unwrap();
+        let catalog = CatalogTechnical::from(&track);
+
+        // Before the file is available only the catalog is known
+        let technical = combine(7, &catalog, None);
+        assert_eq!(technical.sampling_rate_khz, Some(TechnicalValue::catalog(96.0)));
+        assert_eq!(technical.bit_depth, Some(TechnicalValue::catalog(24)));
+        assert_eq!(technical.bit_rate_kbps, Some(TechnicalValue::catalog(4608.0)));
+        assert_eq!(technical.peak_dbfs, None);
+        assert_eq!(technical.dynamic_range, None);
+
+        // 9s of a -12 dBFS sine with one -6 dBFS spike per second: DR6
+        let rate = 8000;
+        let samples: Vec<i16> = (0..rate * 9)
+            .map(|i| {
+                if i % rate == 100 {
+                    16384
+                } else {
+                    let t = i as f64 / rate as f64;
+                    ((2.0 * std::f64::consts::PI * 500.0 * t).sin() * 8192.0) as i16
+                }
+            })
+            .collect();
+        let measured = measure(&wav(rate as u32, &samples)).unwrap();
+        let technical = combine(7, &catalog, Some(&measured));
+
+        assert_eq!(technical.sampling_rate_khz, Some(TechnicalValue::measured(8.0)));
+        // WAV headers don't report a depth through the probe, the catalog's is kept
+        assert_eq!(technical.bit_depth.unwrap().source, ValueSource::Catalog);
+        let bit_rate = technical.bit_rate_kbps.unwrap();
+        assert_eq!(bit_rate.source, ValueSource::Measured);
+        assert!((bit_rate.value - 128.0).abs() < 0.5, "{} kbps", bit_rate.value);
+        let peak = technical.peak_dbfs.unwrap();
+        assert_eq!(peak.source, ValueSource::Measured);
+        assert!((peak.value + 6.02).abs() < 0.05, "{} dBFS", peak.value);
+        assert_eq!(technical.dynamic_range, Some(TechnicalValue::measured(6)));
+    }
+}
@@ -1,12 +1,32 @@
 //! Audio settings persistence
 //!
 //! Stores user preferences for audio output device, exclusive mode, and DAC passthrough.
+//! The last volume used on each output device is remembered too, so switching
+//! between headphones and speakers restores the level each was left at.
 
-use crate::audio::{AlsaPlugin, AudioBackendType};
+use crate::api::AudioContainer;
+use crate::audio::fade::MAX_FADE_IN_MS;
+use crate::audio::gain::validate_pregain_db;
+use crate::audio::loudness::NormalizationMode;
+use crate::audio::{
+    AlsaPlugin, AudioBackendType, AudioConfig, ChannelMode, DsdMode, ResampleQuality, SilenceTrimConfig,
+    DEFAULT_FADE_IN_MS,
+};
 use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+/// Volume for an output device that has none remembered yet
+pub const DEFAULT_DEVICE_VOLUME: f32 = 0.5;
+
+/// Key of the system default output in `device_volumes`
+const DEFAULT_DEVICE_KEY: &str = "default";
+
+fn device_key(device: Option<&str>) -> &str {
+    device.filter(|name| !name.is_empty()).unwrap_or(DEFAULT_DEVICE_KEY)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioSettings {
     pub output_device: Option<String>,  // None = system default
@@ -15,6 +35,89 @@ pub struct AudioSettings {
     pub preferred_sample_rate: Option<u32>,  // None = auto
     pub backend_type: Option<AudioBackendType>,  // None = auto-detect
     pub alsa_plugin: Option<AlsaPlugin>,  // Only used when backend is ALSA
+    #[serde(default)]
+    pub dsd_mode: DsdMode,  // DSD delivery (Off = convert to PCM)
+    #[serde(default)]
+    pub buffer_frames: Option<u32>,  // None = device default
+    #[serde(default)]
+    pub period_frames: Option<u32>,  // None = device default
+    #[serde(default)]
+    pub auto_quality: bool,  // Pick stream quality from measured throughput
+    #[serde(default = "default_fade_in_ms")]
+    pub fade_in_ms: u32,  // Fade-in on play/resume, 0 = off
+    #[serde(default)]
+    pub channel_mode: ChannelMode,  // Mono downmix / multichannel placement of stereo content
+    #[serde(default)]
+    pub silence_trim: Option<SilenceTrimConfig>,  // Skip leading/trailing silence, None = off
+    #[serde(default)]
+    pub resample_quality: ResampleQuality,  // SRC used when the output runs at another rate
+    #[serde(default)]
+    pub pregain_db: f32,  // Headroom applied before the output, 0 = off
+    #[serde(default)]
+    pub normalization: NormalizationMode,  // Measured track/album gain to apply, Off = none
+    #[serde(default)]
+    pub preferred_container: Option<AudioContainer>,  // Container to pick when Qobuz offers several, None = as served
+    #[serde(default)]
+    pub register_device: bool,  // Register this machine as a device at login
+}
+
+fn default_fade_in_ms() -> u32 {
+    DEFAULT_FADE_IN_MS
+}
+
+impl AudioSettings {
+    /// Buffer/period sizes to open output streams with
+    pub fn audio_config(&self) -> AudioConfig {
+        AudioConfig {
+            buffer_frames: self.buffer_frames,
+            period_frames: self.period_frames,
+        }
+    }
+
+    /// Fade-in to apply on play/resume (never in bit-perfect mode)
+    pub fn effective_fade_in_ms(&self) -> u32 {
+        if self.dac_passthrough {
+            0
+        } else {
+            self.fade_in_ms
+        }
+    }
+
+    /// Channel mapping to apply (never in bit-perfect mode)
+    pub fn effective_channel_mode(&self) -> ChannelMode {
+        if self.dac_passthrough {
+            ChannelMode::Stereo
+        } else {
+            self.channel_mode
+        }
+    }
+
+    /// Pre-gain to apply in dB (never in bit-perfect mode)
+    pub fn effective_pregain_db(&self) -> f32 {
+        if self.dac_passthrough {
+            0.0
+        } else {
+            self.pregain_db
+        }
+    }
+
+    /// Loudness normalization to apply (never in bit-perfect mode)
+    pub fn effective_normalization(&self) -> NormalizationMode {
+        if self.dac_passthrough {
+            NormalizationMode::Off
+        } else {
+            self.normalization
+        }
+    }
+
+    /// Silence trim to apply (never in bit-perfect mode)
+    pub fn effective_silence_trim(&self) -> Option<SilenceTrimConfig> {
+        if self.dac_passthrough {
+            None
+        } else {
+            self.silence_trim
+        }
+    }
 }
 
 impl Default for AudioSettings {
@@ -26,6 +129,18 @@ impl Default for AudioSettings {
             preferred_sample_rate: None,
             backend_type: None,  // Auto-detect (PipeWire if available, else ALSA)
             alsa_plugin: Some(AlsaPlugin::Hw),  // Default to hw (bit-perfect)
+            dsd_mode: DsdMode::Off,
+            buffer_frames: None,
+            period_frames: None,
+            auto_quality: false,
+            fade_in_ms: DEFAULT_FADE_IN_MS,
+            channel_mode: ChannelMode::Stereo,
+            silence_trim: None,
+            resample_quality: ResampleQuality::Balanced,
+            pregain_db: 0.0,
+            normalization: NormalizationMode::Off,
+            preferred_container: None,
+            register_device: false,
         }
     }
 }
@@ -43,8 +158,11 @@ impl AudioSettingsStore {
         std::fs::create_dir_all(&data_dir)
             .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
-        let db_path = data_dir.join("audio_settings.db");
-        let conn = Connection::open(&db_path)
+        Self::open(&data_dir.join("audio_settings.db"))
+    }
+
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(db_path)
             .map_err(|e| format!("Failed to open audio settings database: {}", e))?;
 
         conn.execute_batch(
@@ -58,12 +176,31 @@ impl AudioSettingsStore {
                 alsa_plugin TEXT
             );
             INSERT OR IGNORE INTO audio_settings (id, exclusive_mode, dac_passthrough)
-            VALUES (1, 0, 0);"
+            VALUES (1, 0, 0);
+            CREATE TABLE IF NOT EXISTS device_volumes (
+                device TEXT PRIMARY KEY,
+                volume REAL NOT NULL
+            );"
         ).map_err(|e| format!("Failed to create audio settings table: {}", e))?;
 
         // Migration: Add new columns if they don't exist (for existing databases)
         let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN backend_type TEXT", []);
         let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN alsa_plugin TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN dsd_mode TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN buffer_frames INTEGER", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN period_frames INTEGER", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN auto_quality INTEGER NOT NULL DEFAULT 0", []);
+        let _ = conn.execute(
+            &format!("ALTER TABLE audio_settings ADD COLUMN fade_in_ms INTEGER NOT NULL DEFAULT {}", DEFAULT_FADE_IN_MS),
+            [],
+        );
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN channel_mode TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN silence_trim TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN resample_quality TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN pregain_db REAL NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN normalization TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN preferred_container TEXT", []);
+        let _ = conn.execute("ALTER TABLE audio_settings ADD COLUMN register_device INTEGER NOT NULL DEFAULT 0", []);
 
         Ok(Self { conn })
     }
@@ -71,7 +208,7 @@ impl AudioSettingsStore {
     pub fn get_settings(&self) -> Result<AudioSettings, String> {
         self.conn
             .query_row(
-                "SELECT output_device, exclusive_mode, dac_passthrough, preferred_sample_rate, backend_type, alsa_plugin FROM audio_settings WHERE id = 1",
+                "SELECT output_device, exclusive_mode, dac_passthrough, preferred_sample_rate, backend_type, alsa_plugin, buffer_frames, period_frames, auto_quality, fade_in_ms, channel_mode, silence_trim, resample_quality, pregain_db, normalization, preferred_container, register_device, dsd_mode FROM audio_settings WHERE id = 1",
                 [],
                 |row| {
                     // Parse backend_type from JSON string
@@ -84,6 +221,40 @@ impl AudioSettingsStore {
                         .get::<_, Option<String>>(5)?
                         .and_then(|s| serde_json::from_str(&s).ok());
 
+                    // Parse dsd_mode from JSON string
+                    let dsd_mode: DsdMode = row
+                        .get::<_, Option<String>>(17)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default();
+
+                    // Parse channel_mode from JSON string
+                    let channel_mode: ChannelMode = row
+                        .get::<_, Option<String>>(10)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default();
+
+                    // Parse silence_trim from JSON string
+                    let silence_trim: Option<SilenceTrimConfig> = row
+                        .get::<_, Option<String>>(11)?
+                        .and_then(|s| serde_json::from_str(&s).ok());
+
+                    // Parse resample_quality from JSON string
+                    let resample_quality: ResampleQuality = row
+                        .get::<_, Option<String>>(12)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default();
+
+                    // Parse normalization from JSON string
+                    let normalization: NormalizationMode = row
+                        .get::<_, Option<String>>(14)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default();
+
+                    // Parse preferred_container from JSON string
+                    let preferred_container: Option<AudioContainer> = row
+                        .get::<_, Option<String>>(15)?
+                        .and_then(|s| serde_json::from_str(&s).ok());
+
                     Ok(AudioSettings {
                         output_device: row.get(0)?,
                         exclusive_mode: row.get::<_, i64>(1)? != 0,
@@ -91,6 +262,18 @@ impl AudioSettingsStore {
                         preferred_sample_rate: row.get(3)?,
                         backend_type,
                         alsa_plugin,
+                        dsd_mode,
+                        buffer_frames: row.get(6)?,
+                        period_frames: row.get(7)?,
+                        auto_quality: row.get::<_, i64>(8)? != 0,
+                        fade_in_ms: row.get(9)?,
+                        channel_mode,
+                        silence_trim,
+                        resample_quality,
+                        pregain_db: row.get::<_, f64>(13)? as f32,
+                        normalization,
+                        preferred_container,
+                        register_device: row.get::<_, i64>(16)? != 0,
                     })
                 },
             )
@@ -107,6 +290,47 @@ impl AudioSettingsStore {
         Ok(())
     }
 
+    /// Volume last used on `device` (None = system default), if any
+    pub fn get_device_volume(&self, device: Option<&str>) -> Result<Option<f32>, String> {
+        self.conn
+            .query_row(
+                "SELECT volume FROM device_volumes WHERE device = ?1",
+                params![device_key(device)],
+                |row| row.get::<_, f64>(0),
+            )
+            .map(|volume| Some(volume as f32))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(format!("Failed to get device volume: {}", e)),
+            })
+    }
+
+    pub fn set_device_volume(&self, device: Option<&str>, volume: f32) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO device_volumes (device, volume) VALUES (?1, ?2)
+                 ON CONFLICT(device) DO UPDATE SET volume = excluded.volume",
+                params![device_key(device), volume.clamp(0.0, 1.0) as f64],
+            )
+            .map_err(|e| format!("Failed to set device volume: {}", e))?;
+        Ok(())
+    }
+
+    /// Switch the output device, remembering `current_volume` for the one
+    /// being left. Returns the volume to apply on the new device: its
+    /// remembered one, or `DEFAULT_DEVICE_VOLUME`. Bit-perfect output keeps
+    /// its volume untouched, so nothing is returned then.
+    pub fn switch_output_device(&self, device: Option<&str>, current_volume: f32) -> Result<Option<f32>, String> {
+        let settings = self.get_settings()?;
+        self.set_device_volume(settings.output_device.as_deref(), current_volume)?;
+        self.set_output_device(device)?;
+
+        if settings.dac_passthrough {
+            return Ok(None);
+        }
+        Ok(Some(self.get_device_volume(device)?.unwrap_or(DEFAULT_DEVICE_VOLUME)))
+    }
+
     pub fn set_exclusive_mode(&self, enabled: bool) -> Result<(), String> {
         self.conn
             .execute(
@@ -166,6 +390,138 @@ impl AudioSettingsStore {
             .map_err(|e| format!("Failed to set ALSA plugin: {}", e))?;
         Ok(())
     }
+
+    pub fn set_buffer_config(&self, config: AudioConfig) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET buffer_frames = ?1, period_frames = ?2 WHERE id = 1",
+                params![config.buffer_frames, config.period_frames],
+            )
+            .map_err(|e| format!("Failed to set buffer config: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_auto_quality(&self, enabled: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET auto_quality = ?1 WHERE id = 1",
+                params![enabled as i64],
+            )
+            .map_err(|e| format!("Failed to set auto quality: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_fade_in_ms(&self, ms: u32) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET fade_in_ms = ?1 WHERE id = 1",
+                params![ms],
+            )
+            .map_err(|e| format!("Failed to set fade-in: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_dsd_mode(&self, mode: DsdMode) -> Result<(), String> {
+        let mode_json = serde_json::to_string(&mode)
+            .map_err(|e| format!("Failed to serialize DSD mode: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET dsd_mode = ?1 WHERE id = 1",
+                params![mode_json],
+            )
+            .map_err(|e| format!("Failed to set DSD mode: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_channel_mode(&self, mode: ChannelMode) -> Result<(), String> {
+        let mode_json = serde_json::to_string(&mode)
+            .map_err(|e| format!("Failed to serialize channel mode: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET channel_mode = ?1 WHERE id = 1",
+                params![mode_json],
+            )
+            .map_err(|e| format!("Failed to set channel mode: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_silence_trim(&self, config: Option<SilenceTrimConfig>) -> Result<(), String> {
+        let config_json = config
+            .map(|c| serde_json::to_string(&c))
+            .transpose()
+            .map_err(|e| format!("Failed to serialize silence trim: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET silence_trim = ?1 WHERE id = 1",
+                params![config_json],
+            )
+            .map_err(|e| format!("Failed to set silence trim: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_resample_quality(&self, quality: ResampleQuality) -> Result<(), String> {
+        let quality_json = serde_json::to_string(&quality)
+            .map_err(|e| format!("Failed to serialize resample quality: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET resample_quality = ?1 WHERE id = 1",
+                params![quality_json],
+            )
+            .map_err(|e| format!("Failed to set resample quality: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_pregain_db(&self, db: f32) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET pregain_db = ?1 WHERE id = 1",
+                params![db as f64],
+            )
+            .map_err(|e| format!("Failed to set pre-gain: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_normalization(&self, mode: NormalizationMode) -> Result<(), String> {
+        let mode_json = serde_json::to_string(&mode)
+            .map_err(|e| format!("Failed to serialize normalization mode: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET normalization = ?1 WHERE id = 1",
+                params![mode_json],
+            )
+            .map_err(|e| format!("Failed to set normalization: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_preferred_container(&self, container: Option<AudioContainer>) -> Result<(), String> {
+        let container_json = container
+            .map(|c| serde_json::to_string(&c))
+            .transpose()
+            .map_err(|e| format!("Failed to serialize container: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET preferred_container = ?1 WHERE id = 1",
+                params![container_json],
+            )
+            .map_err(|e| format!("Failed to set preferred container: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_register_device(&self, enabled: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE audio_settings SET register_device = ?1 WHERE id = 1",
+                params![enabled as i64],
+            )
+            .map_err(|e| format!("Failed to set device registration: {}", e))?;
+        Ok(())
+    }
 }
 
 /// Thread-safe wrapper
@@ -190,13 +546,51 @@ pub fn get_audio_settings(
     store.get_settings()
 }
 
+/// Select the output device and restore the volume last used on it
+/// (outside bit-perfect mode)
 #[tauri::command]
 pub fn set_audio_output_device(
     state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
     device: Option<String>,
 ) -> Result<(), String> {
     let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
-    store.set_output_device(device.as_deref())
+    let current_volume = app_state.player.state.volume();
+    if let Some(volume) = store.switch_output_device(device.as_deref(), current_volume)? {
+        log::info!("Restoring volume {:.2} for output device {:?}", volume, device);
+        app_state.player.set_volume(volume)?;
+    }
+    Ok(())
+}
+
+/// Volume remembered for an output device (None = system default), or the
+/// safe default if it has never been used
+#[tauri::command]
+pub fn get_device_volume(
+    state: tauri::State<'_, AudioSettingsState>,
+    device: Option<String>,
+) -> Result<f32, String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    Ok(store.get_device_volume(device.as_deref())?.unwrap_or(DEFAULT_DEVICE_VOLUME))
+}
+
+/// Remember a volume for an output device; applied right away if it is the
+/// current output (outside bit-perfect mode)
+#[tauri::command]
+pub fn set_device_volume(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    device: Option<String>,
+    volume: f32,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_device_volume(device.as_deref(), volume)?;
+
+    let settings = store.get_settings()?;
+    if !settings.dac_passthrough && device_key(settings.output_device.as_deref()) == device_key(device.as_deref()) {
+        app_state.player.set_volume(volume)?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -243,3 +637,190 @@ pub fn set_audio_alsa_plugin(
     let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
     store.set_alsa_plugin(plugin)
 }
+
+#[tauri::command]
+pub fn set_audio_dsd_mode(
+    state: tauri::State<'_, AudioSettingsState>,
+    mode: DsdMode,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_dsd_mode(mode)
+}
+
+/// Set output buffer/period sizes (frames); applied when the stream is next opened.
+/// Sizes are validated up front; device-specific clamping happens at open time.
+#[tauri::command]
+pub fn set_audio_buffer_config(
+    state: tauri::State<'_, AudioSettingsState>,
+    buffer_frames: Option<u32>,
+    period_frames: Option<u32>,
+) -> Result<(), String> {
+    let config = AudioConfig { buffer_frames, period_frames };
+    config.resolve(None, false)?;
+
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_buffer_config(config)
+}
+
+/// Enable or disable throughput-based stream quality selection
+#[tauri::command]
+pub fn set_auto_quality(
+    state: tauri::State<'_, AudioSettingsState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_auto_quality(enabled)?;
+    crate::download_cache::throughput::auto_quality().set_enabled(enabled);
+    Ok(())
+}
+
+/// Set the fade-in applied on play/resume in ms (0 = off).
+/// Ignored while DAC passthrough (bit-perfect) is enabled.
+#[tauri::command]
+pub fn set_audio_fade_in(
+    state: tauri::State<'_, AudioSettingsState>,
+    ms: u32,
+) -> Result<(), String> {
+    if ms > MAX_FADE_IN_MS {
+        return Err(format!("Fade-in must be at most {} ms", MAX_FADE_IN_MS));
+    }
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_fade_in_ms(ms)
+}
+
+/// Set how stereo content is mapped to the output (mono downmix or a
+/// channel pair of a multichannel device). Applies from the next play or
+/// seek; ignored while DAC passthrough (bit-perfect) is enabled.
+#[tauri::command]
+pub fn set_channel_mode(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    mode: ChannelMode,
+) -> Result<(), String> {
+    mode.validate()?;
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_channel_mode(mode)?;
+    app_state.player.reload_settings(store.get_settings()?)
+}
+
+/// Skip leading/trailing silence between tracks (None = off). Applies
+/// from the next track; ignored while DAC passthrough (bit-perfect) is enabled.
+#[tauri::command]
+pub fn set_silence_trim(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    config: Option<SilenceTrimConfig>,
+) -> Result<(), String> {
+    if let Some(config) = config {
+        config.validate()?;
+    }
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_silence_trim(config)?;
+    app_state.player.reload_settings(store.get_settings()?)
+}
+
+/// Set the resampler quality used when a track's rate differs from the
+/// output's. Applies from the next play or seek; bit-perfect playback
+/// never resamples.
+#[tauri::command]
+pub fn set_resample_quality(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    quality: ResampleQuality,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_resample_quality(quality)?;
+    app_state.player.reload_settings(store.get_settings()?)
+}
+
+/// Set the pre-gain (headroom) in dB applied before the output, e.g. -3 to
+/// avoid intersample overs. Applies immediately; ignored while DAC
+/// passthrough (bit-perfect) is enabled.
+#[tauri::command]
+pub fn set_audio_pregain(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    db: f32,
+) -> Result<(), String> {
+    validate_pregain_db(db)?;
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_pregain_db(db)?;
+    app_state.player.set_pregain_db(db)
+}
+
+/// Choose which measured gain (see `compute_album_gain`) is applied. Takes
+/// effect from the next track; ignored while DAC passthrough (bit-perfect)
+/// is enabled.
+#[tauri::command]
+pub fn set_audio_normalization(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    mode: NormalizationMode,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_normalization(mode)?;
+    app_state.player.reload_settings(store.get_settings()?)
+}
+
+/// Container to pick when Qobuz offers a quality in more than one (e.g.
+/// FLAC over ALAC); None takes the file as served. Applies to stream URLs
+/// requested from now on.
+#[tauri::command]
+pub async fn set_preferred_container(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    container: Option<AudioContainer>,
+) -> Result<(), String> {
+    state
+        .store
+        .lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .set_preferred_container(container)?;
+    app_state.client.lock().await.set_preferred_container(container);
+    Ok(())
+}
+
+/// Register this machine as a Qobuz device at login, for setups that only
+/// get their full formats on a registered device. Applies from the next
+/// login.
+#[tauri::command]
+pub async fn set_register_device(
+    state: tauri::State<'_, AudioSettingsState>,
+    app_state: tauri::State<'_, crate::AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    state
+        .store
+        .lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .set_register_device(enabled)?;
+    app_state.client.lock().await.set_device_registration(enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_output_device_restores_its_own_volume() {
+        let store = AudioSettingsStore::open(Path::new(":memory:")).unwrap();
+        store.set_output_device(Some("speakers")).unwrap();
+
+        // Headphones were never used: they start at the safe default
+        assert_eq!(store.switch_output_device(Some("headphones"), 0.8).unwrap(), Some(DEFAULT_DEVICE_VOLUME));
+        assert_eq!(store.switch_output_device(Some("speakers"), 0.25).unwrap(), Some(0.8));
+        assert_eq!(store.switch_output_device(Some("headphones"), 0.7).unwrap(), Some(0.25));
+        assert_eq!(store.get_device_volume(Some("speakers")).unwrap(), Some(0.7));
+        assert_eq!(store.get_settings().unwrap().output_device.as_deref(), Some("headphones"));
+
+        // The system default output is remembered like any other device
+        assert_eq!(store.switch_output_device(None, 0.3).unwrap(), Some(DEFAULT_DEVICE_VOLUME));
+        assert_eq!(store.get_device_volume(Some("headphones")).unwrap(), Some(0.3));
+
+        // Bit-perfect output leaves the volume alone, but still remembers it
+        store.set_dac_passthrough(true).unwrap();
+        assert_eq!(store.switch_output_device(Some("speakers"), 0.6).unwrap(), None);
+        assert_eq!(store.get_device_volume(None).unwrap(), Some(0.6));
+    }
+}
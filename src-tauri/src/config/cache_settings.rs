@@ -0,0 +1,218 @@
+//! Cache settings persistence
+//!
+//! Stores user preferences for API and audio cache behavior.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::cache::CacheMode;
+use crate::AppState;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheSettings {
+    /// Prefetch favorites and playlists metadata right after login
+    pub warm_cache_on_login: bool,
+    /// Audio caching strategy (stream-only for low-memory systems)
+    #[serde(default)]
+    pub cache_mode: CacheMode,
+    /// Root for the audio caches, downloads and artwork (None = platform cache dir)
+    #[serde(default)]
+    pub storage_dir: Option<String>,
+    /// Prefetch the first tracks of an album/playlist as soon as it's queued
+    #[serde(default)]
+    pub warm_queue_additions: bool,
+}
+
+pub struct CacheSettingsStore {
+    conn: Connection,
+}
+
+impl CacheSettingsStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        let db_path = data_dir.join("cache_settings.db");
+        let conn = Connection::open(&db_path)
+            .map_err(|e| format!("Failed to open cache settings database: {}", e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS cache_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                warm_cache_on_login INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT OR IGNORE INTO cache_settings (id, warm_cache_on_login) VALUES (1, 0);"
+        ).map_err(|e| format!("Failed to create cache settings table: {}", e))?;
+
+        // Migration: Add new columns if they don't exist (for existing databases)
+        let _ = conn.execute("ALTER TABLE cache_settings ADD COLUMN cache_mode TEXT", []);
+        let _ = conn.execute("ALTER TABLE cache_settings ADD COLUMN storage_dir TEXT", []);
+        let _ = conn.execute(
+            "ALTER TABLE cache_settings ADD COLUMN warm_queue_additions INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+
+        Ok(Self { conn })
+    }
+
+    pub fn get_settings(&self) -> Result<CacheSettings, String> {
+        self.conn
+            .query_row(
+                "SELECT warm_cache_on_login, cache_mode, storage_dir, warm_queue_additions FROM cache_settings WHERE id = 1",
+                [],
+                |row| {
+                    let cache_mode: CacheMode = row
+                        .get::<_, Option<String>>(1)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default();
+
+                    Ok(CacheSettings {
+                        warm_cache_on_login: row.get::<_, i64>(0)? != 0,
+                        cache_mode,
+                        storage_dir: row.get(2)?,
+                        warm_queue_additions: row.get::<_, i64>(3)? != 0,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to get cache settings: {}", e))
+    }
+
+    pub fn set_warm_cache_on_login(&self, enabled: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE cache_settings SET warm_cache_on_login = ?1 WHERE id = 1",
+                params![enabled as i64],
+            )
+            .map_err(|e| format!("Failed to set warm_cache_on_login: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_cache_mode(&self, mode: CacheMode) -> Result<(), String> {
+        let mode_json = serde_json::to_string(&mode)
+            .map_err(|e| format!("Failed to serialize cache mode: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE cache_settings SET cache_mode = ?1 WHERE id = 1",
+                params![mode_json],
+            )
+            .map_err(|e| format!("Failed to set cache mode: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_warm_queue_additions(&self, enabled: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE cache_settings SET warm_queue_additions = ?1 WHERE id = 1",
+                params![enabled as i64],
+            )
+            .map_err(|e| format!("Failed to set warm_queue_additions: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_storage_dir(&self, dir: Option<&str>) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE cache_settings SET storage_dir = ?1 WHERE id = 1",
+                params![dir],
+            )
+            .map_err(|e| format!("Failed to set storage dir: {}", e))?;
+        Ok(())
+    }
+}
+
+pub type CacheSettingsState = Arc<Mutex<CacheSettingsStore>>;
+
+pub fn create_cache_settings_state() -> Result<CacheSettingsState, String> {
+    let store = CacheSettingsStore::new()?;
+    Ok(Arc::new(Mutex::new(store)))
+}
+
+/// Whether cache warming is enabled (false if the settings can't be read)
+pub fn warm_cache_on_login_enabled(state: &CacheSettingsState) -> bool {
+    state
+        .lock()
+        .ok()
+        .and_then(|store| store.get_settings().ok())
+        .map(|settings| settings.warm_cache_on_login)
+        .unwrap_or(false)
+}
+
+/// Saved audio cache mode (default if the settings can't be read)
+pub fn saved_cache_mode(state: &CacheSettingsState) -> CacheMode {
+    state
+        .lock()
+        .ok()
+        .and_then(|store| store.get_settings().ok())
+        .map(|settings| settings.cache_mode)
+        .unwrap_or_default()
+}
+
+/// Whether queued albums/playlists are warmed (false if the settings can't be read)
+pub fn warm_queue_additions_enabled(state: &CacheSettingsState) -> bool {
+    state
+        .lock()
+        .ok()
+        .and_then(|store| store.get_settings().ok())
+        .map(|settings| settings.warm_queue_additions)
+        .unwrap_or(false)
+}
+
+// Tauri commands
+
+#[tauri::command]
+pub fn get_cache_settings(
+    state: tauri::State<CacheSettingsState>,
+) -> Result<CacheSettings, String> {
+    log::info!("Command: get_cache_settings");
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.get_settings()
+}
+
+#[tauri::command]
+pub fn set_warm_cache_on_login(
+    enabled: bool,
+    state: tauri::State<CacheSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: set_warm_cache_on_login to: {}", enabled);
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_warm_cache_on_login(enabled)
+}
+
+/// Set the audio cache mode; takes effect immediately
+#[tauri::command]
+pub fn set_cache_mode(
+    mode: CacheMode,
+    state: tauri::State<CacheSettingsState>,
+    app_state: tauri::State<AppState>,
+) -> Result<(), String> {
+    log::info!("Command: set_cache_mode to: {:?}", mode);
+    {
+        let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+        store.set_cache_mode(mode)?;
+    }
+    app_state.audio_cache.set_mode(mode);
+    Ok(())
+}
+
+/// Prefetch the first tracks of each album/playlist added to the queue,
+/// up to the prefetch window; takes effect immediately
+#[tauri::command]
+pub fn set_warm_queue_additions(
+    enabled: bool,
+    state: tauri::State<CacheSettingsState>,
+    app_state: tauri::State<AppState>,
+) -> Result<(), String> {
+    log::info!("Command: set_warm_queue_additions to: {}", enabled);
+    {
+        let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+        store.set_warm_queue_additions(enabled)?;
+    }
+    app_state.audio_cache.set_warm_additions(enabled);
+    Ok(())
+}
@@ -6,24 +6,32 @@ use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 
+use crate::metered::{metered, MeteredStatus};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadSettings {
     pub download_root: String,
     pub show_in_library: bool,
+    /// Hold prefetch and offline downloads while the connection is metered
+    #[serde(default)]
+    pub pause_on_metered: bool,
+    /// Treat the connection as metered regardless of detection
+    #[serde(default)]
+    pub metered_connection: bool,
 }
 
 impl Default for DownloadSettings {
     fn default() -> Self {
-        let default_root = dirs::cache_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join("qbz")
-            .join("audio")
+        let default_root = crate::storage::storage_root()
+            .join(crate::storage::DOWNLOADS_DIR)
             .to_string_lossy()
             .to_string();
 
         Self {
             download_root: default_root,
             show_in_library: false,
+            pause_on_metered: false,
+            metered_connection: false,
         }
     }
 }
@@ -61,18 +69,24 @@ impl DownloadSettingsStore {
             params![default_settings.download_root],
         ).map_err(|e| format!("Failed to initialize download settings: {}", e))?;
 
+        // Migration: metered connection options
+        let _ = conn.execute("ALTER TABLE download_settings ADD COLUMN pause_on_metered INTEGER NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE download_settings ADD COLUMN metered_connection INTEGER NOT NULL DEFAULT 0", []);
+
         Ok(Self { conn })
     }
 
     pub fn get_settings(&self) -> Result<DownloadSettings, String> {
         self.conn
             .query_row(
-                "SELECT download_root, show_in_library FROM download_settings WHERE id = 1",
+                "SELECT download_root, show_in_library, pause_on_metered, metered_connection FROM download_settings WHERE id = 1",
                 [],
                 |row| {
                     Ok(DownloadSettings {
                         download_root: row.get(0)?,
                         show_in_library: row.get::<_, i64>(1)? != 0,
+                        pause_on_metered: row.get::<_, i64>(2)? != 0,
+                        metered_connection: row.get::<_, i64>(3)? != 0,
                     })
                 },
             )
@@ -98,12 +112,36 @@ impl DownloadSettingsStore {
             .map_err(|e| format!("Failed to set show_in_library: {}", e))?;
         Ok(())
     }
+
+    pub fn set_pause_on_metered(&self, enabled: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE download_settings SET pause_on_metered = ?1 WHERE id = 1",
+                params![enabled as i64],
+            )
+            .map_err(|e| format!("Failed to set pause_on_metered: {}", e))?;
+        Ok(())
+    }
+
+    pub fn set_metered_connection(&self, metered: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE download_settings SET metered_connection = ?1 WHERE id = 1",
+                params![metered as i64],
+            )
+            .map_err(|e| format!("Failed to set metered_connection: {}", e))?;
+        Ok(())
+    }
 }
 
 pub type DownloadSettingsState = Arc<Mutex<DownloadSettingsStore>>;
 
 pub fn create_download_settings_state() -> Result<DownloadSettingsState, String> {
     let store = DownloadSettingsStore::new()?;
+    if let Ok(settings) = store.get_settings() {
+        metered().set_pause_on_metered(settings.pause_on_metered);
+        metered().set_manual(settings.metered_connection);
+    }
     Ok(Arc::new(Mutex::new(store)))
 }
 
@@ -148,6 +186,38 @@ pub fn set_show_downloads_in_library(
     store.set_show_in_library(show)
 }
 
+/// Pause background prefetch and offline downloads while the connection is
+/// metered (detected, or flagged with `set_metered_connection`)
+#[tauri::command]
+pub fn set_pause_on_metered(
+    enabled: bool,
+    state: tauri::State<DownloadSettingsState>,
+) -> Result<MeteredStatus, String> {
+    log::info!("Command: set_pause_on_metered to: {}", enabled);
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_pause_on_metered(enabled)?;
+    metered().set_pause_on_metered(enabled);
+    Ok(metered().status())
+}
+
+/// Manually flag the connection as metered, for when it can't be detected
+#[tauri::command]
+pub fn set_metered_connection(
+    metered_connection: bool,
+    state: tauri::State<DownloadSettingsState>,
+) -> Result<MeteredStatus, String> {
+    log::info!("Command: set_metered_connection to: {}", metered_connection);
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_metered_connection(metered_connection)?;
+    metered().set_manual(metered_connection);
+    Ok(metered().status())
+}
+
+#[tauri::command]
+pub fn get_metered_status() -> MeteredStatus {
+    metered().status()
+}
+
 #[tauri::command]
 pub fn validate_download_root(path: String) -> Result<bool, String> {
     log::info!("Command: validate_download_root: {}", path);
@@ -0,0 +1,427 @@
+//! Endpoint priority lists
+//!
+//! Ordered lists of Nostr relays and Qobuz API endpoints. Callers try the
+//! entries in order and fall down the list on failure: the frontend for
+//! relays, the API client through [`PriorityRoute`] for the API. Entries can
+//! be pinned to the top, and per-entry success/latency stats can optionally
+//! reorder the unpinned entries.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::api::endpoints;
+use crate::api::routing::EndpointRoute;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointList {
+    NostrRelays,
+    QobuzApi,
+}
+
+impl EndpointList {
+    fn key(&self) -> &'static str {
+        match self {
+            EndpointList::NostrRelays => "nostr_relays",
+            EndpointList::QobuzApi => "qobuz_api",
+        }
+    }
+
+    /// Entries a list starts with before the user configures it
+    fn defaults(&self) -> &'static [&'static str] {
+        match self {
+            // Relays are configured by the frontend
+            EndpointList::NostrRelays => &[],
+            EndpointList::QobuzApi => &[endpoints::BASE_URL],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointEntry {
+    pub url: String,
+    pub pinned: bool,
+    pub successes: u32,
+    pub failures: u32,
+    /// Running average of successful request latency
+    pub avg_latency_ms: Option<u32>,
+}
+
+impl EndpointEntry {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            pinned: false,
+            successes: 0,
+            failures: 0,
+            avg_latency_ms: None,
+        }
+    }
+
+    /// Share of failed attempts (0 when never tried)
+    fn failure_rate(&self) -> f64 {
+        let attempts = self.successes + self.failures;
+        if attempts == 0 {
+            0.0
+        } else {
+            self.failures as f64 / attempts as f64
+        }
+    }
+}
+
+/// Order to try `entries` (given in configured order): pinned entries first,
+/// then the rest. With `auto_reorder`, unpinned entries are sorted by failure
+/// rate, then latency; ties keep the configured order.
+pub fn try_order(entries: &[EndpointEntry], auto_reorder: bool) -> Vec<String> {
+    let mut pinned: Vec<&EndpointEntry> = entries.iter().filter(|e| e.pinned).collect();
+    let mut rest: Vec<&EndpointEntry> = entries.iter().filter(|e| !e.pinned).collect();
+
+    if auto_reorder {
+        rest.sort_by(|a, b| {
+            a.failure_rate()
+                .total_cmp(&b.failure_rate())
+                .then(a.avg_latency_ms.unwrap_or(u32::MAX).cmp(&b.avg_latency_ms.unwrap_or(u32::MAX)))
+        });
+    }
+
+    pinned.append(&mut rest);
+    pinned.into_iter().map(|e| e.url.clone()).collect()
+}
+
+pub struct EndpointPriorityStore {
+    conn: Connection,
+}
+
+impl EndpointPriorityStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        Self::open(&data_dir.join("endpoint_priority.db"))
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open endpoint priority database: {}", e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS endpoint_priority (
+                list TEXT NOT NULL,
+                url TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                pinned INTEGER NOT NULL DEFAULT 0,
+                successes INTEGER NOT NULL DEFAULT 0,
+                failures INTEGER NOT NULL DEFAULT 0,
+                avg_latency_ms INTEGER,
+                PRIMARY KEY (list, url)
+            );"
+        ).map_err(|e| format!("Failed to create endpoint priority table: {}", e))?;
+
+        Ok(Self { conn })
+    }
+
+    /// Entries in configured order (the list's defaults if it was never set)
+    pub fn get_entries(&self, list: EndpointList) -> Result<Vec<EndpointEntry>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT url, pinned, successes, failures, avg_latency_ms FROM endpoint_priority
+                 WHERE list = ? ORDER BY position",
+            )
+            .map_err(|e| format!("Failed to prepare endpoint query: {}", e))?;
+
+        let entries: Vec<EndpointEntry> = stmt
+            .query_map(params![list.key()], |row| {
+                Ok(EndpointEntry {
+                    url: row.get(0)?,
+                    pinned: row.get::<_, i64>(1)? != 0,
+                    successes: row.get(2)?,
+                    failures: row.get(3)?,
+                    avg_latency_ms: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query endpoints: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        if entries.is_empty() {
+            return Ok(list.defaults().iter().map(|url| EndpointEntry::new(url)).collect());
+        }
+        Ok(entries)
+    }
+
+    /// Replace the list with `urls` in this order. Stats and pins of URLs
+    /// that stay in the list are kept.
+    pub fn set_order(&mut self, list: EndpointList, urls: &[String]) -> Result<(), String> {
+        let existing = self.get_entries(list)?;
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        tx.execute("DELETE FROM endpoint_priority WHERE list = ?", params![list.key()])
+            .map_err(|e| format!("Failed to reset endpoints: {}", e))?;
+
+        for (position, url) in urls.iter().enumerate() {
+            let entry = existing
+                .iter()
+                .find(|e| &e.url == url)
+                .cloned()
+                .unwrap_or_else(|| EndpointEntry::new(url));
+            tx.execute(
+                "INSERT OR IGNORE INTO endpoint_priority
+                 (list, url, position, pinned, successes, failures, avg_latency_ms)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    list.key(),
+                    url,
+                    position as i64,
+                    entry.pinned as i64,
+                    entry.successes,
+                    entry.failures,
+                    entry.avg_latency_ms,
+                ],
+            )
+            .map_err(|e| format!("Failed to save endpoint: {}", e))?;
+        }
+
+        tx.commit().map_err(|e| format!("Failed to save endpoint order: {}", e))
+    }
+
+    /// Pin or unpin an entry. Pinning moves it to the top of the configured order.
+    pub fn set_pinned(&mut self, list: EndpointList, url: &str, pinned: bool) -> Result<(), String> {
+        let mut urls: Vec<String> = self.get_entries(list)?.into_iter().map(|e| e.url).collect();
+        if !urls.iter().any(|u| u == url) {
+            return Err(format!("Unknown endpoint: {}", url));
+        }
+        if pinned {
+            urls.retain(|u| u != url);
+            urls.insert(0, url.to_string());
+        }
+        // Materialize the list so the pin has a row to live on
+        self.set_order(list, &urls)?;
+
+        self.conn
+            .execute(
+                "UPDATE endpoint_priority SET pinned = ? WHERE list = ? AND url = ?",
+                params![pinned as i64, list.key(), url],
+            )
+            .map_err(|e| format!("Failed to pin endpoint: {}", e))?;
+        Ok(())
+    }
+
+    /// Record the outcome of a request to an entry
+    pub fn record_result(
+        &self,
+        list: EndpointList,
+        url: &str,
+        success: bool,
+        latency_ms: Option<u32>,
+    ) -> Result<(), String> {
+        let result = if success {
+            self.conn.execute(
+                "UPDATE endpoint_priority SET
+                    successes = successes + 1,
+                    avg_latency_ms = CASE
+                        WHEN ?1 IS NULL THEN avg_latency_ms
+                        WHEN avg_latency_ms IS NULL THEN ?1
+                        ELSE (avg_latency_ms * 3 + ?1) / 4
+                    END
+                 WHERE list = ?2 AND url = ?3",
+                params![latency_ms, list.key(), url],
+            )
+        } else {
+            self.conn.execute(
+                "UPDATE endpoint_priority SET failures = failures + 1 WHERE list = ? AND url = ?",
+                params![list.key(), url],
+            )
+        };
+        result.map_err(|e| format!("Failed to record endpoint result: {}", e))?;
+        Ok(())
+    }
+
+    /// Order to try the list's entries in (see [`try_order`])
+    pub fn try_order(&self, list: EndpointList, auto_reorder: bool) -> Result<Vec<String>, String> {
+        Ok(try_order(&self.get_entries(list)?, auto_reorder))
+    }
+}
+
+pub type EndpointPriorityState = Arc<Mutex<EndpointPriorityStore>>;
+
+pub fn create_endpoint_priority_state() -> Result<EndpointPriorityState, String> {
+    let store = EndpointPriorityStore::new()?;
+    Ok(Arc::new(Mutex::new(store)))
+}
+
+/// A list as the API client's endpoint route, tried in configured order
+pub struct PriorityRoute {
+    pub state: EndpointPriorityState,
+    pub list: EndpointList,
+}
+
+impl EndpointRoute for PriorityRoute {
+    fn try_order(&self) -> Vec<String> {
+        match self.state.lock().map_err(|e| e.to_string()).and_then(|store| store.try_order(self.list, false)) {
+            Ok(order) if !order.is_empty() => order,
+            Ok(_) => self.list.defaults().iter().map(|url| url.to_string()).collect(),
+            Err(e) => {
+                log::warn!("Using the default endpoints: {}", e);
+                self.list.defaults().iter().map(|url| url.to_string()).collect()
+            }
+        }
+    }
+
+    fn record(&self, url: &str, success: bool, latency_ms: Option<u32>) {
+        if let Ok(store) = self.state.lock() {
+            let _ = store.record_result(self.list, url, success, latency_ms);
+        }
+    }
+}
+
+// Tauri commands
+
+#[tauri::command]
+pub fn get_endpoint_priority(
+    list: EndpointList,
+    state: tauri::State<EndpointPriorityState>,
+) -> Result<Vec<EndpointEntry>, String> {
+    log::info!("Command: get_endpoint_priority {:?}", list);
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.get_entries(list)
+}
+
+/// Set (reorder, add or remove) the entries of a list
+#[tauri::command]
+pub fn set_endpoint_priority(
+    list: EndpointList,
+    urls: Vec<String>,
+    state: tauri::State<EndpointPriorityState>,
+) -> Result<(), String> {
+    log::info!("Command: set_endpoint_priority {:?} ({} entries)", list, urls.len());
+    let mut store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_order(list, &urls)
+}
+
+/// Pin an entry to the top of its list (or unpin it)
+#[tauri::command]
+pub fn pin_endpoint(
+    list: EndpointList,
+    url: String,
+    pinned: bool,
+    state: tauri::State<EndpointPriorityState>,
+) -> Result<(), String> {
+    log::info!("Command: pin_endpoint {:?} {} pinned={}", list, url, pinned);
+    let mut store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_pinned(list, &url, pinned)
+}
+
+/// Record a request outcome reported by the frontend (e.g. a relay connection)
+#[tauri::command]
+pub fn record_endpoint_result(
+    list: EndpointList,
+    url: String,
+    success: bool,
+    latency_ms: Option<u32>,
+    state: tauri::State<EndpointPriorityState>,
+) -> Result<(), String> {
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.record_result(list, &url, success, latency_ms)
+}
+
+/// Order in which to try a list's entries
+#[tauri::command]
+pub fn get_endpoint_try_order(
+    list: EndpointList,
+    auto_reorder: Option<bool>,
+    state: tauri::State<EndpointPriorityState>,
+) -> Result<Vec<String>, String> {
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.try_order(list, auto_reorder.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routing::try_in_order;
+    use crate::api::ApiError;
+
+    fn memory_state(urls: &[&str]) -> EndpointPriorityState {
+        let mut store = EndpointPriorityStore::open(Path::new(":memory:")).unwrap();
+        let urls: Vec<String> = urls.iter().map(|u| u.to_string()).collect();
+        store.set_order(EndpointList::NostrRelays, &urls).unwrap();
+        Arc::new(Mutex::new(store))
+    }
+
+    #[test]
+    fn test_try_order_follows_configured_priority() {
+        let state = memory_state(&["wss://b", "wss://a", "wss://c"]);
+        let store = state.lock().unwrap();
+        assert_eq!(
+            store.try_order(EndpointList::NostrRelays, false).unwrap(),
+            vec!["wss://b", "wss://a", "wss://c"]
+        );
+        // Untouched list falls back to its defaults
+        assert_eq!(
+            store.try_order(EndpointList::QobuzApi, false).unwrap(),
+            vec![endpoints::BASE_URL]
+        );
+    }
+
+    #[test]
+    fn test_pinned_entry_stays_first_regardless_of_stats() {
+        let state = memory_state(&["wss://a", "wss://b", "wss://c"]);
+        let mut store = state.lock().unwrap();
+        store.set_pinned(EndpointList::NostrRelays, "wss://c", true).unwrap();
+
+        store.record_result(EndpointList::NostrRelays, "wss://c", false, None).unwrap();
+        store.record_result(EndpointList::NostrRelays, "wss://a", false, None).unwrap();
+        store.record_result(EndpointList::NostrRelays, "wss://b", true, Some(40)).unwrap();
+
+        assert_eq!(
+            store.try_order(EndpointList::NostrRelays, true).unwrap(),
+            vec!["wss://c", "wss://b", "wss://a"]
+        );
+        assert_eq!(
+            store.try_order(EndpointList::NostrRelays, false).unwrap(),
+            vec!["wss://c", "wss://a", "wss://b"]
+        );
+
+        // Reordering keeps the pin and the stats
+        let urls = vec!["wss://b".to_string(), "wss://a".to_string(), "wss://c".to_string()];
+        store.set_order(EndpointList::NostrRelays, &urls).unwrap();
+        let entries = store.get_entries(EndpointList::NostrRelays).unwrap();
+        assert!(entries[2].pinned);
+        assert_eq!(entries[0].avg_latency_ms, Some(40));
+        assert_eq!(store.try_order(EndpointList::NostrRelays, false).unwrap()[0], "wss://c");
+    }
+
+    #[tokio::test]
+    async fn test_falls_down_the_list_on_failure() {
+        let state = memory_state(&["wss://down", "wss://up"]);
+        let route = PriorityRoute { state: state.clone(), list: EndpointList::NostrRelays };
+        let mut tried = Vec::new();
+
+        let result = try_in_order(&route, |url| {
+            tried.push(url.clone());
+            async move {
+                if url == "wss://down" {
+                    Err(ApiError::ApiResponse("connection refused".to_string()))
+                } else {
+                    Ok(url)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "wss://up");
+        assert_eq!(tried, vec!["wss://down", "wss://up"]);
+        let entries = state.lock().unwrap().get_entries(EndpointList::NostrRelays).unwrap();
+        assert_eq!((entries[0].failures, entries[1].successes), (1, 1));
+    }
+}
@@ -9,7 +9,12 @@
 //! - Cached favorites
 
 pub mod audio_settings;
+pub mod cache_settings;
 pub mod download_settings;
+pub mod endpoint_priority;
+pub mod playback_settings;
+pub mod shortcut_settings;
+pub mod startup_settings;
 
 pub use audio_settings::{
     AudioSettings,
@@ -21,6 +26,14 @@ pub use audio_settings::{
     set_audio_sample_rate,
 };
 
+pub use cache_settings::{
+    CacheSettings,
+    CacheSettingsState,
+    get_cache_settings,
+    set_cache_mode,
+    set_warm_cache_on_login,
+};
+
 pub use download_settings::{
     DownloadSettings,
     DownloadSettingsState,
@@ -29,3 +42,10 @@ pub use download_settings::{
     set_show_downloads_in_library,
     validate_download_root,
 };
+
+pub use startup_settings::{
+    StartupConfig,
+    StartupSettingsState,
+    get_startup_config,
+    set_startup_config,
+};
@@ -0,0 +1,147 @@
+//! Playback settings persistence
+//!
+//! Preferences for what happens around playback rather than to the audio
+//! itself, such as the streaming quality, what happens when the queue runs
+//! out and reporting streams to Qobuz.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// When listens are reported to Qobuz (see `crate::stream_report`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportPolicy {
+    pub enabled: bool,
+    /// Playback needed before a listen is reported
+    pub min_listen_secs: u64,
+}
+
+impl ReportPolicy {
+    pub const fn new() -> Self {
+        Self { enabled: true, min_listen_secs: 30 }
+    }
+}
+
+impl Default for ReportPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct PlaybackSettingsStore {
+    conn: Connection,
+}
+
+impl PlaybackSettingsStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        let db_path = data_dir.join("playback_settings.db");
+        let conn = Connection::open(&db_path)
+            .map_err(|e| format!("Failed to open playback settings database: {}", e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS playback_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                report_streams INTEGER NOT NULL DEFAULT 1,
+                min_listen_secs INTEGER NOT NULL DEFAULT 30,
+                streaming_quality INTEGER NOT NULL DEFAULT 27,
+                queue_end_radio INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT OR IGNORE INTO playback_settings (id) VALUES (1);"
+        ).map_err(|e| format!("Failed to create playback settings table: {}", e))?;
+
+        // Migration: add streaming_quality to existing databases
+        let _ = conn.execute(
+            "ALTER TABLE playback_settings ADD COLUMN streaming_quality INTEGER NOT NULL DEFAULT 27",
+            [],
+        );
+        // Migration: add queue_end_radio to existing databases
+        let _ = conn.execute(
+            "ALTER TABLE playback_settings ADD COLUMN queue_end_radio INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+
+        Ok(Self { conn })
+    }
+
+    pub fn get_report_policy(&self) -> Result<ReportPolicy, String> {
+        self.conn
+            .query_row(
+                "SELECT report_streams, min_listen_secs FROM playback_settings WHERE id = 1",
+                [],
+                |row| {
+                    Ok(ReportPolicy {
+                        enabled: row.get::<_, i64>(0)? != 0,
+                        min_listen_secs: row.get::<_, i64>(1)?.max(0) as u64,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to get playback settings: {}", e))
+    }
+
+    pub fn set_report_policy(&self, policy: &ReportPolicy) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE playback_settings SET report_streams = ?1, min_listen_secs = ?2 WHERE id = 1",
+                params![policy.enabled as i64, policy.min_listen_secs as i64],
+            )
+            .map_err(|e| format!("Failed to set playback settings: {}", e))?;
+        Ok(())
+    }
+
+    /// Qobuz format id the user chose to stream at
+    pub fn get_streaming_quality(&self) -> Result<u32, String> {
+        self.conn
+            .query_row(
+                "SELECT streaming_quality FROM playback_settings WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to get streaming quality: {}", e))
+    }
+
+    pub fn set_streaming_quality(&self, format_id: u32) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE playback_settings SET streaming_quality = ?1 WHERE id = 1",
+                params![format_id],
+            )
+            .map_err(|e| format!("Failed to set streaming quality: {}", e))?;
+        Ok(())
+    }
+
+    /// Whether radio continues playback once the queue runs out
+    pub fn get_queue_end_radio(&self) -> Result<bool, String> {
+        self.conn
+            .query_row(
+                "SELECT queue_end_radio FROM playback_settings WHERE id = 1",
+                [],
+                |row| Ok(row.get::<_, i64>(0)? != 0),
+            )
+            .map_err(|e| format!("Failed to get queue end action: {}", e))
+    }
+
+    pub fn set_queue_end_radio(&self, radio: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE playback_settings SET queue_end_radio = ?1 WHERE id = 1",
+                params![radio as i64],
+            )
+            .map_err(|e| format!("Failed to set queue end action: {}", e))?;
+        Ok(())
+    }
+}
+
+pub type PlaybackSettingsState = Arc<Mutex<PlaybackSettingsStore>>;
+
+pub fn create_playback_settings_state() -> Result<PlaybackSettingsState, String> {
+    let store = PlaybackSettingsStore::new()?;
+    Ok(Arc::new(Mutex::new(store)))
+}
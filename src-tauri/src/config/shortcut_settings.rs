@@ -0,0 +1,81 @@
+//! Global shortcut persistence
+//!
+//! Stores the accelerator bound to each transport action (see
+//! `crate::shortcuts`), registered again at every launch.
+
+use rusqlite::{Connection, params};
+use std::sync::{Arc, Mutex};
+
+pub struct ShortcutSettingsStore {
+    conn: Connection,
+}
+
+impl ShortcutSettingsStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        let db_path = data_dir.join("shortcut_settings.db");
+        let conn = Connection::open(&db_path)
+            .map_err(|e| format!("Failed to open shortcut settings database: {}", e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS global_shortcuts (
+                action TEXT PRIMARY KEY,
+                accelerator TEXT NOT NULL
+            );"
+        ).map_err(|e| format!("Failed to create shortcut settings table: {}", e))?;
+
+        Ok(Self { conn })
+    }
+
+    /// Saved (action, accelerator) pairs
+    pub fn get_bindings(&self) -> Result<Vec<(String, String)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT action, accelerator FROM global_shortcuts ORDER BY action")
+            .map_err(|e| format!("Failed to prepare shortcuts query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to get shortcuts: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read shortcut row: {}", e))
+    }
+
+    pub fn set_binding(&self, action: &str, accelerator: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO global_shortcuts (action, accelerator) VALUES (?1, ?2)",
+                params![action, accelerator],
+            )
+            .map_err(|e| format!("Failed to save shortcut: {}", e))?;
+        Ok(())
+    }
+
+    pub fn remove_binding(&self, action: &str) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM global_shortcuts WHERE action = ?1", params![action])
+            .map_err(|e| format!("Failed to remove shortcut: {}", e))?;
+        Ok(())
+    }
+}
+
+pub type ShortcutSettingsState = Arc<Mutex<ShortcutSettingsStore>>;
+
+pub fn create_shortcut_settings_state() -> Result<ShortcutSettingsState, String> {
+    let store = ShortcutSettingsStore::new()?;
+    Ok(Arc::new(Mutex::new(store)))
+}
+
+/// Saved bindings (none if the settings can't be read)
+pub fn saved_bindings(state: &ShortcutSettingsState) -> Vec<(String, String)> {
+    state
+        .lock()
+        .ok()
+        .and_then(|store| store.get_bindings().ok())
+        .unwrap_or_default()
+}
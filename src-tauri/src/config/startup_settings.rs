@@ -0,0 +1,117 @@
+//! Startup settings persistence
+//!
+//! Controls which startup steps the backend runs on its own before the UI
+//! takes over (see `crate::startup`).
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartupConfig {
+    /// Extract bundle tokens (init the API client) at launch
+    pub auto_init: bool,
+    /// Log in with saved credentials at launch
+    pub auto_restore_session: bool,
+    /// Check network connectivity at launch
+    pub auto_offline_check: bool,
+}
+
+pub struct StartupSettingsStore {
+    conn: Connection,
+}
+
+impl StartupSettingsStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        let db_path = data_dir.join("startup_settings.db");
+        let conn = Connection::open(&db_path)
+            .map_err(|e| format!("Failed to open startup settings database: {}", e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS startup_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                auto_init INTEGER NOT NULL DEFAULT 0,
+                auto_restore_session INTEGER NOT NULL DEFAULT 0,
+                auto_offline_check INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT OR IGNORE INTO startup_settings (id) VALUES (1);"
+        ).map_err(|e| format!("Failed to create startup settings table: {}", e))?;
+
+        Ok(Self { conn })
+    }
+
+    pub fn get_config(&self) -> Result<StartupConfig, String> {
+        self.conn
+            .query_row(
+                "SELECT auto_init, auto_restore_session, auto_offline_check FROM startup_settings WHERE id = 1",
+                [],
+                |row| {
+                    Ok(StartupConfig {
+                        auto_init: row.get::<_, i64>(0)? != 0,
+                        auto_restore_session: row.get::<_, i64>(1)? != 0,
+                        auto_offline_check: row.get::<_, i64>(2)? != 0,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to get startup settings: {}", e))
+    }
+
+    pub fn set_config(&self, config: &StartupConfig) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE startup_settings SET auto_init = ?1, auto_restore_session = ?2, auto_offline_check = ?3 WHERE id = 1",
+                params![
+                    config.auto_init as i64,
+                    config.auto_restore_session as i64,
+                    config.auto_offline_check as i64,
+                ],
+            )
+            .map_err(|e| format!("Failed to set startup settings: {}", e))?;
+        Ok(())
+    }
+}
+
+pub type StartupSettingsState = Arc<Mutex<StartupSettingsStore>>;
+
+pub fn create_startup_settings_state() -> Result<StartupSettingsState, String> {
+    let store = StartupSettingsStore::new()?;
+    Ok(Arc::new(Mutex::new(store)))
+}
+
+/// Saved startup config (everything off if the settings can't be read)
+pub fn saved_startup_config(state: &StartupSettingsState) -> StartupConfig {
+    state
+        .lock()
+        .ok()
+        .and_then(|store| store.get_config().ok())
+        .unwrap_or_default()
+}
+
+// Tauri commands
+
+#[tauri::command]
+pub fn get_startup_config(
+    state: tauri::State<StartupSettingsState>,
+) -> Result<StartupConfig, String> {
+    log::info!("Command: get_startup_config");
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.get_config()
+}
+
+/// Save the startup config; applies from the next launch
+#[tauri::command]
+pub fn set_startup_config(
+    config: StartupConfig,
+    state: tauri::State<StartupSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: set_startup_config {:?}", config);
+    let store = state.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_config(&config)
+}
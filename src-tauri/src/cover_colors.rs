@@ -0,0 +1,227 @@
+//! Dominant colors of cover art
+//!
+//! Used to theme the UI to the album that is playing. The cover is
+//! downsampled, transparent pixels and uniform padding bars (letterboxed
+//! covers) are ignored, and a median cut splits the remaining pixels into
+//! at most `count` boxes whose average colors are returned, most common first.
+
+use image::{DynamicImage, GenericImageView, Rgba};
+use serde::Serialize;
+
+/// Covers are reduced to at most this size before analysis
+const SAMPLE_SIZE: u32 = 64;
+
+/// Pixels more transparent than this are skipped
+const MIN_ALPHA: u8 = 128;
+
+/// Per-channel tolerance when detecting uniform padding
+const PADDING_TOLERANCE: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl RgbColor {
+    pub const BLACK: RgbColor = RgbColor { r: 0, g: 0, b: 0 };
+    pub const WHITE: RgbColor = RgbColor { r: 255, g: 255, b: 255 };
+
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// WCAG relative luminance
+    fn luminance(&self) -> f64 {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    /// WCAG contrast ratio (1.0 to 21.0)
+    pub fn contrast(&self, other: &RgbColor) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
+/// Dominant colors of a cover plus a readable text/background pair
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoverPalette {
+    /// Most common first
+    pub colors: Vec<RgbColor>,
+    pub background: RgbColor,
+    /// Black or white, whichever contrasts more with `background`
+    pub text: RgbColor,
+}
+
+/// Palette of an image with up to `count` colors
+pub fn palette_from_image(image: &DynamicImage, count: usize) -> Option<CoverPalette> {
+    let sample = if image.width() > SAMPLE_SIZE || image.height() > SAMPLE_SIZE {
+        image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+    } else {
+        image.clone()
+    };
+    let (x, y, width, height) = content_bounds(&sample);
+
+    let pixels: Vec<[u8; 3]> = sample
+        .view(x, y, width, height)
+        .pixels()
+        .filter(|(_, _, p)| p[3] >= MIN_ALPHA)
+        .map(|(_, _, p)| [p[0], p[1], p[2]])
+        .collect();
+
+    let colors = median_cut(pixels, count.max(1));
+    let background = *colors.first()?;
+    let text = if background.contrast(&RgbColor::WHITE) >= background.contrast(&RgbColor::BLACK) {
+        RgbColor::WHITE
+    } else {
+        RgbColor::BLACK
+    };
+
+    Some(CoverPalette { colors, background, text })
+}
+
+/// Decode an image file and compute its palette
+pub fn palette_from_bytes(bytes: &[u8], count: usize) -> Result<CoverPalette, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("Failed to decode cover: {}", e))?;
+    palette_from_image(&image, count).ok_or_else(|| "Cover has no opaque pixels".to_string())
+}
+
+fn similar(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+    (0..4).all(|i| a[i].abs_diff(b[i]) <= PADDING_TOLERANCE)
+}
+
+/// Bounds of the image without uniform bars along its edges.
+/// At most a quarter of each dimension is trimmed from each side.
+fn content_bounds(image: &DynamicImage) -> (u32, u32, u32, u32) {
+    let (width, height) = image.dimensions();
+    if width < 4 || height < 4 {
+        return (0, 0, width, height);
+    }
+
+    let corner = image.get_pixel(0, 0);
+    let row_is_padding = |y: u32| (0..width).all(|x| similar(image.get_pixel(x, y), corner));
+    let col_is_padding = |x: u32| (0..height).all(|y| similar(image.get_pixel(x, y), corner));
+
+    let (max_x, max_y) = (width / 4, height / 4);
+    let top = (0..max_y).take_while(|&y| row_is_padding(y)).count() as u32;
+    let bottom = (0..max_y).take_while(|&i| row_is_padding(height - 1 - i)).count() as u32;
+    let left = (0..max_x).take_while(|&x| col_is_padding(x)).count() as u32;
+    let right = (0..max_x).take_while(|&i| col_is_padding(width - 1 - i)).count() as u32;
+
+    // A solid image is all "padding": keep it whole
+    if top == max_y && left == max_x {
+        return (0, 0, width, height);
+    }
+
+    (left, top, width - left - right, height - top - bottom)
+}
+
+/// Split pixels into at most `count` boxes along their widest channel and
+/// return each box's average color, largest box first
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<RgbColor> {
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    let range = |pixels: &[[u8; 3]]| -> (usize, u8) {
+        (0..3)
+            .map(|c| {
+                let min = pixels.iter().map(|p| p[c]).min().unwrap_or(0);
+                let max = pixels.iter().map(|p| p[c]).max().unwrap_or(0);
+                (c, max - min)
+            })
+            .max_by_key(|&(_, r)| r)
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        // Split the box with the widest channel range
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (i, range(b)))
+            .filter(|(_, (_, r))| *r > 0)
+            .max_by_key(|(_, (_, r))| *r)
+            .map(|(i, (c, _))| (i, c))
+        else {
+            break;
+        };
+
+        let mut bucket = boxes.swap_remove(index);
+        bucket.sort_unstable_by_key(|p| p[channel]);
+        // Split at the median, moved off runs of equal values
+        let mut split = bucket.len() / 2;
+        let median = bucket[split][channel];
+        split = bucket.iter().position(|p| p[channel] == median).unwrap_or(split);
+        if split == 0 {
+            split = bucket.iter().rposition(|p| p[channel] == median).map_or(1, |i| i + 1);
+        }
+        let upper = bucket.split_off(split);
+        boxes.push(bucket);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|b| std::cmp::Reverse(b.len()));
+    boxes
+        .iter()
+        .map(|b| {
+            let n = b.len() as u64;
+            let sum = b.iter().fold([0u64; 3], |acc, p| [acc[0] + p[0] as u64, acc[1] + p[1] as u64, acc[2] + p[2] as u64]);
+            RgbColor {
+                r: (sum[0] / n) as u8,
+                g: (sum[1] / n) as u8,
+                b: (sum[2] / n) as u8,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_solid_cover() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([200, 30, 40, 255])));
+
+        let palette = palette_from_image(&image, 5).unwrap();
+        assert_eq!(palette.colors, vec![RgbColor { r: 200, g: 30, b: 40 }]);
+        assert_eq!(palette.background, palette.colors[0]);
+        assert_eq!(palette.text, RgbColor::WHITE);
+    }
+
+    #[test]
+    fn test_two_color_cover_ignores_padding_and_transparency() {
+        // Blue (larger) and yellow halves inside black letterbox bars,
+        // with a transparent patch that must not count
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            if !(8..56).contains(&y) {
+                Rgba([0, 0, 0, 255])
+            } else if x < 8 {
+                Rgba([255, 0, 255, 0])
+            } else if x < 40 {
+                Rgba([20, 40, 220, 255])
+            } else {
+                Rgba([250, 220, 20, 255])
+            }
+        });
+
+        let palette = palette_from_image(&DynamicImage::ImageRgba8(image), 2).unwrap();
+        assert_eq!(
+            palette.colors,
+            vec![RgbColor { r: 20, g: 40, b: 220 }, RgbColor { r: 250, g: 220, b: 20 }]
+        );
+        assert_eq!(palette.text, RgbColor::WHITE);
+    }
+}
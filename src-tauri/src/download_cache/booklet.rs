@@ -0,0 +1,151 @@
+//! Digital booklet (PDF goodie) downloads
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::{sanitize_filename, Downloader};
+use crate::api::models::Album;
+
+/// Progress update for a booklet download
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookletProgress {
+    pub album_id: String,
+    pub progress_percent: u8,
+    pub bytes_downloaded: u64,
+    pub total_bytes: Option<u64>,
+}
+
+/// Download an album's booklet PDF.
+///
+/// `dest` may be a file path or an existing directory; in the latter case the
+/// file is named after the album. Returns the path the PDF was written to.
+pub async fn download_booklet<F>(
+    album: &Album,
+    downloader: &Downloader,
+    dest: &Path,
+    mut on_progress: F,
+) -> Result<PathBuf, String>
+where
+    F: FnMut(BookletProgress),
+{
+    let album_id = album.id.as_str();
+    let url = album
+        .booklet()
+        .and_then(|goodie| goodie.download_url())
+        .ok_or_else(|| format!("Album \"{}\" has no digital booklet", album.title))?
+        .to_string();
+
+    let target = if dest.is_dir() {
+        dest.join(format!(
+            "{} - {} (Booklet).pdf",
+            sanitize_filename(&album.artist.name),
+            sanitize_filename(&album.title)
+        ))
+    } else {
+        dest.to_path_buf()
+    };
+
+    log::info!("Downloading booklet for album {} to {:?}", album_id, target);
+
+    let bytes = downloader
+        .download_with_progress(&url, &target, |progress_percent, bytes_downloaded, total_bytes| {
+            on_progress(BookletProgress {
+                album_id: album_id.to_string(),
+                progress_percent,
+                bytes_downloaded,
+                total_bytes,
+            })
+        })
+        .await
+        .map_err(|e| format!("Failed to download booklet: {}", e))?;
+
+    log::info!("Booklet for album {} downloaded: {} bytes", album_id, bytes);
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::mock_client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qbz-booklet-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_booklet_is_fetched_to_target_path() {
+        let server = MockServer::start().await;
+        let pdf = b"%PDF-1.4 booklet".to_vec();
+
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .and(query_param("album_id", "alb1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "alb1",
+                "title": "Kind of Blue",
+                "goodies": [{
+                    "id": 1,
+                    "file_format_id": 21,
+                    "name": "Livret Numérique",
+                    "original_url": format!("{}/goodies/booklet.pdf", server.uri())
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/goodies/booklet.pdf"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(pdf.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = temp_dir("fetch");
+        let target = dir.join("booklet.pdf");
+        let mut updates = Vec::new();
+
+        let album = mock_client(&server).get_album("alb1").await.unwrap();
+        let written = download_booklet(&album, &Downloader::new(), &target, |p| updates.push(p))
+            .await
+            .unwrap();
+
+        assert_eq!(written, target);
+        assert_eq!(std::fs::read(&target).unwrap(), pdf);
+        assert_eq!(updates.last().map(|p| p.progress_percent), Some(100));
+        assert_eq!(updates[0].album_id, "alb1");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_album_without_booklet_errors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(paths::ALBUM_GET))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "alb2",
+                "title": "No Extras",
+                "goodies": []
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = temp_dir("missing");
+        let album = mock_client(&server).get_album("alb2").await.unwrap();
+        let err = download_booklet(&album, &Downloader::new(), &dir, |_| {})
+            .await
+            .unwrap_err();
+
+        assert!(err.contains("has no digital booklet"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,27 +1,38 @@
 //! Tauri commands for download cache functionality
 
+use futures_util::stream::{self, StreamExt};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, State};
 
 use crate::api::models::Quality;
+use crate::api::ApiError;
 use crate::AppState;
 
+use crate::download_cache::booklet;
+use crate::download_cache::upgrade;
+use crate::download_cache::verify::{self, OfflineVerifyReport};
+use crate::download_cache::grouping::{self, OfflineAlbumGroup};
+use crate::download_cache::estimate::{build_estimate, estimate_track_bytes, DownloadSizeEstimate};
 use crate::download_cache::path_validator::{self, PathValidationResult};
 use crate::download_cache::{DownloadCacheDb, DownloadCacheState};
-use crate::download_cache::metadata::{fetch_complete_metadata, write_flac_tags, embed_artwork, organize_download, save_album_artwork};
+use crate::download_cache::metadata::{CompleteTrackMetadata, fetch_complete_metadata, write_flac_tags, embed_artwork, organize_download, save_album_artwork};
 use super::{
     CachedTrackInfo, DownloadCacheStats, DownloadStatus,
     TrackDownloadInfo,
 };
 
-/// Post-process a downloaded track: fetch metadata, tag FLAC, embed artwork, organize files
+/// Track lookups in flight while estimating a download's size
+const ESTIMATE_LOOKUP_CONCURRENCY: usize = 4;
+
+/// Post-process a downloaded track: fetch metadata, tag FLAC, embed artwork, organize files.
+/// Returns the new path and the metadata fetched.
 async fn post_process_track(
     track_id: u64,
     current_path: &str,
     download_root: &str,
     qobuz_client: &crate::api::QobuzClient,
     library_db: Arc<tokio::sync::Mutex<crate::library::database::LibraryDatabase>>,
-) -> Result<String, String> {
+) -> Result<(String, CompleteTrackMetadata), String> {
     log::info!("Post-processing track {}", track_id);
 
     // 1. Fetch complete metadata from Qobuz
@@ -108,7 +119,7 @@ async fn post_process_track(
     }
     
     log::info!("Track {} organized to: {}", track_id, new_path);
-    Ok(new_path)
+    Ok((new_path, metadata))
 }
 
 /// Download a track for offline listening
@@ -163,6 +174,12 @@ pub async fn download_track(
 
     // Spawn download task
     tokio::spawn(async move {
+        // Stays queued while the connection is metered (if so configured)
+        if crate::metered::metered().is_paused() {
+            log::info!("Download of track {} waiting for an unmetered connection", track_id);
+        }
+        crate::metered::metered().wait_until_unmetered().await;
+
         let _permit = match semaphore.acquire_owned().await {
             Ok(permit) => permit,
             Err(err) => {
@@ -199,6 +216,15 @@ pub async fn download_track(
                 .await
         };
 
+        // A preview clip is not worth keeping offline
+        let stream_url = stream_url.and_then(|s| {
+            if s.is_preview {
+                Err(ApiError::ApiResponse("Only a preview is available".to_string()))
+            } else {
+                Ok(s)
+            }
+        });
+
         let url = match stream_url {
             Ok(s) => s.url,
             Err(e) => {
@@ -246,12 +272,22 @@ pub async fn download_track(
                     &*qobuz_client,
                     library_db.clone(),
                 ).await {
-                    Ok(new_path) => {
+                    Ok((new_path, metadata)) => {
                         // Update database with new path
                         let db_guard = db.lock().await;
                         if let Err(e) = db_guard.update_file_path(track_id, &new_path) {
                             log::error!("Failed to update path for track {}: {}", track_id, e);
                         }
+                        if let Err(e) = db_guard.update_track_position(
+                            track_id,
+                            metadata.disc_number,
+                            metadata.track_number,
+                        ) {
+                            log::error!("Failed to update album position for track {}: {}", track_id, e);
+                        }
+                        if let Ok(metadata) = std::fs::metadata(&new_path) {
+                            let _ = db_guard.update_file_size(track_id, metadata.len());
+                        }
                         
                         let _ = app.emit("download:processed", serde_json::json!({
                             "trackId": track_id,
@@ -279,6 +315,140 @@ pub async fn download_track(
     Ok(())
 }
 
+/// Check that every downloaded file still exists (and, unless `check_contents`
+/// is false, isn't truncated or damaged). Broken entries are marked failed.
+/// Emits `offline-verify:progress` events while checking.
+#[tauri::command]
+pub async fn verify_offline_library(
+    check_contents: Option<bool>,
+    app_handle: AppHandle,
+    cache_state: State<'_, DownloadCacheState>,
+) -> Result<OfflineVerifyReport, String> {
+    log::info!("Command: verify_offline_library");
+
+    let entries = {
+        let db = cache_state.db.lock().await;
+        db.get_ready_files()?
+    };
+
+    let check_contents = check_contents.unwrap_or(true);
+    let report = tokio::task::spawn_blocking(move || {
+        verify::check_files(&entries, check_contents, |progress| {
+            let _ = app_handle.emit("offline-verify:progress", progress);
+        })
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e))?;
+
+    log::info!(
+        "Offline library verified: {} ok, {} missing, {} corrupt",
+        report.ok,
+        report.missing.len(),
+        report.corrupt.len()
+    );
+
+    let db = cache_state.db.lock().await;
+    verify::apply_report(&db, &report)?;
+    Ok(report)
+}
+
+/// Re-download an offline track in a higher quality (e.g. after a
+/// subscription upgrade). The existing file is only replaced once the new
+/// one verifies (see `upgrade::verify_audio`); it is re-tagged first and its
+/// index entry updated.
+#[tauri::command]
+pub async fn upgrade_offline_track(
+    track_id: u64,
+    new_quality: Quality,
+    state: State<'_, AppState>,
+    cache_state: State<'_, DownloadCacheState>,
+    app_handle: AppHandle,
+) -> Result<CachedTrackInfo, String> {
+    log::info!("Command: upgrade_offline_track {} to {:?}", track_id, new_quality);
+
+    let current_path = {
+        let db = cache_state.db.lock().await;
+        let info = db
+            .get_track(track_id)?
+            .filter(|info| info.status == DownloadStatus::Ready)
+            .ok_or_else(|| format!("Track {} is not downloaded", track_id))?;
+        upgrade::check_upgrade(upgrade::downloaded_quality(&info), new_quality)?;
+        db.get_file_path(track_id)?
+            .ok_or_else(|| format!("Track {} is not downloaded", track_id))?
+    };
+
+    let stream_url = state
+        .client
+        .lock()
+        .await
+        .get_stream_url(track_id, new_quality)
+        .await
+        .map_err(|e| format!("Failed to get stream URL: {}", e))?;
+    let delivered = Quality::from_id(stream_url.format_id);
+    if stream_url.is_preview || !delivered.is_some_and(|quality| quality >= new_quality) {
+        return Err(format!("Track {} is not available in {}", track_id, new_quality.label()));
+    }
+
+    let current = std::path::PathBuf::from(&current_path);
+    let staged = upgrade::staged_path(&current);
+    if let Err(e) = cache_state
+        .downloader
+        .download_to_file(&stream_url.url, &staged, track_id, Some(&app_handle))
+        .await
+    {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    let verified = tokio::task::spawn_blocking({
+        let staged = staged.clone();
+        move || upgrade::verify_audio(&staged, new_quality)
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e))?;
+    if let Err(e) = verified {
+        let _ = std::fs::remove_file(&staged);
+        return Err(format!("Upgraded file for track {} failed verification: {}", track_id, e));
+    }
+
+    // Re-tag the new file before it replaces the old one
+    {
+        let staged_str = staged.to_string_lossy().to_string();
+        let metadata = fetch_complete_metadata(track_id, &*state.client.lock().await).await;
+        match metadata {
+            Ok(metadata) => {
+                if let Err(e) = write_flac_tags(&staged_str, &metadata) {
+                    log::warn!("Failed to tag upgraded track {}: {}", track_id, e);
+                }
+                if let Some(artwork_url) = &metadata.artwork_url {
+                    if let Err(e) = embed_artwork(&staged_str, artwork_url).await {
+                        log::warn!("Failed to embed artwork for upgraded track {}: {}", track_id, e);
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to fetch metadata for upgraded track {}: {}", track_id, e),
+        }
+    }
+
+    // Checksum and properties are read before the index is locked
+    let db = cache_state.db.clone();
+    let replacement = tokio::task::spawn_blocking(move || {
+        let replacement = upgrade::prepare_upgrade(track_id, &current, &staged, new_quality)?;
+        upgrade::install_upgrade(&db, track_id, &current, &staged, &replacement)?;
+        Ok::<_, String>(replacement)
+    })
+    .await
+    .map_err(|e| format!("Upgrade task failed: {}", e))??;
+    log::info!("Track {} upgraded to {} ({} bytes)", track_id, replacement.quality, replacement.file_size_bytes);
+
+    let _ = app_handle.emit("download:upgraded", serde_json::json!({
+        "trackId": track_id,
+        "quality": replacement.quality
+    }));
+    cache_state.db.lock().await.get_track(track_id)?
+        .ok_or_else(|| format!("Track {} is not downloaded", track_id))
+}
+
 /// Check if a track is cached and ready for playback
 #[tauri::command]
 pub async fn is_track_downloaded(
@@ -318,6 +488,15 @@ pub async fn get_downloaded_tracks(
     db.get_all_tracks()
 }
 
+/// Get downloaded tracks grouped by album, sorted by artist then album
+#[tauri::command]
+pub async fn get_offline_library_grouped(
+    cache_state: State<'_, DownloadCacheState>,
+) -> Result<Vec<OfflineAlbumGroup>, String> {
+    let db = cache_state.db.lock().await;
+    grouping::offline_library_grouped(&db)
+}
+
 /// Get download cache statistics
 #[tauri::command]
 pub async fn get_download_cache_stats(
@@ -364,27 +543,43 @@ pub async fn clear_download_cache(
     library_state: State<'_, crate::library::commands::LibraryState>,
 ) -> Result<(), String> {
     log::info!("Command: clear_download_cache");
+    clear_offline_downloads(&cache_state, &library_state).await?;
+    Ok(())
+}
 
+/// Delete every downloaded file and its library entry, returning the bytes freed
+pub async fn clear_offline_downloads(
+    cache_state: &DownloadCacheState,
+    library_state: &crate::library::commands::LibraryState,
+) -> Result<u64, String> {
     let db = cache_state.db.lock().await;
 
     // Get all file paths and clear DB
     let paths = db.clear_all()?;
     drop(db);
 
+    let mut freed = 0;
+    let mut remove = |p: &std::path::Path| {
+        let size = std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+        if std::fs::remove_file(p).is_ok() {
+            freed += size;
+        }
+    };
+
     // Delete all files
     for path in paths {
         let p = std::path::Path::new(&path);
         if p.exists() {
-            let _ = std::fs::remove_file(p);
+            remove(p);
         }
     }
 
     // Also clear the tracks directory
-    let tracks_dir = cache_state.cache_dir.join("tracks");
+    let tracks_dir = cache_state.cache_dir().join("tracks");
     if tracks_dir.exists() {
         if let Ok(entries) = std::fs::read_dir(&tracks_dir) {
             for entry in entries.flatten() {
-                let _ = std::fs::remove_file(entry.path());
+                remove(&entry.path());
             }
         }
     }
@@ -395,7 +590,78 @@ pub async fn clear_download_cache(
         .map_err(|e| format!("Failed to remove downloads from library: {}", e))?;
     log::info!("Removed {} Qobuz downloads from library", removed_count);
 
-    Ok(())
+    Ok(freed)
+}
+
+/// Estimate disk usage for downloading the given tracks at `quality`
+#[tauri::command]
+pub async fn estimate_download_size(
+    track_ids: Vec<u64>,
+    quality: Quality,
+    state: State<'_, AppState>,
+    cache_state: State<'_, DownloadCacheState>,
+) -> Result<DownloadSizeEstimate, String> {
+    log::info!("Command: estimate_download_size {} tracks at {:?}", track_ids.len(), quality);
+
+    let client = state.client.lock().await.clone();
+    let client = &client;
+    let per_track: Vec<Result<(u64, u64), String>> = stream::iter(track_ids)
+        .map(|track_id| async move {
+            let track = client
+                .get_track(track_id)
+                .await
+                .map_err(|e| format!("Failed to get track {}: {}", track_id, e))?;
+            let bytes = estimate_track_bytes(
+                track.duration as u64,
+                quality,
+                track.maximum_sampling_rate,
+                track.maximum_bit_depth,
+            );
+            Ok((track_id, bytes))
+        })
+        .buffered(ESTIMATE_LOOKUP_CONCURRENCY)
+        .collect()
+        .await;
+    let per_track = per_track.into_iter().collect::<Result<Vec<_>, String>>()?;
+
+    let limit = *cache_state.limit_bytes.lock().await;
+    let db = cache_state.db.lock().await;
+    let stats = db.get_stats(&cache_state.get_cache_path(), limit)?;
+
+    Ok(build_estimate(per_track, stats.total_size_bytes, limit))
+}
+
+/// Download an album's digital booklet PDF to `dest_path` (file or directory).
+/// Emits `booklet:progress` events while downloading.
+#[tauri::command]
+pub async fn download_booklet(
+    album_id: String,
+    dest_path: String,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    cache_state: State<'_, DownloadCacheState>,
+) -> Result<String, String> {
+    log::info!("Command: download_booklet {} -> {}", album_id, dest_path);
+
+    let album = {
+        let client = state.client.lock().await;
+        client
+            .get_album(&album_id)
+            .await
+            .map_err(|e| format!("Failed to get album {}: {}", album_id, e))?
+    };
+
+    let path = booklet::download_booklet(
+        &album,
+        &cache_state.downloader,
+        std::path::Path::new(&dest_path),
+        |progress| {
+            let _ = app_handle.emit("booklet:progress", progress);
+        },
+    )
+    .await?;
+
+    Ok(path.to_string_lossy().to_string())
 }
 
 /// Set cache size limit
@@ -426,7 +692,7 @@ pub async fn open_download_cache_folder(
 ) -> Result<(), String> {
     log::info!("Command: open_download_cache_folder");
 
-    let path = cache_state.cache_dir.clone();
+    let path = cache_state.cache_dir();
 
     // Ensure directory exists
     std::fs::create_dir_all(&path)
@@ -554,7 +820,7 @@ pub async fn check_download_root_mounted(
 ) -> Result<bool, String> {
     log::info!("Command: check_download_root_mounted");
     
-    let root_path = cache_state.cache_dir.to_string_lossy().to_string();
+    let root_path = cache_state.cache_dir().to_string_lossy().to_string();
     super::path_validator::is_download_root_available(&root_path)
 }
 
@@ -571,7 +837,7 @@ pub async fn move_downloads_to_path(
 ) -> Result<super::path_validator::MoveReport, String> {
     log::info!("Command: move_downloads_to_path: {}", new_path);
     
-    let old_path = cache_state.cache_dir.to_string_lossy().to_string();
+    let old_path = cache_state.cache_dir().to_string_lossy().to_string();
     super::path_validator::move_downloads_to_new_path(&old_path, &new_path)
 }
 
@@ -582,7 +848,7 @@ pub async fn detect_legacy_downloads(
 ) -> Result<super::MigrationStatus, String> {
     log::info!("Command: detect_legacy_downloads");
     
-    let tracks_dir = cache_state.cache_dir.join("tracks");
+    let tracks_dir = cache_state.cache_dir().join("tracks");
     
     match super::detect_legacy_downloads(&tracks_dir) {
         Ok(track_ids) => {
@@ -606,7 +872,7 @@ pub async fn start_legacy_migration(
 ) -> Result<(), String> {
     log::info!("Command: start_legacy_migration");
     
-    let tracks_dir = cache_state.cache_dir.join("tracks");
+    let tracks_dir = cache_state.cache_dir().join("tracks");
     let track_ids = super::detect_legacy_downloads(&tracks_dir)?;
     
     if track_ids.is_empty() {
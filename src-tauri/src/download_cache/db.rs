@@ -3,6 +3,7 @@
 use rusqlite::{Connection, params};
 use std::path::Path;
 
+use super::upgrade::ReplacementFile;
 use super::{CachedTrackInfo, DownloadCacheStats, DownloadStatus, ReadyTrackForSync, TrackDownloadInfo};
 
 /// Database wrapper for cached tracks index
@@ -58,6 +59,12 @@ impl DownloadCacheDb {
             "
         ).map_err(|e| format!("Failed to initialize database schema: {}", e))?;
 
+        // Added with quality upgrades; only set for replaced files
+        let _ = self.conn.execute("ALTER TABLE cached_tracks ADD COLUMN checksum TEXT", []);
+        // Album position, recorded when a download is post-processed
+        let _ = self.conn.execute("ALTER TABLE cached_tracks ADD COLUMN disc_number INTEGER", []);
+        let _ = self.conn.execute("ALTER TABLE cached_tracks ADD COLUMN track_number INTEGER", []);
+
         Ok(())
     }
 
@@ -181,7 +188,7 @@ impl DownloadCacheDb {
     /// Get track info
     pub fn get_track(&self, track_id: u64) -> Result<Option<CachedTrackInfo>, String> {
         let result = self.conn.query_row(
-            "SELECT track_id, title, artist, album, album_id, duration_secs, file_size_bytes, quality, bit_depth, sample_rate, status, progress_percent, error_message, created_at, last_accessed_at
+            "SELECT track_id, title, artist, album, album_id, duration_secs, file_size_bytes, quality, bit_depth, sample_rate, status, progress_percent, error_message, created_at, last_accessed_at, disc_number, track_number
              FROM cached_tracks WHERE track_id = ?1",
             params![track_id as i64],
             |row| {
@@ -201,6 +208,8 @@ impl DownloadCacheDb {
                     error_message: row.get(12)?,
                     created_at: row.get(13)?,
                     last_accessed_at: row.get(14)?,
+                    disc_number: row.get::<_, Option<i64>>(15)?.map(|v| v as u32),
+                    track_number: row.get::<_, Option<i64>>(16)?.map(|v| v as u32),
                 })
             },
         );
@@ -215,7 +224,7 @@ impl DownloadCacheDb {
     /// Get all cached tracks
     pub fn get_all_tracks(&self) -> Result<Vec<CachedTrackInfo>, String> {
         let mut stmt = self.conn.prepare(
-            "SELECT track_id, title, artist, album, album_id, duration_secs, file_size_bytes, quality, bit_depth, sample_rate, status, progress_percent, error_message, created_at, last_accessed_at
+            "SELECT track_id, title, artist, album, album_id, duration_secs, file_size_bytes, quality, bit_depth, sample_rate, status, progress_percent, error_message, created_at, last_accessed_at, disc_number, track_number
              FROM cached_tracks ORDER BY last_accessed_at DESC"
         ).map_err(|e| format!("Failed to prepare query: {}", e))?;
 
@@ -236,6 +245,8 @@ impl DownloadCacheDb {
                 error_message: row.get(12)?,
                 created_at: row.get(13)?,
                 last_accessed_at: row.get(14)?,
+                disc_number: row.get::<_, Option<i64>>(15)?.map(|v| v as u32),
+                track_number: row.get::<_, Option<i64>>(16)?.map(|v| v as u32),
             })
         }).map_err(|e| format!("Failed to query tracks: {}", e))?;
 
@@ -364,6 +375,72 @@ impl DownloadCacheDb {
         Ok(())
     }
 
+    /// Record where a track sits on its album
+    pub fn update_track_position(
+        &self,
+        track_id: u64,
+        disc_number: Option<u32>,
+        track_number: Option<u32>,
+    ) -> Result<(), String> {
+        self.conn.execute(
+            "UPDATE cached_tracks SET disc_number = ?1, track_number = ?2 WHERE track_id = ?3",
+            params![disc_number, track_number, track_id as i64],
+        ).map_err(|e| format!("Failed to update track position: {}", e))?;
+        Ok(())
+    }
+
+    /// Update recorded file size (after tagging changed it)
+    pub fn update_file_size(&self, track_id: u64, size_bytes: u64) -> Result<(), String> {
+        self.conn.execute(
+            "UPDATE cached_tracks SET file_size_bytes = ?1 WHERE track_id = ?2",
+            params![size_bytes as i64, track_id as i64],
+        ).map_err(|e| format!("Failed to update file size: {}", e))?;
+        Ok(())
+    }
+
+    /// Get (track_id, file_path, file_size_bytes) for every ready track
+    pub fn get_ready_files(&self) -> Result<Vec<(u64, String, u64)>, String> {
+        let mut stmt = self.conn.prepare(
+            "SELECT track_id, file_path, file_size_bytes FROM cached_tracks WHERE status = 'ready' ORDER BY track_id"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let files = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as u64,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? as u64,
+            ))
+        })
+        .map_err(|e| format!("Failed to query ready files: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect ready files: {}", e))?;
+
+        Ok(files)
+    }
+
+    /// Point a ready track at a replacement file, recording its size,
+    /// quality and checksum in one statement
+    pub fn replace_file(&self, track_id: u64, file: &ReplacementFile) -> Result<(), String> {
+        let updated = self.conn.execute(
+            "UPDATE cached_tracks SET file_path = ?1, file_size_bytes = ?2, quality = ?3, bit_depth = ?4,
+                 sample_rate = ?5, checksum = ?6, status = 'ready', error_message = NULL
+             WHERE track_id = ?7",
+            params![
+                file.file_path,
+                file.file_size_bytes as i64,
+                file.quality,
+                file.bit_depth.map(|v| v as i64),
+                file.sample_rate,
+                file.checksum,
+                track_id as i64,
+            ],
+        ).map_err(|e| format!("Failed to record replaced file: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Track {} is not in the download cache", track_id));
+        }
+        Ok(())
+    }
+
     /// Update artwork path for a track
     pub fn update_artwork_path(&self, track_id: u64, artwork_path: &str) -> Result<(), String> {
         self.conn.execute(
@@ -372,4 +449,33 @@ impl DownloadCacheDb {
         ).map_err(|e| format!("Failed to update artwork path: {}", e))?;
         Ok(())
     }
+
+    /// Rewrite file and artwork paths starting with `old_prefix` to start with
+    /// `new_prefix`, in one transaction. Returns the number of rows changed.
+    pub fn relocate_paths(&mut self, old_prefix: &str, new_prefix: &str) -> Result<usize, String> {
+        let tx = self.conn.transaction()
+            .map_err(|e| format!("Failed to start path update: {}", e))?;
+
+        let mut changed = 0;
+        for column in ["file_path", "artwork_path"] {
+            changed += tx.execute(
+                &format!(
+                    "UPDATE cached_tracks SET {col} = ?2 || substr({col}, length(?1) + 1)
+                     WHERE substr({col}, 1, length(?1)) = ?1",
+                    col = column
+                ),
+                params![old_prefix, new_prefix],
+            ).map_err(|e| format!("Failed to update {}: {}", column, e))?;
+        }
+
+        tx.commit().map_err(|e| format!("Failed to commit path update: {}", e))?;
+        Ok(changed)
+    }
+
+    /// Write a consistent copy of the index to `path`
+    pub fn backup_to(&self, path: &Path) -> Result<(), String> {
+        self.conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])
+            .map_err(|e| format!("Failed to copy download cache database: {}", e))?;
+        Ok(())
+    }
 }
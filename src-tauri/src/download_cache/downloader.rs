@@ -2,14 +2,20 @@
 
 use std::io::Write;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
 use tauri::{AppHandle, Emitter};
 
+use super::throughput::auto_quality;
 use super::{DownloadProgress, DownloadStatus};
+use crate::metered::{metered, MeteredGate};
 
 /// Downloader handles fetching audio files and saving them to disk
 pub struct Downloader {
     client: reqwest::Client,
+    /// Holds downloads to disk while the connection is metered
+    gate: &'static MeteredGate,
 }
 
 impl Downloader {
@@ -20,7 +26,13 @@ impl Downloader {
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client }
+        Self { client, gate: metered() }
+    }
+
+    /// Follow another metered gate than the process-wide one
+    pub fn with_gate(mut self, gate: &'static MeteredGate) -> Self {
+        self.gate = gate;
+        self
     }
 
     /// Download a file to disk with progress updates
@@ -33,32 +45,61 @@ impl Downloader {
     ) -> Result<u64, String> {
         log::info!("Downloading track {} to {:?}", track_id, dest_path);
 
-        // Create parent directories if needed
-        if let Some(parent) = dest_path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
-        }
-
-        // Start the download
-        let response = self
-            .client
-            .get(url)
-            .header("User-Agent", "Mozilla/5.0")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to start download: {}", e))?;
+        let downloaded = self
+            .download_with_progress(url, dest_path, |progress, downloaded, total_size| {
+                if let Some(app) = app_handle {
+                    let _ = app.emit(
+                        "download:progress",
+                        DownloadProgress {
+                            track_id,
+                            progress_percent: progress,
+                            bytes_downloaded: downloaded,
+                            total_bytes: total_size,
+                            status: DownloadStatus::Downloading,
+                        },
+                    );
+                }
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()));
-        }
+                log::debug!(
+                    "Download progress for track {}: {}% ({}/{:?} bytes)",
+                    track_id,
+                    progress,
+                    downloaded,
+                    total_size
+                );
+            })
+            .await?;
 
-        let total_size = response.content_length();
         log::info!(
-            "Download started for track {}, total size: {:?} bytes",
+            "Download complete for track {}: {} bytes",
             track_id,
-            total_size
+            downloaded
         );
 
+        Ok(downloaded)
+    }
+
+    /// Download any file to disk, reporting `(percent, bytes_downloaded, total_bytes)`
+    /// every 2% of progress.
+    ///
+    /// While the metered gate is paused the download waits, between chunks
+    /// once started, then asks for the rest of the file with a range
+    /// request (starting over if the server ignores it).
+    pub async fn download_with_progress<F>(
+        &self,
+        url: &str,
+        dest_path: &Path,
+        mut on_progress: F,
+    ) -> Result<u64, String>
+    where
+        F: FnMut(u8, u64, Option<u64>),
+    {
+        // Create parent directories if needed
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+
         // Create temp file for downloading
         let temp_path = dest_path.with_extension("tmp");
         let mut file = std::fs::File::create(&temp_path)
@@ -66,52 +107,76 @@ impl Downloader {
 
         let mut downloaded: u64 = 0;
         let mut last_progress: u8 = 0;
-
-        // Stream the response body
-        let mut stream = response.bytes_stream();
+        let mut total_size = None;
+        let mut transfer_time = Duration::ZERO;
         use futures_util::StreamExt;
 
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result.map_err(|e| format!("Download error: {}", e))?;
+        'transfer: loop {
+            if self.gate.is_paused() {
+                log::info!("Download to {:?} waiting for an unmetered connection", dest_path);
+                self.gate.wait_until_unmetered().await;
+            }
 
-            file.write_all(&chunk)
-                .map_err(|e| format!("Failed to write chunk: {}", e))?;
+            // Start (or resume) the download
+            let started = Instant::now();
+            let mut request = self.client.get(url).header("User-Agent", "Mozilla/5.0");
+            if downloaded > 0 {
+                request = request.header(RANGE, format!("bytes={}-", downloaded));
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Failed to start download: {}", e))?;
 
-            downloaded += chunk.len() as u64;
+            if !response.status().is_success() {
+                return Err(format!("HTTP error: {}", response.status()));
+            }
+            if downloaded > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+                log::info!("Server ignored the range request, restarting {:?}", dest_path);
+                file.set_len(0)
+                    .and_then(|_| std::io::Seek::rewind(&mut file))
+                    .map_err(|e| format!("Failed to reset temp file: {}", e))?;
+                downloaded = 0;
+                last_progress = 0;
+            }
 
-            // Calculate progress
-            let progress = if let Some(total) = total_size {
-                ((downloaded as f64 / total as f64) * 100.0) as u8
-            } else {
-                // If we don't know total size, report bytes downloaded
-                0
-            };
+            if downloaded == 0 {
+                total_size = response.content_length();
+                log::info!("Download started for {:?}, total size: {:?} bytes", dest_path, total_size);
+            }
 
-            // Emit progress event every 2% change
-            if progress != last_progress && (progress - last_progress >= 2 || progress == 100) {
-                last_progress = progress;
+            // Stream the response body
+            let mut stream = response.bytes_stream();
+            while let Some(chunk_result) = stream.next().await {
+                let chunk = chunk_result.map_err(|e| format!("Download error: {}", e))?;
 
-                if let Some(app) = app_handle {
-                    let _ = app.emit(
-                        "download:progress",
-                        DownloadProgress {
-                            track_id,
-                            progress_percent: progress,
-                            bytes_downloaded: downloaded,
-                            total_bytes: total_size,
-                            status: DownloadStatus::Downloading,
-                        },
-                    );
+                file.write_all(&chunk)
+                    .map_err(|e| format!("Failed to write chunk: {}", e))?;
+
+                downloaded += chunk.len() as u64;
+
+                // Calculate progress
+                let progress = if let Some(total) = total_size {
+                    ((downloaded as f64 / total as f64) * 100.0) as u8
+                } else {
+                    // If we don't know total size, report bytes downloaded
+                    0
+                };
+
+                // Report progress every 2% change
+                if progress != last_progress && (progress - last_progress >= 2 || progress == 100) {
+                    last_progress = progress;
+                    on_progress(progress, downloaded, total_size);
                 }
 
-                log::debug!(
-                    "Download progress for track {}: {}% ({}/{:?} bytes)",
-                    track_id,
-                    progress,
-                    downloaded,
-                    total_size
-                );
+                if self.gate.is_paused() && total_size.is_none_or(|total| downloaded < total) {
+                    log::info!("Connection became metered, pausing download to {:?}", dest_path);
+                    transfer_time += started.elapsed();
+                    continue 'transfer;
+                }
             }
+            transfer_time += started.elapsed();
+            break;
         }
 
         // Ensure all data is written
@@ -119,16 +184,12 @@ impl Downloader {
             .map_err(|e| format!("Failed to flush file: {}", e))?;
         drop(file);
 
+        auto_quality().record(downloaded, transfer_time);
+
         // Move temp file to final destination
         std::fs::rename(&temp_path, dest_path)
             .map_err(|e| format!("Failed to move temp file: {}", e))?;
 
-        log::info!(
-            "Download complete for track {}: {} bytes",
-            track_id,
-            downloaded
-        );
-
         Ok(downloaded)
     }
 
@@ -161,3 +222,67 @@ impl Default for Downloader {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    /// Serves `body`, or the part a range request asks for
+    struct RangeBody(Vec<u8>);
+
+    impl Respond for RangeBody {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let from = request
+                .headers
+                .get("range")
+                .and_then(|range| range.to_str().ok()?.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok());
+            match from {
+                Some(from) => ResponseTemplate::new(206).set_body_bytes(self.0[from..].to_vec()),
+                None => ResponseTemplate::new(200).set_body_bytes(self.0.clone()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metered_connection_pauses_and_resumes_download() {
+        let body: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(RangeBody(body.clone())).mount(&server).await;
+
+        let gate: &'static MeteredGate = Box::leak(Box::new(MeteredGate::new()));
+        gate.set_pause_on_metered(true);
+        let downloader = Downloader::new().with_gate(gate);
+        let dir = std::env::temp_dir().join(format!("qbz-downloader-{}", std::process::id()));
+        let dest = dir.join("track.flac");
+
+        // Goes metered once the first chunks are in
+        let went_metered = AtomicBool::new(false);
+        let url = server.uri();
+        let download = downloader.download_with_progress(&url, &dest, |_, downloaded, _| {
+            if downloaded < body.len() as u64 && !went_metered.swap(true, Ordering::SeqCst) {
+                gate.set_manual(true);
+            }
+        });
+        let unmetered = async {
+            while !went_metered.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // Still held, with a single request made
+            assert_eq!(server.received_requests().await.unwrap().len(), 1);
+            gate.set_manual(false);
+        };
+        let (downloaded, _) = tokio::join!(download, unmetered);
+
+        assert_eq!(downloaded.unwrap(), body.len() as u64);
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].headers.get("range").is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
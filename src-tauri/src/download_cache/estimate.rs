@@ -0,0 +1,108 @@
+//! Download size estimation
+//!
+//! Predicts on-disk size before offline-saving, from track duration and the
+//! requested quality. FLAC size ≈ duration × rate × depth × channels × ratio.
+
+use serde::Serialize;
+
+use crate::api::models::Quality;
+
+/// Typical FLAC compression ratio relative to raw PCM
+const FLAC_COMPRESSION_RATIO: f64 = 0.6;
+const CHANNELS: f64 = 2.0;
+const MP3_BITRATE: f64 = 320_000.0;
+
+/// Estimated size of a batch of downloads
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadSizeEstimate {
+    pub total_bytes: u64,
+    /// (track_id, estimated bytes)
+    pub per_track: Vec<(u64, u64)>,
+    /// Space left under the cache limit (None = unlimited)
+    pub remaining_bytes: Option<u64>,
+    /// True if the download would not fit in the remaining budget
+    pub exceeds_budget: bool,
+}
+
+/// Estimate the file size of one track at `quality`.
+///
+/// The track's own maximum sampling rate / bit depth caps the estimate,
+/// since Qobuz never delivers more than the master provides.
+pub fn estimate_track_bytes(
+    duration_secs: u64,
+    quality: Quality,
+    max_sample_rate_khz: Option<f64>,
+    max_bit_depth: Option<u32>,
+) -> u64 {
+    let duration = duration_secs as f64;
+
+    let (rate, depth) = match quality {
+        Quality::Mp3 => return (duration * MP3_BITRATE / 8.0).round() as u64,
+        Quality::Lossless => (44_100.0, 16.0),
+        Quality::HiRes => (96_000.0, 24.0),
+        Quality::UltraHiRes => (192_000.0, 24.0),
+    };
+
+    let rate = max_sample_rate_khz
+        .map(|khz| (khz * 1000.0).min(rate))
+        .unwrap_or(rate);
+    let depth = max_bit_depth
+        .map(|bits| (bits as f64).min(depth))
+        .unwrap_or(depth);
+
+    (duration * rate * depth * CHANNELS / 8.0 * FLAC_COMPRESSION_RATIO).round() as u64
+}
+
+/// Build an estimate and check it against the remaining storage budget
+pub fn build_estimate(
+    per_track: Vec<(u64, u64)>,
+    used_bytes: u64,
+    limit_bytes: Option<u64>,
+) -> DownloadSizeEstimate {
+    let total_bytes = per_track.iter().map(|(_, bytes)| bytes).sum();
+    let remaining_bytes = limit_bytes.map(|limit| limit.saturating_sub(used_bytes));
+
+    DownloadSizeEstimate {
+        total_bytes,
+        per_track,
+        remaining_bytes,
+        exceeds_budget: remaining_bytes.is_some_and(|remaining| total_bytes > remaining),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: u64, expected: f64) {
+        let diff = (actual as f64 - expected).abs() / expected;
+        assert!(diff < 0.01, "estimate {} not within 1% of {}", actual, expected);
+    }
+
+    #[test]
+    fn test_estimates_for_known_durations() {
+        // 4 min CD track: 240 × 44100 × 16 × 2 / 8 × 0.6 ≈ 25.4 MB
+        assert_close(estimate_track_bytes(240, Quality::Lossless, None, None), 25_401_600.0);
+        // 320 kbps MP3: 240 × 40 KB/s = 9.6 MB
+        assert_close(estimate_track_bytes(240, Quality::Mp3, None, None), 9_600_000.0);
+        // Hi-res request capped by a 48kHz/24-bit master
+        assert_close(
+            estimate_track_bytes(240, Quality::UltraHiRes, Some(48.0), Some(24)),
+            240.0 * 48_000.0 * 24.0 * 2.0 / 8.0 * 0.6,
+        );
+    }
+
+    #[test]
+    fn test_budget_flag() {
+        let estimate = build_estimate(vec![(1, 600), (2, 500)], 900, Some(2_000));
+        assert_eq!(estimate.total_bytes, 1_100);
+        assert_eq!(estimate.remaining_bytes, Some(1_100));
+        assert!(!estimate.exceeds_budget);
+
+        let estimate = build_estimate(vec![(1, 600), (2, 501)], 900, Some(2_000));
+        assert!(estimate.exceeds_budget);
+
+        assert!(!build_estimate(vec![(1, u64::MAX / 2)], 0, None).exceeds_budget);
+    }
+}
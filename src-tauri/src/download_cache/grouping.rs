@@ -0,0 +1,167 @@
+//! Offline library grouped by album
+//!
+//! Downloaded tracks are grouped on the album metadata stored in the
+//! download index; no network access is needed. Tracks without an album
+//! (singles, loose downloads) share one "Unknown album" group, listed last.
+//! Album tracks are in disc/track order; tracks whose position isn't known
+//! (downloads not post-processed) follow, by title.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::{CachedTrackInfo, DownloadCacheDb, DownloadStatus};
+
+pub const UNKNOWN_ALBUM: &str = "Unknown album";
+const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Downloaded tracks of one album
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfflineAlbumGroup {
+    pub album: String,
+    pub album_id: Option<String>,
+    pub artist: String,
+    pub tracks: Vec<CachedTrackInfo>,
+    pub total_size: u64,
+}
+
+/// Album id when known, otherwise the album title; None for loose tracks
+fn group_key(track: &CachedTrackInfo) -> Option<String> {
+    let album = track.album.as_deref().map(str::trim).filter(|a| !a.is_empty());
+    match (track.album_id.as_deref().filter(|id| !id.is_empty()), album) {
+        (Some(id), _) => Some(format!("id:{}", id)),
+        (None, Some(album)) => Some(format!("title:{}", album.to_lowercase())),
+        (None, None) => None,
+    }
+}
+
+/// Group tracks by album, sorted by artist then album (case-insensitive)
+pub fn group_tracks(tracks: Vec<CachedTrackInfo>) -> Vec<OfflineAlbumGroup> {
+    let mut by_key: HashMap<Option<String>, Vec<CachedTrackInfo>> = HashMap::new();
+    for track in tracks {
+        by_key.entry(group_key(&track)).or_default().push(track);
+    }
+
+    let mut groups: Vec<OfflineAlbumGroup> = by_key
+        .into_iter()
+        .map(|(key, mut tracks)| {
+            tracks.sort_by_key(|track| {
+                (
+                    track.disc_number.unwrap_or(1),
+                    track.track_number.unwrap_or(u32::MAX),
+                    track.title.to_lowercase(),
+                )
+            });
+            let album = match key {
+                Some(_) => tracks[0].album.clone().unwrap_or_default().trim().to_string(),
+                None => UNKNOWN_ALBUM.to_string(),
+            };
+            let artist = if tracks.iter().all(|t| t.artist == tracks[0].artist) {
+                tracks[0].artist.clone()
+            } else {
+                VARIOUS_ARTISTS.to_string()
+            };
+            OfflineAlbumGroup {
+                album_id: key.and(tracks[0].album_id.clone()),
+                album,
+                artist,
+                total_size: tracks.iter().map(|t| t.file_size_bytes).sum(),
+                tracks,
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| {
+        let unknown = |g: &OfflineAlbumGroup| g.album_id.is_none() && g.album == UNKNOWN_ALBUM;
+        unknown(a)
+            .cmp(&unknown(b))
+            .then_with(|| a.artist.to_lowercase().cmp(&b.artist.to_lowercase()))
+            .then_with(|| a.album.to_lowercase().cmp(&b.album.to_lowercase()))
+    });
+    groups
+}
+
+/// Ready tracks of the download index, grouped by album
+pub fn offline_library_grouped(db: &DownloadCacheDb) -> Result<Vec<OfflineAlbumGroup>, String> {
+    let tracks = db
+        .get_all_tracks()?
+        .into_iter()
+        .filter(|t| t.status == DownloadStatus::Ready)
+        .collect();
+    Ok(group_tracks(tracks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download_cache::TrackDownloadInfo;
+    use std::path::Path;
+
+    fn download(db: &DownloadCacheDb, track_id: u64, artist: &str, album: Option<(&str, &str)>, size: u64) {
+        let info = TrackDownloadInfo {
+            track_id,
+            title: format!("Track {}", track_id),
+            artist: artist.to_string(),
+            album: album.map(|(_, title)| title.to_string()),
+            album_id: album.map(|(id, _)| id.to_string()),
+            duration_secs: 60,
+            quality: "FLAC".to_string(),
+            bit_depth: Some(16),
+            sample_rate: Some(44100.0),
+        };
+        db.insert_track(&info, &format!("/tmp/{}.flac", track_id)).unwrap();
+        db.mark_complete(track_id, size).unwrap();
+    }
+
+    #[test]
+    fn test_downloads_grouped_by_album_with_loose_tracks_last() {
+        let db = DownloadCacheDb::new(Path::new(":memory:")).unwrap();
+        download(&db, 1, "Miles Davis", Some(("kob", "Kind of Blue")), 100);
+        download(&db, 2, "Miles Davis", Some(("kob", "Kind of Blue")), 150);
+        download(&db, 6, "Miles Davis", Some(("kob", "Kind of Blue")), 120);
+        // Album order, not title order
+        db.update_track_position(6, Some(1), Some(1)).unwrap();
+        db.update_track_position(2, Some(1), Some(2)).unwrap();
+        db.update_track_position(1, Some(2), Some(1)).unwrap();
+        download(&db, 3, "Bill Evans", Some(("wfd", "Waltz for Debby")), 200);
+        download(&db, 4, "Nina Simone", None, 50);
+        // Still downloading, not part of the offline library
+        db.insert_track(
+            &TrackDownloadInfo {
+                track_id: 5,
+                title: "Track 5".to_string(),
+                artist: "Bill Evans".to_string(),
+                album: Some("Waltz for Debby".to_string()),
+                album_id: Some("wfd".to_string()),
+                duration_secs: 60,
+                quality: "FLAC".to_string(),
+                bit_depth: None,
+                sample_rate: None,
+            },
+            "/tmp/5.flac",
+        )
+        .unwrap();
+
+        let groups = offline_library_grouped(&db).unwrap();
+        let summary: Vec<(&str, &str, Vec<u64>, u64)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.artist.as_str(),
+                    g.album.as_str(),
+                    g.tracks.iter().map(|t| t.track_id).collect(),
+                    g.total_size,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Bill Evans", "Waltz for Debby", vec![3], 200),
+                ("Miles Davis", "Kind of Blue", vec![6, 2, 1], 370),
+                ("Nina Simone", UNKNOWN_ALBUM, vec![4], 50),
+            ]
+        );
+    }
+}
@@ -5,11 +5,20 @@
 //! - File-based storage for audio data
 //! - LRU eviction with configurable limits
 //! - Progress events for UI updates
+//! - Album booklet (PDF goodie) downloads
 
+pub mod booklet;
 pub mod commands;
 pub mod db;
 pub mod downloader;
+pub mod estimate;
+pub mod grouping;
 pub mod path_validator;
+pub mod throughput;
+pub mod upgrade;
+pub mod verify;
+#[cfg(test)]
+pub(crate) mod test_support;
 pub mod metadata;
 pub mod migration;
 
@@ -18,8 +27,12 @@ use std::sync::Arc;
 use tokio::sync::{Mutex, Semaphore};
 use serde::{Deserialize, Serialize};
 
+pub use booklet::BookletProgress;
 pub use db::DownloadCacheDb;
 pub use downloader::Downloader;
+pub use estimate::DownloadSizeEstimate;
+pub use grouping::OfflineAlbumGroup;
+pub use verify::OfflineVerifyReport;
 pub use path_validator::{is_download_root_available, validate_path, PathStatus};
 pub use metadata::{CompleteTrackMetadata, sanitize_filename};
 pub use migration::{MigrationStatus, MigrationError, detect_legacy_downloads, migrate_legacy_downloads};
@@ -74,6 +87,9 @@ pub struct CachedTrackInfo {
     pub error_message: Option<String>,
     pub created_at: String,
     pub last_accessed_at: String,
+    /// Position on the album, known once the download was post-processed
+    pub disc_number: Option<u32>,
+    pub track_number: Option<u32>,
 }
 
 /// Minimal track info for syncing to library
@@ -131,7 +147,7 @@ pub struct TrackDownloadInfo {
 pub struct DownloadCacheState {
     pub db: Arc<Mutex<DownloadCacheDb>>,
     pub downloader: Arc<Downloader>,
-    pub cache_dir: PathBuf,
+    cache_dir: std::sync::RwLock<PathBuf>,
     /// Cache limit in bytes (None = unlimited)
     pub limit_bytes: Arc<Mutex<Option<u64>>>,
     pub download_semaphore: Arc<Semaphore>,
@@ -140,10 +156,7 @@ pub struct DownloadCacheState {
 impl DownloadCacheState {
     /// Initialize the download cache
     pub fn new() -> Result<Self, String> {
-        let cache_dir = dirs::cache_dir()
-            .ok_or("Could not determine cache directory")?
-            .join("qbz")
-            .join("audio");
+        let cache_dir = crate::storage::storage_root().join(crate::storage::DOWNLOADS_DIR);
 
         // Create directories
         std::fs::create_dir_all(&cache_dir)
@@ -162,7 +175,7 @@ impl DownloadCacheState {
         let state = Self {
             db: Arc::new(Mutex::new(db)),
             downloader: Arc::new(Downloader::new()),
-            cache_dir: cache_dir.clone(),
+            cache_dir: std::sync::RwLock::new(cache_dir.clone()),
             limit_bytes: Arc::new(Mutex::new(default_limit)),
             download_semaphore: Arc::new(Semaphore::new(3)),
         };
@@ -172,18 +185,28 @@ impl DownloadCacheState {
         Ok(state)
     }
 
+    /// Get the cache directory
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir.read().unwrap().clone()
+    }
+
+    /// Put new downloads in `dir` from now on (after a storage migration)
+    pub fn set_cache_dir(&self, dir: PathBuf) {
+        *self.cache_dir.write().unwrap() = dir;
+    }
+
     /// Get the path for a track's audio file
     pub fn track_file_path(&self, track_id: u64, format: &str) -> PathBuf {
-        self.cache_dir.join("tracks").join(format!("{}.{}", track_id, format))
+        self.cache_dir().join("tracks").join(format!("{}.{}", track_id, format))
     }
 
     /// Get the path for an album's artwork
     pub fn artwork_path(&self, album_id: &str) -> PathBuf {
-        self.cache_dir.join("artwork").join(format!("{}.jpg", album_id))
+        self.cache_dir().join("artwork").join(format!("{}.jpg", album_id))
     }
 
     /// Get the cache directory path
     pub fn get_cache_path(&self) -> String {
-        self.cache_dir.to_string_lossy().to_string()
+        self.cache_dir().to_string_lossy().to_string()
     }
 }
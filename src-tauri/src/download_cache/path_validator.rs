@@ -151,19 +151,18 @@ pub fn move_downloads_to_new_path(
 
         let new_file = new_path.join(relative_path);
 
-        // Create parent directories in new location
-        if let Some(parent) = new_file.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
-        }
-
-        // Move file
-        match fs::rename(&old_file, &new_file) {
-            Ok(_) => {
+        // Copied and checked like a storage migration, so a move to
+        // another disk works and a bad copy keeps the original
+        match crate::storage::copy_verified(&old_file, &new_file) {
+            Ok(()) => {
+                if let Err(e) = fs::remove_file(&old_file) {
+                    log::warn!("Failed to remove moved file {:?}: {}", old_file, e);
+                }
                 report.moved_count += 1;
             }
             Err(e) => {
                 log::warn!("Failed to move file {:?}: {}", old_file, e);
+                let _ = fs::remove_file(&new_file);
                 report.failed_files.push(old_file.to_string_lossy().to_string());
             }
         }
@@ -0,0 +1,56 @@
+//! Audio fixtures shared by the download cache tests
+
+use md5::{Digest, Md5};
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// Mono 44.1kHz FLAC of one verbatim frame of `samples`, with the MD5
+/// of the audio in its header
+pub(crate) fn flac(bits: u32, samples: &[i32]) -> Vec<u8> {
+    let bytes = bits as usize / 8;
+    let audio: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()[..bytes].to_vec()).collect();
+
+    let mut data = b"fLaC".to_vec();
+    data.extend_from_slice(&[0x80, 0, 0, 34]);
+    let block = samples.len() as u16;
+    data.extend_from_slice(&block.to_be_bytes());
+    data.extend_from_slice(&block.to_be_bytes());
+    data.extend_from_slice(&[0; 6]);
+    let packed = (44_100u64 << 44) | ((bits as u64 - 1) << 36) | samples.len() as u64;
+    data.extend_from_slice(&packed.to_be_bytes());
+    data.extend_from_slice(&Md5::digest(&audio));
+
+    let size_code = if bits == 16 { 0b100 } else { 0b110 };
+    let mut frame = vec![0xFF, 0xF8, 0b0110_0000, size_code << 1, 0, block as u8 - 1];
+    frame.push(crc8(&frame));
+    frame.push(0x02);
+    for sample in samples {
+        frame.extend_from_slice(&sample.to_be_bytes()[4 - bytes..]);
+    }
+    let crc = crc16(&frame);
+    frame.extend_from_slice(&crc.to_be_bytes());
+    data.extend_from_slice(&frame);
+    data
+}
+
+pub(crate) fn samples(scale: i32) -> Vec<i32> {
+    (0..200).map(|i| (i - 100) * scale).collect()
+}
@@ -0,0 +1,198 @@
+//! Download throughput tracking and automatic stream quality selection
+//!
+//! Completed downloads (offline downloads and streamed tracks) report how many
+//! bytes they fetched and how long it took. When auto quality is enabled, the
+//! starting quality for `get_stream_url_with_fallback` is picked from the
+//! recent throughput instead of always trying the highest tier first.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::api::models::Quality;
+
+/// Number of recent downloads considered
+const MAX_SAMPLES: usize = 5;
+
+/// Downloads smaller than this are dominated by latency and are ignored
+const MIN_SAMPLE_BYTES: u64 = 256 * 1024;
+
+/// Throughput needed to start at each quality, in bytes/second, highest first
+const TIERS: [(Quality, f64); 3] = [
+    (Quality::UltraHiRes, 2_500_000.0), // ~20 Mbit/s
+    (Quality::HiRes, 1_000_000.0),      // ~8 Mbit/s
+    (Quality::Lossless, 250_000.0),     // ~2 Mbit/s
+];
+
+/// Extra margin required before climbing back to a higher tier, so the
+/// quality doesn't flap around a threshold
+const UPGRADE_MARGIN: f64 = 1.25;
+
+static AUTO_QUALITY: AutoQuality = AutoQuality::new();
+
+/// Process-wide auto quality state
+pub fn auto_quality() -> &'static AutoQuality {
+    &AUTO_QUALITY
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    bytes: u64,
+    elapsed: Duration,
+}
+
+/// Throughput-based starting quality selection
+pub struct AutoQuality {
+    enabled: AtomicBool,
+    samples: Mutex<VecDeque<Sample>>,
+    current: Mutex<Option<Quality>>,
+}
+
+impl Default for AutoQuality {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoQuality {
+    pub const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            samples: Mutex::new(VecDeque::new()),
+            current: Mutex::new(None),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *self.current.lock().unwrap() = None;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Record a completed download
+    pub fn record(&self, bytes: u64, elapsed: Duration) {
+        if bytes < MIN_SAMPLE_BYTES || elapsed.is_zero() {
+            return;
+        }
+
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back(Sample { bytes, elapsed });
+        while samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// Recent throughput in bytes/second (None until a download was recorded)
+    pub fn throughput(&self) -> Option<f64> {
+        let samples = self.samples.lock().unwrap();
+        let bytes: u64 = samples.iter().map(|s| s.bytes).sum();
+        let secs: f64 = samples.iter().map(|s| s.elapsed.as_secs_f64()).sum();
+        if secs > 0.0 {
+            Some(bytes as f64 / secs)
+        } else {
+            None
+        }
+    }
+
+    /// Quality to start streaming at. Returns `ceiling` when auto quality is
+    /// disabled or nothing has been measured yet.
+    pub fn starting_quality(&self, ceiling: Quality) -> Quality {
+        if !self.is_enabled() {
+            return ceiling;
+        }
+
+        let throughput = self.throughput();
+        let mut current = self.current.lock().unwrap();
+        let quality = match throughput {
+            Some(throughput) => select_quality(throughput, *current, ceiling),
+            None => current.unwrap_or(ceiling).min(ceiling),
+        };
+
+        if *current != Some(quality) {
+            log::info!("Auto quality: {:?} (throughput {:?} bytes/s)", quality, throughput.map(|t| t.round()));
+        }
+        *current = Some(quality);
+        quality
+    }
+
+    /// Last auto-chosen quality (None when auto quality is off)
+    pub fn current(&self) -> Option<Quality> {
+        if self.is_enabled() {
+            *self.current.lock().unwrap()
+        } else {
+            None
+        }
+    }
+}
+
+/// Pick the highest tier the throughput supports, never above `ceiling`.
+/// Moving above `previous` requires `UPGRADE_MARGIN` headroom.
+fn select_quality(throughput: f64, previous: Option<Quality>, ceiling: Quality) -> Quality {
+    for (quality, required) in TIERS {
+        if quality > ceiling {
+            continue;
+        }
+        let required = match previous {
+            Some(prev) if quality > prev => required * UPGRADE_MARGIN,
+            _ => required,
+        };
+        if throughput >= required {
+            return quality;
+        }
+    }
+    Quality::Mp3.min(ceiling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(auto: &AutoQuality, bytes_per_sec: u64) {
+        for _ in 0..MAX_SAMPLES {
+            auto.record(bytes_per_sec * 4, Duration::from_secs(4));
+        }
+    }
+
+    #[test]
+    fn test_quality_follows_bandwidth_tiers() {
+        let auto = AutoQuality::new();
+        auto.set_enabled(true);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::UltraHiRes);
+
+        feed(&auto, 100_000);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::Mp3);
+
+        feed(&auto, 500_000);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::Lossless);
+
+        // 1.1 MB/s meets the HiRes tier but not the upgrade margin
+        feed(&auto, 1_100_000);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::Lossless);
+
+        feed(&auto, 1_500_000);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::HiRes);
+
+        feed(&auto, 5_000_000);
+        assert_eq!(auto.starting_quality(Quality::UltraHiRes), Quality::UltraHiRes);
+        assert_eq!(auto.starting_quality(Quality::Lossless), Quality::Lossless);
+        assert_eq!(auto.current(), Some(Quality::Lossless));
+    }
+
+    #[test]
+    fn test_disabled_uses_ceiling_and_ignores_small_samples() {
+        let auto = AutoQuality::new();
+        feed(&auto, 100_000);
+        assert_eq!(auto.starting_quality(Quality::HiRes), Quality::HiRes);
+        assert_eq!(auto.current(), None);
+
+        let auto = AutoQuality::new();
+        auto.record(1024, Duration::from_secs(10));
+        assert_eq!(auto.throughput(), None);
+    }
+}
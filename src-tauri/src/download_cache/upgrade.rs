@@ -0,0 +1,280 @@
+//! Upgrading offline tracks to a higher quality
+//!
+//! The new file is downloaded next to the existing one and only swapped in
+//! once it verifies: it must decode to the end, match the audio checksum in
+//! its header (FLAC) and have the bit depth of the requested quality. The
+//! old file is kept aside until the index row (path, size, quality,
+//! checksum) has been updated, and restored if that fails.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use md5::{Digest, Md5};
+use tokio::sync::Mutex;
+
+use crate::api::models::Quality;
+
+use super::verify;
+use super::{CachedTrackInfo, DownloadCacheDb};
+
+/// What the index records for a replaced file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplacementFile {
+    pub file_path: String,
+    pub file_size_bytes: u64,
+    pub quality: String,
+    pub bit_depth: Option<u32>,
+    pub sample_rate: Option<f64>,
+    /// MD5 of the file contents, hex
+    pub checksum: String,
+}
+
+/// Quality of an existing download, from its recorded audio properties
+pub fn downloaded_quality(info: &CachedTrackInfo) -> Option<Quality> {
+    match (info.bit_depth, info.sample_rate) {
+        (Some(bits), _) if bits <= 16 => Some(Quality::Lossless),
+        (Some(_), Some(rate)) if rate > 96_000.0 => Some(Quality::UltraHiRes),
+        (Some(_), _) => Some(Quality::HiRes),
+        (None, _) if info.quality.to_lowercase().contains("mp3") => Some(Quality::Mp3),
+        (None, _) => None,
+    }
+}
+
+/// Refuse upgrades that wouldn't raise the quality, or might not
+pub fn check_upgrade(current: Option<Quality>, requested: Quality) -> Result<(), String> {
+    match current {
+        Some(current) if current >= requested => Err(format!(
+            "Track is already downloaded in {}, which is not lower than {}",
+            current.label(),
+            requested.label()
+        )),
+        Some(_) => Ok(()),
+        None => Err("The quality of the downloaded track is unknown".to_string()),
+    }
+}
+
+/// Decode the whole file at `path` (see [`verify::decode_audio`]) and check
+/// it has the bit depth of `quality`. Blocking.
+pub fn verify_audio(path: &Path, quality: Quality) -> Result<(), String> {
+    let bits = verify::decode_audio(path)?;
+    if quality >= Quality::HiRes && bits.unwrap_or(0) <= 16 {
+        return Err(format!("File is not in {}", quality.label()));
+    }
+    Ok(())
+}
+
+/// Where the upgrade is downloaded before it replaces `current`
+pub fn staged_path(current: &Path) -> PathBuf {
+    let mut name = current.file_name().unwrap_or_default().to_os_string();
+    name.push(".upgrade");
+    current.with_file_name(name)
+}
+
+fn file_checksum(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut hasher = Md5::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Bit depth and sample rate read from the file, falling back to the
+/// nominal values of `quality`
+fn audio_properties(path: &Path, quality: Quality) -> (Option<u32>, Option<f64>) {
+    use lofty::AudioFile;
+
+    if let Ok(tagged_file) = lofty::read_from_path(path) {
+        let properties = tagged_file.properties();
+        if let (Some(bits), Some(rate)) = (properties.bit_depth(), properties.sample_rate()) {
+            return (Some(bits as u32), Some(rate as f64));
+        }
+    }
+    match quality {
+        Quality::Mp3 => (None, None),
+        Quality::Lossless => (Some(16), Some(44_100.0)),
+        Quality::HiRes => (Some(24), Some(96_000.0)),
+        Quality::UltraHiRes => (Some(24), Some(192_000.0)),
+    }
+}
+
+/// Read what the index will record for the `staged` file, which passed
+/// `verify_audio`, before it replaces `current`.
+///
+/// A staged file that isn't audio is deleted and the existing download is
+/// left untouched.
+pub fn prepare_upgrade(
+    track_id: u64,
+    current: &Path,
+    staged: &Path,
+    quality: Quality,
+) -> Result<ReplacementFile, String> {
+    if !verify::is_intact(staged) {
+        let _ = std::fs::remove_file(staged);
+        return Err(format!("Upgraded file for track {} failed verification", track_id));
+    }
+
+    let (bit_depth, sample_rate) = audio_properties(staged, quality);
+    let replacement = ReplacementFile {
+        file_path: current.to_string_lossy().to_string(),
+        file_size_bytes: std::fs::metadata(staged)
+            .map_err(|e| format!("Failed to read {:?}: {}", staged, e))?
+            .len(),
+        quality: quality.label().to_string(),
+        bit_depth,
+        sample_rate,
+        checksum: file_checksum(staged)?,
+    };
+    Ok(replacement)
+}
+
+/// Replace `current` with the `staged` file and update the index with
+/// `replacement`. The index is only locked for the update itself (blocking;
+/// run on a blocking task).
+pub fn install_upgrade(
+    db: &Mutex<DownloadCacheDb>,
+    track_id: u64,
+    current: &Path,
+    staged: &Path,
+    replacement: &ReplacementFile,
+) -> Result<(), String> {
+    // Keep the old file aside until the index points at the new one
+    let mut backup_name = current.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(".old");
+    let backup = current.with_file_name(backup_name);
+    let had_current = current.exists();
+    if had_current {
+        std::fs::rename(current, &backup)
+            .map_err(|e| format!("Failed to set aside {:?}: {}", current, e))?;
+    }
+    let restore = || {
+        if had_current {
+            let _ = std::fs::rename(&backup, current);
+        }
+    };
+
+    if let Err(e) = std::fs::rename(staged, current) {
+        restore();
+        return Err(format!("Failed to install upgraded file: {}", e));
+    }
+    if let Err(e) = db.blocking_lock().replace_file(track_id, replacement) {
+        let _ = std::fs::remove_file(current);
+        restore();
+        return Err(e);
+    }
+
+    if had_current {
+        if let Err(e) = std::fs::remove_file(&backup) {
+            log::warn!("Failed to remove replaced file {:?}: {}", backup, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download_cache::test_support::{flac, samples};
+    use crate::download_cache::TrackDownloadInfo;
+
+    #[test]
+    fn test_verify_audio_decodes_the_whole_file() {
+        let dir = std::env::temp_dir().join(format!("qbz-verify-audio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("1.flac");
+
+        std::fs::write(&path, flac(24, &samples(1000))).unwrap();
+        verify_audio(&path, Quality::HiRes).unwrap();
+
+        // CD quality delivered for a hi-res request
+        std::fs::write(&path, flac(16, &samples(100))).unwrap();
+        assert!(verify_audio(&path, Quality::HiRes).is_err());
+        verify_audio(&path, Quality::Lossless).unwrap();
+
+        // Cut short, or not the audio the header describes
+        let file = flac(24, &samples(1000));
+        std::fs::write(&path, &file[..file.len() - 20]).unwrap();
+        assert!(verify_audio(&path, Quality::HiRes).is_err());
+        let mut wrong_checksum = file.clone();
+        wrong_checksum[26] ^= 0xFF;
+        std::fs::write(&path, &wrong_checksum).unwrap();
+        assert!(verify_audio(&path, Quality::HiRes).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_upgrade_replaces_file_and_quality_only_after_verification() {
+        let dir = std::env::temp_dir().join(format!("qbz-upgrade-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = DownloadCacheDb::new(Path::new(":memory:")).unwrap();
+
+        let current = dir.join("7.flac");
+        let cd_quality = flac(16, &samples(100));
+        std::fs::write(&current, &cd_quality).unwrap();
+        db.insert_track(
+            &TrackDownloadInfo {
+                track_id: 7,
+                title: "Track".to_string(),
+                artist: "Artist".to_string(),
+                album: None,
+                album_id: None,
+                duration_secs: 60,
+                quality: Quality::Lossless.label().to_string(),
+                bit_depth: Some(16),
+                sample_rate: Some(44_100.0),
+            },
+            &current.to_string_lossy(),
+        )
+        .unwrap();
+        db.mark_complete(7, cd_quality.len() as u64).unwrap();
+
+        let info = db.get_track(7).unwrap().unwrap();
+        assert_eq!(downloaded_quality(&info), Some(Quality::Lossless));
+        assert!(check_upgrade(Some(Quality::Lossless), Quality::Lossless).is_err());
+        assert!(check_upgrade(Some(Quality::HiRes), Quality::Lossless).is_err());
+        assert!(check_upgrade(None, Quality::HiRes).is_err());
+        check_upgrade(Some(Quality::Lossless), Quality::HiRes).unwrap();
+
+        // A broken download is discarded and the CD-quality file stays
+        let staged = staged_path(&current);
+        std::fs::write(&staged, b"<html>error</html>").unwrap();
+        assert!(verify_audio(&staged, Quality::HiRes).is_err());
+        assert!(prepare_upgrade(7, &current, &staged, Quality::HiRes).is_err());
+        assert!(!staged.exists());
+        assert_eq!(std::fs::read(&current).unwrap(), cd_quality);
+        assert_eq!(db.get_track(7).unwrap().unwrap().bit_depth, Some(16));
+
+        let hi_res = flac(24, &samples(1000));
+        std::fs::write(&staged, &hi_res).unwrap();
+        verify_audio(&staged, Quality::HiRes).unwrap();
+        let replacement = prepare_upgrade(7, &current, &staged, Quality::HiRes).unwrap();
+        let db = Mutex::new(db);
+        install_upgrade(&db, 7, &current, &staged, &replacement).unwrap();
+        let db = db.into_inner();
+        assert_eq!(std::fs::read(&current).unwrap(), hi_res);
+        assert!(!staged.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let info = db.get_track(7).unwrap().unwrap();
+        assert_eq!(info.quality, Quality::HiRes.label());
+        assert_eq!(info.bit_depth, Some(24));
+        assert_eq!(downloaded_quality(&info), Some(Quality::HiRes));
+        let checksum: String = db
+            .conn()
+            .query_row("SELECT checksum FROM cached_tracks WHERE track_id = 7", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(checksum, replacement.checksum);
+        assert_eq!(checksum, format!("{:x}", Md5::digest(&hi_res)));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,217 @@
+//! Offline library integrity checks
+//!
+//! Files listed as ready in the download index can disappear (deleted
+//! externally, unmounted drive) or get damaged. Verification checks each file
+//! and flags broken entries as failed so they stop counting as available.
+//! A deep check decodes every file: sizes can't be trusted since retagging
+//! changes them either way.
+
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use super::{DownloadCacheDb, DownloadStatus};
+
+/// Result of verifying the offline library
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OfflineVerifyReport {
+    pub missing: Vec<u64>,
+    pub corrupt: Vec<u64>,
+    pub ok: usize,
+}
+
+/// Progress update while verifying
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfflineVerifyProgress {
+    pub checked: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileCheck {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+/// Check every `(track_id, path, recorded_size)` entry.
+///
+/// With `check_contents`, files are also decoded to the end. The recorded
+/// size isn't compared: it is taken before tagging, which can shrink a file.
+pub fn check_files<F>(
+    entries: &[(u64, String, u64)],
+    check_contents: bool,
+    mut on_progress: F,
+) -> OfflineVerifyReport
+where
+    F: FnMut(OfflineVerifyProgress),
+{
+    let mut report = OfflineVerifyReport::default();
+
+    for (index, (track_id, path, _)) in entries.iter().enumerate() {
+        match check_file(Path::new(path), check_contents) {
+            FileCheck::Ok => report.ok += 1,
+            FileCheck::Missing => report.missing.push(*track_id),
+            FileCheck::Corrupt => report.corrupt.push(*track_id),
+        }
+        on_progress(OfflineVerifyProgress {
+            checked: index + 1,
+            total: entries.len(),
+        });
+    }
+
+    report
+}
+
+/// Flag missing and corrupt tracks as failed so they are no longer offered offline
+pub fn apply_report(db: &DownloadCacheDb, report: &OfflineVerifyReport) -> Result<(), String> {
+    for track_id in &report.missing {
+        db.update_status(*track_id, DownloadStatus::Failed, Some("File missing"))?;
+    }
+    for track_id in &report.corrupt {
+        db.update_status(*track_id, DownloadStatus::Failed, Some("File corrupt"))?;
+    }
+    Ok(())
+}
+
+/// The file exists and decodes to the end
+pub(crate) fn is_intact(path: &Path) -> bool {
+    check_file(path, true) == FileCheck::Ok
+}
+
+/// Decode the whole file at `path`, checking it against the MD5 of the
+/// audio recorded in its header when there is one. Returns the bits per
+/// sample it declares. Blocking.
+pub fn decode_audio(path: &Path) -> Result<Option<u32>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unrecognized audio file: {}", e))?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| "No audio track in file".to_string())?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions { verify: true })
+        .map_err(|e| format!("Unsupported audio file: {}", e))?;
+    let mut frames = 0u64;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(format!("Failed to read audio: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = decoder
+            .decode(&packet)
+            .map_err(|e| format!("Failed to decode audio: {}", e))?;
+        frames += decoded.frames() as u64;
+    }
+
+    if params.n_frames.is_some_and(|expected| frames < expected) {
+        return Err("File is truncated".to_string());
+    }
+    if decoder.finalize().verify_ok == Some(false) {
+        return Err("Audio doesn't match its checksum".to_string());
+    }
+    Ok(params.bits_per_sample)
+}
+
+fn check_file(path: &Path, check_contents: bool) -> FileCheck {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => {}
+        _ => return FileCheck::Missing,
+    }
+
+    if check_contents {
+        if let Err(e) = decode_audio(path) {
+            log::warn!("Offline file {:?} is corrupt: {}", path, e);
+            return FileCheck::Corrupt;
+        }
+    }
+
+    FileCheck::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download_cache::test_support::{flac, samples};
+    use crate::download_cache::TrackDownloadInfo;
+    use std::path::PathBuf;
+
+    fn track(track_id: u64) -> TrackDownloadInfo {
+        TrackDownloadInfo {
+            track_id,
+            title: format!("Track {}", track_id),
+            artist: "Artist".to_string(),
+            album: None,
+            album_id: None,
+            duration_secs: 60,
+            quality: "FLAC".to_string(),
+            bit_depth: Some(16),
+            sample_rate: Some(44100.0),
+        }
+    }
+
+    fn write_ready(db: &DownloadCacheDb, dir: &Path, track_id: u64) -> PathBuf {
+        let path = dir.join(format!("{}.flac", track_id));
+        let data = flac(16, &samples(100));
+        std::fs::write(&path, &data).unwrap();
+        db.insert_track(&track(track_id), &path.to_string_lossy()).unwrap();
+        // Recorded before retagging replaced a larger tag block
+        db.mark_complete(track_id, data.len() as u64 + 4096).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_missing_and_corrupt_files_are_flagged() {
+        let dir = std::env::temp_dir().join(format!("qbz-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = DownloadCacheDb::new(Path::new(":memory:")).unwrap();
+
+        write_ready(&db, &dir, 1);
+        let deleted = write_ready(&db, &dir, 2);
+        let corrupted = write_ready(&db, &dir, 3);
+        let truncated = write_ready(&db, &dir, 4);
+
+        std::fs::remove_file(&deleted).unwrap();
+        std::fs::write(&corrupted, b"garbage").unwrap();
+        let data = std::fs::read(&truncated).unwrap();
+        std::fs::write(&truncated, &data[..data.len() - 20]).unwrap();
+
+        let entries = db.get_ready_files().unwrap();
+        let mut progress = Vec::new();
+        let report = check_files(&entries, true, |p| progress.push(p.checked));
+
+        assert_eq!(report.missing, vec![2]);
+        assert_eq!(report.corrupt, vec![3, 4]);
+        assert_eq!(report.ok, 1);
+        assert_eq!(progress, vec![1, 2, 3, 4]);
+
+        apply_report(&db, &report).unwrap();
+        assert!(db.is_cached(1).unwrap());
+        assert!(!db.is_cached(2).unwrap());
+        assert!(!db.is_cached(3).unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -10,6 +10,7 @@ pub mod cache;
 pub mod cast;
 pub mod commands;
 pub mod config;
+pub mod cover_colors;
 pub mod credentials;
 pub mod discogs;
 pub mod download_cache;
@@ -17,6 +18,7 @@ pub mod lastfm;
 pub mod library;
 pub mod lyrics;
 pub mod media_controls;
+pub mod metered;
 pub mod network;
 pub mod nostr_cache;
 pub mod offline;
@@ -24,12 +26,17 @@ pub mod player;
 pub mod playlist_import;
 pub mod queue;
 pub mod reco_store;
+pub mod search_history;
 pub mod session_store;
 pub mod share;
+pub mod shortcuts;
+pub mod startup;
+pub mod storage;
+pub mod stream_report;
 pub mod tray;
 
 use std::sync::Arc;
-use tauri::{Emitter, Manager};
+use tauri::{Emitter, Listener, Manager};
 use tokio::sync::Mutex;
 
 use api::QobuzClient;
@@ -49,6 +56,8 @@ pub struct AppState {
     pub audio_cache: Arc<AudioCache>,
     pub lastfm: Arc<Mutex<LastFmClient>>,
     pub songlink: SongLinkClient,
+    /// The client's cancellable requests, reachable while it is locked
+    pub requests: Arc<api::InFlightRequests>,
 }
 
 impl AppState {
@@ -80,14 +89,18 @@ impl AppState {
             Arc::new(AudioCache::default())
         };
 
+        let client = QobuzClient::default();
+        let requests = client.requests();
+
         Self {
-            client: Arc::new(Mutex::new(QobuzClient::default())),
+            client: Arc::new(Mutex::new(client)),
             player: Player::new(device_name, audio_settings),
             queue: QueueManager::new(),
             media_controls: MediaControlsManager::new(),
             audio_cache,
             lastfm: Arc::new(Mutex::new(LastFmClient::default())),
             songlink: SongLinkClient::new(),
+            requests,
         }
     }
 }
@@ -160,12 +173,32 @@ pub fn run() {
     // Initialize session store state
     let session_store_state = session_store::SessionStoreState::new()
         .expect("Failed to initialize session store");
+    // Initialize recent search history state
+    let search_history_state = search_history::SearchHistoryState::new()
+        .expect("Failed to initialize search history");
     // Initialize audio settings state
     let audio_settings_state = config::audio_settings::AudioSettingsState::new()
         .expect("Failed to initialize audio settings");
     // Initialize download settings state
     let download_settings_state = config::download_settings::create_download_settings_state()
         .expect("Failed to initialize download settings");
+    // Initialize cache settings state
+    let cache_settings_state = config::cache_settings::create_cache_settings_state()
+        .expect("Failed to initialize cache settings");
+    // Initialize playback settings state
+    let playback_settings_state = config::playback_settings::create_playback_settings_state()
+        .expect("Failed to initialize playback settings");
+    stream_report::load_policy(&playback_settings_state);
+    commands::playback::load_streaming_quality(&playback_settings_state);
+    // Initialize startup settings state
+    let startup_settings_state = config::startup_settings::create_startup_settings_state()
+        .expect("Failed to initialize startup settings");
+    // Initialize global shortcut bindings
+    let shortcut_settings_state = config::shortcut_settings::create_shortcut_settings_state()
+        .expect("Failed to initialize shortcut settings");
+    // Initialize endpoint priority lists
+    let endpoint_priority_state = config::endpoint_priority::create_endpoint_priority_state()
+        .expect("Failed to initialize endpoint priority");
     // Initialize offline mode state
     let offline_state = offline::OfflineState::new()
         .expect("Failed to initialize offline state");
@@ -198,13 +231,39 @@ pub fn run() {
         audio_settings.preferred_sample_rate
     );
 
+    download_cache::throughput::auto_quality().set_enabled(audio_settings.auto_quality);
+    let preferred_container = audio_settings.preferred_container;
+    let register_device = audio_settings.register_device;
+    let app_state = AppState::with_device_and_settings(saved_device, audio_settings);
+    app_state
+        .audio_cache
+        .set_mode(config::cache_settings::saved_cache_mode(&cache_settings_state));
+    commands::queue::load_queue_end_action(&app_state.queue, &playback_settings_state);
+    app_state
+        .audio_cache
+        .set_warm_additions(config::cache_settings::warm_queue_additions_enabled(&cache_settings_state));
+    app_state
+        .client
+        .blocking_lock()
+        .set_endpoint_route(Arc::new(config::endpoint_priority::PriorityRoute {
+            state: endpoint_priority_state.clone(),
+            list: config::endpoint_priority::EndpointList::QobuzApi,
+        }));
+    app_state.client.blocking_lock().set_preferred_container(preferred_container);
+    app_state.client.blocking_lock().set_device_registration(register_device);
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_fs::init())
-        .manage(AppState::with_device_and_settings(saved_device, audio_settings))
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(shortcuts::handle_shortcut)
+                .build(),
+        )
+        .manage(app_state)
         .setup(|app| {
             // Initialize system tray icon
             if let Err(e) = tray::init_tray(app.handle()) {
@@ -216,6 +275,88 @@ pub fn run() {
                 .media_controls
                 .init(app.handle().clone());
 
+            // Bring back the user's global shortcuts
+            shortcuts::restore_shortcuts(app.handle());
+
+            // Forward cache and prefetch progress to the UI
+            let cache_events_handle = app.handle().clone();
+            app.state::<AppState>()
+                .audio_cache
+                .set_event_emitter(Arc::new(move |event| {
+                    let _ = cache_events_handle.emit(event.name(), &event);
+                }));
+
+            // Re-plan prefetching when the queue is reordered or edited, so
+            // tracks that are no longer up next stop downloading
+            let prefetch_handle = app.handle().clone();
+            app.listen_any("queue-changed", move |_| {
+                commands::refresh_prefetch(&prefetch_handle.state::<AppState>());
+            });
+
+            // Follow NetworkManager's metered flag (see `metered`)
+            tauri::async_runtime::spawn(metered::watch_connection());
+
+            // Stop prefetching while the connection is metered and plan it
+            // again once it isn't
+            let metered_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                metered::metered()
+                    .follow(|_| commands::refresh_prefetch(&metered_handle.state::<AppState>()))
+                    .await
+            });
+
+            // Run the configured startup steps (emits app-ready)
+            startup::spawn(app.handle().clone());
+
+            // Start periodic Nostr cache cleanup
+            let nostr_maintenance = app.state::<nostr_cache::NostrCacheState>().maintenance_task();
+            tauri::async_runtime::spawn(nostr_maintenance);
+
+            // Learn the network region for region restriction errors
+            let client = app.state::<AppState>().client.clone();
+            tauri::async_runtime::spawn(async move {
+                let client = client.lock().await.clone();
+                client.detect_network_region().await;
+            });
+
+            // Keep resolved stream URLs from expiring while tracks wait in the queue
+            let client = app.state::<AppState>().client.clone();
+            let queue_handle = app.handle().clone();
+            tauri::async_runtime::spawn(api::stream_urls::refresh_task(client, move || {
+                queue_handle.state::<AppState>().queue.track_ids().into_iter().collect()
+            }));
+
+            // Log out and prompt a re-login when Qobuz ends the session
+            // because the account signed in on another device
+            let client = app.state::<AppState>().client.clone();
+            let session_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut invalidated = client.lock().await.subscribe_session_invalidated();
+                loop {
+                    match invalidated.recv().await {
+                        Ok(invalidation) => {
+                            let client = client.lock().await.clone();
+                            let cache_state = session_handle.state::<api_cache::ApiCacheState>();
+                            if let Err(e) = commands::auth::end_account_session(
+                                &client,
+                                &cache_state,
+                                Some(invalidation.user_id),
+                            )
+                            .await
+                            {
+                                log::warn!("Failed to clear the invalidated session's data: {}", e);
+                            }
+                            let _ = session_handle.emit(
+                                "session-invalidated",
+                                serde_json::json!({ "reason": invalidation.reason }),
+                            );
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
             // Start background task to emit playback events
             let app_handle = app.handle().clone();
             let player_state = app.state::<AppState>().player.state.clone();
@@ -223,7 +364,12 @@ pub fn run() {
             std::thread::spawn(move || {
                 let mut last_position: u64 = 0;
                 let mut last_is_playing: bool = false;
+                let mut last_is_buffering: bool = false;
                 let mut last_track_id: u64 = 0;
+                let mut listens = stream_report::ListenTracker::default();
+                // Whether the last seen track is local or Nostr, looked up once per track
+                let mut external = (0, false);
+                let clock = std::time::Instant::now();
 
                 loop {
                     // Check playing/track state first to determine sleep duration
@@ -245,14 +391,31 @@ pub fn run() {
 
                     // Re-check after sleep (state might have changed)
                     let is_playing = player_state.is_playing();
-                    let position = player_state.current_position();
+                    let position_sample = player_state.position_sample();
+                    let position = position_sample.position_ms / 1000;
                     let duration = player_state.duration();
                     let track_id = player_state.current_track_id();
                     let volume = player_state.volume();
+                    let is_buffering = player_state.is_buffering();
+
+                    if external.0 != track_id {
+                        external = (track_id, stream_report::is_external(&app_handle, track_id));
+                    }
+                    let sample = stream_report::PlaybackSample {
+                        track_id,
+                        position_secs: position,
+                        duration_secs: duration,
+                        is_playing,
+                        external: external.1,
+                    };
+                    let reports = listens.observe(sample, clock.elapsed().as_millis() as u64, &stream_report::policy());
+                    stream_report::dispatch(&app_handle, reports);
+                    stream_report::record_listens(&app_handle, listens.take_ended());
 
                     // Only emit if state changed or position advanced
                     let should_emit = track_id != 0 && (
                         is_playing != last_is_playing
+                        || is_buffering != last_is_buffering
                         || track_id != last_track_id
                         || (is_playing && position != last_position)
                     );
@@ -263,13 +426,17 @@ pub fn run() {
                         let event = player::PlaybackEvent {
                             is_playing,
                             position,
+                            position_ms: position_sample.position_ms,
+                            measured_at_monotonic_ms: position_sample.measured_at_monotonic_ms,
                             duration,
                             track_id,
                             volume,
+                            is_buffering,
                         };
                         let _ = app_handle.emit("playback:state", &event);
                         last_position = position;
                         last_is_playing = is_playing;
+                        last_is_buffering = is_buffering;
                         last_track_id = track_id;
                     }
 
@@ -295,17 +462,36 @@ pub fn run() {
         .manage(reco_state)
         .manage(api_cache_state)
         .manage(session_store_state)
+        .manage(search_history_state)
         .manage(audio_settings_state)
         .manage(download_settings_state)
+        .manage(cache_settings_state)
+        .manage(startup_settings_state)
+        .manage(playback_settings_state)
+        .manage(shortcut_settings_state)
+        .manage(shortcuts::ShortcutsState::default())
+        .manage(startup::StartupState::default())
+        .manage(endpoint_priority_state)
         .manage(offline_state)
         .manage(nostr_cache_state)
+        .manage(commands::LoudnessState::default())
+        .manage(commands::TrackTechnicalState::default())
+        .manage(commands::CoverColorsState::default())
+        .manage(commands::DeviceCapsState::default())
         .invoke_handler(tauri::generate_handler![
             // Auth commands
             commands::init_client,
+            commands::refresh_bundle_tokens,
+            commands::get_api_diagnostics,
             commands::login,
+            commands::login_with_token,
             commands::logout,
             commands::is_logged_in,
             commands::get_user_info,
+            commands::get_session_zone,
+            commands::get_account_devices,
+            commands::revoke_device,
+            commands::get_account_features,
             commands::set_api_locale,
             // Credential persistence commands
             commands::has_saved_credentials,
@@ -321,23 +507,45 @@ pub fn run() {
             commands::search_tracks,
             commands::search_artists,
             commands::search_all,
+            commands::get_search_suggestions,
+            commands::search_stream,
+            commands::cancel_request,
             commands::get_album,
+            commands::get_related_releases,
+            commands::get_albums,
+            commands::measure_loudness,
+            commands::measure_album_loudness,
+            commands::compute_album_gain,
+            commands::get_track_technical,
             commands::get_featured_albums,
+            commands::get_editorial,
             commands::get_track,
+            commands::get_track_availability,
+            commands::get_album_availability,
             commands::get_artist,
             commands::get_artist_detail,
+            commands::get_artist_page,
             commands::get_artist_albums,
             commands::get_similar_artists,
             // Playback commands
             commands::play_track,
             commands::play_track_url,
             commands::prefetch_track,
+            commands::preload_track,
+            commands::cancel_preload,
+            commands::probe_available_qualities,
+            commands::retry_stream,
+            commands::probe_stream,
             commands::pause_playback,
             commands::resume_playback,
             commands::stop_playback,
             commands::set_volume,
             commands::seek,
+            commands::scrub_preview,
+            commands::commit_seek,
             commands::get_playback_state,
+            commands::get_output_format,
+            commands::dump_playback_chain,
             commands::set_media_metadata,
             commands::get_audio_devices,
             commands::get_audio_output_status,
@@ -346,14 +554,20 @@ pub fn run() {
             commands::set_pipewire_default_sink,
             commands::reinit_audio_device,
             commands::get_hardware_audio_status,
+            commands::run_bitperfect_test,
             // Queue commands
             commands::add_to_queue,
             commands::add_to_queue_next,
             commands::add_tracks_to_queue,
+            commands::queue_add_album,
+            commands::queue_continue_album,
+            commands::queue_add_playlist,
+            commands::queue_play_favorites,
             commands::set_queue,
             commands::clear_queue,
             commands::remove_from_queue,
             commands::move_queue_track,
+            commands::move_queue_tracks,
             commands::get_current_queue_track,
             commands::peek_next_track,
             commands::next_track,
@@ -363,30 +577,54 @@ pub fn run() {
             commands::get_shuffle,
             commands::set_repeat,
             commands::get_repeat,
+            commands::set_queue_end_action,
+            commands::get_queue_end_action,
+            commands::set_unplayable_policy,
+            commands::get_unplayable_policy,
+            commands::skip_unplayable_track,
+            commands::play_my_mix,
             commands::get_queue_state,
+            commands::get_now_playing_context,
             // Playlist commands
             commands::get_user_playlists,
             commands::get_playlist,
             commands::search_playlists,
             commands::create_playlist,
             commands::delete_playlist,
+            commands::subscribe_playlist,
+            commands::unsubscribe_playlist,
             commands::add_tracks_to_playlist,
             commands::remove_tracks_from_playlist,
+            commands::move_track_between_playlists,
             commands::update_playlist,
             // Playlist import commands
             commands::playlist_import_preview,
             commands::playlist_import_execute,
             // Favorites commands
             commands::get_favorites,
+            commands::get_purchases,
             commands::add_favorite,
             commands::remove_favorite,
+            commands::favorite_album_tracks,
+            commands::sync_favorites,
+            commands::get_favorites_status,
+            commands::get_synced_favorites,
+            commands::filter_favorites,
+            commands::find_duplicate_favorites,
+            commands::merge_favorite_duplicates,
             // Notification commands
             commands::show_track_notification,
             commands::show_notification,
+            // Cover art commands
+            commands::extract_dominant_colors,
             // Cache commands
             commands::get_cache_stats,
+            commands::get_library_stats,
             commands::clear_cache,
             commands::clear_artist_cache,
+            commands::clear_caches,
+            commands::refresh_entity,
+            commands::migrate_storage,
             // Last.fm commands
             commands::lastfm_has_embedded_credentials,
             commands::lastfm_has_credentials,
@@ -399,12 +637,18 @@ pub fn run() {
             commands::lastfm_disconnect,
             commands::lastfm_scrobble,
             commands::lastfm_now_playing,
+            // Stream reporting
+            stream_report::get_stream_report_policy,
+            commands::get_streaming_quality,
+            commands::set_streaming_quality,
+            stream_report::set_stream_report_policy,
             // Share commands
             commands::share_track_songlink,
             commands::share_album_songlink,
             commands::get_qobuz_track_url,
             commands::get_qobuz_album_url,
             commands::get_qobuz_artist_url,
+            commands::build_share_url,
             // Local library commands
             library::commands::library_add_folder,
             library::commands::library_remove_folder,
@@ -517,13 +761,18 @@ pub fn run() {
             // Download cache commands
             download_cache::commands::download_track,
             download_cache::commands::is_track_downloaded,
+            download_cache::commands::verify_offline_library,
+            download_cache::commands::upgrade_offline_track,
             download_cache::commands::get_downloaded_track_path,
             download_cache::commands::get_downloaded_track,
             download_cache::commands::get_downloaded_tracks,
+            download_cache::commands::get_offline_library_grouped,
             download_cache::commands::get_download_cache_stats,
             download_cache::commands::remove_downloaded_track,
             download_cache::commands::clear_download_cache,
             download_cache::commands::set_download_cache_limit,
+            download_cache::commands::estimate_download_size,
+            download_cache::commands::download_booklet,
             download_cache::commands::open_download_cache_folder,
             download_cache::commands::open_album_folder,
             download_cache::commands::check_album_fully_downloaded,
@@ -539,6 +788,7 @@ pub fn run() {
             // Recommendation store commands
             reco_store::commands::reco_log_event,
             reco_store::commands::reco_get_home,
+            reco_store::commands::export_history,
             reco_store::commands::reco_train_scores,
             reco_store::commands::reco_get_home_ml,
             reco_store::commands::get_playlist_suggestions,
@@ -549,23 +799,64 @@ pub fn run() {
             session_store::save_session_position,
             session_store::save_session_playback_mode,
             session_store::clear_session,
+            session_store::get_saved_position,
+            session_store::handoff::export_session_state,
+            session_store::handoff::import_session_state,
+            // Search history commands
+            search_history::add_recent_search,
+            search_history::get_recent_searches,
+            search_history::clear_recent_searches,
             // Audio settings commands
             config::audio_settings::get_audio_settings,
             config::audio_settings::set_audio_output_device,
+            config::audio_settings::get_device_volume,
+            config::audio_settings::set_device_volume,
             config::audio_settings::set_audio_exclusive_mode,
             config::audio_settings::set_audio_dac_passthrough,
             config::audio_settings::set_audio_sample_rate,
             config::audio_settings::set_audio_backend_type,
             config::audio_settings::set_audio_alsa_plugin,
+            config::audio_settings::set_audio_dsd_mode,
+            config::audio_settings::set_audio_buffer_config,
+            config::audio_settings::set_auto_quality,
+            config::audio_settings::set_preferred_container,
+            config::audio_settings::set_register_device,
+            config::audio_settings::set_audio_fade_in,
+            config::audio_settings::set_channel_mode,
+            config::audio_settings::set_silence_trim,
+            config::audio_settings::set_resample_quality,
+            config::audio_settings::set_audio_pregain,
+            config::audio_settings::set_audio_normalization,
             // Audio backend commands
             commands::get_available_backends,
             commands::get_devices_for_backend,
+            commands::get_device_capabilities,
             commands::get_alsa_plugins,
             // Download settings commands
             config::download_settings::get_download_settings,
             config::download_settings::set_download_root,
             config::download_settings::set_show_downloads_in_library,
             config::download_settings::validate_download_root,
+            config::download_settings::set_pause_on_metered,
+            config::download_settings::set_metered_connection,
+            config::download_settings::get_metered_status,
+            // Cache settings commands
+            config::cache_settings::get_cache_settings,
+            config::cache_settings::set_warm_cache_on_login,
+            config::cache_settings::set_cache_mode,
+            config::cache_settings::set_warm_queue_additions,
+            config::startup_settings::get_startup_config,
+            config::startup_settings::set_startup_config,
+            startup::get_startup_state,
+            // Global shortcuts
+            shortcuts::get_global_shortcuts,
+            shortcuts::register_global_shortcut,
+            shortcuts::unregister_global_shortcut,
+            config::endpoint_priority::get_endpoint_priority,
+            config::endpoint_priority::set_endpoint_priority,
+            config::endpoint_priority::pin_endpoint,
+            config::endpoint_priority::record_endpoint_result,
+            config::endpoint_priority::get_endpoint_try_order,
             // Offline mode commands
             offline::commands::get_offline_status,
             offline::commands::get_offline_settings,
@@ -609,7 +900,17 @@ pub fn run() {
             nostr_cache::nostr_cache_set_query,
             nostr_cache::nostr_cache_get_stats,
             nostr_cache::nostr_cache_clear,
+            nostr_cache::export_nostr_cache,
+            nostr_cache::import_nostr_cache,
+            nostr_cache::nostr_cache_get_maintenance_config,
+            nostr_cache::nostr_cache_set_maintenance_config,
+            nostr_cache::hydrate::nostr_cache_hydrate_tracks,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                app_handle.state::<nostr_cache::NostrCacheState>().stop_maintenance();
+            }
+        });
 }
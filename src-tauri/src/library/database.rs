@@ -1968,6 +1968,36 @@ impl LibraryDatabase {
         Ok(count)
     }
 
+    /// Rewrite stored file and artwork paths starting with `old_prefix` to
+    /// start with `new_prefix`, in one transaction. Returns the rows changed.
+    pub fn relocate_paths(&mut self, old_prefix: &str, new_prefix: &str) -> Result<usize, LibraryError> {
+        let tx = self.conn.transaction()
+            .map_err(|e| LibraryError::Database(format!("Failed to start path update: {}", e)))?;
+
+        let mut changed = 0;
+        for (table, column) in [
+            ("local_tracks", "file_path"),
+            ("local_tracks", "artwork_path"),
+            ("playlist_settings", "custom_artwork_path"),
+            ("artist_images", "custom_image_path"),
+        ] {
+            changed += tx.execute(
+                &format!(
+                    "UPDATE {table} SET {col} = ?2 || substr({col}, length(?1) + 1)
+                     WHERE substr({col}, 1, length(?1)) = ?1",
+                    table = table,
+                    col = column
+                ),
+                params![old_prefix, new_prefix],
+            )
+            .map_err(|e| LibraryError::Database(format!("Failed to update {}.{}: {}", table, column, e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| LibraryError::Database(format!("Failed to commit path update: {}", e)))?;
+        Ok(changed)
+    }
+
     // === Artist Images Management ===
 
     /// Get cached artist image
@@ -2011,6 +2041,24 @@ impl LibraryDatabase {
         Ok(())
     }
 
+    /// Image files the library still points at (album artwork, custom
+    /// playlist covers and artist images), which must survive a cache clear
+    pub fn referenced_image_paths(&self) -> Result<std::collections::HashSet<String>, LibraryError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT artwork_path FROM local_tracks WHERE artwork_path IS NOT NULL
+             UNION SELECT custom_artwork_path FROM playlist_settings WHERE custom_artwork_path IS NOT NULL
+             UNION SELECT custom_image_path FROM artist_images WHERE custom_image_path IS NOT NULL",
+        )
+        .map_err(|e| LibraryError::Database(format!("Failed to prepare image paths query: {}", e)))?;
+
+        let paths = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| LibraryError::Database(format!("Failed to query image paths: {}", e)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(paths)
+    }
+
     // === Offline Mode: Local Content Detection ===
 
     /// Check if a track exists locally by Qobuz track ID
@@ -35,10 +35,7 @@ pub fn get_db_path() -> PathBuf {
 
 /// Get artwork cache directory
 pub fn get_artwork_cache_dir() -> PathBuf {
-    let cache_dir = dirs::cache_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("qbz")
-        .join("artwork");
+    let cache_dir = crate::storage::storage_root().join(crate::storage::ARTWORK_DIR);
     std::fs::create_dir_all(&cache_dir).ok();
     cache_dir
 }
@@ -148,8 +148,9 @@ impl MediaControlsManager {
     }
 }
 
+/// Payload of the `media:control` event
 #[derive(Debug, Serialize)]
-struct MediaControlPayload {
+pub(crate) struct MediaControlPayload {
     action: String,
     direction: Option<String>,
     offset_secs: Option<i64>,
@@ -209,7 +210,7 @@ impl From<MediaControlEvent> for MediaControlPayload {
 }
 
 impl MediaControlPayload {
-    fn action_only(action: &str) -> Self {
+    pub(crate) fn action_only(action: &str) -> Self {
         Self {
             action: action.to_string(),
             direction: None,
@@ -218,6 +219,28 @@ impl MediaControlPayload {
             volume: None,
         }
     }
+
+    /// Seek relative to the current position (negative = backward)
+    pub(crate) fn seek_by(offset_secs: i64) -> Self {
+        let direction = if offset_secs < 0 { SeekDirection::Backward } else { SeekDirection::Forward };
+        Self {
+            action: "seek_by".to_string(),
+            direction: Some(direction_to_string(direction)),
+            offset_secs: Some(offset_secs),
+            position_secs: None,
+            volume: None,
+        }
+    }
+
+    pub(crate) fn set_volume(volume: f64) -> Self {
+        Self {
+            action: "set_volume".to_string(),
+            direction: None,
+            offset_secs: None,
+            position_secs: None,
+            volume: Some(volume),
+        }
+    }
 }
 
 fn direction_to_string(direction: SeekDirection) -> String {
@@ -0,0 +1,218 @@
+//! Metered connection handling
+//!
+//! With "pause on metered" enabled, background prefetch and offline downloads
+//! hold off while the connection is metered and resume once it isn't:
+//! prefetches are cancelled and planned again, downloads in flight stop
+//! between chunks and pick up where they were.
+//! NetworkManager's view is polled when available; the manual flag covers
+//! systems (or connections) it can't tell about.
+
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// How often NetworkManager is asked about the connection
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+static METERED: MeteredGate = MeteredGate::new();
+
+/// Process-wide metered state
+pub fn metered() -> &'static MeteredGate {
+    &METERED
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeteredStatus {
+    pub pause_on_metered: bool,
+    /// Flagged as metered by the user
+    pub manual: bool,
+    /// Reported as metered by NetworkManager
+    pub detected: bool,
+    /// Prefetch and downloads are currently held
+    pub paused: bool,
+}
+
+pub struct MeteredGate {
+    pause_on_metered: AtomicBool,
+    manual: AtomicBool,
+    detected: AtomicBool,
+    change: Notify,
+}
+
+impl Default for MeteredGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeteredGate {
+    pub const fn new() -> Self {
+        Self {
+            pause_on_metered: AtomicBool::new(false),
+            manual: AtomicBool::new(false),
+            detected: AtomicBool::new(false),
+            change: Notify::const_new(),
+        }
+    }
+
+    pub fn set_pause_on_metered(&self, enabled: bool) {
+        self.pause_on_metered.store(enabled, Ordering::SeqCst);
+        self.changed();
+    }
+
+    pub fn set_manual(&self, metered: bool) {
+        self.manual.store(metered, Ordering::SeqCst);
+        self.changed();
+    }
+
+    pub fn set_detected(&self, metered: bool) {
+        self.detected.store(metered, Ordering::SeqCst);
+        self.changed();
+    }
+
+    pub fn is_metered(&self) -> bool {
+        self.manual.load(Ordering::SeqCst) || self.detected.load(Ordering::SeqCst)
+    }
+
+    /// Background network use should hold off
+    pub fn is_paused(&self) -> bool {
+        self.pause_on_metered.load(Ordering::SeqCst) && self.is_metered()
+    }
+
+    pub fn status(&self) -> MeteredStatus {
+        MeteredStatus {
+            pause_on_metered: self.pause_on_metered.load(Ordering::SeqCst),
+            manual: self.manual.load(Ordering::SeqCst),
+            detected: self.detected.load(Ordering::SeqCst),
+            paused: self.is_paused(),
+        }
+    }
+
+    /// Return once background transfers may run
+    pub async fn wait_until_unmetered(&self) {
+        self.wait_for(false).await
+    }
+
+    /// Return once background transfers should hold off
+    pub async fn wait_until_paused(&self) {
+        self.wait_for(true).await
+    }
+
+    /// Call `on_change` each time background transfers are paused (true) or
+    /// resumed (false). Never returns.
+    pub async fn follow(&self, mut on_change: impl FnMut(bool)) {
+        let mut paused = self.is_paused();
+        loop {
+            self.wait_for(!paused).await;
+            paused = !paused;
+            on_change(paused);
+        }
+    }
+
+    async fn wait_for(&self, paused: bool) {
+        loop {
+            let notified = self.change.notified();
+            tokio::pin!(notified);
+            // Register before checking so a change in between isn't missed
+            notified.as_mut().enable();
+            if self.is_paused() == paused {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn changed(&self) {
+        self.change.notify_waiters();
+    }
+}
+
+/// Parse `busctl get-property` output for NetworkManager's `Metered`
+/// property (`u 1`): 1 yes and 3 guessed yes are metered, 2 and 4 are not,
+/// 0 is unknown.
+fn parse_nm_metered(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("u ")?.trim() {
+        "1" | "3" => Some(true),
+        "2" | "4" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether NetworkManager considers the primary connection metered
+pub fn detect_metered() -> Option<bool> {
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_nm_metered(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Keep the detected state in step with NetworkManager
+pub async fn watch_connection() {
+    loop {
+        let detected = tokio::task::spawn_blocking(detect_metered)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(false);
+        let gate = metered();
+        if detected != gate.detected.load(Ordering::SeqCst) {
+            log::info!("Connection is {}metered", if detected { "" } else { "no longer " });
+            gate.set_detected(detected);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_metered_flag_holds_transfers_until_cleared() {
+        let gate = Arc::new(MeteredGate::new());
+
+        // Metered alone doesn't pause anything unless the option is on
+        gate.set_manual(true);
+        assert!(!gate.is_paused());
+        gate.set_pause_on_metered(true);
+        assert!(gate.is_paused());
+
+        let waiting = tokio::spawn({
+            let gate = gate.clone();
+            async move { gate.wait_until_unmetered().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        gate.set_manual(false);
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert!(!gate.is_paused());
+
+        // Detection pauses too; clearing it resumes
+        gate.set_detected(true);
+        assert_eq!(
+            gate.status(),
+            MeteredStatus { pause_on_metered: true, manual: false, detected: true, paused: true }
+        );
+        gate.set_detected(false);
+        gate.wait_until_unmetered().await;
+
+        assert_eq!(parse_nm_metered("u 1\n"), Some(true));
+        assert_eq!(parse_nm_metered("u 4"), Some(false));
+        assert_eq!(parse_nm_metered("u 0"), None);
+    }
+}
@@ -0,0 +1,323 @@
+//! JSON backup of the Nostr cache
+//!
+//! Exports every cached profile, track and playlist so a local library can
+//! be backed up or moved to another machine. Query results are left out:
+//! they are short-lived and rebuilt on the next fetch. Follow lists aren't
+//! cached locally, so there is nothing to back up for them yet.
+//!
+//! Profiles, tracks and playlists are replaceable events, so on import the
+//! row with the newest `created_at` wins and an equally old import never
+//! overwrites what is already cached.
+
+use rusqlite::{params, OptionalExtension, Row, Transaction};
+use serde::{Deserialize, Serialize};
+
+use super::{CachedPlaylist, CachedProfile, CachedTrack, NostrCache};
+
+/// Version of the export format, bumped on incompatible changes
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NostrCacheExport {
+    pub version: u32,
+    pub exported_at: i64,
+    pub profiles: Vec<CachedProfile>,
+    pub tracks: Vec<CachedTrack>,
+    pub playlists: Vec<CachedPlaylist>,
+}
+
+/// Rows written by an import; older or equal rows are counted as skipped
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NostrImportSummary {
+    pub profiles: usize,
+    pub tracks: usize,
+    pub playlists: usize,
+    pub skipped: usize,
+}
+
+fn profile_from_row(row: &Row) -> rusqlite::Result<CachedProfile> {
+    Ok(CachedProfile {
+        pubkey: row.get(0)?,
+        name: row.get(1)?,
+        display_name: row.get(2)?,
+        picture: row.get(3)?,
+        about: row.get(4)?,
+        nip05: row.get(5)?,
+        created_at: row.get(6)?,
+        fetched_at: row.get(7)?,
+    })
+}
+
+pub(super) fn track_from_row(row: &Row) -> rusqlite::Result<CachedTrack> {
+    Ok(CachedTrack {
+        event_id: row.get(0)?,
+        pubkey: row.get(1)?,
+        d_tag: row.get(2)?,
+        title: row.get(3)?,
+        artist: row.get(4)?,
+        album: row.get(5)?,
+        url: row.get(6)?,
+        image: row.get(7)?,
+        duration: row.get(8)?,
+        genres: row.get(9)?,
+        created_at: row.get(10)?,
+        fetched_at: row.get(11)?,
+        qobuz_track_id: row.get::<_, Option<i64>>(12)?.map(|id| id as u64),
+        quality: row.get(13)?,
+        hydrated_at: row.get(14)?,
+    })
+}
+
+fn playlist_from_row(row: &Row) -> rusqlite::Result<CachedPlaylist> {
+    Ok(CachedPlaylist {
+        event_id: row.get(0)?,
+        pubkey: row.get(1)?,
+        d_tag: row.get(2)?,
+        title: row.get(3)?,
+        description: row.get(4)?,
+        image: row.get(5)?,
+        is_public: row.get::<_, i64>(6)? != 0,
+        track_refs: row.get(7)?,
+        created_at: row.get(8)?,
+        fetched_at: row.get(9)?,
+    })
+}
+
+/// Whether a row created at `created_at` should replace the cached one
+fn is_newer(tx: &Transaction, sql: &str, key: &[&str], created_at: i64) -> Result<bool, String> {
+    let existing: Option<i64> = tx
+        .query_row(sql, rusqlite::params_from_iter(key), |row| row.get(0))
+        .optional()
+        .map_err(|e| format!("Failed to check cached Nostr row: {}", e))?;
+    Ok(existing.is_none_or(|existing| created_at > existing))
+}
+
+impl NostrCache {
+    fn export_rows<T>(
+        &self,
+        sql: &str,
+        from_row: fn(&Row) -> rusqlite::Result<T>,
+    ) -> Result<Vec<T>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| format!("Failed to prepare Nostr cache export: {}", e))?;
+        let rows = stmt
+            .query_map([], from_row)
+            .map_err(|e| format!("Failed to export Nostr cache: {}", e))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Failed to read Nostr cache row: {}", e))?);
+        }
+        Ok(results)
+    }
+
+    /// Every cached profile, track and playlist
+    pub fn export_all(&self) -> Result<NostrCacheExport, String> {
+        Ok(NostrCacheExport {
+            version: EXPORT_VERSION,
+            exported_at: Self::current_timestamp(),
+            profiles: self.export_rows(
+                "SELECT pubkey, name, display_name, picture, about, nip05, created_at, fetched_at
+                 FROM nostr_profiles ORDER BY pubkey",
+                profile_from_row,
+            )?,
+            tracks: self.export_rows(
+                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                 qobuz_track_id, quality, hydrated_at
+                 FROM nostr_tracks ORDER BY pubkey, d_tag",
+                track_from_row,
+            )?,
+            playlists: self.export_rows(
+                "SELECT event_id, pubkey, d_tag, title, description, image, is_public, track_refs, created_at, fetched_at
+                 FROM nostr_playlists ORDER BY pubkey, d_tag",
+                playlist_from_row,
+            )?,
+        })
+    }
+
+    /// Upsert an export in one transaction, keeping the newest version of each row
+    pub fn import_all(&mut self, export: &NostrCacheExport) -> Result<NostrImportSummary, String> {
+        if export.version > EXPORT_VERSION {
+            return Err(format!(
+                "Nostr cache export version {} is newer than supported version {}",
+                export.version, EXPORT_VERSION
+            ));
+        }
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to start Nostr cache import: {}", e))?;
+        let mut summary = NostrImportSummary::default();
+
+        for profile in &export.profiles {
+            if !is_newer(&tx, "SELECT created_at FROM nostr_profiles WHERE pubkey = ?", &[profile.pubkey.as_str()], profile.created_at)? {
+                summary.skipped += 1;
+                continue;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO nostr_profiles
+                 (pubkey, name, display_name, picture, about, nip05, created_at, fetched_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    profile.pubkey,
+                    profile.name,
+                    profile.display_name,
+                    profile.picture,
+                    profile.about,
+                    profile.nip05,
+                    profile.created_at,
+                    profile.fetched_at,
+                ],
+            )
+            .map_err(|e| format!("Failed to import profile: {}", e))?;
+            summary.profiles += 1;
+        }
+
+        for track in &export.tracks {
+            let key = [track.pubkey.as_str(), track.d_tag.as_str()];
+            if !is_newer(&tx, "SELECT created_at FROM nostr_tracks WHERE pubkey = ? AND d_tag = ?", &key, track.created_at)? {
+                summary.skipped += 1;
+                continue;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO nostr_tracks
+                 (event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                  qobuz_track_id, quality, hydrated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    track.event_id,
+                    track.pubkey,
+                    track.d_tag,
+                    track.title,
+                    track.artist,
+                    track.album,
+                    track.url,
+                    track.image,
+                    track.duration,
+                    track.genres,
+                    track.created_at,
+                    track.fetched_at,
+                    track.qobuz_track_id.map(|id| id as i64),
+                    track.quality,
+                    track.hydrated_at,
+                ],
+            )
+            .map_err(|e| format!("Failed to import track: {}", e))?;
+            summary.tracks += 1;
+        }
+
+        for playlist in &export.playlists {
+            let key = [playlist.pubkey.as_str(), playlist.d_tag.as_str()];
+            if !is_newer(&tx, "SELECT created_at FROM nostr_playlists WHERE pubkey = ? AND d_tag = ?", &key, playlist.created_at)? {
+                summary.skipped += 1;
+                continue;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO nostr_playlists
+                 (event_id, pubkey, d_tag, title, description, image, is_public, track_refs, created_at, fetched_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    playlist.event_id,
+                    playlist.pubkey,
+                    playlist.d_tag,
+                    playlist.title,
+                    playlist.description,
+                    playlist.image,
+                    playlist.is_public as i64,
+                    playlist.track_refs,
+                    playlist.created_at,
+                    playlist.fetched_at,
+                ],
+            )
+            .map_err(|e| format!("Failed to import playlist: {}", e))?;
+            summary.playlists += 1;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit Nostr cache import: {}", e))?;
+        log::info!("Imported Nostr cache backup: {:?}", summary);
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn populated_cache() -> NostrCache {
+        let cache = NostrCache::new(Path::new(":memory:")).unwrap();
+        cache
+            .set_profile(&CachedProfile {
+                pubkey: "alice".to_string(),
+                name: Some("alice".to_string()),
+                display_name: Some("Alice".to_string()),
+                picture: None,
+                about: Some("Makes music".to_string()),
+                nip05: None,
+                created_at: 100,
+                fetched_at: 200,
+            })
+            .unwrap();
+        for (d_tag, created_at) in [("one", 10), ("two", 20)] {
+            cache
+                .set_track(&CachedTrack {
+                    event_id: format!("event-{}", d_tag),
+                    pubkey: "alice".to_string(),
+                    d_tag: d_tag.to_string(),
+                    title: format!("Track {}", d_tag),
+                    artist: "Alice".to_string(),
+                    album: None,
+                    url: format!("https://example.com/{}.mp3", d_tag),
+                    image: None,
+                    duration: Some(180),
+                    genres: r#"["ambient"]"#.to_string(),
+                    created_at,
+                    fetched_at: 200,
+                    qobuz_track_id: None,
+                    quality: None,
+                    hydrated_at: None,
+                })
+                .unwrap();
+        }
+        cache
+            .set_playlist(&CachedPlaylist {
+                event_id: "event-list".to_string(),
+                pubkey: "alice".to_string(),
+                d_tag: "list".to_string(),
+                title: "Favorites".to_string(),
+                description: None,
+                image: None,
+                is_public: false,
+                track_refs: r#"["alice:one","alice:two"]"#.to_string(),
+                created_at: 30,
+                fetched_at: 200,
+            })
+            .unwrap();
+        cache
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = populated_cache();
+        let json = serde_json::to_string(&source.export_all().unwrap()).unwrap();
+
+        let mut target = NostrCache::new(Path::new(":memory:")).unwrap();
+        let export: NostrCacheExport = serde_json::from_str(&json).unwrap();
+        let summary = target.import_all(&export).unwrap();
+        assert_eq!(summary, NostrImportSummary { profiles: 1, tracks: 2, playlists: 1, skipped: 0 });
+
+        let (mut original, mut restored) = (source.export_all().unwrap(), target.export_all().unwrap());
+        restored.exported_at = original.exported_at;
+        assert_eq!(restored, original);
+
+        // Re-importing the same (or older) data changes nothing
+        original.tracks[0].title = "Older edit".to_string();
+        let summary = target.import_all(&original).unwrap();
+        assert_eq!(summary.skipped, 4);
+        assert_eq!(target.get_track("alice", "one").unwrap().unwrap().title, "Track one");
+    }
+}
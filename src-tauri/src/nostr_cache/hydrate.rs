@@ -0,0 +1,397 @@
+//! Qobuz metadata for cached Nostr tracks
+//!
+//! Track events whose `url` points at a Qobuz track only carry what the
+//! publisher wrote. The hydrator looks those tracks up on Qobuz and fills
+//! in quality, duration and cover, a batch at a time and spaced out to stay
+//! under the API rate limit. Rows are marked once hydrated and skipped on
+//! later runs, as are rows Qobuz refused `MAX_HYDRATION_ATTEMPTS` times.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+use super::{NostrCache, NostrCacheState};
+use crate::api::{ApiError, Quality, Track};
+use crate::AppState;
+
+/// Only one hydration runs at a time
+static HYDRATING: AtomicBool = AtomicBool::new(false);
+
+/// Failed lookups after which a track is no longer retried. Network errors
+/// and rate limiting don't count.
+pub const MAX_HYDRATION_ATTEMPTS: u32 = 3;
+
+/// Batch size and pacing of Qobuz lookups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HydrationConfig {
+    /// Tracks looked up between progress updates
+    pub batch_size: usize,
+    /// Minimum delay between two lookups
+    pub request_interval_ms: u64,
+    /// Pause after each batch
+    pub batch_pause_ms: u64,
+}
+
+impl Default for HydrationConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 20,
+            request_interval_ms: 250,
+            batch_pause_ms: 2_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HydrationProgress {
+    /// Un-hydrated Qobuz tracks found at the start
+    pub total: usize,
+    pub hydrated: usize,
+    pub failed: usize,
+    pub done: bool,
+}
+
+/// What a Qobuz lookup adds to a cached track
+#[derive(Debug, Clone, PartialEq)]
+pub struct QobuzTrackMetadata {
+    pub qobuz_track_id: u64,
+    /// None when Qobuz doesn't say what format the track comes in
+    pub quality: Option<String>,
+    pub duration: Option<i64>,
+    pub image: Option<String>,
+}
+
+impl QobuzTrackMetadata {
+    pub fn from_track(track: &Track) -> Self {
+        let quality = match (track.maximum_bit_depth, track.maximum_sampling_rate) {
+            (Some(bits), Some(rate)) if bits > 16 && rate > 96.0 => Some(Quality::UltraHiRes),
+            (Some(bits), _) if bits > 16 => Some(Quality::HiRes),
+            (Some(_), _) => Some(Quality::Lossless),
+            // Lossy-only tracks come without a bit depth
+            (None, _) if track.maximum_bit_rate.is_some_and(|kbps| kbps <= 320.0) => Some(Quality::Mp3),
+            (None, _) => None,
+        };
+        let image = track.album.as_ref().and_then(|album| {
+            album.image.large.clone().or_else(|| album.image.small.clone())
+        });
+
+        Self {
+            qobuz_track_id: track.id,
+            quality: quality.map(|q| q.label().to_string()),
+            duration: (track.duration > 0).then_some(track.duration as i64),
+            image,
+        }
+    }
+}
+
+/// Qobuz track id of a `play.`/`open.`/`www.qobuz.com/.../track/<id>` URL
+pub fn qobuz_track_id(url: &str) -> Option<u64> {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let (host, path) = rest.split_once('/')?;
+    let host = host.to_ascii_lowercase();
+    if host != "qobuz.com" && !host.ends_with(".qobuz.com") {
+        return None;
+    }
+
+    let mut segments = path.split(['/', '?', '#']);
+    segments.find(|segment| *segment == "track")?;
+    segments.next()?.parse().ok()
+}
+
+impl NostrCache {
+    /// Cached tracks pointing at Qobuz that haven't been hydrated yet and
+    /// haven't run out of attempts
+    pub fn pending_hydration(&self) -> Result<Vec<(String, u64)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT event_id, url FROM nostr_tracks
+                 WHERE hydrated_at IS NULL AND hydration_attempts < ?1 AND url LIKE '%qobuz.com%'",
+            )
+            .map_err(|e| format!("Failed to prepare hydration query: {}", e))?;
+        let rows = stmt
+            .query_map(params![MAX_HYDRATION_ATTEMPTS], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query tracks to hydrate: {}", e))?;
+
+        let mut pending = Vec::new();
+        for row in rows {
+            let (event_id, url) = row.map_err(|e| format!("Failed to read track row: {}", e))?;
+            if let Some(track_id) = qobuz_track_id(&url) {
+                pending.push((event_id, track_id));
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Store Qobuz metadata on a cached track. What the publisher provided
+    /// (duration, cover) is kept; only missing fields are filled.
+    pub fn apply_hydration(&self, event_id: &str, metadata: &QobuzTrackMetadata) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE nostr_tracks SET
+                    qobuz_track_id = ?2,
+                    quality = ?3,
+                    duration = COALESCE(duration, ?4),
+                    image = COALESCE(image, ?5),
+                    hydrated_at = ?6
+                 WHERE event_id = ?1",
+                params![
+                    event_id,
+                    metadata.qobuz_track_id as i64,
+                    metadata.quality,
+                    metadata.duration,
+                    metadata.image,
+                    Self::current_timestamp(),
+                ],
+            )
+            .map_err(|e| format!("Failed to store hydrated track: {}", e))?;
+        Ok(())
+    }
+
+    /// Count a failed Qobuz lookup against a cached track
+    pub fn record_hydration_failure(&self, event_id: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE nostr_tracks SET hydration_attempts = hydration_attempts + 1 WHERE event_id = ?1",
+                params![event_id],
+            )
+            .map_err(|e| format!("Failed to record hydration failure: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Hydrate every pending track, reporting progress after each batch.
+/// The cache is only locked for reads and writes, never across a lookup.
+pub async fn hydrate_tracks<F, Fut>(
+    cache: &Mutex<NostrCache>,
+    config: &HydrationConfig,
+    lookup: F,
+    mut on_progress: impl FnMut(&HydrationProgress),
+) -> Result<HydrationProgress, String>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<Track, ApiError>>,
+{
+    let pending = cache.lock().await.pending_hydration()?;
+    let mut progress = HydrationProgress { total: pending.len(), ..Default::default() };
+    let interval = Duration::from_millis(config.request_interval_ms);
+
+    for (batch_index, batch) in pending.chunks(config.batch_size.max(1)).enumerate() {
+        if batch_index > 0 {
+            tokio::time::sleep(Duration::from_millis(config.batch_pause_ms)).await;
+        }
+
+        for (index, (event_id, track_id)) in batch.iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(interval).await;
+            }
+            let result = match lookup(*track_id).await {
+                Err(ApiError::RateLimited(retry_after)) => {
+                    log::info!("Qobuz rate limit hit while hydrating, waiting {}s", retry_after);
+                    tokio::time::sleep(Duration::from_secs(retry_after)).await;
+                    lookup(*track_id).await
+                }
+                result => result,
+            };
+
+            match result {
+                Ok(track) => {
+                    let metadata = QobuzTrackMetadata::from_track(&track);
+                    cache.lock().await.apply_hydration(event_id, &metadata)?;
+                    progress.hydrated += 1;
+                }
+                Err(e) => {
+                    // Left un-hydrated, so the next run tries again until
+                    // Qobuz has refused it MAX_HYDRATION_ATTEMPTS times
+                    log::debug!("Failed to hydrate Nostr track {} (Qobuz {}): {}", event_id, track_id, e);
+                    if !e.is_transport() {
+                        cache.lock().await.record_hydration_failure(event_id)?;
+                    }
+                    progress.failed += 1;
+                }
+            }
+        }
+        on_progress(&progress);
+    }
+
+    progress.done = true;
+    on_progress(&progress);
+    Ok(progress)
+}
+
+/// Start hydrating cached Nostr tracks in the background. Progress is
+/// emitted as `nostr-cache:hydration-progress`; returns false when a
+/// hydration is already running.
+#[tauri::command]
+pub fn nostr_cache_hydrate_tracks(
+    config: Option<HydrationConfig>,
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    cache_state: tauri::State<'_, NostrCacheState>,
+) -> Result<bool, String> {
+    if HYDRATING.swap(true, Ordering::SeqCst) {
+        return Ok(false);
+    }
+
+    let client = state.client.clone();
+    let cache = cache_state.cache.clone();
+    let config = config.unwrap_or_default();
+    tauri::async_runtime::spawn(async move {
+        let result = hydrate_tracks(
+            &cache,
+            &config,
+            |track_id| {
+                let client = client.clone();
+                async move { client.lock().await.get_track(track_id).await }
+            },
+            |progress| {
+                let _ = app_handle.emit("nostr-cache:hydration-progress", progress);
+            },
+        )
+        .await;
+
+        match result {
+            Ok(progress) => log::info!(
+                "Nostr track hydration finished: {} hydrated, {} failed",
+                progress.hydrated,
+                progress.failed
+            ),
+            Err(e) => log::warn!("Nostr track hydration failed: {}", e),
+        }
+        HYDRATING.store(false, Ordering::SeqCst);
+    });
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_cache::CachedTrack;
+    use std::path::Path;
+    use std::sync::Mutex as StdMutex;
+
+    fn cached(event_id: &str, url: &str, hydrated_at: Option<i64>) -> CachedTrack {
+        CachedTrack {
+            event_id: event_id.to_string(),
+            pubkey: "alice".to_string(),
+            d_tag: event_id.to_string(),
+            title: event_id.to_string(),
+            artist: "Alice".to_string(),
+            album: None,
+            url: url.to_string(),
+            image: None,
+            duration: None,
+            genres: "[]".to_string(),
+            created_at: 10,
+            fetched_at: 10,
+            qobuz_track_id: hydrated_at.map(|_| 1),
+            quality: hydrated_at.map(|_| Quality::Lossless.label().to_string()),
+            hydrated_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_unhydrated_qobuz_rows_are_fetched_and_filled() {
+        let cache = NostrCache::new(Path::new(":memory:")).unwrap();
+        cache.set_track(&cached("new", "https://open.qobuz.com/track/52341234", None)).unwrap();
+        cache.set_track(&cached("done", "https://play.qobuz.com/track/1", Some(5))).unwrap();
+        cache.set_track(&cached("blossom", "https://blossom.example/abc.flac", None)).unwrap();
+        let cache = Mutex::new(cache);
+
+        let fetched = StdMutex::new(Vec::new());
+        let config = HydrationConfig { batch_size: 10, request_interval_ms: 0, batch_pause_ms: 0 };
+        let mut updates = Vec::new();
+        let progress = hydrate_tracks(
+            &cache,
+            &config,
+            |track_id| {
+                fetched.lock().unwrap().push(track_id);
+                async move {
+                    Ok(serde_json::from_value::<Track>(serde_json::json!({
+                        "id": track_id,
+                        "duration": 245,
+                        "maximum_bit_depth": 24,
+                        "maximum_sampling_rate": 192.0,
+                        "album": { "id": "a", "title": "A", "image": { "large": "https://img/cover.jpg" } }
+                    }))
+                    .unwrap())
+                }
+            },
+            |progress| updates.push(progress.clone()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*fetched.lock().unwrap(), vec![52341234]);
+        assert_eq!(progress, HydrationProgress { total: 1, hydrated: 1, failed: 0, done: true });
+        assert!(updates.last().unwrap().done);
+
+        let cache = cache.lock().await;
+        let track = cache.get_track("alice", "new").unwrap().unwrap();
+        assert_eq!(track.qobuz_track_id, Some(52341234));
+        assert_eq!(track.quality.as_deref(), Some(Quality::UltraHiRes.label()));
+        assert_eq!(track.duration, Some(245));
+        assert_eq!(track.image.as_deref(), Some("https://img/cover.jpg"));
+        assert!(track.hydrated_at.is_some());
+
+        // Re-caching the event from a relay keeps the hydrated fields
+        cache.set_track(&cached("new", "https://open.qobuz.com/track/52341234", None)).unwrap();
+        let track = cache.get_track("alice", "new").unwrap().unwrap();
+        assert_eq!(track.duration, Some(245));
+        assert!(cache.pending_hydration().unwrap().is_empty());
+
+        assert_eq!(qobuz_track_id("https://www.qobuz.com/fr-fr/track/77?x=1"), Some(77));
+        assert_eq!(qobuz_track_id("https://notqobuz.com/track/77"), None);
+    }
+
+    #[tokio::test]
+    async fn test_tracks_qobuz_keeps_refusing_stop_being_retried() {
+        let cache = NostrCache::new(Path::new(":memory:")).unwrap();
+        cache.set_track(&cached("gone", "https://open.qobuz.com/track/404", None)).unwrap();
+        cache.set_track(&cached("offline", "https://open.qobuz.com/track/500", None)).unwrap();
+        let cache = Mutex::new(cache);
+        let config = HydrationConfig { batch_size: 10, request_interval_ms: 0, batch_pause_ms: 0 };
+
+        for _ in 0..MAX_HYDRATION_ATTEMPTS {
+            let progress = hydrate_tracks(
+                &cache,
+                &config,
+                |track_id| async move {
+                    Err(match track_id {
+                        404 => ApiError::ApiResponse("Failed to get track 404: 404 Not Found".to_string()),
+                        _ => ApiError::RateLimited(0),
+                    })
+                },
+                |_| {},
+            )
+            .await
+            .unwrap();
+            assert_eq!(progress.failed, 2);
+        }
+
+        // Network trouble and rate limiting never use up attempts
+        let pending = cache.lock().await.pending_hydration().unwrap();
+        assert_eq!(pending, vec![("offline".to_string(), 500)]);
+    }
+
+    #[test]
+    fn test_quality_is_only_set_when_qobuz_reports_the_format() {
+        let track = |fields: serde_json::Value| serde_json::from_value::<Track>(fields).unwrap();
+        let quality = |fields| QobuzTrackMetadata::from_track(&track(fields)).quality;
+
+        assert_eq!(
+            quality(serde_json::json!({ "id": 1, "maximum_bit_depth": 16, "maximum_sampling_rate": 44.1 })),
+            Some(Quality::Lossless.label().to_string())
+        );
+        assert_eq!(
+            quality(serde_json::json!({ "id": 1, "maximum_bit_rate": 320.0 })),
+            Some(Quality::Mp3.label().to_string())
+        );
+        assert_eq!(quality(serde_json::json!({ "id": 1 })), None);
+    }
+}
@@ -7,12 +7,43 @@ use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Mutex};
+
+pub mod backup;
+pub mod hydrate;
+
+pub use backup::{NostrCacheExport, NostrImportSummary};
 
 /// Nostr cache state shared across commands
 pub struct NostrCacheState {
     pub cache: Arc<Mutex<NostrCache>>,
+    pub maintenance: Arc<std::sync::Mutex<MaintenanceConfig>>,
+    shutdown: watch::Sender<bool>,
+}
+
+/// Background cleanup settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// How often the cleanup runs
+    pub interval_secs: u64,
+    /// Max age (since fetched_at) before a cached profile is pruned
+    pub profile_ttl_secs: i64,
+    /// Max age before a cached track is pruned
+    pub track_ttl_secs: i64,
+    /// Max age before a cached playlist is pruned
+    pub playlist_ttl_secs: i64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60 * 60,               // hourly
+            profile_ttl_secs: 7 * 24 * 60 * 60,   // 7 days
+            track_ttl_secs: 30 * 24 * 60 * 60,    // 30 days
+            playlist_ttl_secs: 7 * 24 * 60 * 60,  // 7 days
+        }
+    }
 }
 
 impl NostrCacheState {
@@ -30,15 +61,69 @@ impl NostrCacheState {
 
         log::info!("Nostr cache initialized at {:?}", db_path);
 
-        Ok(Self {
+        Ok(Self::with_cache(cache))
+    }
+
+    pub fn with_cache(cache: NostrCache) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self {
             cache: Arc::new(Mutex::new(cache)),
-        })
+            maintenance: Arc::new(std::sync::Mutex::new(MaintenanceConfig::default())),
+            shutdown,
+        }
     }
+
+    /// Future running the periodic cleanup until `stop_maintenance` is called.
+    /// The caller decides which runtime to spawn it on.
+    pub fn maintenance_task(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        run_maintenance(
+            self.cache.clone(),
+            self.maintenance.clone(),
+            self.shutdown.subscribe(),
+        )
+    }
+
+    /// Stop the background cleanup task
+    pub fn stop_maintenance(&self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+async fn run_maintenance(
+    cache: Arc<Mutex<NostrCache>>,
+    config: Arc<std::sync::Mutex<MaintenanceConfig>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    log::info!("Nostr cache maintenance task started");
+    loop {
+        let current = config
+            .lock()
+            .map(|c| c.clone())
+            .unwrap_or_default();
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(current.interval_secs.max(1))) => {}
+            _ = shutdown.changed() => break,
+        }
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let cache = cache.lock().await;
+        match cache.cleanup_stale(&current) {
+            Ok(removed) if removed > 0 => {
+                log::info!("Nostr cache maintenance removed {} stale row(s)", removed)
+            }
+            Ok(_) => log::debug!("Nostr cache maintenance: nothing to prune"),
+            Err(e) => log::warn!("Nostr cache maintenance failed: {}", e),
+        }
+    }
+    log::info!("Nostr cache maintenance task stopped");
 }
 
 // ============ Cached Data Types ============
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CachedProfile {
     pub pubkey: String,
     pub name: Option<String>,
@@ -50,7 +135,7 @@ pub struct CachedProfile {
     pub fetched_at: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CachedTrack {
     pub event_id: String,
     pub pubkey: String,
@@ -64,9 +149,18 @@ pub struct CachedTrack {
     pub genres: String, // JSON array
     pub created_at: i64,
     pub fetched_at: i64,
+    /// Qobuz track the `url` points to, once hydrated
+    #[serde(default)]
+    pub qobuz_track_id: Option<u64>,
+    /// Best quality Qobuz offers for it
+    #[serde(default)]
+    pub quality: Option<String>,
+    /// When Qobuz metadata was looked up (see `hydrate`)
+    #[serde(default)]
+    pub hydrated_at: Option<i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CachedPlaylist {
     pub event_id: String,
     pub pubkey: String,
@@ -162,6 +256,15 @@ impl NostrCache {
                 "#,
             )
             .map_err(|e| format!("Failed to initialize Nostr cache: {}", e))?;
+
+        // Migration: Qobuz metadata hydrated from track URLs
+        let _ = self.conn.execute("ALTER TABLE nostr_tracks ADD COLUMN qobuz_track_id INTEGER", []);
+        let _ = self.conn.execute("ALTER TABLE nostr_tracks ADD COLUMN quality TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE nostr_tracks ADD COLUMN hydrated_at INTEGER", []);
+        let _ = self.conn.execute(
+            "ALTER TABLE nostr_tracks ADD COLUMN hydration_attempts INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
         Ok(())
     }
 
@@ -230,25 +333,11 @@ impl NostrCache {
         let result: Option<CachedTrack> = self
             .conn
             .query_row(
-                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at
+                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                 qobuz_track_id, quality, hydrated_at
                  FROM nostr_tracks WHERE pubkey = ? AND d_tag = ?",
                 params![pubkey, d_tag],
-                |row| {
-                    Ok(CachedTrack {
-                        event_id: row.get(0)?,
-                        pubkey: row.get(1)?,
-                        d_tag: row.get(2)?,
-                        title: row.get(3)?,
-                        artist: row.get(4)?,
-                        album: row.get(5)?,
-                        url: row.get(6)?,
-                        image: row.get(7)?,
-                        duration: row.get(8)?,
-                        genres: row.get(9)?,
-                        created_at: row.get(10)?,
-                        fetched_at: row.get(11)?,
-                    })
-                },
+                backup::track_from_row,
             )
             .optional()
             .map_err(|e| format!("Failed to query cached track: {}", e))?;
@@ -261,28 +350,14 @@ impl NostrCache {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at
+                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                 qobuz_track_id, quality, hydrated_at
                  FROM nostr_tracks WHERE pubkey = ? ORDER BY created_at DESC",
             )
             .map_err(|e| format!("Failed to prepare tracks query: {}", e))?;
 
         let rows = stmt
-            .query_map(params![pubkey], |row| {
-                Ok(CachedTrack {
-                    event_id: row.get(0)?,
-                    pubkey: row.get(1)?,
-                    d_tag: row.get(2)?,
-                    title: row.get(3)?,
-                    artist: row.get(4)?,
-                    album: row.get(5)?,
-                    url: row.get(6)?,
-                    image: row.get(7)?,
-                    duration: row.get(8)?,
-                    genres: row.get(9)?,
-                    created_at: row.get(10)?,
-                    fetched_at: row.get(11)?,
-                })
-            })
+            .query_map(params![pubkey], backup::track_from_row)
             .map_err(|e| format!("Failed to query tracks: {}", e))?;
 
         let mut results = Vec::new();
@@ -297,28 +372,14 @@ impl NostrCache {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at
+                "SELECT event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                 qobuz_track_id, quality, hydrated_at
                  FROM nostr_tracks ORDER BY created_at DESC LIMIT ?",
             )
             .map_err(|e| format!("Failed to prepare recent tracks query: {}", e))?;
 
         let rows = stmt
-            .query_map(params![limit], |row| {
-                Ok(CachedTrack {
-                    event_id: row.get(0)?,
-                    pubkey: row.get(1)?,
-                    d_tag: row.get(2)?,
-                    title: row.get(3)?,
-                    artist: row.get(4)?,
-                    album: row.get(5)?,
-                    url: row.get(6)?,
-                    image: row.get(7)?,
-                    duration: row.get(8)?,
-                    genres: row.get(9)?,
-                    created_at: row.get(10)?,
-                    fetched_at: row.get(11)?,
-                })
-            })
+            .query_map(params![limit], backup::track_from_row)
             .map_err(|e| format!("Failed to query recent tracks: {}", e))?;
 
         let mut results = Vec::new();
@@ -328,13 +389,22 @@ impl NostrCache {
         Ok(results)
     }
 
-    /// Cache a track
+    /// Cache a track. Re-caching the same event keeps whatever hydration
+    /// filled in that the new row leaves empty.
     pub fn set_track(&self, track: &CachedTrack) -> Result<(), String> {
         self.conn
             .execute(
                 "INSERT OR REPLACE INTO nostr_tracks
-                 (event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                 (event_id, pubkey, d_tag, title, artist, album, url, image, duration, genres, created_at, fetched_at,
+                  qobuz_track_id, quality, hydrated_at, hydration_attempts)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7,
+                  COALESCE(?8, (SELECT image FROM nostr_tracks WHERE event_id = ?1)),
+                  COALESCE(?9, (SELECT duration FROM nostr_tracks WHERE event_id = ?1)),
+                  ?10, ?11, ?12,
+                  COALESCE(?13, (SELECT qobuz_track_id FROM nostr_tracks WHERE event_id = ?1)),
+                  COALESCE(?14, (SELECT quality FROM nostr_tracks WHERE event_id = ?1)),
+                  COALESCE(?15, (SELECT hydrated_at FROM nostr_tracks WHERE event_id = ?1)),
+                  COALESCE((SELECT hydration_attempts FROM nostr_tracks WHERE event_id = ?1), 0))",
                 params![
                     track.event_id,
                     track.pubkey,
@@ -348,6 +418,9 @@ impl NostrCache {
                     track.genres,
                     track.created_at,
                     track.fetched_at,
+                    track.qobuz_track_id.map(|id| id as i64),
+                    track.quality,
+                    track.hydrated_at,
                 ],
             )
             .map_err(|e| format!("Failed to cache track: {}", e))?;
@@ -550,19 +623,49 @@ impl NostrCache {
         Ok(deleted)
     }
 
-    /// Clear all cached data
-    pub fn clear_all(&self) -> Result<(), String> {
-        self.conn
-            .execute_batch(
-                r#"
-                DELETE FROM nostr_profiles;
-                DELETE FROM nostr_tracks;
-                DELETE FROM nostr_playlists;
-                DELETE FROM nostr_query_cache;
-                "#,
+    /// Prune expired queries and profiles/tracks/playlists older than their TTL.
+    /// Returns the total number of rows removed.
+    pub fn cleanup_stale(&self, config: &MaintenanceConfig) -> Result<usize, String> {
+        let now = Self::current_timestamp();
+        let mut removed = self.cleanup_expired_queries()?;
+
+        removed += self
+            .conn
+            .execute(
+                "DELETE FROM nostr_profiles WHERE fetched_at <= ?",
+                params![now - config.profile_ttl_secs],
             )
-            .map_err(|e| format!("Failed to clear Nostr cache: {}", e))?;
-        Ok(())
+            .map_err(|e| format!("Failed to cleanup stale profiles: {}", e))?;
+
+        removed += self
+            .conn
+            .execute(
+                "DELETE FROM nostr_tracks WHERE fetched_at <= ?",
+                params![now - config.track_ttl_secs],
+            )
+            .map_err(|e| format!("Failed to cleanup stale tracks: {}", e))?;
+
+        removed += self
+            .conn
+            .execute(
+                "DELETE FROM nostr_playlists WHERE fetched_at <= ?",
+                params![now - config.playlist_ttl_secs],
+            )
+            .map_err(|e| format!("Failed to cleanup stale playlists: {}", e))?;
+
+        Ok(removed)
+    }
+
+    /// Clear all cached data, returning the number of rows removed
+    pub fn clear_all(&self) -> Result<usize, String> {
+        let mut removed = 0;
+        for table in ["nostr_profiles", "nostr_tracks", "nostr_playlists", "nostr_query_cache"] {
+            removed += self
+                .conn
+                .execute(&format!("DELETE FROM {}", table), [])
+                .map_err(|e| format!("Failed to clear Nostr cache: {}", e))?;
+        }
+        Ok(removed)
     }
 }
 
@@ -709,5 +812,100 @@ pub async fn nostr_cache_clear(
     state: tauri::State<'_, NostrCacheState>,
 ) -> Result<(), String> {
     let cache = state.cache.lock().await;
-    cache.clear_all()
+    cache.clear_all().map(|_| ())
+}
+
+/// Export the whole cache as a versioned JSON document for backup
+#[tauri::command]
+pub async fn export_nostr_cache(
+    state: tauri::State<'_, NostrCacheState>,
+) -> Result<String, String> {
+    let export = state.cache.lock().await.export_all()?;
+    serde_json::to_string_pretty(&export).map_err(|e| format!("Failed to serialize Nostr cache: {}", e))
+}
+
+/// Import a backup made by `export_nostr_cache`, keeping newer cached rows
+#[tauri::command]
+pub async fn import_nostr_cache(
+    state: tauri::State<'_, NostrCacheState>,
+    json: String,
+) -> Result<NostrImportSummary, String> {
+    let export: NostrCacheExport =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid Nostr cache backup: {}", e))?;
+    let mut cache = state.cache.lock().await;
+    cache.import_all(&export)
+}
+
+#[tauri::command]
+pub fn nostr_cache_get_maintenance_config(
+    state: tauri::State<'_, NostrCacheState>,
+) -> Result<MaintenanceConfig, String> {
+    let config = state.maintenance.lock().map_err(|e| format!("Lock error: {}", e))?;
+    Ok(config.clone())
+}
+
+/// Update cleanup interval/TTLs (applies from the next cleanup cycle)
+#[tauri::command]
+pub fn nostr_cache_set_maintenance_config(
+    state: tauri::State<'_, NostrCacheState>,
+    config: MaintenanceConfig,
+) -> Result<(), String> {
+    let mut current = state.maintenance.lock().map_err(|e| format!("Lock error: {}", e))?;
+    *current = config;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(event_id: &str, fetched_at: i64) -> CachedTrack {
+        CachedTrack {
+            event_id: event_id.to_string(),
+            pubkey: "pubkey".to_string(),
+            d_tag: event_id.to_string(),
+            title: "Title".to_string(),
+            artist: "Artist".to_string(),
+            album: None,
+            url: "https://example.com/track.mp3".to_string(),
+            image: None,
+            duration: None,
+            genres: "[]".to_string(),
+            created_at: fetched_at,
+            fetched_at,
+            qobuz_track_id: None,
+            quality: None,
+            hydrated_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_task_prunes_stale_rows() {
+        let cache = NostrCache::new(Path::new(":memory:")).unwrap();
+        let now = NostrCache::current_timestamp();
+        cache.set_track(&track("stale", now - 1_000)).unwrap();
+        cache.set_track(&track("fresh", now)).unwrap();
+
+        let state = NostrCacheState::with_cache(cache);
+        {
+            let mut config = state.maintenance.lock().unwrap();
+            config.interval_secs = 1;
+            config.track_ttl_secs = 100;
+        }
+
+        let handle = tokio::spawn(state.maintenance_task());
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+
+        {
+            let cache = state.cache.lock().await;
+            assert!(cache.get_track("pubkey", "stale").unwrap().is_none());
+            assert!(cache.get_track("pubkey", "fresh").unwrap().is_some());
+        }
+
+        state.stop_maintenance();
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("maintenance task should stop on shutdown")
+            .unwrap();
+    }
 }
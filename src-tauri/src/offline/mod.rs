@@ -13,6 +13,8 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tauri::{AppHandle, Emitter};
 
+use crate::api_cache::fetch::FetchStrategy;
+
 /// Reason why the app is in offline mode
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -435,6 +437,25 @@ impl OfflineState {
             store: Arc::new(Mutex::new(OfflineStore::new()?)),
         })
     }
+
+    /// Manual offline mode is on (network state is not checked)
+    pub fn is_manual_offline(&self) -> bool {
+        self.store
+            .lock()
+            .ok()
+            .and_then(|store| store.get_settings().ok())
+            .is_some_and(|settings| settings.manual_offline_mode)
+    }
+
+    /// The strategy a cached catalog fetch uses: cache only in manual
+    /// offline mode, else the requested one
+    pub fn fetch_strategy(&self, requested: Option<FetchStrategy>) -> FetchStrategy {
+        if self.is_manual_offline() {
+            FetchStrategy::CacheOnly
+        } else {
+            requested.unwrap_or_default()
+        }
+    }
 }
 
 /// Check network connectivity by attempting to reach Qobuz API.
@@ -0,0 +1,234 @@
+//! Playback chain report
+//!
+//! A snapshot of the signal path for bug reports: every stage in the order
+//! the audio thread applies it (see `audio::gain` for the chain), with its
+//! parameters and whether it currently leaves samples untouched.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{OutputFormat, SourceFormat};
+use crate::audio::{ChannelMode, ResampleQuality, DECODED_BITS};
+use crate::config::audio_settings::AudioSettings;
+
+/// One stage of the signal path
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainStage {
+    pub name: &'static str,
+    /// The stage passes samples through unchanged
+    pub bypassed: bool,
+    pub params: Value,
+}
+
+/// The signal path from decoder to output device
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackChainReport {
+    pub track_id: u64,
+    /// DAC passthrough is enabled in the settings
+    pub dac_passthrough: bool,
+    /// Every stage between decoder and sink is bypassed and the decoder
+    /// keeps the source's full bit depth
+    pub bit_perfect: bool,
+    pub stages: Vec<ChainStage>,
+}
+
+/// Everything the report is built from
+pub(crate) struct ChainInputs<'a> {
+    pub settings: &'a AudioSettings,
+    pub track_id: u64,
+    pub source: Option<SourceFormat>,
+    pub output: Option<OutputFormat>,
+    pub resampling: Option<ResampleQuality>,
+    pub device: Option<String>,
+    pub pregain_db: f32,
+    pub replaygain_db: f32,
+    pub taps: usize,
+    pub volume: f32,
+}
+
+pub(crate) fn describe_chain(inputs: &ChainInputs) -> PlaybackChainReport {
+    let settings = inputs.settings;
+    let output = inputs.output;
+    let resampling = inputs.resampling;
+    let channel_mode = settings.effective_channel_mode();
+    let silence_trim = settings.effective_silence_trim();
+    let fade_in_ms = settings.effective_fade_in_ms();
+
+    let processing = vec![
+        ChainStage {
+            name: "silence_trim",
+            bypassed: silence_trim.is_none(),
+            params: json!(silence_trim),
+        },
+        ChainStage {
+            name: "replaygain",
+            bypassed: inputs.replaygain_db == 0.0,
+            params: json!({ "gain_db": inputs.replaygain_db }),
+        },
+        ChainStage {
+            name: "pregain",
+            bypassed: inputs.pregain_db == 0.0,
+            params: json!({ "gain_db": inputs.pregain_db, "configured_db": settings.pregain_db }),
+        },
+        ChainStage {
+            name: "resampler",
+            bypassed: resampling.is_none(),
+            params: json!({
+                "quality": resampling,
+                "from_rate": inputs.source.map(|s| s.sample_rate),
+                "to_rate": output.map(|o| o.sample_rate),
+            }),
+        },
+        ChainStage {
+            name: "channel_map",
+            bypassed: channel_mode == ChannelMode::Stereo,
+            params: json!(channel_mode),
+        },
+        ChainStage {
+            name: "fade_in",
+            bypassed: fade_in_ms == 0,
+            params: json!({ "duration_ms": fade_in_ms }),
+        },
+        ChainStage {
+            name: "taps",
+            // Taps copy the output and never alter it
+            bypassed: true,
+            params: json!({ "registered": inputs.taps }),
+        },
+        ChainStage {
+            name: "volume",
+            bypassed: inputs.volume == 1.0,
+            params: json!({ "level": inputs.volume }),
+        },
+    ];
+    // Samples carry DECODED_BITS, so deeper sources lose bits
+    let truncated = inputs
+        .source
+        .and_then(|s| s.bits_per_sample)
+        .is_some_and(|bits| bits > DECODED_BITS);
+    let bit_perfect = !truncated && processing.iter().all(|stage| stage.bypassed);
+
+    let mut stages = Vec::with_capacity(processing.len() + 2);
+    stages.push(ChainStage {
+        name: "decoder",
+        bypassed: false,
+        params: json!({
+            "format": inputs.source,
+            "output_bits": DECODED_BITS,
+            "truncated": truncated,
+        }),
+    });
+    stages.extend(processing);
+    stages.push(ChainStage {
+        name: "sink",
+        bypassed: false,
+        params: json!({
+            "device": inputs.device,
+            "backend": settings.backend_type,
+            "alsa_plugin": settings.alsa_plugin,
+            "exclusive_mode": settings.exclusive_mode,
+            "dsd_mode": settings.dsd_mode,
+            "format": output,
+        }),
+    });
+
+    PlaybackChainReport {
+        track_id: inputs.track_id,
+        dac_passthrough: settings.dac_passthrough,
+        bit_perfect,
+        stages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::SilenceTrimConfig;
+
+    fn output() -> OutputFormat {
+        OutputFormat {
+            sample_rate: 96_000,
+            channels: 2,
+            sample_format: None,
+            buffer_frames: None,
+            period_frames: None,
+            resampling: None,
+        }
+    }
+
+    fn bypassed(report: &PlaybackChainReport) -> Vec<(&'static str, bool)> {
+        report.stages.iter().map(|stage| (stage.name, stage.bypassed)).collect()
+    }
+
+    #[test]
+    fn test_chain_lists_stages_in_order_with_bypass_flags() {
+        let mut settings = AudioSettings {
+            pregain_db: -3.0,
+            fade_in_ms: 200,
+            silence_trim: Some(SilenceTrimConfig::default()),
+            ..AudioSettings::default()
+        };
+        let source = |bits| SourceFormat { sample_rate: 44_100, channels: 2, bits_per_sample: Some(bits) };
+        let inputs = |settings: &AudioSettings, resampling, volume| {
+            describe_chain(&ChainInputs {
+                settings,
+                track_id: 42,
+                source: Some(source(16)),
+                output: Some(output()),
+                resampling,
+                device: Some("hw:0".to_string()),
+                pregain_db: settings.effective_pregain_db(),
+                replaygain_db: 0.0,
+                taps: 1,
+                volume,
+            })
+        };
+
+        let report = inputs(&settings, Some(ResampleQuality::Best), 0.5);
+        assert_eq!(
+            bypassed(&report),
+            vec![
+                ("decoder", false),
+                ("silence_trim", false),
+                ("replaygain", true),
+                ("pregain", false),
+                ("resampler", false),
+                ("channel_map", true),
+                ("fade_in", false),
+                ("taps", true),
+                ("volume", false),
+                ("sink", false),
+            ]
+        );
+        assert!(!report.bit_perfect);
+        assert_eq!(report.stages[3].params["gain_db"], -3.0);
+        assert_eq!(report.stages[4].params["to_rate"], 96_000);
+
+        // Bit-perfect: passthrough, native rate and unity volume bypass every stage
+        settings.dac_passthrough = true;
+        let report = inputs(&settings, None, 1.0);
+        let flags: Vec<bool> = report.stages[1..report.stages.len() - 1]
+            .iter()
+            .map(|stage| stage.bypassed)
+            .collect();
+        assert_eq!(flags, vec![true; 8]);
+        assert!(report.bit_perfect);
+        assert_eq!(report.stages[3].params["configured_db"], -3.0);
+
+        // A 32-bit source is truncated by the decoder even with every stage bypassed
+        let report = describe_chain(&ChainInputs {
+            settings: &settings,
+            track_id: 42,
+            source: Some(source(32)),
+            output: Some(output()),
+            resampling: None,
+            device: None,
+            pregain_db: 0.0,
+            replaygain_db: 0.0,
+            taps: 0,
+            volume: 1.0,
+        });
+        assert!(!report.bit_perfect);
+        assert_eq!(report.stages[0].params["truncated"], true);
+    }
+}
@@ -0,0 +1,73 @@
+//! Whole-file track downloads
+//!
+//! Playback without streaming, prefetching, preloading and casting all
+//! download through here, so they share the timeouts, the throughput
+//! measurement behind auto quality and the handling of signed URLs that
+//! expired since they were resolved.
+
+use std::time::{Duration, Instant};
+
+use crate::api::client::QobuzClient;
+use crate::api::models::Quality;
+use crate::api::stream_urls;
+use crate::download_cache::throughput::auto_quality;
+
+/// Error prefix for a signed URL the CDN rejected as expired
+pub const EXPIRED_URL_ERROR: &str = "Stream URL expired";
+
+/// Download audio from URL
+pub async fn download_audio(url: &str) -> Result<Vec<u8>, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(120))
+        .connect_timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    log::info!("Downloading audio...");
+
+    let started = Instant::now();
+    let response = client
+        .get(url)
+        .header("User-Agent", "Mozilla/5.0")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch audio: {}", e))?;
+
+    if stream_urls::is_expired_status(response.status()) {
+        return Err(format!("{} ({})", EXPIRED_URL_ERROR, response.status()));
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+
+    log::info!("Downloaded {} bytes", bytes.len());
+    auto_quality().record(bytes.len() as u64, started.elapsed());
+    Ok(bytes.to_vec())
+}
+
+/// Download a track from the stream `url` resolved for it. A URL that
+/// expired since it was resolved is re-requested once.
+pub async fn download_track(
+    client: &QobuzClient,
+    track_id: u64,
+    quality: Quality,
+    url: &str,
+) -> Result<Vec<u8>, String> {
+    match download_audio(url).await {
+        Err(e) if e.starts_with(EXPIRED_URL_ERROR) => {
+            log::info!("Stream URL for track {} expired, requesting a new one", track_id);
+            client.invalidate_stream_url(track_id).await;
+            let fresh = client
+                .playback_stream_url(track_id, quality)
+                .await
+                .map_err(|e| format!("Failed to get stream URL: {}", e))?;
+            download_audio(&fresh.url).await
+        }
+        result => result,
+    }
+}
@@ -13,14 +13,14 @@ use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::{self, Sender, RecvTimeoutError};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::{Duration, Instant};
 use rodio::{Decoder, OutputStream, Sink, Source};
 use rodio::buffer::SamplesBuffer;
 use rodio::decoder::Mp4Type;
 use rodio::cpal::traits::{DeviceTrait, HostTrait};
-use rodio::cpal::{StreamConfig, SampleRate, BufferSize, SupportedStreamConfig, SupportedBufferSize, SampleFormat};
+use rodio::cpal::{SampleRate, SupportedStreamConfig};
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::errors::Error as SymphoniaError;
@@ -31,18 +31,39 @@ use symphonia::core::probe::Hint;
 use symphonia::default::{get_codecs, get_probe};
 
 use crate::api::{client::QobuzClient, models::Quality};
-use crate::audio::{AudioBackendType, BackendConfig, BackendManager};
+use crate::audio::{
+    cpal_buffer_size, device_buffer_range, DEFAULT_BUFFER_RANGE, device_sample_formats, negotiate_sample_format,
+    validate_output_rate, AudioConfig, BackendConfig, BackendManager,
+    AudioTap, ChannelMap, ChannelMode, FadeControl, FadeIn, Gain, GainControl, OpenedStream,
+    OutputSampleFormat, Resample, ResampleQuality, ScrubSession, ScrubSource, SilenceTrim,
+    SilenceTrimConfig, TapRegistry, Tapped,
+    SCRUB_TIMEOUT,
+};
+use crate::audio::loudness::NormalizationMode;
 use crate::config::audio_settings::AudioSettings;
 
+mod chain;
+mod download;
+mod preload;
+mod streaming;
+
+pub use chain::{ChainStage, PlaybackChainReport};
+pub use download::{download_audio, download_track, EXPIRED_URL_ERROR};
+pub use preload::{PreloadedTrack, Preloader};
+pub use streaming::{spawn_download, DownloadHandle, StreamDecoder, StreamReader};
+use streaming::{StreamControl, StreamSource};
+
 /// Commands sent to the audio thread
 enum AudioCommand {
-    /// Play audio data with track ID, duration, and audio specs
+    /// Play audio with track ID, duration, and audio specs
     Play {
-        data: Vec<u8>,
+        input: PlayInput,
         track_id: u64,
         duration_secs: u64,
         sample_rate: u32,
         channels: u16,
+        /// Source bit depth (None for lossy/unknown)
+        bits_per_sample: Option<u32>,
     },
     /// Pause playback
     Pause,
@@ -54,8 +75,22 @@ enum AudioCommand {
     SetVolume(f32),
     /// Seek to position in seconds
     Seek(u64),
+    /// Play a short snippet at a position (seconds) without seeking
+    ScrubPreview(u64),
+    /// Stop scrub snippets and restore the main sink's volume
+    EndScrub,
     /// Reinitialize audio device (releases and re-acquires)
     ReinitDevice { device_name: Option<String> },
+    /// The stream of a track finished downloading; its data enables seeking
+    StreamComplete { track_id: u64, data: Vec<u8> },
+}
+
+/// What a Play command decodes
+enum PlayInput {
+    /// A complete file
+    Data(Vec<u8>),
+    /// A download in progress, decoded as it arrives (see `streaming`)
+    Stream { source: StreamSource, control: StreamControl },
 }
 
 struct CursorMediaSource {
@@ -94,7 +129,7 @@ impl Seek for CursorMediaSource {
 
 /// Audio specifications extracted from decoded audio
 struct AudioSpecs {
-    samples: SamplesBuffer<i16>,
+    samples: SamplesBuffer<f32>,
     sample_rate: u32,
     channels: u16,
 }
@@ -128,7 +163,7 @@ fn decode_with_symphonia(data: &[u8]) -> Result<AudioSpecs, String> {
 
     let mut sample_rate = 0;
     let mut channels = 0u16;
-    let mut samples: Vec<i16> = Vec::new();
+    let mut samples: Vec<f32> = Vec::new();
 
     loop {
         let packet = match probed.format.next_packet() {
@@ -149,7 +184,7 @@ fn decode_with_symphonia(data: &[u8]) -> Result<AudioSpecs, String> {
                     channels = spec.channels.count() as u16;
                 }
 
-                let mut sample_buf = SampleBuffer::<i16>::new(audio_buf.frames() as u64, spec);
+                let mut sample_buf = SampleBuffer::<f32>::new(audio_buf.frames() as u64, spec);
                 sample_buf.copy_interleaved_ref(audio_buf);
                 samples.extend_from_slice(sample_buf.samples());
             }
@@ -181,9 +216,53 @@ fn is_isomp4(data: &[u8]) -> bool {
     &data[4..8] == b"ftyp"
 }
 
-/// Extract audio metadata (sample rate, channels) without full decode.
+/// Bits per sample from a FLAC STREAMINFO block (always the first metadata block)
+fn flac_bits_per_sample(data: &[u8]) -> Option<u32> {
+    if data.len() < 22 || &data[..4] != b"fLaC" || data[4] & 0x7f != 0 {
+        return None;
+    }
+    let info = &data[8..];
+    Some(((((info[12] & 0x01) as u32) << 4) | (info[13] >> 4) as u32) + 1)
+}
+
+/// Apply the configured silence trim to a decoded source. Leading silence
+/// is only trimmed when the track starts from the beginning.
+fn trim_silence(
+    source: Box<dyn Source<Item = f32> + Send>,
+    config: Option<SilenceTrimConfig>,
+    trim_leading: bool,
+) -> Box<dyn Source<Item = f32> + Send> {
+    match config {
+        Some(config) => Box::new(SilenceTrim::new(source, config, trim_leading)),
+        None => source,
+    }
+}
+
+/// Convert a decoded source to the output stream's rate. Returns the
+/// quality used, or None when the rates already match.
+fn resample_for_output(
+    source: Box<dyn Source<Item = f32> + Send>,
+    output_rate: Option<u32>,
+    quality: ResampleQuality,
+) -> (Box<dyn Source<Item = f32> + Send>, Option<ResampleQuality>) {
+    let Some(output_rate) = output_rate else {
+        return (source, None);
+    };
+    let source_rate = source.sample_rate();
+    match Resample::new(source, output_rate, quality) {
+        Ok(resampled) => {
+            log::info!("Resampling {}Hz -> {}Hz ({:?})", source_rate, output_rate, quality);
+            (Box::new(resampled), Some(quality))
+        }
+        // Same rate, or left to the output's own conversion
+        Err(source) => (source, None),
+    }
+}
+
+/// Extract audio metadata (sample rate, channels, bits per sample) without full decode.
 /// This is much faster than decode_with_symphonia as it only reads headers.
-fn extract_audio_metadata(data: &[u8]) -> Result<(u32, u16), String> {
+/// Bits per sample is None for lossy or unknown formats.
+pub(crate) fn extract_audio_metadata(data: &[u8]) -> Result<(u32, u16, Option<u32>), String> {
     // For non-isomp4 files (FLAC, etc.), try rodio's decoder first - it reads headers quickly
     if !is_isomp4(data) {
         let result = panic::catch_unwind(AssertUnwindSafe(|| {
@@ -191,7 +270,7 @@ fn extract_audio_metadata(data: &[u8]) -> Result<(u32, u16), String> {
         }));
 
         if let Ok(Ok(decoder)) = result {
-            return Ok((decoder.sample_rate(), decoder.channels()));
+            return Ok((decoder.sample_rate(), decoder.channels(), flac_bits_per_sample(data)));
         }
     }
 
@@ -222,17 +301,26 @@ fn extract_audio_metadata(data: &[u8]) -> Result<(u32, u16), String> {
         .map(|c| c.count() as u16)
         .ok_or_else(|| "No channel info in codec params".to_string())?;
 
-    Ok((sample_rate, channels))
+    Ok((sample_rate, channels, track.codec_params.bits_per_sample))
 }
 
-fn decode_with_fallback(
+/// Decode a whole track. Rodio's decoder only yields 16-bit samples, so
+/// sources deeper than that are decoded with symphonia to keep their depth.
+pub(crate) fn decode_with_fallback(
     data: &[u8],
-) -> Result<Box<dyn Source<Item = i16> + Send>, String> {
+) -> Result<Box<dyn Source<Item = f32> + Send>, String> {
+    if flac_bits_per_sample(data).is_some_and(|bits| bits > 16) {
+        match decode_with_symphonia(data) {
+            Ok(specs) => return Ok(Box::new(specs.samples)),
+            Err(err) => log::warn!("Symphonia decode failed, falling back to 16-bit: {}", err),
+        }
+    }
+
     if is_isomp4(data) {
         return decode_with_symphonia(data)
             .map(|specs| {
                 log::info!("Decoded audio using symphonia fallback (isomp4)");
-                Box::new(specs.samples) as Box<dyn Source<Item = i16> + Send>
+                Box::new(specs.samples) as Box<dyn Source<Item = f32> + Send>
             });
     }
 
@@ -241,7 +329,7 @@ fn decode_with_fallback(
     }));
 
     match primary {
-        Ok(Ok(decoder)) => return Ok(Box::new(decoder)),
+        Ok(Ok(decoder)) => return Ok(Box::new(decoder.convert_samples())),
         Ok(Err(err)) => {
             log::warn!("Primary decode failed, attempting mp4 fallback: {}", err);
         }
@@ -260,7 +348,7 @@ fn decode_with_fallback(
         match attempt {
             Ok(Ok(decoder)) => {
                 log::info!("Decoded audio using mp4 fallback ({})", hint_label);
-                return Ok(Box::new(decoder));
+                return Ok(Box::new(decoder.convert_samples()));
             }
             Ok(Err(err)) => {
                 log::warn!("mp4 fallback ({}) failed: {}", hint_label, err);
@@ -280,13 +368,49 @@ fn decode_with_fallback(
     }
 }
 
+/// Frames per chunk handed out by `DecodedChunks`
+const DECODED_CHUNK_FRAMES: usize = 4096;
+
+/// A whole track decoded for analysis, read in chunks of interleaved
+/// samples (whole frames, full scale = 1.0)
+pub(crate) struct DecodedChunks {
+    source: Box<dyn Source<Item = f32> + Send>,
+    chunk: Vec<f32>,
+}
+
+impl DecodedChunks {
+    pub fn new(data: &[u8]) -> Result<Self, String> {
+        let source = decode_with_fallback(data)?;
+        let chunk = Vec::with_capacity(DECODED_CHUNK_FRAMES * source.channels() as usize);
+        Ok(Self { source, chunk })
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    /// The next chunk, or None at the end of the track
+    pub fn next_chunk(&mut self) -> Option<&[f32]> {
+        let chunk_len = DECODED_CHUNK_FRAMES * self.source.channels() as usize;
+        self.chunk.clear();
+        self.chunk.extend(self.source.by_ref().take(chunk_len));
+        (!self.chunk.is_empty()).then_some(&self.chunk[..])
+    }
+}
+
 /// Create OutputStream with custom sample rate configuration
 fn create_output_stream_with_config(
     device: &rodio::cpal::Device,
     sample_rate: u32,
     channels: u16,
+    source_bits: Option<u32>,
     exclusive_mode: bool,
-) -> Result<(OutputStream, rodio::OutputStreamHandle), String> {
+    audio_config: AudioConfig,
+) -> Result<OpenedStream, String> {
     log::info!(
         "Creating OutputStream: {}Hz, {} channels, exclusive: {}",
         sample_rate,
@@ -294,59 +418,61 @@ fn create_output_stream_with_config(
         exclusive_mode
     );
 
-    // Create StreamConfig with desired sample rate
-    let config = StreamConfig {
-        channels,
-        sample_rate: SampleRate(sample_rate),
-        buffer_size: if exclusive_mode {
-            BufferSize::Fixed(512)  // Lower latency for exclusive mode
-        } else {
-            BufferSize::Default
-        },
-    };
-
     // Check if device supports this configuration
-    let supported_configs = device
+    let supported_configs: Vec<_> = device
         .supported_output_configs()
-        .map_err(|e| format!("Failed to get supported configs: {}", e))?;
-
-    let mut found_matching = false;
-    for range in supported_configs {
-        if range.channels() == channels
-            && sample_rate >= range.min_sample_rate().0
-            && sample_rate <= range.max_sample_rate().0
-        {
-            found_matching = true;
-            log::info!(
-                "Device supports {}Hz (range: {}-{}Hz)",
-                sample_rate,
-                range.min_sample_rate().0,
-                range.max_sample_rate().0
-            );
-            break;
-        }
-    }
+        .map_err(|e| format!("Failed to get supported configs: {}", e))?
+        .collect();
+
+    let ranges: Vec<(u16, u32, u32)> = supported_configs
+        .iter()
+        .map(|range| (range.channels(), range.min_sample_rate().0, range.max_sample_rate().0))
+        .collect();
 
-    if !found_matching {
+    // exclusive_mode here is DAC passthrough: refuse rates the device can't take
+    let found_matching = validate_output_rate(sample_rate, channels, &ranges, exclusive_mode)?;
+
+    if found_matching {
+        log::info!("Device supports {}Hz", sample_rate);
+    } else {
         log::warn!(
             "Device may not support {}Hz, attempting anyway",
             sample_rate
         );
     }
 
+    // Resolve buffer/period sizes against what the device reports
+    let buffer = audio_config.resolve(
+        device_buffer_range(&supported_configs, channels),
+        exclusive_mode,
+    )?;
+
+    // Pick the sample format for the source depth
+    let sample_format = negotiate_sample_format(
+        &device_sample_formats(&supported_configs, channels),
+        source_bits,
+        exclusive_mode,
+    )?;
+    let cpal_format = sample_format
+        .to_cpal()
+        .ok_or_else(|| format!("{:?} output is not supported", sample_format))?;
+
     // Create SupportedStreamConfig
     let supported_config = SupportedStreamConfig::new(
-        config.channels,
-        config.sample_rate,
-        SupportedBufferSize::Range { min: 64, max: 8192 },
-        SampleFormat::F32,
+        channels,
+        SampleRate(sample_rate),
+        DEFAULT_BUFFER_RANGE,
+        cpal_format,
     );
 
     // Create OutputStream with custom config
-    match OutputStream::try_from_device_config(device, supported_config) {
+    match OutputStream::try_from_device_config_with_buffer_size(device, supported_config, cpal_buffer_size(buffer)) {
         Ok((stream, handle)) => {
-            log::info!("✅ OutputStream created successfully at {}Hz", sample_rate);
-            Ok((stream, handle))
+            log::info!(
+                "✅ OutputStream created successfully at {}Hz/{:?} (buffer: {:?})",
+                sample_rate, sample_format, buffer
+            );
+            Ok(OpenedStream { stream, handle, buffer, sample_format })
         }
         Err(e) => {
             log::error!("❌ Failed to create OutputStream at {}Hz: {}", sample_rate, e);
@@ -361,7 +487,8 @@ fn try_init_stream_with_backend(
     audio_settings: &AudioSettings,
     sample_rate: u32,
     channels: u16,
-) -> Option<Result<(OutputStream, rodio::OutputStreamHandle), String>> {
+    source_bits: Option<u32>,
+) -> Option<Result<OpenedStream, String>> {
     // Check if backend system is configured
     let backend_type = audio_settings.backend_type?;
 
@@ -394,8 +521,12 @@ fn try_init_stream_with_backend(
         device_id: audio_settings.output_device.clone(),
         sample_rate,
         channels,
+        source_bits,
         exclusive_mode: audio_settings.exclusive_mode,
         alsa_plugin: audio_settings.alsa_plugin,
+        bit_perfect: audio_settings.dac_passthrough,
+        dsd_mode: audio_settings.dsd_mode,
+        audio_config: audio_settings.audio_config(),
     };
 
     // Create output stream via backend
@@ -416,9 +547,44 @@ fn try_init_stream_with_backend(
 pub struct PlaybackEvent {
     pub is_playing: bool,
     pub position: u64,
+    /// Position in milliseconds, measured at `measured_at_monotonic_ms`
+    pub position_ms: u64,
+    /// `monotonic_millis` at the time of measurement
+    pub measured_at_monotonic_ms: u64,
     pub duration: u64,
     pub track_id: u64,
     pub volume: f32,
+    /// Waiting for the download to catch up (see `PlaybackState::is_buffering`)
+    pub is_buffering: bool,
+}
+
+/// Reference point of `monotonic_millis`: an instant and the Unix time in
+/// milliseconds at that instant
+static MONOTONIC_EPOCH: OnceLock<(Instant, u64)> = OnceLock::new();
+
+/// Milliseconds on a monotonic clock (unaffected by wall-clock changes).
+/// It starts at the Unix time of the first call in this process, so the
+/// frontend can compare it with `Date.now()`.
+pub fn monotonic_millis() -> u64 {
+    let (instant, unix_ms) = MONOTONIC_EPOCH.get_or_init(|| {
+        let unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        (Instant::now(), unix_ms)
+    });
+    unix_ms + instant.elapsed().as_millis() as u64
+}
+
+/// Playback position together with when it was measured, so a UI can
+/// extrapolate between updates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct PositionSample {
+    pub position_ms: u64,
+    /// `monotonic_millis` at the time of measurement
+    pub measured_at_monotonic_ms: u64,
+    /// The position moves with the clock (playing and not at the end)
+    pub is_advancing: bool,
 }
 
 /// Shared state between main thread and audio thread
@@ -426,22 +592,74 @@ pub struct PlaybackEvent {
 pub struct SharedState {
     /// Is currently playing
     is_playing: Arc<AtomicBool>,
-    /// Current position in seconds
-    position: Arc<AtomicU64>,
+    /// Current position in milliseconds
+    position_ms: Arc<AtomicU64>,
     /// Total duration in seconds
     duration: Arc<AtomicU64>,
     /// Current track ID
     current_track_id: Arc<AtomicU64>,
     /// Volume (0.0 - 1.0 stored as 0-100)
     volume: Arc<AtomicU64>,
-    /// Playback start time (Unix timestamp millis when started/resumed)
+    /// Playback start time (`monotonic_millis` when started/resumed, 0 when stopped)
     playback_start_millis: Arc<AtomicU64>,
-    /// Position when playback was started/resumed (in seconds)
-    position_at_start: Arc<AtomicU64>,
+    /// Position when playback was started/resumed (in milliseconds)
+    position_at_start_ms: Arc<AtomicU64>,
     /// Current output device name
     current_device: Arc<std::sync::RwLock<Option<String>>>,
     /// Stream error flag (set when ALSA/audio errors are detected)
     stream_error: Arc<AtomicBool>,
+    /// Requested and delivered quality of the current stream (None for cached/local playback)
+    stream_quality: Arc<std::sync::RwLock<(Option<Quality>, Option<Quality>)>>,
+    /// The current stream is only a preview clip
+    stream_preview: Arc<AtomicBool>,
+    /// Format and buffering of the most recently opened output stream
+    output_format: Arc<std::sync::RwLock<Option<OutputFormat>>>,
+    /// Resampler converting the current track, kept apart from the output
+    /// format so it survives streams opened at the device default
+    resampling: Arc<std::sync::RwLock<Option<ResampleQuality>>>,
+    /// Decoded format of the current track
+    source_format: Arc<std::sync::RwLock<Option<SourceFormat>>>,
+    /// The current track is playing from a download in progress
+    streaming: Arc<AtomicBool>,
+    /// The streamed track ran out of downloaded audio and is playing silence
+    buffering: Arc<AtomicBool>,
+}
+
+/// Format the output stream was opened with
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// PCM sample format (None = device default)
+    pub sample_format: Option<OutputSampleFormat>,
+    /// Effective buffer/period sizes in frames (None = device default)
+    pub buffer_frames: Option<u32>,
+    pub period_frames: Option<u32>,
+    /// Resampler converting the current track to the output rate (None = not resampling)
+    pub resampling: Option<ResampleQuality>,
+}
+
+/// Format the current track decodes to
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct SourceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// None for lossy/unknown
+    pub bits_per_sample: Option<u32>,
+}
+
+impl OutputFormat {
+    fn new(sample_rate: u32, channels: u16, opened: Option<&OpenedStream>) -> Self {
+        let buffer = opened.and_then(|o| o.buffer);
+        Self {
+            sample_rate,
+            channels,
+            sample_format: opened.map(|o| o.sample_format),
+            buffer_frames: buffer.map(|b| b.buffer_frames),
+            period_frames: buffer.map(|b| b.period_frames),
+            resampling: None,
+        }
+    }
 }
 
 impl Default for SharedState {
@@ -454,14 +672,21 @@ impl SharedState {
     pub fn new() -> Self {
         Self {
             is_playing: Arc::new(AtomicBool::new(false)),
-            position: Arc::new(AtomicU64::new(0)),
+            position_ms: Arc::new(AtomicU64::new(0)),
             duration: Arc::new(AtomicU64::new(0)),
             current_track_id: Arc::new(AtomicU64::new(0)),
             volume: Arc::new(AtomicU64::new(75)),
             playback_start_millis: Arc::new(AtomicU64::new(0)),
-            position_at_start: Arc::new(AtomicU64::new(0)),
+            position_at_start_ms: Arc::new(AtomicU64::new(0)),
             current_device: Arc::new(std::sync::RwLock::new(None)),
             stream_error: Arc::new(AtomicBool::new(false)),
+            stream_quality: Arc::new(std::sync::RwLock::new((None, None))),
+            stream_preview: Arc::new(AtomicBool::new(false)),
+            output_format: Arc::new(std::sync::RwLock::new(None)),
+            resampling: Arc::new(std::sync::RwLock::new(None)),
+            source_format: Arc::new(std::sync::RwLock::new(None)),
+            streaming: Arc::new(AtomicBool::new(false)),
+            buffering: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -473,6 +698,57 @@ impl SharedState {
         self.stream_error.load(Ordering::SeqCst)
     }
 
+    /// Record the requested and delivered quality for the track being played
+    pub fn set_stream_quality(&self, requested: Option<Quality>, delivered: Option<Quality>) {
+        if let Ok(mut q) = self.stream_quality.write() {
+            *q = (requested, delivered);
+        }
+    }
+
+    pub fn stream_quality(&self) -> (Option<Quality>, Option<Quality>) {
+        self.stream_quality.read().map(|q| *q).unwrap_or((None, None))
+    }
+
+    pub fn set_stream_preview(&self, preview: bool) {
+        self.stream_preview.store(preview, Ordering::SeqCst);
+    }
+
+    pub fn is_stream_preview(&self) -> bool {
+        self.stream_preview.load(Ordering::SeqCst)
+    }
+
+    pub fn set_output_format(&self, format: Option<OutputFormat>) {
+        if let Ok(mut f) = self.output_format.write() {
+            *f = format;
+        }
+    }
+
+    /// Record whether the current track is resampled to the output rate
+    pub fn set_resampling(&self, quality: Option<ResampleQuality>) {
+        if let Ok(mut r) = self.resampling.write() {
+            *r = quality;
+        }
+    }
+
+    pub fn resampling(&self) -> Option<ResampleQuality> {
+        self.resampling.read().ok().and_then(|r| *r)
+    }
+
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        let format = self.output_format.read().ok().and_then(|f| *f)?;
+        Some(OutputFormat { resampling: self.resampling(), ..format })
+    }
+
+    fn set_source_format(&self, format: Option<SourceFormat>) {
+        if let Ok(mut f) = self.source_format.write() {
+            *f = format;
+        }
+    }
+
+    pub fn source_format(&self) -> Option<SourceFormat> {
+        self.source_format.read().ok().and_then(|f| *f)
+    }
+
     pub fn set_current_device(&self, device: Option<String>) {
         if let Ok(mut d) = self.current_device.write() {
             *d = device;
@@ -485,43 +761,51 @@ impl SharedState {
 
     /// Get current position based on elapsed time since playback started
     pub fn current_position(&self) -> u64 {
+        self.position_sample().position_ms / 1000
+    }
+
+    /// Current position in milliseconds and the monotonic time it was
+    /// measured at
+    pub fn position_sample(&self) -> PositionSample {
+        let measured_at = monotonic_millis();
+        let paused = PositionSample {
+            position_ms: self.position_ms.load(Ordering::SeqCst),
+            measured_at_monotonic_ms: measured_at,
+            is_advancing: false,
+        };
         if !self.is_playing.load(Ordering::SeqCst) {
-            return self.position.load(Ordering::SeqCst);
+            return paused;
         }
 
         let start_millis = self.playback_start_millis.load(Ordering::SeqCst);
         if start_millis == 0 {
-            return self.position.load(Ordering::SeqCst);
+            return paused;
         }
 
-        let now_millis = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        let elapsed_secs = (now_millis.saturating_sub(start_millis)) / 1000;
-        let position_at_start = self.position_at_start.load(Ordering::SeqCst);
-        let duration = self.duration.load(Ordering::SeqCst);
+        let elapsed_ms = measured_at.saturating_sub(start_millis);
+        let position_at_start_ms = self.position_at_start_ms.load(Ordering::SeqCst);
+        let duration_ms = self.duration.load(Ordering::SeqCst) * 1000;
 
         // Clamp to duration
-        (position_at_start + elapsed_secs).min(duration)
+        let position_ms = (position_at_start_ms + elapsed_ms).min(duration_ms);
+        PositionSample {
+            position_ms,
+            measured_at_monotonic_ms: measured_at,
+            is_advancing: position_ms < duration_ms,
+        }
     }
 
-    /// Mark playback as started/resumed at current position
-    fn start_playback_timer(&self, position: u64) {
-        let now_millis = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        self.playback_start_millis.store(now_millis, Ordering::SeqCst);
-        self.position_at_start.store(position, Ordering::SeqCst);
+    /// Mark playback as started/resumed at `position_ms`
+    fn start_playback_timer(&self, position_ms: u64) {
+        // 0 is reserved for "not running"
+        self.playback_start_millis.store(monotonic_millis().max(1), Ordering::SeqCst);
+        self.position_at_start_ms.store(position_ms, Ordering::SeqCst);
     }
 
     /// Mark playback as paused, saving current position
     fn pause_playback_timer(&self) {
-        let current_pos = self.current_position();
-        self.position.store(current_pos, Ordering::SeqCst);
+        let current_ms = self.position_sample().position_ms;
+        self.position_ms.store(current_ms, Ordering::SeqCst);
         self.playback_start_millis.store(0, Ordering::SeqCst);
     }
 
@@ -529,8 +813,32 @@ impl SharedState {
         self.is_playing.load(Ordering::SeqCst)
     }
 
+    /// Whether the current track is still being streamed
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.load(Ordering::SeqCst)
+    }
+
+    /// Whether the streamed track is waiting for the download to catch up
+    pub fn is_buffering(&self) -> bool {
+        self.buffering.load(Ordering::SeqCst)
+    }
+
+    /// Mark the streamed track as stalled on the network, or flowing again.
+    /// The position holds still while it is stalled.
+    fn set_buffering(&self, buffering: bool) {
+        if self.buffering.swap(buffering, Ordering::SeqCst) == buffering || !self.is_playing() {
+            return;
+        }
+        if buffering {
+            self.pause_playback_timer();
+        } else {
+            self.start_playback_timer(self.position_ms.load(Ordering::SeqCst));
+        }
+    }
+
+    /// Position saved at the last pause or seek, in seconds
     pub fn position(&self) -> u64 {
-        self.position.load(Ordering::SeqCst)
+        self.position_ms.load(Ordering::SeqCst) / 1000
     }
 
     pub fn duration(&self) -> u64 {
@@ -555,6 +863,14 @@ pub struct Player {
     pub state: SharedState,
     /// Audio settings (exclusive mode, DAC passthrough, etc.)
     audio_settings: Arc<Mutex<AudioSettings>>,
+    /// Seek bar drag in progress
+    scrub: Arc<Mutex<ScrubSession>>,
+    /// External consumers of the output PCM
+    taps: TapRegistry,
+    /// Selected track downloaded ahead of play
+    preloader: Preloader,
+    /// Pre-gain/ReplayGain applied to every track (see `audio::gain`)
+    gain: GainControl,
 }
 
 impl Default for Player {
@@ -563,6 +879,23 @@ impl Default for Player {
     }
 }
 
+#[cfg(test)]
+impl Player {
+    /// A player without its audio thread, for tests of its own state
+    pub(crate) fn detached() -> Self {
+        let (tx, _rx) = mpsc::channel();
+        Self {
+            tx,
+            state: SharedState::new(),
+            audio_settings: Arc::new(Mutex::new(AudioSettings::default())),
+            scrub: Arc::new(Mutex::new(ScrubSession::default())),
+            taps: TapRegistry::default(),
+            preloader: Preloader::default(),
+            gain: GainControl::new(),
+        }
+    }
+}
+
 impl Player {
     /// Create a new player with an optional specific output device and audio settings
     /// If device_name is None, uses the system default device
@@ -574,6 +907,11 @@ impl Player {
         // Clone settings for thread
         let settings = Arc::new(Mutex::new(audio_settings.clone()));
         let thread_settings = settings.clone();
+        let taps = TapRegistry::default();
+        let thread_taps = taps.clone();
+        let gain = GainControl::new();
+        gain.set_pregain_db(audio_settings.effective_pregain_db());
+        let thread_gain = gain.clone();
 
         // Spawn dedicated audio thread
         thread::spawn(move || {
@@ -591,17 +929,18 @@ impl Player {
 
             // Helper to find and initialize audio device
             // Try backend system first, fall back to legacy CPAL
-            // Takes desired sample_rate and channels to maintain DAC passthrough
-            let init_device = |name: &Option<String>, state: &SharedState, sample_rate: u32, channels: u16| -> Option<(OutputStream, rodio::OutputStreamHandle)> {
+            // Takes desired sample_rate, channels and source bit depth to maintain DAC passthrough
+            let init_device = |name: &Option<String>, state: &SharedState, sample_rate: u32, channels: u16, source_bits: Option<u32>| -> Option<(OutputStream, rodio::OutputStreamHandle)> {
                 // Try backend system if configured
                 if let Ok(settings) = thread_settings.lock() {
                     if settings.backend_type.is_some() {
                         // Use provided sample rate/channels to maintain DAC passthrough
                         log::info!("Initializing backend system with {}Hz/{}ch", sample_rate, channels);
-                        match try_init_stream_with_backend(&settings, sample_rate, channels) {
-                            Some(Ok(stream)) => {
+                        match try_init_stream_with_backend(&settings, sample_rate, channels, source_bits) {
+                            Some(Ok(opened)) => {
                                 log::info!("Audio output initialized via backend system at {}Hz", sample_rate);
-                                return Some(stream);
+                                state.set_output_format(Some(OutputFormat::new(sample_rate, channels, Some(&opened))));
+                                return Some((opened.stream, opened.handle));
                             }
                             Some(Err(e)) => {
                                 log::warn!("Backend system init failed: {}, falling back to legacy", e);
@@ -659,6 +998,7 @@ impl Player {
                 match OutputStream::try_from_device(&device) {
                     Ok(s) => {
                         log::info!("Audio output initialized successfully");
+                        state.set_output_format(Some(OutputFormat::new(sample_rate, channels, None)));
                         Some(s)
                     }
                     Err(e) => {
@@ -666,6 +1006,7 @@ impl Player {
                         match OutputStream::try_default() {
                             Ok(s) => {
                                 log::info!("Fallback to default audio output succeeded");
+                                state.set_output_format(None);
                                 Some(s)
                             }
                             Err(e2) => {
@@ -683,6 +1024,7 @@ impl Player {
             let mut stream_opt: Option<(OutputStream, rodio::OutputStreamHandle)> = None;
             let mut current_sample_rate: Option<u32> = None;
             let mut current_channels: Option<u16> = None;
+            let mut current_bits: Option<u32> = None;
 
             const MAX_INIT_RETRIES: u32 = 5;
             const RETRY_DELAY_MS: u64 = 500;
@@ -697,6 +1039,41 @@ impl Player {
             const PAUSE_SUSPEND_DELAY_MS: u64 = 2000;
             let mut pause_suspend_deadline: Option<Instant> = None;
             let mut last_empty_check = Instant::now();
+            // Fades in the current source on play/resume
+            let fade = FadeControl::new();
+            let fade_in_ms = || {
+                thread_settings
+                    .lock()
+                    .map(|s| s.effective_fade_in_ms())
+                    .unwrap_or(0)
+            };
+            // Layout stereo content is mapped to (mono, multichannel pair)
+            let channel_mode = || {
+                thread_settings
+                    .lock()
+                    .map(|s| s.effective_channel_mode())
+                    .unwrap_or_default()
+            };
+            // Converter used when a track's rate differs from the stream's
+            let resample_quality = || {
+                thread_settings
+                    .lock()
+                    .map(|s| s.resample_quality)
+                    .unwrap_or_default()
+            };
+            // Leading/trailing silence skipped between tracks
+            let silence_trim = || {
+                thread_settings
+                    .lock()
+                    .ok()
+                    .and_then(|s| s.effective_silence_trim())
+            };
+            // Plays scrub snippets alongside the (muted) main sink
+            let mut scrub_sink: Option<Sink> = None;
+            // The current track decoded once per drag for the snippets
+            let mut scrub_source: Option<ScrubSource> = None;
+            // Seeks the current track while it is still streaming
+            let mut current_stream: Option<StreamControl> = None;
 
             log::info!("Audio thread ready and waiting for commands");
 
@@ -708,9 +1085,10 @@ impl Player {
                                       consecutive_sink_failures: &mut u32,
                                       pause_suspend_deadline: &mut Option<Instant>,
                                       current_sample_rate: &mut Option<u32>,
-                                      current_channels: &mut Option<u16>| {
+                                      current_channels: &mut Option<u16>,
+                                      current_bits: &mut Option<u32>| {
                 match command {
-                    AudioCommand::Play { data, track_id, duration_secs, sample_rate, channels } => {
+                    AudioCommand::Play { input, track_id, duration_secs, sample_rate, channels, bits_per_sample } => {
                         log::info!(
                             "Audio thread: playing track {} ({}Hz, {} channels)",
                             track_id,
@@ -718,6 +1096,13 @@ impl Player {
                             channels
                         );
                         *pause_suspend_deadline = None;
+                        // A drag in progress was over the previous track
+                        scrub_source = None;
+                        thread_state.set_source_format(Some(SourceFormat {
+                            sample_rate,
+                            channels,
+                            bits_per_sample,
+                        }));
 
                         // Get DAC passthrough setting
                         let dac_passthrough = thread_settings
@@ -726,12 +1111,25 @@ impl Player {
                             .map(|s| s.dac_passthrough)
                             .unwrap_or(false);
 
+                        // Channels the output is opened with (differs from the
+                        // source when stereo is downmixed to mono or placed on a
+                        // multichannel device)
+                        let mode = channel_mode();
+                        let channels = mode.stream_channels(channels);
+
                         // Check if we need to recreate the stream
-                        // Only recreate on format change if DAC passthrough is enabled
+                        // Only recreate on format change if DAC passthrough is enabled,
+                        // or when entering/leaving a mono or multichannel layout
                         let format_changed = *current_sample_rate != Some(sample_rate)
-                            || *current_channels != Some(channels);
+                            || *current_channels != Some(channels)
+                            || *current_bits != bits_per_sample;
+                        let layout_changed = *current_channels != Some(channels)
+                            && (channels > 2
+                                || matches!(mode, ChannelMode::Mono { .. })
+                                || current_channels.is_some_and(|c| c != 2));
                         let needs_new_stream = stream_opt.is_none()
-                            || (dac_passthrough && format_changed);
+                            || (dac_passthrough && format_changed)
+                            || layout_changed;
 
                         if needs_new_stream {
                             if stream_opt.is_some() {
@@ -743,6 +1141,12 @@ impl Player {
                                         sample_rate,
                                         channels
                                     );
+                                } else if layout_changed {
+                                    log::info!(
+                                        "Channel layout changed from {:?}ch to {}ch - recreating OutputStream",
+                                        *current_channels,
+                                        channels
+                                    );
                                 } else {
                                     log::info!("Creating initial OutputStream");
                                 }
@@ -795,7 +1199,7 @@ impl Player {
 
                             // Try backend system first (if configured), then fall back to legacy CPAL
                             let stream_result = if let Some(settings) = thread_settings.lock().ok() {
-                                match try_init_stream_with_backend(&settings, sample_rate, channels) {
+                                match try_init_stream_with_backend(&settings, sample_rate, channels, bits_per_sample) {
                                     Some(result) => result,
                                     None => {
                                         // Backend system not configured, use legacy CPAL path
@@ -804,7 +1208,9 @@ impl Player {
                                             &device,
                                             sample_rate,
                                             channels,
+                                            bits_per_sample,
                                             dac_passthrough,
+                                            settings.audio_config(),
                                         )
                                     }
                                 }
@@ -814,19 +1220,30 @@ impl Player {
                                     &device,
                                     sample_rate,
                                     channels,
+                                    bits_per_sample,
                                     dac_passthrough,
+                                    AudioConfig::default(),
                                 )
                             };
 
                             // Handle stream creation result
                             match stream_result {
-                                Ok(stream) => {
-                                    *stream_opt = Some(stream);
+                                Ok(opened) => {
+                                    thread_state.set_output_format(Some(OutputFormat::new(sample_rate, channels, Some(&opened))));
+                                    *stream_opt = Some((opened.stream, opened.handle));
                                     *current_sample_rate = Some(sample_rate);
                                     *current_channels = Some(channels);
+                                    *current_bits = bits_per_sample;
                                     thread_state.set_stream_error(false);
                                     log::info!("✅ Audio stream ready at {}Hz", sample_rate);
                                 }
+                                Err(e) if dac_passthrough => {
+                                    // Falling back to the default config would resample,
+                                    // which defeats bit-perfect output
+                                    log::error!("❌ Bit-perfect stream at {}Hz unavailable: {}", sample_rate, e);
+                                    thread_state.set_stream_error(true);
+                                    return;
+                                }
                                 Err(e) => {
                                     log::error!("❌ Failed to create stream at {}Hz: {}", sample_rate, e);
                                     log::warn!("Attempting fallback to default device config...");
@@ -835,9 +1252,11 @@ impl Player {
                                     match OutputStream::try_from_device(&device) {
                                         Ok(stream) => {
                                             log::warn!("Using default device config as fallback");
+                                            thread_state.set_output_format(None);
                                             *stream_opt = Some(stream);
                                             *current_sample_rate = Some(sample_rate);
                                             *current_channels = Some(channels);
+                                            *current_bits = bits_per_sample;
                                             thread_state.set_stream_error(false);
                                         }
                                         Err(e2) => {
@@ -869,7 +1288,14 @@ impl Player {
                             sink.stop();
                         }
 
-                        *current_audio_data = Some(data.clone());
+                        *current_audio_data = match &input {
+                            PlayInput::Data(data) => Some(data.clone()),
+                            PlayInput::Stream { .. } => None,
+                        };
+                        thread_state
+                            .streaming
+                            .store(matches!(input, PlayInput::Stream { .. }), Ordering::SeqCst);
+                        thread_state.buffering.store(false, Ordering::SeqCst);
 
                         let sink = match Sink::try_new(&stream.1) {
                             Ok(s) => {
@@ -898,7 +1324,7 @@ impl Player {
                                     // Use last known sample rate/channels to maintain DAC passthrough
                                     let sr = current_sample_rate.unwrap_or(48000);
                                     let ch = current_channels.unwrap_or(2);
-                                    *stream_opt = init_device(current_device_name, &thread_state, sr, ch);
+                                    *stream_opt = init_device(current_device_name, &thread_state, sr, ch, *current_bits);
                                     if stream_opt.is_some() {
                                         log::info!("Audio stream auto-reinitialized successfully at {}Hz", sr);
                                         *consecutive_sink_failures = 0;
@@ -916,11 +1342,20 @@ impl Player {
                         let volume = thread_state.volume.load(Ordering::SeqCst) as f32 / 100.0;
                         sink.set_volume(volume);
 
-                        let source = match decode_with_fallback(&data) {
-                            Ok(s) => s,
-                            Err(e) => {
-                                log::error!("Failed to decode audio: {}", e);
-                                return;
+                        let source: Box<dyn Source<Item = f32> + Send> = match input {
+                            PlayInput::Data(data) => {
+                                current_stream = None;
+                                match decode_with_fallback(&data) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        log::error!("Failed to decode audio: {}", e);
+                                        return;
+                                    }
+                                }
+                            }
+                            PlayInput::Stream { source, control } => {
+                                current_stream = Some(control);
+                                Box::new(source)
                             }
                         };
 
@@ -930,10 +1365,21 @@ impl Player {
                             .unwrap_or(duration_secs);
                         thread_state.duration.store(actual_duration, Ordering::SeqCst);
 
-                        sink.append(source);
+                        let source = trim_silence(source, silence_trim(), true);
+                        let source = Box::new(Gain::new(source, thread_gain.clone()));
+                        let (source, resampling) = resample_for_output(source, *current_sample_rate, resample_quality());
+                        thread_state.set_resampling(resampling);
+                        fade.trigger(fade_in_ms());
+                        sink.append(Tapped::new(
+                            FadeIn::new(
+                                ChannelMap::new(source, channel_mode()),
+                                fade.clone(),
+                            ),
+                            thread_taps.clone(),
+                        ));
 
                         thread_state.is_playing.store(true, Ordering::SeqCst);
-                        thread_state.position.store(0, Ordering::SeqCst);
+                        thread_state.position_ms.store(0, Ordering::SeqCst);
                         thread_state.current_track_id.store(track_id, Ordering::SeqCst);
                         thread_state.start_playback_timer(0);
 
@@ -951,8 +1397,8 @@ impl Player {
                             *pause_suspend_deadline =
                                 Some(Instant::now() + Duration::from_millis(PAUSE_SUSPEND_DELAY_MS));
                             log::info!(
-                                "Audio thread: paused at {}s",
-                                thread_state.position.load(Ordering::SeqCst)
+                                "Audio thread: paused at {}ms",
+                                thread_state.position_ms.load(Ordering::SeqCst)
                             );
                         }
                     }
@@ -969,7 +1415,7 @@ impl Player {
                                 let sr = current_sample_rate.unwrap_or(48000);
                                 let ch = current_channels.unwrap_or(2);
                                 log::info!("Resume: reinitializing stream at {}Hz/{}ch", sr, ch);
-                                *stream_opt = init_device(current_device_name, &thread_state, sr, ch);
+                                *stream_opt = init_device(current_device_name, &thread_state, sr, ch, *current_bits);
                             }
 
                             let Some(ref stream) = *stream_opt else {
@@ -996,39 +1442,60 @@ impl Player {
                                 }
                             };
 
-                            let resume_pos = thread_state.position.load(Ordering::SeqCst);
-                            let skipped_source: Box<dyn Source<Item = i16> + Send> = if resume_pos > 0 {
-                                Box::new(source.skip_duration(Duration::from_secs(resume_pos)))
+                            let resume_ms = thread_state.position_ms.load(Ordering::SeqCst);
+                            let skipped_source: Box<dyn Source<Item = f32> + Send> = if resume_ms > 0 {
+                                Box::new(source.skip_duration(Duration::from_millis(resume_ms)))
                             } else {
                                 source
                             };
-
-                            sink.append(skipped_source);
-                            thread_state.start_playback_timer(resume_pos);
+                            let skipped_source = trim_silence(skipped_source, silence_trim(), resume_ms == 0);
+                            let skipped_source = Box::new(Gain::new(skipped_source, thread_gain.clone()));
+                            let (skipped_source, resampling) =
+                                resample_for_output(skipped_source, *current_sample_rate, resample_quality());
+                            thread_state.set_resampling(resampling);
+
+                            fade.trigger(fade_in_ms());
+                            sink.append(Tapped::new(
+                                FadeIn::new(
+                                    ChannelMap::new(skipped_source, channel_mode()),
+                                    fade.clone(),
+                                ),
+                                thread_taps.clone(),
+                            ));
+                            thread_state.start_playback_timer(resume_ms);
                             thread_state.is_playing.store(true, Ordering::SeqCst);
                             *current_sink = Some(sink);
 
-                            log::info!("Audio thread: resumed from {}s", resume_pos);
+                            log::info!("Audio thread: resumed from {}ms", resume_ms);
                             return;
                         }
 
                         if let Some(ref sink) = *current_sink {
+                            fade.trigger(fade_in_ms());
                             sink.play();
-                            let current_pos = thread_state.position.load(Ordering::SeqCst);
-                            thread_state.start_playback_timer(current_pos);
+                            let current_ms = thread_state.position_ms.load(Ordering::SeqCst);
+                            thread_state.start_playback_timer(current_ms);
                             thread_state.is_playing.store(true, Ordering::SeqCst);
                             log::info!("Audio thread: resumed");
                         }
                     }
                     AudioCommand::Stop => {
+                        if let Some(sink) = scrub_sink.take() {
+                            sink.stop();
+                        }
+                        scrub_source = None;
                         if let Some(sink) = current_sink.take() {
                             sink.stop();
                         }
                         *current_audio_data = None;
+                        current_stream = None;
+                        thread_state.set_source_format(None);
+                        thread_state.streaming.store(false, Ordering::SeqCst);
+                        thread_state.buffering.store(false, Ordering::SeqCst);
                         thread_state.is_playing.store(false, Ordering::SeqCst);
-                        thread_state.position.store(0, Ordering::SeqCst);
+                        thread_state.position_ms.store(0, Ordering::SeqCst);
                         thread_state.playback_start_millis.store(0, Ordering::SeqCst);
-                        thread_state.position_at_start.store(0, Ordering::SeqCst);
+                        thread_state.position_at_start_ms.store(0, Ordering::SeqCst);
                         // Drop the stream to release the device and stop background CPU use.
                         drop(stream_opt.take());
                         *pause_suspend_deadline = None;
@@ -1045,6 +1512,17 @@ impl Player {
                     }
                     AudioCommand::Seek(position_secs) => {
                         *pause_suspend_deadline = None;
+                        // A track still downloading seeks within the download; its sink keeps playing
+                        if let Some(ref control) = current_stream {
+                            log::info!("Audio thread: seeking stream to {}s", position_secs);
+                            control.seek(Duration::from_secs(position_secs));
+                            thread_state.position_ms.store(position_secs * 1000, Ordering::SeqCst);
+                            if thread_state.is_playing() {
+                                thread_state.start_playback_timer(position_secs * 1000);
+                            }
+                            return;
+                        }
+
                         let Some(ref audio_data) = *current_audio_data else {
                             log::warn!("Audio thread: cannot seek - no audio data available");
                             return;
@@ -1081,18 +1559,32 @@ impl Player {
                         };
 
                         let skip_duration = Duration::from_secs(position_secs);
-                        let skipped_source = source.skip_duration(skip_duration);
-
-                        sink.append(skipped_source);
+                        let skipped_source = trim_silence(
+                            Box::new(source.skip_duration(skip_duration)),
+                            silence_trim(),
+                            false,
+                        );
+                        let skipped_source = Box::new(Gain::new(skipped_source, thread_gain.clone()));
+                        let (skipped_source, resampling) =
+                            resample_for_output(skipped_source, *current_sample_rate, resample_quality());
+                        thread_state.set_resampling(resampling);
+
+                        sink.append(Tapped::new(
+                            FadeIn::new(
+                                ChannelMap::new(skipped_source, channel_mode()),
+                                fade.clone(),
+                            ),
+                            thread_taps.clone(),
+                        ));
 
                         let was_playing = thread_state.is_playing.load(Ordering::SeqCst);
                         if !was_playing {
                             sink.pause();
                         }
 
-                        thread_state.position.store(position_secs, Ordering::SeqCst);
+                        thread_state.position_ms.store(position_secs * 1000, Ordering::SeqCst);
                         if was_playing {
-                            thread_state.start_playback_timer(position_secs);
+                            thread_state.start_playback_timer(position_secs * 1000);
                         }
 
                         *current_sink = Some(sink);
@@ -1102,6 +1594,72 @@ impl Player {
                             was_playing
                         );
                     }
+                    AudioCommand::ScrubPreview(position_secs) => {
+                        let dac_passthrough = thread_settings
+                            .lock()
+                            .map(|s| s.dac_passthrough)
+                            .unwrap_or(false);
+                        if dac_passthrough {
+                            log::debug!("Audio thread: scrub preview disabled in bit-perfect mode");
+                            return;
+                        }
+
+                        let (Some(audio_data), Some(stream)) = (&*current_audio_data, &*stream_opt) else {
+                            log::debug!("Audio thread: nothing to scrub");
+                            return;
+                        };
+
+                        if let Some(sink) = scrub_sink.take() {
+                            sink.stop();
+                        }
+
+                        if scrub_source.is_none() {
+                            match decode_with_fallback(audio_data) {
+                                Ok(source) => {
+                                    let source = Box::new(ChannelMap::new(source, channel_mode()));
+                                    scrub_source = Some(ScrubSource::new(source));
+                                }
+                                Err(e) => {
+                                    log::error!("Failed to decode audio for scrub preview: {}", e);
+                                    return;
+                                }
+                            }
+                        }
+                        let Some(snippet) = scrub_source
+                            .as_mut()
+                            .and_then(|source| source.snippet(Duration::from_secs(position_secs)))
+                        else {
+                            log::debug!("Audio thread: track can't be scrubbed (not seekable)");
+                            return;
+                        };
+
+                        let sink = match Sink::try_new(&stream.1) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                log::error!("Failed to create sink for scrub preview: {}", e);
+                                return;
+                            }
+                        };
+
+                        // Keep the main sink running (so the position stays
+                        // consistent) but silent while scrubbing
+                        if let Some(ref main) = *current_sink {
+                            main.set_volume(0.0);
+                        }
+
+                        sink.set_volume(thread_state.volume());
+                        sink.append(snippet);
+                        scrub_sink = Some(sink);
+                    }
+                    AudioCommand::EndScrub => {
+                        if let Some(sink) = scrub_sink.take() {
+                            sink.stop();
+                        }
+                        scrub_source = None;
+                        if let Some(ref main) = *current_sink {
+                            main.set_volume(thread_state.volume());
+                        }
+                    }
                     AudioCommand::ReinitDevice { device_name: new_device } => {
                         log::info!(
                             "Audio thread: reinitializing device (new: {:?})",
@@ -1109,6 +1667,10 @@ impl Player {
                         );
                         *pause_suspend_deadline = None;
 
+                        if let Some(sink) = scrub_sink.take() {
+                            sink.stop();
+                        }
+                        scrub_source = None;
                         if let Some(sink) = current_sink.take() {
                             sink.stop();
                         }
@@ -1123,7 +1685,7 @@ impl Player {
                         let sr = current_sample_rate.unwrap_or(48000);
                         let ch = current_channels.unwrap_or(2);
                         log::info!("ReinitDevice: reinitializing at {}Hz/{}ch", sr, ch);
-                        *stream_opt = init_device(current_device_name, &thread_state, sr, ch);
+                        *stream_opt = init_device(current_device_name, &thread_state, sr, ch, *current_bits);
 
                         if stream_opt.is_some() {
                             log::info!("Audio thread: device reinitialized successfully");
@@ -1133,9 +1695,21 @@ impl Player {
                         }
 
                         thread_state.is_playing.store(false, Ordering::SeqCst);
-                        thread_state.position.store(0, Ordering::SeqCst);
+                        thread_state.position_ms.store(0, Ordering::SeqCst);
                         thread_state.playback_start_millis.store(0, Ordering::SeqCst);
                         *current_audio_data = None;
+                        current_stream = None;
+                        thread_state.streaming.store(false, Ordering::SeqCst);
+                        thread_state.buffering.store(false, Ordering::SeqCst);
+                    }
+                    AudioCommand::StreamComplete { track_id, data } => {
+                        // A later track may have started meanwhile
+                        if thread_state.is_streaming() && thread_state.current_track_id() == track_id {
+                            log::info!("Audio thread: stream of track {} complete ({} bytes)", track_id, data.len());
+                            *current_audio_data = Some(data);
+                            current_stream = None;
+                            thread_state.streaming.store(false, Ordering::SeqCst);
+                        }
                     }
                 }
             };
@@ -1153,6 +1727,7 @@ impl Player {
                             &mut pause_suspend_deadline,
                             &mut current_sample_rate,
                             &mut current_channels,
+                            &mut current_bits,
                         ),
                         Err(RecvTimeoutError::Timeout) => {
                             let now = Instant::now();
@@ -1165,7 +1740,7 @@ impl Player {
                                         log::info!("Audio thread: track finished (sink empty)");
                                         thread_state.is_playing.store(false, Ordering::SeqCst);
                                         let duration = thread_state.duration.load(Ordering::SeqCst);
-                                        thread_state.position.store(duration, Ordering::SeqCst);
+                                        thread_state.position_ms.store(duration * 1000, Ordering::SeqCst);
                                         thread_state.playback_start_millis.store(0, Ordering::SeqCst);
                                     }
                                 }
@@ -1181,6 +1756,12 @@ impl Player {
                         if stream_opt.is_some() {
                             let now = Instant::now();
                             if now >= deadline {
+                                // A stream still downloading couldn't be restored on resume
+                                if current_audio_data.is_none() {
+                                    pause_suspend_deadline =
+                                        Some(now + Duration::from_millis(PAUSE_SUSPEND_DELAY_MS));
+                                    continue;
+                                }
                                 if let Some(sink) = current_sink.take() {
                                     sink.stop();
                                 }
@@ -1203,6 +1784,7 @@ impl Player {
                                     &mut pause_suspend_deadline,
                                     &mut current_sample_rate,
                                     &mut current_channels,
+                                    &mut current_bits,
                                 ),
                                 Err(RecvTimeoutError::Timeout) => {}
                                 Err(RecvTimeoutError::Disconnected) => {
@@ -1226,6 +1808,7 @@ impl Player {
                             &mut pause_suspend_deadline,
                             &mut current_sample_rate,
                             &mut current_channels,
+                            &mut current_bits,
                         ),
                         Err(_) => {
                             log::info!("Audio thread: channel closed, exiting");
@@ -1236,7 +1819,15 @@ impl Player {
             }
         });
 
-        Self { tx, state, audio_settings: settings }
+        Self {
+            tx,
+            state,
+            audio_settings: settings,
+            scrub: Arc::new(Mutex::new(ScrubSession::default())),
+            taps,
+            preloader: Preloader::default(),
+            gain,
+        }
     }
 
     /// Play a track by ID (downloads audio)
@@ -1248,6 +1839,13 @@ impl Player {
     ) -> Result<(), String> {
         log::info!("Player: Starting playback for track {} with quality {:?}", track_id, quality);
 
+        if let Some(preloaded) = self.take_preloaded(track_id) {
+            log::info!("Player: Playing preloaded track {}", track_id);
+            self.state.set_stream_quality(Some(preloaded.requested_quality), preloaded.delivered_quality);
+            self.state.set_stream_preview(preloaded.is_preview);
+            return self.play_data(preloaded.data, track_id);
+        }
+
         // Get the stream URL
         log::info!("Player: Getting stream URL...");
         let stream_url = client
@@ -1259,10 +1857,12 @@ impl Player {
             })?;
 
         log::info!("Player: Got stream URL: {} (format: {})", stream_url.url, stream_url.mime_type);
+        self.state.set_stream_quality(Some(quality), stream_url.delivered_quality());
+        self.state.set_stream_preview(stream_url.is_preview);
 
         // Download the audio data
         log::info!("Player: Starting audio download...");
-        let audio_data = self.download_audio(&stream_url.url).await.map_err(|e| {
+        let audio_data = download_track(client, track_id, quality, &stream_url.url).await.map_err(|e| {
             log::error!("Player: Download failed: {}", e);
             e
         })?;
@@ -1272,27 +1872,50 @@ impl Player {
         self.play_data(audio_data, track_id)
     }
 
+    /// Resolve and download a track without starting output, so the next
+    /// play of it starts instantly. Cancels the preload of any other track.
+    pub async fn preload(
+        &self,
+        client: &tokio::sync::Mutex<QobuzClient>,
+        track_id: u64,
+        quality: Quality,
+    ) -> Result<(), String> {
+        self.preloader.preload(client, track_id, quality).await
+    }
+
+    /// Drop the preloaded track, cancelling a preload in progress
+    pub fn cancel_preload(&self) {
+        self.preloader.cancel();
+    }
+
+    /// Preloaded data for `track_id`; any other preload is cancelled
+    pub fn take_preloaded(&self, track_id: u64) -> Option<PreloadedTrack> {
+        self.preloader.take(track_id)
+    }
+
     /// Play from raw audio data (for cached tracks)
     pub fn play_data(&self, data: Vec<u8>, track_id: u64) -> Result<(), String> {
         log::info!("Player: Playing {} bytes of audio data for track {}", data.len(), track_id);
 
         // Extract audio metadata (sample rate and channels) - fast header-only read
-        let (sample_rate, channels) = extract_audio_metadata(&data)
+        let (sample_rate, channels, bits_per_sample) = extract_audio_metadata(&data)
             .map_err(|e| format!("Failed to extract audio metadata: {}", e))?;
 
         log::info!(
-            "Player: Detected audio format - {}Hz, {} channels",
+            "Player: Detected audio format - {}Hz, {} channels, {:?}-bit",
             sample_rate,
-            channels
+            channels,
+            bits_per_sample
         );
 
         self.tx
             .send(AudioCommand::Play {
-                data,
+                input: PlayInput::Data(data),
                 track_id,
                 duration_secs: 0, // Will be determined by decoder
                 sample_rate,
                 channels,
+                bits_per_sample,
             })
             .map_err(|e| {
                 log::error!("Player: Failed to send to audio thread: {}", e);
@@ -1303,38 +1926,66 @@ impl Player {
         Ok(())
     }
 
-    /// Download audio from URL with timeout
-    async fn download_audio(&self, url: &str) -> Result<Vec<u8>, String> {
-        use std::time::Duration;
-
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .connect_timeout(Duration::from_secs(10))
-            .build()
-            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-        log::info!("Downloading audio from URL...");
+    /// Play a track while it downloads; `decoder` is already probed
+    pub fn play_stream(&self, decoder: StreamDecoder, track_id: u64) -> Result<(), String> {
+        let sample_rate = decoder.sample_rate();
+        let channels = decoder.channels();
+        let bits_per_sample = decoder.bits_per_sample();
+        log::info!(
+            "Player: Streaming track {} - {}Hz, {} channels, {:?}-bit",
+            track_id,
+            sample_rate,
+            channels,
+            bits_per_sample
+        );
 
-        let response = client
-            .get(url)
-            .header("User-Agent", "Mozilla/5.0")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch audio: {}", e))?;
+        let state = self.state.clone();
+        let (source, control) = decoder.spawn(move |stalled| {
+            if state.current_track_id() == track_id {
+                state.set_buffering(stalled);
+            }
+        })?;
+        self.tx
+            .send(AudioCommand::Play {
+                input: PlayInput::Stream { source, control },
+                track_id,
+                duration_secs: 0,
+                sample_rate,
+                channels,
+                bits_per_sample,
+            })
+            .map_err(|e| format!("Failed to send play command (audio thread may have crashed): {}", e))
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()));
+    /// Play a track while it downloads, starting `start_secs` into it. A
+    /// seek ahead waits for the download to get there.
+    pub fn play_stream_from(&self, decoder: StreamDecoder, track_id: u64, start_secs: u64) -> Result<(), String> {
+        self.play_stream(decoder, track_id)?;
+        if start_secs > 0 {
+            self.tx
+                .send(AudioCommand::Seek(start_secs))
+                .map_err(|e| format!("Failed to send seek command: {}", e))?;
         }
+        Ok(())
+    }
 
-        log::info!("Download response received, reading bytes...");
-
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+    /// Hand over the complete data of a streamed track, so it can be seeked
+    pub fn complete_stream(&self, track_id: u64, data: Vec<u8>) -> Result<(), String> {
+        self.tx
+            .send(AudioCommand::StreamComplete { track_id, data })
+            .map_err(|e| format!("Failed to send stream data: {}", e))
+    }
 
-        log::info!("Downloaded {} bytes", bytes.len());
-        Ok(bytes.to_vec())
+    /// Play audio data starting `start_secs` into the track
+    pub fn play_data_from(&self, data: Vec<u8>, track_id: u64, start_secs: u64) -> Result<(), String> {
+        self.play_data(data, track_id)?;
+        if start_secs > 0 {
+            // Not clamped: the duration still belongs to the previous track until Play is handled
+            self.tx
+                .send(AudioCommand::Seek(start_secs))
+                .map_err(|e| format!("Failed to send seek command: {}", e))?;
+        }
+        Ok(())
     }
 
     /// Pause playback
@@ -1381,6 +2032,87 @@ impl Player {
             .map_err(|e| format!("Failed to send seek command: {}", e))
     }
 
+    /// Play a ~200ms snippet at `position` (seconds) at reduced volume while the
+    /// seek bar is dragged. The playback position is left alone until
+    /// `commit_seek`; if no preview follows within `SCRUB_TIMEOUT`, playback
+    /// is unmuted anyway. Not available in bit-perfect mode.
+    pub fn scrub_preview(&self, position: u64) -> Result<(), String> {
+        let bit_perfect = self
+            .audio_settings
+            .lock()
+            .map(|s| s.dac_passthrough)
+            .unwrap_or(false);
+        if bit_perfect {
+            return Err("Scrub preview is disabled in bit-perfect mode".to_string());
+        }
+
+        let duration = self.state.duration();
+        let position = if duration > 0 { position.min(duration) } else { position };
+
+        let started = self
+            .scrub
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .preview(position, Instant::now());
+        self.tx
+            .send(AudioCommand::ScrubPreview(position))
+            .map_err(|e| format!("Failed to send scrub command: {}", e))?;
+        if started {
+            self.watch_scrub();
+        }
+        Ok(())
+    }
+
+    /// End the preview of a drag that went quiet
+    fn watch_scrub(&self) {
+        let scrub = self.scrub.clone();
+        let tx = self.tx.clone();
+        thread::spawn(move || loop {
+            thread::sleep(SCRUB_TIMEOUT / 4);
+            let Ok(mut session) = scrub.lock() else {
+                return;
+            };
+            if !session.is_previewing() {
+                return;
+            }
+            if session.time_out(Instant::now()) {
+                log::debug!("Scrub preview timed out, unmuting playback");
+                let _ = tx.send(AudioCommand::EndScrub);
+                return;
+            }
+        });
+    }
+
+    /// End a seek bar drag, seeking to the last previewed position.
+    /// Returns the committed position (None if no preview was played).
+    pub fn commit_seek(&self) -> Result<Option<u64>, String> {
+        let target = self
+            .scrub
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .commit();
+
+        self.tx
+            .send(AudioCommand::EndScrub)
+            .map_err(|e| format!("Failed to send scrub command: {}", e))?;
+
+        if let Some(position) = target {
+            self.seek(position)?;
+        }
+        Ok(target)
+    }
+
+    /// Deliver a copy of the output PCM to `tap` (see `audio::tap`).
+    /// Returns an id for `unregister_tap`.
+    pub fn register_tap(&self, tap: Box<dyn AudioTap>) -> Result<u64, String> {
+        self.taps.register(tap)
+    }
+
+    /// Stop delivering output PCM to a tap
+    pub fn unregister_tap(&self, id: u64) -> bool {
+        self.taps.unregister(id)
+    }
+
     /// Reinitialize audio device (releases and re-acquires the device)
     /// Use this when changing audio settings like exclusive mode
     pub fn reinit_device(&self, device_name: Option<String>) -> Result<(), String> {
@@ -1389,10 +2121,47 @@ impl Player {
             .map_err(|e| format!("Failed to send reinit command: {}", e))
     }
 
+    /// Set the pre-gain (headroom) in dB, applied immediately. Bypassed in
+    /// bit-perfect mode; adds to ReplayGain, and the volume applies on top.
+    pub fn set_pregain_db(&self, db: f32) -> Result<(), String> {
+        crate::audio::gain::validate_pregain_db(db)?;
+        let mut settings = self
+            .audio_settings
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?;
+        settings.pregain_db = db;
+        self.gain.set_pregain_db(settings.effective_pregain_db());
+        Ok(())
+    }
+
+    /// Normalization mode in effect (Off in bit-perfect mode)
+    pub fn normalization_mode(&self) -> NormalizationMode {
+        self.audio_settings
+            .lock()
+            .map(|settings| settings.effective_normalization())
+            .unwrap_or_default()
+    }
+
+    /// Measured track or album gain for the track about to play, None to
+    /// leave it unnormalized. Ignored unless normalization is enabled.
+    pub fn set_replaygain_db(&self, db: Option<f32>) {
+        let enabled = self.normalization_mode() != NormalizationMode::Off;
+        self.gain.set_replaygain_db(db.filter(|_| enabled));
+    }
+
+    /// ReplayGain applied to the current track, in dB
+    pub fn replaygain_db(&self) -> f32 {
+        self.gain.replaygain_db()
+    }
+
     /// Reload audio settings from fresh config (e.g., after database update)
     /// Call this before reinit_device() to ensure Player uses latest settings
     pub fn reload_settings(&self, settings: AudioSettings) -> Result<(), String> {
         if let Ok(mut current_settings) = self.audio_settings.lock() {
+            self.gain.set_pregain_db(settings.effective_pregain_db());
+            if settings.effective_normalization() == NormalizationMode::Off {
+                self.gain.set_replaygain_db(None);
+            }
             *current_settings = settings;
             Ok(())
         } else {
@@ -1400,25 +2169,62 @@ impl Player {
         }
     }
 
+    /// Describe every stage of the current signal path, in order
+    pub fn dump_playback_chain(&self) -> Result<PlaybackChainReport, String> {
+        let settings = self
+            .audio_settings
+            .lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .clone();
+
+        Ok(chain::describe_chain(&chain::ChainInputs {
+            settings: &settings,
+            track_id: self.state.current_track_id(),
+            source: self.state.source_format(),
+            output: self.state.output_format(),
+            resampling: self.state.resampling(),
+            device: self.state.current_device(),
+            pregain_db: self.gain.pregain_db(),
+            replaygain_db: self.gain.replaygain_db(),
+            taps: self.taps.len(),
+            volume: self.state.volume(),
+        }))
+    }
+
     /// Get current playback state with real-time position
     pub fn get_state(&self) -> Result<PlaybackState, String> {
+        let (requested_quality, delivered_quality) = self.state.stream_quality();
+        let sample = self.state.position_sample();
         Ok(PlaybackState {
             is_playing: self.state.is_playing(),
-            position: self.state.current_position(),
+            position: sample.position_ms / 1000,
+            position_ms: sample.position_ms,
+            measured_at_monotonic_ms: sample.measured_at_monotonic_ms,
+            is_advancing: sample.is_advancing,
             duration: self.state.duration(),
             track_id: self.state.current_track_id(),
             volume: self.state.volume(),
+            requested_quality,
+            delivered_quality,
+            auto_quality: crate::download_cache::throughput::auto_quality().current(),
+            is_preview: self.state.is_stream_preview(),
+            is_buffering: self.state.is_buffering(),
+            preloaded_track_id: self.preloader.ready_track_id(),
         })
     }
 
     /// Get playback event for emitting to frontend
     pub fn get_playback_event(&self) -> PlaybackEvent {
+        let sample = self.state.position_sample();
         PlaybackEvent {
             is_playing: self.state.is_playing(),
-            position: self.state.current_position(),
+            position: sample.position_ms / 1000,
+            position_ms: sample.position_ms,
+            measured_at_monotonic_ms: sample.measured_at_monotonic_ms,
             duration: self.state.duration(),
             track_id: self.state.current_track_id(),
             volume: self.state.volume(),
+            is_buffering: self.state.is_buffering(),
         }
     }
 }
@@ -1428,7 +2234,103 @@ impl Player {
 pub struct PlaybackState {
     pub is_playing: bool,
     pub position: u64,
+    /// Position in milliseconds, measured at `measured_at_monotonic_ms`
+    pub position_ms: u64,
+    /// `monotonic_millis` at the time of measurement; the frontend
+    /// extrapolates from it against `Date.now()` while `is_advancing`
+    pub measured_at_monotonic_ms: u64,
+    pub is_advancing: bool,
     pub duration: u64,
     pub track_id: u64,
     pub volume: f32,
+    /// Quality asked of the API (None when playing from cache/local)
+    pub requested_quality: Option<Quality>,
+    /// Quality the API actually delivered
+    pub delivered_quality: Option<Quality>,
+    /// Starting quality picked by auto quality (None when disabled)
+    pub auto_quality: Option<Quality>,
+    /// Only a preview clip of the track is playing
+    pub is_preview: bool,
+    /// The streamed track is waiting for the download and playing silence
+    pub is_buffering: bool,
+    /// Track downloaded and ready to start without buffering
+    pub preloaded_track_id: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_sample_advances_with_monotonic_clock_and_stops_when_paused() {
+        let state = SharedState::new();
+        state.duration.store(300, Ordering::SeqCst);
+        state.is_playing.store(true, Ordering::SeqCst);
+        state.start_playback_timer(10_500);
+
+        let first = state.position_sample();
+        thread::sleep(Duration::from_millis(30));
+        let second = state.position_sample();
+
+        assert!(first.is_advancing && second.is_advancing);
+        assert!(first.position_ms >= 10_500);
+        let elapsed = second.measured_at_monotonic_ms - first.measured_at_monotonic_ms;
+        assert!(elapsed >= 30);
+        // Position moves exactly with the clock it is measured against
+        assert_eq!(second.position_ms - first.position_ms, elapsed);
+
+        state.pause_playback_timer();
+        state.is_playing.store(false, Ordering::SeqCst);
+        let paused = state.position_sample();
+        thread::sleep(Duration::from_millis(10));
+        let still_paused = state.position_sample();
+
+        assert!(!paused.is_advancing);
+        assert_eq!(paused.position_ms, still_paused.position_ms);
+        // Pausing keeps the milliseconds instead of rounding down to the second
+        assert!(paused.position_ms >= second.position_ms);
+        assert!(still_paused.measured_at_monotonic_ms > paused.measured_at_monotonic_ms);
+        assert_eq!(state.current_position(), paused.position_ms / 1000);
+    }
+
+    #[test]
+    fn test_resampling_survives_a_stream_at_the_device_default() {
+        let state = SharedState::new();
+        state.set_output_format(None);
+        state.set_resampling(Some(ResampleQuality::Best));
+        assert_eq!(state.resampling(), Some(ResampleQuality::Best));
+
+        state.set_output_format(Some(OutputFormat::new(48_000, 2, None)));
+        assert_eq!(state.output_format().unwrap().resampling, Some(ResampleQuality::Best));
+    }
+
+    #[test]
+    fn test_scrub_preview_times_out_and_keeps_the_drag_target() {
+        // A player without its audio thread, to see the commands it sends
+        let (tx, rx) = mpsc::channel();
+        let player = Player {
+            tx,
+            state: SharedState::new(),
+            audio_settings: Arc::new(Mutex::new(AudioSettings::default())),
+            scrub: Arc::new(Mutex::new(ScrubSession::default())),
+            taps: TapRegistry::default(),
+            preloader: Preloader::default(),
+            gain: GainControl::new(),
+        };
+
+        player.scrub_preview(42).unwrap();
+        player.scrub_preview(40).unwrap();
+        assert!(matches!(rx.try_recv(), Ok(AudioCommand::ScrubPreview(42))));
+        assert!(matches!(rx.try_recv(), Ok(AudioCommand::ScrubPreview(40))));
+
+        // No further preview and no commit: the preview ends and playback is unmuted
+        assert!(matches!(rx.recv_timeout(SCRUB_TIMEOUT * 2), Ok(AudioCommand::EndScrub)));
+        assert!(!player.scrub.lock().unwrap().is_previewing());
+
+        // A late end of the drag still seeks to where it stopped
+        assert_eq!(player.commit_seek().unwrap(), Some(40));
+        assert!(matches!(rx.try_recv(), Ok(AudioCommand::EndScrub)));
+        assert!(matches!(rx.try_recv(), Ok(AudioCommand::Seek(40))));
+        assert_eq!(player.commit_seek().unwrap(), None);
+    }
 }
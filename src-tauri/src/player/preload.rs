@@ -0,0 +1,251 @@
+//! Preloading the selected track
+//!
+//! When a track is selected but not yet played, its stream URL is resolved
+//! and the audio downloaded and probed ahead of time, so pressing play only
+//! has to hand the data to the audio thread. Only one track is preloaded at
+//! a time: selecting another one (or playing anything) cancels the previous
+//! preload, and a download that finishes after being cancelled is dropped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::api::client::QobuzClient;
+use crate::api::models::Quality;
+
+/// Audio of a preloaded track, ready to play
+#[derive(Debug, Clone)]
+pub struct PreloadedTrack {
+    pub track_id: u64,
+    pub data: Vec<u8>,
+    pub requested_quality: Quality,
+    pub delivered_quality: Option<Quality>,
+    pub is_preview: bool,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+#[derive(Debug, Default)]
+enum Slot {
+    #[default]
+    Empty,
+    Loading(u64),
+    Ready(PreloadedTrack),
+}
+
+#[derive(Debug, Default)]
+pub struct Preloader {
+    /// Bumped on every new preload or cancel; stale downloads compare against it
+    generation: AtomicU64,
+    slot: Mutex<Slot>,
+}
+
+impl Preloader {
+    /// Track that is preloaded and ready to play
+    pub fn ready_track_id(&self) -> Option<u64> {
+        match &*self.slot.lock().ok()? {
+            Slot::Ready(track) => Some(track.track_id),
+            _ => None,
+        }
+    }
+
+    /// Start preloading `track_id`, cancelling any other preload.
+    /// Returns None if the track is already loading or ready.
+    fn begin(&self, track_id: u64) -> Option<u64> {
+        let mut slot = self.slot.lock().ok()?;
+        match &*slot {
+            Slot::Loading(id) if *id == track_id => return None,
+            Slot::Ready(track) if track.track_id == track_id => return None,
+            _ => {}
+        }
+        *slot = Slot::Loading(track_id);
+        Some(self.generation.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    /// Store a finished preload unless it was cancelled meanwhile
+    fn finish(&self, generation: u64, track: PreloadedTrack) -> bool {
+        let Ok(mut slot) = self.slot.lock() else {
+            return false;
+        };
+        if self.generation.load(Ordering::SeqCst) != generation {
+            log::debug!("Dropping cancelled preload of track {}", track.track_id);
+            return false;
+        }
+        *slot = Slot::Ready(track);
+        true
+    }
+
+    /// Forget the preloaded track and cancel an in-flight preload
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = Slot::Empty;
+        }
+    }
+
+    /// Take the preloaded data for `track_id`. Any other preload is
+    /// cancelled, since playback moved on to a different track.
+    pub fn take(&self, track_id: u64) -> Option<PreloadedTrack> {
+        let mut slot = self.slot.lock().ok()?;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        match std::mem::take(&mut *slot) {
+            Slot::Ready(track) if track.track_id == track_id => Some(track),
+            _ => None,
+        }
+    }
+
+    /// Resolve, download and probe a track without starting output. The
+    /// client isn't held locked, so playing another track isn't held up by
+    /// the download.
+    pub async fn preload(
+        &self,
+        client: &AsyncMutex<QobuzClient>,
+        track_id: u64,
+        quality: Quality,
+    ) -> Result<(), String> {
+        let Some(generation) = self.begin(track_id) else {
+            log::debug!("Track {} is already preloaded", track_id);
+            return Ok(());
+        };
+
+        let result = async {
+            let client = client.lock().await.clone();
+            let stream_url = client
+                .playback_stream_url(track_id, quality)
+                .await
+                .map_err(|e| format!("Failed to get stream URL: {}", e))?;
+            let data = super::download_track(&client, track_id, quality, &stream_url.url).await?;
+            // Probing the headers up front makes sure the data decodes
+            let (sample_rate, channels, _) = super::extract_audio_metadata(&data)?;
+
+            Ok::<_, String>(PreloadedTrack {
+                track_id,
+                data,
+                requested_quality: quality,
+                delivered_quality: stream_url.delivered_quality(),
+                is_preview: stream_url.is_preview,
+                sample_rate,
+                channels,
+            })
+        }
+        .await;
+
+        match result {
+            Ok(track) => {
+                if self.finish(generation, track) {
+                    log::info!("Track {} preloaded", track_id);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                // Leave the slot alone if a newer preload took over
+                if self.generation.load(Ordering::SeqCst) == generation {
+                    if let Ok(mut slot) = self.slot.lock() {
+                        *slot = Slot::Empty;
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::endpoints::paths;
+    use crate::api::test_support::logged_in_client;
+    use crate::audio::wav::pcm16;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A short 16-bit mono WAV file
+    fn wav() -> Vec<u8> {
+        pcm16(44100, 1, &[0i16; 441])
+    }
+
+    #[tokio::test]
+    async fn test_preload_downloads_once_for_the_following_play() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("{}/audio.wav", server.uri()),
+                "format_id": 6,
+                "mime_type": "audio/wav"
+            })))
+            .mount(&server)
+            .await;
+        // The only download, even though play follows the preload
+        Mock::given(method("GET"))
+            .and(path("/audio.wav"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(wav()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = logged_in_client(&server).await;
+        let client = AsyncMutex::new(client);
+
+        let preloader = Preloader::default();
+        preloader.preload(&client, 42, Quality::Lossless).await.unwrap();
+        assert_eq!(preloader.ready_track_id(), Some(42));
+        // Selecting the same track again doesn't refetch
+        preloader.preload(&client, 42, Quality::Lossless).await.unwrap();
+
+        // Play takes the preloaded data instead of downloading
+        let track = preloader.take(42).unwrap();
+        assert_eq!(track.data, wav());
+        assert_eq!((track.sample_rate, track.channels), (44100, 1));
+        assert_eq!(preloader.ready_track_id(), None);
+
+        // A cancelled preload is never handed out
+        let generation = preloader.begin(7).unwrap();
+        preloader.cancel();
+        assert!(!preloader.finish(generation, track));
+        assert!(preloader.take(7).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preload_requests_a_new_url_when_the_first_expired() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("{}/expired.wav", server.uri()),
+                "format_id": 6,
+                "mime_type": "audio/wav"
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(paths::TRACK_GET_FILE_URL))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("{}/audio.wav", server.uri()),
+                "format_id": 6,
+                "mime_type": "audio/wav"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/expired.wav"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/audio.wav"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(wav()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = AsyncMutex::new(logged_in_client(&server).await);
+
+        let preloader = Preloader::default();
+        preloader.preload(&client, 42, Quality::Lossless).await.unwrap();
+
+        assert_eq!(preloader.take(42).unwrap().data, wav());
+    }
+}
@@ -0,0 +1,1035 @@
+//! Progressive playback of a download
+//!
+//! The download appends to a bounded shared buffer on its own thread and a
+//! decode thread reads from it, so playback starts once the first frames
+//! arrive instead of after the whole file. When the buffer is full the
+//! download stops reading from the network until the decoder has caught
+//! up; a read before the buffered bytes (a seek back) restarts the download
+//! there with a range request. Decoded samples reach the output through a
+//! bounded queue, and the output never waits on it: when the network falls
+//! behind, the queue runs dry and the output plays silence and reports the
+//! stall until samples arrive again. The download thread can also keep a
+//! whole copy of the file, handed over at the end so the track can be cached.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::Source;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder as SymphoniaDecoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::default::{get_codecs, get_probe};
+
+/// Decoded audio queued ahead of the output
+const QUEUED_SECONDS: usize = 2;
+
+/// Samples the output takes from the queue at once
+const OUTPUT_CHUNK_SAMPLES: usize = 4096;
+
+/// Size of each read from the network
+const DOWNLOAD_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Downloaded bytes buffered for the readers
+const BUFFER_BYTES: usize = 4 * 1024 * 1024;
+
+/// A download thread; its result is the whole file when it was kept
+pub type DownloadHandle = JoinHandle<Result<Option<Vec<u8>>, String>>;
+
+struct DownloadState {
+    /// Buffered bytes, starting at stream offset `window_start`
+    window: VecDeque<u8>,
+    window_start: u64,
+    capacity: usize,
+    /// The download reached the end of the stream
+    done: bool,
+    /// The writer is gone, so nothing is downloaded again
+    closed: bool,
+    error: Option<String>,
+    /// Read positions of the open readers
+    readers: HashMap<u64, u64>,
+    next_reader: u64,
+    /// Open `KeepAlive` guards
+    keep_alive: usize,
+    /// Offset a reader needs that is no longer buffered
+    restart: Option<u64>,
+}
+
+impl DownloadState {
+    fn window_end(&self) -> u64 {
+        self.window_start + self.window.len() as u64
+    }
+
+    /// The download stops once nothing reads or keeps it
+    fn abandoned(&self) -> bool {
+        self.readers.is_empty() && self.keep_alive == 0
+    }
+
+    /// Drop the bytes every reader is done with. A quarter of the buffer is
+    /// kept behind the slowest reader, for the decoder's short seeks back.
+    fn trim(&mut self) {
+        let keep_behind = self.capacity as u64 / 4;
+        let keep_from = self
+            .readers
+            .values()
+            .min()
+            .map_or(u64::MAX, |position| position.saturating_sub(keep_behind));
+        let count = keep_from.saturating_sub(self.window_start).min(self.window.len() as u64);
+        self.window.drain(..count as usize);
+        self.window_start += count;
+    }
+}
+
+struct Download {
+    state: Mutex<DownloadState>,
+    changed: Condvar,
+}
+
+/// Create a download buffer. `content_length` is reported to the decoder
+/// as the stream length when known, and makes the stream seekable. With
+/// `keep`, the writer also keeps the whole file.
+pub fn download_buffer(content_length: Option<u64>, keep: bool) -> (DownloadWriter, StreamReader) {
+    buffer_with_capacity(content_length, keep, BUFFER_BYTES)
+}
+
+fn buffer_with_capacity(content_length: Option<u64>, keep: bool, capacity: usize) -> (DownloadWriter, StreamReader) {
+    let shared = Arc::new(Download {
+        state: Mutex::new(DownloadState {
+            window: VecDeque::with_capacity(capacity),
+            window_start: 0,
+            capacity,
+            done: false,
+            closed: false,
+            error: None,
+            readers: HashMap::from([(0, 0)]),
+            next_reader: 1,
+            keep_alive: 0,
+            restart: None,
+        }),
+        changed: Condvar::new(),
+    });
+    (
+        DownloadWriter {
+            shared: shared.clone(),
+            finished: false,
+            kept: keep.then(Vec::new),
+            position: 0,
+        },
+        StreamReader { shared, id: 0, position: 0, content_length },
+    )
+}
+
+/// Download side of the buffer
+pub struct DownloadWriter {
+    shared: Arc<Download>,
+    finished: bool,
+    /// The whole file so far, when kept
+    kept: Option<Vec<u8>>,
+    /// Stream offset of the next appended byte
+    position: u64,
+}
+
+impl DownloadWriter {
+    /// Append `data`, waiting while the buffer is full. Fails once every
+    /// reader is gone, so an abandoned download can stop. Returns early
+    /// when a reader asked for a restart.
+    pub fn append(&mut self, data: &[u8]) -> std::io::Result<()> {
+        // Bytes downloaded again after a restart are already kept
+        if let Some(kept) = self.kept.as_mut() {
+            let kept_len = kept.len() as u64;
+            if (self.position..self.position + data.len() as u64).contains(&kept_len) {
+                kept.extend_from_slice(&data[(kept_len - self.position) as usize..]);
+            }
+        }
+        self.position += data.len() as u64;
+
+        let mut state = self.shared.state.lock().unwrap();
+        let mut pending = data;
+        while !pending.is_empty() {
+            if state.abandoned() {
+                return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Stream reader closed"));
+            }
+            if state.restart.is_some() {
+                break;
+            }
+            state.trim();
+            let room = state.capacity - state.window.len();
+            if room == 0 {
+                state = self.shared.changed.wait(state).unwrap();
+                continue;
+            }
+            let count = room.min(pending.len());
+            state.window.extend(&pending[..count]);
+            pending = &pending[count..];
+            self.shared.changed.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Offset the download should continue from, if a reader asked for it
+    fn restart_requested(&self) -> Option<u64> {
+        self.shared.state.lock().unwrap().restart
+    }
+
+    /// Continue the download from `offset`, dropping the buffered bytes
+    fn restart_at(&mut self, offset: u64) {
+        self.position = offset;
+        let mut state = self.shared.state.lock().unwrap();
+        state.window.clear();
+        state.window_start = offset;
+        state.restart = None;
+        state.done = false;
+        self.shared.changed.notify_all();
+    }
+
+    /// Mark the end of the stream without finishing, then wait until a
+    /// reader asks for a restart (its offset) or every reader is gone (None)
+    fn wait_for_restart(&self) -> Option<u64> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.done = true;
+        self.shared.changed.notify_all();
+        while state.restart.is_none() && !state.abandoned() {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+        state.restart
+    }
+
+    /// Mark the end of the download, handing over the whole file if it was kept
+    pub fn finish(mut self) -> Option<Vec<u8>> {
+        self.close(None);
+        self.kept.take()
+    }
+
+    /// End the download with an error, returned to readers at the end of the data
+    pub fn fail(mut self, error: String) {
+        self.close(Some(error));
+    }
+
+    fn close(&mut self, error: Option<String>) {
+        self.finished = true;
+        let mut state = self.shared.state.lock().unwrap();
+        state.done = true;
+        state.closed = true;
+        state.error = error;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Drop for DownloadWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.close(Some("Stream writer dropped".to_string()));
+        }
+    }
+}
+
+/// Keeps a download going without reading from it (e.g. to finish it
+/// after the decoder gave up on the stream)
+pub struct KeepAlive {
+    shared: Arc<Download>,
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.keep_alive -= 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Reads a download in progress, blocking until the requested bytes
+/// arrive. Clones read independently and keep the download going.
+pub struct StreamReader {
+    shared: Arc<Download>,
+    id: u64,
+    position: u64,
+    content_length: Option<u64>,
+}
+
+impl StreamReader {
+    /// A guard keeping the download going once this reader is dropped
+    pub fn keep_alive(&self) -> KeepAlive {
+        self.shared.state.lock().unwrap().keep_alive += 1;
+        KeepAlive { shared: self.shared.clone() }
+    }
+
+    fn set_position(&mut self, position: u64) {
+        self.position = position;
+        let mut state = self.shared.state.lock().unwrap();
+        state.readers.insert(self.id, position);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Clone for StreamReader {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.state.lock().unwrap();
+        let id = state.next_reader;
+        state.next_reader += 1;
+        state.readers.insert(id, self.position);
+        Self {
+            shared: self.shared.clone(),
+            id,
+            position: self.position,
+            content_length: self.content_length,
+        }
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if self.position < state.window_start {
+                if state.closed {
+                    return Err(std::io::Error::other("Stream data is no longer buffered"));
+                }
+                // Already downloaded and dropped: download it again
+                state.restart.get_or_insert(self.position);
+                self.shared.changed.notify_all();
+            } else if self.position < state.window_end() {
+                break;
+            } else if state.done {
+                return match &state.error {
+                    Some(error) => Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, error.clone())),
+                    None => Ok(0),
+                };
+            }
+            state = self.shared.changed.wait(state).unwrap();
+        }
+
+        let start = (self.position - state.window_start) as usize;
+        let count = out.len().min(state.window.len() - start);
+        for (slot, byte) in out.iter_mut().zip(state.window.range(start..start + count)) {
+            *slot = *byte;
+        }
+        self.position += count as u64;
+        state.readers.insert(self.id, self.position);
+        // The writer may be waiting for room
+        self.shared.changed.notify_all();
+        Ok(count)
+    }
+}
+
+impl Seek for StreamReader {
+    /// Moves the read position; reads past the downloaded bytes wait for them
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => {
+                let length = self.content_length.ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::Unsupported, "Stream length unknown")
+                })?;
+                length.checked_add_signed(delta)
+            }
+        };
+        let target = target.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the stream")
+        })?;
+        self.set_position(target);
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for StreamReader {
+    fn is_seekable(&self) -> bool {
+        self.content_length.is_some()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.content_length
+    }
+}
+
+impl Drop for StreamReader {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.readers.remove(&self.id);
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Copy `response` into `writer`, reopening the download with `reopen`
+/// (from a byte offset) when a reader needs bytes that are no longer
+/// buffered, or once when the connection drops. Returns the whole file
+/// when it was kept; otherwise keeps serving restarts until the readers
+/// are gone.
+fn pump<R, F>(mut response: R, mut reopen: F, mut writer: DownloadWriter) -> Result<Option<Vec<u8>>, String>
+where
+    R: Read,
+    F: FnMut(u64) -> Result<R, String>,
+{
+    let mut chunk = vec![0u8; DOWNLOAD_CHUNK_BYTES];
+    let mut reconnected = false;
+    loop {
+        if let Some(offset) = writer.restart_requested() {
+            log::debug!("Restarting stream download at byte {}", offset);
+            response = match reopen(offset) {
+                Ok(response) => response,
+                Err(e) => {
+                    writer.fail(e.clone());
+                    return Err(e);
+                }
+            };
+            writer.restart_at(offset);
+        }
+
+        let count = match response.read(&mut chunk) {
+            Ok(0) if writer.kept.is_some() => break,
+            Ok(0) => match writer.wait_for_restart() {
+                Some(_) => continue,
+                None => return Ok(None),
+            },
+            Ok(count) => count,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            // E.g. the server dropped a connection paused for long
+            Err(e) if !reconnected => {
+                log::info!("Stream download interrupted ({}), reconnecting", e);
+                reconnected = true;
+                match reopen(writer.position) {
+                    Ok(reopened) => response = reopened,
+                    Err(e) => {
+                        writer.fail(e.clone());
+                        return Err(e);
+                    }
+                }
+                continue;
+            }
+            Err(e) => {
+                let error = format!("Failed to read audio bytes: {}", e);
+                writer.fail(error.clone());
+                return Err(error);
+            }
+        };
+        reconnected = false;
+        if writer.append(&chunk[..count]).is_err() {
+            // The player moved on; not worth finishing
+            return match writer.kept {
+                Some(_) => Err("Stream abandoned before the download finished".to_string()),
+                None => Ok(None),
+            };
+        }
+    }
+    Ok(writer.finish())
+}
+
+/// Start copying a download into a new buffer on its own thread. `reopen`
+/// requests the file again from a byte offset.
+pub fn spawn_download<R, F>(
+    response: R,
+    reopen: F,
+    content_length: Option<u64>,
+    keep: bool,
+) -> Result<(StreamReader, DownloadHandle), String>
+where
+    R: Read + Send + 'static,
+    F: FnMut(u64) -> Result<R, String> + Send + 'static,
+{
+    let (writer, reader) = download_buffer(content_length, keep);
+    let handle = thread::Builder::new()
+        .name("qbz-stream-download".to_string())
+        .spawn(move || pump(response, reopen, writer))
+        .map_err(|e| format!("Failed to start stream download: {}", e))?;
+    Ok((reader, handle))
+}
+
+/// Decodes packets as they are read from a `StreamReader`
+pub struct StreamDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn SymphoniaDecoder>,
+    track_id: u32,
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: Option<u32>,
+    total_duration: Option<Duration>,
+    /// Samples of the last decoded packet
+    buffer: Vec<f32>,
+    /// Frames before this timestamp are dropped (set by an accurate seek)
+    skip_until_ts: Option<u64>,
+}
+
+impl StreamDecoder {
+    /// Probe the stream, blocking until its headers have been downloaded
+    pub fn new(reader: StreamReader) -> Result<Self, String> {
+        let mss = MediaSourceStream::new(Box::new(reader), Default::default());
+        let format_opts = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let probed = get_probe()
+            .format(&Hint::new(), mss, &format_opts, &MetadataOptions::default())
+            .map_err(|err| format!("Stream probe failed: {}", err))?;
+
+        let track = probed
+            .format
+            .default_track()
+            .ok_or_else(|| "Stream has no supported audio track".to_string())?;
+        let params = track.codec_params.clone();
+        let track_id = track.id;
+
+        let sample_rate = params.sample_rate.ok_or_else(|| "No sample rate in stream".to_string())?;
+        let channels = params
+            .channels
+            .map(|c| c.count() as u16)
+            .ok_or_else(|| "No channel info in stream".to_string())?;
+        let total_duration = params
+            .n_frames
+            .map(|frames| Duration::from_secs_f64(frames as f64 / sample_rate as f64));
+
+        let decoder = get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(|err| format!("Stream decoder init failed: {}", err))?;
+
+        Ok(Self {
+            format: probed.format,
+            decoder,
+            track_id,
+            sample_rate,
+            channels,
+            bits_per_sample: params.bits_per_sample,
+            total_duration,
+            buffer: Vec::new(),
+            skip_until_ts: None,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Source bit depth (None for lossy or unknown formats)
+    pub fn bits_per_sample(&self) -> Option<u32> {
+        self.bits_per_sample
+    }
+
+    pub fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    /// Move to `position`, waiting for the download to reach it
+    fn seek(&mut self, position: Duration) -> Result<(), String> {
+        let seeked = self
+            .format
+            .seek(SeekMode::Accurate, SeekTo::Time { time: position.into(), track_id: Some(self.track_id) })
+            .map_err(|err| format!("Stream seek failed: {}", err))?;
+        self.decoder.reset();
+        self.buffer.clear();
+        self.skip_until_ts = Some(seeked.required_ts);
+        Ok(())
+    }
+
+    /// Decode the next packet into `buffer`; false at the end of the stream
+    fn decode_next(&mut self) -> bool {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // End of stream, or the download failed
+                Err(SymphoniaError::IoError(e)) => {
+                    log::debug!("Stream ended: {}", e);
+                    return false;
+                }
+                Err(err) => {
+                    log::warn!("Stream read error: {}", err);
+                    return false;
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(audio_buf) => {
+                    let mut samples = SampleBuffer::<f32>::new(audio_buf.frames() as u64, *audio_buf.spec());
+                    samples.copy_interleaved_ref(audio_buf);
+                    // Frames an accurate seek landed before
+                    let skip_frames = self
+                        .skip_until_ts
+                        .map_or(0, |ts| ts.saturating_sub(packet.ts()) as usize);
+                    let skip = (skip_frames * self.channels as usize).min(samples.samples().len());
+                    self.buffer.clear();
+                    self.buffer.extend_from_slice(&samples.samples()[skip..]);
+                    if !self.buffer.is_empty() {
+                        self.skip_until_ts = None;
+                        return true;
+                    }
+                }
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(SymphoniaError::ResetRequired) => self.decoder.reset(),
+                Err(err) => {
+                    log::warn!("Stream decode error: {}", err);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Start decoding on its own thread. `on_stall` is called with true when
+    /// the output runs out of samples before the end, and false once they
+    /// flow again.
+    pub fn spawn(self, on_stall: impl FnMut(bool) + Send + 'static) -> Result<(StreamSource, StreamControl), String> {
+        let channels = self.channels.max(1) as usize;
+        let capacity = self.sample_rate as usize * channels * QUEUED_SECONDS;
+        let shared = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                samples: VecDeque::with_capacity(capacity),
+                capacity: capacity.max(channels),
+                finished: false,
+                seek: None,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+            generation: AtomicU64::new(0),
+        });
+
+        let source = StreamSource {
+            shared: shared.clone(),
+            chunk: VecDeque::with_capacity(OUTPUT_CHUNK_SAMPLES),
+            generation: 0,
+            frame_position: 0,
+            silent_frame: false,
+            stalled: false,
+            on_stall: Box::new(on_stall),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            total_duration: self.total_duration,
+        };
+        let control = StreamControl { shared: shared.clone() };
+        thread::Builder::new()
+            .name("qbz-stream-decode".to_string())
+            .spawn(move || decode_into(self, &shared))
+            .map_err(|e| format!("Failed to start stream decoder: {}", e))?;
+        Ok((source, control))
+    }
+}
+
+struct QueueState {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    /// The decoder reached the end of the stream
+    finished: bool,
+    /// Position the decoder should move to
+    seek: Option<Duration>,
+    /// The output is gone; the decoder exits
+    stopped: bool,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    /// Bumped by every seek, so the output drops samples it took before
+    generation: AtomicU64,
+}
+
+/// Decode thread: fill the queue, following seeks, until the output is gone.
+/// At the end of the stream it waits, since a seek can still move back.
+fn decode_into(mut decoder: StreamDecoder, queue: &Queue) {
+    let channels = decoder.channels.max(1) as usize;
+    loop {
+        let seek = {
+            let mut state = queue.state.lock().unwrap();
+            while state.finished && state.seek.is_none() && !state.stopped {
+                state = queue.changed.wait(state).unwrap();
+            }
+            if state.stopped {
+                return;
+            }
+            state.seek.take()
+        };
+        if let Some(position) = seek {
+            if let Err(e) = decoder.seek(position) {
+                log::warn!("{}", e);
+            }
+        }
+
+        if !decoder.decode_next() {
+            let mut state = queue.state.lock().unwrap();
+            if state.seek.is_none() {
+                state.finished = true;
+            }
+            queue.changed.notify_all();
+            continue;
+        }
+
+        let mut pending = &decoder.buffer[..];
+        let mut state = queue.state.lock().unwrap();
+        while !pending.is_empty() {
+            while state.samples.len() + channels > state.capacity && state.seek.is_none() && !state.stopped {
+                state = queue.changed.wait(state).unwrap();
+            }
+            // Samples from before a seek are dropped
+            if state.stopped || state.seek.is_some() {
+                break;
+            }
+            let room = state.capacity - state.samples.len();
+            let count = pending.len().min(room - room % channels);
+            state.samples.extend(&pending[..count]);
+            pending = &pending[count..];
+        }
+    }
+}
+
+/// Seeks a stream being played, from outside the output
+pub struct StreamControl {
+    shared: Arc<Queue>,
+}
+
+impl StreamControl {
+    /// Continue the stream from `position`. Queued samples are dropped; the
+    /// output plays silence until the decoder has caught up.
+    pub fn seek(&self, position: Duration) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.samples.clear();
+        state.finished = false;
+        state.seek = Some(position);
+        self.shared.generation.fetch_add(1, Ordering::Release);
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Output side of a stream: plays decoded samples, or silence while the
+/// decoder is behind. Never blocks on the network.
+pub struct StreamSource {
+    shared: Arc<Queue>,
+    /// Samples taken from the queue, not played yet
+    chunk: VecDeque<f32>,
+    /// Seek generation `chunk` was taken in
+    generation: u64,
+    /// Channel of the next sample; seeks and stalls apply at frame starts
+    frame_position: u16,
+    /// The frame being played is silence
+    silent_frame: bool,
+    stalled: bool,
+    on_stall: Box<dyn FnMut(bool) + Send>,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl StreamSource {
+    /// Refill `chunk` from the queue (whole frames); None at the end of the stream
+    fn refill(&mut self) -> Option<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.samples.is_empty() {
+            return if state.finished { None } else { Some(()) };
+        }
+        let count = state.samples.len().min(OUTPUT_CHUNK_SAMPLES);
+        let count = count - count % self.channels.max(1) as usize;
+        self.chunk.extend(state.samples.drain(..count));
+        self.generation = self.shared.generation.load(Ordering::Acquire);
+        self.shared.changed.notify_all();
+        Some(())
+    }
+
+    /// First sample of the next frame: decoded, or silence while the decoder is behind
+    fn start_frame(&mut self) -> Option<f32> {
+        if self.shared.generation.load(Ordering::Acquire) != self.generation {
+            self.chunk.clear();
+        }
+        if self.chunk.is_empty() {
+            self.refill()?;
+        }
+        let sample = self.chunk.pop_front();
+        self.silent_frame = sample.is_none();
+        self.set_stalled(sample.is_none());
+        Some(sample.unwrap_or(0.0))
+    }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        if self.stalled != stalled {
+            self.stalled = stalled;
+            (self.on_stall)(stalled);
+        }
+    }
+}
+
+impl Iterator for StreamSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = if self.frame_position == 0 {
+            self.start_frame()?
+        } else if self.silent_frame {
+            0.0
+        } else {
+            self.chunk.pop_front().unwrap_or(0.0)
+        };
+        self.frame_position = (self.frame_position + 1) % self.channels.max(1);
+        Some(sample)
+    }
+}
+
+impl Source for StreamSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, _pos: Duration) -> Result<(), SeekError> {
+        Err(SeekError::NotSupported { underlying_source: std::any::type_name::<Self>() })
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        self.set_stalled(false);
+        let mut state = self.shared.state.lock().unwrap();
+        state.stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::wav::to_i16;
+    use std::io::Cursor;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    const RATE: u32 = 8000;
+
+    /// Mono 16-bit WAV whose samples count up by one every 10ms, from 1000
+    fn wav(seconds: u32) -> Vec<u8> {
+        let frames = RATE * seconds;
+        let mut out = Vec::new();
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + frames * 2).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&RATE.to_le_bytes());
+        out.extend_from_slice(&(RATE * 2).to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&(frames * 2).to_le_bytes());
+        for frame in 0..frames {
+            out.extend_from_slice(&expected(frame).to_le_bytes());
+        }
+        out
+    }
+
+    fn expected(frame: u32) -> i16 {
+        1000 + (frame / (RATE / 100)) as i16
+    }
+
+    /// Pull `count` samples, waiting out silence while the decoder catches up
+    fn pull(source: &mut StreamSource, count: usize) -> Vec<i16> {
+        let mut out = Vec::new();
+        while out.len() < count {
+            match source.next().map(to_i16) {
+                Some(0) => thread::sleep(Duration::from_millis(1)),
+                Some(sample) => out.push(sample),
+                None => break,
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_reader_waits_for_bytes_and_abandoned_download_stops() {
+        let data = pattern(200_000);
+
+        // Slow download: reads wait instead of ending early
+        let (mut writer, mut reader) = download_buffer(None, true);
+        let producer = thread::spawn({
+            let data = data.clone();
+            move || {
+                for chunk in data.chunks(3_001) {
+                    writer.append(chunk).unwrap();
+                    thread::sleep(Duration::from_micros(200));
+                }
+                writer.finish()
+            }
+        });
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        assert_eq!(producer.join().unwrap(), Some(data.clone()));
+
+        // Bytes already read can be read again after a seek
+        reader.seek(SeekFrom::Start(10)).unwrap();
+        let mut buf = [0u8; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &data[10..15]);
+
+        // An abandoned download stops once every reader is gone
+        let (mut writer, reader) = download_buffer(None, true);
+        let spare = reader.clone();
+        drop(reader);
+        assert!(writer.append(&data).is_ok());
+        drop(spare);
+        assert!(writer.append(&data).is_err());
+    }
+
+    /// Pump `data` into a buffer of `capacity` bytes on its own thread,
+    /// recording the offsets the download is reopened at
+    fn pump_cursor(
+        data: &[u8],
+        capacity: usize,
+        keep: bool,
+    ) -> (StreamReader, DownloadHandle, Arc<Mutex<Vec<u64>>>) {
+        let (writer, reader) = buffer_with_capacity(Some(data.len() as u64), keep, capacity);
+        let reopened = Arc::new(Mutex::new(Vec::new()));
+        let reopen = {
+            let data = data.to_vec();
+            let reopened = reopened.clone();
+            move |offset| {
+                reopened.lock().unwrap().push(offset);
+                let mut response = Cursor::new(data.clone());
+                response.set_position(offset);
+                Ok(response)
+            }
+        };
+        let response = Cursor::new(data.to_vec());
+        let handle = thread::spawn(move || pump(response, reopen, writer));
+        (reader, handle, reopened)
+    }
+
+    fn buffered(reader: &StreamReader) -> usize {
+        reader.shared.state.lock().unwrap().window.len()
+    }
+
+    #[test]
+    fn test_fast_download_waits_for_a_slow_reader() {
+        let data = pattern(1_000_000);
+        let capacity = 64 * 1024;
+        let (mut reader, download, reopened) = pump_cursor(&data, capacity, true);
+
+        // The download fills the buffer, then waits instead of reading ahead
+        let mut read = vec![0u8; 4096];
+        reader.read_exact(&mut read).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!download.is_finished());
+        assert_eq!(buffered(&reader), capacity);
+
+        let mut chunk = [0u8; 10_000];
+        loop {
+            let count = reader.read(&mut chunk).unwrap();
+            if count == 0 {
+                break;
+            }
+            read.extend_from_slice(&chunk[..count]);
+            assert!(buffered(&reader) <= capacity);
+            thread::sleep(Duration::from_micros(100));
+        }
+        assert_eq!(read, data);
+
+        // The kept file is handed over whole
+        assert_eq!(download.join().unwrap().unwrap(), Some(data));
+        assert!(reopened.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_seek_back_past_the_buffer_restarts_the_download() {
+        let data = pattern(500_000);
+        let (mut reader, download, reopened) = pump_cursor(&data, 64 * 1024, false);
+
+        let mut read = vec![0u8; 300_000];
+        reader.read_exact(&mut read).unwrap();
+
+        // While downloading
+        reader.seek(SeekFrom::Start(100)).unwrap();
+        let mut buf = [0u8; 1000];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[100..1100]);
+        assert_eq!(*reopened.lock().unwrap(), vec![100]);
+
+        // After the end, until the reader is gone
+        reader.seek(SeekFrom::Start(1100)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[1100..]);
+        reader.seek(SeekFrom::Start(10)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[10..1010]);
+        assert_eq!(*reopened.lock().unwrap(), vec![100, 10]);
+
+        drop(reader);
+        assert_eq!(download.join().unwrap().unwrap(), None);
+    }
+
+    #[test]
+    fn test_stall_plays_silence_and_seek_continues_from_the_new_position() {
+        let file = wav(3);
+        let header = 44;
+        let (mut writer, reader) = download_buffer(Some(file.len() as u64), true);
+        // Headers and the first half second
+        let first = header + RATE as usize;
+        writer.append(&file[..first]).unwrap();
+
+        let stalls = Arc::new(Mutex::new(Vec::new()));
+        let decoder = StreamDecoder::new(reader).unwrap();
+        let (mut source, control) = decoder
+            .spawn({
+                let stalls = stalls.clone();
+                move |stalled| stalls.lock().unwrap().push(stalled)
+            })
+            .unwrap();
+
+        // Plays what has arrived (up to the last whole packet), then silence
+        // without waiting on the network
+        let mut played = pull(&mut source, (RATE / 4) as usize);
+        thread::sleep(Duration::from_millis(20));
+        while let Some(sample) = source.next().map(to_i16).filter(|&sample| sample != 0) {
+            played.push(sample);
+        }
+        let frames = played.len() as u32;
+        assert!((RATE / 4..=RATE / 2).contains(&frames), "played {} frames", frames);
+        assert_eq!(played, (0..frames).map(expected).collect::<Vec<_>>());
+        for _ in 0..100 {
+            assert_eq!(source.next(), Some(0.0));
+        }
+        assert_eq!(stalls.lock().unwrap().last(), Some(&true));
+
+        // The rest arrives and playback continues where it stopped
+        writer.append(&file[first..]).unwrap();
+        assert_eq!(writer.finish(), Some(file));
+        let played = pull(&mut source, 100);
+        assert_eq!(played, (frames..frames + 100).map(expected).collect::<Vec<_>>());
+        assert_eq!(stalls.lock().unwrap().last(), Some(&false));
+
+        // Seek forward and back within the download
+        control.seek(Duration::from_millis(2500));
+        let played = pull(&mut source, 10);
+        assert_eq!(played, (20_000..20_010).map(expected).collect::<Vec<_>>());
+        control.seek(Duration::from_secs(1));
+        let played = pull(&mut source, 10);
+        assert_eq!(played, (8_000..8_010).map(expected).collect::<Vec<_>>());
+
+        // Plays to the end
+        let rest = pull(&mut source, usize::MAX);
+        assert_eq!(rest.len(), (RATE * 3 - 8_010) as usize);
+        assert_eq!(source.next(), None);
+    }
+}
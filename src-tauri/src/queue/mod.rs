@@ -6,6 +6,8 @@
 //! - Shuffle mode
 //! - Repeat modes (off, all, one)
 //! - Play history for going back
+//! - What happens once the last track has played (stop or radio)
+//! - Skipping unplayable tracks, with a consecutive-failure circuit breaker
 
 use std::collections::VecDeque;
 use std::sync::Mutex;
@@ -35,6 +37,58 @@ pub struct QueueTrack {
     /// Nostr pubkey for track attribution
     #[serde(default)]
     pub nostr_pubkey: Option<String>,
+    /// Where this track was queued from (album, playlist, search, ...)
+    #[serde(default)]
+    pub source: Option<QueueSource>,
+}
+
+/// Kind of entity a queued track was played from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueSourceKind {
+    Album,
+    Playlist,
+    Search,
+    Radio,
+    Artist,
+    Favorites,
+    Other,
+}
+
+/// Originating source of a queued track
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct QueueSource {
+    pub kind: QueueSourceKind,
+    /// Entity ID (album ID, playlist ID, search query, ...)
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Display name at the time the track was queued
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Whether the source of the now playing track still matches what was queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceStatus {
+    /// Source was not (or cannot be) verified, e.g. search results or radio
+    Unknown,
+    /// Source still exists and contains the track
+    Available,
+    /// Source still exists but no longer contains the track
+    TrackRemoved,
+    /// Source no longer exists
+    Removed,
+}
+
+/// Now playing position resolved against the queue and its source
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NowPlayingContext {
+    pub track: QueueTrack,
+    pub index: usize,
+    pub queue_length: usize,
+    pub source: Option<QueueSource>,
+    pub source_status: SourceStatus,
 }
 
 /// Repeat mode options
@@ -51,6 +105,67 @@ impl Default for RepeatMode {
     }
 }
 
+/// What to do when the last queued track has played
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueEndAction {
+    /// Stop playback and reset the position
+    #[default]
+    Stop,
+    /// Append tracks related to the last one and keep playing
+    Radio,
+}
+
+/// What to do when a queued track turns out to be unplayable
+/// (restricted in the user's region, removed from the catalog, ...)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnplayablePolicy {
+    /// Move on to the next track without telling the user
+    SkipSilently,
+    /// Move on to the next track and notify the user
+    #[default]
+    SkipWithNotice,
+    /// Stop and let the user decide
+    StopAndPrompt,
+}
+
+/// Unplayable tracks in a row after which skipping stops, so a dead queue
+/// isn't skipped through end to end
+pub const MAX_CONSECUTIVE_UNPLAYABLE: u32 = 5;
+
+/// How an unplayable track was handled
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UnplayableOutcome {
+    pub track_id: u64,
+    pub reason: Option<String>,
+    pub policy: UnplayablePolicy,
+    /// Unplayable tracks in a row, including this one
+    pub consecutive: u32,
+    /// Track to play instead; None when playback should stop
+    pub next: Option<QueueTrack>,
+    /// Skipping stopped after `MAX_CONSECUTIVE_UNPLAYABLE` tracks in a row
+    pub breaker_tripped: bool,
+}
+
+impl UnplayableOutcome {
+    /// Whether the user should hear about it
+    pub fn notify(&self) -> bool {
+        self.policy != UnplayablePolicy::SkipSilently || self.breaker_tripped
+    }
+}
+
+/// How a block of tracks (an album, a playlist) goes into the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum QueueInsertMode {
+    /// Replace the queue, starting at the first track
+    Replace,
+    /// Add after the last track
+    Append,
+    /// Add right after the current track
+    PlayNext,
+}
+
 /// Queue state snapshot for frontend
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct QueueState {
@@ -79,6 +194,12 @@ struct InternalState {
     repeat: RepeatMode,
     /// History of played track indices (for going back)
     history: VecDeque<usize>,
+    /// Behavior once the queue runs out
+    end_action: QueueEndAction,
+    /// Behavior when a track can't be played
+    unplayable_policy: UnplayablePolicy,
+    /// Unplayable tracks skipped since the last regular track change
+    unplayable_streak: u32,
 }
 
 /// Queue manager for handling playback queue
@@ -103,6 +224,9 @@ impl QueueManager {
                 shuffle_position: 0,
                 repeat: RepeatMode::Off,
                 history: VecDeque::with_capacity(50),
+                end_action: QueueEndAction::Stop,
+                unplayable_policy: UnplayablePolicy::default(),
+                unplayable_streak: 0,
             }),
         }
     }
@@ -118,8 +242,8 @@ impl QueueManager {
         }
     }
 
-    /// Add multiple tracks to the queue
-    pub fn add_tracks(&self, new_tracks: Vec<QueueTrack>) {
+    /// Add multiple tracks to the queue, returning the index of the first
+    pub fn add_tracks(&self, new_tracks: Vec<QueueTrack>) -> usize {
         let mut state = self.state.lock().unwrap();
         let start_idx = state.tracks.len();
         state.tracks.extend(new_tracks);
@@ -129,38 +253,56 @@ impl QueueManager {
                 state.shuffle_order.push(i);
             }
         }
+        start_idx
     }
 
     /// Add a track to play next (after current index if set)
     pub fn add_track_next(&self, track: QueueTrack) {
+        self.add_tracks_next(vec![track]);
+    }
+
+    /// Add tracks to play next as one block, keeping their order
+    pub fn add_tracks_next(&self, new_tracks: Vec<QueueTrack>) {
         let mut state = self.state.lock().unwrap();
-        let insert_index = state.current_index.map(|idx| idx + 1).unwrap_or(0);
+        let count = new_tracks.len();
+        let insert_index = state
+            .current_index
+            .map(|idx| idx + 1)
+            .unwrap_or(0)
+            .min(state.tracks.len());
 
-        if insert_index >= state.tracks.len() {
-            state.tracks.push(track);
-        } else {
-            state.tracks.insert(insert_index, track);
-        }
+        state.tracks.splice(insert_index..insert_index, new_tracks);
 
         if state.shuffle {
             for idx in state.shuffle_order.iter_mut() {
                 if *idx >= insert_index {
-                    *idx += 1;
+                    *idx += count;
                 }
             }
 
-            let new_idx = insert_index;
             let next_pos = if state.current_index.is_some() {
-                state.shuffle_position + 1
+                (state.shuffle_position + 1).min(state.shuffle_order.len())
             } else {
                 state.shuffle_order.len()
             };
 
-            if next_pos >= state.shuffle_order.len() {
-                state.shuffle_order.push(new_idx);
-            } else {
-                state.shuffle_order.insert(next_pos, new_idx);
+            state
+                .shuffle_order
+                .splice(next_pos..next_pos, insert_index..insert_index + count);
+        }
+    }
+
+    /// Insert tracks as one block according to `mode`
+    pub fn insert_tracks(&self, new_tracks: Vec<QueueTrack>, mode: QueueInsertMode) {
+        match mode {
+            QueueInsertMode::Replace => {
+                let start_index = (!new_tracks.is_empty()).then_some(0);
+                self.set_queue(new_tracks, start_index);
             }
+            QueueInsertMode::Append => {
+                self.add_tracks(new_tracks);
+            }
+            QueueInsertMode::PlayNext => self.add_tracks_next(new_tracks),
         }
     }
 
@@ -170,6 +312,7 @@ impl QueueManager {
         state.tracks = new_tracks;
         state.current_index = start_index;
         state.history.clear();
+        state.unplayable_streak = 0;
 
         // Regenerate shuffle order
         Self::regenerate_shuffle_order_internal(&mut state);
@@ -237,12 +380,106 @@ impl QueueManager {
         true
     }
 
+    /// Move a selection of tracks (contiguous or not) as one block.
+    ///
+    /// The selected tracks keep their relative order and the first of them
+    /// ends up at `to_index` in the resulting queue (clamped to the end).
+    pub fn move_many(&self, indices: &[usize], to_index: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let len = state.tracks.len();
+
+        let mut selected: Vec<usize> = indices.to_vec();
+        selected.sort_unstable();
+        selected.dedup();
+        if selected.is_empty() || selected.iter().any(|&idx| idx >= len) {
+            return false;
+        }
+
+        let rest: Vec<usize> = (0..len).filter(|idx| selected.binary_search(idx).is_err()).collect();
+        let insert_at = to_index.min(rest.len());
+
+        // New position -> old index
+        let mut order = Vec::with_capacity(len);
+        order.extend_from_slice(&rest[..insert_at]);
+        order.extend_from_slice(&selected);
+        order.extend_from_slice(&rest[insert_at..]);
+
+        if order.iter().enumerate().all(|(new_idx, &old_idx)| new_idx == old_idx) {
+            return false;
+        }
+
+        // Old index -> new position
+        let mut new_position = vec![0; len];
+        for (new_idx, &old_idx) in order.iter().enumerate() {
+            new_position[old_idx] = new_idx;
+        }
+
+        let mut old_tracks: Vec<Option<QueueTrack>> = state.tracks.drain(..).map(Some).collect();
+        state.tracks = order.iter().filter_map(|&old_idx| old_tracks[old_idx].take()).collect();
+
+        state.current_index = state.current_index.map(|idx| new_position[idx]);
+        for idx in state.history.iter_mut() {
+            *idx = new_position[*idx];
+        }
+
+        Self::regenerate_shuffle_order_internal(&mut state);
+        true
+    }
+
     /// Get current track
     pub fn current_track(&self) -> Option<QueueTrack> {
         let state = self.state.lock().unwrap();
         state.current_index.and_then(|idx| state.tracks.get(idx).cloned())
     }
 
+    /// Get the now playing track with its queue position and source
+    pub fn now_playing_context(&self) -> Option<NowPlayingContext> {
+        let state = self.state.lock().unwrap();
+        let index = state.current_index?;
+        let track = state.tracks.get(index)?.clone();
+
+        Some(NowPlayingContext {
+            source: track.source.clone(),
+            track,
+            index,
+            queue_length: state.tracks.len(),
+            source_status: SourceStatus::Unknown,
+        })
+    }
+
+    /// IDs of every queued track, in queue order
+    pub fn track_ids(&self) -> Vec<u64> {
+        self.state.lock().unwrap().tracks.iter().map(|t| t.id).collect()
+    }
+
+    /// Every queued track, in queue order
+    pub fn tracks(&self) -> Vec<QueueTrack> {
+        self.state.lock().unwrap().tracks.clone()
+    }
+
+    /// Play order while shuffling, as indices into the queue
+    pub fn shuffle_order(&self) -> Vec<usize> {
+        self.state.lock().unwrap().shuffle_order.clone()
+    }
+
+    /// Replace the shuffle order, resuming it at the current track. Ignored
+    /// unless `order` lists every queue index once.
+    pub fn restore_shuffle_order(&self, order: Vec<usize>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..state.tracks.len()) {
+            return false;
+        }
+
+        state.shuffle_position = state
+            .current_index
+            .and_then(|current| order.iter().position(|&index| index == current))
+            .unwrap_or(0);
+        state.shuffle_order = order;
+        true
+    }
+
     /// Get next track without advancing
     pub fn peek_next(&self) -> Option<QueueTrack> {
         let state = self.state.lock().unwrap();
@@ -328,6 +565,13 @@ impl QueueManager {
     /// Advance to next track and return it
     pub fn next(&self) -> Option<QueueTrack> {
         let mut state = self.state.lock().unwrap();
+        state.unplayable_streak = 0;
+        Self::advance_internal(&mut state, true)
+    }
+
+    /// Move past the current track. `repeat_one` replays the current track
+    /// under `RepeatMode::One`; skipping an unplayable track never does.
+    fn advance_internal(state: &mut InternalState, repeat_one: bool) -> Option<QueueTrack> {
         if state.tracks.is_empty() {
             return None;
         }
@@ -340,7 +584,7 @@ impl QueueManager {
             }
         }
 
-        if state.repeat == RepeatMode::One {
+        if repeat_one && state.repeat == RepeatMode::One {
             return state.current_index.and_then(|idx| state.tracks.get(idx).cloned());
         }
 
@@ -370,9 +614,48 @@ impl QueueManager {
         next_idx.and_then(|idx| state.tracks.get(idx).cloned())
     }
 
+    /// Track `track_id` can't be played: skip it or stop, by the
+    /// unplayable policy and the consecutive-failure circuit breaker. None
+    /// when it's no longer the current track (a late report): the queue has
+    /// moved on already.
+    pub fn handle_unplayable(&self, track_id: u64, reason: Option<String>) -> Option<UnplayableOutcome> {
+        let mut state = self.state.lock().unwrap();
+        let current_id = state.current_index.and_then(|idx| state.tracks.get(idx)).map(|track| track.id);
+        if current_id != Some(track_id) {
+            return None;
+        }
+        state.unplayable_streak += 1;
+
+        let policy = state.unplayable_policy;
+        let consecutive = state.unplayable_streak;
+        let breaker_tripped = consecutive >= MAX_CONSECUTIVE_UNPLAYABLE;
+        let next = if breaker_tripped || policy == UnplayablePolicy::StopAndPrompt {
+            None
+        } else {
+            Self::advance_internal(&mut state, false)
+        };
+        if next.is_none() {
+            // Whatever the user plays next starts a fresh count
+            state.unplayable_streak = 0;
+        }
+
+        Some(UnplayableOutcome { track_id, reason, policy, consecutive, next, breaker_tripped })
+    }
+
+    /// Set what happens when a track can't be played
+    pub fn set_unplayable_policy(&self, policy: UnplayablePolicy) {
+        self.state.lock().unwrap().unplayable_policy = policy;
+    }
+
+    /// Get what happens when a track can't be played
+    pub fn unplayable_policy(&self) -> UnplayablePolicy {
+        self.state.lock().unwrap().unplayable_policy
+    }
+
     /// Go to previous track and return it
     pub fn previous(&self) -> Option<QueueTrack> {
         let mut state = self.state.lock().unwrap();
+        state.unplayable_streak = 0;
         if state.tracks.is_empty() {
             return None;
         }
@@ -422,6 +705,7 @@ impl QueueManager {
         if index >= state.tracks.len() {
             return None;
         }
+        state.unplayable_streak = 0;
 
         // Save current to history
         if let Some(curr_idx) = state.current_index {
@@ -470,6 +754,16 @@ impl QueueManager {
         self.state.lock().unwrap().repeat
     }
 
+    /// Set what happens once the last track has played
+    pub fn set_end_action(&self, action: QueueEndAction) {
+        self.state.lock().unwrap().end_action = action;
+    }
+
+    /// Get what happens once the last track has played
+    pub fn end_action(&self) -> QueueEndAction {
+        self.state.lock().unwrap().end_action
+    }
+
     /// Get queue state for frontend
     pub fn get_state(&self) -> QueueState {
         let state = self.state.lock().unwrap();
@@ -541,3 +835,148 @@ impl QueueManager {
         state.shuffle_position = 0;
     }
 }
+
+/// Plain Qobuz queue track for tests elsewhere in the crate
+#[cfg(test)]
+pub(crate) fn test_track(id: u64) -> QueueTrack {
+    QueueTrack {
+        id,
+        title: format!("Track {}", id),
+        artist: "Artist".to_string(),
+        album: "Album".to_string(),
+        duration_secs: 240,
+        artwork_url: None,
+        hires: false,
+        bit_depth: None,
+        sample_rate: None,
+        is_local: false,
+        audio_url: None,
+        nostr_event_id: None,
+        nostr_pubkey: None,
+        source: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn album_track(id: u64, album_id: &str) -> QueueTrack {
+        QueueTrack {
+            source: Some(QueueSource {
+                kind: QueueSourceKind::Album,
+                id: Some(album_id.to_string()),
+                name: Some("Album".to_string()),
+            }),
+            ..test_track(id)
+        }
+    }
+
+    #[test]
+    fn test_unplayable_tracks_follow_policy_until_breaker_trips() {
+        let queue = QueueManager::new();
+        let tracks: Vec<QueueTrack> = (1..=10).map(|id| album_track(id, "alb1")).collect();
+        queue.set_queue(tracks, Some(0));
+        assert_eq!(queue.unplayable_policy(), UnplayablePolicy::SkipWithNotice);
+
+        // Skips with notice, even under repeat-one
+        queue.set_repeat(RepeatMode::One);
+        let outcome = queue.handle_unplayable(1, Some("Restricted".to_string())).unwrap();
+        assert_eq!(outcome.next.as_ref().map(|t| t.id), Some(2));
+        assert!(outcome.notify());
+        queue.set_repeat(RepeatMode::Off);
+
+        queue.set_unplayable_policy(UnplayablePolicy::SkipSilently);
+        for (track_id, expected_next) in [(2, 3), (3, 4), (4, 5)] {
+            let outcome = queue.handle_unplayable(track_id, None).unwrap();
+            assert_eq!(outcome.next.as_ref().map(|t| t.id), Some(expected_next));
+            assert!(!outcome.notify());
+        }
+
+        // A late report for a track already skipped changes nothing
+        assert!(queue.handle_unplayable(3, None).is_none());
+        assert_eq!(queue.current_track().map(|t| t.id), Some(5));
+
+        // Fifth failure in a row trips the breaker and is always reported
+        let outcome = queue.handle_unplayable(5, None).unwrap();
+        assert_eq!(outcome.consecutive, MAX_CONSECUTIVE_UNPLAYABLE);
+        assert!(outcome.breaker_tripped);
+        assert!(outcome.next.is_none());
+        assert!(outcome.notify());
+        assert_eq!(queue.current_track().map(|t| t.id), Some(5));
+
+        // A regular track change resets the count
+        assert_eq!(queue.next().map(|t| t.id), Some(6));
+        let outcome = queue.handle_unplayable(6, None).unwrap();
+        assert_eq!(outcome.consecutive, 1);
+        assert_eq!(outcome.next.map(|t| t.id), Some(7));
+
+        queue.set_unplayable_policy(UnplayablePolicy::StopAndPrompt);
+        let outcome = queue.handle_unplayable(7, None).unwrap();
+        assert!(outcome.next.is_none());
+        assert!(!outcome.breaker_tripped);
+        assert!(outcome.notify());
+        assert_eq!(queue.current_track().map(|t| t.id), Some(7));
+    }
+
+    #[test]
+    fn test_now_playing_context_points_to_current_album_track() {
+        let queue = QueueManager::new();
+        assert!(queue.now_playing_context().is_none());
+
+        let tracks: Vec<QueueTrack> = (1..=5).map(|id| album_track(id, "alb1")).collect();
+        queue.set_queue(tracks, Some(0));
+        queue.next();
+        queue.next();
+
+        let context = queue.now_playing_context().unwrap();
+        assert_eq!(context.index, 2);
+        assert_eq!(context.track.id, 3);
+        assert_eq!(context.queue_length, 5);
+        let source = context.source.unwrap();
+        assert_eq!(source.kind, QueueSourceKind::Album);
+        assert_eq!(source.id.as_deref(), Some("alb1"));
+        assert_eq!(context.source_status, SourceStatus::Unknown);
+    }
+
+    fn ids(queue: &QueueManager) -> Vec<u64> {
+        queue.state.lock().unwrap().tracks.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn test_move_many_forward_keeps_relative_order() {
+        let queue = QueueManager::new();
+        queue.set_queue((0..6).map(|id| album_track(id, "alb1")).collect(), Some(3));
+
+        // Move tracks 0 and 2 so the block starts at position 2
+        assert!(queue.move_many(&[2, 0], 2));
+        assert_eq!(ids(&queue), vec![1, 3, 0, 2, 4, 5]);
+        // Current track (id 3) shifted from index 3 to 1
+        assert_eq!(queue.now_playing_context().unwrap().index, 1);
+        assert_eq!(queue.current_track().unwrap().id, 3);
+    }
+
+    #[test]
+    fn test_move_many_backward_and_current_in_selection() {
+        let queue = QueueManager::new();
+        queue.set_queue((0..6).map(|id| album_track(id, "alb1")).collect(), Some(4));
+
+        assert!(queue.move_many(&[4, 5, 2], 0));
+        assert_eq!(ids(&queue), vec![2, 4, 5, 0, 1, 3]);
+        assert_eq!(queue.current_track().unwrap().id, 4);
+        assert_eq!(queue.get_state().current_index, Some(1));
+
+        // Past-the-end target clamps, out-of-range selection is rejected
+        assert!(queue.move_many(&[0], 99));
+        assert_eq!(ids(&queue), vec![4, 5, 0, 1, 3, 2]);
+        assert!(!queue.move_many(&[6], 0));
+    }
+
+    #[test]
+    fn test_queue_track_without_source_deserializes() {
+        let json = r#"{"id":1,"title":"t","artist":"a","album":"b","duration_secs":1,
+            "artwork_url":null,"bit_depth":null,"sample_rate":null}"#;
+        let track: QueueTrack = serde_json::from_str(json).unwrap();
+        assert!(track.source.is_none());
+    }
+}
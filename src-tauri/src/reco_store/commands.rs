@@ -1,12 +1,19 @@
 //! Tauri commands for recommendation store
 
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use tauri::State;
+use tokio::sync::Mutex;
 
-use crate::reco_store::db::{RecoEventRecord, RecoScoreEntry};
-use crate::reco_store::{HomeSeeds, RecoEventInput, RecoState, TopArtistSeed};
+use crate::api_cache::{ApiCache, ApiCacheState};
+use crate::reco_store::db::{RecoEventRecord, RecoScoreEntry, RecoStoreDb};
+use crate::reco_store::history::{self, CachedDetails, HistoryFormat, TimeRange};
+use crate::reco_store::{HomeSeeds, PlayedTrackInfo, RecoEventInput, RecoEventType, RecoState, TopArtistSeed};
+use crate::AppState;
 
 const DEFAULT_LOOKBACK_DAYS: i64 = 90;
 const DEFAULT_HALF_LIFE_DAYS: f64 = 21.0;
@@ -30,10 +37,14 @@ pub struct RecoTrainStats {
     pub favorite_scores: RecoScoreCounts,
 }
 
+/// Record an event. Plays logged without track details get them from the
+/// queue (with the quality the player streamed) or the API cache.
 #[tauri::command]
 pub async fn reco_log_event(
-    event: RecoEventInput,
+    mut event: RecoEventInput,
     state: State<'_, RecoState>,
+    app_state: State<'_, AppState>,
+    cache_state: State<'_, ApiCacheState>,
 ) -> Result<(), String> {
     log::info!(
         "Command: reco_log_event type={} item={}",
@@ -41,10 +52,82 @@ pub async fn reco_log_event(
         event.item_type.as_str()
     );
 
+    if let (RecoEventType::Play, None, Some(track_id)) = (event.event_type, &event.played, event.track_id) {
+        event.played = queued_details(&app_state, track_id);
+        if event.played.is_none() {
+            let cache = cache_state.cache.lock().await;
+            event.played = CachedDetails::new(&cache).lookup(track_id, event.album_id.as_deref());
+        }
+    }
+
     let db = state.db.lock().await;
     db.insert_event(&event)
 }
 
+/// Details of `track_id` as queued, with the quality actually streamed when
+/// it is the player's current track
+fn queued_details(app_state: &AppState, track_id: u64) -> Option<PlayedTrackInfo> {
+    let track = app_state.queue.tracks().into_iter().find(|track| track.id == track_id)?;
+    let player = &app_state.player.state;
+    let quality = (player.current_track_id() == track_id)
+        .then(|| player.stream_quality().1)
+        .flatten();
+    Some(PlayedTrackInfo::from_queue_track(&track, quality))
+}
+
+/// Write the play history to a new file at `path` (blocking)
+fn write_history_file(
+    path: &Path,
+    format: HistoryFormat,
+    range: TimeRange,
+    db: &Mutex<RecoStoreDb>,
+    cache: &Mutex<ApiCache>,
+) -> Result<usize, String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = BufWriter::new(file);
+
+    let db = db.blocking_lock();
+    let cache = cache.blocking_lock();
+    let count = history::write_history(&db, format, range, Some(CachedDetails::new(&cache)), &mut out)?;
+    out.flush().map_err(|e| format!("Failed to write play history: {}", e))?;
+    Ok(count)
+}
+
+/// Export the play history (track, artist, album, time, quality) as CSV
+/// or JSON to `path`, oldest first, optionally limited to a time range.
+/// Returns the number of plays written.
+#[tauri::command]
+pub async fn export_history(
+    path: String,
+    format: HistoryFormat,
+    range: Option<TimeRange>,
+    state: State<'_, RecoState>,
+    cache_state: State<'_, ApiCacheState>,
+) -> Result<usize, String> {
+    log::info!("Command: export_history {} {:?} {:?}", path, format, range);
+    let db = state.db.clone();
+    let cache = cache_state.cache.clone();
+    let count = tokio::task::spawn_blocking(move || {
+        // Written next to the destination and moved into place once complete
+        let path = Path::new(&path);
+        let temp_path = path.with_extension("tmp");
+        let written = write_history_file(&temp_path, format, range.unwrap_or_default(), &db, &cache)
+            .and_then(|count| {
+                std::fs::rename(&temp_path, path)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                Ok(count)
+            });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        written
+    })
+    .await
+    .map_err(|e| format!("History export task failed: {}", e))??;
+    log::info!("Exported {} plays", count);
+    Ok(count)
+}
+
 #[tauri::command]
 pub async fn reco_get_home(
     limit_recent_albums: Option<u32>,
@@ -4,7 +4,8 @@ use rusqlite::{params, Connection};
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::reco_store::{RecoEventInput, TopArtistSeed};
+use crate::reco_store::history::{PlayRecord, TimeRange};
+use crate::reco_store::{ListeningStats, PlayedTrackInfo, RecoEventInput, TopArtistSeed, TopTrackSeed};
 
 #[derive(Debug, Clone)]
 pub struct RecoEventRecord {
@@ -24,6 +25,22 @@ pub struct RecoScoreEntry {
     pub score: f64,
 }
 
+fn play_record(row: &rusqlite::Row) -> rusqlite::Result<PlayRecord> {
+    Ok(PlayRecord {
+        played_at: row.get(0)?,
+        track_id: row.get(1)?,
+        album_id: row.get(2)?,
+        artist_id: row.get(3)?,
+        duration_secs: row.get(4)?,
+        track: PlayedTrackInfo {
+            title: row.get(5)?,
+            artist: row.get(6)?,
+            album: row.get(7)?,
+            quality: row.get(8)?,
+        },
+    })
+}
+
 pub struct RecoStoreDb {
     conn: Connection,
 }
@@ -75,6 +92,17 @@ impl RecoStoreDb {
                 "#,
             )
             .map_err(|e| format!("Failed to initialize reco database: {}", e))?;
+
+        // Migration: listened seconds of play events
+        let _ = self
+            .conn
+            .execute("ALTER TABLE reco_events ADD COLUMN duration_secs INTEGER", []);
+        // Migration: track details for the history export
+        for column in ["title", "artist_name", "album_title", "quality"] {
+            let _ = self
+                .conn
+                .execute(&format!("ALTER TABLE reco_events ADD COLUMN {} TEXT", column), []);
+        }
         Ok(())
     }
 
@@ -83,7 +111,12 @@ impl RecoStoreDb {
             .duration_since(UNIX_EPOCH)
             .map(|d| d.as_secs() as i64)
             .unwrap_or(0);
+        self.insert_event_at(event, created_at)
+    }
 
+    /// Record an event with an explicit time (Unix seconds)
+    pub fn insert_event_at(&self, event: &RecoEventInput, created_at: i64) -> Result<(), String> {
+        let played = event.played.clone().unwrap_or_default();
         self.conn
             .execute(
                 r#"
@@ -94,8 +127,13 @@ impl RecoStoreDb {
                     album_id,
                     artist_id,
                     playlist_id,
+                    duration_secs,
+                    title,
+                    artist_name,
+                    album_title,
+                    quality,
                     created_at
-                ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#,
                 params![
                     event.event_type.as_str(),
@@ -104,6 +142,11 @@ impl RecoStoreDb {
                     event.album_id.as_deref(),
                     event.artist_id,
                     event.playlist_id,
+                    event.duration_secs,
+                    played.title,
+                    played.artist,
+                    played.album,
+                    played.quality,
                     created_at,
                 ],
             )
@@ -231,6 +274,38 @@ impl RecoStoreDb {
         Ok(events)
     }
 
+    /// Visit the play events in `range`, oldest first, one row at a time.
+    /// Returns the number of plays visited.
+    pub fn for_each_play(
+        &self,
+        range: TimeRange,
+        mut visit: impl FnMut(PlayRecord) -> Result<(), String>,
+    ) -> Result<usize, String> {
+        let mut stmt = self.conn
+            .prepare(
+                r#"
+                SELECT created_at, track_id, album_id, artist_id, duration_secs,
+                       title, artist_name, album_title, quality
+                FROM reco_events
+                WHERE event_type = 'play' AND created_at >= ? AND created_at < ?
+                ORDER BY created_at, id
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare play history query: {}", e))?;
+
+        let mut rows = stmt
+            .query(params![range.from.unwrap_or(i64::MIN), range.to.unwrap_or(i64::MAX)])
+            .map_err(|e| format!("Failed to query play history: {}", e))?;
+
+        let mut count = 0;
+        while let Some(row) = rows.next().map_err(|e| format!("Failed to read play history row: {}", e))? {
+            let play = play_record(row).map_err(|e| format!("Failed to read play history row: {}", e))?;
+            visit(play)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     pub fn get_top_artist_ids(&self, limit: u32) -> Result<Vec<TopArtistSeed>, String> {
         let mut stmt = self.conn
             .prepare(
@@ -261,6 +336,73 @@ impl RecoStoreDb {
         Ok(artists)
     }
 
+    /// Record the listened time of the latest play of `track_id` that has none
+    pub fn set_play_duration(&self, track_id: u64, duration_secs: u64) -> Result<(), String> {
+        self.conn
+            .execute(
+                r#"
+                UPDATE reco_events SET duration_secs = ?
+                WHERE id = (
+                    SELECT id FROM reco_events
+                    WHERE event_type = 'play' AND track_id = ? AND duration_secs IS NULL
+                    ORDER BY id DESC
+                    LIMIT 1
+                )
+                "#,
+                params![duration_secs, track_id],
+            )
+            .map_err(|e| format!("Failed to record play duration: {}", e))?;
+        Ok(())
+    }
+
+    pub fn get_top_track_ids(&self, limit: u32) -> Result<Vec<TopTrackSeed>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                r#"
+                SELECT track_id, COUNT(*) AS play_count, MAX(created_at) AS last_played
+                FROM reco_events
+                WHERE event_type = 'play' AND track_id IS NOT NULL
+                GROUP BY track_id
+                ORDER BY play_count DESC, last_played DESC
+                LIMIT ?
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare top tracks query: {}", e))?;
+
+        let rows = stmt
+            .query_map(params![limit], |row| {
+                Ok(TopTrackSeed {
+                    track_id: row.get::<_, u64>(0)?,
+                    play_count: row.get::<_, u32>(1)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query top tracks: {}", e))?;
+
+        let mut tracks = Vec::new();
+        for row in rows {
+            tracks.push(row.map_err(|e| format!("Failed to read top track row: {}", e))?);
+        }
+        Ok(tracks)
+    }
+
+    /// Play count, listening time and most played artists/tracks
+    pub fn get_listening_stats(&self, top_limit: u32) -> Result<ListeningStats, String> {
+        let (play_count, listening_secs): (i64, i64) = self.conn
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(duration_secs), 0) FROM reco_events WHERE event_type = 'play'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| format!("Failed to query listening totals: {}", e))?;
+
+        Ok(ListeningStats {
+            play_count: play_count as u64,
+            listening_secs: listening_secs as u64,
+            top_artists: self.get_top_artist_ids(top_limit)?,
+            top_tracks: self.get_top_track_ids(top_limit)?,
+        })
+    }
+
     pub fn get_favorite_album_ids(&self, limit: u32) -> Result<Vec<String>, String> {
         let mut stmt = self.conn
             .prepare(
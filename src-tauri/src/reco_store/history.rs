@@ -0,0 +1,282 @@
+//! Play history export
+//!
+//! Play events written out as CSV or JSON, oldest first, for analysing
+//! listening elsewhere. Rows go from the database cursor straight to the
+//! output, so a long history is never collected in memory first. Plays
+//! recorded without details are filled in from the API cache.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::db::RecoStoreDb;
+use super::PlayedTrackInfo;
+use crate::api::models::{Album, Track};
+use crate::api_cache::ApiCache;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+/// Unix-second bounds, `from` inclusive and `to` exclusive; unset = open
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+/// One play of the history
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayRecord {
+    /// Unix seconds
+    pub played_at: i64,
+    pub track_id: Option<u64>,
+    pub album_id: Option<String>,
+    pub artist_id: Option<u64>,
+    pub duration_secs: Option<u64>,
+    #[serde(flatten)]
+    pub track: PlayedTrackInfo,
+}
+
+impl PlayRecord {
+    /// `played_at` as RFC 3339 in UTC
+    pub fn played_at_utc(&self) -> String {
+        chrono::DateTime::from_timestamp(self.played_at, 0)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default()
+    }
+}
+
+/// A play as exported to JSON
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonPlay<'a> {
+    #[serde(flatten)]
+    play: &'a PlayRecord,
+    played_at_utc: String,
+}
+
+const CSV_HEADER: &str = "played_at,track_id,title,artist,album,quality,duration_secs,album_id,artist_id";
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn write_csv_row(out: &mut impl Write, play: &PlayRecord) -> std::io::Result<()> {
+    let number = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    let text = |value: &Option<String>| csv_field(value.as_deref().unwrap_or("")).into_owned();
+    writeln!(
+        out,
+        "{},{},{},{},{},{},{},{},{}",
+        play.played_at_utc(),
+        number(play.track_id),
+        text(&play.track.title),
+        text(&play.track.artist),
+        text(&play.track.album),
+        text(&play.track.quality),
+        number(play.duration_secs),
+        text(&play.album_id),
+        number(play.artist_id),
+    )
+}
+
+/// Details of played tracks from the API cache's tracks and albums
+pub struct CachedDetails<'a> {
+    cache: &'a ApiCache,
+    /// Albums already read, so an album's plays parse it once
+    albums: HashMap<String, Option<Album>>,
+}
+
+impl<'a> CachedDetails<'a> {
+    pub fn new(cache: &'a ApiCache) -> Self {
+        Self { cache, albums: HashMap::new() }
+    }
+
+    pub fn lookup(&mut self, track_id: u64, album_id: Option<&str>) -> Option<PlayedTrackInfo> {
+        let cached_track = self
+            .cache
+            .get_track(track_id, None)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str::<Track>(&json).ok());
+        let album = album_id.and_then(|id| self.album(id));
+        let track = cached_track.as_ref().or_else(|| {
+            album?.tracks.as_ref()?.items.iter().find(|track| track.id == track_id)
+        })?;
+        Some(PlayedTrackInfo::from_catalog(track, album))
+    }
+
+    fn album(&mut self, album_id: &str) -> Option<&Album> {
+        let cache = self.cache;
+        self.albums
+            .entry(album_id.to_string())
+            .or_insert_with(|| {
+                let json = cache.get_album(album_id, None).ok().flatten()?;
+                serde_json::from_str(&json).ok()
+            })
+            .as_ref()
+    }
+}
+
+/// Write the plays in `range` to `out`, oldest first, filling in plays
+/// recorded without details from `details`. Returns the number of plays
+/// written.
+pub fn write_history(
+    db: &RecoStoreDb,
+    format: HistoryFormat,
+    range: TimeRange,
+    mut details: Option<CachedDetails>,
+    out: &mut impl Write,
+) -> Result<usize, String> {
+    let io_error = |e: std::io::Error| format!("Failed to write play history: {}", e);
+    let mut fill = |play: &mut PlayRecord| {
+        if play.track != PlayedTrackInfo::default() {
+            return;
+        }
+        if let (Some(details), Some(track_id)) = (details.as_mut(), play.track_id) {
+            if let Some(found) = details.lookup(track_id, play.album_id.as_deref()) {
+                play.track = found;
+            }
+        }
+    };
+
+    match format {
+        HistoryFormat::Csv => {
+            writeln!(out, "{}", CSV_HEADER).map_err(io_error)?;
+            db.for_each_play(range, |mut play| {
+                fill(&mut play);
+                write_csv_row(&mut *out, &play).map_err(io_error)
+            })
+        }
+        HistoryFormat::Json => {
+            out.write_all(b"[").map_err(io_error)?;
+            let mut first = true;
+            let count = db.for_each_play(range, |mut play| {
+                fill(&mut play);
+                if !first {
+                    out.write_all(b",").map_err(io_error)?;
+                }
+                first = false;
+                let row = JsonPlay { played_at_utc: play.played_at_utc(), play: &play };
+                serde_json::to_writer(&mut *out, &row)
+                    .map_err(|e| format!("Failed to serialize play: {}", e))
+            })?;
+            out.write_all(b"]").map_err(io_error)?;
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reco_store::{RecoEventInput, RecoEventType, RecoItemType};
+    use std::path::Path;
+
+    /// 2024-03-01 00:00:00 UTC
+    const MARCH_1: i64 = 1_709_251_200;
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn play(track_id: u64, title: &str) -> RecoEventInput {
+        RecoEventInput {
+            event_type: RecoEventType::Play,
+            item_type: RecoItemType::Track,
+            track_id: Some(track_id),
+            album_id: Some("a1".to_string()),
+            artist_id: Some(10),
+            playlist_id: None,
+            duration_secs: Some(200),
+            played: Some(PlayedTrackInfo {
+                title: Some(title.to_string()),
+                artist: Some("Artist".to_string()),
+                album: Some("Album".to_string()),
+                quality: Some("FLAC 24-bit/96kHz".to_string()),
+            }),
+        }
+    }
+
+    fn export(db: &RecoStoreDb, format: HistoryFormat, range: TimeRange) -> String {
+        let mut out = Vec::new();
+        write_history(db, format, range, None, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_export_is_chronological_and_limited_to_range() {
+        let db = RecoStoreDb::new(Path::new(":memory:")).unwrap();
+        // Recorded out of order, spread over three days
+        db.insert_event_at(&play(3, "Third"), MARCH_1 + 2 * DAY).unwrap();
+        db.insert_event_at(&play(1, "First, \"live\""), MARCH_1).unwrap();
+        db.insert_event_at(&play(2, "Second"), MARCH_1 + DAY).unwrap();
+        db.insert_event_at(&RecoEventInput { event_type: RecoEventType::Favorite, ..play(9, "Fav") }, MARCH_1 + DAY)
+            .unwrap();
+
+        let csv = export(&db, HistoryFormat::Csv, TimeRange::default());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "2024-03-01T00:00:00+00:00,1,\"First, \"\"live\"\"\",Artist,Album,FLAC 24-bit/96kHz,200,a1,10"
+        );
+        assert!(lines[2].starts_with("2024-03-02T00:00:00+00:00,2,Second,"));
+        assert!(lines[3].starts_with("2024-03-03T00:00:00+00:00,3,Third,"));
+        assert_eq!(lines.len(), 4);
+
+        // `to` is exclusive: the third day is left out
+        let range = TimeRange { from: Some(MARCH_1 + DAY), to: Some(MARCH_1 + 2 * DAY) };
+        let csv = export(&db, HistoryFormat::Csv, range);
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.contains(",2,Second,"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&export(&db, HistoryFormat::Json, TimeRange { from: Some(MARCH_1 + DAY), to: None }))
+                .unwrap();
+        let titles: Vec<&str> = json.as_array().unwrap().iter().map(|p| p["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, vec!["Second", "Third"]);
+        assert_eq!(json[0]["playedAt"], MARCH_1 + DAY);
+        assert_eq!(json[0]["playedAtUtc"], "2024-03-02T00:00:00+00:00");
+        assert_eq!(json[0]["quality"], "FLAC 24-bit/96kHz");
+
+        let empty = TimeRange { from: Some(MARCH_1 + 10 * DAY), to: None };
+        assert_eq!(export(&db, HistoryFormat::Json, empty), "[]");
+    }
+
+    #[test]
+    fn test_plays_without_details_are_filled_from_the_cache() {
+        let db = RecoStoreDb::new(Path::new(":memory:")).unwrap();
+        db.insert_event_at(&RecoEventInput { played: None, ..play(1, "") }, MARCH_1).unwrap();
+        db.insert_event_at(&RecoEventInput { played: None, album_id: None, ..play(2, "") }, MARCH_1 + DAY)
+            .unwrap();
+
+        let cache = ApiCache::new(Path::new(":memory:")).unwrap();
+        let album = serde_json::json!({
+            "id": "a1",
+            "title": "Cached Album",
+            "artist": { "id": 10, "name": "Cached Artist" },
+            "tracks": { "items": [{ "id": 1, "title": "From Album" }], "total": 1 }
+        });
+        cache.set_album("a1", &album.to_string()).unwrap();
+        cache
+            .set_track(2, &serde_json::json!({ "id": 2, "title": "From Track", "performer": { "id": 11, "name": "Solo" } }).to_string())
+            .unwrap();
+
+        let mut out = Vec::new();
+        write_history(&db, HistoryFormat::Csv, TimeRange::default(), Some(CachedDetails::new(&cache)), &mut out)
+            .unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[1].contains(",1,From Album,Cached Artist,Cached Album,,200,"), "{}", lines[1]);
+        assert!(lines[2].contains(",2,From Track,Solo,,,200,"), "{}", lines[2]);
+    }
+}
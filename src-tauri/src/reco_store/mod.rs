@@ -4,6 +4,7 @@
 
 pub mod commands;
 pub mod db;
+pub mod history;
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -11,6 +12,9 @@ use tokio::sync::Mutex;
 
 use db::RecoStoreDb;
 
+use crate::api::models::{Album, Quality, Track};
+use crate::queue::QueueTrack;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum RecoEventType {
@@ -56,15 +60,83 @@ pub struct RecoEventInput {
     pub album_id: Option<String>,
     pub artist_id: Option<u64>,
     pub playlist_id: Option<u64>,
+    /// Seconds listened, for play events (counts toward listening time)
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// What was played, kept for the history export
+    #[serde(default)]
+    pub played: Option<PlayedTrackInfo>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Display details of a played track, as they were at play time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayedTrackInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Quality label of the stream, e.g. "FLAC 24-bit/96kHz"
+    pub quality: Option<String>,
+}
+
+impl PlayedTrackInfo {
+    /// Details of a queued track; `quality` is what was streamed, when known
+    pub fn from_queue_track(track: &QueueTrack, quality: Option<Quality>) -> Self {
+        let text = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        Self {
+            title: text(&track.title),
+            artist: text(&track.artist),
+            album: text(&track.album),
+            quality: quality.map(|q| q.label().to_string()),
+        }
+    }
+
+    /// Details of a catalog track, with `album` filling in what it lacks.
+    /// The streamed quality isn't known afterwards and is left out.
+    pub fn from_catalog(track: &Track, album: Option<&Album>) -> Self {
+        let text = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        Self {
+            title: text(&track.title),
+            artist: track
+                .performer
+                .as_ref()
+                .and_then(|artist| text(&artist.name))
+                .or_else(|| album.and_then(|album| text(&album.artist.name))),
+            album: track
+                .album
+                .as_ref()
+                .and_then(|summary| text(&summary.title))
+                .or_else(|| album.and_then(|album| text(&album.title))),
+            quality: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TopArtistSeed {
     pub artist_id: u64,
     pub play_count: u32,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopTrackSeed {
+    pub track_id: u64,
+    pub play_count: u32,
+}
+
+/// Play history totals
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListeningStats {
+    pub play_count: u64,
+    /// Sum of the listened seconds recorded with play events
+    pub listening_secs: u64,
+    pub top_artists: Vec<TopArtistSeed>,
+    pub top_tracks: Vec<TopTrackSeed>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HomeSeeds {
@@ -0,0 +1,195 @@
+//! Recent search history
+//!
+//! Persists submitted search queries so the search box can offer them as
+//! suggestions. Repeating a query moves it to the top instead of duplicating
+//! it, and the history is capped at `MAX_HISTORY` entries.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum number of stored searches; oldest entries are evicted first
+pub const MAX_HISTORY: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentSearch {
+    pub query: String,
+    /// "all", "albums", "tracks", "artists", ...
+    pub search_type: String,
+    pub searched_at: i64,
+}
+
+pub struct SearchHistoryStore {
+    conn: Connection,
+    max_entries: usize,
+}
+
+impl SearchHistoryStore {
+    pub fn new() -> Result<Self, String> {
+        let data_dir = dirs::data_dir()
+            .ok_or("Could not determine data directory")?
+            .join("qbz");
+
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+        Self::open(&data_dir.join("search_history.db"), MAX_HISTORY)
+    }
+
+    pub fn open(path: &Path, max_entries: usize) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open search history database: {}", e))?;
+
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS recent_searches (
+                query_key TEXT NOT NULL,
+                search_type TEXT NOT NULL,
+                query TEXT NOT NULL,
+                searched_at INTEGER NOT NULL,
+                seq INTEGER NOT NULL,
+                PRIMARY KEY (query_key, search_type)
+            );
+            CREATE INDEX IF NOT EXISTS idx_recent_searches_seq ON recent_searches(seq);
+            "
+        ).map_err(|e| format!("Failed to create search history table: {}", e))?;
+
+        Ok(Self { conn, max_entries })
+    }
+
+    /// Record a search. An existing entry for the same query/type is moved to the top.
+    pub fn add(&self, query: &str, search_type: &str) -> Result<(), String> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // Monotonic ordering key; timestamps alone collide within the same second
+        let seq: i64 = self.conn.query_row(
+            "SELECT COALESCE(MAX(seq), 0) + 1 FROM recent_searches",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to read search history: {}", e))?;
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO recent_searches (query_key, search_type, query, searched_at, seq)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![query.to_lowercase(), search_type, query, now, seq],
+        ).map_err(|e| format!("Failed to save search: {}", e))?;
+
+        self.conn.execute(
+            "DELETE FROM recent_searches WHERE seq NOT IN (
+                SELECT seq FROM recent_searches ORDER BY seq DESC LIMIT ?1
+             )",
+            params![self.max_entries as i64],
+        ).map_err(|e| format!("Failed to trim search history: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Most recent searches first
+    pub fn get_recent(&self, limit: usize) -> Result<Vec<RecentSearch>, String> {
+        let mut stmt = self.conn.prepare(
+            "SELECT query, search_type, searched_at FROM recent_searches
+             ORDER BY seq DESC LIMIT ?1"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok(RecentSearch {
+                query: row.get(0)?,
+                search_type: row.get(1)?,
+                searched_at: row.get(2)?,
+            })
+        }).map_err(|e| format!("Failed to query search history: {}", e))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read search history: {}", e))
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.conn.execute("DELETE FROM recent_searches", [])
+            .map_err(|e| format!("Failed to clear search history: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Thread-safe wrapper for SearchHistoryStore
+pub struct SearchHistoryState {
+    pub store: Arc<Mutex<SearchHistoryStore>>,
+}
+
+impl SearchHistoryState {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {
+            store: Arc::new(Mutex::new(SearchHistoryStore::new()?)),
+        })
+    }
+}
+
+// Tauri commands
+#[tauri::command]
+pub fn add_recent_search(
+    state: tauri::State<'_, SearchHistoryState>,
+    query: String,
+    search_type: Option<String>,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.add(&query, search_type.as_deref().unwrap_or("all"))
+}
+
+#[tauri::command]
+pub fn get_recent_searches(
+    state: tauri::State<'_, SearchHistoryState>,
+    limit: Option<usize>,
+) -> Result<Vec<RecentSearch>, String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.get_recent(limit.unwrap_or(10))
+}
+
+#[tauri::command]
+pub fn clear_recent_searches(
+    state: tauri::State<'_, SearchHistoryState>,
+) -> Result<(), String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_entries: usize) -> SearchHistoryStore {
+        SearchHistoryStore::open(Path::new(":memory:"), max_entries).unwrap()
+    }
+
+    fn queries(store: &SearchHistoryStore) -> Vec<String> {
+        store.get_recent(100).unwrap().into_iter().map(|s| s.query).collect()
+    }
+
+    #[test]
+    fn test_repeated_query_moves_to_top() {
+        let store = store(10);
+        store.add("miles davis", "all").unwrap();
+        store.add("coltrane", "all").unwrap();
+        store.add("  Miles Davis ", "all").unwrap();
+
+        assert_eq!(queries(&store), vec!["Miles Davis", "coltrane"]);
+    }
+
+    #[test]
+    fn test_size_cap_evicts_oldest() {
+        let store = store(3);
+        for query in ["a", "b", "c", "d"] {
+            store.add(query, "all").unwrap();
+        }
+
+        assert_eq!(queries(&store), vec!["d", "c", "b"]);
+    }
+}
@@ -0,0 +1,346 @@
+//! Cross-device session handoff
+//!
+//! "What I'm listening to" as a small JSON document (queue, now playing,
+//! position, shuffle/repeat) that can be carried to another machine by file
+//! or clipboard. On import every track is looked up again, since the target
+//! device may be in another region or lack the source's local library.
+
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures_util::future::OptionFuture;
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::api::{ApiError, Track};
+use crate::queue::{QueueManager, QueueTrack, RepeatMode};
+use crate::AppState;
+
+/// Identifies a handoff document
+pub const HANDOFF_FORMAT: &str = "qbz-session";
+/// Current format version; older versions are accepted, newer ones refused
+pub const HANDOFF_VERSION: u32 = 1;
+/// Track lookups in flight at once on import
+const LOOKUP_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionHandoff {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub queue: Vec<QueueTrack>,
+    pub current_index: Option<usize>,
+    pub position_secs: u64,
+    pub shuffle: bool,
+    /// Play order while shuffling, as indices into `queue`
+    #[serde(default)]
+    pub shuffle_order: Vec<usize>,
+    pub repeat: RepeatMode,
+    pub was_playing: bool,
+}
+
+impl SessionHandoff {
+    /// Snapshot the queue with the player's position
+    pub fn capture(queue: &QueueManager, position_secs: u64, was_playing: bool) -> Self {
+        let exported_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let current_index = queue.now_playing_context().map(|context| context.index);
+        let shuffle = queue.is_shuffle();
+
+        Self {
+            format: HANDOFF_FORMAT.to_string(),
+            version: HANDOFF_VERSION,
+            exported_at,
+            queue: queue.tracks(),
+            current_index,
+            position_secs: if current_index.is_some() { position_secs } else { 0 },
+            shuffle,
+            shuffle_order: if shuffle { queue.shuffle_order() } else { Vec::new() },
+            repeat: queue.get_repeat(),
+            was_playing: was_playing && current_index.is_some(),
+        }
+    }
+
+    /// Parse and validate an exported document
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let handoff: Self =
+            serde_json::from_str(json).map_err(|e| format!("Not a valid session export: {}", e))?;
+        if handoff.format != HANDOFF_FORMAT {
+            return Err(format!("Not a session export (format \"{}\")", handoff.format));
+        }
+        if handoff.version == 0 || handoff.version > HANDOFF_VERSION {
+            return Err(format!(
+                "Session export version {} is not supported (expected at most {})",
+                handoff.version, HANDOFF_VERSION
+            ));
+        }
+        if handoff.queue.is_empty() {
+            return Err("Session export has no tracks".to_string());
+        }
+        if handoff.current_index.is_some_and(|index| index >= handoff.queue.len()) {
+            return Err("Session export points past the end of its queue".to_string());
+        }
+        Ok(handoff)
+    }
+}
+
+/// A track left out on import
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedTrack {
+    pub id: u64,
+    pub title: String,
+    pub reason: String,
+}
+
+/// What was restored from a handoff
+#[derive(Debug, Clone, Serialize)]
+pub struct HandoffImportReport {
+    pub restored: usize,
+    pub skipped: Vec<SkippedTrack>,
+    /// Kept without being checked: Qobuz couldn't be reached for them
+    pub unchecked: Vec<u64>,
+    /// Track to resume, and where in it
+    pub current_track: Option<QueueTrack>,
+    pub current_index: Option<usize>,
+    pub position_secs: u64,
+    pub was_playing: bool,
+}
+
+/// The queue rebuilt from a handoff
+#[derive(Debug, Clone)]
+pub struct RestoredQueue {
+    pub tracks: Vec<QueueTrack>,
+    pub current_index: Option<usize>,
+    pub shuffle: bool,
+    /// The exported shuffle order, as indices into `tracks`
+    pub shuffle_order: Vec<usize>,
+    pub repeat: RepeatMode,
+}
+
+/// Look every track up again on this device, a few at a time. Local library
+/// tracks don't travel; Nostr tracks carry their own URL and are kept as
+/// they are, and so are tracks whose lookup couldn't reach Qobuz.
+pub async fn resolve_handoff<F, Fut>(
+    handoff: SessionHandoff,
+    lookup: F,
+) -> (RestoredQueue, HandoffImportReport)
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<Track, ApiError>>,
+{
+    let lookup = &lookup;
+    let to_look_up: Vec<Option<u64>> = handoff
+        .queue
+        .iter()
+        .map(|track| (!track.is_local && track.audio_url.is_none()).then_some(track.id))
+        .collect();
+    let lookups: Vec<Option<Result<Track, ApiError>>> = stream::iter(to_look_up)
+        .map(|track_id| OptionFuture::from(track_id.map(lookup)))
+        .buffered(LOOKUP_CONCURRENCY)
+        .collect()
+        .await;
+
+    let today = chrono::Utc::now().date_naive();
+    let mut tracks = Vec::with_capacity(handoff.queue.len());
+    // New index of every exported track that was kept
+    let mut kept_at = Vec::with_capacity(handoff.queue.len());
+    let mut skipped = Vec::new();
+    let mut unchecked = Vec::new();
+    let mut current_index = None;
+    let mut current_kept = false;
+
+    for ((index, mut track), found) in handoff.queue.into_iter().enumerate().zip(lookups) {
+        let is_current = handoff.current_index == Some(index);
+        let skip_reason = if track.is_local {
+            Some("Local library track".to_string())
+        } else {
+            match found {
+                None => None,
+                Some(Ok(found)) if !found.availability(None, today).streamable_now => {
+                    Some("Not streamable on this device".to_string())
+                }
+                Some(Ok(found)) => {
+                    // Follow catalog replacements
+                    track.id = found.id;
+                    None
+                }
+                Some(Err(e)) if e.is_transport() => {
+                    log::warn!("Couldn't check track {} on import: {}", track.id, e);
+                    unchecked.push(track.id);
+                    None
+                }
+                Some(Err(e)) => Some(e.to_string()),
+            }
+        };
+
+        match skip_reason {
+            Some(reason) => {
+                kept_at.push(None);
+                skipped.push(SkippedTrack { id: track.id, title: track.title, reason });
+            }
+            None => {
+                // A skipped current track hands over to the next one kept
+                if current_index.is_none() && handoff.current_index.is_some_and(|current| index >= current) {
+                    current_index = Some(tracks.len());
+                    current_kept = is_current;
+                }
+                kept_at.push(Some(tracks.len()));
+                tracks.push(track);
+            }
+        }
+    }
+
+    let report = HandoffImportReport {
+        restored: tracks.len(),
+        skipped,
+        unchecked,
+        current_track: current_index.and_then(|index| tracks.get(index).cloned()),
+        current_index,
+        position_secs: if current_kept { handoff.position_secs } else { 0 },
+        was_playing: handoff.was_playing && current_index.is_some(),
+    };
+    let shuffle_order = handoff
+        .shuffle_order
+        .iter()
+        .filter_map(|&index| kept_at.get(index).copied().flatten())
+        .collect();
+    let restored = RestoredQueue {
+        tracks,
+        current_index,
+        shuffle: handoff.shuffle,
+        shuffle_order,
+        repeat: handoff.repeat,
+    };
+    (restored, report)
+}
+
+/// Replace the queue with an imported session, keeping its shuffle order
+/// when it has one
+pub fn apply_handoff(queue: &QueueManager, restored: RestoredQueue) {
+    queue.set_queue(restored.tracks, restored.current_index);
+    queue.set_repeat(restored.repeat);
+    queue.set_shuffle(restored.shuffle);
+    if restored.shuffle && !restored.shuffle_order.is_empty() {
+        queue.restore_shuffle_order(restored.shuffle_order);
+    }
+}
+
+/// Export the queue, now playing track and position as JSON
+#[tauri::command]
+pub fn export_session_state(state: State<'_, AppState>) -> Result<String, String> {
+    let player_state = &state.player.state;
+    let handoff = SessionHandoff::capture(
+        &state.queue,
+        player_state.current_position(),
+        player_state.is_playing(),
+    );
+    log::info!("Command: export_session_state ({} tracks)", handoff.queue.len());
+    serde_json::to_string_pretty(&handoff).map_err(|e| format!("Failed to serialize session: {}", e))
+}
+
+/// Replace the queue with an exported session. Playback isn't started: the
+/// report says which track to resume and at what position.
+#[tauri::command]
+pub async fn import_session_state(
+    json: String,
+    state: State<'_, AppState>,
+) -> Result<HandoffImportReport, String> {
+    let handoff = SessionHandoff::parse(&json)?;
+    log::info!("Command: import_session_state ({} tracks)", handoff.queue.len());
+
+    let client = state.client.lock().await.clone();
+    let client = &client;
+    let (restored, report) =
+        resolve_handoff(handoff, |track_id| async move { client.get_track(track_id).await }).await;
+    if restored.tracks.is_empty() {
+        return Err("None of the session's tracks are available on this device".to_string());
+    }
+
+    if !report.skipped.is_empty() {
+        log::info!("Session import skipped {} unavailable tracks", report.skipped.len());
+    }
+    if !report.unchecked.is_empty() {
+        log::info!("Session import kept {} tracks it couldn't check", report.unchecked.len());
+    }
+    apply_handoff(&state.queue, restored);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::test_track;
+
+    #[tokio::test]
+    async fn test_playing_session_round_trips_to_fresh_queue() {
+        let source = QueueManager::new();
+        let mut local = test_track(4);
+        local.is_local = true;
+        source.set_queue(vec![test_track(1), test_track(2), test_track(3), local, test_track(5)], Some(0));
+        source.play_index(2);
+        source.set_repeat(RepeatMode::All);
+
+        let json = serde_json::to_string(&SessionHandoff::capture(&source, 95, true)).unwrap();
+        let handoff = SessionHandoff::parse(&json).unwrap();
+        assert_eq!(handoff.version, HANDOFF_VERSION);
+
+        // Track 1 isn't available in the target's region
+        let target = QueueManager::new();
+        let (restored, report) = resolve_handoff(handoff, |id| async move {
+            Ok(serde_json::from_value::<Track>(serde_json::json!({ "id": id, "streamable": id != 1 }))?)
+        })
+        .await;
+        apply_handoff(&target, restored);
+
+        assert_eq!(target.track_ids(), vec![2, 3, 5]);
+        assert_eq!(target.current_track().map(|t| t.id), Some(3));
+        assert_eq!(target.get_repeat(), RepeatMode::All);
+        assert_eq!(report.current_index, Some(1));
+        assert_eq!(report.position_secs, 95);
+        assert!(report.was_playing);
+        let skipped: Vec<u64> = report.skipped.iter().map(|t| t.id).collect();
+        assert_eq!(skipped, vec![1, 4]);
+        assert!(report.unchecked.is_empty());
+
+        // Other documents and future versions are refused
+        assert!(SessionHandoff::parse("{}").is_err());
+        let newer = json.replace(&format!("\"version\":{}", HANDOFF_VERSION), "\"version\":99");
+        assert!(SessionHandoff::parse(&newer).unwrap_err().contains("not supported"));
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_shuffle_order_and_unreachable_tracks() {
+        let source = QueueManager::new();
+        source.set_queue((1..=6).map(test_track).collect(), Some(0));
+        source.set_shuffle(true);
+        source.restore_shuffle_order(vec![4, 2, 0, 5, 1, 3]);
+        source.play_index(5);
+        let handoff = SessionHandoff::capture(&source, 10, false);
+        assert_eq!(handoff.shuffle_order, vec![4, 2, 0, 5, 1, 3]);
+
+        // Track 3 is gone; track 6's lookup can't reach Qobuz
+        let (restored, report) = resolve_handoff(handoff, |id| async move {
+            match id {
+                3 => Err(ApiError::ApiResponse("Failed to get track 3: 404 Not Found".to_string())),
+                6 => Err(ApiError::RateLimited(30)),
+                _ => Ok(serde_json::from_value::<Track>(serde_json::json!({ "id": id, "streamable": true }))?),
+            }
+        })
+        .await;
+        let skipped: Vec<u64> = report.skipped.iter().map(|t| t.id).collect();
+        assert_eq!(skipped, vec![3]);
+        assert_eq!(report.unchecked, vec![6]);
+
+        let target = QueueManager::new();
+        apply_handoff(&target, restored);
+        assert_eq!(target.track_ids(), vec![1, 2, 4, 5, 6]);
+        assert!(target.is_shuffle());
+        // Tracks 5, 1, 6, 2, 4 in the exported order, resuming at track 6
+        assert_eq!(target.shuffle_order(), vec![3, 0, 4, 1, 2]);
+        assert_eq!(target.current_track().map(|t| t.id), Some(6));
+        assert_eq!(target.peek_next().map(|t| t.id), Some(2));
+    }
+}
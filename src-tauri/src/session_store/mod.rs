@@ -1,8 +1,17 @@
-use rusqlite::{Connection, params};
+pub mod handoff;
+
+use rusqlite::{Connection, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Only tracks at least this long (mixes, audiobooks, long movements) remember their position
+pub const RESUME_MIN_DURATION_SECS: u64 = 10 * 60;
+
+/// Positions closer than this to either end aren't worth resuming from
+const RESUME_EDGE_SECS: u64 = 30;
+
 /// Represents a track in the persisted queue
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersistedQueueTrack {
@@ -59,8 +68,11 @@ impl SessionStore {
         std::fs::create_dir_all(&data_dir)
             .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
-        let db_path = data_dir.join("session.db");
-        let conn = Connection::open(&db_path)
+        Self::open(&data_dir.join("session.db"))
+    }
+
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(db_path)
             .map_err(|e| format!("Failed to open session database: {}", e))?;
 
         // Enable WAL mode for better concurrent access
@@ -94,6 +106,13 @@ impl SessionStore {
                 sample_rate REAL
             );
 
+            CREATE TABLE IF NOT EXISTS track_positions (
+                track_id INTEGER PRIMARY KEY,
+                position_secs INTEGER NOT NULL,
+                duration_secs INTEGER NOT NULL,
+                saved_at INTEGER NOT NULL
+            );
+
             -- Insert default row if not exists
             INSERT OR IGNORE INTO player_state (id, current_position_secs, volume, shuffle_enabled, repeat_mode, was_playing, saved_at)
             VALUES (1, 0, 0.75, 0, 'off', 0, 0);
@@ -291,6 +310,54 @@ impl SessionStore {
         Ok(())
     }
 
+    /// Remember where a long track was left. Short tracks are ignored; a
+    /// position near the start or end (fully played) clears the entry.
+    pub fn save_track_position(&self, track_id: u64, position_secs: u64, duration_secs: u64) -> Result<(), String> {
+        if duration_secs < RESUME_MIN_DURATION_SECS {
+            return Ok(());
+        }
+
+        if position_secs < RESUME_EDGE_SECS || position_secs + RESUME_EDGE_SECS >= duration_secs {
+            return self.clear_track_position(track_id);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO track_positions (track_id, position_secs, duration_secs, saved_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![track_id as i64, position_secs as i64, duration_secs as i64, now],
+            )
+            .map_err(|e| format!("Failed to save track position: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Saved resume position for a track, if any
+    pub fn get_track_position(&self, track_id: u64) -> Result<Option<u64>, String> {
+        self.conn
+            .query_row(
+                "SELECT position_secs FROM track_positions WHERE track_id = ?1",
+                params![track_id as i64],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map(|p| p.map(|p| p as u64))
+            .map_err(|e| format!("Failed to load track position: {}", e))
+    }
+
+    pub fn clear_track_position(&self, track_id: u64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM track_positions WHERE track_id = ?1", params![track_id as i64])
+            .map_err(|e| format!("Failed to clear track position: {}", e))?;
+
+        Ok(())
+    }
+
     /// Clear the session (e.g., on logout)
     pub fn clear_session(&self) -> Result<(), String> {
         self.conn.execute("DELETE FROM queue_tracks", [])
@@ -388,3 +455,40 @@ pub fn clear_session(
     let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
     store.clear_session()
 }
+
+/// Saved resume position for a long track (None if it has none)
+#[tauri::command]
+pub fn get_saved_position(
+    state: tauri::State<'_, SessionStoreState>,
+    track_id: u64,
+) -> Result<Option<u64>, String> {
+    let store = state.store.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.get_track_position(track_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_position_saved_on_stop_and_cleared_when_played() {
+        let store = SessionStore::open(Path::new(":memory:")).unwrap();
+        let mix = 42;
+
+        // Stopped 12 minutes into an hour-long mix
+        store.save_track_position(mix, 720, 3600).unwrap();
+        assert_eq!(store.get_track_position(mix).unwrap(), Some(720));
+
+        // Replaying resumes there; stopping again later moves the mark
+        store.save_track_position(mix, 1500, 3600).unwrap();
+        assert_eq!(store.get_track_position(mix).unwrap(), Some(1500));
+
+        // Playing to the end clears it
+        store.save_track_position(mix, 3590, 3600).unwrap();
+        assert_eq!(store.get_track_position(mix).unwrap(), None);
+
+        // Short tracks are never remembered
+        store.save_track_position(7, 120, 240).unwrap();
+        assert_eq!(store.get_track_position(7).unwrap(), None);
+    }
+}
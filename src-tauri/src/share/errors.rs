@@ -25,6 +25,12 @@ pub enum ShareError {
     #[error("Invalid content type: {0}")]
     InvalidContentType(String),
 
+    #[error("Invalid identifier: {0}")]
+    InvalidIdentifier(String),
+
+    #[error("Invalid locale: {0}")]
+    InvalidLocale(String),
+
     #[error("Request timeout")]
     Timeout,
 }
@@ -0,0 +1,114 @@
+//! Canonical Qobuz share links (open.qobuz.com)
+
+use std::str::FromStr;
+
+use super::errors::ShareError;
+
+const OPEN_QOBUZ_BASE: &str = "https://open.qobuz.com";
+
+/// Catalog entities that have a public Qobuz page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QobuzEntity {
+    Album,
+    Track,
+    Artist,
+    Playlist,
+}
+
+impl QobuzEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QobuzEntity::Album => "album",
+            QobuzEntity::Track => "track",
+            QobuzEntity::Artist => "artist",
+            QobuzEntity::Playlist => "playlist",
+        }
+    }
+}
+
+impl FromStr for QobuzEntity {
+    type Err = ShareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "album" => Ok(QobuzEntity::Album),
+            "track" => Ok(QobuzEntity::Track),
+            "artist" => Ok(QobuzEntity::Artist),
+            "playlist" => Ok(QobuzEntity::Playlist),
+            _ => Err(ShareError::InvalidContentType(s.to_string())),
+        }
+    }
+}
+
+/// Build the canonical link for an entity, optionally with a locale
+/// segment such as `fr-fr`: `https://open.qobuz.com/[locale/]album/<id>`
+pub fn build_share_url(entity: QobuzEntity, id: &str, locale: Option<&str>) -> Result<String, ShareError> {
+    let id = id.trim();
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ShareError::InvalidIdentifier(id.to_string()));
+    }
+
+    match locale.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(locale) => {
+            let locale = normalize_locale(locale)?;
+            Ok(format!("{}/{}/{}/{}", OPEN_QOBUZ_BASE, locale, entity.as_str(), id))
+        }
+        None => Ok(format!("{}/{}/{}", OPEN_QOBUZ_BASE, entity.as_str(), id)),
+    }
+}
+
+/// Accepts `fr-fr`, `fr_FR`, `en-US`... and returns the lowercase dashed form
+fn normalize_locale(locale: &str) -> Result<String, ShareError> {
+    let normalized = locale.replace('_', "-").to_ascii_lowercase();
+    let valid = normalized.len() == 5
+        && normalized.as_bytes()[2] == b'-'
+        && normalized
+            .chars()
+            .enumerate()
+            .all(|(i, c)| i == 2 || c.is_ascii_lowercase());
+
+    if valid {
+        Ok(normalized)
+    } else {
+        Err(ShareError::InvalidLocale(locale.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_urls_for_each_entity() {
+        let cases = [
+            ("album", "0060254728881", "https://open.qobuz.com/album/0060254728881"),
+            ("track", "52341234", "https://open.qobuz.com/track/52341234"),
+            ("artist", "36819", "https://open.qobuz.com/artist/36819"),
+            ("playlist", "1234567", "https://open.qobuz.com/playlist/1234567"),
+        ];
+        for (entity, id, expected) in cases {
+            let entity: QobuzEntity = entity.parse().unwrap();
+            assert_eq!(build_share_url(entity, id, None).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_locale_segment() {
+        assert_eq!(
+            build_share_url(QobuzEntity::Album, "abc123", Some("fr_FR")).unwrap(),
+            "https://open.qobuz.com/fr-fr/album/abc123"
+        );
+        assert_eq!(
+            build_share_url(QobuzEntity::Track, "1", Some("")).unwrap(),
+            "https://open.qobuz.com/track/1"
+        );
+        assert!(build_share_url(QobuzEntity::Track, "1", Some("french")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_input() {
+        assert!(matches!("label".parse::<QobuzEntity>(), Err(ShareError::InvalidContentType(_))));
+        assert!(build_share_url(QobuzEntity::Album, "", None).is_err());
+        assert!(build_share_url(QobuzEntity::Album, "../x", None).is_err());
+    }
+}
@@ -4,9 +4,11 @@
 //! that work across multiple music streaming platforms using Odesli/song.link.
 
 pub mod errors;
+pub mod links;
 pub mod models;
 pub mod songlink;
 
 pub use errors::ShareError;
+pub use links::{build_share_url, QobuzEntity};
 pub use models::{ContentType, SongLinkResponse};
 pub use songlink::SongLinkClient;
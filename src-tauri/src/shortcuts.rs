@@ -0,0 +1,289 @@
+//! Global keyboard shortcuts
+//!
+//! Transport actions can be bound to system-wide key combinations, for
+//! desktops without MPRIS or media keys. A triggered shortcut goes out as a
+//! `media:control` event, like an MPRIS request. Bindings are saved in
+//! `config::shortcut_settings` and registered again at startup.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use crate::config::shortcut_settings::{saved_bindings, ShortcutSettingsState};
+use crate::media_controls::MediaControlPayload;
+use crate::AppState;
+
+/// Seek shortcuts move this far
+const SEEK_STEP_SECS: i64 = 10;
+/// Volume shortcuts change the volume by this much (0.0 - 1.0)
+const VOLUME_STEP: f64 = 0.05;
+
+/// Actions shortcuts can be bound to. The names are stable: they're what
+/// commands take and what the config stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    PlayPause,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    VolumeUp,
+    VolumeDown,
+}
+
+impl ShortcutAction {
+    pub const ALL: [ShortcutAction; 7] = [
+        ShortcutAction::PlayPause,
+        ShortcutAction::Next,
+        ShortcutAction::Previous,
+        ShortcutAction::SeekForward,
+        ShortcutAction::SeekBackward,
+        ShortcutAction::VolumeUp,
+        ShortcutAction::VolumeDown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShortcutAction::PlayPause => "play_pause",
+            ShortcutAction::Next => "next",
+            ShortcutAction::Previous => "previous",
+            ShortcutAction::SeekForward => "seek_forward",
+            ShortcutAction::SeekBackward => "seek_backward",
+            ShortcutAction::VolumeUp => "volume_up",
+            ShortcutAction::VolumeDown => "volume_down",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    /// The `media:control` request for the action at volume `volume`
+    fn payload(self, volume: f64) -> MediaControlPayload {
+        match self {
+            ShortcutAction::PlayPause => MediaControlPayload::action_only("toggle"),
+            ShortcutAction::Next => MediaControlPayload::action_only("next"),
+            ShortcutAction::Previous => MediaControlPayload::action_only("previous"),
+            ShortcutAction::SeekForward => MediaControlPayload::seek_by(SEEK_STEP_SECS),
+            ShortcutAction::SeekBackward => MediaControlPayload::seek_by(-SEEK_STEP_SECS),
+            ShortcutAction::VolumeUp => MediaControlPayload::set_volume((volume + VOLUME_STEP).min(1.0)),
+            ShortcutAction::VolumeDown => MediaControlPayload::set_volume((volume - VOLUME_STEP).max(0.0)),
+        }
+    }
+}
+
+/// An action and the accelerator bound to it, if any
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShortcutBinding {
+    pub action: ShortcutAction,
+    pub accelerator: Option<String>,
+}
+
+/// Parse an accelerator such as `Ctrl+Alt+P` or `MediaPlayPause`
+pub fn parse_accelerator(accelerator: &str) -> Result<Shortcut, String> {
+    let trimmed = accelerator.trim();
+    if trimmed.is_empty() {
+        return Err("No shortcut given".to_string());
+    }
+    trimmed
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", accelerator, e))
+}
+
+/// Accelerator bound to each action
+#[derive(Debug, Default)]
+pub struct ShortcutMap {
+    bindings: HashMap<ShortcutAction, (String, Shortcut)>,
+}
+
+impl ShortcutMap {
+    /// Bind `accelerator` to `action`, returning the shortcut it replaces.
+    /// A combination already bound to another action is refused.
+    pub fn bind(&mut self, action: ShortcutAction, accelerator: &str) -> Result<Option<Shortcut>, String> {
+        let shortcut = parse_accelerator(accelerator)?;
+        let taken_by = self
+            .bindings
+            .iter()
+            .find(|(bound, (_, existing))| **bound != action && *existing == shortcut)
+            .map(|(bound, _)| *bound);
+        if let Some(other) = taken_by {
+            return Err(format!("{} is already bound to {}", accelerator.trim(), other.name()));
+        }
+        let previous = self.bindings.insert(action, (accelerator.trim().to_string(), shortcut));
+        Ok(previous.map(|(_, shortcut)| shortcut))
+    }
+
+    pub fn unbind(&mut self, action: ShortcutAction) -> Option<Shortcut> {
+        self.bindings.remove(&action).map(|(_, shortcut)| shortcut)
+    }
+
+    /// Action a triggered shortcut stands for
+    pub fn action_for(&self, shortcut: &Shortcut) -> Option<ShortcutAction> {
+        self.bindings
+            .iter()
+            .find(|(_, (_, bound))| bound == shortcut)
+            .map(|(action, _)| *action)
+    }
+
+    /// Every action, bound or not
+    pub fn bindings(&self) -> Vec<ShortcutBinding> {
+        ShortcutAction::ALL
+            .into_iter()
+            .map(|action| ShortcutBinding {
+                action,
+                accelerator: self.bindings.get(&action).map(|(accelerator, _)| accelerator.clone()),
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct ShortcutsState {
+    pub map: Mutex<ShortcutMap>,
+}
+
+/// Global shortcut handler: run the bound action on key press
+pub fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let action = app
+        .state::<ShortcutsState>()
+        .map
+        .lock()
+        .ok()
+        .and_then(|map| map.action_for(shortcut));
+    if let Some(action) = action {
+        log::info!("Global shortcut: {}", action.name());
+        let volume = app.state::<AppState>().player.state.volume() as f64;
+        let _ = app.emit("media:control", &action.payload(volume));
+    }
+}
+
+/// Register the saved bindings; ones that no longer parse or that another
+/// application holds are skipped
+pub fn restore_shortcuts(app: &AppHandle) {
+    let settings = app.state::<ShortcutSettingsState>();
+    let state = app.state::<ShortcutsState>();
+    let Ok(mut map) = state.map.lock() else {
+        return;
+    };
+    for (name, accelerator) in saved_bindings(&settings) {
+        let Some(action) = ShortcutAction::from_name(&name) else {
+            log::warn!("Ignoring saved shortcut for unknown action {}", name);
+            continue;
+        };
+        let registered = map.bind(action, &accelerator).and_then(|_| {
+            let shortcut = parse_accelerator(&accelerator)?;
+            app.global_shortcut().register(shortcut).map_err(|e| e.to_string())
+        });
+        if let Err(e) = registered {
+            log::warn!("Failed to restore shortcut {} for {}: {}", accelerator, name, e);
+            map.unbind(action);
+        }
+    }
+}
+
+/// Every transport action and its accelerator
+#[tauri::command]
+pub fn get_global_shortcuts(state: State<'_, ShortcutsState>) -> Result<Vec<ShortcutBinding>, String> {
+    let map = state.map.lock().map_err(|e| format!("Lock error: {}", e))?;
+    Ok(map.bindings())
+}
+
+/// Bind `accelerator` to `action` system-wide, in place of the action's
+/// previous shortcut. Fails on an invalid accelerator or one that's taken.
+#[tauri::command]
+pub fn register_global_shortcut(
+    action: ShortcutAction,
+    accelerator: String,
+    app: AppHandle,
+    state: State<'_, ShortcutsState>,
+    settings: State<'_, ShortcutSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: register_global_shortcut {} = {}", action.name(), accelerator);
+    let mut map = state.map.lock().map_err(|e| format!("Lock error: {}", e))?;
+    let shortcut = parse_accelerator(&accelerator)?;
+    let previous_accelerator = map
+        .bindings()
+        .into_iter()
+        .find(|binding| binding.action == action)
+        .and_then(|binding| binding.accelerator);
+    let previous = map.bind(action, &accelerator)?;
+
+    if previous != Some(shortcut) {
+        // The previous shortcut stays registered until the new one is
+        let global = app.global_shortcut();
+        if let Err(e) = global.register(shortcut) {
+            map.unbind(action);
+            if let Some(previous_accelerator) = previous_accelerator {
+                let _ = map.bind(action, &previous_accelerator);
+            }
+            return Err(format!(
+                "Can't use {}, it may be taken by another application: {}",
+                accelerator.trim(),
+                e
+            ));
+        }
+        if let Some(previous) = previous {
+            let _ = global.unregister(previous);
+        }
+    }
+
+    let store = settings.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.set_binding(action.name(), accelerator.trim())
+}
+
+/// Remove the shortcut of `action`
+#[tauri::command]
+pub fn unregister_global_shortcut(
+    action: ShortcutAction,
+    app: AppHandle,
+    state: State<'_, ShortcutsState>,
+    settings: State<'_, ShortcutSettingsState>,
+) -> Result<(), String> {
+    log::info!("Command: unregister_global_shortcut {}", action.name());
+    let removed = state.map.lock().map_err(|e| format!("Lock error: {}", e))?.unbind(action);
+    if let Some(shortcut) = removed {
+        app.global_shortcut()
+            .unregister(shortcut)
+            .map_err(|e| format!("Failed to unregister shortcut: {}", e))?;
+    }
+    let store = settings.lock().map_err(|e| format!("Lock error: {}", e))?;
+    store.remove_binding(action.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bound_shortcut_maps_to_action_and_bad_ones_are_refused() {
+        let mut map = ShortcutMap::default();
+        assert_eq!(map.bind(ShortcutAction::Next, "Ctrl+Alt+Right").unwrap(), None);
+        map.bind(ShortcutAction::PlayPause, " Ctrl+Alt+P ").unwrap();
+
+        let pressed = parse_accelerator("ctrl+alt+p").unwrap();
+        assert_eq!(map.action_for(&pressed), Some(ShortcutAction::PlayPause));
+        assert_eq!(map.action_for(&parse_accelerator("Ctrl+Alt+Left").unwrap()), None);
+
+        // Invalid accelerators and combinations already in use are refused
+        assert!(map.bind(ShortcutAction::VolumeUp, "Ctrl+Banana").unwrap_err().contains("Invalid shortcut"));
+        assert!(map.bind(ShortcutAction::VolumeUp, "   ").is_err());
+        let err = map.bind(ShortcutAction::Previous, "Ctrl+Alt+P").unwrap_err();
+        assert!(err.contains("already bound to play_pause"), "{}", err);
+
+        // Rebinding an action replaces its shortcut
+        let replaced = map.bind(ShortcutAction::PlayPause, "Ctrl+Shift+Space").unwrap();
+        assert_eq!(replaced, Some(pressed));
+        assert_eq!(map.action_for(&pressed), None);
+        let bindings = map.bindings();
+        assert_eq!(bindings.len(), ShortcutAction::ALL.len());
+        assert_eq!(bindings[0].accelerator.as_deref(), Some("Ctrl+Shift+Space"));
+        assert_eq!(ShortcutAction::from_name("seek_backward"), Some(ShortcutAction::SeekBackward));
+    }
+}
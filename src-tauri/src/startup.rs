@@ -0,0 +1,241 @@
+//! Backend-driven startup
+//!
+//! Runs the startup steps enabled in [`StartupConfig`] (client init, session
+//! restore, connectivity check) once at launch and emits `app-ready` with the
+//! outcome. Every step is optional and a failing step doesn't stop the others.
+//! The outcome is also kept for `get_startup_state`, since the event may fire
+//! before the frontend listens for it.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::api_cache::ApiCacheState;
+use crate::commands::login_with_saved_credentials;
+use crate::config::cache_settings::CacheSettingsState;
+use crate::config::startup_settings::{saved_startup_config, StartupConfig, StartupSettingsState};
+use crate::AppState;
+
+/// Outcome of one startup step, e.g. `{"status": "done", "result": true}`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(tag = "status", content = "result", rename_all = "snake_case")]
+pub enum StepStatus {
+    /// Turned off in the startup config
+    #[default]
+    Disabled,
+    /// Enabled, but not run because the network is offline
+    Skipped,
+    Done(bool),
+}
+
+/// Payload of the `app-ready` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AppReady {
+    pub client_initialized: StepStatus,
+    pub logged_in: StepStatus,
+    pub online: StepStatus,
+    /// Messages from steps that failed
+    pub errors: Vec<String>,
+}
+
+/// The individual startup steps (mocked in tests)
+pub(crate) trait StartupSteps {
+    async fn init_client(&self) -> Result<(), String>;
+    /// Log in with saved credentials; Ok(false) when there's nothing to restore
+    async fn restore_session(&self) -> Result<bool, String>;
+    async fn check_online(&self) -> bool;
+}
+
+/// Run the enabled steps in order and report the result once via `on_ready`
+pub(crate) async fn run_startup<S: StartupSteps>(
+    config: StartupConfig,
+    steps: &S,
+    on_ready: impl FnOnce(&AppReady),
+) -> AppReady {
+    let mut ready = AppReady::default();
+
+    if config.auto_offline_check {
+        let online = steps.check_online().await;
+        log::info!("Startup: network {}", if online { "online" } else { "offline" });
+        ready.online = StepStatus::Done(online);
+    }
+
+    let offline = ready.online == StepStatus::Done(false);
+
+    if config.auto_init && offline {
+        ready.client_initialized = StepStatus::Skipped;
+    } else if config.auto_init {
+        match steps.init_client().await {
+            Ok(()) => ready.client_initialized = StepStatus::Done(true),
+            Err(e) => {
+                log::warn!("Startup: client init failed: {}", e);
+                ready.client_initialized = StepStatus::Done(false);
+                ready.errors.push(format!("Client init failed: {}", e));
+            }
+        }
+    }
+
+    if config.auto_restore_session && offline {
+        ready.logged_in = StepStatus::Skipped;
+    } else if config.auto_restore_session {
+        match steps.restore_session().await {
+            Ok(logged_in) => ready.logged_in = StepStatus::Done(logged_in),
+            Err(e) => {
+                log::warn!("Startup: session restore failed: {}", e);
+                ready.logged_in = StepStatus::Done(false);
+                ready.errors.push(format!("Session restore failed: {}", e));
+            }
+        }
+    }
+
+    on_ready(&ready);
+    ready
+}
+
+/// Startup steps backed by the managed app state
+struct AppStartup {
+    app: AppHandle,
+}
+
+impl StartupSteps for AppStartup {
+    async fn init_client(&self) -> Result<(), String> {
+        let state = self.app.state::<AppState>();
+        let client = state.client.lock().await;
+        client.init().await.map_err(|e| e.to_string())
+    }
+
+    async fn restore_session(&self) -> Result<bool, String> {
+        if !crate::credentials::has_saved_credentials() {
+            return Ok(false);
+        }
+
+        let response = login_with_saved_credentials(
+            &self.app.state::<AppState>(),
+            &self.app.state::<ApiCacheState>(),
+            &self.app.state::<CacheSettingsState>(),
+        )
+        .await;
+
+        match response.error {
+            Some(e) if !response.success => Err(e),
+            _ => Ok(response.success),
+        }
+    }
+
+    async fn check_online(&self) -> bool {
+        crate::offline::check_network_connectivity().await
+    }
+}
+
+/// Outcome of the startup task, None while it is still running
+#[derive(Default)]
+pub struct StartupState {
+    pub ready: Mutex<Option<AppReady>>,
+}
+
+/// Spawn the startup task; stores the outcome and emits `app-ready` when it finishes
+pub fn spawn(app: AppHandle) {
+    let config = saved_startup_config(&app.state::<StartupSettingsState>());
+    log::info!("Startup config: {:?}", config);
+
+    tauri::async_runtime::spawn(async move {
+        let steps = AppStartup { app: app.clone() };
+        run_startup(config, &steps, |ready| {
+            if let Ok(mut stored) = app.state::<StartupState>().ready.lock() {
+                *stored = Some(ready.clone());
+            }
+            let _ = app.emit("app-ready", ready);
+        })
+        .await;
+    });
+}
+
+/// The `app-ready` payload, or None if startup hasn't finished yet
+#[tauri::command]
+pub fn get_startup_state(state: State<'_, StartupState>) -> Result<Option<AppReady>, String> {
+    log::info!("Command: get_startup_state");
+    let ready = state.ready.lock().map_err(|e| format!("Lock error: {}", e))?;
+    Ok(ready.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Default)]
+    struct MockSteps {
+        init_calls: AtomicU32,
+        restore_calls: AtomicU32,
+        online_calls: AtomicU32,
+        online: bool,
+        fail_init: bool,
+    }
+
+    impl StartupSteps for MockSteps {
+        async fn init_client(&self) -> Result<(), String> {
+            self.init_calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail_init {
+                Err("bundle unavailable".to_string())
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn restore_session(&self) -> Result<bool, String> {
+            self.restore_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        }
+
+        async fn check_online(&self) -> bool {
+            self.online_calls.fetch_add(1, Ordering::SeqCst);
+            self.online
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enabled_steps_run_and_ready_fires_once() {
+        let steps = MockSteps { online: true, fail_init: true, ..Default::default() };
+        let config = StartupConfig { auto_init: true, auto_restore_session: true, auto_offline_check: false };
+
+        let mut fired = 0;
+        let ready = run_startup(config, &steps, |_| fired += 1).await;
+
+        assert_eq!(fired, 1);
+        assert_eq!(steps.init_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(steps.restore_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(steps.online_calls.load(Ordering::SeqCst), 0);
+        // A failed init doesn't stop the session restore
+        assert_eq!(ready.client_initialized, StepStatus::Done(false));
+        assert_eq!(ready.logged_in, StepStatus::Done(true));
+        assert_eq!(ready.online, StepStatus::Disabled);
+        assert_eq!(ready.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_offline_skips_network_steps() {
+        let steps = MockSteps::default();
+        let config = StartupConfig { auto_init: true, auto_restore_session: true, auto_offline_check: true };
+
+        let mut fired = 0;
+        let ready = run_startup(config, &steps, |_| fired += 1).await;
+
+        assert_eq!(fired, 1);
+        assert_eq!(ready.online, StepStatus::Done(false));
+        assert_eq!(ready.client_initialized, StepStatus::Skipped);
+        assert_eq!(ready.logged_in, StepStatus::Skipped);
+        assert_eq!(steps.init_calls.load(Ordering::SeqCst), 0);
+        assert_eq!(steps.restore_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_step_status_payload() {
+        let ready = AppReady { online: StepStatus::Done(true), logged_in: StepStatus::Skipped, ..Default::default() };
+        let json = serde_json::to_value(&ready).unwrap();
+
+        assert_eq!(json["online"], serde_json::json!({"status": "done", "result": true}));
+        assert_eq!(json["logged_in"], serde_json::json!({"status": "skipped"}));
+        assert_eq!(json["client_initialized"], serde_json::json!({"status": "disabled"}));
+    }
+}
@@ -0,0 +1,478 @@
+//! On-disk storage location
+//!
+//! The disk audio cache (`playback/`), offline downloads (`audio/`) and the
+//! artwork cache (`artwork/`) live under one storage root. Moving them to a
+//! new root copies and verifies every file, rewrites the paths stored in the
+//! download index and the library, and only then removes the originals.
+//! The running caches are then pointed at the new root.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::RwLock;
+
+use md5::{Digest, Md5};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::config::cache_settings::CacheSettingsStore;
+use crate::download_cache::path_validator::check_permissions;
+use crate::download_cache::DownloadCacheDb;
+use crate::library::LibraryDatabase;
+
+/// Disk playback cache (L2)
+pub const PLAYBACK_DIR: &str = "playback";
+/// Offline downloads and their index
+pub const DOWNLOADS_DIR: &str = "audio";
+/// Downloaded and extracted artwork
+pub const ARTWORK_DIR: &str = "artwork";
+
+/// Download index file inside `DOWNLOADS_DIR`
+const DOWNLOAD_INDEX: &str = "index.db";
+
+const STORAGE_DIRS: [&str; 3] = [PLAYBACK_DIR, DOWNLOADS_DIR, ARTWORK_DIR];
+
+static STORAGE_ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Platform cache directory used when no storage location is configured
+pub fn default_storage_root() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("qbz")
+}
+
+/// Current storage root, read from the cache settings on first use
+pub fn storage_root() -> PathBuf {
+    if let Some(root) = STORAGE_ROOT.read().unwrap().as_ref() {
+        return root.clone();
+    }
+    let root = CacheSettingsStore::new()
+        .and_then(|store| store.get_settings())
+        .ok()
+        .and_then(|settings| settings.storage_dir)
+        .map(PathBuf::from)
+        .unwrap_or_else(default_storage_root);
+    STORAGE_ROOT.write().unwrap().get_or_insert(root).clone()
+}
+
+/// Resolve storage paths under `root` from now on (after a migration)
+pub fn set_storage_root(root: &Path) {
+    *STORAGE_ROOT.write().unwrap() = Some(root.to_path_buf());
+}
+
+/// Progress update while copying files
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageMigrationProgress {
+    pub files_done: usize,
+    pub total_files: usize,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+}
+
+/// Result of a completed migration
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageMigrationReport {
+    pub from: String,
+    pub to: String,
+    pub files_moved: usize,
+    pub bytes_moved: u64,
+    pub download_paths_updated: usize,
+    pub library_paths_updated: usize,
+}
+
+/// Move everything under `from` to `to`.
+///
+/// Files are copied and checked against the originals first, without
+/// locking the databases. Stored paths are then rewritten and the download
+/// index is written to its new home; `persist` saves the new root. Any
+/// failure up to that point removes the copies and restores the stored
+/// paths, leaving `from` untouched. Blocking.
+pub fn migrate_storage<P, F>(
+    from: &Path,
+    to: &Path,
+    downloads: &Mutex<DownloadCacheDb>,
+    library: &Mutex<LibraryDatabase>,
+    persist: P,
+    mut on_progress: F,
+) -> Result<StorageMigrationReport, String>
+where
+    P: FnOnce(&Path) -> Result<(), String>,
+    F: FnMut(StorageMigrationProgress),
+{
+    prepare_destination(from, to)?;
+
+    let files = collect_files(from)?;
+    let mut progress = StorageMigrationProgress {
+        total_files: files.len(),
+        total_bytes: files.iter().map(|(_, size)| size).sum(),
+        ..Default::default()
+    };
+    log::info!(
+        "Migrating storage from {:?} to {:?}: {} files, {} bytes",
+        from,
+        to,
+        progress.total_files,
+        progress.total_bytes
+    );
+
+    let mut copies = Vec::with_capacity(files.len());
+    for (relative, size) in &files {
+        let target = to.join(relative);
+        if let Err(e) = copy_verified(&from.join(relative), &target) {
+            remove_files(&copies);
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        copies.push(target);
+        progress.files_done += 1;
+        progress.bytes_done += size;
+        on_progress(progress.clone());
+    }
+
+    let mut downloads = downloads.blocking_lock();
+    let mut library = library.blocking_lock();
+    let old_prefix = path_prefix(from);
+    let new_prefix = path_prefix(to);
+    let (download_paths_updated, library_paths_updated) =
+        match relocate_databases(&mut downloads, &mut library, &old_prefix, &new_prefix) {
+            Ok(counts) => counts,
+            Err(e) => {
+                remove_files(&copies);
+                return Err(e);
+            }
+        };
+
+    let new_index = to.join(DOWNLOADS_DIR).join(DOWNLOAD_INDEX);
+    let moved_index = copy_index(&downloads, &new_index).and_then(|db| {
+        persist(to)?;
+        Ok(db)
+    });
+    let moved_index = match moved_index {
+        Ok(db) => db,
+        Err(e) => {
+            if let Err(restore) = relocate_databases(&mut downloads, &mut library, &new_prefix, &old_prefix) {
+                log::error!("Failed to restore stored paths after migration error: {}", restore);
+            }
+            remove_files(&copies);
+            let _ = fs::remove_file(&new_index);
+            return Err(e);
+        }
+    };
+
+    // From here on the new location is authoritative
+    *downloads = moved_index;
+    for (relative, _) in &files {
+        if let Err(e) = fs::remove_file(from.join(relative)) {
+            log::warn!("Failed to remove migrated file {:?}: {}", relative, e);
+        }
+    }
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let _ = fs::remove_file(from.join(DOWNLOADS_DIR).join(format!("{}{}", DOWNLOAD_INDEX, suffix)));
+    }
+    for dir in STORAGE_DIRS {
+        prune_empty_dirs(&from.join(dir));
+    }
+
+    log::info!("Storage migrated to {:?}", to);
+
+    Ok(StorageMigrationReport {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+        files_moved: files.len(),
+        bytes_moved: progress.total_bytes,
+        download_paths_updated,
+        library_paths_updated,
+    })
+}
+
+/// Reject overlapping or non-empty destinations and create the target dirs
+fn prepare_destination(from: &Path, to: &Path) -> Result<(), String> {
+    if !to.is_absolute() {
+        return Err("Storage location must be an absolute path".to_string());
+    }
+
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {:?}: {}", to, e))?;
+    let to_canonical = to
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", to, e))?;
+    let from_canonical = from.canonicalize().unwrap_or_else(|_| from.to_path_buf());
+    if to_canonical.starts_with(&from_canonical) || from_canonical.starts_with(&to_canonical) {
+        return Err("New storage location must not overlap the current one".to_string());
+    }
+
+    if !check_permissions(&to.to_string_lossy())? {
+        return Err(format!("No write permission for {:?}", to));
+    }
+
+    for dir in STORAGE_DIRS {
+        let target = to.join(dir);
+        let occupied = fs::read_dir(&target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false);
+        if occupied {
+            return Err(format!("{:?} already contains files", target));
+        }
+        fs::create_dir_all(&target)
+            .map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+    }
+
+    Ok(())
+}
+
+/// Every file under the storage dirs as (path relative to `root`, size),
+/// excluding the download index which is copied through SQLite
+fn collect_files(root: &Path) -> Result<Vec<(PathBuf, u64)>, String> {
+    let mut files = Vec::new();
+    for dir in STORAGE_DIRS {
+        collect_dir(root, &root.join(dir), &mut files)?;
+    }
+
+    let index_dir = Path::new(DOWNLOADS_DIR);
+    files.retain(|(relative, _)| {
+        let is_index = relative.parent() == Some(index_dir)
+            && relative
+                .file_name()
+                .map(|name| name.to_string_lossy().starts_with(DOWNLOAD_INDEX))
+                .unwrap_or(false);
+        !is_index
+    });
+    Ok(files)
+}
+
+fn collect_dir(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, u64)>) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+        let path = entry.path();
+        let metadata = entry
+            .metadata()
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+        if metadata.is_dir() {
+            collect_dir(root, &path, files)?;
+        } else if metadata.is_file() {
+            let relative = path
+                .strip_prefix(root)
+                .map_err(|e| format!("Failed to get relative path: {}", e))?;
+            files.push((relative.to_path_buf(), metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Copy `source` to `target` and check the copy matches byte for byte
+pub(crate) fn copy_verified(source: &Path, target: &Path) -> Result<(), String> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    fs::copy(source, target).map_err(|e| format!("Failed to copy {:?}: {}", source, e))?;
+
+    if file_digest(source)? != file_digest(target)? {
+        return Err(format!("Copy of {:?} does not match the original", source));
+    }
+    Ok(())
+}
+
+fn file_digest(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut hasher = Md5::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Rewrite stored paths in both databases, undoing the first if the second fails
+fn relocate_databases(
+    downloads: &mut DownloadCacheDb,
+    library: &mut LibraryDatabase,
+    old_prefix: &str,
+    new_prefix: &str,
+) -> Result<(usize, usize), String> {
+    let missing_before = missing_downloads(downloads)?;
+
+    let download_paths = downloads.relocate_paths(old_prefix, new_prefix)?;
+    let restore_downloads = |downloads: &mut DownloadCacheDb| {
+        if let Err(e) = downloads.relocate_paths(new_prefix, old_prefix) {
+            log::error!("Failed to restore download paths: {}", e);
+        }
+    };
+
+    // Every download that was on disk before must still be found
+    match missing_downloads(downloads) {
+        Ok(missing) if missing == missing_before => {}
+        Ok(missing) => {
+            restore_downloads(downloads);
+            return Err(format!(
+                "{} downloads missing after the move",
+                missing.len().saturating_sub(missing_before.len())
+            ));
+        }
+        Err(e) => {
+            restore_downloads(downloads);
+            return Err(e);
+        }
+    }
+
+    match library.relocate_paths(old_prefix, new_prefix) {
+        Ok(library_paths) => Ok((download_paths, library_paths)),
+        Err(e) => {
+            restore_downloads(downloads);
+            Err(e.to_string())
+        }
+    }
+}
+
+fn missing_downloads(db: &DownloadCacheDb) -> Result<Vec<u64>, String> {
+    let entries = db.get_ready_files()?;
+    Ok(crate::download_cache::verify::check_files(&entries, false, |_| {}).missing)
+}
+
+/// Write the download index to `path` and open it, checking it lists the same files
+fn copy_index(db: &DownloadCacheDb, path: &Path) -> Result<DownloadCacheDb, String> {
+    db.backup_to(path)?;
+    let copy = DownloadCacheDb::new(path)?;
+    if copy.get_ready_files()? != db.get_ready_files()? {
+        return Err("Copied download index does not match the original".to_string());
+    }
+    Ok(copy)
+}
+
+fn remove_files(files: &[PathBuf]) {
+    for file in files {
+        let _ = fs::remove_file(file);
+    }
+}
+
+/// Remove empty subdirectories of `dir`, keeping `dir` itself
+fn prune_empty_dirs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            prune_empty_dirs(&path);
+            let _ = fs::remove_dir(&path);
+        }
+    }
+}
+
+/// Root as a string prefix that only matches paths inside it
+fn path_prefix(root: &Path) -> String {
+    let root = root.to_string_lossy();
+    format!("{}{}", root.trim_end_matches(MAIN_SEPARATOR), MAIN_SEPARATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download_cache::TrackDownloadInfo;
+
+    fn track(track_id: u64) -> TrackDownloadInfo {
+        TrackDownloadInfo {
+            track_id,
+            title: format!("Track {}", track_id),
+            artist: "Artist".to_string(),
+            album: Some("Album".to_string()),
+            album_id: Some("album1".to_string()),
+            duration_secs: 60,
+            quality: "FLAC".to_string(),
+            bit_depth: Some(16),
+            sample_rate: Some(44100.0),
+        }
+    }
+
+    fn write(path: &Path, data: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_moves_files_and_rewrites_paths() {
+        let base = std::env::temp_dir().join(format!("qbz-storage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let from = base.join("old");
+        let to = base.join("new");
+
+        let playback = from.join(PLAYBACK_DIR).join("7.audio");
+        let download = from.join(DOWNLOADS_DIR).join("Artist").join("Album").join("01.flac");
+        let cover = from.join(DOWNLOADS_DIR).join(ARTWORK_DIR).join("album1.jpg");
+        let library_art = from.join(ARTWORK_DIR).join("cover.jpg");
+        write(&playback, b"cached audio");
+        write(&download, b"fLaC downloaded audio");
+        write(&cover, b"cover");
+        write(&library_art, b"library cover");
+
+        let downloads = DownloadCacheDb::new(&from.join(DOWNLOADS_DIR).join(DOWNLOAD_INDEX)).unwrap();
+        downloads.insert_track(&track(1), &download.to_string_lossy()).unwrap();
+        downloads.mark_complete(1, 21).unwrap();
+        downloads.update_artwork_path(1, &cover.to_string_lossy()).unwrap();
+
+        let library = LibraryDatabase::open(&base.join("library.db")).unwrap();
+        library
+            .insert_qobuz_download_direct(1, "Track 1", "Artist", Some("Album"), 60, &download.to_string_lossy(), None, None)
+            .unwrap();
+
+        let downloads = Mutex::new(downloads);
+        let library = Mutex::new(library);
+        let mut persisted = None;
+        let mut progress = Vec::new();
+        let report = migrate_storage(
+            &from,
+            &to,
+            &downloads,
+            &library,
+            |root| {
+                persisted = Some(root.to_path_buf());
+                Ok(())
+            },
+            |p| progress.push(p.files_done),
+        )
+        .unwrap();
+
+        assert_eq!(report.files_moved, 4);
+        assert_eq!(progress, vec![1, 2, 3, 4]);
+        assert_eq!(persisted.as_deref(), Some(to.as_path()));
+
+        for (old, data) in [
+            (&playback, &b"cached audio"[..]),
+            (&download, &b"fLaC downloaded audio"[..]),
+            (&cover, &b"cover"[..]),
+            (&library_art, &b"library cover"[..]),
+        ] {
+            assert!(!old.exists(), "{:?} left behind", old);
+            assert_eq!(fs::read(to.join(old.strip_prefix(&from).unwrap())).unwrap(), data);
+        }
+        assert!(!from.join(DOWNLOADS_DIR).join(DOWNLOAD_INDEX).exists());
+        assert!(to.join(DOWNLOADS_DIR).join(DOWNLOAD_INDEX).exists());
+
+        let downloads = downloads.into_inner();
+        let library = library.into_inner();
+        let new_download = to.join(download.strip_prefix(&from).unwrap());
+        let new_cover = to.join(cover.strip_prefix(&from).unwrap());
+        let artwork: Option<String> = downloads
+            .conn()
+            .query_row("SELECT artwork_path FROM cached_tracks WHERE track_id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(downloads.get_file_path(1).unwrap(), Some(new_download.to_string_lossy().to_string()));
+        assert_eq!(artwork, Some(new_cover.to_string_lossy().to_string()));
+        assert!(missing_downloads(&downloads).unwrap().is_empty());
+
+        assert!(library.get_track_by_path(&new_download.to_string_lossy()).unwrap().is_some());
+        assert!(library.get_track_by_path(&download.to_string_lossy()).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,325 @@
+//! Stream reporting
+//!
+//! Qobuz is told when a track starts streaming and, when it stops, for how
+//! long it was listened to. Each genuine listen is reported once: it only
+//! counts after `min_listen_secs` of actual playback, and pausing, resuming
+//! or seeking within the track keep it the same listen. A new listen begins
+//! when the track changes, playback stops, or the track plays again after
+//! reaching its end (repeat one). Local library and Nostr tracks aren't
+//! Qobuz streams and are never reported.
+//!
+//! Every ended listen, reported or not, also records its listened time on
+//! the local play history.
+
+use std::sync::RwLock;
+
+use tauri::{AppHandle, Manager, State};
+
+pub use crate::config::playback_settings::ReportPolicy;
+use crate::config::playback_settings::PlaybackSettingsState;
+use crate::reco_store::RecoState;
+use crate::AppState;
+
+/// Longest gap between two samples counted as listening, so a stalled
+/// poller or a suspended machine doesn't inflate the listened time
+const MAX_SAMPLE_GAP_MS: u64 = 2_000;
+/// A track that finished playing back within this many seconds of its
+/// start is being played again
+const RESTART_WINDOW_SECS: u64 = 2;
+
+static POLICY: RwLock<ReportPolicy> = RwLock::new(ReportPolicy::new());
+
+/// Process-wide reporting policy
+pub fn policy() -> ReportPolicy {
+    POLICY.read().map(|policy| *policy).unwrap_or_default()
+}
+
+/// Apply the saved policy at startup
+pub fn load_policy(settings: &PlaybackSettingsState) {
+    match settings.lock().map_err(|e| e.to_string()).and_then(|store| store.get_report_policy()) {
+        Ok(saved) => {
+            if let Ok(mut policy) = POLICY.write() {
+                *policy = saved;
+            }
+        }
+        Err(e) => log::warn!("Using the default stream report policy: {}", e),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamReport {
+    Start { track_id: u64 },
+    End { track_id: u64, listened_secs: u64 },
+}
+
+/// A listen that ended, for the local play history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndedListen {
+    pub track_id: u64,
+    pub listened_secs: u64,
+}
+
+/// Player state at one poll
+#[derive(Debug, Clone, Copy)]
+pub struct PlaybackSample {
+    /// 0 when nothing is loaded
+    pub track_id: u64,
+    pub position_secs: u64,
+    pub duration_secs: u64,
+    pub is_playing: bool,
+    /// A local library or Nostr track, not streamed from Qobuz
+    pub external: bool,
+}
+
+#[derive(Debug)]
+struct Listen {
+    track_id: u64,
+    external: bool,
+    listened_ms: u64,
+    /// When the previous sample found it playing
+    playing_since_ms: Option<u64>,
+    reported: bool,
+    /// Playback stopped at the end of the track
+    finished: bool,
+}
+
+/// Turns player samples into start/end reports for the current listen
+#[derive(Debug, Default)]
+pub struct ListenTracker {
+    listen: Option<Listen>,
+    ended: Vec<EndedListen>,
+}
+
+impl ListenTracker {
+    /// Account for one sample taken at `now_ms` (a monotonic clock)
+    pub fn observe(&mut self, sample: PlaybackSample, now_ms: u64, policy: &ReportPolicy) -> Vec<StreamReport> {
+        let mut reports = Vec::new();
+        let continues = self.listen.as_ref().is_some_and(|listen| {
+            listen.track_id == sample.track_id
+                && !(listen.finished && sample.position_secs <= RESTART_WINDOW_SECS)
+        });
+        if !continues {
+            reports.extend(self.finish());
+            if sample.track_id != 0 {
+                self.listen = Some(Listen {
+                    track_id: sample.track_id,
+                    external: sample.external,
+                    listened_ms: 0,
+                    playing_since_ms: None,
+                    reported: false,
+                    finished: false,
+                });
+            }
+        }
+        let Some(listen) = self.listen.as_mut() else {
+            return reports;
+        };
+
+        if sample.is_playing {
+            if let Some(since) = listen.playing_since_ms {
+                listen.listened_ms += now_ms.saturating_sub(since).min(MAX_SAMPLE_GAP_MS);
+            }
+            listen.playing_since_ms = Some(now_ms);
+        } else {
+            listen.playing_since_ms = None;
+        }
+        // The player parks a track that played out at its full duration; a
+        // seek near the end keeps playing and doesn't count
+        if !sample.is_playing && sample.duration_secs > 0 && sample.position_secs >= sample.duration_secs {
+            listen.finished = true;
+        }
+
+        if policy.enabled && !listen.external && !listen.reported && listen.listened_ms >= policy.min_listen_secs * 1000 {
+            listen.reported = true;
+            reports.push(StreamReport::Start { track_id: listen.track_id });
+        }
+        reports
+    }
+
+    /// Close the current listen; its end report if its start was reported
+    pub fn finish(&mut self) -> Option<StreamReport> {
+        let listen = self.listen.take()?;
+        if listen.listened_ms >= 1000 {
+            self.ended.push(EndedListen {
+                track_id: listen.track_id,
+                listened_secs: listen.listened_ms / 1000,
+            });
+        }
+        listen.reported.then_some(StreamReport::End {
+            track_id: listen.track_id,
+            listened_secs: listen.listened_ms / 1000,
+        })
+    }
+
+    /// Listens closed since the last call
+    pub fn take_ended(&mut self) -> Vec<EndedListen> {
+        std::mem::take(&mut self.ended)
+    }
+}
+
+/// Whether the queue's current track `track_id` is a local library or Nostr
+/// track
+pub fn is_external(app_handle: &AppHandle, track_id: u64) -> bool {
+    app_handle
+        .state::<AppState>()
+        .queue
+        .current_track()
+        .is_some_and(|track| track.id == track_id && (track.is_local || track.audio_url.is_some()))
+}
+
+/// Send reports to Qobuz in the background
+pub fn dispatch(app_handle: &AppHandle, reports: Vec<StreamReport>) {
+    if reports.is_empty() {
+        return;
+    }
+
+    let client = app_handle.state::<AppState>().client.clone();
+    tauri::async_runtime::spawn(async move {
+        let client = client.lock().await.clone();
+        for report in reports {
+            let result = match report {
+                StreamReport::Start { track_id } => client.report_stream_start(track_id).await,
+                StreamReport::End { track_id, listened_secs } => {
+                    client.report_stream_end(track_id, listened_secs).await
+                }
+            };
+            if let Err(e) = result {
+                log::debug!("Failed to report {:?}: {}", report, e);
+            }
+        }
+    });
+}
+
+/// Record the listened time of ended listens on their play events in the
+/// background
+pub fn record_listens(app_handle: &AppHandle, ended: Vec<EndedListen>) {
+    if ended.is_empty() {
+        return;
+    }
+
+    let db = app_handle.state::<RecoState>().db.clone();
+    tauri::async_runtime::spawn(async move {
+        let db = db.lock().await;
+        for listen in ended {
+            if let Err(e) = db.set_play_duration(listen.track_id, listen.listened_secs) {
+                log::debug!("Failed to record {:?}: {}", listen, e);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_stream_report_policy() -> ReportPolicy {
+    policy()
+}
+
+#[tauri::command]
+pub fn set_stream_report_policy(
+    policy: ReportPolicy,
+    settings: State<'_, PlaybackSettingsState>,
+) -> Result<ReportPolicy, String> {
+    log::info!("Command: set_stream_report_policy {:?}", policy);
+    settings
+        .lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .set_report_policy(&policy)?;
+    *POLICY.write().map_err(|e| format!("Lock error: {}", e))? = policy;
+    Ok(policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(track_id: u64, position_secs: u64, is_playing: bool) -> PlaybackSample {
+        PlaybackSample { track_id, position_secs, duration_secs: 240, is_playing, external: false }
+    }
+
+    /// Feed one sample a second from `from`, for `secs` seconds
+    fn run(tracker: &mut ListenTracker, now: &mut u64, from: u64, secs: u64, is_playing: bool) -> Vec<StreamReport> {
+        let policy = ReportPolicy { enabled: true, min_listen_secs: 30 };
+        let mut reports = Vec::new();
+        for second in 0..=secs {
+            let position = if is_playing { from + second } else { from };
+            reports.extend(tracker.observe(sample(7, position, is_playing), *now, &policy));
+            *now += 1000;
+        }
+        reports
+    }
+
+    #[test]
+    fn test_pause_resume_and_seek_report_one_listen() {
+        let mut tracker = ListenTracker::default();
+        let mut now = 0;
+
+        let mut reports = run(&mut tracker, &mut now, 0, 20, true);
+        // Paused for a minute: not listening
+        reports.extend(run(&mut tracker, &mut now, 20, 60, false));
+        assert!(reports.is_empty());
+
+        // Resumed, then seeked forward and back
+        reports.extend(run(&mut tracker, &mut now, 20, 15, true));
+        reports.extend(run(&mut tracker, &mut now, 150, 10, true));
+        reports.extend(run(&mut tracker, &mut now, 5, 10, true));
+        assert_eq!(reports, vec![StreamReport::Start { track_id: 7 }]);
+
+        // Next track closes the listen
+        reports.extend(tracker.observe(sample(8, 0, true), now, &ReportPolicy::default()));
+        assert_eq!(
+            reports,
+            vec![StreamReport::Start { track_id: 7 }, StreamReport::End { track_id: 7, listened_secs: 57 }]
+        );
+
+        // Skipped before the threshold: nothing reported
+        run(&mut tracker, &mut now, 0, 5, true);
+        assert_eq!(tracker.finish(), None);
+        // Both still count as listening time
+        assert_eq!(
+            tracker.take_ended(),
+            vec![
+                EndedListen { track_id: 7, listened_secs: 57 },
+                EndedListen { track_id: 7, listened_secs: 5 },
+            ]
+        );
+        assert!(tracker.take_ended().is_empty());
+    }
+
+    #[test]
+    fn test_only_a_natural_end_starts_a_new_listen() {
+        let mut tracker = ListenTracker::default();
+        let mut now = 0;
+
+        // Seeked to the last seconds and back to the start: the same listen
+        let mut reports = run(&mut tracker, &mut now, 0, 35, true);
+        reports.extend(run(&mut tracker, &mut now, 238, 1, true));
+        reports.extend(run(&mut tracker, &mut now, 0, 5, true));
+        assert_eq!(reports, vec![StreamReport::Start { track_id: 7 }]);
+
+        // Played out, then played again (repeat one)
+        reports.extend(run(&mut tracker, &mut now, 240, 1, false));
+        reports.extend(run(&mut tracker, &mut now, 0, 1, true));
+        assert_eq!(
+            reports,
+            vec![StreamReport::Start { track_id: 7 }, StreamReport::End { track_id: 7, listened_secs: 43 }]
+        );
+    }
+
+    #[test]
+    fn test_external_tracks_are_never_reported() {
+        let policy = ReportPolicy::default();
+        let mut tracker = ListenTracker::default();
+        let mut reports = Vec::new();
+        let mut now = 0;
+        for position in 0..40 {
+            let local = PlaybackSample { external: true, ..sample(3, position, true) };
+            reports.extend(tracker.observe(local, now, &policy));
+            now += 1000;
+        }
+        for position in 0..40 {
+            reports.extend(tracker.observe(sample(4, position, true), now, &policy));
+            now += 1000;
+        }
+        // The local track's listen ends on the Qobuz track without a report
+        assert_eq!(reports, vec![StreamReport::Start { track_id: 4 }]);
+    }
+}